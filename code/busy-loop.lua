@@ -2,4 +2,4 @@ local i = 0
 while i <= 10000000 do
     i = i + 1
 end
-return i
+print(i)
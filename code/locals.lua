@@ -0,0 +1,17 @@
+local function twice(x)
+    return x + x
+end
+
+local function run()
+    local sum = 0
+    local i = 0
+    local f = twice
+    while i < 10000000 do
+        sum = sum + i
+        sum = sum + f(i)
+        i = i + 1
+    end
+    return sum
+end
+
+print(run())
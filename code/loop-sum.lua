@@ -0,0 +1,11 @@
+local function run()
+    local sum = 0
+    local i = 0
+    while i < 20000000 do
+        sum = sum + i * 2 - 1
+        i = i + 1
+    end
+    return sum
+end
+
+print(run())
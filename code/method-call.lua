@@ -0,0 +1,26 @@
+local Counter = {}
+Counter.__index = Counter
+
+function Counter.new()
+    return setmetatable({ count = 0 }, Counter)
+end
+
+function Counter:increment(by)
+    self.count = self.count + by
+end
+
+function Counter:count_()
+    return self.count
+end
+
+local function run()
+    local counter = Counter.new()
+    local i = 0
+    while i < 5000000 do
+        counter:increment(1)
+        i = i + 1
+    end
+    return counter:count_()
+end
+
+print(run())
@@ -0,0 +1,13 @@
+//! Compiles arbitrary input, checking that the compiler reports errors instead of panicking.
+//!
+//! Run with `cargo +nightly fuzz run compile` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mica::Engine;
+
+fuzz_target!(|source: &str| {
+    let mut engine = Engine::new();
+    let _ = engine.compile("fuzz.mi", source);
+});
@@ -1,63 +1,123 @@
-use std::path::PathBuf;
+mod profile;
+mod repl;
+
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
-use mica::{Engine, Error, LanguageError, LanguageErrorKind, Value};
-use rustyline::{
-    completion::Completer,
-    highlight::Highlighter,
-    hint::Hinter,
-    validate::{ValidationContext, ValidationResult, Validator},
-    Editor, Helper,
-};
+use mica::{Engine, GcMode, Lint, Severity, Value};
 
 #[derive(Parser)]
-#[clap(name = "mica")]
+#[clap(
+    name = "mica",
+    args_conflicts_with_subcommands = true,
+    trailing_var_arg = true
+)]
 struct Options {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Script to run. `mica <FILE>` is a shorthand for `mica run <FILE>`.
     file: Option<PathBuf>,
+    /// Arguments passed to the script, available through `Process.args`.
+    #[clap(allow_hyphen_values = true)]
+    args: Vec<String>,
 
     #[clap(flatten)]
     engine_options: EngineOptions,
 }
 
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Runs a script.
+    #[clap(trailing_var_arg = true)]
+    Run {
+        file: PathBuf,
+        /// Arguments passed to the script, available through `Process.args`.
+        #[clap(allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Starts an interactive read-eval-print loop. This is the default when no file is given.
+    Repl,
+    /// Runs scripts, then runs the tests they declared with `Test.case`. The exit code is 1 if any
+    /// of the tests fail.
+    Test { files: Vec<PathBuf> },
+    /// Compiles a script and prints its bytecode without running it.
+    Disasm { file: PathBuf },
+    /// Generates documentation from the `##` doc comments in scripts.
+    Doc {
+        files: Vec<PathBuf>,
+        /// Generate HTML instead of Markdown.
+        #[clap(long)]
+        html: bool,
+        /// Write the documentation for each script into a file in this directory, rather than
+        /// printing it.
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Formats scripts in place.
+    Fmt {
+        files: Vec<PathBuf>,
+        /// Don't write anything, only report which files are not formatted. The exit code is 1 if
+        /// any of them aren't.
+        #[clap(long)]
+        check: bool,
+    },
+}
+
 #[derive(clap::Args)]
 struct EngineOptions {
-    #[clap(long)]
+    /// Print the syntax tree of each compiled script.
+    #[clap(long, global = true)]
     dump_ast: bool,
-    #[clap(long)]
+    /// Print the bytecode of each compiled script.
+    #[clap(long, global = true)]
     dump_bytecode: bool,
+    /// After running a script, print how many times each function was called and how much time
+    /// was spent in it.
+    #[clap(long, global = true)]
+    profile: bool,
+    /// After running a script, print how many garbage collections happened and how long they
+    /// paused execution for.
+    #[clap(long, global = true)]
+    trace_gc: bool,
+    /// Collect garbage incrementally, marking or sweeping at most this many objects at a time,
+    /// instead of pausing until the whole heap is collected.
+    #[clap(long, value_name = "OBJECTS", global = true)]
+    gc_step: Option<usize>,
+    /// Allow scripts to add methods to built-in types such as `Number` and `String`.
+    #[clap(long, global = true)]
+    extend_builtins: bool,
+    /// Don't report warnings of the given lint, eg. `-A unused_variable`. `all` stands for every
+    /// lint.
+    #[clap(short = 'A', long = "allow", value_name = "LINT", global = true, value_parser = parse_lints)]
+    allow: Vec<Lints>,
+    /// Report warnings of the given lint, including ones that are allowed by default, such as
+    /// `redeclared_variable`.
+    #[clap(short = 'W', long = "warn", value_name = "LINT", global = true, value_parser = parse_lints)]
+    warn: Vec<Lints>,
+    /// Turn warnings of the given lint into errors, which stop the script from running.
+    #[clap(short = 'D', long = "deny", value_name = "LINT", global = true, value_parser = parse_lints)]
+    deny: Vec<Lints>,
 }
 
-struct MicaValidator;
-
-impl Helper for MicaValidator {}
-
-impl Hinter for MicaValidator {
-    type Hint = String;
-}
-
-impl Highlighter for MicaValidator {}
+/// The lints selected by a `-A`, `-W`, or `-D` flag.
+#[derive(Clone)]
+struct Lints(Vec<Lint>);
 
-impl Completer for MicaValidator {
-    type Candidate = String;
-}
-
-impl Validator for MicaValidator {
-    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
-        let mut engine = Engine::new();
-        if let Err(error) = engine.compile("(repl)", ctx.input()) {
-            use LanguageErrorKind as ErrorKind;
-            if let Error::Compile(LanguageError::Compile {
-                kind:
-                    ErrorKind::MissingEnd
-                    | ErrorKind::MissingClosingQuote
-                    | ErrorKind::MissingRightParen,
-                ..
-            }) = error
-            {
-                return Ok(ValidationResult::Incomplete);
-            }
+fn parse_lints(name: &str) -> Result<Lints, String> {
+    if name == "all" {
+        return Ok(Lints(Lint::BUILTIN.to_vec()));
+    }
+    let name = name.replace('-', "_");
+    match Lint::from_name(&name) {
+        Some(lint) => Ok(Lints(vec![lint])),
+        None => {
+            let names: Vec<_> = Lint::BUILTIN.iter().map(|lint| lint.name()).collect();
+            Err(format!(
+                "unknown lint; expected `all` or one of: {}",
+                names.join(", ")
+            ))
         }
-        Ok(ValidationResult::Valid(None))
     }
 }
 
@@ -66,76 +126,220 @@ fn interpret<'e>(
     filename: &str,
     input: String,
 ) -> Result<impl Iterator<Item = Result<Value, mica::Error>> + 'e, mica::Error> {
-    let mut fiber = match engine.start(filename, input) {
-        Ok(fiber) => fiber,
+    let mut fiber = match engine.compile(filename, input) {
+        Ok(script) => {
+            for warning in script.warnings() {
+                eprintln!("{warning}");
+            }
+            script.into_fiber()
+        }
         Err(error) => {
-            eprintln!("{error}");
-            return Ok(None.into_iter().flatten());
+            eprintln!("{error:#}");
+            return Err(error);
         }
     };
-    Ok(Some(std::iter::from_fn(move || match fiber.resume() {
+    Ok(std::iter::from_fn(move || match fiber.resume() {
+        Ok(Some(_)) if fiber.is_blocked() => {
+            eprintln!("{}", mica::Error::Deadlock);
+            Some(Err(mica::Error::Deadlock))
+        }
         Ok(Some(value)) => Some(Ok(value)),
         Ok(None) => None,
         Err(error) => {
-            eprintln!("{error}");
+            if exit_code(&error).is_none() {
+                eprintln!("{error:#}");
+            }
             Some(Err(error))
         }
     }))
-    .into_iter()
-    .flatten())
+}
+
+/// Returns the exit code the script asked for with `Process.exit`, if that's what the error is.
+fn exit_code(error: &mica::Error) -> Option<u8> {
+    match error {
+        mica::Error::Runtime(error) => error.exit_code(),
+        _ => None,
+    }
 }
 
 fn engine(options: &EngineOptions) -> Engine {
-    Engine::with_debug_options(
+    let mut engine = Engine::with_debug_options(
         mica::corelib::Lib,
         mica::DebugOptions {
             dump_ast: options.dump_ast,
             dump_bytecode: options.dump_bytecode,
         },
-    )
+    );
+    engine.set_builtin_extensions(options.extend_builtins);
+    if let Some(step_size) = options.gc_step {
+        engine.set_gc_mode(GcMode::Incremental { step_size });
+    }
+    // Flags are applied from the least to the most severe, so that eg. `-A all -W unused_variable`
+    // only reports unused variables.
+    for (lints, severity) in [
+        (&options.allow, Severity::Allow),
+        (&options.warn, Severity::Warn),
+        (&options.deny, Severity::Deny),
+    ] {
+        for &lint in lints.iter().flat_map(|Lints(lints)| lints) {
+            engine.set_lint_severity(lint, severity);
+        }
+    }
+    engine
 }
 
-fn repl(engine_options: &EngineOptions) -> Result<(), mica::Error> {
-    println!("Mica {} REPL", env!("CARGO_PKG_VERSION"));
-    println!("Press Ctrl-C to exit.");
-    println!();
-
-    let mut editor =
-        Editor::with_config(rustyline::Config::builder().auto_add_history(true).build());
-    editor.set_helper(Some(MicaValidator));
-
+fn run(
+    path: &Path,
+    args: &[String],
+    engine_options: &EngineOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file = std::fs::read_to_string(path)?;
     let mut engine = engine(engine_options);
-    while let Ok(line) = editor.readline("> ") {
-        let iterator = match interpret(&mut engine, "(repl)", line) {
-            Ok(iterator) => iterator,
-            Err(_) => break,
-        };
-        for result in iterator.flatten() {
-            println!("< {result:?}");
-            println!();
+    engine.set_process_args(args);
+    let profiler = engine_options
+        .profile
+        .then(|| profile::Profiler::attach(&mut engine));
+    let fiber = match interpret(&mut engine, &path.to_string_lossy(), file) {
+        Ok(iterator) => iterator,
+        Err(_) => std::process::exit(-1),
+    };
+    // The exit code is only ever set explicitly, with `Process.exit`. What the script evaluates
+    // to is usually the value of whatever its last statement happened to be, such as a `let`.
+    let mut code = 0;
+    for result in fiber {
+        if let Err(error) = result {
+            code = exit_code(&error).map_or(1, i32::from);
+            break;
         }
     }
-
+    if let Some(profiler) = &profiler {
+        profiler.report();
+    }
+    if engine_options.trace_gc {
+        profile::report_gc(&engine.gc_stats());
+    }
+    if code != 0 {
+        std::process::exit(code);
+    }
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let opts = Options::parse();
-    if let Some(path) = &opts.file {
+fn test(
+    paths: &[PathBuf],
+    engine_options: &EngineOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut failed = false;
+    for path in paths {
+        println!("running tests in {}", path.display());
         let file = std::fs::read_to_string(path)?;
-        let mut engine = engine(&opts.engine_options);
-        let fiber = match interpret(&mut engine, path.to_str().unwrap(), file) {
+        let mut engine = engine(engine_options);
+        let fiber = match interpret(&mut engine, &path.to_string_lossy(), file) {
             Ok(iterator) => iterator,
             Err(_) => std::process::exit(-1),
         };
-        for result in fiber {
-            if result.is_err() {
-                std::process::exit(1);
+        if fiber.into_iter().any(|result| result.is_err()) {
+            failed = true;
+            continue;
+        }
+        let report = engine.run_tests();
+        println!("{report}");
+        println!();
+        failed |= !report.is_success();
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn disasm(path: &Path, engine_options: &EngineOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let file = std::fs::read_to_string(path)?;
+    let mut engine = engine(engine_options);
+    match engine.compile(path.to_string_lossy(), file) {
+        Ok(script) => print!("{}", script.disassemble()),
+        Err(error) => {
+            eprintln!("{error:#}");
+            std::process::exit(-1);
+        }
+    }
+    Ok(())
+}
+
+fn doc(
+    paths: &[PathBuf],
+    html: bool,
+    output: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for path in paths {
+        let source = std::fs::read_to_string(path)?;
+        let items = match mica_doc::extract(&path.to_string_lossy(), &source) {
+            Ok(items) => items,
+            Err(error) => {
+                eprintln!("{error:#}");
+                std::process::exit(-1);
             }
+        };
+        let title = path.file_stem().unwrap_or_default().to_string_lossy();
+        let (docs, extension) = if html {
+            (mica_doc::to_html(&title, &items), "html")
+        } else {
+            (mica_doc::to_markdown(&title, &items), "md")
+        };
+        match output {
+            Some(directory) => {
+                std::fs::create_dir_all(directory)?;
+                std::fs::write(directory.join(format!("{title}.{extension}")), docs)?;
+            }
+            None => print!("{docs}"),
         }
-    } else {
-        repl(&opts.engine_options)?;
     }
+    Ok(())
+}
 
+fn fmt(paths: &[PathBuf], check: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let options = mica_fmt::Options::default();
+    let mut unformatted = false;
+    for path in paths {
+        let source = std::fs::read_to_string(path)?;
+        let formatted = match mica_fmt::format(&path.to_string_lossy(), &source, &options) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("{error:#}");
+                std::process::exit(-1);
+            }
+        };
+        if formatted != source {
+            if check {
+                println!("{} is not formatted", path.display());
+                unformatted = true;
+            } else {
+                std::fs::write(path, formatted)?;
+            }
+        }
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let opts = Options::parse();
+    match (&opts.command, &opts.file) {
+        (Some(Command::Run { file, args }), _) => run(file, args, &opts.engine_options)?,
+        (None, Some(file)) => run(file, &opts.args, &opts.engine_options)?,
+        (Some(Command::Test { files }), _) => test(files, &opts.engine_options)?,
+        (Some(Command::Disasm { file }), _) => disasm(file, &opts.engine_options)?,
+        (
+            Some(Command::Doc {
+                files,
+                html,
+                output,
+            }),
+            _,
+        ) => doc(files, *html, output.as_deref())?,
+        (Some(Command::Fmt { files, check }), _) => fmt(files, *check)?,
+        (Some(Command::Repl), _) | (None, None) => repl::repl(engine(&opts.engine_options)),
+    }
     Ok(())
 }
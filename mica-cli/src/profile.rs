@@ -0,0 +1,138 @@
+//! Execution reports printed after running a script with `--profile` or `--trace-gc`.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use mica::{DebuggerHooks, Engine, GcStats, PausedFiber};
+
+/// How much time was spent in a single function.
+#[derive(Default)]
+struct FunctionStats {
+    calls: usize,
+    /// Time spent in the function, including the functions it called.
+    total: Duration,
+    /// Time spent in the function itself.
+    own: Duration,
+}
+
+/// A call that hasn't returned yet.
+struct Frame {
+    name: Rc<str>,
+    start: Instant,
+    /// Time spent in functions called from this one.
+    callees: Duration,
+}
+
+#[derive(Default)]
+struct Profile {
+    functions: HashMap<Rc<str>, FunctionStats>,
+    stack: Vec<Frame>,
+}
+
+impl Profile {
+    fn enter(&mut self, name: &str, now: Instant) {
+        let name = self
+            .functions
+            .get_key_value(name)
+            .map(|(name, _)| Rc::clone(name))
+            .unwrap_or_else(|| Rc::from(name));
+        self.stack.push(Frame {
+            name,
+            start: now,
+            callees: Duration::ZERO,
+        });
+    }
+
+    fn leave(&mut self, now: Instant) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        let elapsed = now - frame.start;
+        // Recursive calls are already accounted for by the outermost call, so only that one
+        // counts towards the total.
+        let is_outermost = !self.stack.iter().any(|outer| outer.name == frame.name);
+        let stats = self.functions.entry(frame.name).or_default();
+        stats.calls += 1;
+        stats.own += elapsed.saturating_sub(frame.callees);
+        if is_outermost {
+            stats.total += elapsed;
+        }
+        if let Some(caller) = self.stack.last_mut() {
+            caller.callees += elapsed;
+        }
+    }
+}
+
+/// Collects per-function call counts and timings while the engine runs scripts.
+pub struct Profiler {
+    profile: Rc<RefCell<Profile>>,
+    start: Instant,
+}
+
+impl Profiler {
+    /// Installs the profiler into the engine.
+    pub fn attach(engine: &mut Engine) -> Self {
+        let profile = Rc::new(RefCell::new(Profile::default()));
+        engine.set_debugger_hooks(Hooks(Rc::clone(&profile)));
+        Self {
+            profile,
+            start: Instant::now(),
+        }
+    }
+
+    /// Prints a table of functions, sorted by the time spent in them, to stderr.
+    pub fn report(&self) {
+        let now = Instant::now();
+        let mut profile = self.profile.borrow_mut();
+        // Functions that were interrupted by an error never returned, so finish them off here.
+        while !profile.stack.is_empty() {
+            profile.leave(now);
+        }
+
+        let mut functions: Vec<_> = profile.functions.iter().collect();
+        functions.sort_by(|(a_name, a), (b_name, b)| b.own.cmp(&a.own).then(a_name.cmp(b_name)));
+        eprintln!();
+        eprintln!("profile (total run time: {:.3?})", now - self.start);
+        eprintln!("{:>10}  {:>12}  {:>12}  function", "calls", "total", "self");
+        for (name, stats) in functions {
+            eprintln!(
+                "{:>10}  {:>12}  {:>12}  {name}",
+                stats.calls,
+                format!("{:.3?}", stats.total),
+                format!("{:.3?}", stats.own),
+            );
+        }
+    }
+}
+
+struct Hooks(Rc<RefCell<Profile>>);
+
+impl DebuggerHooks for Hooks {
+    fn on_line(&mut self, _fiber: &PausedFiber<'_>) {}
+
+    fn on_call(&mut self, _fiber: &PausedFiber<'_>, function_name: &str) {
+        self.0.borrow_mut().enter(function_name, Instant::now());
+    }
+
+    fn on_return(&mut self, _fiber: &PausedFiber<'_>) {
+        self.0.borrow_mut().leave(Instant::now());
+    }
+}
+
+/// Prints a summary of the garbage collections the engine performed to stderr.
+pub fn report_gc(stats: &GcStats) {
+    eprintln!();
+    eprintln!(
+        "gc: {} collection(s) in {} pause(s), {:.3?} paused in total (longest {:.3?}, average {:.3?}), {} bytes freed",
+        stats.collections,
+        stats.pauses,
+        stats.total_pause,
+        stats.max_pause,
+        stats.average_pause(),
+        stats.freed_bytes,
+    );
+}
@@ -0,0 +1,213 @@
+//! The interactive read-eval-print loop.
+
+use std::{borrow::Cow, path::PathBuf};
+
+use mica::{
+    tooling::{self, TokenKind},
+    Engine, Session, Value,
+};
+use rustyline::{
+    completion::Completer,
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Context, Editor, Helper,
+};
+
+const HELP: &str = "\
+Commands:
+  :help         Show this message.
+  :type <expr>  Evaluate an expression and print the name of its type.
+  :dis <expr>   Print the bytecode an expression compiles to, without running it.
+
+Input that isn't finished yet, such as a `do` block without its `end`, continues on the next line.
+Press Tab to complete the names of globals, or methods after a dot.";
+
+const COMMANDS: &[&str] = &[":help", ":type", ":dis"];
+
+/// Completes names and tells the editor when input needs more lines.
+#[derive(Default)]
+struct ReplHelper {
+    globals: Vec<String>,
+    methods: Vec<String>,
+}
+
+impl ReplHelper {
+    /// Updates the names offered for completion after the engine's globals may have changed.
+    fn refresh(&mut self, engine: &Engine) {
+        let introspection = engine.introspect();
+        self.globals = introspection
+            .globals()
+            .into_iter()
+            .map(|global| global.name.to_string())
+            .collect();
+        self.methods = introspection
+            .method_signatures()
+            .into_iter()
+            .map(|method| method.name.to_string())
+            .collect();
+        self.methods.dedup();
+    }
+}
+
+impl Helper for ReplHelper {}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let mut highlighted = String::with_capacity(line.len());
+        let mut end = 0;
+        for (kind, span) in tooling::tokenize(line) {
+            highlighted.push_str(&line[end..span.start.byte]);
+            let token = &line[span.start.byte..span.end.byte];
+            match color(kind) {
+                Some(color) => highlighted.push_str(&format!("\x1b[{color}m{token}\x1b[0m")),
+                None => highlighted.push_str(token),
+            }
+            end = span.end.byte;
+        }
+        highlighted.push_str(&line[end..]);
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+/// Returns the ANSI color code tokens of the given kind are highlighted with.
+fn color(kind: TokenKind) -> Option<&'static str> {
+    match kind {
+        TokenKind::Keyword => Some("35"),
+        TokenKind::Constant | TokenKind::Number => Some("33"),
+        TokenKind::String => Some("32"),
+        TokenKind::Label => Some("36"),
+        TokenKind::Comment | TokenKind::DocComment => Some("90"),
+        TokenKind::Error => Some("31"),
+        _ => None,
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let mut start = before
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let candidates: Vec<&str> = if start <= 1 && before.starts_with(':') {
+            start = 0;
+            COMMANDS.to_vec()
+        } else if before[..start].ends_with('.') {
+            self.methods.iter().map(String::as_str).collect()
+        } else {
+            self.globals.iter().map(String::as_str).collect()
+        };
+        let word = &before[start..];
+        Ok((
+            start,
+            candidates
+                .into_iter()
+                .filter(|candidate| candidate.starts_with(word))
+                .map(String::from)
+                .collect(),
+        ))
+    }
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input();
+        if input.starts_with(':') || Session::is_complete(input) {
+            Ok(ValidationResult::Valid(None))
+        } else {
+            Ok(ValidationResult::Incomplete)
+        }
+    }
+}
+
+/// Returns where the REPL's history is saved: `MICA_HISTORY` if it's set, or `.mica_history` in
+/// the user's home directory.
+fn history_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("MICA_HISTORY") {
+        return Some(PathBuf::from(path));
+    }
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".mica_history"))
+}
+
+/// Evaluates a line of input, which is either a command or code, and prints the result.
+fn eval(session: &mut Session<'_>, line: &str) -> Result<(), mica::Error> {
+    let (command, argument) = match line.trim().split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, argument.trim()),
+        None => (line.trim(), ""),
+    };
+    match command {
+        ":help" => println!("{HELP}"),
+        ":type" => {
+            let value: Value = session.eval(argument)?;
+            println!("{}", value.type_name());
+        }
+        ":dis" => {
+            let script = session.engine().compile("(repl)", argument)?;
+            print!("{}", script.disassemble());
+        }
+        _ if command.starts_with(':') => {
+            eprintln!("unknown command {command}; type :help for a list of commands");
+        }
+        _ => {
+            let result = session.eval::<Value>(line);
+            for warning in session.warnings() {
+                eprintln!("{warning}");
+            }
+            let value = result?.display_debug(session.engine())?;
+            println!("< {value}");
+        }
+    }
+    Ok(())
+}
+
+pub fn repl(mut engine: Engine) {
+    println!("Mica {} REPL", env!("CARGO_PKG_VERSION"));
+    println!("Type :help for help. Press Ctrl-C to exit.");
+    println!();
+
+    let mut editor =
+        Editor::with_config(rustyline::Config::builder().auto_add_history(true).build());
+    let mut helper = ReplHelper::default();
+    helper.refresh(&engine);
+    editor.set_helper(Some(helper));
+    let history_path = history_path();
+    if let Some(path) = &history_path {
+        // The history file doesn't exist the first time the REPL is started.
+        let _ = editor.load_history(path);
+    }
+
+    let mut session = Session::new(&mut engine, "(repl)");
+    while let Ok(line) = editor.readline("> ") {
+        if let Err(error) = eval(&mut session, &line) {
+            eprintln!("{error:#}");
+        }
+        println!();
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh(session.engine());
+        }
+        if let Some(path) = &history_path {
+            if let Err(error) = editor.save_history(path) {
+                eprintln!("could not save history to {}: {error}", path.display());
+            }
+        }
+    }
+}
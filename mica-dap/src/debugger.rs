@@ -0,0 +1,472 @@
+//! The debugging session, which answers the client's requests by inspecting paused fibers.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{self, BufReader, Read, Write},
+    mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use mica::{DebuggerHooks, Engine, PausedFiber, Value};
+use serde_json::{json, Value as Json};
+
+use crate::protocol::{read_message, write_message, Request};
+
+/// Mica engines execute scripts on a single thread, so this is the only thread reported to the
+/// client.
+const THREAD_ID: i64 = 1;
+
+/// The scopes shown for each stack frame, in the order they are listed in `scopes` responses.
+const SCOPES: [&str; 3] = ["Locals", "Upvalues", "Globals"];
+
+/// A debugger that a client (such as an editor) controls through the Debug Adapter Protocol.
+///
+/// Messages from the client are read on a background thread, but the debugger itself lives on the
+/// thread that runs scripts: it suspends execution by blocking inside the engine's
+/// [`DebuggerHooks`] until the client resumes it.
+#[derive(Clone)]
+pub struct Debugger {
+    session: Rc<RefCell<Session>>,
+}
+
+impl Debugger {
+    /// Creates a debugger communicating with the client through the given streams.
+    pub fn new(input: impl Read + Send + 'static, output: impl Write + 'static) -> Self {
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            let mut input = BufReader::new(input);
+            while let Ok(Some(message)) = read_message(&mut input) {
+                if message["type"] != "request" {
+                    continue;
+                }
+                let Ok(request) = serde_json::from_value(message) else {
+                    continue;
+                };
+                if sender.send(request).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            session: Rc::new(RefCell::new(Session {
+                requests,
+                output: Box::new(output),
+                seq: 1,
+                lines_start_at_1: true,
+                columns_start_at_1: true,
+                launch_arguments: None,
+                configured: false,
+                breakpoints: HashMap::new(),
+                module_paths: HashMap::new(),
+                step: None,
+                stop_on_entry: false,
+                pause_requested: false,
+                disconnected: false,
+            })),
+        }
+    }
+
+    /// Handles requests until the client is done configuring the session and asks for the script
+    /// to be launched (or attached to.) Returns the arguments of the `launch` or `attach` request,
+    /// which may contain host-specific settings such as the path of the script to run.
+    pub fn wait_for_launch(&self) -> io::Result<Json> {
+        let mut session = self.session.borrow_mut();
+        loop {
+            if session.configured {
+                if let Some(arguments) = session.launch_arguments.take() {
+                    return Ok(arguments);
+                }
+            }
+            let request = session.requests.recv().map_err(|_| {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "the client disconnected")
+            })?;
+            session.handle(request, None)?;
+        }
+    }
+
+    /// Installs the debugger's hooks into an engine. Scripts started in the engine from now on can
+    /// be debugged.
+    pub fn attach(&self, engine: &mut Engine) {
+        engine.set_debugger_hooks(self.clone());
+    }
+
+    /// Sends text to be shown in the client's debug console.
+    pub fn output(&self, text: &str) -> io::Result<()> {
+        self.session.borrow_mut().event(
+            "output",
+            json!({
+                "category": "stdout",
+                "output": text,
+            }),
+        )
+    }
+
+    /// Notifies the client that the script has finished executing, and handles requests until
+    /// the client disconnects.
+    pub fn finish(&self) -> io::Result<()> {
+        let mut session = self.session.borrow_mut();
+        session.event("terminated", json!({}))?;
+        while !session.disconnected {
+            let Ok(request) = session.requests.recv() else {
+                break;
+            };
+            session.handle(request, None)?;
+        }
+        Ok(())
+    }
+}
+
+impl DebuggerHooks for Debugger {
+    fn on_line(&mut self, fiber: &PausedFiber<'_>) {
+        let mut session = self.session.borrow_mut();
+        // There is no way of reporting errors to the script, so failing to talk to the client is
+        // treated as the client disconnecting.
+        if session.on_line(fiber).is_err() {
+            session.disconnect();
+        }
+    }
+}
+
+impl std::fmt::Debug for Debugger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Debugger").finish_non_exhaustive()
+    }
+}
+
+/// A pending step requested by the client.
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    /// Stop on the next line executed, even if it's in another function.
+    In,
+    /// Stop on the next line executed in the current function or one of its callers.
+    Over { call_depth: usize },
+    /// Stop on the next line executed in one of the current function's callers.
+    Out { call_depth: usize },
+}
+
+/// What to do after handling a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Stay,
+    Resume,
+}
+
+struct Session {
+    requests: Receiver<Request>,
+    output: Box<dyn Write>,
+    /// The sequence number of the next message sent to the client.
+    seq: i64,
+    lines_start_at_1: bool,
+    columns_start_at_1: bool,
+
+    launch_arguments: Option<Json>,
+    configured: bool,
+
+    /// Lines with breakpoints, by the path of the file they're in.
+    breakpoints: HashMap<PathBuf, Vec<u32>>,
+    /// Cache of the paths module names resolve to.
+    module_paths: HashMap<Rc<str>, PathBuf>,
+
+    step: Option<Step>,
+    stop_on_entry: bool,
+    pause_requested: bool,
+    disconnected: bool,
+}
+
+impl Session {
+    fn on_line(&mut self, fiber: &PausedFiber<'_>) -> io::Result<()> {
+        if self.disconnected {
+            return Ok(());
+        }
+        // The client may send requests while the script is running, such as to change
+        // breakpoints or to pause execution.
+        loop {
+            match self.requests.try_recv() {
+                Ok(request) => {
+                    self.handle(request, Some(fiber))?;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.disconnect();
+                    return Ok(());
+                }
+            }
+        }
+        if let Some(reason) = self.stop_reason(fiber) {
+            self.stop(fiber, reason)?;
+        }
+        Ok(())
+    }
+
+    /// Returns why execution should stop at the current line, or `None` if it shouldn't.
+    fn stop_reason(&mut self, fiber: &PausedFiber<'_>) -> Option<&'static str> {
+        if mem::take(&mut self.stop_on_entry) {
+            return Some("entry");
+        }
+        if mem::take(&mut self.pause_requested) {
+            return Some("pause");
+        }
+        let call_depth = fiber.call_depth();
+        match self.step {
+            Some(Step::In) => return Some("step"),
+            Some(Step::Over { call_depth: from }) if call_depth <= from => return Some("step"),
+            Some(Step::Out { call_depth: from }) if call_depth < from => return Some("step"),
+            _ => (),
+        }
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+        let frame = fiber.stack().into_iter().next()?;
+        let path = self.module_path(&frame.module_name).to_owned();
+        let lines = self.breakpoints.get(&path)?;
+        lines.contains(&frame.location.line).then_some("breakpoint")
+    }
+
+    /// Suspends execution and handles requests until the client resumes it.
+    fn stop(&mut self, fiber: &PausedFiber<'_>, reason: &str) -> io::Result<()> {
+        self.step = None;
+        self.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        )?;
+        loop {
+            let Ok(request) = self.requests.recv() else {
+                self.disconnect();
+                return Ok(());
+            };
+            if self.handle(request, Some(fiber))? == Flow::Resume {
+                return Ok(());
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.disconnected = true;
+        self.breakpoints.clear();
+        self.step = None;
+        self.pause_requested = false;
+    }
+
+    fn handle(&mut self, request: Request, fiber: Option<&PausedFiber<'_>>) -> io::Result<Flow> {
+        let arguments = &request.arguments;
+        let mut flow = Flow::Stay;
+        let body = match request.command.as_str() {
+            "initialize" => {
+                self.lines_start_at_1 = arguments["linesStartAt1"].as_bool().unwrap_or(true);
+                self.columns_start_at_1 = arguments["columnsStartAt1"].as_bool().unwrap_or(true);
+                self.respond(
+                    &request,
+                    Ok(json!({ "supportsConfigurationDoneRequest": true })),
+                )?;
+                return self.event("initialized", json!({})).map(|_| flow);
+            }
+            "launch" | "attach" => {
+                self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+                self.launch_arguments = Some(arguments.clone());
+                Ok(json!({}))
+            }
+            "configurationDone" => {
+                self.configured = true;
+                Ok(json!({}))
+            }
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "threads" => Ok(json!({
+                "threads": [{ "id": THREAD_ID, "name": "main" }],
+            })),
+            "stackTrace" => paused(fiber).map(|fiber| self.stack_trace(fiber)),
+            "scopes" => paused(fiber).map(|_| scopes(arguments)),
+            "variables" => paused(fiber).map(|fiber| variables(fiber, arguments)),
+            "continue" => paused(fiber).map(|_| {
+                flow = Flow::Resume;
+                json!({ "allThreadsContinued": true })
+            }),
+            "next" | "stepIn" | "stepOut" => paused(fiber).map(|fiber| {
+                let call_depth = fiber.call_depth();
+                self.step = Some(match request.command.as_str() {
+                    "next" => Step::Over { call_depth },
+                    "stepIn" => Step::In,
+                    _ => Step::Out { call_depth },
+                });
+                flow = Flow::Resume;
+                json!({})
+            }),
+            "pause" => {
+                self.pause_requested = true;
+                Ok(json!({}))
+            }
+            "disconnect" => {
+                self.disconnect();
+                flow = Flow::Resume;
+                Ok(json!({}))
+            }
+            command => Err(format!("unsupported request: {command}")),
+        };
+        self.respond(&request, body)?;
+        Ok(flow)
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        let source = &arguments["source"];
+        let Some(path) = source["path"].as_str().or(source["name"].as_str()) else {
+            return json!({ "breakpoints": [] });
+        };
+        let lines: Vec<u64> = match arguments["breakpoints"].as_array() {
+            Some(breakpoints) => breakpoints
+                .iter()
+                .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                .collect(),
+            // Older clients only send line numbers.
+            None => arguments["lines"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Json::as_u64)
+                .collect(),
+        };
+        let lines: Vec<u32> = lines
+            .into_iter()
+            .map(|line| self.line_from_client(line))
+            .collect();
+        let response = lines
+            .iter()
+            .map(|&line| json!({ "verified": true, "line": self.line_to_client(line) }))
+            .collect::<Vec<_>>();
+        self.breakpoints.insert(resolve_path(path), lines);
+        json!({ "breakpoints": response })
+    }
+
+    fn stack_trace(&mut self, fiber: &PausedFiber<'_>) -> Json {
+        let frames: Vec<_> = fiber
+            .stack()
+            .into_iter()
+            .enumerate()
+            .map(|(id, frame)| {
+                let path = self.module_path(&frame.module_name);
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| frame.module_name.to_string());
+                json!({
+                    "id": id,
+                    "name": &*frame.function_name,
+                    "source": { "name": name, "path": path },
+                    "line": self.line_to_client(frame.location.line),
+                    "column": frame.location.column as u64 + u64::from(self.columns_start_at_1)
+                        - 1,
+                })
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    /// Returns the path of the file a module was loaded from, such that it can be compared with
+    /// paths sent by the client.
+    fn module_path(&mut self, module_name: &Rc<str>) -> &Path {
+        self.module_paths
+            .entry(Rc::clone(module_name))
+            .or_insert_with(|| resolve_path(module_name))
+    }
+
+    fn line_from_client(&self, line: u64) -> u32 {
+        (line + 1 - u64::from(self.lines_start_at_1)) as u32
+    }
+
+    fn line_to_client(&self, line: u32) -> u64 {
+        u64::from(line) + u64::from(self.lines_start_at_1) - 1
+    }
+
+    fn respond(&mut self, request: &Request, body: Result<Json, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+            "success": body.is_ok(),
+        });
+        match body {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = Json::from(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        message["seq"] = Json::from(self.seq);
+        self.seq += 1;
+        write_message(&mut self.output, &message)
+    }
+}
+
+fn paused<'a, 'f>(fiber: Option<&'a PausedFiber<'f>>) -> Result<&'a PausedFiber<'f>, String> {
+    fiber.ok_or_else(|| String::from("the script is not paused"))
+}
+
+/// Resolves a path to its canonical form if the file exists, so that different spellings of the
+/// same path compare equal.
+fn resolve_path(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// Variable references encode the stack frame and the scope within it.
+fn variables_reference(frame: usize, scope: usize) -> usize {
+    frame * SCOPES.len() + scope + 1
+}
+
+fn scopes(arguments: &Json) -> Json {
+    let frame = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+    let scopes: Vec<_> = SCOPES
+        .iter()
+        .enumerate()
+        .map(|(scope, name)| {
+            json!({
+                "name": name,
+                "variablesReference": variables_reference(frame, scope),
+                "expensive": false,
+            })
+        })
+        .collect();
+    json!({ "scopes": scopes })
+}
+
+fn variables(fiber: &PausedFiber<'_>, arguments: &Json) -> Json {
+    let reference = arguments["variablesReference"].as_u64().unwrap_or(0) as usize;
+    let variables = match reference.checked_sub(1) {
+        Some(index) => {
+            let frame = index / SCOPES.len();
+            match index % SCOPES.len() {
+                0 => fiber.locals(frame),
+                1 => fiber.upvalues(frame),
+                _ => fiber.globals(),
+            }
+        }
+        None => Vec::new(),
+    };
+    let variables: Vec<_> = variables
+        .into_iter()
+        .map(|(name, value): (Rc<str>, Value)| {
+            json!({
+                "name": &*name,
+                "value": format!("{value:?}"),
+                "variablesReference": 0,
+            })
+        })
+        .collect();
+    json!({ "variables": variables })
+}
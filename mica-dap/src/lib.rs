@@ -0,0 +1,40 @@
+//! A debugger for Mica scripts, controlled by editors such as VS Code through the
+//! [Debug Adapter Protocol][dap].
+//!
+//! Scripts run inside the application embedding Mica, so the debug adapter is a library rather
+//! than a standalone program: the host creates a [`Debugger`] connected to the client, waits for
+//! the client to launch the session, attaches the debugger to its engine, and runs scripts as it
+//! normally would. Breakpoints, stepping, and inspection of the call stack and variables then
+//! work while the scripts execute.
+//!
+//! ```no_run
+//! use std::net::TcpListener;
+//!
+//! use mica::{Engine, Value};
+//! use mica_dap::Debugger;
+//!
+//! // The script's standard output can't be used for talking to the client, as `print` would
+//! // interfere with it.
+//! let listener = TcpListener::bind("127.0.0.1:4711")?;
+//! let (stream, _) = listener.accept()?;
+//! let debugger = Debugger::new(stream.try_clone()?, stream);
+//! let launch = debugger.wait_for_launch()?;
+//! let path = launch["program"].as_str().unwrap_or("main.mi").to_owned();
+//!
+//! let mut engine = Engine::new();
+//! debugger.attach(&mut engine);
+//! let source = std::fs::read_to_string(&path)?;
+//! let result: Result<Value, _> = engine.start(&path, source)?.trampoline();
+//! if let Err(error) = result {
+//!     debugger.output(&format!("{error}\n"))?;
+//! }
+//! debugger.finish()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [dap]: https://microsoft.github.io/debug-adapter-protocol/
+
+mod debugger;
+pub mod protocol;
+
+pub use debugger::*;
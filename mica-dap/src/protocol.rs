@@ -0,0 +1,63 @@
+//! The base protocol of the Debug Adapter Protocol: JSON messages prefixed with a
+//! `Content-Length` header.
+
+use std::io::{self, BufRead, Write};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A request sent by the client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    /// The sequence number of the request, which the response refers back to.
+    pub seq: i64,
+    /// The command to execute, eg. `setBreakpoints`.
+    pub command: String,
+    /// The arguments of the command. Their shape depends on the command.
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+/// Reads a single message. Returns `None` if the stream ended before a message started.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return match content_length {
+                None => Ok(None),
+                Some(_) => Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            // An empty line separates the headers from the content.
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let length = value.trim().parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length header")
+                })?;
+                content_length = Some(length);
+            }
+        }
+    }
+    let Some(content_length) = content_length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Content-Length header",
+        ));
+    };
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+    Ok(Some(serde_json::from_slice(&content)?))
+}
+
+/// Writes a single message and flushes the writer.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    writer.flush()
+}
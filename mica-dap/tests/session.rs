@@ -0,0 +1,207 @@
+use std::{
+    io::{self, BufReader, Read, Write},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use mica::{Engine, Value};
+use mica_dap::{
+    protocol::{read_message, write_message},
+    Debugger,
+};
+use serde_json::{json, Value as Json};
+
+/// One end of an in-memory pipe.
+struct PipeReader {
+    chunks: Receiver<Vec<u8>>,
+    buffer: io::Cursor<Vec<u8>>,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.buffer.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            match self.chunks.recv() {
+                Ok(chunk) => self.buffer = io::Cursor::new(chunk),
+                Err(_) => return Ok(0),
+            }
+        }
+    }
+}
+
+/// The other end of an in-memory pipe.
+struct PipeWriter(Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(buf.to_owned())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn pipe() -> (PipeWriter, PipeReader) {
+    let (sender, chunks) = mpsc::channel();
+    let reader = PipeReader {
+        chunks,
+        buffer: io::Cursor::new(Vec::new()),
+    };
+    (PipeWriter(sender), reader)
+}
+
+struct Client {
+    input: BufReader<PipeReader>,
+    output: PipeWriter,
+    seq: i64,
+}
+
+impl Client {
+    fn request(&mut self, command: &str, arguments: Json) -> Json {
+        let seq = self.seq;
+        self.seq += 1;
+        let request = json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        });
+        write_message(&mut self.output, &request).unwrap();
+        loop {
+            let message = self.receive();
+            if message["type"] == "response" && message["request_seq"] == seq {
+                assert_eq!(message["success"], true, "{message}");
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn event(&mut self, event: &str) -> Json {
+        loop {
+            let message = self.receive();
+            if message["type"] == "event" && message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Json {
+        read_message(&mut self.input)
+            .unwrap()
+            .expect("the debugger stopped sending messages")
+    }
+
+    /// Returns the names of the functions on the call stack and the innermost frame's line.
+    fn stack(&mut self) -> (Vec<String>, u64) {
+        let body = self.request("stackTrace", json!({ "threadId": 1 }));
+        let frames = body["stackFrames"].as_array().unwrap();
+        let names = frames
+            .iter()
+            .map(|frame| frame["name"].as_str().unwrap().to_owned())
+            .collect();
+        (names, frames[0]["line"].as_u64().unwrap())
+    }
+
+    /// Returns the variables in the given scope of the innermost frame, as `name = value` strings.
+    fn variables(&mut self, scope: &str) -> Vec<String> {
+        let scopes = self.request("scopes", json!({ "frameId": 0 }));
+        let reference = scopes["scopes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["name"] == scope)
+            .unwrap()["variablesReference"]
+            .clone();
+        let body = self.request("variables", json!({ "variablesReference": reference }));
+        body["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variable| {
+                let name = variable["name"].as_str().unwrap();
+                let value = variable["value"].as_str().unwrap();
+                format!("{name} = {value}")
+            })
+            .collect()
+    }
+}
+
+const SCRIPT: &str = "\
+func double(x) = do
+    let y = x * 2
+    y
+end
+let a = 1
+let b = double(a)
+let c = b
+";
+
+#[test]
+fn breakpoints_stepping_and_variables() {
+    let (client_output, server_input) = pipe();
+    let (server_output, client_input) = pipe();
+    let host = thread::spawn(move || {
+        let debugger = Debugger::new(server_input, server_output);
+        let launch = debugger.wait_for_launch().unwrap();
+        assert_eq!(launch["program"], "test.mi");
+
+        let mut engine = Engine::new();
+        debugger.attach(&mut engine);
+        let _: Value = engine
+            .start("test.mi", SCRIPT)
+            .unwrap()
+            .trampoline()
+            .unwrap();
+        debugger.finish().unwrap();
+    });
+    let mut client = Client {
+        input: BufReader::new(client_input),
+        output: client_output,
+        seq: 1,
+    };
+
+    let capabilities = client.request("initialize", json!({ "adapterID": "mica" }));
+    assert_eq!(capabilities["supportsConfigurationDoneRequest"], true);
+    client.event("initialized");
+    let breakpoints = client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": "test.mi" },
+            "breakpoints": [{ "line": 2 }],
+        }),
+    );
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    client.request("launch", json!({ "program": "test.mi" }));
+    client.request("configurationDone", json!({}));
+
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], "breakpoint");
+    assert_eq!(client.stack(), (vec!["double".into(), "<main>".into()], 2));
+    assert_eq!(client.variables("Locals"), ["x = 1"]);
+    let globals = client.variables("Globals");
+    assert_eq!(globals[..3], ["a = 1", "b = nil", "c = nil"]);
+    assert!(globals[3].starts_with("double = <func"));
+
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.stack().1, 3);
+    assert_eq!(client.variables("Locals"), ["x = 1", "y = 2"]);
+
+    // Returning to the line that called the function doesn't count as a step, so stepping out
+    // ends up on the line after it.
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.stack(), (vec!["<main>".into()], 7));
+
+    client.request("continue", json!({ "threadId": 1 }));
+    client.event("terminated");
+    client.request("disconnect", json!({}));
+    host.join().unwrap();
+}
@@ -0,0 +1,272 @@
+//! Derive macros for exposing Rust types to the Mica scripting language.
+//!
+//! The macros are re-exported by `mica` when its `derive` feature is enabled, and should be used
+//! from there. See the documentation of `mica::MicaType` for examples.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Fields, FnArg, ImplItem,
+    ItemImpl, LitStr,
+};
+
+/// The maximum number of parameters a foreign function can have, which limits the number of
+/// fields a generated constructor can accept.
+const MAX_PARAMETERS: usize = 8;
+
+/// Generates an implementation of `mica::MicaType`, which describes the struct to Mica with a
+/// `TypeBuilder`.
+///
+/// Every field gets a getter named after it, which returns a clone of the field's value, and a
+/// setter with a `set_` prefix. A static `new` function constructs the struct from all of its
+/// fields, in the order they're declared in.
+///
+/// The struct can be customized with `#[mica(...)]` attributes:
+/// - `name = "Name"` sets the name of the type in Mica, which defaults to the struct's name,
+/// - `constructor = "name"` renames the constructor, and `no_constructor` leaves it out,
+/// - `methods` adds the functions from an `impl` block marked with `#[mica::methods]`.
+///
+/// And so can its fields:
+/// - `name = "name"` renames the getter (and the setter, which becomes `set_name`),
+/// - `readonly` leaves out the setter,
+/// - `skip` leaves out the getter and the setter; the constructor initializes the field with its
+///   `Default` value.
+#[proc_macro_derive(MicaType, attributes(mica))]
+pub fn derive_mica_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_mica_type(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generates an implementation of `mica::MicaMethods` from the functions in an `impl` block that
+/// are marked with `#[mica]`.
+///
+/// Functions taking `&self` or `&mut self` become instance functions, and the rest become static
+/// functions. `#[mica(name = "name")]` changes the name a function has in Mica.
+#[proc_macro_attribute]
+pub fn methods(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = TokenStream2::from(args);
+    if !args.is_empty() {
+        return syn::Error::new(args.span(), "#[mica::methods] does not accept arguments")
+            .into_compile_error()
+            .into();
+    }
+    let input = parse_macro_input!(input as ItemImpl);
+    expand_methods(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct StructOptions {
+    name: Option<LitStr>,
+    constructor: Option<LitStr>,
+    no_constructor: bool,
+    methods: bool,
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    name: Option<LitStr>,
+    readonly: bool,
+    skip: bool,
+}
+
+fn mica_attributes(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("mica"))
+}
+
+fn parse_struct_options(attrs: &[Attribute]) -> syn::Result<StructOptions> {
+    let mut options = StructOptions::default();
+    for attr in mica_attributes(attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                options.name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("constructor") {
+                options.constructor = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("no_constructor") {
+                options.no_constructor = true;
+            } else if meta.path.is_ident("methods") {
+                options.methods = true;
+            } else {
+                return Err(meta.error("unknown MicaType option"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn parse_field_options(attrs: &[Attribute]) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    for attr in mica_attributes(attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                options.name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("readonly") {
+                options.readonly = true;
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else {
+                return Err(meta.error("unknown MicaType field option"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn expand_mica_type(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let options = parse_struct_options(&input.attrs)?;
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect(),
+            Fields::Unit => Vec::new(),
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "MicaType can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "MicaType can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut accessors = Vec::new();
+    let mut parameters = Vec::new();
+    let mut initializers = Vec::new();
+    for field in fields {
+        let field_options = parse_field_options(&field.attrs)?;
+        let field_ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        if field_options.skip {
+            initializers.push(quote! { #field_ident: ::std::default::Default::default() });
+            continue;
+        }
+        let name = field_options
+            .name
+            .map(|name| name.value())
+            .unwrap_or_else(|| field_ident.to_string());
+        let getter = quote! { |this: &Self| ::std::clone::Clone::clone(&this.#field_ident) };
+        accessors.push(if field_options.readonly {
+            quote! { .add_readonly_field(#name, #getter) }
+        } else {
+            quote! {
+                .add_field(#name, #getter, |this: &mut Self, value: #ty| {
+                    this.#field_ident = value;
+                })
+            }
+        });
+        parameters.push(quote! { #field_ident: #ty });
+        initializers.push(quote! { #field_ident });
+    }
+
+    let constructor = if options.no_constructor {
+        quote! {}
+    } else {
+        if parameters.len() > MAX_PARAMETERS {
+            return Err(syn::Error::new(
+                input.span(),
+                format!(
+                    "the constructor would have more than {MAX_PARAMETERS} parameters; \
+                     use #[mica(no_constructor)] and add one yourself"
+                ),
+            ));
+        }
+        let name = options
+            .constructor
+            .map(|name| name.value())
+            .unwrap_or_else(|| "new".to_owned());
+        quote! {
+            .add_static(#name, |#(#parameters),*| Self { #(#initializers),* })
+        }
+    };
+
+    let methods = if options.methods {
+        quote! { <Self as ::mica::MicaMethods>::add_methods(builder) }
+    } else {
+        quote! { builder }
+    };
+
+    let type_name = options
+        .name
+        .map(|name| name.value())
+        .unwrap_or_else(|| ident.to_string());
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mica::MicaType for #ident #ty_generics #where_clause {
+            fn type_builder() -> ::mica::TypeBuilder<Self> {
+                let builder = ::mica::TypeBuilder::<Self>::new(#type_name)
+                    #(#accessors)*
+                    #constructor;
+                #methods
+            }
+        }
+    })
+}
+
+fn expand_methods(mut input: ItemImpl) -> syn::Result<TokenStream2> {
+    if let Some((_, path, _)) = &input.trait_ {
+        return Err(syn::Error::new(
+            path.span(),
+            "#[mica::methods] can only be used on inherent impl blocks",
+        ));
+    }
+
+    let mut registrations = Vec::new();
+    for item in &mut input.items {
+        let ImplItem::Fn(function) = item else {
+            continue;
+        };
+        let mut marked = false;
+        let mut name = None;
+        for attr in mica_attributes(&function.attrs) {
+            marked = true;
+            if matches!(attr.meta, syn::Meta::Path(_)) {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown #[mica] option"))
+                }
+            })?;
+        }
+        if !marked {
+            continue;
+        }
+        function.attrs.retain(|attr| !attr.path().is_ident("mica"));
+
+        let fn_ident = &function.sig.ident;
+        let name = name
+            .map(|name| name.value())
+            .unwrap_or_else(|| fn_ident.to_string());
+        let add = match function.sig.inputs.first() {
+            Some(FnArg::Receiver(_)) => format_ident!("add_function"),
+            _ => format_ident!("add_static"),
+        };
+        registrations.push(quote! { .#add(#name, Self::#fn_ident) });
+    }
+
+    let self_ty = &input.self_ty;
+    let (impl_generics, _, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #input
+
+        impl #impl_generics ::mica::MicaMethods for #self_ty #where_clause {
+            fn add_methods(builder: ::mica::TypeBuilder<Self>) -> ::mica::TypeBuilder<Self> {
+                builder #(#registrations)*
+            }
+        }
+    })
+}
@@ -0,0 +1,288 @@
+//! A documentation generator for the Mica scripting language.
+//!
+//! Documentation is written in doc comments, which are comments beginning with `##` placed on the
+//! lines right above a function, struct, or trait, or a method inside of an `impl` block or trait.
+//! Doc comments are Markdown; examples are written in fenced code blocks, and are kept as they are
+//! in the generated docs.
+//!
+//! Private methods (ones marked with `priv`) and anonymous functions are not documented.
+//!
+//! # Examples
+//! ```
+//! # // Rustdoc strips one `#` from lines beginning with `##`, so `###` below becomes `##`.
+//! let source = r#"
+//!     ### Greets the person with the given name.
+//!     func greet(name) = print("Hello, ", name, "!")
+//! "#;
+//! let items = mica_doc::extract("greeter.mi", source)?;
+//! assert_eq!(items[0].signature, "func greet(name)");
+//! assert_eq!(items[0].docs, "Greets the person with the given name.");
+//!
+//! let markdown = mica_doc::to_markdown("greeter", &items);
+//! assert!(markdown.contains("## `func greet(name)`"));
+//! # Ok::<(), mica::Error>(())
+//! ```
+
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+
+use std::fmt::Write;
+
+use mica::tooling::{self, Ast, NodeId, NodeKind};
+
+/// The kind of a documented [`Item`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    /// A function, or a method of a struct or trait.
+    Function,
+    /// A struct. Its children are the methods declared in its `impl` block.
+    Struct,
+    /// A trait. Its children are the methods it requires.
+    Trait,
+    /// An `as` block inside of a struct's `impl` block. Its children are the methods implementing
+    /// the trait.
+    Implementation,
+}
+
+/// A documented item.
+#[derive(Debug, Clone)]
+pub struct Item {
+    /// What kind of item this is.
+    pub kind: ItemKind,
+    /// The name of the item. For [`ItemKind::Implementation`] this is the implemented trait.
+    pub name: String,
+    /// The item's signature, the way it'd be written in source code, eg. `func add(a, b)`.
+    pub signature: String,
+    /// The contents of the item's doc comment, with the leading `##` stripped. Empty if the item
+    /// doesn't have one.
+    pub docs: String,
+    /// Items nested in this one.
+    pub children: Vec<Item>,
+}
+
+/// Extracts the documented items from source code. The file name is only used for reporting
+/// syntax errors.
+pub fn extract(filename: &str, source: &str) -> Result<Vec<Item>, mica::Error> {
+    let (ast, root) = tooling::parse(filename, source)?;
+    let extractor = Extractor {
+        ast: &ast,
+        lines: source.lines().collect(),
+    };
+    Ok(extractor.items(ast.children(root).unwrap_or(&[])))
+}
+
+struct Extractor<'a> {
+    ast: &'a Ast,
+    lines: Vec<&'a str>,
+}
+
+impl Extractor<'_> {
+    fn items(&self, nodes: &[NodeId]) -> Vec<Item> {
+        nodes.iter().filter_map(|&node| self.item(node)).collect()
+    }
+
+    fn item(&self, node: NodeId) -> Option<Item> {
+        let ast = self.ast;
+        match ast.kind(node) {
+            NodeKind::Func => self.function(node),
+            NodeKind::Struct => Some(self.structure(node, vec![])),
+            NodeKind::Impl => {
+                let implementee = ast.node_pair(node).0;
+                (ast.kind(implementee) == NodeKind::Struct).then(|| {
+                    self.structure(implementee, self.items(ast.children(node).unwrap_or(&[])))
+                })
+            }
+            NodeKind::ImplAs => {
+                let implementee = ast.node_pair(node).0;
+                let name = ast.source_text(implementee).unwrap_or("?").to_owned();
+                Some(Item {
+                    kind: ItemKind::Implementation,
+                    signature: format!("as {name}"),
+                    name,
+                    docs: self.docs(node),
+                    children: self.items(ast.children(node).unwrap_or(&[])),
+                })
+            }
+            NodeKind::Trait => {
+                let name = ast.string(ast.node_pair(node).0)?.to_string();
+                Some(Item {
+                    kind: ItemKind::Trait,
+                    signature: format!("trait {name}"),
+                    name,
+                    docs: self.docs(node),
+                    children: self.items(ast.children(node).unwrap_or(&[])),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn structure(&self, node: NodeId, children: Vec<Item>) -> Item {
+        let name = self
+            .ast
+            .string(self.ast.node_pair(node).0)
+            .map_or("?", |name| name)
+            .to_owned();
+        Item {
+            kind: ItemKind::Struct,
+            signature: format!("struct {name}"),
+            name,
+            docs: self.docs(node),
+            children,
+        }
+    }
+
+    fn function(&self, node: NodeId) -> Option<Item> {
+        let ast = self.ast;
+        let (head, _) = ast.node_pair(node);
+        let (name, parameters) = ast.node_pair(head);
+        let name = ast.string(name)?.to_string();
+        let (kind, visibility) = ast.node_pair(parameters);
+        if visibility != NodeId::EMPTY && ast.kind(visibility) == NodeKind::Priv {
+            return None;
+        }
+
+        let parameters: Vec<_> = ast
+            .children(parameters)
+            .unwrap_or(&[])
+            .iter()
+            .map(|&parameter| ast.pattern_to_string(parameter))
+            .collect();
+        let mut signature = format!("func {name}({})", parameters.join(", "));
+        if kind != NodeId::EMPTY {
+            match ast.kind(kind) {
+                NodeKind::Constructor => signature.push_str(" constructor"),
+                NodeKind::Static => signature.push_str(" static"),
+                _ => (),
+            }
+        }
+        if visibility != NodeId::EMPTY {
+            signature.push_str(" pub");
+        }
+
+        Some(Item {
+            kind: ItemKind::Function,
+            name,
+            signature,
+            docs: self.docs(node),
+            children: vec![],
+        })
+    }
+
+    /// Returns the doc comment written on the lines right above the node.
+    fn docs(&self, node: NodeId) -> String {
+        let line = self.ast.location(node).line as usize;
+        let mut docs: Vec<_> = self.lines[..line.saturating_sub(1)]
+            .iter()
+            .rev()
+            .map_while(|line| line.trim_start().strip_prefix("##"))
+            .map(|line| line.strip_prefix(' ').unwrap_or(line))
+            .collect();
+        docs.reverse();
+        docs.join("\n").trim().to_owned()
+    }
+}
+
+/// Renders documentation as a Markdown document with the given title.
+pub fn to_markdown(title: &str, items: &[Item]) -> String {
+    fn write_items(out: &mut String, items: &[Item], level: usize) {
+        for item in items {
+            let _ = write!(out, "\n{} `{}`\n", "#".repeat(level), item.signature);
+            if !item.docs.is_empty() {
+                let _ = write!(out, "\n{}\n", item.docs);
+            }
+            write_items(out, &item.children, (level + 1).min(6));
+        }
+    }
+
+    let mut out = format!("# {title}\n");
+    write_items(&mut out, items, 2);
+    out
+}
+
+/// Renders documentation as a standalone HTML page with the given title.
+pub fn to_html(title: &str, items: &[Item]) -> String {
+    fn write_items(out: &mut String, items: &[Item], level: usize) {
+        for item in items {
+            let _ = writeln!(
+                out,
+                "<section>\n<h{level}><code>{}</code></h{level}>",
+                escape(&item.signature)
+            );
+            write_docs(out, &item.docs);
+            write_items(out, &item.children, (level + 1).min(6));
+            out.push_str("</section>\n");
+        }
+    }
+
+    let title = escape(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         </head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    write_items(&mut out, items, 2);
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Writes the HTML for a doc comment. Only paragraphs, fenced code blocks, and inline code are
+/// rendered; everything else is kept as plain text.
+fn write_docs(out: &mut String, docs: &str) {
+    let mut paragraph = String::new();
+    let mut in_code_block = false;
+    for line in docs.lines() {
+        if line.trim_start().starts_with("```") {
+            if in_code_block {
+                out.push_str("</code></pre>\n");
+            } else {
+                end_paragraph(out, &mut paragraph);
+                out.push_str("<pre><code>");
+            }
+            in_code_block = !in_code_block;
+        } else if in_code_block {
+            out.push_str(&escape(line));
+            out.push('\n');
+        } else if line.trim().is_empty() {
+            end_paragraph(out, &mut paragraph);
+        } else {
+            if !paragraph.is_empty() {
+                paragraph.push(' ');
+            }
+            paragraph.push_str(line.trim());
+        }
+    }
+    if in_code_block {
+        out.push_str("</code></pre>\n");
+    }
+    end_paragraph(out, &mut paragraph);
+}
+
+fn end_paragraph(out: &mut String, paragraph: &mut String) {
+    if paragraph.is_empty() {
+        return;
+    }
+    out.push_str("<p>");
+    // Backticks alternate between opening and closing inline code.
+    for (i, part) in paragraph.split('`').enumerate() {
+        if i % 2 == 1 {
+            let _ = write!(out, "<code>{}</code>", escape(part));
+        } else {
+            out.push_str(&escape(part));
+        }
+    }
+    out.push_str("</p>\n");
+    paragraph.clear();
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,124 @@
+use mica_doc::{Item, ItemKind};
+
+fn extract(source: &str) -> Vec<Item> {
+    match mica_doc::extract("test.mi", source) {
+        Ok(items) => items,
+        Err(error) => panic!("extracting docs failed:\n{error:#}"),
+    }
+}
+
+#[test]
+fn doc_comments_are_attached_to_the_item_below() {
+    let items = extract(
+        r#"
+## Not attached to anything, because of the blank line.
+
+## Adds two numbers.
+##
+## Works with any numbers.
+func add(a, b) = a + b
+
+# A regular comment.
+func sub(a, b) = a - b
+"#,
+    );
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].kind, ItemKind::Function);
+    assert_eq!(items[0].signature, "func add(a, b)");
+    assert_eq!(
+        items[0].docs,
+        "Adds two numbers.\n\nWorks with any numbers."
+    );
+    assert_eq!(items[1].name, "sub");
+    assert_eq!(items[1].docs, "");
+}
+
+#[test]
+fn structs_and_traits_contain_their_methods() {
+    let items = extract(
+        r#"
+## Has an area.
+trait Shape
+    ## Returns the area.
+    func area()
+end
+
+## A square.
+struct Square impl
+    ## Creates a square.
+    func new(side) constructor = @side = side
+    func unit() static = Square.new(1)
+    func secret() priv = nil
+
+    as Shape
+        func area() = @side * @side
+    end
+end
+"#,
+    );
+    let signatures = |item: &Item| -> Vec<String> {
+        item.children
+            .iter()
+            .map(|child| child.signature.clone())
+            .collect()
+    };
+
+    assert_eq!(items[0].kind, ItemKind::Trait);
+    assert_eq!(items[0].docs, "Has an area.");
+    assert_eq!(signatures(&items[0]), ["func area()"]);
+    assert_eq!(items[0].children[0].docs, "Returns the area.");
+
+    assert_eq!(items[1].kind, ItemKind::Struct);
+    assert_eq!(items[1].signature, "struct Square");
+    assert_eq!(
+        signatures(&items[1]),
+        [
+            "func new(side) constructor",
+            "func unit() static",
+            "as Shape"
+        ]
+    );
+    assert_eq!(items[1].children[0].docs, "Creates a square.");
+    assert_eq!(items[1].children[2].kind, ItemKind::Implementation);
+    assert_eq!(signatures(&items[1].children[2]), ["func area()"]);
+}
+
+#[test]
+fn examples_are_kept_in_markdown() {
+    let items = extract(
+        r#"
+## Doubles a number.
+## ```
+## double(2)  # 4
+## ```
+func double(x) = x * 2
+"#,
+    );
+    assert_eq!(
+        mica_doc::to_markdown("numbers", &items),
+        "# numbers\n\n## `func double(x)`\n\nDoubles a number.\n```\ndouble(2)  # 4\n```\n"
+    );
+}
+
+#[test]
+fn html_is_escaped() {
+    let items = extract(
+        r#"
+## Returns whether `a < b`.
+## ```
+## less(1, 2) && true
+## ```
+func less(a, b) = a < b
+"#,
+    );
+    let html = mica_doc::to_html("<comparisons>", &items);
+    assert!(html.contains("<title>&lt;comparisons&gt;</title>"));
+    assert!(html.contains("<h2><code>func less(a, b)</code></h2>"));
+    assert!(html.contains("<p>Returns whether <code>a &lt; b</code>.</p>"));
+    assert!(html.contains("<pre><code>less(1, 2) &amp;&amp; true\n</code></pre>"));
+}
+
+#[test]
+fn syntax_errors_are_reported() {
+    assert!(mica_doc::extract("test.mi", "func (").is_err());
+}
@@ -0,0 +1,504 @@
+//! A code formatter for the Mica scripting language.
+//!
+//! The formatter works on the token stream rather than the syntax tree, which allows it to keep
+//! comments and the line structure of the source code intact. It:
+//! - re-indents lines according to the blocks and brackets they're nested in, aligning each `end`
+//!   with the line that opened its block,
+//! - normalizes the spacing between tokens on each line,
+//! - collapses runs of blank lines into a single blank line, and removes trailing whitespace,
+//! - wraps argument lists (and other bracketed lists) of lines that are too long, placing each
+//!   element on a separate line.
+//!
+//! Source code that does not parse is rejected, so that the formatter never has to guess what the
+//! programmer meant.
+//!
+//! # Examples
+//! ```
+//! let source = "func add(a,b) = do\n  a+b\n    end";
+//! let formatted = mica_fmt::format("example.mi", source, &mica_fmt::Options::default())?;
+//! assert_eq!(formatted, "func add(a, b) = do\n    a + b\nend\n");
+//! # Ok::<(), mica::Error>(())
+//! ```
+
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+
+use std::rc::Rc;
+
+use mica::ll::{
+    lexer::{Lexer, TokenKind},
+    parser::Parser,
+};
+
+/// Options controlling the output of the formatter.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The number of spaces each level of indentation is made of.
+    pub indent_width: usize,
+    /// The maximum number of characters in a line. Lines longer than this get their outermost
+    /// bracketed list wrapped, if they have one.
+    pub max_width: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            max_width: 100,
+        }
+    }
+}
+
+/// Formats the given source code. The file name is only used for reporting syntax errors.
+pub fn format(filename: &str, source: &str, options: &Options) -> Result<String, mica::Error> {
+    let module_name: Rc<str> = Rc::from(filename);
+    Parser::new(Lexer::new(Rc::clone(&module_name), source.to_owned()))
+        .parse()
+        .map_err(mica::Error::from)?;
+
+    let mut lexer = Lexer::new(module_name, source.to_owned());
+    let mut splitter = LineSplitter::default();
+    let mut position = 0;
+    loop {
+        let token = lexer.next_token().map_err(mica::Error::from)?;
+        // The front matter is skipped by the lexer along with the first token's leading
+        // whitespace, and is kept as it is.
+        if position == 0 {
+            if let Some(span) = lexer.front_matter().span() {
+                splitter.gap(&source[..span.start.byte]);
+                splitter.verbatim(&source[span.start.byte..span.end.byte]);
+                position = span.end.byte;
+            }
+        }
+        if token.kind == TokenKind::Eof {
+            break;
+        }
+        let (start, end) = (token.location.byte, token.end.byte);
+        splitter.gap(&source[position..start]);
+        splitter.word(Word {
+            kind: token.kind,
+            text: &source[start..end],
+        });
+        position = end;
+    }
+    splitter.gap(&source[position..]);
+    splitter.end_line();
+
+    let mut formatter = Formatter {
+        options,
+        output: String::new(),
+        blocks: Vec::new(),
+        in_long_string: false,
+    };
+    formatter.format_lines(splitter.lines);
+    Ok(formatter.output)
+}
+
+/// A token, along with the source code it was lexed from.
+#[derive(Debug)]
+struct Word<'s> {
+    kind: TokenKind,
+    text: &'s str,
+}
+
+/// A line of source code.
+#[derive(Debug)]
+enum Line<'s> {
+    Code {
+        words: Vec<Word<'s>>,
+        comment: Option<&'s str>,
+    },
+    Comment(&'s str),
+    Blank,
+}
+
+/// Splits a token stream into lines, recovering the comments and blank lines the lexer skips
+/// from the gaps between tokens.
+#[derive(Default)]
+struct LineSplitter<'s> {
+    lines: Vec<Line<'s>>,
+    words: Vec<Word<'s>>,
+    comment: Option<&'s str>,
+    line_has_content: bool,
+}
+
+impl<'s> LineSplitter<'s> {
+    fn gap(&mut self, mut gap: &'s str) {
+        while let Some(c) = gap.chars().next() {
+            match c {
+                '\n' => {
+                    self.end_line();
+                    gap = &gap[1..];
+                }
+                '#' => {
+                    let end = gap.find('\n').unwrap_or(gap.len());
+                    let comment = gap[..end].trim_end();
+                    if self.words.is_empty() {
+                        self.lines.push(Line::Comment(comment));
+                    } else {
+                        self.comment = Some(comment);
+                    }
+                    self.line_has_content = true;
+                    gap = &gap[end..];
+                }
+                _ => gap = &gap[c.len_utf8()..],
+            }
+        }
+    }
+
+    /// Adds lines that are output without any changes.
+    fn verbatim(&mut self, text: &'s str) {
+        for line in text.lines() {
+            self.lines.push(Line::Comment(line.trim_end()));
+        }
+    }
+
+    fn word(&mut self, word: Word<'s>) {
+        self.words.push(word);
+        self.line_has_content = true;
+    }
+
+    fn end_line(&mut self) {
+        if !self.words.is_empty() {
+            self.lines.push(Line::Code {
+                words: std::mem::take(&mut self.words),
+                comment: self.comment.take(),
+            });
+        } else if !self.line_has_content {
+            self.lines.push(Line::Blank);
+        }
+        self.line_has_content = false;
+    }
+}
+
+/// A block, bracket, or unfinished expression that's open at some point in the source code.
+struct OpenBlock {
+    /// Whether this is an expression continued on the next line, eg. after a `=`, rather than a
+    /// block delimited by tokens. Continuations end together with the expression.
+    is_continuation: bool,
+    /// The indentation level of the line the block was opened on. The block's contents are
+    /// indented one level deeper, and its closing token is aligned with this line.
+    line_indent: usize,
+}
+
+struct Formatter<'o> {
+    options: &'o Options,
+    output: String,
+    blocks: Vec<OpenBlock>,
+    /// Whether the previous line ended with a long string literal, which may be continued by
+    /// long string literals on the following lines.
+    in_long_string: bool,
+}
+
+impl Formatter<'_> {
+    fn format_lines(&mut self, lines: Vec<Line<'_>>) {
+        let mut pending_blank = false;
+        for line in lines {
+            match line {
+                Line::Blank => pending_blank = !self.output.is_empty(),
+                Line::Comment(comment) => {
+                    self.write_pending_blank(&mut pending_blank);
+                    if std::mem::take(&mut self.in_long_string) {
+                        self.end_expression();
+                    }
+                    let indent = self.indent_level();
+                    self.write_line(indent, comment);
+                }
+                Line::Code { words, comment } => {
+                    self.write_pending_blank(&mut pending_blank);
+                    self.format_code_line(&words, comment);
+                }
+            }
+        }
+    }
+
+    fn write_pending_blank(&mut self, pending_blank: &mut bool) {
+        if *pending_blank {
+            self.output.push('\n');
+            *pending_blank = false;
+        }
+    }
+
+    fn indent_level(&self) -> usize {
+        self.blocks.last().map_or(0, |block| block.line_indent + 1)
+    }
+
+    fn write_line(&mut self, indent: usize, text: &str) {
+        for _ in 0..indent * self.options.indent_width {
+            self.output.push(' ');
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    /// Closes the innermost block, along with any expressions continued within it. Returns the
+    /// indentation level of the line the block was opened on.
+    fn close_block(&mut self) -> usize {
+        while let Some(block) = self.blocks.pop() {
+            if !block.is_continuation {
+                return block.line_indent;
+            }
+        }
+        0
+    }
+
+    /// Ends the expressions continued since the innermost block was opened.
+    fn end_expression(&mut self) {
+        while self
+            .blocks
+            .last()
+            .is_some_and(|block| block.is_continuation)
+        {
+            self.blocks.pop();
+        }
+    }
+
+    fn format_code_line(&mut self, words: &[Word<'_>], comment: Option<&str>) {
+        let starts_with_long_string = matches!(words[0].kind, TokenKind::LongString(_));
+        if std::mem::take(&mut self.in_long_string) && !starts_with_long_string {
+            self.end_expression();
+        }
+
+        // Closing tokens at the start of the line align the line with the one that opened
+        // the block.
+        let mut indent = self.indent_level();
+        let leading_closers = words
+            .iter()
+            .take_while(|word| closes_block(&word.kind))
+            .count();
+        for _ in 0..leading_closers {
+            indent = self.close_block();
+        }
+        for (i, word) in words.iter().enumerate() {
+            if i >= leading_closers && closes_block(&word.kind) {
+                self.close_block();
+            }
+            if opens_block(&word.kind) {
+                self.blocks.push(OpenBlock {
+                    is_continuation: false,
+                    line_indent: indent,
+                });
+            }
+        }
+        let last = &words[words.len() - 1].kind;
+        if expects_operand(last) {
+            // Operator chains like `a +\n b +\n c` are aligned, but each `=` starts a new
+            // expression that's indented further.
+            let continues_chain = self
+                .blocks
+                .last()
+                .is_some_and(|block| block.is_continuation);
+            if *last == TokenKind::Assign || !continues_chain {
+                self.blocks.push(OpenBlock {
+                    is_continuation: true,
+                    line_indent: indent,
+                });
+            }
+        } else if matches!(last, TokenKind::LongString(_)) {
+            self.in_long_string = true;
+        } else {
+            self.end_expression();
+        }
+
+        let mut lines = Vec::new();
+        self.wrap(words, indent, &mut lines);
+        if let Some(comment) = comment {
+            let (_, last) = lines.last_mut().unwrap();
+            last.push_str("  ");
+            last.push_str(comment);
+        }
+        for (indent, text) in lines {
+            self.write_line(indent, &text);
+        }
+    }
+
+    /// Renders the words into one or more lines, wrapping the outermost bracketed list if the
+    /// line doesn't fit within the maximum width.
+    fn wrap(&self, words: &[Word<'_>], indent: usize, lines: &mut Vec<(usize, String)>) {
+        let text = render(words);
+        let width = indent * self.options.indent_width + text.chars().count();
+        let group = if width > self.options.max_width {
+            outermost_group(words)
+        } else {
+            None
+        };
+        let Some((open, close)) = group else {
+            lines.push((indent, text));
+            return;
+        };
+
+        lines.push((indent, render(&words[..=open])));
+        let mut element_start = open + 1;
+        let mut depth = 0_usize;
+        for i in open + 1..close {
+            match words[i].kind {
+                TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => {
+                    depth = depth.saturating_sub(1)
+                }
+                TokenKind::Comma if depth == 0 => {
+                    self.wrap(&words[element_start..=i], indent + 1, lines);
+                    element_start = i + 1;
+                }
+                _ => (),
+            }
+        }
+        if element_start < close {
+            self.wrap(&words[element_start..close], indent + 1, lines);
+        }
+        self.wrap(&words[close..], indent, lines);
+    }
+}
+
+/// Returns whether the token opens a block or a bracket, increasing the indentation level.
+fn opens_block(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Do
+            | TokenKind::Else
+            | TokenKind::Try
+            | TokenKind::Catch
+            | TokenKind::Impl
+            | TokenKind::Enum
+            | TokenKind::Trait
+            | TokenKind::As
+            | TokenKind::LeftParen
+            | TokenKind::LeftBracket
+            | TokenKind::LeftBrace
+    )
+}
+
+/// Returns whether the token closes a block or a bracket. Note that `else` both closes the previous
+/// branch of an `if` and opens a new one, and so does `catch` with the body of a `try`.
+fn closes_block(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::End
+            | TokenKind::Elif
+            | TokenKind::Else
+            | TokenKind::Catch
+            | TokenKind::RightParen
+            | TokenKind::RightBracket
+            | TokenKind::RightBrace
+    )
+}
+
+/// Returns whether the token is a binary operator, which must be followed by another operand.
+fn expects_operand(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Assign
+            | TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Star
+            | TokenKind::Slash
+            | TokenKind::SlashSlash
+            | TokenKind::Percent
+            | TokenKind::And
+            | TokenKind::Or
+            | TokenKind::Equal
+            | TokenKind::NotEqual
+            | TokenKind::Less
+            | TokenKind::Greater
+            | TokenKind::LessEqual
+            | TokenKind::GreaterEqual
+            | TokenKind::Implements
+    )
+}
+
+/// Returns whether the token can be the last token of an operand.
+fn ends_operand(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Number(_)
+            | TokenKind::String(_)
+            | TokenKind::LongString(_)
+            | TokenKind::Identifier(_)
+            | TokenKind::Nil
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::Underscore
+            | TokenKind::End
+            | TokenKind::RightParen
+            | TokenKind::RightBracket
+            | TokenKind::RightBrace
+    )
+}
+
+/// Returns whether a space should be placed between two adjacent tokens.
+fn space_between(previous: &TokenKind, previous_is_unary: bool, next: &TokenKind) -> bool {
+    use TokenKind::*;
+    match (previous, next) {
+        (_, Comma | RightParen | RightBracket | Dot | QuestionDot | Colon) => false,
+        (LeftParen | LeftBracket | Dot | QuestionDot | At | Bang | Ellipsis, _) => false,
+        (Minus, _) if previous_is_unary => false,
+        // Cascades are written like method calls and slices like ranges, whereas `..` in records
+        // stands on its own.
+        (DotDot, RightBrace) => true,
+        (DotDot, _) => false,
+        (_, DotDot) => !ends_operand(previous),
+        (LeftBrace, RightBrace) => false,
+        (_, LeftParen | LeftBracket) => !ends_operand(previous),
+        _ => true,
+    }
+}
+
+/// Renders words into a single line, with normalized spacing.
+fn render(words: &[Word<'_>]) -> String {
+    let mut line = String::new();
+    let mut previous: Option<(&TokenKind, bool)> = None;
+    for word in words {
+        // A minus is unary if it's not preceded by an operand.
+        let is_unary =
+            word.kind == TokenKind::Minus && previous.is_none_or(|(kind, _)| !ends_operand(kind));
+        if let Some((previous, previous_is_unary)) = previous {
+            if space_between(previous, previous_is_unary, &word.kind) {
+                line.push(' ');
+            }
+        }
+        line.push_str(word.text);
+        previous = Some((&word.kind, is_unary));
+    }
+    line
+}
+
+/// Finds the first bracketed list that is not nested inside any other bracket, and is closed on
+/// the same line. Returns the indices of its opening and closing brackets.
+fn outermost_group(words: &[Word<'_>]) -> Option<(usize, usize)> {
+    let mut depth = 0_usize;
+    for (open, word) in words.iter().enumerate() {
+        match word.kind {
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                if depth == 0 {
+                    if let Some(close) = matching_bracket(words, open) {
+                        if close > open + 1 {
+                            return Some((open, close));
+                        }
+                    }
+                }
+                depth += 1;
+            }
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => {
+                depth = depth.saturating_sub(1);
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn matching_bracket(words: &[Word<'_>], open: usize) -> Option<usize> {
+    let mut depth = 0_usize;
+    for (i, word) in words.iter().enumerate().skip(open) {
+        match word.kind {
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
@@ -0,0 +1,213 @@
+use mica_fmt::Options;
+
+fn format(source: &str) -> String {
+    format_with(source, &Options::default())
+}
+
+fn format_with(source: &str, options: &Options) -> String {
+    let formatted = match mica_fmt::format("test.mi", source, options) {
+        Ok(formatted) => formatted,
+        Err(error) => panic!("formatting failed:\n{error:#}"),
+    };
+    let reformatted = mica_fmt::format("test.mi", &formatted, options).unwrap();
+    assert_eq!(formatted, reformatted, "formatting is not idempotent");
+    formatted
+}
+
+#[test]
+fn blocks_are_reindented_and_ends_aligned() {
+    assert_eq!(
+        format(
+            r#"
+func factorial(n) = do
+  let x = 1
+      while n > 1 do
+   x = x * n
+         n = n - 1
+     end
+  x
+    end
+
+if x do
+print(1)
+  elif y do
+print(2)
+      else
+print(3)
+    end
+"#
+        ),
+        r#"func factorial(n) = do
+    let x = 1
+    while n > 1 do
+        x = x * n
+        n = n - 1
+    end
+    x
+end
+
+if x do
+    print(1)
+elif y do
+    print(2)
+else
+    print(3)
+end
+"#
+    );
+}
+
+#[test]
+fn structs_and_traits_are_indented() {
+    assert_eq!(
+        format(
+            r#"
+trait Animal
+func speak()
+end
+struct Dog impl
+func new(name) constructor = do
+@name = name
+end
+as Animal
+func speak() = print("woof")
+end
+end
+"#
+        ),
+        r#"trait Animal
+    func speak()
+end
+struct Dog impl
+    func new(name) constructor = do
+        @name = name
+    end
+    as Animal
+        func speak() = print("woof")
+    end
+end
+"#
+    );
+}
+
+#[test]
+fn spacing_is_normalized() {
+    assert_eq!(
+        format("let x=-f( 1 ,[2,3] , -y )+{ a :1 }.a\nlet y = [ : ]\nlet f = func ( a ) = ! a\n"),
+        "let x = -f(1, [2, 3], -y) + { a: 1 }.a\nlet y = [:]\nlet f = func (a) = !a\n"
+    );
+}
+
+#[test]
+fn comments_and_blank_lines_are_preserved() {
+    assert_eq!(
+        format("\n\n# A comment.\nlet x = 1   # Trailing.\n\n\n\ndo\n# Inside.\n  x\nend   \n\n\n"),
+        "# A comment.\nlet x = 1  # Trailing.\n\ndo\n    # Inside.\n    x\nend\n"
+    );
+}
+
+#[test]
+fn lines_continuing_an_expression_are_indented() {
+    assert_eq!(
+        format("let x =\n1 +\n2\nlet y = x and\n  x\nlet f = func (y) =\nfunc (z) =\ny + z\n"),
+        "let x =\n    1 +\n    2\nlet y = x and\n    x\nlet f = func (y) =\n    func (z) =\n        y + z\n"
+    );
+}
+
+#[test]
+fn nested_openers_on_one_line_indent_once() {
+    assert_eq!(
+        format("list.push(func (x) = do\nx + 1\nend)\n"),
+        "list.push(func (x) = do\n    x + 1\nend)\n"
+    );
+}
+
+#[test]
+fn long_argument_lists_are_wrapped() {
+    let options = Options {
+        max_width: 30,
+        ..Options::default()
+    };
+    assert_eq!(
+        format_with(
+            "do\nlet result = compute(first_argument, [1, 2, 3], third) + 1\nend\n",
+            &options
+        ),
+        "do\n    let result = compute(\n        first_argument,\n        [1, 2, 3],\n        third\n    ) + 1\nend\n"
+    );
+}
+
+#[test]
+fn strings_are_kept_verbatim() {
+    assert_eq!(
+        format("let s =   \"a  #  b\"\nlet t =\n\\\\ long  \n  \\\\ string\n"),
+        "let s = \"a  #  b\"\nlet t =\n    \\\\ long  \n    \\\\ string\n"
+    );
+}
+
+#[test]
+fn code_that_does_not_parse_is_rejected() {
+    let error = mica_fmt::format("test.mi", "let x = (", &Options::default()).unwrap_err();
+    assert!(error.to_string().starts_with("test.mi:1:"));
+}
+
+#[test]
+fn cascades_are_spaced_like_method_calls() {
+    assert_eq!(
+        format("let b = Builder.new ..x( 1 ).. y\nlet { x, .. } = r\n"),
+        "let b = Builder.new..x(1)..y\nlet { x, .. } = r\n"
+    );
+}
+
+#[test]
+fn struct_patterns_are_spaced_like_records() {
+    assert_eq!(
+        format("let Point{x,y:(a,b)}=p\n"),
+        "let Point { x, y: (a, b) } = p\n"
+    );
+}
+
+#[test]
+fn dynamic_method_calls_are_spaced_like_method_calls() {
+    assert_eq!(
+        format("let r = c . ( op ) ( 5, 3 )\nprint(c.(\"zero\"))\n"),
+        "let r = c.(op)(5, 3)\nprint(c.(\"zero\"))\n"
+    );
+}
+
+#[test]
+fn floor_division_is_spaced_like_other_operators() {
+    assert_eq!(
+        format("let q = a//b\nlet r =\n7 //\n2\n"),
+        "let q = a // b\nlet r =\n    7 //\n    2\n"
+    );
+}
+
+#[test]
+fn rest_parameters_are_not_spaced() {
+    assert_eq!(
+        format("func f(a, ... rest) = rest\n"),
+        "func f(a, ...rest) = rest\n"
+    );
+}
+
+#[test]
+fn nil_safe_calls_are_not_spaced() {
+    assert_eq!(format("a ?. b ?. c(1)\n"), "a?.b?.c(1)\n");
+}
+
+#[test]
+fn subscripts_and_slices_are_not_spaced() {
+    assert_eq!(
+        format("xs [ 0 ] = ys [ 1 .. n ] [ .. 2 ]\n"),
+        "xs[0] = ys[1..n][..2]\n"
+    );
+}
+
+#[test]
+fn shebang_and_front_matter_are_kept_verbatim() {
+    assert_eq!(
+        format("#!/usr/bin/env mica\n---\nname   =  \"x\"\n  # Indented.\n---\n\nprint( 1 )\n"),
+        "#!/usr/bin/env mica\n---\nname   =  \"x\"\n  # Indented.\n---\n\nprint(1)\n"
+    );
+}
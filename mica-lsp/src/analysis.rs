@@ -0,0 +1,534 @@
+//! Editor features, implemented on top of documents' syntax trees and engine introspection.
+
+use std::{collections::BTreeMap, fmt::Write, ops::Range, rc::Rc};
+
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, Hover, HoverContents,
+    MarkupContent, MarkupKind, NumberOrString,
+};
+use mica::{
+    ll::{
+        cst::{SyntaxElement, SyntaxNode, SyntaxNodeKind, SyntaxTokenKind},
+        lexer::TokenKind,
+    },
+    Engine, GlobalKind, MethodInfo, TypeInfo,
+};
+
+use crate::Document;
+
+/// The names of the types built into the language, whose methods are offered when the type of a
+/// method call's receiver is not known.
+const BUILTIN_TYPES: &[&str] = &[
+    "Nil", "Boolean", "Number", "String", "Function", "List", "Dict",
+];
+
+const KEYWORDS: &[&str] = &[
+    "and",
+    "as",
+    "break",
+    "catch",
+    "constructor",
+    "continue",
+    "defer",
+    "do",
+    "elif",
+    "else",
+    "end",
+    "enum",
+    "false",
+    "for",
+    "func",
+    "if",
+    "impl",
+    "implements",
+    "import",
+    "in",
+    "let",
+    "nil",
+    "or",
+    "priv",
+    "pub",
+    "raise",
+    "return",
+    "static",
+    "struct",
+    "trait",
+    "true",
+    "try",
+    "while",
+];
+
+/// Compiles the document and returns the errors and warnings reported by the compiler.
+///
+/// Compiling a document declares its globals in the engine, so a fresh engine should be used for
+/// every compilation.
+pub fn diagnostics(engine: &mut Engine, module_name: &str, document: &Document) -> Vec<Diagnostic> {
+    match engine.compile(module_name, document.source()) {
+        Ok(script) => script
+            .warnings()
+            .iter()
+            .map(|warning| {
+                // Warnings only point to where the offending code begins, so they're extended
+                // to cover the token there.
+                let byte = warning.location.byte;
+                let range = match document.tree().token_at(byte) {
+                    Some((range, _)) => range,
+                    None => byte..byte,
+                };
+                let code = warning.kind.lint().name().to_owned();
+                diagnostic(
+                    document,
+                    range,
+                    &mica::Diagnostic::from(warning),
+                    Some(code),
+                )
+            })
+            .collect(),
+        Err(error) => {
+            let diagnostics: Vec<_> = error
+                .diagnostics()
+                .iter()
+                .map(|problem| {
+                    let range = problem.span.start.byte..problem.span.end.byte;
+                    diagnostic(document, range, problem, None)
+                })
+                .collect();
+            if diagnostics.is_empty() {
+                // Errors that don't come from the code, such as a failure to import a module,
+                // are reported at the start of the document.
+                vec![Diagnostic {
+                    range: document.range(0..0),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some(String::from("mica")),
+                    message: error.to_string(),
+                    ..Default::default()
+                }]
+            } else {
+                diagnostics
+            }
+        }
+    }
+}
+
+fn diagnostic(
+    document: &Document,
+    range: Range<usize>,
+    problem: &mica::Diagnostic,
+    code: Option<String>,
+) -> Diagnostic {
+    let severity = match problem.severity {
+        mica::DiagnosticSeverity::Error => DiagnosticSeverity::ERROR,
+        mica::DiagnosticSeverity::Warning => DiagnosticSeverity::WARNING,
+    };
+    let mut message = problem.message.clone();
+    if let Some(hint) = problem.hint {
+        let _ = write!(message, "\nhint: {hint}");
+    }
+    Diagnostic {
+        range: document.range(range),
+        severity: Some(severity),
+        code: code.map(NumberOrString::String),
+        source: Some(String::from("mica")),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Returns the byte range of the name in the declaration of the global variable or function
+/// under the cursor, if it's declared in the document.
+pub fn definition(document: &Document, offset: usize) -> Option<Range<usize>> {
+    let tokens = significant_tokens(document);
+    let index = identifier_at(&tokens, offset)?;
+    if is_method_name(&tokens, index) {
+        return None;
+    }
+    let TokenKind::Identifier(name) = &tokens[index].0 else {
+        unreachable!()
+    };
+    declarations(document)
+        .into_iter()
+        .find(|declaration| &declaration.name == name)
+        .map(|declaration| declaration.name_range)
+}
+
+/// Returns information about the identifier under the cursor: the signature of a function, the
+/// type of a global, or the types and arities of the methods a method call could refer to.
+pub fn hover(engine: &Engine, document: &Document, offset: usize) -> Option<Hover> {
+    let tokens = significant_tokens(document);
+    let index = identifier_at(&tokens, offset)?;
+    let (TokenKind::Identifier(name), range) = &tokens[index] else {
+        unreachable!()
+    };
+
+    let text = if is_method_name(&tokens, index) {
+        let mut text = String::new();
+        for (type_name, methods) in receiver_methods(engine, &tokens, index - 1) {
+            for method in methods.iter().filter(|method| &method.name == name) {
+                let _ = writeln!(text, "{}", render_method(&type_name, method));
+            }
+        }
+        if text.is_empty() {
+            return None;
+        }
+        format!("```\n{text}```")
+    } else if let Some(declaration) = declarations(document)
+        .into_iter()
+        .find(|declaration| &declaration.name == name)
+    {
+        let rendered = match &declaration.kind {
+            DeclarationKind::Function { parameters } => {
+                format!("func {name}({})", parameters.join(", "))
+            }
+            DeclarationKind::Variable => format!("let {name}"),
+            DeclarationKind::Struct => format!("struct {name}"),
+            DeclarationKind::Enum => format!("enum {name}"),
+            DeclarationKind::Trait => format!("trait {name}"),
+        };
+        let mut text = format!("```mica\n{rendered}\n```");
+        if !declaration.docs.is_empty() {
+            let _ = write!(text, "\n\n{}", declaration.docs);
+        }
+        text
+    } else {
+        let global = engine.introspect().global(name)?;
+        let rendered = match &global.kind {
+            GlobalKind::Function(function) => {
+                match (&function.parameter_names, function.parameter_count) {
+                    (Some(names), _) => format!("{name}({})", names.join(", ")),
+                    (None, Some(count)) => format!("{name}/{count}"),
+                    (None, None) => format!("{name}(...)"),
+                }
+            }
+            GlobalKind::Type(info) => {
+                let mut text = format!("type {}", info.name);
+                for method in &info.type_methods {
+                    let _ = write!(text, "\n{}", render_method(&info.name, method));
+                }
+                text
+            }
+            GlobalKind::Value { type_name } => format!("{name}: {type_name}"),
+        };
+        format!("```\n{rendered}\n```")
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: text,
+        }),
+        range: Some(document.range(range.clone())),
+    })
+}
+
+/// Returns completions for the cursor position. After a `.`, these are the methods of the
+/// receiver; otherwise they're the globals and keywords.
+pub fn completions(engine: &Engine, document: &Document, offset: usize) -> Vec<CompletionItem> {
+    // Nothing is completed inside of comments and strings.
+    if let Some((range, token)) = document.tree().token_at(offset.saturating_sub(1)) {
+        match token.kind {
+            // Comments and long strings extend up to the end of the line, so a cursor placed
+            // right after them is still inside of them.
+            SyntaxTokenKind::Token(TokenKind::Comment | TokenKind::LongString(_)) => {
+                return Vec::new()
+            }
+            SyntaxTokenKind::Token(TokenKind::String(_)) if offset < range.end => {
+                return Vec::new()
+            }
+            _ => (),
+        }
+    }
+
+    let tokens = significant_tokens(document);
+    let before_cursor = tokens.partition_point(|(_, range)| range.end <= offset);
+    let dot = match tokens[..before_cursor] {
+        [.., (TokenKind::Dot | TokenKind::QuestionDot | TokenKind::DotDot, _)] => {
+            Some(before_cursor - 1)
+        }
+        [.., (TokenKind::Dot | TokenKind::QuestionDot | TokenKind::DotDot, _), (TokenKind::Identifier(_), ref range)]
+            if range.end == offset =>
+        {
+            Some(before_cursor - 2)
+        }
+        _ => None,
+    };
+
+    if let Some(dot) = dot {
+        // Methods with the same name are merged into a single item, regardless of their arity
+        // and the type they belong to.
+        let mut signatures: BTreeMap<Rc<str>, Vec<String>> = BTreeMap::new();
+        for (type_name, methods) in receiver_methods(engine, &tokens, dot) {
+            for method in methods {
+                signatures
+                    .entry(Rc::clone(&method.name))
+                    .or_default()
+                    .push(render_method(&type_name, &method));
+            }
+        }
+        return signatures
+            .into_iter()
+            .map(|(name, signatures)| CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::METHOD),
+                detail: Some(signatures.join(", ")),
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    let mut items: BTreeMap<String, CompletionItem> = BTreeMap::new();
+    for global in engine.introspect().globals() {
+        let kind = match global.kind {
+            GlobalKind::Function(_) => CompletionItemKind::FUNCTION,
+            GlobalKind::Type(_) => CompletionItemKind::STRUCT,
+            GlobalKind::Value { .. } => CompletionItemKind::VARIABLE,
+        };
+        items.insert(global.name.to_string(), completion(&global.name, kind));
+    }
+    for declaration in declarations(document) {
+        let kind = match declaration.kind {
+            DeclarationKind::Function { .. } => CompletionItemKind::FUNCTION,
+            DeclarationKind::Variable => CompletionItemKind::VARIABLE,
+            DeclarationKind::Struct => CompletionItemKind::STRUCT,
+            DeclarationKind::Enum => CompletionItemKind::ENUM,
+            DeclarationKind::Trait => CompletionItemKind::INTERFACE,
+        };
+        items.insert(
+            declaration.name.to_string(),
+            completion(&declaration.name, kind),
+        );
+    }
+    for keyword in KEYWORDS {
+        items.insert(
+            keyword.to_string(),
+            completion(keyword, CompletionItemKind::KEYWORD),
+        );
+    }
+    items.into_values().collect()
+}
+
+fn completion(label: &str, kind: CompletionItemKind) -> CompletionItem {
+    CompletionItem {
+        label: label.to_owned(),
+        kind: Some(kind),
+        ..Default::default()
+    }
+}
+
+/// Renders a method the same way the VM does in error messages, prefixed with the type name.
+fn render_method(type_name: &str, method: &MethodInfo) -> String {
+    let mut rendered = format!("{type_name}.{}/{}", method.name, method.parameter_count);
+    if let Some(trait_name) = &method.trait_name {
+        let _ = write!(rendered, " (as {trait_name})");
+    }
+    rendered
+}
+
+/// Returns the tokens meaningful to the parser, along with their byte ranges.
+fn significant_tokens(document: &Document) -> Vec<(TokenKind, Range<usize>)> {
+    document
+        .tree()
+        .tokens()
+        .filter_map(|(range, token)| match &token.kind {
+            SyntaxTokenKind::Token(TokenKind::Comment | TokenKind::Error(_)) => None,
+            SyntaxTokenKind::Token(kind) => Some((kind.clone(), range)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the index of the identifier token under the cursor. A cursor placed right after an
+/// identifier is also considered to be on it.
+fn identifier_at(tokens: &[(TokenKind, Range<usize>)], offset: usize) -> Option<usize> {
+    tokens.iter().position(|(kind, range)| {
+        matches!(kind, TokenKind::Identifier(_)) && range.start <= offset && offset <= range.end
+    })
+}
+
+/// Returns whether the identifier at the given index is the name of a method being called.
+fn is_method_name(tokens: &[(TokenKind, Range<usize>)], index: usize) -> bool {
+    index > 0
+        && matches!(
+            tokens[index - 1].0,
+            TokenKind::Dot | TokenKind::QuestionDot | TokenKind::DotDot
+        )
+}
+
+/// Returns the methods that can be called on the receiver of the `.` at the given index, grouped
+/// by the name of the type they belong to. If the receiver's type can't be inferred, the methods
+/// of all known types are returned.
+fn receiver_methods(
+    engine: &Engine,
+    tokens: &[(TokenKind, Range<usize>)],
+    dot: usize,
+) -> Vec<(Rc<str>, Vec<MethodInfo>)> {
+    let introspection = engine.introspect();
+    let instance_of = |type_name: &str| {
+        introspection
+            .builtin_type(type_name)
+            .map(|info| vec![(info.name, info.instance_methods)])
+            .unwrap_or_default()
+    };
+    let receiver = dot.checked_sub(1).map(|index| &tokens[index].0);
+    match receiver {
+        Some(TokenKind::Nil) => instance_of("Nil"),
+        Some(TokenKind::True | TokenKind::False) => instance_of("Boolean"),
+        Some(TokenKind::Number(_)) => instance_of("Number"),
+        Some(TokenKind::String(_) | TokenKind::LongString(_)) => instance_of("String"),
+        Some(TokenKind::RightBracket) => {
+            if is_dict_literal(&tokens[..dot]) {
+                instance_of("Dict")
+            } else {
+                instance_of("List")
+            }
+        }
+        Some(TokenKind::Identifier(name)) => match introspection.global(name) {
+            Some(global) => match global.kind {
+                GlobalKind::Type(info) => vec![(info.name, info.type_methods)],
+                _ => all_instance_methods(engine),
+            },
+            None => all_instance_methods(engine),
+        },
+        _ => all_instance_methods(engine),
+    }
+}
+
+/// Returns whether the tokens end with a dict literal, rather than a list literal.
+fn is_dict_literal(tokens: &[(TokenKind, Range<usize>)]) -> bool {
+    let mut depth = 0_usize;
+    for (kind, _) in tokens.iter().rev() {
+        match kind {
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace => depth += 1,
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return false;
+                }
+            }
+            TokenKind::Colon if depth == 1 => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Returns the instance methods of the builtin types and the types registered in the engine.
+fn all_instance_methods(engine: &Engine) -> Vec<(Rc<str>, Vec<MethodInfo>)> {
+    let introspection = engine.introspect();
+    let builtin = BUILTIN_TYPES
+        .iter()
+        .filter_map(|name| introspection.builtin_type(name));
+    let user = introspection
+        .globals()
+        .into_iter()
+        .filter_map(|global| match global.kind {
+            GlobalKind::Type(info) if !BUILTIN_TYPES.contains(&&*info.name) => Some(info),
+            _ => None,
+        });
+    builtin
+        .chain(user)
+        .map(|info: TypeInfo| (info.name, info.instance_methods))
+        .collect()
+}
+
+/// A global declared in a document.
+struct Declaration {
+    name: Rc<str>,
+    kind: DeclarationKind,
+    name_range: Range<usize>,
+    /// The declaration's doc comment, with the leading `##` stripped.
+    docs: String,
+}
+
+enum DeclarationKind {
+    Function { parameters: Vec<Rc<str>> },
+    Variable,
+    Struct,
+    Enum,
+    Trait,
+}
+
+/// Returns the globals declared by the top-level items of the document.
+fn declarations(document: &Document) -> Vec<Declaration> {
+    let mut declarations = Vec::new();
+    let mut offset = 0;
+    for child in document.tree().root().children() {
+        if let SyntaxElement::Node(item) = child {
+            declarations.extend(item_declaration(document.source(), item, offset));
+        }
+        offset += child.len();
+    }
+    declarations
+}
+
+fn item_declaration(source: &str, item: &SyntaxNode, start: usize) -> Option<Declaration> {
+    let mut offset = start;
+    let mut head = Vec::new();
+    let mut parameters = None;
+    for child in item.children() {
+        match child {
+            SyntaxElement::Token(token) if head.len() < 2 && !token.kind.is_trivia() => {
+                let SyntaxTokenKind::Token(kind) = &token.kind else {
+                    unreachable!()
+                };
+                head.push((kind, offset..offset + token.len));
+            }
+            SyntaxElement::Node(node) if parameters.is_none() => {
+                parameters = Some(group_identifiers(node));
+            }
+            _ => (),
+        }
+        offset += child.len();
+    }
+
+    let [(keyword, keyword_range), (TokenKind::Identifier(name), name_range)] = &head[..] else {
+        return None;
+    };
+    let kind = match keyword {
+        TokenKind::Func => DeclarationKind::Function {
+            parameters: parameters.unwrap_or_default(),
+        },
+        TokenKind::Let | TokenKind::Const | TokenKind::Import => DeclarationKind::Variable,
+        TokenKind::Struct => DeclarationKind::Struct,
+        TokenKind::Enum => DeclarationKind::Enum,
+        TokenKind::Trait => DeclarationKind::Trait,
+        _ => return None,
+    };
+    Some(Declaration {
+        name: Rc::clone(name),
+        kind,
+        name_range: name_range.clone(),
+        docs: doc_comment(source, keyword_range.start),
+    })
+}
+
+/// Returns the doc comment written on the lines right above the line containing the given offset.
+fn doc_comment(source: &str, offset: usize) -> String {
+    let line_start = source[..offset]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let mut lines: Vec<_> = source[..line_start]
+        .lines()
+        .rev()
+        .map_while(|line| line.trim_start().strip_prefix("##"))
+        .map(|line| line.strip_prefix(' ').unwrap_or(line))
+        .collect();
+    lines.reverse();
+    lines.join("\n")
+}
+
+/// Returns the identifiers directly inside of a group, such as a function's parameters.
+fn group_identifiers(node: &SyntaxNode) -> Vec<Rc<str>> {
+    if node.kind() != SyntaxNodeKind::Group {
+        return Vec::new();
+    }
+    node.children()
+        .iter()
+        .filter_map(|child| match child {
+            SyntaxElement::Token(token) => match &token.kind {
+                SyntaxTokenKind::Token(TokenKind::Identifier(name)) => Some(Rc::clone(name)),
+                _ => None,
+            },
+            SyntaxElement::Node(_) => None,
+        })
+        .collect()
+}
@@ -0,0 +1,95 @@
+//! Open documents and conversion between byte offsets and LSP positions.
+
+use std::ops::Range;
+
+use lsp_types::{Position, TextDocumentContentChangeEvent};
+use mica::ll::cst::SyntaxTree;
+
+/// A document opened in the editor, kept in sync with the editor's copy through incremental
+/// edits.
+#[derive(Debug, Clone)]
+pub struct Document {
+    tree: SyntaxTree,
+    /// The byte offsets at which each line starts.
+    line_starts: Vec<usize>,
+}
+
+impl Document {
+    /// Creates a document from its full source code.
+    pub fn new(source: impl Into<String>) -> Self {
+        let tree = SyntaxTree::parse(source);
+        let line_starts = line_starts(tree.source());
+        Self { tree, line_starts }
+    }
+
+    /// Returns the source code of the document.
+    pub fn source(&self) -> &str {
+        self.tree.source()
+    }
+
+    /// Returns the document's syntax tree.
+    pub fn tree(&self) -> &SyntaxTree {
+        &self.tree
+    }
+
+    /// Replaces the given byte range of the document with new text.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) {
+        self.tree.edit(range, replacement);
+        self.line_starts = line_starts(self.tree.source());
+    }
+
+    /// Applies a change sent by the editor. Changes without a range replace the whole document.
+    pub fn apply_change(&mut self, change: &TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let range = self.offset(range.start)..self.offset(range.end);
+                self.edit(range, &change.text);
+            }
+            None => *self = Self::new(change.text.clone()),
+        }
+    }
+
+    /// Converts an LSP position to a byte offset. Positions past the end of a line are clamped to
+    /// the end of that line, and positions past the end of the document to the end of the
+    /// document.
+    pub fn offset(&self, position: Position) -> usize {
+        let source = self.source();
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return source.len();
+        };
+        let line = &source[line_start..];
+        let line = &line[..line.find('\n').unwrap_or(line.len())];
+        // LSP positions count UTF-16 code units by default.
+        let mut utf16_column = 0;
+        for (byte, c) in line.char_indices() {
+            if utf16_column >= position.character as usize {
+                return line_start + byte;
+            }
+            utf16_column += c.len_utf16();
+        }
+        line_start + line.len()
+    }
+
+    /// Converts a byte offset to an LSP position.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.source().len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let character: usize = self.source()[line_start..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        Position::new(line as u32, character as u32)
+    }
+
+    /// Converts a byte range to an LSP range.
+    pub fn range(&self, range: Range<usize>) -> lsp_types::Range {
+        lsp_types::Range::new(self.position(range.start), self.position(range.end))
+    }
+}
+
+fn line_starts(source: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+        .collect()
+}
@@ -0,0 +1,25 @@
+//! A language server for Mica, providing diagnostics, hover information, go-to-definition, and
+//! completion to editors that support the [Language Server Protocol][lsp].
+//!
+//! Mica is meant to be embedded, and the globals and types available to scripts depend on the
+//! host application. Thus, rather than being a standalone program, the language server is a
+//! library: the host provides a function that creates an [`Engine`][mica::Engine] set up the same
+//! way as the one it runs scripts in, and the language server uses it to compile documents and to
+//! look up functions and methods.
+//!
+//! ```no_run
+//! use lsp_server::Connection;
+//!
+//! let (connection, io_threads) = Connection::stdio();
+//! mica_lsp::run(connection, mica::Engine::new).unwrap();
+//! io_threads.join().unwrap();
+//! ```
+//!
+//! [lsp]: https://microsoft.github.io/language-server-protocol/
+
+pub mod analysis;
+mod document;
+mod server;
+
+pub use document::*;
+pub use server::*;
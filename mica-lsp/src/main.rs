@@ -0,0 +1,16 @@
+//! A standalone language server for scripts run with the `mica` command line interface.
+//!
+//! Scripts are analyzed with the same core library the command line interface provides. Programs
+//! that embed Mica and register their own globals should run the language server with their own
+//! engine setup instead; see [`mica_lsp::run`].
+
+use std::error::Error;
+
+use lsp_server::Connection;
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (connection, io_threads) = Connection::stdio();
+    mica_lsp::run(connection, mica::Engine::new)?;
+    io_threads.join()?;
+    Ok(())
+}
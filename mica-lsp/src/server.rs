@@ -0,0 +1,210 @@
+//! The language server's main loop, translating LSP messages into calls to the analysis
+//! functions.
+
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{Completion, GotoDefinition, HoverRequest, Request as RequestTrait},
+    CompletionOptions, CompletionResponse, GotoDefinitionResponse, HoverProviderCapability,
+    Location, OneOf, PublishDiagnosticsParams, ServerCapabilities, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use mica::Engine;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{analysis, Document};
+
+/// Returns the capabilities of the language server, to be sent to the client during
+/// initialization.
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![String::from(".")]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Initializes the connection and serves requests until the client asks the server to shut down.
+///
+/// `new_engine` is used to create the engines documents are analyzed with. It should set up the
+/// same globals and types that are available to scripts in the host application, such that they
+/// can be completed and don't produce errors.
+pub fn run(
+    connection: Connection,
+    new_engine: impl Fn() -> Engine + 'static,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    Server {
+        engine: new_engine(),
+        new_engine: Box::new(new_engine),
+        documents: HashMap::new(),
+        connection: &connection,
+    }
+    .main_loop()
+}
+
+struct Server<'c> {
+    connection: &'c Connection,
+    new_engine: Box<dyn Fn() -> Engine>,
+    /// The engine used for looking up globals and types. Documents are never compiled in it, so
+    /// it only contains what was registered by `new_engine`.
+    engine: Engine,
+    documents: HashMap<Url, Document>,
+}
+
+impl Server<'_> {
+    fn main_loop(mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for message in &self.connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => {
+                    self.handle_notification(notification)?;
+                }
+                Message::Response(_) => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            HoverRequest::METHOD => self.respond::<HoverRequest>(request, |server, params| {
+                let (document, offset) = server.locate(&params.text_document_position_params)?;
+                analysis::hover(&server.engine, document, offset)
+            }),
+            GotoDefinition::METHOD => self.respond::<GotoDefinition>(request, |server, params| {
+                let position = &params.text_document_position_params;
+                let (document, offset) = server.locate(position)?;
+                let range = analysis::definition(document, offset)?;
+                Some(GotoDefinitionResponse::Scalar(Location {
+                    uri: position.text_document.uri.clone(),
+                    range: document.range(range),
+                }))
+            }),
+            Completion::METHOD => self.respond::<Completion>(request, |server, params| {
+                let (document, offset) = server.locate(&params.text_document_position)?;
+                Some(CompletionResponse::Array(analysis::completions(
+                    &server.engine,
+                    document,
+                    offset,
+                )))
+            }),
+            method => Response::new_err(
+                request.id.clone(),
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request: {method}"),
+            ),
+        }
+    }
+
+    /// Responds to a request using the given handler. Requests with malformed parameters are
+    /// responded to with an error.
+    fn respond<R>(
+        &self,
+        request: Request,
+        handler: impl FnOnce(&Self, R::Params) -> R::Result,
+    ) -> Response
+    where
+        R: RequestTrait,
+        R::Params: DeserializeOwned,
+        R::Result: Serialize,
+    {
+        let id = request.id.clone();
+        match request.extract::<R::Params>(R::METHOD) {
+            Ok((_, params)) => Response::new_ok(id, handler(self, params)),
+            Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error.to_string()),
+        }
+    }
+
+    /// Returns the document and the byte offset a text document position points to.
+    fn locate(&self, position: &TextDocumentPositionParams) -> Option<(&Document, usize)> {
+        let document = self.documents.get(&position.text_document.uri)?;
+        Some((document, document.offset(position.position)))
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: Notification,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = extract::<DidOpenTextDocument>(notification)?;
+                let document = params.text_document;
+                self.documents
+                    .insert(document.uri.clone(), Document::new(document.text));
+                self.publish_diagnostics(document.uri, Some(document.version))?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = extract::<DidChangeTextDocument>(notification)?;
+                let uri = params.text_document.uri;
+                if let Some(document) = self.documents.get_mut(&uri) {
+                    for change in &params.content_changes {
+                        document.apply_change(change);
+                    }
+                    self.publish_diagnostics(uri, Some(params.text_document.version))?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = extract::<DidCloseTextDocument>(notification)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.send_diagnostics(PublishDiagnosticsParams::new(uri, Vec::new(), None))?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn publish_diagnostics(
+        &self,
+        uri: Url,
+        version: Option<i32>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(document) = self.documents.get(&uri) else {
+            return Ok(());
+        };
+        let module_name = match uri.to_file_path() {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(()) => uri.to_string(),
+        };
+        let diagnostics = analysis::diagnostics(&mut (self.new_engine)(), &module_name, document);
+        self.send_diagnostics(PublishDiagnosticsParams::new(uri, diagnostics, version))
+    }
+
+    fn send_diagnostics(
+        &self,
+        params: PublishDiagnosticsParams,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_owned(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(notification))?;
+        Ok(())
+    }
+}
+
+fn extract<N>(notification: Notification) -> Result<N::Params, Box<dyn Error + Send + Sync>>
+where
+    N: NotificationTrait,
+    N::Params: DeserializeOwned,
+{
+    Ok(notification.extract::<N::Params>(N::METHOD)?)
+}
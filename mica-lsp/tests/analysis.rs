@@ -0,0 +1,186 @@
+use lsp_types::{
+    CompletionItem, DiagnosticSeverity, HoverContents, NumberOrString, Position,
+    TextDocumentContentChangeEvent,
+};
+use mica::Engine;
+use mica_lsp::{analysis, Document};
+
+/// Creates a document from source code containing a `|` marking the cursor, and returns it along
+/// with the cursor's offset.
+fn document_with_cursor(source: &str) -> (Document, usize) {
+    let offset = source.find('|').expect("the source must contain a cursor");
+    (Document::new(source.replacen('|', "", 1)), offset)
+}
+
+fn hover(engine: &Engine, source: &str) -> Option<String> {
+    let (document, offset) = document_with_cursor(source);
+    let hover = analysis::hover(engine, &document, offset)?;
+    match hover.contents {
+        HoverContents::Markup(markup) => Some(markup.value),
+        _ => unreachable!(),
+    }
+}
+
+fn completion_labels(engine: &Engine, source: &str) -> Vec<String> {
+    let (document, offset) = document_with_cursor(source);
+    analysis::completions(engine, &document, offset)
+        .into_iter()
+        .map(|item: CompletionItem| item.label)
+        .collect()
+}
+
+#[test]
+fn positions_count_utf16_code_units() {
+    let document = Document::new("let a = \"źdźbło\"\nlet 🦀 = 1\n");
+    let crab = document.source().find('🦀').unwrap();
+    assert_eq!(document.position(crab), Position::new(1, 4));
+    assert_eq!(
+        document.position(crab + '🦀'.len_utf8()),
+        Position::new(1, 6)
+    );
+    assert_eq!(document.offset(Position::new(1, 6)), crab + '🦀'.len_utf8());
+    // Positions past the end of a line are clamped to it.
+    assert_eq!(document.offset(Position::new(0, 100)), 19);
+    assert_eq!(
+        document.offset(Position::new(5, 0)),
+        document.source().len()
+    );
+}
+
+#[test]
+fn incremental_changes_are_applied() {
+    let mut document = Document::new("let x = 1\nprint(x)\n");
+    document.apply_change(&TextDocumentContentChangeEvent {
+        range: Some(lsp_types::Range::new(
+            Position::new(1, 6),
+            Position::new(1, 7),
+        )),
+        range_length: None,
+        text: String::from("x + 2"),
+    });
+    assert_eq!(document.source(), "let x = 1\nprint(x + 2)\n");
+    document.apply_change(&TextDocumentContentChangeEvent {
+        range: None,
+        range_length: None,
+        text: String::from("nil"),
+    });
+    assert_eq!(document.source(), "nil");
+}
+
+#[test]
+fn diagnostics_include_errors_and_warnings() {
+    let document = Document::new("func f() = do\n    let unused = 1\n    nil\nend\n");
+    let diagnostics = analysis::diagnostics(&mut Engine::new(), "test.mi", &document);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    assert_eq!(
+        diagnostics[0].range,
+        lsp_types::Range::new(Position::new(1, 8), Position::new(1, 14))
+    );
+    assert_eq!(
+        diagnostics[0].code,
+        Some(NumberOrString::String(String::from("unused_variable")))
+    );
+
+    let document = Document::new("let x = 1\nlet y = (x +\n");
+    let diagnostics = analysis::diagnostics(&mut Engine::new(), "test.mi", &document);
+    assert!(!diagnostics.is_empty());
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::ERROR)));
+}
+
+#[test]
+fn error_diagnostics_include_hints() {
+    let document = Document::new("print(undeclared)\n");
+    let diagnostics = analysis::diagnostics(&mut Engine::new(), "test.mi", &document);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].range,
+        lsp_types::Range::new(Position::new(0, 6), Position::new(0, 16))
+    );
+    assert!(
+        diagnostics[0].message.contains("\nhint: "),
+        "{}",
+        diagnostics[0].message
+    );
+}
+
+#[test]
+fn definitions_of_globals_are_found() {
+    let source = "func add(a, b) = a + b\nlet total = 0\nprint(ad|d(total, 1))\n";
+    let (document, offset) = document_with_cursor(source);
+    let range = analysis::definition(&document, offset).unwrap();
+    assert_eq!(&document.source()[range.clone()], "add");
+    assert_eq!(range.start, 5);
+
+    let (document, offset) = document_with_cursor("let total = 0\nprint(total|)\n");
+    let range = analysis::definition(&document, offset).unwrap();
+    assert_eq!(range, 4..9);
+
+    // Functions that are not declared in the document have no definition.
+    let (document, offset) = document_with_cursor("pri|nt(1)");
+    assert!(analysis::definition(&document, offset).is_none());
+}
+
+#[test]
+fn hover_shows_signatures() {
+    let mut engine = Engine::new();
+    engine
+        .add_function("clamp", |x: f64, _min: f64, _max: f64| x)
+        .unwrap();
+
+    let declared = hover(&engine, "func add(a, b) = a + b\nad|d(1, 2)\n").unwrap();
+    assert!(declared.contains("func add(a, b)"), "{declared}");
+
+    let documented = hover(
+        &engine,
+        "# Not documentation.\n## Adds two numbers.\n## Both must be numbers.\nfunc add(a, b) = a + b\nad|d(1, 2)\n",
+    )
+    .unwrap();
+    assert!(
+        documented.ends_with("```\n\nAdds two numbers.\nBoth must be numbers."),
+        "{documented}"
+    );
+
+    let builtin = hover(&engine, "cla|mp(1, 2, 3)").unwrap();
+    assert!(builtin.contains("clamp/3"), "{builtin}");
+
+    let method = hover(&engine, "\"abc\".ca|t(\"def\")").unwrap();
+    assert!(method.contains("String.cat/1"), "{method}");
+    assert!(!method.contains("List."), "{method}");
+
+    assert!(hover(&engine, "un|known").is_none());
+}
+
+#[test]
+fn methods_are_completed_after_dots() {
+    let engine = Engine::new();
+
+    let string_methods = completion_labels(&engine, "\"abc\".|");
+    assert!(string_methods.contains(&String::from("cat")));
+    assert!(!string_methods.contains(&String::from("push")));
+
+    let list_methods = completion_labels(&engine, "[1, 2].pu|");
+    assert!(list_methods.contains(&String::from("push")));
+
+    let dict_methods = completion_labels(&engine, "[1: 2].|");
+    assert!(dict_methods.contains(&String::from("insert")));
+
+    // When the receiver's type is unknown, methods of all types are offered.
+    let any_methods = completion_labels(&engine, "x.|");
+    assert!(any_methods.contains(&String::from("cat")));
+    assert!(any_methods.contains(&String::from("push")));
+}
+
+#[test]
+fn globals_are_completed() {
+    let engine = Engine::new();
+    let labels = completion_labels(&engine, "func double(x) = x * 2\n|");
+    for expected in ["double", "print", "String", "while"] {
+        assert!(labels.contains(&String::from(expected)), "{expected}");
+    }
+
+    assert!(completion_labels(&engine, "# a comment |").is_empty());
+    assert!(completion_labels(&engine, "\"in a |string\"").is_empty());
+}
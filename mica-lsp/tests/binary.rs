@@ -0,0 +1,84 @@
+#![cfg(feature = "binary")]
+
+use std::{
+    io::BufReader,
+    process::{Command, Stdio},
+};
+
+use lsp_server::{Message, Notification, Request, RequestId};
+use lsp_types::{
+    notification::{
+        DidOpenTextDocument, Exit, Initialized, Notification as NotificationTrait,
+        PublishDiagnostics,
+    },
+    request::{Initialize, Request as RequestTrait, Shutdown},
+    DidOpenTextDocumentParams, InitializeParams, PublishDiagnosticsParams, TextDocumentItem, Url,
+};
+
+#[test]
+fn binary_analyzes_scripts_with_the_cli_library() {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_mica-lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = server.stdin.take().unwrap();
+    let mut output = BufReader::new(server.stdout.take().unwrap());
+    let mut send = |message: Message| message.write(&mut input).unwrap();
+
+    send(
+        Request::new(
+            RequestId::from(1),
+            Initialize::METHOD.to_owned(),
+            InitializeParams::default(),
+        )
+        .into(),
+    );
+    send(
+        Notification::new(
+            Initialized::METHOD.to_owned(),
+            lsp_types::InitializedParams {},
+        )
+        .into(),
+    );
+    let uri = Url::parse("file:///test.mi").unwrap();
+    // `Process` is only available with the `os` feature, which the CLI enables.
+    send(
+        Notification::new(
+            DidOpenTextDocument::METHOD.to_owned(),
+            DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    String::from("mica"),
+                    1,
+                    String::from("print(Process.args)\nprint(undeclared)\n"),
+                ),
+            },
+        )
+        .into(),
+    );
+
+    let diagnostics = loop {
+        match Message::read(&mut output).unwrap().unwrap() {
+            Message::Notification(notification)
+                if notification.method == PublishDiagnostics::METHOD =>
+            {
+                break serde_json::from_value::<PublishDiagnosticsParams>(notification.params)
+                    .unwrap();
+            }
+            _ => (),
+        }
+    };
+    assert_eq!(diagnostics.uri, uri);
+    assert_eq!(
+        diagnostics.diagnostics.len(),
+        1,
+        "{:?}",
+        diagnostics.diagnostics
+    );
+    assert_eq!(diagnostics.diagnostics[0].range.start.line, 1);
+
+    send(Request::new(RequestId::from(2), Shutdown::METHOD.to_owned(), ()).into());
+    send(Notification::new(Exit::METHOD.to_owned(), ()).into());
+    assert!(server.wait().unwrap().success());
+}
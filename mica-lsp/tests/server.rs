@@ -0,0 +1,121 @@
+use std::thread;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{GotoDefinition, Initialize, Request as RequestTrait, Shutdown},
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, GotoDefinitionParams,
+    GotoDefinitionResponse, InitializeParams, Position, PublishDiagnosticsParams,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
+};
+
+struct Client {
+    connection: Connection,
+    next_id: i32,
+}
+
+impl Client {
+    fn request<R: RequestTrait>(&mut self, params: R::Params) -> R::Result {
+        let id = RequestId::from(self.next_id);
+        self.next_id += 1;
+        let request = Request::new(id.clone(), R::METHOD.to_owned(), params);
+        self.connection.sender.send(request.into()).unwrap();
+        loop {
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(response) if response.id == id => {
+                    assert!(response.error.is_none(), "{:?}", response.error);
+                    return serde_json::from_value(response.result.unwrap()).unwrap();
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn notify<N: NotificationTrait>(&self, params: N::Params) {
+        let notification = Notification::new(N::METHOD.to_owned(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    fn diagnostics(&self) -> PublishDiagnosticsParams {
+        loop {
+            if let Message::Notification(notification) = self.connection.receiver.recv().unwrap() {
+                if notification.method == PublishDiagnostics::METHOD {
+                    return serde_json::from_value(notification.params).unwrap();
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn server_publishes_diagnostics_and_answers_requests() {
+    let (server_connection, client_connection) = Connection::memory();
+    let server = thread::spawn(move || {
+        mica_lsp::run(server_connection, mica::Engine::new).unwrap();
+    });
+    let mut client = Client {
+        connection: client_connection,
+        next_id: 1,
+    };
+
+    #[allow(deprecated)]
+    let initialize = InitializeParams {
+        root_uri: None,
+        ..Default::default()
+    };
+    let result = client.request::<Initialize>(initialize);
+    assert!(result.capabilities.hover_provider.is_some());
+    client.notify::<Initialized>(lsp_types::InitializedParams {});
+
+    let uri = Url::parse("file:///test.mi").unwrap();
+    client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(
+            uri.clone(),
+            String::from("mica"),
+            1,
+            String::from("func f() = 1\nf(\n"),
+        ),
+    });
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.uri, uri);
+    assert!(!diagnostics.diagnostics.is_empty());
+
+    client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: Some(lsp_types::Range::new(
+                Position::new(1, 2),
+                Position::new(1, 2),
+            )),
+            range_length: None,
+            text: String::from(")"),
+        }],
+    });
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.version, Some(2));
+    assert!(diagnostics.diagnostics.is_empty());
+
+    let definition = client.request::<GotoDefinition>(GotoDefinitionParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            Position::new(1, 0),
+        ),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    });
+    let Some(GotoDefinitionResponse::Scalar(location)) = definition else {
+        panic!("unexpected definition response: {definition:?}");
+    };
+    assert_eq!(
+        location.range,
+        lsp_types::Range::new(Position::new(0, 5), Position::new(0, 6))
+    );
+
+    client.request::<Shutdown>(());
+    client.notify::<Exit>(());
+    server.join().unwrap();
+}
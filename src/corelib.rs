@@ -1,15 +1,31 @@
 //! The Mica core library. Provides the fundamental set of functions and types.
 
+pub(crate) use self::option::link_option;
 use self::{builtins::*, core::load_core};
 use crate::{
-    ll::value::{Dict, RawValue, Record, Tuple},
+    ll::value::{Dict, RawValue, Record, Str, Tuple},
     CoreLibrary, Engine, Error, TypeBuilder,
 };
 
 mod builtins;
+mod channel;
 mod core;
+mod coroutine;
+mod deque;
 mod gc;
+#[cfg(feature = "io")]
+mod io;
 mod iterators;
+mod json;
+mod option;
+#[cfg(feature = "os")]
+mod os;
+mod persistent;
+mod reflection;
+#[cfg(feature = "regex")]
+mod regex;
+mod tasks;
+mod test;
 
 /// Unit struct representing the core library.
 #[derive(Debug, Clone, Copy)]
@@ -28,7 +44,7 @@ impl CoreLibrary for Lib {
         number::define(builder)
     }
 
-    fn define_string(&self, builder: TypeBuilder<String>) -> TypeBuilder<String> {
+    fn define_string(&self, builder: TypeBuilder<Str>) -> TypeBuilder<Str> {
         string::define(builder)
     }
 
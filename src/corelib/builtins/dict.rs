@@ -1,6 +1,7 @@
 use crate::{
-    corelib::iterators::dict::DictIter, ll::value::Dict, Arguments, IntoValue,
-    MethodParameterCount, RawFunctionKind, TypeBuilder,
+    corelib::iterators::dict::DictIter,
+    ll::value::{Dict, RawValue},
+    Arguments, IntoValue, MethodParameterCount, RawFunctionKind, TypeBuilder,
 };
 
 pub(crate) fn define(builder: TypeBuilder<Dict>) -> TypeBuilder<Dict> {
@@ -11,6 +12,13 @@ pub(crate) fn define(builder: TypeBuilder<Dict>) -> TypeBuilder<Dict> {
         .add_function("remove", Dict::remove)
         .add_function("get", Dict::get)
         .add_function("contains_key", Dict::contains_key)
+        // Subscripts `dict[key]` work like `get` and `insert`, except that assigning to one
+        // evaluates to the new value rather than the old one.
+        .add_function("index", Dict::get)
+        .add_function("set_index", |d: &Dict, key: RawValue, value: RawValue| {
+            d.insert(key, value);
+            value
+        })
         .add_function("clone", Dict::clone)
         // TODO: It should be possible to implement this without raw functions in the future.
         .add_raw_function(
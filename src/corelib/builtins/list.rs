@@ -1,7 +1,16 @@
+use std::{cmp::Ordering, mem};
+
 use crate::{
     corelib::iterators::list::ListIter,
-    ll::value::{List, RawValue},
-    Arguments, IntoValue, MethodParameterCount, RawFunctionKind, TypeBuilder,
+    ll::{
+        bytecode::{Control, Environment, Library},
+        error::LanguageErrorKind,
+        gc::{Gc, Memory},
+        value::{checked_slice, List, RawValue, Str},
+        vm::Globals,
+    },
+    Arguments, EngineContext, IntoValue, MethodParameterCount, MicaLanguageResultExt,
+    RawFunctionKind, TypeBuilder, Value,
 };
 
 pub(crate) fn define(builder: TypeBuilder<Vec<RawValue>>) -> TypeBuilder<Vec<RawValue>> {
@@ -39,6 +48,76 @@ pub(crate) fn define(builder: TypeBuilder<Vec<RawValue>>) -> TypeBuilder<Vec<Raw
                 }
             },
         )
+        // The subscript operator reads and writes lists directly, but these methods are what it
+        // calls when indexing other values, so they are provided for uniformity.
+        .add_raw_function(
+            "index",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(|env, _, args| {
+                let arguments = Arguments::new(args, env);
+                let list = unsafe { arguments.raw_self().downcast_user_data_unchecked::<List>() };
+                unsafe { list.index(*arguments.nth(0).unwrap()) }
+            })),
+        )
+        .add_raw_function(
+            "set_index",
+            MethodParameterCount::from_count_with_self(3),
+            RawFunctionKind::Foreign(Box::new(|env, _, args| {
+                let arguments = Arguments::new(args, env);
+                let list = unsafe { arguments.raw_self().downcast_user_data_unchecked::<List>() };
+                unsafe { list.set_index(*arguments.nth(0).unwrap(), *arguments.nth(1).unwrap()) }
+            })),
+        )
+        .add_raw_function(
+            "slice",
+            MethodParameterCount::from_count_with_self(3),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                let arguments = Arguments::new(args, library);
+                let v = unsafe {
+                    arguments
+                        .raw_self()
+                        .downcast_user_data_unchecked::<List>()
+                        .as_slice()
+                };
+                let range = checked_slice(
+                    *arguments.nth(0).unwrap(),
+                    *arguments.nth(1).unwrap(),
+                    v.len(),
+                )?;
+                Ok(v[range]
+                    .to_vec()
+                    .into_value_with_engine_state(library, gc)
+                    .to_raw(gc))
+            })),
+        )
+        .add_raw_function(
+            "set_slice",
+            MethodParameterCount::from_count_with_self(4),
+            RawFunctionKind::Foreign(Box::new(|library, _, args| {
+                let arguments = Arguments::new(args, library);
+                let list = unsafe { arguments.raw_self().downcast_user_data_unchecked::<List>() };
+                let values_v = *arguments.nth(2).unwrap();
+                let values = values_v
+                    .get_raw_user_data()
+                    .and_then(|user_data| {
+                        unsafe { user_data.get() }.as_any().downcast_ref::<List>()
+                    })
+                    .ok_or_else(|| LanguageErrorKind::TypeError {
+                        expected: "List".into(),
+                        got: values_v.type_name(),
+                    })?;
+                // Copy the new elements out first, as they may come from the list itself.
+                let values = unsafe { values.as_slice() }.to_vec();
+                let len = unsafe { list.as_slice() }.len();
+                let range =
+                    checked_slice(*arguments.nth(0).unwrap(), *arguments.nth(1).unwrap(), len)?;
+                library
+                    .limits
+                    .check_len_of("List", len - range.len() + values.len())?;
+                unsafe { (*list.get_mut()).splice(range, values) };
+                Ok(values_v)
+            })),
+        )
         .add_function("first", |v: &Vec<RawValue>| v.first().copied())
         .add_function("last", |v: &Vec<RawValue>| v.last().copied())
         .add_function("contains", |v: &Vec<RawValue>, x: RawValue| v.contains(&x))
@@ -49,9 +128,39 @@ pub(crate) fn define(builder: TypeBuilder<Vec<RawValue>>) -> TypeBuilder<Vec<Raw
         .add_function("swap_remove", Vec::swap_remove)
         .add_function("push", Vec::push)
         .add_function("pop", Vec::pop)
-        .add_function("resize", Vec::resize)
+        // resize and repeat can allocate a lot of memory in one go, so they check the list length
+        // and memory limits before doing so.
+        .add_raw_function(
+            "resize",
+            MethodParameterCount::from_count_with_self(3),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                let arguments = Arguments::new(args, library);
+                let list = unsafe { arguments.raw_self().downcast_user_data_unchecked::<List>() };
+                let new_len: usize = arguments.get(0).to_language_error()?;
+                let value = arguments.nth(1).copied().unwrap_or_default();
+                library.limits.check_len_of("List", new_len)?;
+                let old_len = unsafe { list.as_slice() }.len();
+                gc.reserve(
+                    new_len
+                        .saturating_sub(old_len)
+                        .saturating_mul(mem::size_of::<RawValue>()),
+                )?;
+                unsafe { (*list.get_mut()).resize(new_len, value) };
+                Ok(RawValue::from(()))
+            })),
+        )
         .add_function("truncate", Vec::truncate)
-        .add_function("repeat", |v: &Vec<RawValue>, n: usize| v.repeat(n))
+        .add_raw_function(
+            "repeat",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(repeat)),
+        )
+        // `mul` is what the `*` operator calls, so that `[0] * n` creates a list of n zeros.
+        .add_raw_function(
+            "mul",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(repeat)),
+        )
         .add_function("reverse", |v: &mut Vec<RawValue>| v.reverse())
         .add_function("rotate_left", |v: &mut Vec<RawValue>, n: usize| {
             v.rotate_left(n)
@@ -63,6 +172,69 @@ pub(crate) fn define(builder: TypeBuilder<Vec<RawValue>>) -> TypeBuilder<Vec<Raw
             v.swap(a, b)
         })
         .add_function("clone", |v: &Vec<RawValue>| v.clone())
+        // Elements are joined the same way `print` would print them, `to_string` methods included,
+        // so strings don't need to be quoted, and lists of numbers can be joined without
+        // converting them first. Calling `to_string` requires going back into the VM, which only
+        // contextual functions can do.
+        .add_raw_function(
+            "join",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Contextual(Box::new(join)),
+        )
+        // Comparing elements can fail with a type error, which only raw functions can surface.
+        .add_raw_function(
+            "sort",
+            MethodParameterCount::from_count_with_self(1),
+            RawFunctionKind::Foreign(Box::new(|env, _, args| {
+                let arguments = Arguments::new(args, env);
+                let list = unsafe { arguments.raw_self().downcast_user_data_unchecked::<List>() };
+                // Sort a copy, such that comparing a list that contains itself does not observe
+                // it in the middle of being sorted.
+                let mut sorted = unsafe { list.as_slice() }.to_vec();
+                let mut error = None;
+                sorted.sort_by(|a, b| {
+                    a.total_cmp(b).unwrap_or_else(|e| {
+                        error.get_or_insert(e);
+                        Ordering::Equal
+                    })
+                });
+                if let Some(error) = error {
+                    return Err(error);
+                }
+                unsafe { *list.get_mut() = sorted };
+                Ok(RawValue::from(()))
+            })),
+        )
+        .add_raw_function(
+            "sort_by",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Control(Control::SortBy),
+        )
+        .add_raw_function(
+            "sort_by_key",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Control(Control::SortByKey),
+        )
+        .add_raw_function(
+            "binary_search",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(|env, _, args| {
+                let arguments = Arguments::new(args, env);
+                let list = unsafe { arguments.raw_self().downcast_user_data_unchecked::<List>() };
+                let x = *arguments.nth(0).unwrap();
+                let mut error = None;
+                let found = unsafe { list.as_slice() }.binary_search_by(|element| {
+                    element.total_cmp(&x).unwrap_or_else(|e| {
+                        error.get_or_insert(e);
+                        Ordering::Equal
+                    })
+                });
+                if let Some(error) = error {
+                    return Err(error);
+                }
+                Ok(found.map_or(RawValue::from(()), |index| RawValue::from(index as f64)))
+            })),
+        )
         // TODO: It should be possible to implement this without raw functions in the future.
         .add_raw_function(
             "iter",
@@ -75,10 +247,63 @@ pub(crate) fn define(builder: TypeBuilder<Vec<RawValue>>) -> TypeBuilder<Vec<Raw
         )
 }
 
+fn repeat(
+    library: &Library,
+    gc: &mut Memory,
+    args: &[RawValue],
+) -> Result<RawValue, LanguageErrorKind> {
+    let arguments = Arguments::new(args, library);
+    let v = unsafe {
+        arguments
+            .raw_self()
+            .downcast_user_data_unchecked::<List>()
+            .as_slice()
+    };
+    let n: usize = arguments.get(0).to_language_error()?;
+    let len = v.len().saturating_mul(n);
+    library.limits.check_len_of("List", len)?;
+    gc.reserve(len.saturating_mul(mem::size_of::<RawValue>()))?;
+    Ok(v.repeat(n)
+        .into_value_with_engine_state(library, gc)
+        .to_raw(gc))
+}
+
+fn join(
+    env: &Environment,
+    library: &Library,
+    globals: &mut Globals,
+    gc: &mut Memory,
+    args: &[RawValue],
+) -> Result<RawValue, LanguageErrorKind> {
+    let arguments = Arguments::new(args, library);
+    let separator: Gc<Str> = arguments.get(0).to_language_error()?;
+    // The elements are copied out, since `to_string` methods may modify the list while it's being
+    // joined.
+    let elements: Vec<_> = unsafe {
+        arguments
+            .raw_self()
+            .downcast_user_data_unchecked::<List>()
+            .as_slice()
+    }
+    .iter()
+    .copied()
+    .map(Value::from_raw)
+    .collect();
+    let mut context = EngineContext::new(env, library, globals, gc);
+    let mut joined = String::new();
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            joined.push_str(&separator);
+        }
+        joined.push_str(&context.display(element).to_language_error()?);
+    }
+    Ok(joined.into_value_with_engine_state(library, gc).to_raw(gc))
+}
+
 #[derive(Debug)]
-struct OutOfBounds {
-    index: usize,
-    len: usize,
+pub(crate) struct OutOfBounds {
+    pub(crate) index: usize,
+    pub(crate) len: usize,
 }
 
 impl std::fmt::Display for OutOfBounds {
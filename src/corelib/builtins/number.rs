@@ -1,5 +1,8 @@
 use super::{ref_self1, ref_self2};
-use crate::TypeBuilder;
+use crate::{
+    ll::{value::RawValue, vm::floored_remainder},
+    Arguments, MethodParameterCount, MicaLanguageResultExt, RawFunctionKind, TypeBuilder,
+};
 
 pub(crate) fn define(builder: TypeBuilder<f64>) -> TypeBuilder<f64> {
     builder
@@ -9,6 +12,9 @@ pub(crate) fn define(builder: TypeBuilder<f64>) -> TypeBuilder<f64> {
         .add_static("epsilon", || f64::EPSILON)
         .add_static("e", || std::f64::consts::E)
         .add_static("pi", || std::f64::consts::PI)
+        .add_static("INFINITY", || f64::INFINITY)
+        .add_static("E", || std::f64::consts::E)
+        .add_static("PI", || std::f64::consts::PI)
         // Math stuff
         .add_function("floor", ref_self1(f64::floor))
         .add_function("ceil", ref_self1(f64::ceil))
@@ -17,8 +23,12 @@ pub(crate) fn define(builder: TypeBuilder<f64>) -> TypeBuilder<f64> {
         .add_function("fract", ref_self1(f64::fract))
         .add_function("abs", ref_self1(f64::abs))
         .add_function("signum", ref_self1(f64::signum))
-        .add_function("div", ref_self2(f64::div_euclid))
-        .add_function("mod", ref_self2(f64::rem_euclid))
+        .add_function("sign", |x: &f64| if *x == 0.0 { *x } else { x.signum() })
+        .add_raw_function("div", TWO_ARGUMENTS, division(f64::div_euclid))
+        .add_raw_function("div_floor", TWO_ARGUMENTS, division(|x, y| (x / y).floor()))
+        .add_raw_function("div_euclid", TWO_ARGUMENTS, division(f64::div_euclid))
+        .add_raw_function("mod", TWO_ARGUMENTS, division(floored_remainder))
+        .add_raw_function("rem_euclid", TWO_ARGUMENTS, division(f64::rem_euclid))
         .add_function("pow", ref_self2(f64::powf))
         .add_function("sqrt", ref_self1(f64::sqrt))
         .add_function("exp", ref_self1(f64::exp))
@@ -49,6 +59,16 @@ pub(crate) fn define(builder: TypeBuilder<f64>) -> TypeBuilder<f64> {
         .add_function("to_radians", ref_self1(f64::to_radians))
         .add_function("min", ref_self2(f64::min))
         .add_function("max", ref_self2(f64::max))
+        .add_function(
+            "clamp",
+            |x: &f64, min: f64, max: f64| -> Result<_, InvalidClampRange> {
+                if min <= max {
+                    Ok(x.clamp(min, max))
+                } else {
+                    Err(InvalidClampRange { min, max })
+                }
+            },
+        )
         // Float properties
         .add_function("is_nan", ref_self1(f64::is_nan))
         .add_function("is_finite", ref_self1(f64::is_finite))
@@ -69,10 +89,117 @@ pub(crate) fn define(builder: TypeBuilder<f64>) -> TypeBuilder<f64> {
         })
         // Strings
         .add_static("parse", |s: String| -> Result<f64, _> { s.parse() })
+        .add_static(
+            "parse",
+            |s: String, radix: u32| -> Result<f64, RadixError> {
+                check_radix(radix)?;
+                let n = i128::from_str_radix(&s, radix).map_err(RadixError::Parse)?;
+                Ok(n as f64)
+            },
+        )
         .add_function("to_string", |x: &f64| x.to_string())
+        .add_function("to_string", |x: &f64, radix: u32| {
+            to_string_radix(*x, radix)
+        })
+        .add_function("to_fixed", |x: &f64, digits: usize| {
+            if digits <= MAX_FIXED_DIGITS {
+                Ok(format!("{x:.digits$}"))
+            } else {
+                Err(TooManyDigits(digits))
+            }
+        })
         .add_function("to_debug", |x: &f64| x.to_string())
 }
 
+const TWO_ARGUMENTS: MethodParameterCount = MethodParameterCount::from_count_with_self(2);
+
+/// Creates a division method. These are raw functions because, like the division operators, they
+/// raise errors in checked arithmetic, which they need the library to know about.
+fn division(f: fn(f64, f64) -> f64) -> RawFunctionKind {
+    RawFunctionKind::Foreign(Box::new(move |library, _, arguments| {
+        let arguments = Arguments::new(arguments, library);
+        let x = arguments.raw_self().ensure_number()?;
+        let y = arguments.get(0).to_language_error()?;
+        let result = library.arithmetic.check_division(y, f(x, y))?;
+        Ok(RawValue::from(result))
+    }))
+}
+
+/// The most digits `to_fixed` can produce after the decimal point. Numbers don't have anywhere near
+/// this much precision, so this mostly prevents scripts from allocating huge strings by accident.
+const MAX_FIXED_DIGITS: usize = 100;
+
+fn check_radix(radix: u32) -> Result<(), RadixError> {
+    if (2..=36).contains(&radix) {
+        Ok(())
+    } else {
+        Err(RadixError::InvalidRadix(radix))
+    }
+}
+
+/// Converts an integer to a string of digits in the given radix. Only integers are supported, as
+/// fractions usually don't have an exact representation in other radices.
+fn to_string_radix(x: f64, radix: u32) -> Result<String, RadixError> {
+    check_radix(radix)?;
+    if x.fract() != 0.0 || !x.is_finite() || x.abs() >= i128::MAX as f64 {
+        return Err(RadixError::NotAnInteger(x));
+    }
+    let mut n = (x as i128).unsigned_abs();
+    let mut digits = Vec::new();
+    loop {
+        digits.push(char::from_digit((n % u128::from(radix)) as u32, radix).unwrap());
+        n /= u128::from(radix);
+        if n == 0 {
+            break;
+        }
+    }
+    if x < 0.0 {
+        digits.push('-');
+    }
+    Ok(digits.into_iter().rev().collect())
+}
+
+#[derive(Debug)]
+enum RadixError {
+    InvalidRadix(u32),
+    NotAnInteger(f64),
+    Parse(std::num::ParseIntError),
+}
+
+impl std::fmt::Display for RadixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidRadix(radix) => {
+                write!(f, "radix must be between 2 and 36, but got {radix}")
+            }
+            Self::NotAnInteger(x) => {
+                write!(
+                    f,
+                    "only integers can be converted to other radices, but got {x}"
+                )
+            }
+            Self::Parse(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for RadixError {}
+
+#[derive(Debug)]
+struct TooManyDigits(usize);
+
+impl std::fmt::Display for TooManyDigits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "at most {MAX_FIXED_DIGITS} digits can be shown after the decimal point, but got {}",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for TooManyDigits {}
+
 #[derive(Debug)]
 struct ShiftOverflow;
 
@@ -83,3 +210,21 @@ impl std::fmt::Display for ShiftOverflow {
 }
 
 impl std::error::Error for ShiftOverflow {}
+
+#[derive(Debug)]
+struct InvalidClampRange {
+    min: f64,
+    max: f64,
+}
+
+impl std::fmt::Display for InvalidClampRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot clamp to the range from {} to {}",
+            self.min, self.max
+        )
+    }
+}
+
+impl std::error::Error for InvalidClampRange {}
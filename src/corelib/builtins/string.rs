@@ -1,66 +1,163 @@
-use std::ops::Deref;
+use std::{iter, ops::Deref};
 
 use crate::{
     corelib::iterators::string::{
-        bytes::StringBytes, chars::StringChars, code_points::StringCodePoints, lines::StringLines,
-        rsplit::StringRSplit, split::StringSplit,
+        bytes::StringBytes, chars::StringChars, code_points::StringCodePoints,
+        graphemes::StringGraphemes, lines::StringLines, rsplit::StringRSplit, split::StringSplit,
     },
-    ll::gc::Gc,
-    Arguments, IntoValue, MethodParameterCount, MicaLanguageResultExt, RawFunctionKind,
-    TypeBuilder, Value,
+    ll::{
+        bytecode::Library,
+        error::LanguageErrorKind,
+        gc::{Gc, Memory},
+        value::{checked_index, checked_slice, RawValue, Str},
+    },
+    Arguments, EngineContext, IntoValue, MethodParameterCount, MicaLanguageResultExt,
+    RawFunctionKind, TypeBuilder, Value,
 };
 
-pub(crate) fn define(builder: TypeBuilder<String>) -> TypeBuilder<String> {
+pub(crate) fn define(builder: TypeBuilder<Str>) -> TypeBuilder<Str> {
     builder
-        .add_static("debug", |x: Value| format!("{x:?}"))
-        .add_function("cat", |s: &String, t: Gc<String>| format!("{s}{t}"))
-        .add_function("contains", |s: &String, sub: Gc<String>| {
+        .add_static("debug", |context: &mut EngineContext, x: Value| {
+            context.debug(&x)
+        })
+        .add_function("cat", |s: &Str, t: Gc<Str>| Str::concat(&[s, &t]))
+        .add_function("contains", |s: &Str, sub: Gc<Str>| {
             s.contains(sub.deref().deref())
         })
-        .add_function("starts_with", |s: &String, prefix: Gc<String>| {
+        .add_function("starts_with", |s: &Str, prefix: Gc<Str>| {
             s.starts_with(prefix.deref().deref())
         })
-        .add_function("ends_with", |s: &String, suffix: Gc<String>| {
+        .add_function("ends_with", |s: &Str, suffix: Gc<Str>| {
             s.ends_with(suffix.deref().deref())
         })
-        .add_function("strip_prefix", |s: &String, prefix: Gc<String>| {
-            s.strip_prefix(prefix.deref().deref()).map(|x| x.to_owned())
+        .add_function("strip_prefix", |s: &Str, prefix: Gc<Str>| {
+            s.strip_prefix(prefix.deref().deref()).map(Str::new)
         })
-        .add_function("strip_suffix", |s: &String, suffix: Gc<String>| {
-            s.strip_suffix(suffix.deref().deref()).map(|x| x.to_owned())
+        .add_function("strip_suffix", |s: &Str, suffix: Gc<Str>| {
+            s.strip_suffix(suffix.deref().deref()).map(Str::new)
         })
-        .add_function("find", |s: &String, substr: Gc<String>| {
+        .add_function("find", |s: &Str, substr: Gc<Str>| {
             s.find(substr.deref().deref())
         })
-        .add_function("rfind", |s: &String, substr: Gc<String>| {
+        .add_function("rfind", |s: &Str, substr: Gc<Str>| {
             s.rfind(substr.deref().deref())
         })
-        .add_function("byte_at", |s: &String, position: usize| {
+        .add_function("byte_at", |s: &Str, position: usize| {
             s.as_bytes().get(position).copied()
         })
-        .add_function("byte_len", |s: &String| s.len())
-        .add_function("nth_char", |s: &String, position: usize| {
+        .add_function("byte_len", |s: &Str| s.len())
+        // `char_at` and `code_point_at` take byte positions, such as the ones returned by `find`,
+        // and return `nil` if the position is not at the start of a character.
+        .add_function("char_at", |s: &Str, position: usize| {
+            s.get(position..).and_then(|rest| rest.chars().next())
+        })
+        .add_function("code_point_at", |s: &Str, position: usize| {
+            s.get(position..)
+                .and_then(|rest| rest.chars().next())
+                .map(u32::from)
+        })
+        .add_function("nth_char", |s: &Str, position: usize| {
             s.chars().nth(position)
         })
-        .add_function("nth_code_point", |s: &String, position: usize| {
+        .add_function("nth_code_point", |s: &Str, position: usize| {
             s.chars().nth(position).map(u32::from)
         })
-        .add_function("char_len", |s: &String| s.chars().count())
-        .add_function("is_empty", |s: &String| s.is_empty())
-        .add_function("to_lowercase", |s: &String| s.to_lowercase())
-        .add_function("to_uppercase", |s: &String| s.to_uppercase())
-        .add_function("repeat", |s: &String, n: usize| s.repeat(n))
-        .add_function(
-            "replace",
-            |s: &String, pat: Gc<String>, with: Gc<String>| s.replace(pat.deref().deref(), &with),
+        .add_function("char_len", |s: &Str| s.chars().count())
+        .add_function("is_empty", |s: &Str| s.is_empty())
+        .add_function("is_ascii", |s: &Str| s.is_ascii())
+        // Unlike `Number.parse`, this is meant for input that may not be a number at all.
+        .add_function("parse_number", |s: &Str| s.parse::<f64>().ok())
+        .add_function("to_lowercase", |s: &Str| s.to_lowercase())
+        .add_function("to_uppercase", |s: &Str| s.to_uppercase())
+        .add_raw_function(
+            "repeat",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(repeat)),
+        )
+        // `mul` is what the `*` operator calls, so that `"ab" * 3` repeats the string.
+        .add_raw_function(
+            "mul",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(repeat)),
         )
+        .add_function("replace", |s: &Str, pat: Gc<Str>, with: Gc<Str>| {
+            s.replace(pat.deref().deref(), &with)
+        })
         .add_function(
             "replace",
-            |s: &String, pat: Gc<String>, with: Gc<String>, n: usize| {
+            |s: &Str, pat: Gc<Str>, with: Gc<Str>, n: usize| {
                 s.replacen(pat.deref().deref(), &with, n)
             },
         )
-        .add_function("trim", |s: &String| s.trim().to_owned())
+        .add_function("trim", |s: &Str| Str::new(s.trim()))
+        .add_function("trim_start", |s: &Str| Str::new(s.trim_start()))
+        .add_function("trim_end", |s: &Str| Str::new(s.trim_end()))
+        .add_raw_function(
+            "pad_start",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                pad(library, gc, args, Side::Start)
+            })),
+        )
+        .add_raw_function(
+            "pad_start",
+            MethodParameterCount::from_count_with_self(3),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                pad(library, gc, args, Side::Start)
+            })),
+        )
+        .add_raw_function(
+            "pad_end",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                pad(library, gc, args, Side::End)
+            })),
+        )
+        .add_raw_function(
+            "pad_end",
+            MethodParameterCount::from_count_with_self(3),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                pad(library, gc, args, Side::End)
+            })),
+        )
+        // Subscripts index strings by characters rather than bytes, such that `s[i]` agrees with
+        // `s.nth_char(i)`.
+        .add_raw_function(
+            "index",
+            MethodParameterCount::from_count_with_self(2),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                let arguments = Arguments::new(args, library);
+                let s = unsafe { arguments.raw_self().get_raw_string_unchecked().get() };
+                let index = checked_index(*arguments.nth(0).unwrap(), s.chars().count())?;
+                let c = s.chars().nth(index).unwrap();
+                Ok(Str::from(c)
+                    .into_value_with_engine_state(library, gc)
+                    .to_raw(gc))
+            })),
+        )
+        .add_raw_function(
+            "slice",
+            MethodParameterCount::from_count_with_self(3),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                let arguments = Arguments::new(args, library);
+                let s = unsafe { arguments.raw_self().get_raw_string_unchecked().get() };
+                let range = checked_slice(
+                    *arguments.nth(0).unwrap(),
+                    *arguments.nth(1).unwrap(),
+                    s.chars().count(),
+                )?;
+                // Find the byte positions the range starts and ends at, so that the slice can be
+                // copied out of the string in one go.
+                let mut boundaries = s.char_indices().map(|(i, _)| i).chain(iter::once(s.len()));
+                let start = boundaries.nth(range.start).unwrap();
+                let end = match range.len() {
+                    0 => start,
+                    len => boundaries.nth(len - 1).unwrap(),
+                };
+                let slice = Str::new(&s[start..end]);
+                Ok(slice.into_value_with_engine_state(library, gc).to_raw(gc))
+            })),
+        )
         // TODO: It should be possible to implement these without raw functions in the future.
         .add_raw_function(
             "bytes",
@@ -89,6 +186,15 @@ pub(crate) fn define(builder: TypeBuilder<String>) -> TypeBuilder<String> {
                 Ok(iter.into_value_with_engine_state(library, gc).to_raw(gc))
             })),
         )
+        .add_raw_function(
+            "graphemes",
+            MethodParameterCount::from_count_with_self(1),
+            RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                let arguments = Arguments::new(args, library);
+                let iter = unsafe { StringGraphemes::new(*arguments.raw_self()) };
+                Ok(iter.into_value_with_engine_state(library, gc).to_raw(gc))
+            })),
+        )
         .add_raw_function(
             "lines",
             MethodParameterCount::from_count_with_self(1),
@@ -103,7 +209,7 @@ pub(crate) fn define(builder: TypeBuilder<String>) -> TypeBuilder<String> {
             MethodParameterCount::from_count_with_self(2),
             RawFunctionKind::Foreign(Box::new(|library, gc, args| {
                 let arguments = Arguments::new(args, library);
-                let sep: Gc<String> = arguments.get(0).to_language_error()?;
+                let sep: Gc<Str> = arguments.get(0).to_language_error()?;
                 let iter = unsafe { StringSplit::new(*arguments.raw_self(), sep) };
                 Ok(iter.into_value_with_engine_state(library, gc).to_raw(gc))
             })),
@@ -113,9 +219,66 @@ pub(crate) fn define(builder: TypeBuilder<String>) -> TypeBuilder<String> {
             MethodParameterCount::from_count_with_self(2),
             RawFunctionKind::Foreign(Box::new(|library, gc, args| {
                 let arguments = Arguments::new(args, library);
-                let sep: Gc<String> = arguments.get(0).to_language_error()?;
+                let sep: Gc<Str> = arguments.get(0).to_language_error()?;
                 let iter = unsafe { StringRSplit::new(*arguments.raw_self(), sep) };
                 Ok(iter.into_value_with_engine_state(library, gc).to_raw(gc))
             })),
         )
 }
+
+// Repeating can allocate a lot of memory in one go, so the length and memory limits are checked
+// before doing so.
+fn repeat(
+    library: &Library,
+    gc: &mut Memory,
+    args: &[RawValue],
+) -> Result<RawValue, LanguageErrorKind> {
+    let arguments = Arguments::new(args, library);
+    let s = unsafe { arguments.raw_self().get_raw_string_unchecked().get() };
+    let n: usize = arguments.get(0).to_language_error()?;
+    let len = s.len().saturating_mul(n);
+    library.limits.check_len_of("String", len)?;
+    gc.reserve(len)?;
+    Ok(s.repeat(n)
+        .into_value_with_engine_state(library, gc)
+        .to_raw(gc))
+}
+
+enum Side {
+    Start,
+    End,
+}
+
+/// Pads the string with copies of the fill string (a space by default) until it's at least as many
+/// characters long as the given width. The last copy of the fill string is cut short if it
+/// doesn't fit.
+fn pad(
+    library: &Library,
+    gc: &mut Memory,
+    args: &[RawValue],
+    side: Side,
+) -> Result<RawValue, LanguageErrorKind> {
+    let arguments = Arguments::new(args, library);
+    let s = unsafe { arguments.raw_self().get_raw_string_unchecked().get() };
+    let width: usize = arguments.get(0).to_language_error()?;
+    let fill = match arguments.nth(1) {
+        Some(fill) => unsafe { fill.ensure_raw_string()?.get() }.as_str(),
+        None => " ",
+    };
+
+    let missing = width.saturating_sub(s.chars().count());
+    if missing == 0 || fill.is_empty() {
+        return Ok(*arguments.raw_self());
+    }
+    let max_char_len = fill.chars().map(char::len_utf8).max().unwrap_or(1);
+    let len = s.len().saturating_add(missing.saturating_mul(max_char_len));
+    library.limits.check_len_of("String", len)?;
+    gc.reserve(len)?;
+    let padding: String = fill.chars().cycle().take(missing).collect();
+
+    let padded = match side {
+        Side::Start => Str::concat(&[&padding, s]),
+        Side::End => Str::concat(&[s, &padding]),
+    };
+    Ok(padded.into_value_with_engine_state(library, gc).to_raw(gc))
+}
@@ -0,0 +1,105 @@
+//! The `Channel` type.
+
+use std::{collections::VecDeque, fmt};
+
+use crate::{
+    builtin_traits::iterator, ll::value::RawValue, Engine, Error, MicaResultExt, TypeBuilder,
+    UserData,
+};
+
+/// A queue of values that fibers use to communicate with each other. Sending to a full channel
+/// or receiving from an empty one blocks the fiber until another fiber receives or sends a value.
+pub(crate) struct Channel {
+    buffer: VecDeque<RawValue>,
+    capacity: usize,
+    closed: bool,
+}
+
+impl Channel {
+    fn with_capacity(capacity: usize) -> Result<Self, Error> {
+        if capacity == 0 {
+            return Err(ZeroCapacity).mica();
+        }
+        Ok(Self {
+            buffer: VecDeque::new(),
+            capacity,
+            closed: false,
+        })
+    }
+
+    fn send(&mut self, value: RawValue) -> Result<(), Error> {
+        if self.closed {
+            return Err(SendToClosedChannel).mica();
+        }
+        if self.buffer.len() >= self.capacity {
+            return Err(Error::WouldBlock);
+        }
+        self.buffer.push_back(value);
+        Ok(())
+    }
+
+    /// Receives a value, or returns `nil` if the channel is closed and there are no more values
+    /// left in it.
+    pub(crate) fn receive(&mut self) -> Result<RawValue, Error> {
+        match self.buffer.pop_front() {
+            Some(value) => Ok(value),
+            None if self.closed => Ok(RawValue::from(())),
+            None => Err(Error::WouldBlock),
+        }
+    }
+
+    fn has_next(&mut self) -> Result<bool, Error> {
+        if self.buffer.is_empty() && !self.closed {
+            Err(Error::WouldBlock)
+        } else {
+            Ok(!self.buffer.is_empty())
+        }
+    }
+}
+
+impl UserData for Channel {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        self.buffer.iter().copied().for_each(visit);
+    }
+}
+
+#[derive(Debug)]
+struct ZeroCapacity;
+
+impl fmt::Display for ZeroCapacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel capacity must be at least 1")
+    }
+}
+
+#[derive(Debug)]
+struct SendToClosedChannel;
+
+impl fmt::Display for SendToClosedChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cannot send to a closed channel")
+    }
+}
+
+pub(crate) fn load_channel(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<Channel>::new("Channel")
+            .add_static("new", || Channel::with_capacity(1))
+            .add_static("new", Channel::with_capacity)
+            .add_function("send", Channel::send)
+            .add_function("receive", Channel::receive)
+            .add_function("try_receive", |channel: &mut Channel| {
+                channel.buffer.pop_front()
+            })
+            .add_function("close", |channel: &mut Channel| channel.closed = true)
+            .add_function("is_closed", |channel: &Channel| channel.closed)
+            .add_function("len", |channel: &Channel| channel.buffer.len())
+            .add_function("capacity", |channel: &Channel| channel.capacity)
+            .add_builtin_trait_function(iterator::HasNext, Channel::has_next)
+            .add_builtin_trait_function(iterator::Next, |channel: &mut Channel| {
+                channel.buffer.pop_front()
+            }),
+    )?;
+
+    Ok(())
+}
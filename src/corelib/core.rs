@@ -1,31 +1,61 @@
 //! Core functions.
 
-use std::{fmt, fmt::Write};
+use std::{cell::RefCell, fmt, rc::Rc};
 
 use crate::{
-    corelib::{gc::load_gc, iterators::load_iterators},
-    Arguments, Engine, Error, MicaResultExt, Value,
+    corelib::{
+        channel::load_channel, coroutine::load_coroutine, deque::load_deque, gc::load_gc,
+        iterators::load_iterators, json::load_json, option::load_option,
+        persistent::load_persistent, reflection::load_reflection, tasks::load_tasks,
+        test::comparison, test::load_test,
+    },
+    Arguments, Engine, EngineContext, Error, FunctionParameterCount, MicaResultExt, Output, Value,
 };
 
-fn print(arguments: Arguments) {
-    for value in arguments.array() {
-        print!("{value}");
+/// Returns the arguments as values, which keep them alive while their `to_string` methods are
+/// called.
+fn values(arguments: &Arguments) -> Vec<Value> {
+    arguments
+        .array()
+        .iter()
+        .copied()
+        .map(Value::from_raw)
+        .collect()
+}
+
+fn print(
+    output: &RefCell<Output>,
+    context: &mut EngineContext,
+    arguments: Arguments,
+) -> Result<(), Error> {
+    let mut line = String::new();
+    for value in values(&arguments) {
+        line.push_str(&context.display(&value)?);
     }
-    println!();
+    line.push('\n');
+    output.borrow_mut().write(&line);
+    Ok(())
 }
 
-fn debug(arguments: Arguments) {
-    for (i, value) in arguments.array().iter().enumerate() {
+fn debug(
+    output: &RefCell<Output>,
+    context: &mut EngineContext,
+    arguments: Arguments,
+) -> Result<(), Error> {
+    let mut line = String::new();
+    for (i, value) in values(&arguments).iter().enumerate() {
         if i > 0 {
-            print!("\t");
+            line.push('\t');
         }
-        print!("{value:?}");
+        line.push_str(&context.debug(value)?);
     }
-    println!();
+    line.push('\n');
+    output.borrow_mut().write(&line);
+    Ok(())
 }
 
-fn string(x: Value) -> String {
-    x.to_string()
+fn string(context: &mut EngineContext, x: Value) -> Result<String, Error> {
+    context.display(&x)
 }
 
 #[derive(Debug)]
@@ -39,19 +69,24 @@ impl fmt::Display for UserError {
 
 impl std::error::Error for UserError {}
 
-fn error(arguments: Arguments) -> Result<(), UserError> {
+fn error(context: &mut EngineContext, arguments: Arguments) -> Result<(), Error> {
     let mut message = String::new();
-    for value in arguments.array() {
-        write!(message, "{value}").unwrap();
+    for value in values(&arguments) {
+        message.push_str(&context.display(&value)?);
     }
-    Err(UserError(message))
+    Err(Error::User(Box::new(UserError(message))))
 }
 
-fn assert(condition: Value, message: Option<Value>) -> Result<Value, Error> {
+fn assert(
+    context: &mut EngineContext,
+    condition: Value,
+    message: Option<Value>,
+) -> Result<Value, Error> {
     if condition.is_falsy() {
-        let message = message
-            .map(|value| value.to_string())
-            .unwrap_or_else(|| "assertion failed".to_owned());
+        let message = match message {
+            Some(value) => context.display(&value)?,
+            None => "assertion failed".to_owned(),
+        };
         Err(message).mica()
     } else {
         Ok(condition)
@@ -60,14 +95,42 @@ fn assert(condition: Value, message: Option<Value>) -> Result<Value, Error> {
 
 /// Loads the core library into the engine.
 pub(crate) fn load_core(engine: &mut Engine) -> Result<(), Error> {
-    engine.add_function("print", print)?;
-    engine.add_function("debug", debug)?;
+    let output = Rc::clone(&engine.output);
+    engine.add_function(
+        "print",
+        move |context: &mut EngineContext, arguments: Arguments| print(&output, context, arguments),
+    )?;
+    let output = Rc::clone(&engine.output);
+    engine.add_function(
+        "debug",
+        move |context: &mut EngineContext, arguments: Arguments| debug(&output, context, arguments),
+    )?;
     engine.add_function("string", string)?;
     engine.add_function("error", error)?;
     engine.add_function("assert", assert)?;
+    engine.add_raw_function(
+        "assert_eq",
+        FunctionParameterCount::Fixed(2),
+        comparison(true, "expected"),
+    )?;
 
+    load_channel(engine)?;
+    load_coroutine(engine)?;
+    load_deque(engine)?;
     load_gc(engine)?;
     load_iterators(engine)?;
+    load_json(engine)?;
+    load_option(engine)?;
+    load_persistent(engine)?;
+    load_reflection(engine)?;
+    load_tasks(engine)?;
+    load_test(engine)?;
+    #[cfg(feature = "io")]
+    crate::corelib::io::load_io(engine)?;
+    #[cfg(feature = "os")]
+    crate::corelib::os::load_os(engine)?;
+    #[cfg(feature = "regex")]
+    crate::corelib::regex::load_regex(engine)?;
 
     Ok(())
 }
@@ -0,0 +1,76 @@
+//! The `Fiber` type, and the `yield` function for suspending fibers.
+
+use crate::{
+    call_fiber,
+    ll::{
+        bytecode::Control,
+        value::{self, RawValue, ValueKind},
+        vm::{self, Coroutine},
+    },
+    wrap_in_language_error, Arguments, Engine, Error, FunctionParameterCount, MethodParameterCount,
+    RawFunctionKind, TypeBuilder, UserData,
+};
+
+impl UserData for Coroutine {}
+
+pub(crate) fn load_coroutine(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<Coroutine>::new("Fiber")
+            .add_raw_static(
+                "new",
+                MethodParameterCount::from_count_with_self(2),
+                RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                    let arguments = Arguments::new(args, library);
+                    let function = *arguments.nth(0).unwrap();
+                    if function.kind() != ValueKind::Function {
+                        return wrap_in_language_error(Err(Error::ArgumentTypeMismatch {
+                            index: 0,
+                            expected: "Function".into(),
+                            got: function.type_name(),
+                        }));
+                    }
+                    let fiber = wrap_in_language_error(call_fiber(vec![function]))?;
+                    // The instance dispatch table is reached through the type, which is what the
+                    // static is called on.
+                    let type_dtable = vm::Fiber::get_dispatch_table(*arguments.raw_self(), library);
+                    let instance_dtable = type_dtable.instance.unwrap();
+                    let coroutine: Box<dyn value::UserData> =
+                        Box::new(Coroutine::new(instance_dtable, fiber));
+                    Ok(RawValue::from(gc.allocate(coroutine)))
+                })),
+            )
+            // The first resume starts running the function, so there's no `yield` the argument
+            // could be returned from; it's discarded instead.
+            .add_raw_function(
+                "resume",
+                MethodParameterCount::from_count_with_self(1),
+                RawFunctionKind::Control(Control::Resume),
+            )
+            .add_raw_function(
+                "resume",
+                MethodParameterCount::from_count_with_self(2),
+                RawFunctionKind::Control(Control::Resume),
+            )
+            .add_raw_function(
+                "is_finished",
+                MethodParameterCount::from_count_with_self(1),
+                RawFunctionKind::Foreign(Box::new(|library, _, args| {
+                    let arguments = Arguments::new(args, library);
+                    // SAFETY: This function is only ever added to the dispatch table of fibers.
+                    let coroutine = unsafe {
+                        arguments
+                            .raw_self()
+                            .downcast_user_data_unchecked::<Coroutine>()
+                    };
+                    Ok(RawValue::from(coroutine.is_finished()))
+                })),
+            ),
+    )?;
+    engine.add_raw_function(
+        "yield",
+        FunctionParameterCount::Varargs,
+        RawFunctionKind::Control(Control::Yield),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! The `Deque` type.
+
+use std::{collections::VecDeque, fmt};
+
+use crate::{
+    builtin_traits::iterator,
+    ll::{
+        error::LanguageErrorKind,
+        value::{checked_index, RawValue},
+    },
+    Arguments, Engine, Error, IntoValue, MethodParameterCount, MicaLanguageResultExt,
+    MicaResultExt, RawFunctionKind, SelfFromRawValue, TypeBuilder, UserData,
+};
+
+/// A double-ended queue, which can efficiently add and remove values at both of its ends.
+#[derive(Default)]
+struct Deque(VecDeque<RawValue>);
+
+impl Deque {
+    fn index(&self, index: RawValue) -> Result<RawValue, LanguageErrorKind> {
+        let index = checked_index(index, self.0.len())?;
+        Ok(self.0[index])
+    }
+
+    fn set_index(
+        &mut self,
+        index: RawValue,
+        value: RawValue,
+    ) -> Result<RawValue, LanguageErrorKind> {
+        let index = checked_index(index, self.0.len())?;
+        self.0[index] = value;
+        Ok(value)
+    }
+}
+
+impl UserData for Deque {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        self.0.iter().copied().for_each(visit);
+    }
+}
+
+/// An iterator over the values in a deque, from front to back.
+struct DequeIter {
+    deque: RawValue,
+    i: usize,
+    len: usize,
+}
+
+impl DequeIter {
+    fn has_next(&self) -> bool {
+        self.i < self.len
+    }
+
+    fn next(&mut self) -> Result<Option<RawValue>, Error> {
+        let (deque, _guard) = unsafe { Deque::self_from_raw_value(&self.deque) }?;
+        if deque.0.len() != self.len {
+            return Err(LenChangedDuringIteration {
+                was: self.len,
+                became: deque.0.len(),
+            })
+            .mica();
+        }
+        let value = deque.0.get(self.i).copied();
+        if value.is_some() {
+            self.i += 1;
+        }
+        Ok(value)
+    }
+}
+
+impl UserData for DequeIter {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        visit(self.deque);
+    }
+}
+
+#[derive(Debug)]
+struct LenChangedDuringIteration {
+    was: usize,
+    became: usize,
+}
+
+impl fmt::Display for LenChangedDuringIteration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "deque length changed during iteration (was {}, became {})",
+            self.was, self.became
+        )
+    }
+}
+
+impl std::error::Error for LenChangedDuringIteration {}
+
+pub(crate) fn load_deque(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<Deque>::new("Deque")
+            .add_static("new", Deque::default)
+            .add_function("len", |deque: &Deque| deque.0.len())
+            .add_function("is_empty", |deque: &Deque| deque.0.is_empty())
+            .add_function("push_front", |deque: &mut Deque, x: RawValue| {
+                deque.0.push_front(x)
+            })
+            .add_function("push_back", |deque: &mut Deque, x: RawValue| {
+                deque.0.push_back(x)
+            })
+            .add_function("pop_front", |deque: &mut Deque| deque.0.pop_front())
+            .add_function("pop_back", |deque: &mut Deque| deque.0.pop_back())
+            .add_function("front", |deque: &Deque| deque.0.front().copied())
+            .add_function("back", |deque: &Deque| deque.0.back().copied())
+            .add_function("contains", |deque: &Deque, x: RawValue| {
+                deque.0.contains(&x)
+            })
+            .add_function("clear", |deque: &mut Deque| deque.0.clear())
+            .add_function("index", Deque::index)
+            .add_function("set_index", Deque::set_index)
+            // The iterator needs to refer back to the deque, which only raw functions have access
+            // to.
+            .add_raw_function(
+                "iter",
+                MethodParameterCount::from_count_with_self(1),
+                RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                    let arguments = Arguments::new(args, library);
+                    let deque = *arguments.raw_self();
+                    let (this, _guard) =
+                        unsafe { Deque::self_from_raw_value(&deque) }.to_language_error()?;
+                    let iter = DequeIter {
+                        deque,
+                        i: 0,
+                        len: this.0.len(),
+                    };
+                    Ok(iter.into_value_with_engine_state(library, gc).to_raw(gc))
+                })),
+            ),
+    )?;
+    engine.add_type(
+        TypeBuilder::<DequeIter>::new("DequeIter")
+            .add_builtin_trait_function(iterator::HasNext, DequeIter::has_next)
+            .add_builtin_trait_function(iterator::Next, DequeIter::next),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+//! The `File` and `Path` types, available with the `io` feature.
+
+use std::{
+    fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path as StdPath,
+};
+
+use crate::{builtin_traits::iterator, Engine, Error, TypeBuilder, UserData};
+
+/// Namespace for reading and writing whole files.
+struct File;
+
+impl UserData for File {}
+
+/// Namespace for manipulating paths as strings.
+struct Path;
+
+impl UserData for Path {}
+
+/// An iterator over the lines of a file, which reads the file as it goes.
+///
+/// The next line is read ahead of time, so that `has_next` can tell whether there is one.
+struct FileLines {
+    lines: io::Lines<BufReader<fs::File>>,
+    upcoming: Option<io::Result<String>>,
+}
+
+impl FileLines {
+    fn open(path: String) -> Result<Self, IoError> {
+        let file = fs::File::open(&path).map_err(|error| IoError { path, error })?;
+        let mut lines = BufReader::new(file).lines();
+        let upcoming = lines.next();
+        Ok(Self { lines, upcoming })
+    }
+
+    fn has_next(&self) -> bool {
+        self.upcoming.is_some()
+    }
+
+    fn next(&mut self) -> io::Result<Option<String>> {
+        let line = self.upcoming.take();
+        self.upcoming = self.lines.next();
+        line.transpose()
+    }
+}
+
+impl UserData for FileLines {}
+
+/// An I/O error, along with the path of the file it occurred on.
+#[derive(Debug)]
+struct IoError {
+    path: String,
+    error: io::Error,
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.error)
+    }
+}
+
+impl std::error::Error for IoError {}
+
+/// Runs an I/O operation on the file at `path`, attaching the path to any error.
+fn on_file<T>(path: String, f: impl FnOnce(&str) -> io::Result<T>) -> Result<T, IoError> {
+    f(&path).map_err(|error| IoError { path, error })
+}
+
+fn append(path: &str, text: &str) -> io::Result<()> {
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(text.as_bytes())
+}
+
+/// Converts a path component into a string, or `nil` if there is no such component.
+fn component(path: String, get: impl Fn(&StdPath) -> Option<&StdPath>) -> Option<String> {
+    get(StdPath::new(&path)).map(|p| p.to_string_lossy().into_owned())
+}
+
+pub(crate) fn load_io(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<File>::new("File")
+            .add_static("read_to_string", |path: String| {
+                on_file(path, |path| fs::read_to_string(path))
+            })
+            .add_static("write", |path: String, text: String| {
+                on_file(path, |path| fs::write(path, text))
+            })
+            .add_static("append", |path: String, text: String| {
+                on_file(path, |path| append(path, &text))
+            })
+            .add_static("lines", FileLines::open)
+            .add_static("remove", |path: String| {
+                on_file(path, |path| fs::remove_file(path))
+            }),
+    )?;
+    engine.add_type(
+        TypeBuilder::<FileLines>::new("FileLines")
+            .add_builtin_trait_function(iterator::HasNext, FileLines::has_next)
+            .add_builtin_trait_function(iterator::Next, FileLines::next),
+    )?;
+    engine.add_type(
+        TypeBuilder::<Path>::new("Path")
+            .add_static("join", |base: String, path: String| {
+                StdPath::new(&base)
+                    .join(path)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .add_static("parent", |path: String| component(path, StdPath::parent))
+            .add_static("file_name", |path: String| {
+                component(path, |p| p.file_name().map(StdPath::new))
+            })
+            .add_static("stem", |path: String| {
+                component(path, |p| p.file_stem().map(StdPath::new))
+            })
+            .add_static("extension", |path: String| {
+                component(path, |p| p.extension().map(StdPath::new))
+            })
+            .add_static("exists", |path: String| StdPath::new(&path).exists())
+            .add_static("is_file", |path: String| StdPath::new(&path).is_file())
+            .add_static("is_dir", |path: String| StdPath::new(&path).is_dir()),
+    )?;
+
+    Ok(())
+}
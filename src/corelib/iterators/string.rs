@@ -1,13 +1,14 @@
 use self::{
     bytes::load_string_bytes_iter, chars::load_string_chars_iter,
-    code_points::load_string_code_points_iter, lines::load_string_lines_iter,
-    rsplit::load_string_rsplit_iter, split::load_string_split_iter,
+    code_points::load_string_code_points_iter, graphemes::load_string_graphemes_iter,
+    lines::load_string_lines_iter, rsplit::load_string_rsplit_iter, split::load_string_split_iter,
 };
 use crate::{Engine, Error};
 
 pub mod bytes;
 pub mod chars;
 pub mod code_points;
+pub mod graphemes;
 pub mod lines;
 pub mod rsplit;
 pub mod split;
@@ -16,6 +17,7 @@ pub(crate) fn load_string_iterators(engine: &mut Engine) -> Result<(), Error> {
     load_string_bytes_iter(engine)?;
     load_string_chars_iter(engine)?;
     load_string_code_points_iter(engine)?;
+    load_string_graphemes_iter(engine)?;
     load_string_lines_iter(engine)?;
     load_string_split_iter(engine)?;
     load_string_rsplit_iter(engine)?;
@@ -27,7 +27,11 @@ impl StringBytes {
     }
 }
 
-impl UserData for StringBytes {}
+impl UserData for StringBytes {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        visit(self.string);
+    }
+}
 
 pub(crate) fn load_string_bytes_iter(engine: &mut Engine) -> Result<(), Error> {
     engine.add_type(
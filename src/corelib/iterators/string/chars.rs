@@ -29,7 +29,11 @@ impl StringChars {
     }
 }
 
-impl UserData for StringChars {}
+impl UserData for StringChars {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        visit(self.string);
+    }
+}
 
 pub(crate) fn load_string_chars_iter(engine: &mut Engine) -> Result<(), Error> {
     engine.add_type(
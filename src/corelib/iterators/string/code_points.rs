@@ -29,7 +29,11 @@ impl StringCodePoints {
     }
 }
 
-impl UserData for StringCodePoints {}
+impl UserData for StringCodePoints {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        visit(self.string);
+    }
+}
 
 pub(crate) fn load_string_code_points_iter(engine: &mut Engine) -> Result<(), Error> {
     engine.add_type(
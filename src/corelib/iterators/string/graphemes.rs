@@ -0,0 +1,54 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    builtin_traits::iterator,
+    ll::value::{RawValue, Str},
+    Engine, Error, TypeBuilder, UserData,
+};
+
+/// Iterates over extended grapheme clusters, which is what users perceive as single characters,
+/// even when they are made up of several code points (such as emoji with skin tone modifiers.)
+pub(crate) struct StringGraphemes {
+    string: RawValue,
+    index: usize,
+}
+
+impl StringGraphemes {
+    pub unsafe fn new(s: RawValue) -> Self {
+        Self {
+            string: s,
+            index: 0,
+        }
+    }
+
+    fn has_next(&self) -> bool {
+        self.index < unsafe { self.string.get_raw_string_unchecked().get().len() }
+    }
+
+    fn next(&mut self) -> Option<Str> {
+        unsafe {
+            let s = self.string.get_raw_string_unchecked().get();
+            let grapheme = s[self.index..].graphemes(true).next();
+            if let Some(grapheme) = grapheme {
+                self.index += grapheme.len();
+            }
+            grapheme.map(Str::new)
+        }
+    }
+}
+
+impl UserData for StringGraphemes {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        visit(self.string);
+    }
+}
+
+pub(crate) fn load_string_graphemes_iter(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<StringGraphemes>::new("StringGraphemes")
+            .add_builtin_trait_function(iterator::HasNext, StringGraphemes::has_next)
+            .add_builtin_trait_function(iterator::Next, StringGraphemes::next),
+    )?;
+
+    Ok(())
+}
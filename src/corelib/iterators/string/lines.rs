@@ -1,4 +1,8 @@
-use crate::{builtin_traits::iterator, ll::value::RawValue, Engine, Error, TypeBuilder, UserData};
+use crate::{
+    builtin_traits::iterator,
+    ll::value::{RawValue, Str},
+    Engine, Error, TypeBuilder, UserData,
+};
 
 pub(crate) struct StringLines {
     string: RawValue,
@@ -17,7 +21,7 @@ impl StringLines {
         self.index < unsafe { self.string.get_raw_string_unchecked().get().len() }
     }
 
-    fn next(&mut self) -> Option<String> {
+    fn next(&mut self) -> Option<Str> {
         unsafe {
             let s = self.string.get_raw_string_unchecked().get();
             let line = s[self.index..].lines().next();
@@ -31,12 +35,16 @@ impl StringLines {
             } else if s[self.index..].starts_with('\n') {
                 self.index += 1;
             }
-            line.map(String::from)
+            line.map(Str::new)
         }
     }
 }
 
-impl UserData for StringLines {}
+impl UserData for StringLines {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        visit(self.string);
+    }
+}
 
 pub(crate) fn load_string_lines_iter(engine: &mut Engine) -> Result<(), Error> {
     engine.add_type(
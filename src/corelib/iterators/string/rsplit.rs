@@ -1,15 +1,17 @@
 use crate::{
-    builtin_traits::iterator, ll::value::RawValue, Engine, Error, Gc, TypeBuilder, UserData,
+    builtin_traits::iterator,
+    ll::value::{RawValue, Str},
+    Engine, Error, Gc, TypeBuilder, UserData,
 };
 
 pub(crate) struct StringRSplit {
     string: RawValue,
-    separator: Gc<String>,
+    separator: Gc<Str>,
     index: usize,
 }
 
 impl StringRSplit {
-    pub unsafe fn new(s: RawValue, separator: Gc<String>) -> Self {
+    pub unsafe fn new(s: RawValue, separator: Gc<Str>) -> Self {
         Self {
             string: s,
             separator,
@@ -21,7 +23,7 @@ impl StringRSplit {
         self.index != 0
     }
 
-    fn next(&mut self) -> Option<String> {
+    fn next(&mut self) -> Option<Str> {
         unsafe {
             let s = self.string.get_raw_string_unchecked().get();
             let fragment = s[0..self.index].rsplit(&**self.separator).next();
@@ -29,12 +31,16 @@ impl StringRSplit {
                 self.index -= fragment.len();
                 self.index = self.index.saturating_sub(self.separator.len());
             }
-            fragment.map(String::from)
+            fragment.map(Str::new)
         }
     }
 }
 
-impl UserData for StringRSplit {}
+impl UserData for StringRSplit {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        visit(self.string);
+    }
+}
 
 pub(crate) fn load_string_rsplit_iter(engine: &mut Engine) -> Result<(), Error> {
     engine.add_type(
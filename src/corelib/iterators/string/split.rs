@@ -1,15 +1,17 @@
 use crate::{
-    builtin_traits::iterator, ll::value::RawValue, Engine, Error, Gc, TypeBuilder, UserData,
+    builtin_traits::iterator,
+    ll::value::{RawValue, Str},
+    Engine, Error, Gc, TypeBuilder, UserData,
 };
 
 pub(crate) struct StringSplit {
     string: RawValue,
-    separator: Gc<String>,
+    separator: Gc<Str>,
     index: usize,
 }
 
 impl StringSplit {
-    pub unsafe fn new(s: RawValue, separator: Gc<String>) -> Self {
+    pub unsafe fn new(s: RawValue, separator: Gc<Str>) -> Self {
         Self {
             string: s,
             separator,
@@ -21,7 +23,7 @@ impl StringSplit {
         self.index < unsafe { self.string.get_raw_string_unchecked().get().len() }
     }
 
-    fn next(&mut self) -> Option<String> {
+    fn next(&mut self) -> Option<Str> {
         unsafe {
             let s = self.string.get_raw_string_unchecked().get();
             let fragment = s[self.index..].split(&**self.separator).next();
@@ -29,12 +31,16 @@ impl StringSplit {
                 self.index += fragment.len();
                 self.index += self.separator.len();
             }
-            fragment.map(String::from)
+            fragment.map(Str::new)
         }
     }
 }
 
-impl UserData for StringSplit {}
+impl UserData for StringSplit {
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        visit(self.string);
+    }
+}
 
 pub(crate) fn load_string_split_iter(engine: &mut Engine) -> Result<(), Error> {
     engine.add_type(
@@ -0,0 +1,471 @@
+//! The `Json` type, for converting between values and JSON text.
+
+use std::{fmt, fmt::Write};
+
+use crate::{
+    ll::{
+        error::LanguageErrorKind,
+        gc::Memory,
+        value::{self, Dict, List, RawValue, Record, Str, Tuple, ValueKind},
+    },
+    Arguments, Engine, Error, Gc, IntoValue, MethodParameterCount, MicaLanguageResultExt,
+    RawFunctionKind, TypeBuilder, UserData,
+};
+
+/// How deeply arrays and objects can be nested inside each other. This keeps parsing and
+/// stringifying from overflowing the native stack, and stops stringifying values that contain
+/// themselves.
+const MAX_DEPTH: usize = 256;
+
+/// Namespace for the JSON functions.
+struct Json;
+
+impl UserData for Json {}
+
+#[derive(Debug)]
+enum JsonError {
+    Syntax {
+        message: &'static str,
+        line: usize,
+        column: usize,
+    },
+    TooDeep,
+    NotRepresentable(String),
+    NonStringKey(String),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax {
+                message,
+                line,
+                column,
+            } => write!(f, "invalid JSON: {message} at line {line}, column {column}"),
+            Self::TooDeep => write!(f, "JSON is nested more than {MAX_DEPTH} levels deep"),
+            Self::NotRepresentable(what) => write!(f, "{what} cannot be represented in JSON"),
+            Self::NonStringKey(type_name) => {
+                write!(f, "JSON object keys must be strings, but got {type_name}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// A recursive descent parser producing Mica values.
+struct Parser<'s, 'gc> {
+    input: &'s str,
+    position: usize,
+    depth: usize,
+    gc: &'gc mut Memory,
+}
+
+impl<'s, 'gc> Parser<'s, 'gc> {
+    fn new(input: &'s str, gc: &'gc mut Memory) -> Self {
+        Self {
+            input,
+            position: 0,
+            depth: 0,
+            gc,
+        }
+    }
+
+    fn error(&self, message: &'static str) -> JsonError {
+        let before = &self.input[..self.position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap().chars().count() + 1;
+        JsonError::Syntax {
+            message,
+            line,
+            column,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, message: &'static str) -> Result<(), JsonError> {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<RawValue, JsonError> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.peek().is_some() {
+            return Err(self.error("unexpected trailing characters"));
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<RawValue, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.nested(Self::parse_object),
+            Some(b'[') => self.nested(Self::parse_array),
+            Some(b'"') => {
+                let s = self.parse_string()?;
+                Ok(RawValue::from(self.gc.manage(&Gc::new(Str::from(s)))))
+            }
+            Some(b'-' | b'0'..=b'9') => self.parse_number().map(RawValue::from),
+            Some(b't') => self.parse_keyword("true", RawValue::from(true)),
+            Some(b'f') => self.parse_keyword("false", RawValue::from(false)),
+            Some(b'n') => self.parse_keyword("null", RawValue::from(())),
+            Some(_) => Err(self.error("value expected")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<RawValue, JsonError>,
+    ) -> Result<RawValue, JsonError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, value: RawValue) -> Result<RawValue, JsonError> {
+        if self.input[self.position..].starts_with(keyword) {
+            self.position += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error("value expected"))
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<f64, JsonError> {
+        let start = self.position;
+        let digits = |parser: &mut Self| {
+            let start = parser.position;
+            while let Some(b'0'..=b'9') = parser.peek() {
+                parser.position += 1;
+            }
+            parser.position > start
+        };
+
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.position += 1;
+        } else if !digits(self) {
+            return Err(self.error("digit expected"));
+        }
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            if !digits(self) {
+                return Err(self.error("digit expected after decimal point"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.position += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.position += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("digit expected in exponent"));
+            }
+        }
+        Ok(self.input[start..self.position].parse().unwrap())
+    }
+
+    fn parse_hex_escape(&mut self) -> Result<u32, JsonError> {
+        let hex = self
+            .input
+            .get(self.position..self.position + 4)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("four hexadecimal digits expected after \\u"))?;
+        self.position += 4;
+        Ok(u32::from_str_radix(hex, 16).unwrap())
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"', "string expected")?;
+        let mut s = String::new();
+        loop {
+            let rest = &self.input[self.position..];
+            let Some(c) = rest.chars().next() else {
+                return Err(self.error("unterminated string"));
+            };
+            match c {
+                '"' => {
+                    self.position += 1;
+                    return Ok(s);
+                }
+                '\\' => {
+                    self.position += 1;
+                    let escape = self.peek();
+                    self.position += 1;
+                    match escape {
+                        Some(b'"') => s.push('"'),
+                        Some(b'\\') => s.push('\\'),
+                        Some(b'/') => s.push('/'),
+                        Some(b'b') => s.push('\u{8}'),
+                        Some(b'f') => s.push('\u{c}'),
+                        Some(b'n') => s.push('\n'),
+                        Some(b'r') => s.push('\r'),
+                        Some(b't') => s.push('\t'),
+                        Some(b'u') => s.push(self.parse_unicode_escape()?),
+                        _ => {
+                            self.position -= 1;
+                            return Err(self.error("invalid escape sequence"));
+                        }
+                    }
+                }
+                '\0'..='\u{1f}' => return Err(self.error("control character in string")),
+                _ => {
+                    s.push(c);
+                    self.position += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Parses the digits of a `\u` escape, combining UTF-16 surrogate pairs into one character.
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.parse_hex_escape()?;
+        let code_point = if (0xD800..0xDC00).contains(&high) {
+            if !self.input[self.position..].starts_with("\\u") {
+                return Err(self.error("low surrogate expected"));
+            }
+            self.position += 2;
+            let low = self.parse_hex_escape()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("low surrogate expected"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code_point).ok_or_else(|| self.error("unpaired surrogate"))
+    }
+
+    fn parse_array(&mut self) -> Result<RawValue, JsonError> {
+        self.expect(b'[', "'[' expected")?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+        } else {
+            loop {
+                elements.push(self.parse_value()?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.position += 1,
+                    Some(b']') => {
+                        self.position += 1;
+                        break;
+                    }
+                    _ => return Err(self.error("',' or ']' expected")),
+                }
+            }
+        }
+        let list: Box<dyn value::UserData> = Box::new(List::new(elements));
+        Ok(RawValue::from(self.gc.allocate(list)))
+    }
+
+    fn parse_object(&mut self) -> Result<RawValue, JsonError> {
+        self.expect(b'{', "'{' expected")?;
+        let dict = Dict::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let key = self.parse_string()?;
+                let key = RawValue::from(self.gc.manage(&Gc::new(Str::from(key))));
+                self.skip_whitespace();
+                self.expect(b':', "':' expected")?;
+                let value = self.parse_value()?;
+                dict.insert(key, value);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.position += 1,
+                    Some(b'}') => {
+                        self.position += 1;
+                        break;
+                    }
+                    _ => return Err(self.error("',' or '}' expected")),
+                }
+            }
+        }
+        Ok(dict.into_value(()).to_raw(self.gc))
+    }
+}
+
+/// Writes values out as JSON text, optionally indenting nested arrays and objects.
+struct Stringifier {
+    output: String,
+    pretty: bool,
+    depth: usize,
+}
+
+impl Stringifier {
+    fn newline(&mut self) {
+        if self.pretty {
+            self.output.push('\n');
+            for _ in 0..self.depth {
+                self.output.push_str("  ");
+            }
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.output.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.output.push_str("\\\""),
+                '\\' => self.output.push_str("\\\\"),
+                '\n' => self.output.push_str("\\n"),
+                '\r' => self.output.push_str("\\r"),
+                '\t' => self.output.push_str("\\t"),
+                '\0'..='\u{1f}' => write!(self.output, "\\u{:04x}", c as u32).unwrap(),
+                _ => self.output.push(c),
+            }
+        }
+        self.output.push('"');
+    }
+
+    fn sequence<T>(
+        &mut self,
+        [open, close]: [char; 2],
+        items: impl ExactSizeIterator<Item = T>,
+        mut item: impl FnMut(&mut Self, T) -> Result<(), JsonError>,
+    ) -> Result<(), JsonError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+        self.output.push(open);
+        if items.len() > 0 {
+            self.depth += 1;
+            for (i, x) in items.enumerate() {
+                if i > 0 {
+                    self.output.push(',');
+                }
+                self.newline();
+                item(self, x)?;
+            }
+            self.depth -= 1;
+            self.newline();
+        }
+        self.output.push(close);
+        Ok(())
+    }
+
+    fn entries<'a>(
+        &mut self,
+        entries: impl ExactSizeIterator<Item = (&'a str, RawValue)>,
+    ) -> Result<(), JsonError> {
+        let separator = if self.pretty { ": " } else { ":" };
+        self.sequence(['{', '}'], entries, |s, (key, value)| {
+            s.string(key);
+            s.output.push_str(separator);
+            s.value(value)
+        })
+    }
+
+    fn value(&mut self, value: RawValue) -> Result<(), JsonError> {
+        unsafe {
+            match value.kind() {
+                ValueKind::Nil => self.output.push_str("null"),
+                ValueKind::Boolean => {
+                    write!(self.output, "{}", value.get_boolean_unchecked()).unwrap()
+                }
+                ValueKind::Number => {
+                    let x = *value.get_number_unchecked();
+                    if !x.is_finite() {
+                        return Err(JsonError::NotRepresentable(x.to_string()));
+                    }
+                    write!(self.output, "{x}").unwrap();
+                }
+                ValueKind::String => self.string(value.get_raw_string_unchecked().get()),
+                ValueKind::UserData => {
+                    let user_data = value.get_raw_user_data_unchecked().get();
+                    let any = user_data.as_any();
+                    if let Some(list) = any.downcast_ref::<List>() {
+                        let elements = list.as_slice();
+                        self.sequence(['[', ']'], elements.iter(), |s, &x| s.value(x))?;
+                    } else if let Some(tuple) = any.downcast_ref::<Tuple>() {
+                        self.sequence(['[', ']'], tuple.fields.iter(), |s, &x| s.value(x))?;
+                    } else if let Some(dict) = any.downcast_ref::<Dict>() {
+                        // Sort the keys, so that the output doesn't depend on hashing order.
+                        let mut pairs = Vec::with_capacity(dict.len());
+                        for (key, value) in dict.iter() {
+                            if key.kind() != ValueKind::String {
+                                return Err(JsonError::NonStringKey(key.type_name().into_owned()));
+                            }
+                            pairs.push((key.get_raw_string_unchecked().get().as_str(), value));
+                        }
+                        pairs.sort_unstable_by_key(|&(key, _)| key);
+                        self.entries(pairs.into_iter())?;
+                    } else if let Some(record) = any.downcast_ref::<Record>() {
+                        let names = record.record_type.identifier.split('+');
+                        let entries: Vec<_> = names.zip(record.fields.iter().copied()).collect();
+                        self.entries(entries.into_iter())?;
+                    } else {
+                        return Err(JsonError::NotRepresentable(format!(
+                            "value of type {}",
+                            value.type_name()
+                        )));
+                    }
+                }
+                ValueKind::Function | ValueKind::Struct | ValueKind::Trait => {
+                    return Err(JsonError::NotRepresentable(format!(
+                        "value of type {}",
+                        value.type_name()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn stringify(value: RawValue, pretty: bool) -> Result<String, JsonError> {
+    let mut stringifier = Stringifier {
+        output: String::new(),
+        pretty,
+        depth: 0,
+    };
+    stringifier.value(value)?;
+    Ok(stringifier.output)
+}
+
+pub(crate) fn load_json(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<Json>::new("Json")
+            .add_raw_static(
+                "parse",
+                MethodParameterCount::from_count_with_self(2),
+                RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                    let arguments = Arguments::new(args, library);
+                    let text: Gc<Str> = arguments.get(0).to_language_error()?;
+                    Parser::new(&text, gc)
+                        .parse_document()
+                        .map_err(|error| LanguageErrorKind::User(Box::new(error)))
+                })),
+            )
+            .add_static("stringify", |value: RawValue| stringify(value, false))
+            .add_static("stringify", stringify),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//! The `Option` and `Result` enums.
+//!
+//! Unlike the rest of the core library, the enums are declared in Mica source code, since their
+//! methods need to call the functions passed to them.
+
+use crate::{ll::gc::Gc, Engine, Error, Value};
+
+const SOURCE: &str = include_str!("option.mi");
+
+pub(crate) fn load_option(engine: &mut Engine) -> Result<(), Error> {
+    engine.start("option.mi", SOURCE)?.trampoline::<Value>()?;
+    engine.env.mark_script_globals_builtin();
+    link_option(engine);
+    Ok(())
+}
+
+/// Remembers the instance dispatch tables of the `Option` and `Result` types currently stored in
+/// the globals, such that Rust values can be converted into their variants.
+///
+/// This has to be done again after restoring a heap image, as restoring replaces the types.
+pub(crate) fn link_option(engine: &mut Engine) {
+    let instance_dtable = |name| match engine.get::<Value>(name) {
+        Ok(Value::Struct(s)) => unsafe { s.0.dtable() }
+            .instance
+            .map(|dtable| unsafe { Gc::from_raw(dtable) }),
+        _ => None,
+    };
+    let option = instance_dtable("Option");
+    let result = instance_dtable("Result");
+    engine.library.option_dtable = option;
+    engine.library.result_dtable = result;
+}
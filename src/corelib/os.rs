@@ -0,0 +1,99 @@
+//! The `Env` and `Process` types, available with the `os` feature.
+
+use std::{env, iter::Peekable, rc::Rc};
+
+use crate::{
+    builtin_traits::iterator,
+    ll::value::{RawValue, Str},
+    Engine, Error, IntoValue, MethodParameterCount, RawFunctionKind, TypeBuilder, UserData,
+};
+
+/// Namespace for reading environment variables.
+struct Env;
+
+impl UserData for Env {}
+
+/// Namespace for information about the running process.
+struct Process;
+
+impl UserData for Process {}
+
+/// An iterator over `(name, value)` pairs of environment variables, as they were when the iterator
+/// was created.
+struct EnvVars(Peekable<env::VarsOs>);
+
+impl EnvVars {
+    fn has_next(&mut self) -> bool {
+        self.0.peek().is_some()
+    }
+
+    fn next(&mut self) -> Option<(String, String)> {
+        self.0.next().map(|(name, value)| {
+            (
+                name.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            )
+        })
+    }
+}
+
+impl UserData for EnvVars {}
+
+#[derive(Debug)]
+struct InvalidExitCode(f64);
+
+impl std::fmt::Display for InvalidExitCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid exit code {}; exit codes must be integers from 0 to 255",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidExitCode {}
+
+pub(crate) fn load_os(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<Env>::new("Env")
+            // Variables that aren't valid Unicode are treated as if they were not set.
+            .add_static("get", |name: String| env::var(name).ok())
+            .add_static("vars", || EnvVars(env::vars_os().peekable())),
+    )?;
+    engine.add_type(
+        TypeBuilder::<EnvVars>::new("EnvVars")
+            .add_builtin_trait_function(iterator::HasNext, EnvVars::has_next)
+            .add_builtin_trait_function(iterator::Next, EnvVars::next),
+    )?;
+
+    let args = Rc::clone(&engine.process_args);
+    engine.add_type(
+        TypeBuilder::<Process>::new("Process")
+            .add_raw_static(
+                "args",
+                MethodParameterCount::from_count_with_self(1),
+                RawFunctionKind::Foreign(Box::new(move |library, gc, _| {
+                    let args: Vec<RawValue> = args
+                        .borrow()
+                        .iter()
+                        .map(|arg| RawValue::from(gc.allocate_string(Str::new(arg))))
+                        .collect();
+                    Ok(args.into_value_with_engine_state(library, gc).to_raw(gc))
+                })),
+            )
+            // This doesn't exit the host process; it's up to the host to decide what to do with
+            // the exit code.
+            .add_static("exit", |code: f64| -> Result<(), Error> {
+                // Only the lowest 8 bits of exit codes make it to the parent process on Unix, so
+                // anything outside of that range would exit with a different code than asked for.
+                if code.fract() == 0.0 && (0.0..=255.0).contains(&code) {
+                    Err(Error::Exit(code as u8))
+                } else {
+                    Err(Error::User(Box::new(InvalidExitCode(code))))
+                }
+            }),
+    )?;
+
+    Ok(())
+}
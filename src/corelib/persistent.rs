@@ -0,0 +1,107 @@
+//! The `PersistentList` and `PersistentDict` types.
+
+use std::iter::Peekable;
+
+use crate::{
+    builtin_traits::iterator, corelib::builtins::list::OutOfBounds, DictIter, Engine, Error,
+    ListIter, PersistentDict, PersistentList, SharedValue, TypeBuilder, UserData,
+};
+
+struct PersistentListIter(Peekable<ListIter>);
+
+impl UserData for PersistentListIter {}
+
+struct PersistentDictIter(Peekable<DictIter>);
+
+impl UserData for PersistentDictIter {}
+
+/// Converts a dict passed to `PersistentDict.from` into a persistent dict.
+fn dict_from(value: SharedValue) -> Result<PersistentDict, Error> {
+    match value {
+        SharedValue::Dict(dict) => Ok(dict),
+        other => Err(Error::TypeMismatch {
+            expected: "Dict".into(),
+            got: other.type_name().into(),
+        }),
+    }
+}
+
+pub(crate) fn load_persistent(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<PersistentList>::new("PersistentList")
+            .add_static("new", PersistentList::new)
+            .add_static("from", |elements: Vec<SharedValue>| {
+                elements.into_iter().collect::<PersistentList>()
+            })
+            .add_function("len", PersistentList::len)
+            .add_function("is_empty", PersistentList::is_empty)
+            .add_function("get", |list: &PersistentList, index: usize| {
+                list.get(index).cloned()
+            })
+            .add_function("first", |list: &PersistentList| list.first().cloned())
+            .add_function("last", |list: &PersistentList| list.last().cloned())
+            .add_function("contains", |list: &PersistentList, x: SharedValue| {
+                list.iter().any(|element| element == x)
+            })
+            .add_function("push", |list: &PersistentList, x: SharedValue| list.push(x))
+            .add_function("pop", PersistentList::pop)
+            .add_function(
+                "set",
+                |list: &PersistentList, index: usize, x: SharedValue| {
+                    list.set(index, x).ok_or(OutOfBounds {
+                        index,
+                        len: list.len(),
+                    })
+                },
+            )
+            .add_function("iter", |list: &PersistentList| {
+                PersistentListIter(list.iter().peekable())
+            }),
+    )?;
+    engine.add_type(
+        TypeBuilder::<PersistentListIter>::new("PersistentListIter")
+            .add_builtin_trait_function(iterator::HasNext, |iter: &mut PersistentListIter| {
+                iter.0.peek().is_some()
+            })
+            .add_builtin_trait_function(iterator::Next, |iter: &mut PersistentListIter| {
+                iter.0.next()
+            }),
+    )?;
+
+    engine.add_type(
+        TypeBuilder::<PersistentDict>::new("PersistentDict")
+            .add_static("new", PersistentDict::new)
+            .add_static("from", dict_from)
+            .add_function("len", PersistentDict::len)
+            .add_function("is_empty", PersistentDict::is_empty)
+            .add_function("get", |dict: &PersistentDict, key: SharedValue| {
+                dict.get(&key).cloned()
+            })
+            .add_function("contains_key", |dict: &PersistentDict, key: SharedValue| {
+                dict.contains_key(&key)
+            })
+            .add_function(
+                "insert",
+                |dict: &PersistentDict, key: SharedValue, value: SharedValue| {
+                    dict.insert(key, value)
+                },
+            )
+            .add_function("remove", |dict: &PersistentDict, key: SharedValue| {
+                dict.remove(&key)
+            })
+            .add_function("iter", |dict: &PersistentDict| {
+                PersistentDictIter(dict.iter().peekable())
+            }),
+    )?;
+    engine.add_type(
+        TypeBuilder::<PersistentDictIter>::new("PersistentDictIter")
+            .add_builtin_trait_function(iterator::HasNext, |iter: &mut PersistentDictIter| {
+                iter.0.peek().is_some()
+            })
+            .add_builtin_trait_function(iterator::Next, |iter: &mut PersistentDictIter| {
+                iter.0.next()
+            }),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Functions for inspecting the types of values at runtime.
+
+use crate::{
+    ll::{bytecode::Control, error::LanguageErrorKind, vm::Fiber},
+    Arguments, Engine, Error, FunctionParameterCount, IntoValue, RawFunctionKind,
+};
+
+pub(crate) fn load_reflection(engine: &mut Engine) -> Result<(), Error> {
+    // Values that don't have a type object, such as traits and types themselves, don't have a
+    // type either as far as `type_of` is concerned.
+    engine.add_raw_function(
+        "type_of",
+        FunctionParameterCount::Fixed(1),
+        RawFunctionKind::Foreign(Box::new(|library, _, args| {
+            let arguments = Arguments::new(args, library);
+            let value = arguments.nth(0).copied().unwrap_or_default();
+            Ok(Fiber::get_dispatch_table(value, library)
+                .type_value()
+                .unwrap_or_default())
+        })),
+    )?;
+    engine.add_raw_function(
+        "type_name",
+        FunctionParameterCount::Fixed(1),
+        RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let type_v = arguments.nth(0).copied().unwrap_or_default();
+            let dtable = Fiber::get_dispatch_table(type_v, library);
+            if dtable.instance.is_none() {
+                return Err(LanguageErrorKind::TypeError {
+                    expected: "type".into(),
+                    got: type_v.type_name(),
+                });
+            }
+            Ok(dtable
+                .type_name
+                .to_string()
+                .into_value_with_engine_state(library, gc)
+                .to_raw(gc))
+        })),
+    )?;
+    engine.add_raw_function(
+        "methods_of",
+        FunctionParameterCount::Fixed(1),
+        RawFunctionKind::Control(Control::MethodsOf),
+    )?;
+
+    Ok(())
+}
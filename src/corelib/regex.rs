@@ -0,0 +1,147 @@
+//! The `Regex` type, available with the `regex` feature.
+
+use crate::{
+    builtin_traits::iterator, ll::value::RawValue, Arguments, Engine, Error, IntoValue,
+    MethodParameterCount, MicaLanguageResultExt, RawFunctionKind, SelfFromRawValue, TypeBuilder,
+    UserData, Value,
+};
+
+/// A compiled regular expression.
+struct Regex(regex::Regex);
+
+impl Regex {
+    fn new(pattern: String) -> Result<Self, regex::Error> {
+        regex::Regex::new(&pattern).map(Self)
+    }
+
+    fn is_match(&self, haystack: String) -> bool {
+        self.0.is_match(&haystack)
+    }
+
+    fn find(&self, haystack: String) -> Option<String> {
+        self.0.find(&haystack).map(|m| m.as_str().to_owned())
+    }
+
+    fn replace(&self, haystack: String, replacement: String) -> String {
+        self.0.replace(&haystack, replacement.as_str()).into_owned()
+    }
+
+    fn replace_all(&self, haystack: String, replacement: String) -> String {
+        self.0
+            .replace_all(&haystack, replacement.as_str())
+            .into_owned()
+    }
+
+    fn matches(&self, haystack: String) -> RegexMatches {
+        RegexMatches::new(self.0.clone(), haystack)
+    }
+}
+
+impl UserData for Regex {}
+
+/// An iterator over the non-overlapping matches of a regex in a string.
+///
+/// The next match is always found ahead of time, so that `has_next` can tell whether there is one.
+struct RegexMatches {
+    regex: regex::Regex,
+    haystack: String,
+    /// The byte index at which the search for the match after `upcoming` starts.
+    index: usize,
+    /// The end of the last match, used to skip empty matches directly after it.
+    last_match_end: Option<usize>,
+    upcoming: Option<String>,
+}
+
+impl RegexMatches {
+    fn new(regex: regex::Regex, haystack: String) -> Self {
+        let mut matches = Self {
+            regex,
+            haystack,
+            index: 0,
+            last_match_end: None,
+            upcoming: None,
+        };
+        matches.advance();
+        matches
+    }
+
+    fn advance(&mut self) {
+        self.upcoming = None;
+        while self.index <= self.haystack.len() {
+            let Some(m) = self.regex.find_at(&self.haystack, self.index) else {
+                break;
+            };
+            self.index = if m.is_empty() {
+                // Step over the next character, so that the search doesn't get stuck.
+                m.end()
+                    + self.haystack[m.end()..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8)
+            } else {
+                m.end()
+            };
+            if m.is_empty() && self.last_match_end == Some(m.end()) {
+                continue;
+            }
+            self.last_match_end = Some(m.end());
+            self.upcoming = Some(m.as_str().to_owned());
+            break;
+        }
+    }
+
+    fn has_next(&self) -> bool {
+        self.upcoming.is_some()
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let m = self.upcoming.take();
+        self.advance();
+        m
+    }
+}
+
+impl UserData for RegexMatches {}
+
+pub(crate) fn load_regex(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<Regex>::new("Regex")
+            .add_static("new", Regex::new)
+            .add_function("is_match", Regex::is_match)
+            .add_function("find", Regex::find)
+            .add_raw_function(
+                "captures",
+                MethodParameterCount::from_count_with_self(2),
+                RawFunctionKind::Foreign(Box::new(|library, gc, args| {
+                    let arguments = Arguments::new(args, library);
+                    let (regex, _guard) =
+                        unsafe { Regex::self_from_raw_value(arguments.raw_self()) }
+                            .to_language_error()?;
+                    let haystack: String = arguments.get(0).to_language_error()?;
+                    let Some(captures) = regex.0.captures(&haystack) else {
+                        return Ok(RawValue::from(()));
+                    };
+                    let groups: Vec<RawValue> = captures
+                        .iter()
+                        .map(|group| {
+                            group
+                                .map_or(Value::Nil, |m| Value::new(m.as_str()))
+                                .to_raw(gc)
+                        })
+                        .collect();
+                    Ok(groups.into_value_with_engine_state(library, gc).to_raw(gc))
+                })),
+            )
+            .add_function("replace", Regex::replace)
+            .add_function("replace_all", Regex::replace_all)
+            .add_function("matches", Regex::matches)
+            .add_function("pattern", |regex: &Regex| regex.0.as_str().to_owned()),
+    )?;
+    engine.add_type(
+        TypeBuilder::<RegexMatches>::new("RegexMatches")
+            .add_builtin_trait_function(iterator::HasNext, RegexMatches::has_next)
+            .add_builtin_trait_function(iterator::Next, RegexMatches::next),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! The `Task` and `Completion` types, and functions for running fibers concurrently and waiting
+//! on them.
+
+use std::rc::Rc;
+
+use crate::{
+    corelib::channel::Channel,
+    ll::value::{RawValue, ValueKind},
+    wrap_in_language_error, Arguments, Completion, Engine, Error, FunctionParameterCount,
+    IntoValue, Object, RawFunctionKind, TaskHandle, TypeBuilder, Value,
+};
+
+/// Checks whether the event is ready, returning its result if so. Events that aren't ready
+/// return [`Error::WouldBlock`].
+fn poll(index: usize, event: RawValue) -> Result<Value, Error> {
+    if event.kind() == ValueKind::UserData {
+        let user_data = unsafe { event.get_raw_user_data_unchecked().get() }.as_any();
+        if let Some(channel) = user_data.downcast_ref::<Object<Channel>>() {
+            let (channel, _guard) = unsafe { channel.unsafe_borrow_mut()? };
+            return channel.receive().map(Value::from_raw);
+        }
+        if let Some(task) = user_data.downcast_ref::<Object<TaskHandle>>() {
+            let (task, _guard) = unsafe { task.unsafe_borrow()? };
+            return task.0.join();
+        }
+        if let Some(completion) = user_data.downcast_ref::<Object<Completion>>() {
+            let (completion, _guard) = unsafe { completion.unsafe_borrow()? };
+            return completion.wait();
+        }
+    }
+    Err(Error::ArgumentTypeMismatch {
+        index,
+        expected: "Channel, Task, or Completion".into(),
+        got: event.type_name(),
+    })
+}
+
+/// Waits for the first of the given events to become ready, and returns a tuple of its index and
+/// result.
+fn select(arguments: Arguments) -> Result<(usize, Value), Error> {
+    arguments.expect_at_least(1)?;
+    for (index, &event) in arguments.array().iter().enumerate() {
+        match poll(index, event) {
+            Ok(value) => return Ok((index, value)),
+            Err(Error::WouldBlock) => (),
+            Err(error) => return Err(error),
+        }
+    }
+    Err(Error::WouldBlock)
+}
+
+pub(crate) fn load_tasks(engine: &mut Engine) -> Result<(), Error> {
+    engine.add_type(
+        TypeBuilder::<TaskHandle>::new("Task")
+            .add_function("is_finished", |task: &TaskHandle| task.0.is_finished())
+            .add_function("join", |task: &TaskHandle| task.0.join()),
+    )?;
+    engine.add_type(
+        TypeBuilder::<Completion>::new("Completion")
+            .add_static("new", Completion::new)
+            .add_function("is_complete", Completion::is_complete)
+            .add_function("wait", Completion::wait),
+    )?;
+    engine.add_function("select", select)?;
+
+    let spawner = Rc::clone(&engine.spawner);
+    engine.add_raw_function(
+        "spawn",
+        FunctionParameterCount::Fixed(1),
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
+            let function = args[1];
+            if function.kind() != ValueKind::Function {
+                return wrap_in_language_error(Err(Error::ArgumentTypeMismatch {
+                    index: 0,
+                    expected: "Function".into(),
+                    got: function.type_name(),
+                }));
+            }
+            let task = wrap_in_language_error(spawner.borrow_mut().spawn(gc, function))?;
+            Ok(TaskHandle(task)
+                .into_value_with_engine_state(library, gc)
+                .to_raw(gc))
+        })),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! The `Test` type.
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{
+    ll::{error::LanguageErrorKind, value::RawValue},
+    Arguments, Engine, Error, MethodParameterCount, MicaLanguageResultExt, RawFunctionKind,
+    TestSuite, TypeBuilder, UserData, Value,
+};
+
+struct TestType;
+
+impl UserData for TestType {}
+
+#[derive(Debug)]
+struct AssertionFailed(String);
+
+impl fmt::Display for AssertionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "assertion failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for AssertionFailed {}
+
+fn ensure_function(value: Value) -> Result<Value, Error> {
+    match value {
+        Value::Function(_) => Ok(value),
+        other => Err(Error::TypeMismatch {
+            expected: "Function".into(),
+            got: other.type_name().into_owned().into(),
+        }),
+    }
+}
+
+/// Creates an assertion comparing its two arguments using `==`. The assertion fails if the result
+/// of the comparison is not `expect_equal`.
+///
+/// This is also used for the global `assert_eq` function, which takes the same arguments but is
+/// not a static function.
+pub(crate) fn comparison(expect_equal: bool, message: &'static str) -> RawFunctionKind {
+    RawFunctionKind::Foreign(Box::new(move |library, _, args| {
+        // Raw functions receive however many arguments they were called with.
+        Arguments::new(args, library)
+            .expect_exactly(2)
+            .to_language_error()?;
+        let (actual, expected) = (args[1], args[2]);
+        if (actual == expected) == expect_equal {
+            Ok(RawValue::from(()))
+        } else {
+            Err(LanguageErrorKind::User(Box::new(AssertionFailed(format!(
+                "{message} {expected:?}, got {actual:?}"
+            )))))
+        }
+    }))
+}
+
+fn register(
+    tests: &Rc<RefCell<TestSuite>>,
+    add: fn(&mut TestSuite, Value),
+) -> impl Fn(Value) -> Result<(), Error> {
+    let tests = Rc::clone(tests);
+    move |function| {
+        add(&mut tests.borrow_mut(), ensure_function(function)?);
+        Ok(())
+    }
+}
+
+pub(crate) fn load_test(engine: &mut Engine) -> Result<(), Error> {
+    let tests = Rc::clone(&engine.tests);
+    engine.add_type(
+        TypeBuilder::<TestType>::new("Test")
+            .add_static("case", {
+                let tests = Rc::clone(&tests);
+                move |name: String, function: Value| -> Result<(), Error> {
+                    let function = ensure_function(function)?;
+                    tests.borrow_mut().cases.push((name, function));
+                    Ok(())
+                }
+            })
+            .add_static(
+                "setup",
+                register(&tests, |tests, function| tests.setup.push(function)),
+            )
+            .add_static(
+                "teardown",
+                register(&tests, |tests, function| tests.teardown.push(function)),
+            )
+            .add_raw_static(
+                "assert_eq",
+                MethodParameterCount::from_count_with_self(3),
+                comparison(true, "expected"),
+            )
+            .add_raw_static(
+                "assert_ne",
+                MethodParameterCount::from_count_with_self(3),
+                comparison(false, "expected anything but"),
+            )
+            .add_static("fail", |message: Value| -> Result<(), AssertionFailed> {
+                Err(AssertionFailed(message.to_string()))
+            }),
+    )?;
+
+    Ok(())
+}
@@ -1,26 +1,66 @@
 //! This module contains the safe, high-level API of Mica.
 
 pub mod builtin_traits;
+mod context;
 mod corelib;
+mod debugger;
+mod display;
 mod engine;
 mod error;
 mod fiber;
 mod function;
+mod image;
+mod interceptor;
+mod introspection;
+mod module;
+pub mod operators;
+mod persistent;
+mod program;
+mod reload;
+mod scheduler;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod session;
+mod testing;
+#[cfg(feature = "tooling")]
+pub mod tooling;
+mod trace;
 mod traits;
+mod typed_function;
 mod types;
 mod userdata;
 mod value;
 
 mod generated;
 
+pub use context::*;
 pub use corelib::*;
+pub use debugger::*;
 pub use engine::*;
 pub use error::*;
 pub use fiber::*;
 pub use function::*;
+pub use image::*;
+pub use interceptor::*;
+pub use introspection::*;
+pub use module::*;
+pub use persistent::*;
+pub use program::*;
+pub use scheduler::*;
+pub use session::*;
+pub use testing::*;
+pub use trace::*;
 pub use traits::*;
+pub use typed_function::*;
 pub use types::*;
 pub use userdata::*;
 pub use value::*;
 
-pub use crate::ll::gc::Gc;
+pub use crate::ll::gc::{
+    AllocationChange, AllocationDiff, AllocationSnapshot, AllocationStats, Gc, GcMode, GcStats,
+    Interning, Leak, LeakReport,
+};
+pub use crate::ll::lexer::FrontMatter;
+pub use crate::ll::value::Str;
+#[cfg(feature = "derive")]
+pub use mica_derive::{methods, MicaType};
@@ -1,8 +1,10 @@
 //! Types for representing implementations of built-in traits.
 //!
-//! Note that when implementing a built-in trait, _you must ensure_ that you implement _all_ the
-//! methods. The high-level API does not guard against leaving some methods unimplemented, like the
-//! language interpreter does.
+//! Note that when implementing a built-in trait function by function, _you must ensure_ that you
+//! implement _all_ the methods. The high-level API does not guard against leaving some methods
+//! unimplemented, like the language interpreter does. Helpers such as
+//! [`TypeBuilder::implement_iterator`][crate::TypeBuilder::implement_iterator] implement a whole
+//! trait at once, and should be preferred where available.
 
 use crate::ffvariants::{FallibleSelf, ImmutableSelf, InfallibleSelf, MutableSelf};
 
@@ -0,0 +1,132 @@
+use std::fmt;
+
+use crate::{
+    argument_stack, call_fiber,
+    ll::{
+        bytecode::{Environment, Library},
+        gc::Memory,
+        vm::{self, Globals},
+    },
+    Error, IntoArguments, IntoValue, OptionalGlobalName, TryFromValue, Value,
+};
+
+/// Access to the engine from within a foreign function.
+///
+/// Foreign functions whose first parameter is an `&mut EngineContext` receive one each time they
+/// are called. It can be used to create values, read and write globals, and call Mica functions
+/// passed in as arguments.
+///
+/// # Examples
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use mica::{Engine, EngineContext, Error, Value};
+///
+/// let mut engine = Engine::new();
+/// engine.add_function(
+///     "twice",
+///     |context: &mut EngineContext, f: Value, x: Value| -> Result<Value, Error> {
+///         let once: Value = context.call(f.clone(), (x,))?;
+///         context.call(f, (once,))
+///     },
+/// )?;
+/// let result: f64 = engine
+///     .start("example.mi", "twice(func (x) = x * 3, 2)")?
+///     .trampoline()?;
+/// assert_eq!(result, 18.0);
+/// # Ok(())
+/// # }
+/// ```
+pub struct EngineContext<'a> {
+    pub(crate) env: &'a Environment,
+    pub(crate) library: &'a Library,
+    pub(crate) globals: &'a mut Globals,
+    pub(crate) gc: &'a mut Memory,
+}
+
+impl<'a> EngineContext<'a> {
+    pub(crate) fn new(
+        env: &'a Environment,
+        library: &'a Library,
+        globals: &'a mut Globals,
+        gc: &'a mut Memory,
+    ) -> Self {
+        Self {
+            env,
+            library,
+            globals,
+            gc,
+        }
+    }
+
+    /// Creates a value, like [`Engine::create_value`][crate::Engine::create_value].
+    pub fn create_value(&mut self, from: impl IntoValue) -> Value {
+        from.into_value_with_engine_state(self.library, self.gc)
+    }
+
+    /// Returns the value of a global variable, or `nil` if it's not set.
+    pub fn get<T>(&self, id: impl OptionalGlobalName) -> Result<T, Error>
+    where
+        T: TryFromValue,
+    {
+        let value = id
+            .try_to_global_id(self.env)
+            .map(|id| Value::from_raw(self.globals.get(id.0)))
+            .unwrap_or(Value::Nil);
+        T::try_from_value(&value, self.library)
+    }
+
+    /// Sets a global variable, and returns whether it was set.
+    ///
+    /// New globals cannot be declared while scripts are running, so this only sets globals that
+    /// have been declared already, by a script or [`Engine::global_id`][crate::Engine::global_id].
+    pub fn set(&mut self, id: impl OptionalGlobalName, value: impl IntoValue) -> bool {
+        let Some(id) = id.try_to_global_id(self.env) else {
+            return false;
+        };
+        let value = value
+            .into_value_with_engine_state(self.library, self.gc)
+            .to_raw(self.gc);
+        self.globals.set(id.0, value);
+        true
+    }
+
+    /// Calls the provided function with the given arguments, and returns what it evaluated to.
+    ///
+    /// The function runs in a fiber of its own until it finishes; values it `yield`s are
+    /// discarded. If it blocks, [`Error::WouldBlock`] is returned, which makes the calling foreign
+    /// function block too when it is propagated, such that it is called again later. Similarly,
+    /// [`Error::FuelExhausted`] is returned if the engine runs out of fuel, and the foreign
+    /// function is called again once the engine is refueled.
+    pub fn call<T>(&mut self, function: Value, arguments: impl IntoArguments) -> Result<T, Error>
+    where
+        T: TryFromValue,
+    {
+        let stack = argument_stack(function, arguments, self.library, self.gc);
+        let result = self.run(call_fiber(stack)?)?;
+        T::try_from_value(&result, self.library)
+    }
+
+    /// Runs a fiber until it finishes, and returns the last value it evaluated to.
+    pub(crate) fn run(&mut self, mut fiber: vm::Fiber) -> Result<Value, Error> {
+        let mut result = Value::Nil;
+        while !fiber.halted() {
+            let value = fiber
+                .interpret(self.env, self.library, self.globals, self.gc)
+                .map_err(|error| Error::from_fiber(error, &fiber))?;
+            if fiber.blocked() {
+                return Err(Error::WouldBlock);
+            }
+            if fiber.out_of_fuel() {
+                return Err(Error::FuelExhausted);
+            }
+            result = Value::from_raw(value);
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Debug for EngineContext<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EngineContext").finish_non_exhaustive()
+    }
+}
@@ -1,7 +1,7 @@
 use std::fmt::Debug;
 
 use crate::{
-    ll::value::{Dict, RawValue, Record, Tuple},
+    ll::value::{Dict, RawValue, Record, Str, Tuple},
     Engine, Error, TypeBuilder,
 };
 
@@ -20,7 +20,7 @@ pub trait CoreLibrary: 'static + Debug + Clone {
     fn define_number(&self, builder: TypeBuilder<f64>) -> TypeBuilder<f64>;
 
     /// Defines the `String` type using the given type builder.
-    fn define_string(&self, builder: TypeBuilder<String>) -> TypeBuilder<String>;
+    fn define_string(&self, builder: TypeBuilder<Str>) -> TypeBuilder<Str>;
 
     /// Defines the `List` type using the given type builder.
     fn define_list(&self, builder: TypeBuilder<Vec<RawValue>>) -> TypeBuilder<Vec<RawValue>>;
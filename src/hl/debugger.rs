@@ -0,0 +1,261 @@
+//! Hooks into script execution, for implementing debuggers.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt,
+    rc::Rc,
+};
+
+use crate::{
+    ll::{
+        bytecode::{Environment, Function},
+        error::StackTraceEntry,
+        value::RawValue,
+        vm::{self, CallFrame, DebugHook, Globals},
+    },
+    stack_trace, Value,
+};
+
+/// Callbacks an [`Engine`][crate::Engine] makes while executing scripts, which can be used to
+/// implement debuggers.
+///
+/// Hooks are installed using [`Engine::set_debugger_hooks`][crate::Engine::set_debugger_hooks].
+pub trait DebuggerHooks {
+    /// Called before a fiber executes the first instruction on a new line of source code. This
+    /// also happens when a function is entered, and when a loop jumps back to a line that was
+    /// already executed.
+    ///
+    /// The fiber does not continue executing until this returns, so a debugger can suspend
+    /// execution (eg. upon hitting a breakpoint) by blocking here until the user resumes it.
+    fn on_line(&mut self, fiber: &PausedFiber<'_>);
+
+    /// Called when the fiber calls a function. For functions written in Mica, the function's frame
+    /// is already on the fiber's [`stack`][PausedFiber::stack]; for functions implemented by the
+    /// host, it is not, since they don't have frames of their own.
+    ///
+    /// Every call is eventually matched by an [`on_return`][Self::on_return], except when the
+    /// fiber stops due to an error.
+    fn on_call(&mut self, fiber: &PausedFiber<'_>, function_name: &str) {
+        let _ = (fiber, function_name);
+    }
+
+    /// Called when the function most recently passed to [`on_call`][Self::on_call] returns.
+    fn on_return(&mut self, fiber: &PausedFiber<'_>) {
+        let _ = fiber;
+    }
+
+    /// Called before a fiber executes each instruction. This is useful for stepping through
+    /// code at a finer granularity than lines, but it makes execution very slow.
+    fn on_instruction(&mut self, fiber: &PausedFiber<'_>) {
+        let _ = fiber;
+    }
+
+    /// Called after [`on_line`][Self::on_line] when the fiber reaches a line with a
+    /// [breakpoint][crate::Engine::set_breakpoint] on it. By default, this
+    /// [pauses][PausedFiber::pause] the fiber.
+    fn on_breakpoint(&mut self, fiber: &PausedFiber<'_>) {
+        fiber.pause();
+    }
+}
+
+/// Lines with breakpoints on them, by module name.
+pub(crate) type Breakpoints = HashMap<Rc<str>, HashSet<u32>>;
+
+/// A fiber whose execution is suspended while a debugger inspects it.
+pub struct PausedFiber<'f> {
+    fiber: &'f vm::Fiber,
+    env: &'f Environment,
+    globals: &'f Globals,
+    pause_requested: Cell<bool>,
+}
+
+impl<'f> PausedFiber<'f> {
+    pub(crate) fn new(fiber: &'f vm::Fiber, env: &'f Environment, globals: &'f Globals) -> Self {
+        Self {
+            fiber,
+            env,
+            globals,
+            pause_requested: Cell::new(false),
+        }
+    }
+
+    /// Pauses the fiber before it executes its next instruction. Instead of blocking inside a
+    /// hook, this returns control to whoever resumed the fiber: [`Fiber::resume`] returns `nil`,
+    /// and the fiber's [state][crate::Fiber::state] becomes [`FiberState::Paused`] until it's
+    /// resumed again.
+    ///
+    /// Only fibers that are running can be paused; this does nothing when called on a fiber
+    /// obtained through [`Fiber::inspect`].
+    ///
+    /// [`Fiber::resume`]: crate::Fiber::resume
+    /// [`Fiber::inspect`]: crate::Fiber::inspect
+    /// [`FiberState::Paused`]: crate::FiberState::Paused
+    pub fn pause(&self) {
+        self.pause_requested.set(true);
+    }
+
+    /// Returns the number of function calls the fiber is currently nested in. This is `0` at the
+    /// top level of a script.
+    pub fn call_depth(&self) -> usize {
+        self.fiber.call_depth()
+    }
+
+    /// Returns the fiber's call stack, starting with the innermost frame (the function that's
+    /// currently executing.) Frame indices passed to [`locals`][Self::locals] and
+    /// [`upvalues`][Self::upvalues] are indices into this vector.
+    pub fn stack(&self) -> Vec<StackTraceEntry> {
+        stack_trace(self.fiber, self.env)
+    }
+
+    /// Returns the local variables in scope in the given stack frame. Returns an empty vector if
+    /// there's no such frame.
+    pub fn locals(&self, frame: usize) -> Vec<(Rc<str>, Value)> {
+        self.with_frame(frame, |frame| user_variables(frame.local_variables()))
+    }
+
+    /// Returns the variables captured by the function executing in the given stack frame.
+    /// Returns an empty vector if there's no such frame.
+    pub fn upvalues(&self, frame: usize) -> Vec<(Rc<str>, Value)> {
+        self.with_frame(frame, |frame| user_variables(frame.upvalues()))
+    }
+
+    /// Returns the global variables declared by scripts, sorted by name. Globals set by the host
+    /// application are not included.
+    pub fn globals(&self) -> Vec<(Rc<str>, Value)> {
+        let mut globals: Vec<(Rc<str>, RawValue)> = self
+            .env
+            .global_names()
+            .filter_map(|name| {
+                let slot = self.env.get_global(name)?;
+                if self.env.is_global_builtin(slot) {
+                    return None;
+                }
+                Some((Rc::from(name), self.globals.get(slot)))
+            })
+            .collect();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+        user_variables(globals)
+    }
+
+    fn with_frame(
+        &self,
+        index: usize,
+        f: impl FnOnce(&CallFrame<'_>) -> Vec<(Rc<str>, Value)>,
+    ) -> Vec<(Rc<str>, Value)> {
+        self.fiber
+            .call_frames()
+            .get(index)
+            .map(f)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for PausedFiber<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PausedFiber").finish_non_exhaustive()
+    }
+}
+
+/// Converts variables to safe values, leaving out the ones the compiler creates for itself (such
+/// as `<receiver>`.)
+fn user_variables(variables: Vec<(Rc<str>, RawValue)>) -> Vec<(Rc<str>, Value)> {
+    variables
+        .into_iter()
+        .filter(|(name, _)| !name.starts_with('<'))
+        .map(|(name, value)| (name, Value::from_raw(value)))
+        .collect()
+}
+
+/// Adapts [`DebuggerHooks`] to the VM's [`DebugHook`].
+pub(crate) struct DebugHookAdapter<H> {
+    pub(crate) hooks: H,
+    pub(crate) breakpoints: Rc<RefCell<Breakpoints>>,
+    pause_requested: bool,
+}
+
+impl<H> DebugHookAdapter<H> {
+    pub(crate) fn new(hooks: H, breakpoints: Rc<RefCell<Breakpoints>>) -> Self {
+        Self {
+            hooks,
+            breakpoints,
+            pause_requested: false,
+        }
+    }
+
+    /// Returns whether there's a breakpoint on the line the fiber is about to execute.
+    fn is_on_breakpoint(&self, fiber: &vm::Fiber) -> bool {
+        let breakpoints = self.breakpoints.borrow();
+        if breakpoints.is_empty() {
+            return false;
+        }
+        let frames = fiber.call_frames();
+        let Some(frame) = frames.first() else {
+            return false;
+        };
+        breakpoints
+            .get(&frame.chunk().module_name)
+            .is_some_and(|lines| lines.contains(&frame.location().line))
+    }
+
+    fn with_fiber(
+        &mut self,
+        fiber: &vm::Fiber,
+        env: &Environment,
+        globals: &Globals,
+        f: impl FnOnce(&mut Self, &PausedFiber<'_>),
+    ) {
+        let fiber = PausedFiber::new(fiber, env, globals);
+        f(self, &fiber);
+        self.pause_requested |= fiber.pause_requested.get();
+    }
+}
+
+impl<H> DebugHook for DebugHookAdapter<H>
+where
+    H: DebuggerHooks,
+{
+    fn on_line(&mut self, fiber: &vm::Fiber, env: &Environment, globals: &Globals) {
+        let on_breakpoint = self.is_on_breakpoint(fiber);
+        self.with_fiber(fiber, env, globals, |adapter, fiber| {
+            adapter.hooks.on_line(fiber);
+            if on_breakpoint {
+                adapter.hooks.on_breakpoint(fiber);
+            }
+        });
+    }
+
+    fn on_call(
+        &mut self,
+        fiber: &vm::Fiber,
+        env: &Environment,
+        globals: &Globals,
+        function: &Function,
+    ) {
+        self.with_fiber(fiber, env, globals, |adapter, fiber| {
+            adapter.hooks.on_call(fiber, &function.name)
+        });
+    }
+
+    fn on_return(&mut self, fiber: &vm::Fiber, env: &Environment, globals: &Globals) {
+        self.with_fiber(fiber, env, globals, |adapter, fiber| {
+            adapter.hooks.on_return(fiber)
+        });
+    }
+
+    fn on_instruction(&mut self, fiber: &vm::Fiber, env: &Environment, globals: &Globals) {
+        self.with_fiber(fiber, env, globals, |adapter, fiber| {
+            adapter.hooks.on_instruction(fiber)
+        });
+    }
+
+    fn take_pause_request(&mut self) -> bool {
+        std::mem::take(&mut self.pause_requested)
+    }
+}
+
+impl<H> fmt::Debug for DebugHookAdapter<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugHookAdapter").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,291 @@
+//! Formatting values the way scripts see them.
+
+use std::{fmt::Write, rc::Rc};
+
+use crate::{
+    call_method_chunk,
+    ll::{
+        bytecode::{MethodParameterCount, MethodSignature},
+        value::{Dict, List, RawValue, Record, Tuple, ValueKind},
+        vm,
+    },
+    Engine, EngineContext, Error, TryFromValue, Value,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Display,
+    Debug,
+}
+
+impl Style {
+    /// Returns the names of the methods that can override how a value is formatted, in the order
+    /// they're looked up.
+    fn method_names(self) -> &'static [&'static str] {
+        match self {
+            Style::Display => &["to_string"],
+            Style::Debug => &["to_debug", "to_string"],
+        }
+    }
+}
+
+struct Formatter<'c, 'a> {
+    context: &'c mut EngineContext<'a>,
+    output: String,
+    /// The lists, dicts, tuples, and records that are currently being formatted, so that values
+    /// containing themselves can be printed.
+    ancestors: Vec<*const ()>,
+}
+
+impl Formatter<'_, '_> {
+    fn format(&mut self, value: &Value, style: Style) -> Result<(), Error> {
+        let raw = value.to_raw_unmanaged();
+        match raw.kind() {
+            ValueKind::String if style == Style::Display => write!(self.output, "{raw}").unwrap(),
+            ValueKind::Struct | ValueKind::UserData => {
+                if let Some(string) = self.call_override(value, raw, style)? {
+                    self.output.push_str(&string);
+                } else {
+                    self.format_compound(raw, style)?;
+                }
+            }
+            _ => write!(self.output, "{raw:?}").unwrap(),
+        }
+        Ok(())
+    }
+
+    /// Calls the value's `to_string` or `to_debug` method if it has one, and returns what it
+    /// returned.
+    fn call_override(
+        &mut self,
+        value: &Value,
+        raw: RawValue,
+        style: Style,
+    ) -> Result<Option<String>, Error> {
+        let library = self.context.library;
+        let dtable = vm::Fiber::get_dispatch_table(raw, library);
+        let parameter_count = MethodParameterCount::from_count_with_self(1);
+        let method_index = style.method_names().iter().find_map(|&name| {
+            let signature = MethodSignature::new(Rc::from(name), parameter_count);
+            self.context
+                .env
+                .get_method_index(&signature)
+                .filter(|&index| dtable.get_method(index).is_some())
+        });
+        let Some(method_index) = method_index else {
+            return Ok(None);
+        };
+        let stack = vec![value.to_raw(self.context.gc)];
+        let chunk = call_method_chunk(method_index, parameter_count);
+        let result = self.context.run(vm::Fiber::new(chunk, stack))?;
+        String::try_from_value(&result, library).map(Some)
+    }
+
+    fn format_compound(&mut self, raw: RawValue, style: Style) -> Result<(), Error> {
+        if raw.kind() == ValueKind::Struct {
+            let s = unsafe { raw.get_raw_struct_unchecked().get() };
+            match unsafe { s.enum_variant() } {
+                Some((variant, values)) => {
+                    write!(self.output, "{}.{variant}", unsafe { s.dtable() }.type_name).unwrap();
+                    if !values.is_empty() {
+                        let values = to_values(values);
+                        self.output.push('(');
+                        self.format_elements(&values, Style::Debug)?;
+                        self.output.push(')');
+                    }
+                }
+                None => write!(self.output, "{raw:?}").unwrap(),
+            }
+            return Ok(());
+        }
+
+        let user_data = unsafe { raw.get_raw_user_data_unchecked().get() };
+        let address = user_data.as_any() as *const _ as *const ();
+        let any = user_data.as_any();
+        let cycle = self.ancestors.contains(&address);
+        self.ancestors.push(address);
+        let result = if let Some(list) = any.downcast_ref::<List>() {
+            self.format_list(list, style, cycle)
+        } else if let Some(dict) = any.downcast_ref::<Dict>() {
+            self.format_dict(dict, style, cycle)
+        } else if let Some(tuple) = any.downcast_ref::<Tuple>() {
+            self.format_tuple(tuple, cycle)
+        } else if let Some(record) = any.downcast_ref::<Record>() {
+            self.format_record(record, cycle)
+        } else {
+            write!(self.output, "{raw:?}").unwrap();
+            Ok(())
+        };
+        self.ancestors.pop();
+        result
+    }
+
+    fn format_elements(&mut self, elements: &[Value], style: Style) -> Result<(), Error> {
+        for (i, element) in elements.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.format(element, style)?;
+        }
+        Ok(())
+    }
+
+    fn format_list(&mut self, list: &List, style: Style, cycle: bool) -> Result<(), Error> {
+        if cycle {
+            self.output.push_str("[...]");
+        } else {
+            let elements = to_values(unsafe { list.as_slice() });
+            self.output.push('[');
+            self.format_elements(&elements, style)?;
+            self.output.push(']');
+        }
+        Ok(())
+    }
+
+    fn format_tuple(&mut self, tuple: &Tuple, cycle: bool) -> Result<(), Error> {
+        if cycle {
+            self.output.push_str("(...)");
+        } else {
+            let fields = to_values(&tuple.fields);
+            self.output.push('(');
+            self.format_elements(&fields, Style::Debug)?;
+            if fields.len() == 1 {
+                self.output.push(',');
+            }
+            self.output.push(')');
+        }
+        Ok(())
+    }
+
+    fn format_dict(&mut self, dict: &Dict, style: Style, cycle: bool) -> Result<(), Error> {
+        if dict.is_empty() {
+            self.output.push_str("[:]");
+        } else if cycle {
+            self.output.push_str("[...]");
+        } else {
+            let pairs: Vec<_> = unsafe { dict.iter() }
+                .map(|(key, value)| (Value::from_raw(key), Value::from_raw(value)))
+                .collect();
+            self.output.push('[');
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    self.output.push_str(", ");
+                }
+                self.format(key, style)?;
+                self.output.push_str(": ");
+                self.format(value, style)?;
+            }
+            self.output.push(']');
+        }
+        Ok(())
+    }
+
+    fn format_record(&mut self, record: &Record, cycle: bool) -> Result<(), Error> {
+        if record.fields.is_empty() {
+            self.output.push_str("{}");
+        } else if cycle {
+            self.output.push_str("{...}");
+        } else {
+            let fields = to_values(&record.fields);
+            let field_names = record.record_type.identifier.split('+');
+            self.output.push_str("{ ");
+            for (i, (name, value)) in field_names.zip(&fields).enumerate() {
+                if i > 0 {
+                    self.output.push_str(", ");
+                }
+                write!(self.output, "{name}: ").unwrap();
+                self.format(value, Style::Debug)?;
+            }
+            self.output.push_str(" }");
+        }
+        Ok(())
+    }
+}
+
+/// Converts elements of a compound value to [`Value`]s, so that they stay alive even if the value
+/// is modified by a `to_string` method while they're being formatted.
+fn to_values(raw: &[RawValue]) -> Vec<Value> {
+    raw.iter().copied().map(Value::from_raw).collect()
+}
+
+fn context(engine: &mut Engine) -> EngineContext<'_> {
+    EngineContext::new(
+        &engine.env,
+        &engine.library,
+        &mut engine.globals,
+        &mut engine.gc,
+    )
+}
+
+impl EngineContext<'_> {
+    fn format(&mut self, value: &Value, style: Style) -> Result<String, Error> {
+        let mut formatter = Formatter {
+            context: self,
+            output: String::new(),
+            ancestors: Vec::new(),
+        };
+        formatter.format(value, style)?;
+        Ok(formatter.output)
+    }
+
+    /// Formats a value into a string the same way `print` does.
+    ///
+    /// Structs and user data with a `to_string` method are formatted by calling it, including
+    /// when they're nested inside lists, dicts, tuples, or records. Lists and dicts that contain
+    /// themselves are formatted with `[...]` in place of the cycle.
+    ///
+    /// # Errors
+    /// Errors from calling `to_string` methods are returned, as well as a type mismatch if one of
+    /// them doesn't return a string.
+    pub fn display(&mut self, value: &Value) -> Result<String, Error> {
+        self.format(value, Style::Display)
+    }
+
+    /// Formats a value into a string the same way `debug` does.
+    ///
+    /// This is like [`display`][Self::display], except strings are quoted, and structs and user
+    /// data are formatted by their `to_debug` method, falling back to `to_string` if they don't
+    /// have one. Elements of tuples and records are always formatted this way, even when
+    /// displaying them.
+    pub fn debug(&mut self, value: &Value) -> Result<String, Error> {
+        self.format(value, Style::Debug)
+    }
+}
+
+impl Value {
+    /// Formats the value into a string the same way `print` does inside scripts, calling
+    /// `to_string` methods where values have them. See [`EngineContext::display`].
+    ///
+    /// Unlike the [`Display`][std::fmt::Display] implementation, which doesn't have access to
+    /// the engine, this formats structs and user data the way scripts see them.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let points: Value = engine
+    ///     .start(
+    ///         "points.mi",
+    ///         r#" struct Point impl
+    ///                 func new(x, y) constructor = do @x = x  @y = y end
+    ///                 func to_string() = "(".cat(@x.to_string).cat(" ").cat(@y.to_string).cat(")")
+    ///             end
+    ///             [Point.new(1, 2), Point.new(3, 4)] "#,
+    ///     )?
+    ///     .trampoline()?;
+    /// assert_eq!(points.display(&mut engine)?, "[(1 2), (3 4)]");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn display(&self, engine: &mut Engine) -> Result<String, Error> {
+        context(engine).display(self)
+    }
+
+    /// Formats the value into a string the same way `debug` does inside scripts. See
+    /// [`EngineContext::debug`].
+    pub fn display_debug(&self, engine: &mut Engine) -> Result<String, Error> {
+        context(engine).debug(self)
+    }
+}
@@ -1,28 +1,45 @@
-use std::{any::Any, collections::HashMap, fmt, fmt::Debug, ops::Deref, rc::Rc};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    fmt::{Debug, Write},
+    mem,
+    num::NonZeroUsize,
+    ops::{Deref, Range},
+    rc::Rc,
+};
 
 /// The implementation of a raw foreign function.
 pub use crate::ll::bytecode::ForeignFunction as RawForeignFunction;
 /// The kind of a raw function.
 pub use crate::ll::bytecode::FunctionKind as RawFunctionKind;
+pub use crate::ll::bytecode::{Arithmetic, Limits};
 use crate::{
     corelib, create_trait_value, ffvariants,
+    hl::{image, reload::ModuleFunctions},
     ll::{
-        ast::DumpAst,
+        ast::{DumpAst, NodeKind},
         bytecode,
         bytecode::{
             BuiltinDispatchTableGenerator, BuiltinDispatchTables, BuiltinTraits, Chunk,
             DispatchTable, Environment, Function, FunctionKind, GlobalIndex, Library, MethodIndex,
-            Opcode, Opr24,
+            Opcode, Opr24, Visibility,
         },
         codegen::{self, CodeGenerator},
-        gc::{Gc, Memory},
-        lexer::Lexer,
+        error::{LanguageError, LanguageWarning, Lint, Severity},
+        gc::{AllocationSnapshot, Gc, GcMode, GcStats, Interning, LeakReport, Memory},
+        lexer::{FrontMatter, Lexer},
+        lint::{builtin_lint_passes, run_lint_passes},
         parser::Parser,
         value::{Closure, RawValue},
-        vm::{self, Globals},
+        vm::{self, Globals, StackPool},
     },
-    BuiltType, CoreLibrary, Error, Fiber, ForeignFunction, FunctionParameterCount, IntoValue,
-    MethodParameterCount, MicaResultExt, TraitBuilder, TryFromValue, TypeBuilder, UserData, Value,
+    push_arguments, Breakpoints, BuiltType, CoreLibrary, DebugHookAdapter, DebuggerHooks, Error,
+    Fiber, ForeignFunction, FunctionParameterCount, HeapImage, Interception, InterceptorId,
+    Interceptors, IntoArguments, IntoModule, IntoValue, Introspection, LazyModules, LintPass,
+    MethodParameterCount, MicaResultExt, Output, Spawner, TestReport, TestSuite, Trace, Tracer,
+    TraitBuilder, TryFromValue, TypeBuilder, UserData, Value,
 };
 
 /// Options for debugging the language implementation.
@@ -43,7 +60,23 @@ pub struct Engine {
     pub(crate) globals: Globals,
     // This field is needed to keep all builtin dispatch tables alive for longer than `gc`.
     pub(crate) gc: Memory,
+    /// Stacks left behind by fibers started through the engine, for reuse by the next ones.
+    pub(crate) stacks: StackPool,
     debug_options: DebugOptions,
+    lint_severities: HashMap<Lint, Severity>,
+    lint_passes: Vec<Box<dyn LintPass>>,
+    compilation_threads: NonZeroUsize,
+    lazy_modules: LazyModules,
+    pub(crate) sources: Sources,
+    pub(crate) module_functions: ModuleFunctions,
+    pub(crate) spawner: Rc<RefCell<Spawner>>,
+    tracer: Option<Rc<RefCell<Tracer>>>,
+    interceptors: Rc<RefCell<Interceptors>>,
+    breakpoints: Rc<RefCell<Breakpoints>>,
+    pub(crate) tests: Rc<RefCell<TestSuite>>,
+    pub(crate) output: Rc<RefCell<Output>>,
+    #[cfg(feature = "os")]
+    pub(crate) process_args: Rc<RefCell<Vec<String>>>,
 }
 
 impl Engine {
@@ -122,11 +155,15 @@ impl Engine {
                 builtin_traits: &BuiltinTraits,
                 size: usize,
             ) -> Gc<DispatchTable> {
-                self.corelib
+                let traced = mem::replace(&mut env.trace_foreign_functions, false);
+                let dtable = self
+                    .corelib
                     .define_tuple(size, TypeBuilder::new(format!("Tuple({size})")))
                     .build(env, gc, builtin_traits)
                     .expect("corelib declares too many methods")
-                    .instance_dtable
+                    .instance_dtable;
+                env.trace_foreign_functions = traced;
+                dtable
             }
 
             fn generate_record(
@@ -145,11 +182,15 @@ impl Engine {
                 } else {
                     format!("Record{{{}}}", identifier.replace('+', ", "))
                 };
-                self.corelib
+                let traced = mem::replace(&mut env.trace_foreign_functions, false);
+                let dtable = self
+                    .corelib
                     .define_record(fields, TypeBuilder::new(type_name))
                     .build(env, gc, builtin_traits)
                     .expect("corelib declares too many methods")
-                    .instance_dtable
+                    .instance_dtable;
+                env.trace_foreign_functions = traced;
+                dtable
             }
         }
 
@@ -191,79 +232,1040 @@ impl Engine {
             builtin_traits,
         );
 
-        // Pre-generate tuples up to size 8, which is what you can receive or produce automagically
-        // using the high-level API. If we don't do that and the VM receives any of those tuples,
-        // it'll cause panics whenever the VM tries to touch them.
-        for size in 0..=8 {
-            library.generate_tuple(&mut env, &mut gc, size);
+        // Pre-generate tuples up to size 8, which is what you can receive or produce automagically
+        // using the high-level API. If we don't do that and the VM receives any of those tuples,
+        // it'll cause panics whenever the VM tries to touch them.
+        for size in 0..=8 {
+            library.generate_tuple(&mut env, &mut gc, size);
+        }
+
+        let iterator = create_trait_value(&mut env, &mut gc, library.builtin_traits.iterator);
+
+        let mut engine = Self {
+            env,
+            library,
+            globals: Globals::new(),
+            gc,
+            stacks: StackPool::default(),
+            debug_options,
+            lint_severities: HashMap::new(),
+            lint_passes: builtin_lint_passes(),
+            compilation_threads: NonZeroUsize::MIN,
+            lazy_modules: LazyModules::default(),
+            sources: Sources::default(),
+            module_functions: ModuleFunctions::default(),
+            spawner: Rc::default(),
+            tracer: None,
+            interceptors: Default::default(),
+            breakpoints: Default::default(),
+            tests: Rc::default(),
+            output: Rc::default(),
+            #[cfg(feature = "os")]
+            process_args: Rc::default(),
+        };
+        // Unwrapping here is fine because at this point we haven't got quite that many globals
+        // registered to overflow an Opr24.
+        engine.set_built_type(&nil).unwrap();
+        engine.set_built_type(&boolean).unwrap();
+        engine.set_built_type(&number).unwrap();
+        engine.set_built_type(&string).unwrap();
+        engine.set_built_type(&list).unwrap();
+        engine.set("Iterator", iterator).unwrap();
+
+        corelib
+            .load(&mut engine)
+            .expect("corelib failed to load (in CoreLibrary::load)");
+        engine.env.trace_foreign_functions = true;
+
+        engine
+    }
+
+    /// Compiles a script without executing it.
+    ///
+    /// The filename is used for reporting compilation errors and in stack traces.
+    ///
+    /// Compiling untrusted source code is safe: malformed input results in an error rather than a
+    /// panic, and code that's nested too deeply is rejected before it can overflow the stack.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// let script = engine.compile("example.mi", "2 + 2")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compile(
+        &mut self,
+        filename: impl AsRef<str>,
+        source: impl Into<String>,
+    ) -> Result<Script<'_>, Error> {
+        let module_name: Rc<str> = Rc::from(filename.as_ref());
+        let source: String = source.into();
+        let source_for_snippets: Rc<str> = Rc::from(source.as_str());
+        let with_snippets = |mut errors: Vec<LanguageError>| {
+            for error in &mut errors {
+                error.attach_snippet(&source_for_snippets);
+            }
+            Error::from(errors)
+        };
+
+        let lexer = Lexer::new(Rc::clone(&module_name), source);
+        let (ast, root_node) = Parser::new(lexer).parse().map_err(with_snippets)?;
+        for import in ast.nodes_of_kind(NodeKind::Import) {
+            let (name, _) = ast.node_pair(import);
+            self.load_module(ast.string(name).unwrap())?;
+        }
+        let first_function = self.env.functions().len();
+        if self.debug_options.dump_ast {
+            eprintln!("Mica - AST dump:");
+            eprintln!("{:?}", DumpAst(&ast, root_node));
+        }
+
+        let mut all_warnings = run_lint_passes(&mut self.lint_passes, &self.env, &ast, root_node);
+        let generator = CodeGenerator::new(
+            Rc::clone(&module_name),
+            &mut self.env,
+            &mut self.library,
+            &mut self.gc,
+        );
+        let (main_chunk, codegen_warnings) = if self.compilation_threads.get() > 1 {
+            generator.generate_parallel(&ast, root_node, self.compilation_threads)
+        } else {
+            generator.generate(&ast, root_node)
+        }
+        .map_err(|error| with_snippets(vec![error]))?;
+        all_warnings.extend(codegen_warnings);
+        let mut warnings = Vec::new();
+        let mut denied = Vec::new();
+        for warning in all_warnings {
+            match self.lint_severity(warning.kind.lint()) {
+                Severity::Allow => (),
+                Severity::Warn => warnings.push(warning),
+                Severity::Deny => denied.push(warning.into_error()),
+            }
+        }
+        if !denied.is_empty() {
+            return Err(with_snippets(denied));
+        }
+        // Function bodies are generated before the code surrounding them, and lint passes run
+        // separately from code generation, so warnings come out of order.
+        warnings.sort_by_key(|warning| warning.location.byte);
+        if self.debug_options.dump_bytecode {
+            eprintln!("Mica - global environment:");
+            eprintln!("{:#?}", self.env);
+            eprintln!("Mica - main chunk disassembly:");
+            eprintln!("{main_chunk:#?}");
+        }
+
+        let functions = first_function..self.env.functions().len();
+        self.module_functions
+            .compiled(&module_name, functions.clone());
+        self.sources.add(module_name, source_for_snippets);
+        let front_matter = ast.front_matter().clone();
+        Ok(Script {
+            engine: self,
+            main_chunk,
+            functions,
+            warnings,
+            front_matter,
+        })
+    }
+
+    /// Sets how severe warnings belonging to the given lint are. By default, lints have their
+    /// [default severity][Lint::default_severity].
+    ///
+    /// Warnings whose lint is set to [`Severity::Deny`] make compilation fail.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Lint, Severity};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_lint_severity(Lint::UnreachableCode, Severity::Deny);
+    /// assert!(engine.compile("example.mi", "func f() = do return 1 2 end").is_err());
+    /// ```
+    pub fn set_lint_severity(&mut self, lint: Lint, severity: Severity) {
+        self.lint_severities.insert(lint, severity);
+    }
+
+    /// Adds a lint pass, which checks the syntax tree of every script compiled from now on.
+    ///
+    /// Warnings emitted by lint passes are subject to [lint severities][Self::set_lint_severity]
+    /// just like the built-in ones.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{
+    ///     ll::ast::{NodeId, NodeKind},
+    ///     Engine, LanguageWarningKind, Lint, LintContext, LintPass, Severity,
+    /// };
+    ///
+    /// /// Disallows `while` loops, which could hang the host application.
+    /// #[derive(Debug)]
+    /// struct NoWhile;
+    ///
+    /// impl LintPass for NoWhile {
+    ///     fn check_node(&mut self, cx: &mut LintContext<'_>, node: NodeId) {
+    ///         if cx.ast().kind(node) == NodeKind::While {
+    ///             cx.warn(node, LanguageWarningKind::Custom {
+    ///                 lint: "no_while",
+    ///                 message: "while loops are not allowed".into(),
+    ///             });
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_lint_pass(NoWhile);
+    /// engine.set_lint_severity(Lint::Custom("no_while"), Severity::Deny);
+    /// assert!(engine.compile("example.mi", "while true do end").is_err());
+    /// ```
+    pub fn add_lint_pass(&mut self, pass: impl LintPass + 'static) {
+        self.lint_passes.push(Box::new(pass));
+    }
+
+    /// Returns how severe warnings belonging to the given lint are.
+    pub fn lint_severity(&self, lint: Lint) -> Severity {
+        self.lint_severities
+            .get(&lint)
+            .copied()
+            .unwrap_or(lint.default_severity())
+    }
+
+    /// Sets how many threads compiling a script may use. By default, scripts are compiled on the
+    /// calling thread only.
+    ///
+    /// With more than one thread, the bodies of the script's top-level functions are generated in
+    /// parallel, after the rest of the script. This speeds up compiling scripts with many
+    /// functions, and does not change how they behave. Functions that declare types or import
+    /// modules are still compiled on the calling thread.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::num::NonZeroUsize;
+    ///
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_compilation_threads(NonZeroUsize::new(4).unwrap());
+    /// let sum: f64 = engine
+    ///     .start(
+    ///         "example.mi",
+    ///         r#"
+    ///             func double(x) = x * 2
+    ///             func quadruple(x) = double(double(x))
+    ///             quadruple(1) + double(1)
+    ///         "#,
+    ///     )?
+    ///     .trampoline()?;
+    /// assert_eq!(sum, 6.0);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_compilation_threads(&mut self, threads: NonZeroUsize) {
+        self.compilation_threads = threads;
+    }
+
+    /// Returns how many threads compiling a script may use.
+    pub fn compilation_threads(&self) -> NonZeroUsize {
+        self.compilation_threads
+    }
+
+    /// Sets how arithmetic operators behave when their result is not a finite number. By default,
+    /// IEEE 754 semantics are followed.
+    ///
+    /// The setting applies to all scripts run by the engine, including ones that were compiled
+    /// before it was changed.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Arithmetic, Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let infinity: f64 = engine.start("example.mi", "1 / 0")?.trampoline()?;
+    /// assert_eq!(infinity, f64::INFINITY);
+    ///
+    /// engine.set_arithmetic(Arithmetic::Checked);
+    /// let result: Result<Value, _> = engine.start("example.mi", "1 / 0")?.trampoline();
+    /// assert!(result.is_err());
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.library.arithmetic = arithmetic;
+    }
+
+    /// Returns how arithmetic operators behave when their result is not a finite number.
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.library.arithmetic
+    }
+
+    /// Sets whether scripts are allowed to add methods to built-in types such as `Number` and
+    /// `String`, using `impl` blocks. This is disabled by default.
+    ///
+    /// Extensions apply to the whole engine, so all scripts share the methods added by any of them.
+    /// They may only contain instance and static functions, and cannot replace existing methods.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_builtin_extensions(true);
+    /// let seconds: f64 = engine
+    ///     .start(
+    ///         "example.mi",
+    ///         r#"
+    ///             Number impl
+    ///                 func minutes() = self * 60
+    ///             end
+    ///             (2).minutes
+    ///         "#,
+    ///     )?
+    ///     .trampoline()?;
+    /// assert_eq!(seconds, 120.0);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_builtin_extensions(&mut self, allowed: bool) {
+        self.library.builtin_extensions = allowed;
+    }
+
+    /// Sets the command line arguments returned by `Process.args` in scripts. Only available with
+    /// the `os` feature.
+    ///
+    /// These are not taken from the process automatically, because the host usually has arguments
+    /// of its own that scripts are not interested in.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_process_args(["--verbose", "input.txt"]);
+    /// let count: f64 = engine.start("example.mi", "Process.args.len")?.trampoline()?;
+    /// assert_eq!(count, 2.0);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    #[cfg(feature = "os")]
+    pub fn set_process_args(&mut self, args: impl IntoIterator<Item = impl Into<String>>) {
+        *self.process_args.borrow_mut() = args.into_iter().map(Into::into).collect();
+    }
+
+    /// Enables or disables GC stress testing mode.
+    ///
+    /// In stress mode, a full garbage collection is performed before every allocation the VM
+    /// makes and before every call to a foreign function, and in debug builds freed memory is
+    /// overwritten with garbage before being deallocated. This is very slow, but makes bugs where a
+    /// foreign function holds onto a [`RawValue`] that is not reachable by the GC surface as soon
+    /// as possible, rather than at some arbitrary point later.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_gc_stress(true);
+    /// let sum: f64 = engine
+    ///     .start("example.mi", "let xs = [1, 2, 3]\nxs.get(0) + xs.get(2)")?
+    ///     .trampoline()?;
+    /// assert_eq!(sum, 4.0);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.gc.stress = stress;
+    }
+
+    /// Enables interning of small immutable values with the given settings, or disables it if
+    /// `None` is passed. Interning is disabled by default.
+    ///
+    /// While interning is enabled, short strings and tuples of immutable values are only allocated
+    /// once: creating a value equal to one that's already alive reuses the existing object. This
+    /// is useful when many scripts create copies of the same data, such as configuration keys.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Interning, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_interning(Some(Interning::default()));
+    /// engine.set_allocation_tracking(true);
+    /// let keys: Value = engine
+    ///     .start("example.mi", r#"[("name", 1), ("name", 1), ("name", 1)]"#)?
+    ///     .trampoline()?;
+    /// let snapshot = engine.allocation_snapshot().unwrap();
+    /// assert_eq!(snapshot.get("Tuple(2)").unwrap().count, 1);
+    /// # drop(keys);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_interning(&mut self, interning: Option<Interning>) {
+        self.gc.set_interning(interning);
+    }
+
+    /// Returns the interning settings, or `None` if interning is disabled.
+    pub fn interning(&self) -> Option<Interning> {
+        self.gc.interning()
+    }
+
+    /// Returns a string with the given contents that is shared with string literals in scripts,
+    /// and with every other string interned this way.
+    ///
+    /// String literals are always interned, regardless of the [interning
+    /// settings][Self::set_interning]; a literal evaluates to the same string every time, and
+    /// literals with equal contents evaluate to the same string. Interning strings that are passed
+    /// to scripts frequently, such as dict keys, avoids allocating them anew each time, and makes
+    /// comparing them with literals a pointer comparison. Interned strings live as long as the
+    /// engine.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let key = engine.intern("name")?;
+    /// engine.set("key", key)?;
+    /// let same: bool = engine.start("example.mi", r#"key == "name""#)?.trampoline()?;
+    /// assert!(same);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn intern(&mut self, string: &str) -> Result<Value, Error> {
+        let index = self
+            .env
+            .get_or_create_string(&mut self.gc, string)
+            .map_err(|_| Error::TooManyStrings)?;
+        Ok(Value::String(self.env.get_string(index).clone()))
+    }
+
+    /// Returns whether GC stress testing mode is enabled.
+    pub fn gc_stress(&self) -> bool {
+        self.gc.stress
+    }
+
+    /// Sets how the garbage collector performs collections. The default is
+    /// [`GcMode::StopTheWorld`].
+    ///
+    /// Collecting a large heap all at once can pause a script for long enough to be noticeable,
+    /// such as when it's driving a game that must render a frame every few milliseconds.
+    /// [`GcMode::Incremental`] spreads each collection over many short pauses instead, at the cost
+    /// of some overhead while a collection is in progress. [`gc_stats`][Self::gc_stats] tells how
+    /// long the pauses were.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, GcMode};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_gc_mode(GcMode::Incremental { step_size: 64 });
+    /// let total: f64 = engine
+    ///     .start(
+    ///         "example.mi",
+    ///         r#"
+    ///             let total = 0
+    ///             let i = 1
+    ///             while i <= 10000 do
+    ///                 let pair = [i, [i]]
+    ///                 total = total + pair.get(1).get(0)
+    ///                 i = i + 1
+    ///             end
+    ///             total
+    ///         "#,
+    ///     )?
+    ///     .trampoline()?;
+    /// assert_eq!(total, 50005000.0);
+    /// let stats = engine.gc_stats();
+    /// assert!(stats.collections > 0);
+    /// assert!(stats.pauses > stats.collections);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_gc_mode(&mut self, mode: GcMode) {
+        self.gc.set_mode(mode);
+    }
+
+    /// Returns how the garbage collector performs collections.
+    pub fn gc_mode(&self) -> GcMode {
+        self.gc.mode()
+    }
+
+    /// Returns statistics about the garbage collections performed so far, such as how many there
+    /// were and how long they took.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// let _: mica::Value = engine.start("example.mi", "Gc.collect()")?.trampoline()?;
+    /// let stats = engine.gc_stats();
+    /// assert_eq!(stats.collections, 1);
+    /// assert!(stats.max_pause <= stats.total_pause);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn gc_stats(&self) -> GcStats {
+        self.gc.stats()
+    }
+
+    /// Sets the maximum number of bytes the GC may have allocated at once, or `None` to let scripts
+    /// allocate as much as they like, which is the default.
+    ///
+    /// The size of strings and of the elements of lists, dicts, and other containers counts
+    /// towards the limit. Once it's exceeded, the engine performs a collection, and if that
+    /// doesn't free enough memory, a runtime error is raised that scripts can catch.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_memory_limit(Some(1024 * 1024));
+    /// let result: Result<Value, _> = engine
+    ///     .start("example.mi", "let xs = []\nwhile true do xs.push(1) end")?
+    ///     .trampoline();
+    /// assert!(result.unwrap_err().to_string().contains("exceeds the memory limit"));
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.gc.set_limit(limit);
+    }
+
+    /// Returns the maximum number of bytes the GC may have allocated at once, or `None` if there's
+    /// no limit.
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.gc.limit()
+    }
+
+    /// Enables or disables tracking of live objects by kind, which can then be inspected with
+    /// [`allocation_snapshot`][Self::allocation_snapshot]. Tracking is disabled by default because
+    /// it slows down every allocation.
+    pub fn set_allocation_tracking(&mut self, enabled: bool) {
+        self.gc.set_allocation_tracking(enabled);
+    }
+
+    /// Returns a snapshot of the objects currently alive in the engine, with their counts and sizes
+    /// grouped by kind. User data is grouped by type name, so eg. lists and dicts are counted
+    /// separately. Returns `None` if allocation tracking is disabled.
+    ///
+    /// Objects are only removed from the snapshot once the GC frees them, so it's a good idea to
+    /// run a collection before taking a snapshot. Comparing snapshots taken over time with
+    /// [`AllocationSnapshot::diff`] shows which kinds of objects accumulate.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_allocation_tracking(true);
+    /// let before = engine.allocation_snapshot().unwrap();
+    /// let list: Value = engine
+    ///     .start("example.mi", "let xs = [1, 2, 3]\nGc.collect()\nxs")?
+    ///     .trampoline()?;
+    /// let after = engine.allocation_snapshot().unwrap();
+    /// assert_eq!(after.diff(&before).get("List").unwrap().count, 1);
+    /// # drop(list);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn allocation_snapshot(&self) -> Option<AllocationSnapshot> {
+        self.gc.allocation_snapshot()
+    }
+
+    /// Makes objects created from now on be allocated in an arena made of chunks of the given
+    /// size, or stops doing that if `None` is passed. Arena allocation is disabled by default.
+    ///
+    /// This is meant for servers that run a short script per request: allocating in an arena is
+    /// cheaper, and [`reset_heap`][Self::reset_heap] frees all of a script's objects at once
+    /// between runs, without fragmenting memory. See [`Memory::set_arena`] for details.
+    pub fn set_arena(&mut self, chunk_size: Option<usize>) {
+        self.gc.set_arena(chunk_size);
+    }
+
+    /// Returns the size of the arena's chunks, or `None` if arena allocation is disabled.
+    pub fn arena_chunk_size(&self) -> Option<usize> {
+        self.gc.arena_chunk_size()
+    }
+
+    /// Frees every object allocated in the [arena][Self::set_arena] at once, making its memory
+    /// available to the next script. Objects allocated before the arena was enabled, such as the
+    /// functions registered by the host, are left alone.
+    ///
+    /// Globals referring to objects in the arena, such as the variables and functions declared by
+    /// scripts, are set to `nil`.
+    ///
+    /// # Errors
+    /// [`Error::HeapInUse`] is returned if objects in the arena would still be in use after
+    /// resetting globals, for instance because they're referenced by a [`Value`] held by the host,
+    /// a [`Fiber`] that's still alive, or an object allocated outside of the arena. In that case
+    /// the arena and the globals are left as they are.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_arena(Some(64 * 1024));
+    /// for request in ["alice", "bob"] {
+    ///     let source = format!("let name = \"{request}\"\n\"hello, \".cat(name)");
+    ///     let greeting: String = engine.start("request.mi", source)?.trampoline()?;
+    ///     assert!(greeting.ends_with(request));
+    ///     engine.reset_heap()?;
+    /// }
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn reset_heap(&mut self) -> Result<(), Error> {
+        // Whether the heap can be reset is found out before the globals are touched, so that they
+        // stay as they were if it can't.
+        let remaining_roots = self
+            .globals
+            .iter()
+            .filter(|&global| unsafe { !Memory::is_in_arena(global) });
+        let live_objects = unsafe {
+            self.gc
+                .reachable_arena_objects(remaining_roots, &self.library)
+        };
+        if live_objects > 0 {
+            return Err(Error::HeapInUse { live_objects });
+        }
+        for global in self.globals.iter_mut() {
+            if unsafe { Memory::is_in_arena(*global) } {
+                *global = RawValue::from(());
+            }
+        }
+        unsafe { self.gc.collect(self.globals.iter(), &self.library) }
+        if self.gc.reset_arena() {
+            Ok(())
+        } else {
+            Err(Error::HeapInUse {
+                live_objects: self.gc.live_arena_objects(),
+            })
+        }
+    }
+
+    /// Sets a function to call when the engine is dropped while [`Value`]s referencing its objects
+    /// are still alive. This usually means the host application forgot to drop some values.
+    ///
+    /// The report lists the kinds of the leaked objects and how many there were of each. In debug
+    /// builds it also includes backtraces of where each object was allocated, provided the handler
+    /// was set before the allocation happened.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_leak_handler(|report| eprintln!("{report}"));
+    /// let forgotten: Value = engine.start("example.mi", "[1, 2, 3]")?.trampoline()?;
+    /// drop(engine); // prints "1 object(s) were still referenced [...] 1 × List"
+    /// # drop(forgotten);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_leak_handler(&mut self, handler: impl FnOnce(&LeakReport) + 'static) {
+        self.gc.set_leak_handler(handler);
+    }
+
+    /// Sets the maximum number of nested function calls a script can make. Exceeding it raises a
+    /// stack overflow runtime error, rather than letting runaway recursion consume all available
+    /// memory. Defaults to 10000.
+    ///
+    /// Calls made from within foreign functions and coroutines count toward the limit too. How
+    /// deeply those can nest is limited separately, by
+    /// [`set_max_reentry_depth`][Self::set_max_reentry_depth] and
+    /// [`set_max_reentry_stack`][Self::set_max_reentry_stack].
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_max_call_depth(100);
+    /// let result: Result<Value, _> = engine
+    ///     .start("example.mi", "func f(n) = if n > 0 do f(n - 1) else 0 end\nf(1000)")?
+    ///     .trampoline();
+    /// assert!(result.unwrap_err().to_string().starts_with("error: stack overflow"));
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.library.max_call_depth = depth;
+    }
+
+    /// Returns the maximum number of nested function calls a script can make.
+    pub fn max_call_depth(&self) -> usize {
+        self.library.max_call_depth
+    }
+
+    /// Sets how many times a script can be re-entered from within itself, such as by a foreign
+    /// function calling back into Mica, a `to_string` method formatting another value, or a
+    /// coroutine resuming another. Exceeding the limit raises a stack overflow runtime error.
+    /// Defaults to 1000.
+    ///
+    /// Unlike script calls, re-entries nest on the native stack, so how much of it they may use is
+    /// also limited, by [`set_max_reentry_stack`][Self::set_max_reentry_stack].
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_max_reentry_depth(4);
+    /// let result: Result<Value, _> = engine
+    ///     .start(
+    ///         "example.mi",
+    ///         "func nest(n) = Fiber.new(func () = nest(n + 1)).resume\nnest(0)",
+    ///     )?
+    ///     .trampoline();
+    /// assert!(result.unwrap_err().to_string().starts_with("error: stack overflow"));
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_max_reentry_depth(&mut self, depth: usize) {
+        self.library.max_reentry_depth = depth;
+    }
+
+    /// Returns how many times a script can be re-entered from within itself.
+    pub fn max_reentry_depth(&self) -> usize {
+        self.library.max_reentry_depth
+    }
+
+    /// Sets how many bytes of the native stack re-entering a script from within itself may use,
+    /// counting from where it was first re-entered. Exceeding the limit raises a stack overflow
+    /// runtime error instead of overflowing the native stack. Defaults to 1 MiB, which leaves room
+    /// for the embedder's own frames on threads with Rust's default stack size of 2 MiB.
+    ///
+    /// Raise this when running scripts on threads with a bigger stack, and lower it on threads
+    /// with a smaller one.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_max_reentry_stack(16 * 1024);
+    /// let result: Result<Value, _> = engine
+    ///     .start(
+    ///         "example.mi",
+    ///         "func nest(n) = Fiber.new(func () = nest(n + 1)).resume\nnest(0)",
+    ///     )?
+    ///     .trampoline();
+    /// assert!(result.unwrap_err().to_string().starts_with("error: stack overflow"));
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_max_reentry_stack(&mut self, bytes: usize) {
+        self.library.max_reentry_stack = bytes;
+    }
+
+    /// Returns how many bytes of the native stack re-entering a script from within itself may
+    /// use.
+    pub fn max_reentry_stack(&self) -> usize {
+        self.library.max_reentry_stack
+    }
+
+    /// Sets limits on the size of the values scripts can construct. Exceeding any of them raises
+    /// a runtime error. By default, there are no limits.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Limits, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_limits(Limits {
+    ///     max_list_len: 100,
+    ///     ..Limits::UNLIMITED
+    /// });
+    /// let result: Result<Value, _> = engine
+    ///     .start("example.mi", "let xs = []\nwhile true do xs.push(1) end")?
+    ///     .trampoline();
+    /// assert!(result
+    ///     .unwrap_err()
+    ///     .to_string()
+    ///     .starts_with("error: List of length 101 exceeds the limit of 100"));
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.library.limits = limits;
+    }
+
+    /// Returns the limits on the size of values scripts can construct.
+    pub fn limits(&self) -> Limits {
+        self.library.limits
+    }
+
+    /// Sets the number of instructions scripts may execute before they're interrupted, or `None`
+    /// to let them run for as long as they like, which is the default.
+    ///
+    /// Fuel is shared by all fibers running in the engine, including coroutines and functions
+    /// called by foreign functions. Once it runs out, the fiber that was running is suspended and
+    /// resuming it returns [`Error::FuelExhausted`], until the engine is refueled by calling this
+    /// again. This makes it possible to run untrusted scripts without them hanging the host.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Error, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_fuel(Some(1000));
+    /// let result: Result<Value, _> = engine.start("example.mi", "while true do end")?.trampoline();
+    /// assert!(matches!(result, Err(Error::FuelExhausted)));
+    /// assert_eq!(engine.fuel(), Some(0));
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.library.fuel.set(fuel);
+    }
+
+    /// Returns how much fuel is left, or `None` if scripts can run for as long as they like.
+    pub fn fuel(&self) -> Option<u64> {
+        self.library.fuel.get()
+    }
+
+    /// Installs hooks that are called as scripts execute, replacing any hooks that were installed
+    /// before. This is meant for implementing debuggers.
+    ///
+    /// Note that having hooks installed slows down execution considerably, as the engine has to
+    /// keep track of which line of code is being executed.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::{cell::RefCell, rc::Rc};
+    ///
+    /// use mica::{DebuggerHooks, Engine, PausedFiber, Value};
+    ///
+    /// struct LineCollector(Rc<RefCell<Vec<u32>>>);
+    ///
+    /// impl DebuggerHooks for LineCollector {
+    ///     fn on_line(&mut self, fiber: &PausedFiber<'_>) {
+    ///         self.0.borrow_mut().push(fiber.stack()[0].location.line);
+    ///     }
+    /// }
+    ///
+    /// let lines = Rc::new(RefCell::new(Vec::new()));
+    /// let mut engine = Engine::new();
+    /// engine.set_debugger_hooks(LineCollector(Rc::clone(&lines)));
+    /// let _: Value = engine.start("example.mi", "let x = 1\nlet y = 2\n")?.trampoline()?;
+    /// assert_eq!(*lines.borrow(), [1, 2]);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn set_debugger_hooks(&mut self, hooks: impl DebuggerHooks + 'static) {
+        let adapter = DebugHookAdapter::new(hooks, Rc::clone(&self.breakpoints));
+        self.library.debug_hook = Some(Rc::new(RefCell::new(adapter)));
+    }
+
+    /// Removes the hooks installed by [`set_debugger_hooks`][Self::set_debugger_hooks].
+    pub fn remove_debugger_hooks(&mut self) {
+        self.library.debug_hook = None;
+    }
+
+    /// Sets a breakpoint on a line of the module with the given name. When a fiber reaches the
+    /// line, the debugger's [`on_breakpoint`][DebuggerHooks::on_breakpoint] hook is called,
+    /// which pauses the fiber by default.
+    ///
+    /// Breakpoints only take effect while [debugger hooks][Self::set_debugger_hooks] are
+    /// installed. Lines are counted from 1, and breakpoints on lines without any code on them are
+    /// never hit.
+    pub fn set_breakpoint(&mut self, module_name: &str, line: u32) {
+        self.breakpoints
+            .borrow_mut()
+            .entry(Rc::from(module_name))
+            .or_default()
+            .insert(line);
+    }
+
+    /// Removes a breakpoint set by [`set_breakpoint`][Self::set_breakpoint]. Returns whether
+    /// there was a breakpoint on the line.
+    pub fn remove_breakpoint(&mut self, module_name: &str, line: u32) -> bool {
+        let mut breakpoints = self.breakpoints.borrow_mut();
+        let Some(lines) = breakpoints.get_mut(module_name) else {
+            return false;
+        };
+        let removed = lines.remove(&line);
+        if lines.is_empty() {
+            breakpoints.remove(module_name);
+        }
+        removed
+    }
+
+    /// Removes all breakpoints.
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.borrow_mut().clear();
+    }
+
+    /// Intercepts calls to a method on values of the type with the given name. This includes the
+    /// type itself, so both static methods and instance methods can be intercepted. The method
+    /// doesn't have to exist, which lets tests stub out APIs that are missing in the test
+    /// environment.
+    ///
+    /// The interceptor is called with the receiver and the arguments, and decides whether the
+    /// call should go through to the method, or return a value without calling the method. If
+    /// multiple interceptors match a call, the one installed most recently is asked first.
+    ///
+    /// Only calls using the usual `receiver.method(...)` syntax are intercepted.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Interception, TypeBuilder, UserData, Value};
+    ///
+    /// struct Http;
+    ///
+    /// impl UserData for Http {}
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_type(
+    ///     TypeBuilder::<Http>::new("Http")
+    ///         .add_static("get", |_: String| -> String { unimplemented!("no network in tests") }),
+    /// )?;
+    ///
+    /// let stub = engine.intercept("Http", ("get", 1), |_http, arguments| {
+    ///     let url = arguments[0].to_string();
+    ///     Ok(Interception::Return(Value::new(format!("response from {url}"))))
+    /// })?;
+    /// let response: String = engine
+    ///     .start("example.mi", r#"Http.get("example.com")"#)?
+    ///     .trampoline()?;
+    /// assert_eq!(response, "response from example.com");
+    /// engine.remove_interceptor(stub);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn intercept(
+        &mut self,
+        type_name: &str,
+        signature: impl MethodSignature,
+        interceptor: impl FnMut(&Value, &[Value]) -> Result<Interception, Error> + 'static,
+    ) -> Result<InterceptorId, Error> {
+        let method_id = self.method_id(signature)?;
+        let id = self
+            .interceptors
+            .borrow_mut()
+            .add(type_name, method_id.0, Box::new(interceptor));
+        self.library.call_interceptor = Some(Rc::clone(&self.interceptors) as _);
+        Ok(id)
+    }
+
+    /// Removes an interceptor installed by [`intercept`][Self::intercept]. Returns `false` if the
+    /// interceptor was already removed.
+    pub fn remove_interceptor(&mut self, id: InterceptorId) -> bool {
+        let mut interceptors = self.interceptors.borrow_mut();
+        let removed = interceptors.remove(id);
+        if interceptors.is_empty() {
+            // Without interceptors, method calls don't have to check for them at all.
+            self.library.call_interceptor = None;
         }
+        removed
+    }
 
-        let iterator = create_trait_value(&mut env, &mut gc, library.builtin_traits.iterator);
+    /// Starts recording a [`Trace`] of calls scripts make to functions registered by the embedder,
+    /// along with the values they return. Functions provided by the core library are deterministic
+    /// and are not recorded.
+    ///
+    /// Recording stops once [`stop_tracing`][Self::stop_tracing] is called, which returns the
+    /// recorded trace. Any trace that was being recorded or replayed before is discarded.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{SystemTime, UNIX_EPOCH};
+    ///
+    /// use mica::{Engine, Trace, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_function("now", || {
+    ///     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64()
+    /// })?;
+    ///
+    /// engine.start_recording();
+    /// let recorded: f64 = engine.start("example.mi", "now()")?.trampoline()?;
+    /// let trace: Trace = engine.stop_tracing().unwrap().to_string().parse().unwrap();
+    ///
+    /// engine.start_replaying(trace);
+    /// let replayed: f64 = engine.start("example.mi", "now()")?.trampoline()?;
+    /// assert_eq!(recorded, replayed);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn start_recording(&mut self) {
+        self.set_tracer(Tracer::Recording(Trace::new()));
+    }
 
-        let mut engine = Self {
-            env,
-            library,
-            globals: Globals::new(),
-            gc,
-            debug_options,
-        };
-        // Unwrapping here is fine because at this point we haven't got quite that many globals
-        // registered to overflow an Opr24.
-        engine.set_built_type(&nil).unwrap();
-        engine.set_built_type(&boolean).unwrap();
-        engine.set_built_type(&number).unwrap();
-        engine.set_built_type(&string).unwrap();
-        engine.set_built_type(&list).unwrap();
-        engine.set("Iterator", iterator).unwrap();
+    /// Starts replaying a trace recorded by [`start_recording`][Self::start_recording]. Instead of
+    /// calling functions registered by the embedder, scripts receive the values that were recorded
+    /// in the trace. Functions whose results couldn't be recorded, such as ones returning user
+    /// data, are called again.
+    ///
+    /// If a script calls functions in a different order than the one recorded in the trace, the
+    /// call fails with an error.
+    pub fn start_replaying(&mut self, trace: Trace) {
+        self.set_tracer(Tracer::Replaying { trace, position: 0 });
+    }
 
-        corelib
-            .load(&mut engine)
-            .expect("corelib failed to load (in CoreLibrary::load)");
+    /// Stops recording or replaying a trace, and returns the trace. Returns `None` if no trace was
+    /// being recorded or replayed.
+    pub fn stop_tracing(&mut self) -> Option<Trace> {
+        self.library.trace_hook = None;
+        let tracer = self.tracer.take()?;
+        let tracer = mem::replace(&mut *tracer.borrow_mut(), Tracer::Recording(Trace::new()));
+        Some(tracer.into_trace())
+    }
 
-        engine
+    fn set_tracer(&mut self, tracer: Tracer) {
+        let tracer = Rc::new(RefCell::new(tracer));
+        self.library.trace_hook = Some(Rc::clone(&tracer) as _);
+        self.tracer = Some(tracer);
     }
 
-    /// Compiles a script without executing it.
+    /// Saves the values of all globals, along with every object reachable from them, into a
+    /// [`HeapImage`]. Restoring the image with [`restore`][Self::restore] brings the values back,
+    /// which makes it possible to save and resume long-running scripts without writing
+    /// serialization code for their state.
     ///
-    /// The filename is used for reporting compilation errors and in stack traces.
+    /// Fibers that haven't finished running are not saved.
+    ///
+    /// # Errors
+    /// [`Error::UnsupportedInImage`] is returned if a user data value is reachable from a global,
+    /// unless it's the value of a global set by the embedder (such as a type added with
+    /// [`add_type`][Self::add_type].) Those are saved by name, and restored from the global of the
+    /// same name.
     ///
     /// # Examples
     /// ```
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// use mica::Engine;
+    /// use mica::{Engine, Value};
+    ///
+    /// let source = r#"
+    ///     struct Counter impl
+    ///         func new() constructor = @count = 0
+    ///         func increment() = do @count = @count + 1 end
+    ///     end
+    ///     let counter = Counter.new()
+    /// "#;
     ///
     /// let mut engine = Engine::new();
-    /// let script = engine.compile("example.mi", "2 + 2")?;
+    /// let _: Value = engine.start("counter.mi", source)?.trampoline()?;
+    /// let _: Value = engine.start("increment.mi", "counter.increment()")?.trampoline()?;
+    /// let image = engine.snapshot()?;
+    ///
+    /// // The engine the image is restored in has to compile the same scripts, but it doesn't have
+    /// // to run them.
+    /// let mut restored = Engine::new();
+    /// restored.compile("counter.mi", source)?;
+    /// restored.compile("increment.mi", "counter.increment()")?;
+    /// restored.restore(&image)?;
+    /// let count: f64 = restored.start("resume.mi", "counter.increment()")?.trampoline()?;
+    /// assert_eq!(count, 2.0);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn compile(
-        &mut self,
-        filename: impl AsRef<str>,
-        source: impl Into<String>,
-    ) -> Result<Script, Error> {
-        let module_name = Rc::from(filename.as_ref());
-        let lexer = Lexer::new(Rc::clone(&module_name), source.into());
-        let (ast, root_node) = Parser::new(lexer).parse()?;
-        if self.debug_options.dump_ast {
-            eprintln!("Mica - AST dump:");
-            eprintln!("{:?}", DumpAst(&ast, root_node));
-        }
-
-        let main_chunk =
-            CodeGenerator::new(module_name, &mut self.env, &mut self.library, &mut self.gc)
-                .generate(&ast, root_node)?;
-        if self.debug_options.dump_bytecode {
-            eprintln!("Mica - global environment:");
-            eprintln!("{:#?}", self.env);
-            eprintln!("Mica - main chunk disassembly:");
-            eprintln!("{main_chunk:#?}");
-        }
+    pub fn snapshot(&self) -> Result<HeapImage, Error> {
+        image::save(self)
+    }
 
-        Ok(Script {
-            engine: self,
-            main_chunk,
-        })
+    /// Restores the globals saved in a [`HeapImage`] created by [`snapshot`][Self::snapshot].
+    /// Globals that aren't in the image keep their values.
+    ///
+    /// The image does not contain any code, only references to functions. Therefore the engine
+    /// has to be set up the same way as the one that saved the image: the same functions and
+    /// types must be added, and the same scripts must be compiled, in the same order. The scripts
+    /// don't have to be run. Code compiled after that doesn't affect the image.
+    ///
+    /// # Errors
+    /// [`Error::IncompatibleImage`] is returned if the image is damaged, or any function it refers
+    /// to is different in this engine. In that case the engine is left unchanged.
+    ///
+    /// # Examples
+    /// See [`snapshot`][Self::snapshot].
+    pub fn restore(&mut self, image: &HeapImage) -> Result<(), Error> {
+        image::restore(self, image)
     }
 
     /// Compiles and starts executing a script in a fiber.
@@ -284,7 +1286,7 @@ impl Engine {
         &mut self,
         filename: impl AsRef<str>,
         source: impl Into<String>,
-    ) -> Result<Fiber, Error> {
+    ) -> Result<Fiber<'_>, Error> {
         let script = self.compile(filename, source)?;
         Ok(script.into_fiber())
     }
@@ -301,43 +1303,82 @@ impl Engine {
     ///
     /// let mut engine = Engine::new();
     /// let f: Value = engine.start("example.mi", r#" (func (x) = x + 2) "#)?.trampoline()?;
-    /// let result: f64 = engine.call(f, [Value::new(1.0)])?;
+    /// let result: f64 = engine.call(f, (1,))?;
     /// assert_eq!(result, 3.0);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn call<T>(
-        &mut self,
-        function: Value,
-        arguments: impl IntoIterator<Item = Value>,
-    ) -> Result<T, Error>
+    pub fn call<T>(&mut self, function: Value, arguments: impl IntoArguments) -> Result<T, Error>
     where
         T: TryFromValue,
     {
-        let stack: Vec<_> = Some(function)
-            .into_iter()
-            .chain(arguments)
-            .map(|x| x.to_raw(&mut self.gc))
-            .collect();
-        // Having to construct a chunk here isn't the most clean, but it's the simplest way of
-        // making the VM perform a function call. It reuses sanity checks such as ensuring
-        // `function` can actually be called.
-        let mut chunk = Chunk::new(Rc::from("(call)"));
-        chunk.emit((
-            Opcode::Call,
-            // 1 has to be subtracted from the stack length there because the VM itself adds 1 to
-            // count in the function argument.
-            Opr24::try_from(stack.len() - 1).map_err(|_| Error::TooManyArguments)?,
-        ));
-        chunk.emit(Opcode::Halt);
-        let chunk = Rc::new(chunk);
+        let mut stacks = self.stacks.take();
+        let stack = stacks.values_mut();
+        push_arguments(stack, function, arguments, &self.library, &mut self.gc);
+        let chunk = call_chunk(stack.len() - 1)?;
         let fiber = Fiber {
+            inner: vm::Fiber::with_stacks(chunk, stacks),
             engine: self,
-            inner: vm::Fiber::new(chunk, stack),
         };
         fiber.trampoline()
     }
 
+    /// Runs the tests declared with `Test.case` by the scripts run so far, and returns a report
+    /// of their results.
+    ///
+    /// Before each test, the functions registered with `Test.setup` are called, and after it, the
+    /// ones registered with `Test.teardown`. Teardown functions run even if the test fails.
+    /// Anything printed while a test is running is captured into its [result][crate::TestResult]
+    /// rather than written to stdout.
+    ///
+    /// The tests are removed from the engine once they've run, so calling this again only runs
+    /// tests declared since.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let _: Value = engine
+    ///     .start(
+    ///         "example.test.mi",
+    ///         r#"
+    ///             Test.case("addition", func () = Test.assert_eq(2 + 2, 4))
+    ///             Test.case("subtraction", func () = Test.assert_eq(2 - 2, 4))
+    ///         "#,
+    ///     )?
+    ///     .trampoline()?;
+    ///
+    /// let report = engine.run_tests();
+    /// assert_eq!(report.passed(), 1);
+    /// assert_eq!(report.failed(), 1);
+    /// assert!(!report.results()[1].passed());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_tests(&mut self) -> TestReport {
+        let suite = mem::take(&mut *self.tests.borrow_mut());
+        let mut report = TestReport::default();
+        for (name, case) in suite.cases {
+            self.output.borrow_mut().start_capturing();
+            let mut error = suite
+                .setup
+                .iter()
+                .chain(Some(&case))
+                .try_for_each(|function| self.call::<Value>(function.clone(), ()).map(|_| ()))
+                .err();
+            for function in &suite.teardown {
+                if let Err(teardown_error) = self.call::<Value>(function.clone(), ()) {
+                    error.get_or_insert(teardown_error);
+                }
+            }
+            let output = self.output.borrow_mut().stop_capturing();
+            report.push(name, output, error);
+        }
+        report
+    }
+
     /// Returns the unique ID of a method with a given name and arity.
     ///
     /// Note that there can only exist about 65 thousand unique method signatures. This is usually
@@ -385,7 +1426,7 @@ impl Engine {
     ///             end "#
     ///     )?
     ///     .trampoline()?;
-    /// let instance: Value = engine.call_method(example, ("new", 0), [])?;
+    /// let instance: Value = engine.call_method(example, ("new", 0), ())?;
     /// assert!(matches!(instance, Value::Struct(..)));
     /// # Ok(())
     /// # }
@@ -394,7 +1435,7 @@ impl Engine {
         &mut self,
         receiver: Value,
         signature: impl MethodSignature,
-        arguments: impl IntoIterator<Item = Value>,
+        arguments: impl IntoArguments,
     ) -> Result<T, Error>
     where
         T: TryFromValue,
@@ -403,11 +1444,9 @@ impl Engine {
         // Unwrapping here is fine because `to_method_id` ensures that a method with a given ID
         // exists.
         let signature = self.env.get_method_signature(method_id.0).unwrap();
-        let stack: Vec<_> = Some(receiver)
-            .into_iter()
-            .chain(arguments)
-            .map(|x| x.to_raw(&mut self.gc))
-            .collect();
+        let mut stacks = self.stacks.take();
+        let stack = stacks.values_mut();
+        push_arguments(stack, receiver, arguments, &self.library, &mut self.gc);
         let argument_count = MethodParameterCount::from_count_with_self(
             u8::try_from(stack.len()).map_err(|_| Error::TooManyArguments)?,
         );
@@ -417,16 +1456,10 @@ impl Engine {
                 got: usize::from(argument_count.to_count_without_self()),
             });
         }
-        let mut chunk = Chunk::new(Rc::from("(call)"));
-        chunk.emit((
-            Opcode::CallMethod,
-            Opr24::pack((method_id.0.to_u16(), argument_count.to_count_with_self())),
-        ));
-        chunk.emit(Opcode::Halt);
-        let chunk = Rc::new(chunk);
+        let chunk = call_method_chunk(method_id.0, argument_count);
         let fiber = Fiber {
+            inner: vm::Fiber::with_stacks(chunk, stacks),
             engine: self,
-            inner: vm::Fiber::new(chunk, stack),
         };
         fiber.trampoline()
     }
@@ -537,6 +1570,32 @@ impl Engine {
         Ok(())
     }
 
+    /// Seals a global variable, making it read-only for scripts executed by the engine.
+    ///
+    /// Scripts that declare or assign to a sealed global fail to compile. Scripts that were
+    /// compiled before the global was sealed raise a runtime error when they try to assign to it.
+    /// The embedder can still change the global's value using [`set`][`Self::set`].
+    ///
+    /// The `id` parameter can be either an `&str` or a prefetched [`global_id`][`Self::global_id`].
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.seal("String")?;
+    /// assert!(engine.compile("example.mi", "String = nil").is_err());
+    /// assert!(engine.compile("example.mi", "let String = nil").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn seal(&mut self, id: impl GlobalName) -> Result<(), Error> {
+        let id = id.to_global_id(&mut self.env)?;
+        self.env.seal_global(id.0);
+        Ok(())
+    }
+
     /// Returns the value of a global variable, or `nil` if it's not set.
     ///
     /// The `id` parameter can be either an `&str` or a prefetched [`global_id`][`Self::global_id`].
@@ -613,6 +1672,10 @@ impl Engine {
                 parameter_count: parameter_count.into(),
                 kind: f,
                 hidden_in_stack_traces: false,
+                traced: self.env.trace_foreign_functions,
+                declaration: None,
+                visibility: Visibility::Public,
+                impl_block: None,
             })
             .map_err(|_| Error::TooManyFunctions)?;
         let function = RawValue::from(self.gc.allocate(Closure {
@@ -645,11 +1708,43 @@ impl Engine {
         V: ffvariants::BareMaybeVarargs,
         F: ForeignFunction<V, ParameterCount = FunctionParameterCount>,
     {
-        self.add_raw_function(
-            name,
-            F::PARAMETER_COUNT,
-            FunctionKind::Foreign(f.into_raw_foreign_function()),
-        )
+        self.add_raw_function(name, F::PARAMETER_COUNT, f.into_raw_function_kind())
+    }
+
+    /// Hides foreign functions with the given name from the stack traces of runtime errors. Their
+    /// callers still show up in the trace, as do functions they call back into. Methods are named
+    /// after their type, like `List.get`.
+    ///
+    /// This is meant for functions that are implementation details of a library, such as helpers
+    /// that only forward their arguments elsewhere. Returns whether any foreign function with
+    /// the name exists.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Error, MicaResultExt, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_function("fail", || Err::<(), _>("something went wrong").mica())?;
+    /// assert!(engine.hide_in_stack_traces("fail"));
+    /// let error = engine.start("example.mi", "fail()")?.trampoline::<Value>().unwrap_err();
+    /// let Error::Runtime(error) = error else { panic!("runtime error expected") };
+    /// let functions: Vec<_> = error.stack_trace().iter().map(|entry| &*entry.function_name).collect();
+    /// assert_eq!(functions, ["<main>"]);
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn hide_in_stack_traces(&mut self, function_name: &str) -> bool {
+        let mut found = false;
+        for function in self.env.functions_mut() {
+            if matches!(
+                function.kind,
+                FunctionKind::Foreign(_) | FunctionKind::Contextual(_)
+            ) && &*function.name == function_name
+            {
+                function.hidden_in_stack_traces = true;
+                found = true;
+            }
+        }
+        found
     }
 
     /// Declares a type in the global scope.
@@ -665,6 +1760,79 @@ impl Engine {
         Ok(())
     }
 
+    /// Declares a module in the global scope, such that its functions can be called through
+    /// the global, as in `name.function()`.
+    ///
+    /// Unlike with [`add_lazy_module`][Self::add_lazy_module], the module is created right away,
+    /// and scripts don't need to `import` it.
+    ///
+    /// # Examples
+    /// See [`ModuleBuilder`][crate::ModuleBuilder] for examples.
+    pub fn add_module(&mut self, name: &str, module: impl IntoModule) -> Result<(), Error> {
+        let value = module.into_module(self)?;
+        self.set(name, value)
+    }
+
+    /// Registers a module that scripts can `import`. The module is only created the first time a
+    /// script importing it is compiled, which keeps engine startup fast even when many optional
+    /// modules are registered.
+    ///
+    /// Once a module is imported, it stays loaded, and subsequent imports refer to the same value.
+    /// Registering a module under the name of one that's already been imported has no effect.
+    ///
+    /// # Errors
+    /// Errors returned while creating the module are returned by [`compile`][Self::compile].
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{Engine, TypeBuilder, UserData};
+    ///
+    /// struct Greeter;
+    ///
+    /// impl UserData for Greeter {}
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_lazy_module("greeter", || {
+    ///     TypeBuilder::<Greeter>::new("Greeter")
+    ///         .add_static("greet", |name: String| format!("Hello, {name}!"))
+    /// });
+    /// let greeting: String = engine
+    ///     .start("example.mi", r#" import greeter
+    ///                              greeter.greet("world") "#)?
+    ///     .trampoline()?;
+    /// assert_eq!(greeting, "Hello, world!");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_lazy_module<M>(&mut self, name: &str, factory: impl FnOnce() -> M + 'static)
+    where
+        M: IntoModule,
+    {
+        self.lazy_modules.insert(
+            Rc::from(name),
+            Box::new(move |engine| factory().into_module(engine)),
+        );
+    }
+
+    /// Creates a module that's being imported for the first time. Modules that aren't registered
+    /// are left for the code generator to report.
+    fn load_module(&mut self, name: &str) -> Result<(), Error> {
+        if self.env.get_module(name).is_some() {
+            return Ok(());
+        }
+        if let Some(factory) = self.lazy_modules.take(name) {
+            let value = factory(self)?;
+            let slot = self
+                .env
+                .create_module(name)
+                .map_err(|_| Error::TooManyGlobals)?;
+            let value = value.to_raw(&mut self.gc);
+            self.globals.set(slot, value);
+        }
+        Ok(())
+    }
+
     pub(crate) fn set_built_type<T>(&mut self, typ: &BuiltType<T>) -> Result<(), Error>
     where
         T: Any,
@@ -673,6 +1841,52 @@ impl Engine {
         self.set(typ.type_name.deref(), value)
     }
 
+    /// Returns whether a value implements a trait, which is the case when the value has all of the
+    /// methods required by the trait. This is the same check that's performed by the `implements`
+    /// operator in scripts.
+    ///
+    /// # Errors
+    /// If `implemented_trait` is not a trait.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let iter: Value = engine.start("example.mi", "[1, 2, 3].iter")?.trampoline()?;
+    /// let iterator: Value = engine.get("Iterator")?;
+    /// assert!(engine.implements(&iter, &iterator)?);
+    /// assert!(!engine.implements(&Value::new(1.0), &iterator)?);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn implements(&self, value: &Value, implemented_trait: &Value) -> Result<bool, Error> {
+        let Value::Trait(trait_instance) = implemented_trait else {
+            return Err(Error::TypeMismatch {
+                expected: "any trait".into(),
+                got: implemented_trait.type_name().into_owned().into(),
+            });
+        };
+        // Unwrapping is fine here because trait values can only be created for existing traits.
+        let trait_prototype = self.env.get_trait(trait_instance.0.id).unwrap();
+        let dtable = vm::Fiber::get_dispatch_table(value.to_raw_unmanaged(), &self.library);
+        Ok(dtable.implements_trait(trait_prototype))
+    }
+
+    /// Returns a read-only view into the globals and types registered in the engine.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, GlobalKind};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_function("add", |x: f64, y: f64| x + y).unwrap();
+    /// let add = engine.introspect().global("add").unwrap();
+    /// assert!(matches!(add.kind, GlobalKind::Function(f) if f.parameter_count == Some(2)));
+    /// ```
+    pub fn introspect(&self) -> Introspection<'_> {
+        Introspection::new(self)
+    }
+
     /// Starts building a new trait.
     ///
     /// # Examples
@@ -685,32 +1899,212 @@ impl Engine {
     }
 }
 
+/// The sources of all modules compiled by an engine, kept around for displaying snippets in
+/// runtime errors.
+#[derive(Debug, Default)]
+pub(crate) struct Sources {
+    /// Modules are identified by the address of their name, such that compiling two different
+    /// sources under the same module name (as is the case in a REPL) does not mix them up.
+    modules: Vec<(Rc<str>, Rc<str>)>,
+}
+
+impl Sources {
+    fn add(&mut self, module_name: Rc<str>, source: Rc<str>) {
+        self.modules.push((module_name, source));
+    }
+
+    /// Attaches a snippet to the error, if the source of the module it points to is known.
+    pub(crate) fn attach_snippet(&self, error: &mut LanguageError) {
+        let source = error.location().and_then(|(module_name, _)| {
+            self.modules
+                .iter()
+                .rev()
+                .find(|(name, _)| Rc::ptr_eq(name, module_name))
+                .map(|(_, source)| Rc::clone(source))
+        });
+        if let Some(source) = source {
+            error.attach_snippet(&source);
+        }
+    }
+}
+
 impl Default for Engine {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Creates a fiber that calls the function at the bottom of the stack with the rest of the stack
+/// as arguments.
+pub(crate) fn call_fiber(stack: Vec<RawValue>) -> Result<vm::Fiber, Error> {
+    // 1 has to be subtracted from the stack length there because the VM itself adds 1 to count in
+    // the function argument.
+    let chunk = call_chunk(stack.len() - 1)?;
+    Ok(vm::Fiber::new(chunk, stack))
+}
+
+/// Creates a chunk that calls the function at the bottom of the stack with the given number of
+/// arguments.
+pub(crate) fn call_chunk(argument_count: usize) -> Result<Rc<Chunk>, Error> {
+    // Having to construct a chunk here isn't the most clean, but it's the simplest way of
+    // making the VM perform a function call. It reuses sanity checks such as ensuring
+    // `function` can actually be called.
+    let mut chunk = Chunk::new(Rc::from("(call)"));
+    chunk.emit((
+        Opcode::Call,
+        Opr24::try_from(argument_count).map_err(|_| Error::TooManyArguments)?,
+    ));
+    chunk.emit(Opcode::Halt);
+    Ok(Rc::new(chunk))
+}
+
+/// Creates a chunk that calls a method on the receiver at the bottom of the stack.
+pub(crate) fn call_method_chunk(
+    method_index: MethodIndex,
+    argument_count: MethodParameterCount,
+) -> Rc<Chunk> {
+    let mut chunk = Chunk::new(Rc::from("(call)"));
+    chunk.emit((
+        Opcode::CallMethod,
+        Opr24::pack((method_index.to_u16(), argument_count.to_count_with_self())),
+    ));
+    chunk.emit(Opcode::Halt);
+    Rc::new(chunk)
+}
+
 /// A script pre-compiled into bytecode.
 pub struct Script<'e> {
     engine: &'e mut Engine,
-    main_chunk: Rc<Chunk>,
+    pub(crate) main_chunk: Rc<Chunk>,
+    /// The range of functions in the environment that were created while compiling the script.
+    pub(crate) functions: Range<usize>,
+    warnings: Vec<LanguageWarning>,
+    front_matter: FrontMatter,
 }
 
 impl<'e> Script<'e> {
+    /// Returns the warnings that were emitted while compiling the script.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// let script = engine.compile("example.mi", "1 + 1\n2")?;
+    /// assert_eq!(script.warnings().len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn warnings(&self) -> &[LanguageWarning] {
+        &self.warnings
+    }
+
+    /// Returns the metadata declared in the script's front matter.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// let source = "#!/usr/bin/env mica\n---\nname = \"greeter\"\n---\nprint(\"Hello!\")";
+    /// let script = engine.compile("greeter.mi", source)?;
+    /// assert_eq!(script.front_matter().get("name"), Some("greeter"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn front_matter(&self) -> &FrontMatter {
+        &self.front_matter
+    }
+
+    /// Returns a human-readable listing of the bytecode of the script's main chunk and of all the
+    /// functions it declares. The format is meant for debugging and may change at any time.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::Engine;
+    ///
+    /// let mut engine = Engine::new();
+    /// let script = engine.compile("example.mi", "func double(x) = x * 2\ndouble(21)")?;
+    /// let disassembly = script.disassemble();
+    /// assert!(disassembly.contains("<main>"));
+    /// assert!(disassembly.contains("double(x) (declared at example.mi:1:1)"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn disassemble(&self) -> String {
+        let mut listing = format!("<main>\n{:?}", self.main_chunk);
+        for function in &self.engine.env.functions()[self.functions.clone()] {
+            if let FunctionKind::Bytecode { chunk, .. } = &function.kind {
+                let _ = write!(listing, "\n{}", function.render_signature());
+                if let Some(declaration) = &function.declaration {
+                    let _ = write!(
+                        listing,
+                        " (declared at {}:{})",
+                        declaration.module_name, declaration.location
+                    );
+                }
+                let _ = write!(listing, "\n{chunk:?}");
+            }
+        }
+        listing
+    }
+
+    /// Returns the bytecode chunks of the script, for tools that inspect it programmatically.
+    ///
+    /// The main chunk comes first under the name `<main>`, followed by the chunks of the
+    /// functions the script declares, named by their signatures. Use
+    /// [`Chunk::instructions`] to decode them.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{ll::bytecode::Opcode, Engine};
+    ///
+    /// let mut engine = Engine::new();
+    /// let script = engine.compile("example.mi", "func double(x) = x * 2")?;
+    /// let chunks = script.chunks();
+    /// assert_eq!(chunks[1].0, "double(x)");
+    /// assert!(chunks[1]
+    ///     .1
+    ///     .instructions()
+    ///     .any(|instruction| instruction.opcode == Opcode::Multiply));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chunks(&self) -> Vec<(String, &Chunk)> {
+        let functions = &self.engine.env.functions()[self.functions.clone()];
+        std::iter::once((String::from("<main>"), &*self.main_chunk))
+            .chain(
+                functions
+                    .iter()
+                    .filter_map(|function| match &function.kind {
+                        FunctionKind::Bytecode { chunk, .. } => {
+                            Some((function.render_signature(), &**chunk))
+                        }
+                        _ => None,
+                    }),
+            )
+            .collect()
+    }
+
     /// Starts running a script in a new fiber.
-    pub fn start(&mut self) -> Fiber {
+    pub fn start(&mut self) -> Fiber<'_> {
+        let stacks = self.engine.stacks.take();
         Fiber {
             engine: self.engine,
-            inner: vm::Fiber::new(Rc::clone(&self.main_chunk), Vec::new()),
+            inner: vm::Fiber::with_stacks(Rc::clone(&self.main_chunk), stacks),
         }
     }
 
     /// Starts running a script in a new fiber, consuming the script.
     pub fn into_fiber(self) -> Fiber<'e> {
+        let stacks = self.engine.stacks.take();
         Fiber {
             engine: self.engine,
-            inner: vm::Fiber::new(Rc::clone(&self.main_chunk), Vec::new()),
+            inner: vm::Fiber::with_stacks(Rc::clone(&self.main_chunk), stacks),
         }
     }
 }
@@ -728,7 +2122,7 @@ impl<'e> fmt::Debug for Script<'e> {
 /// Note that these IDs are not portable across different engine instances.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(transparent)]
-pub struct GlobalId(GlobalIndex);
+pub struct GlobalId(pub(crate) GlobalIndex);
 
 mod global_id {
     use crate::GlobalId;
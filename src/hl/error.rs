@@ -1,23 +1,47 @@
 //! Error reporting.
 
-use std::{borrow::Cow, fmt};
+use std::{borrow::Cow, fmt, rc::Rc};
+
+use crate::{
+    ll::{error::Snippet, vm},
+    Value,
+};
 
 /// A raw [`ll`][crate::ll] error, with metadata such as stack traces.
 pub type LanguageError = crate::ll::error::LanguageError;
 /// A raw [`ll`][crate::ll] error kind.
 pub type LanguageErrorKind = crate::ll::error::LanguageErrorKind;
+/// A warning emitted by the compiler.
+pub type LanguageWarning = crate::ll::error::LanguageWarning;
+/// The kind of a compiler warning.
+pub type LanguageWarningKind = crate::ll::error::LanguageWarningKind;
+/// A location in source code.
+pub type Location = crate::ll::error::Location;
+/// A range of source code.
+pub type Span = crate::ll::error::Span;
+/// An entry of a runtime error's stack trace.
+pub type StackTraceEntry = crate::ll::error::StackTraceEntry;
+
+pub use crate::ll::{
+    error::{Lint, Severity},
+    lint::{LintContext, LintPass},
+};
 
 /// An error.
 #[derive(Debug)]
 pub enum Error {
     /// An error occured during compilation.
     Compile(LanguageError),
+    /// Multiple errors occured during compilation.
+    CompileMany(Vec<LanguageError>),
     /// An error occured during runtime.
-    Runtime(LanguageError),
+    Runtime(RuntimeError),
     /// There are too many globals.
     TooManyGlobals,
     /// Too many functions were created.
     TooManyFunctions,
+    /// Too many strings with different contents were interned.
+    TooManyStrings,
     /// Too many methods with different signatures were declared.
     TooManyMethods,
     /// Too many arguments were passed to a method or a function.
@@ -51,6 +75,33 @@ pub enum Error {
     },
     /// A value was mutably borrowed twice.
     ReentrantMutableBorrow,
+    /// Returned by a foreign function to signal that it can't complete yet. The calling fiber is
+    /// suspended and the function is called again once the fiber is resumed.
+    WouldBlock,
+    /// Every fiber is blocked, waiting for another one to do something, so none of them can
+    /// continue.
+    Deadlock,
+    /// The engine ran out of the fuel set by [`Engine::set_fuel`][crate::Engine::set_fuel]. The
+    /// fiber that was running is suspended, and continues where it left off once the engine is
+    /// refueled.
+    FuelExhausted,
+    /// Returned by a foreign function to stop the script with the given exit code, like
+    /// `Process.exit` does. Scripts can't catch it; it reaches the host as a runtime error, whose
+    /// [`exit_code`][RuntimeError::exit_code] is the code.
+    Exit(u8),
+    /// A value that can't be saved in a heap image was reachable from a global.
+    UnsupportedInImage {
+        /// The name of the value's type.
+        type_name: Cow<'static, str>,
+    },
+    /// A heap image could not be restored, because it's damaged or was saved by an engine that
+    /// was set up differently.
+    IncompatibleImage(String),
+    /// The heap could not be reset, because some objects allocated in the arena were still in use.
+    HeapInUse {
+        /// The number of objects that were still alive.
+        live_objects: usize,
+    },
     /// A user-defined error.
     User(Box<dyn std::error::Error>),
 }
@@ -59,17 +110,265 @@ impl From<LanguageError> for Error {
     fn from(error: LanguageError) -> Self {
         match &error {
             LanguageError::Compile { .. } => Self::Compile(error),
-            LanguageError::Runtime { .. } => Self::Runtime(error),
+            LanguageError::Runtime { .. } => Self::Runtime(RuntimeError {
+                error,
+                raised: None,
+            }),
+        }
+    }
+}
+
+impl From<Vec<LanguageError>> for Error {
+    fn from(mut errors: Vec<LanguageError>) -> Self {
+        if errors.len() == 1 {
+            Self::from(errors.remove(0))
+        } else {
+            Self::CompileMany(errors)
+        }
+    }
+}
+
+impl Error {
+    /// Returns the list of compilation errors this error carries. If the error did not occur
+    /// during compilation, the list is empty.
+    pub fn compile_errors(&self) -> &[LanguageError] {
+        match self {
+            Self::Compile(error) => std::slice::from_ref(error),
+            Self::CompileMany(errors) => errors,
+            _ => &[],
+        }
+    }
+
+    /// Returns the underlying language error, if this error originated in the compiler or the VM.
+    /// For [`CompileMany`][Self::CompileMany], this is the first error.
+    pub fn language_error(&self) -> Option<&LanguageError> {
+        match self {
+            Self::Compile(error) => Some(error),
+            Self::Runtime(error) => Some(&error.error),
+            Self::CompileMany(errors) => errors.first(),
+            _ => None,
+        }
+    }
+
+    /// Returns the problems the error describes as [`Diagnostic`]s, one for each compile error,
+    /// or one pointing to the innermost location of a runtime error. Errors that don't point to
+    /// any code, such as ones returned by the host, produce no diagnostics.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{DiagnosticSeverity, Engine};
+    ///
+    /// let mut engine = Engine::new();
+    /// let error = engine.compile("example.mi", "let x = )\nlet y = ]").unwrap_err();
+    /// let diagnostics = error.diagnostics();
+    /// assert_eq!(diagnostics.len(), 2);
+    /// assert_eq!(diagnostics[1].severity, DiagnosticSeverity::Error);
+    /// assert_eq!(diagnostics[1].span.start.line, 2);
+    /// assert_eq!(diagnostics[1].message, "invalid token in prefix position");
+    /// assert!(diagnostics[1].hint.is_some());
+    /// ```
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            Self::Compile(error) => Diagnostic::from_error(error).into_iter().collect(),
+            Self::CompileMany(errors) => errors.iter().filter_map(Diagnostic::from_error).collect(),
+            Self::Runtime(error) => Diagnostic::from_error(&error.error).into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the name of the module and the location in it that the error points to.
+    ///
+    /// See [`LanguageError::location`] for details.
+    pub fn location(&self) -> Option<(&Rc<str>, Location)> {
+        self.language_error().and_then(|error| error.location())
+    }
+
+    /// Returns the name of the module and the span of code in it that the error points to.
+    ///
+    /// See [`LanguageError::span`] for details.
+    pub fn span(&self) -> Option<(&Rc<str>, Span)> {
+        self.language_error().and_then(|error| error.span())
+    }
+}
+
+impl Error {
+    /// Converts an error a fiber failed with, attaching the value it raised, if any.
+    pub(crate) fn from_fiber(error: LanguageError, fiber: &vm::Fiber) -> Self {
+        match error {
+            LanguageError::Runtime {
+                kind: LanguageErrorKind::Raised(_),
+                ..
+            } => Self::Runtime(RuntimeError {
+                error,
+                raised: fiber.raised().map(Value::from_raw),
+            }),
+            error => Self::from(error),
+        }
+    }
+}
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticSeverity {
+    /// The code cannot be compiled or failed while running.
+    Error,
+    /// The code compiles, but likely doesn't do what was intended.
+    Warning,
+}
+
+/// A problem with a piece of source code, as structured data rather than a formatted message.
+/// This is meant for integrating with editors, which display problems alongside the code.
+///
+/// Diagnostics are obtained from errors through [`Error::diagnostics`], and from warnings through
+/// their [`From`] implementation.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// Whether the problem is an error or a warning.
+    pub severity: DiagnosticSeverity,
+    /// The name of the module the problem is in.
+    pub module_name: Rc<str>,
+    /// The span of code the problem is in. Some problems only point to where the code begins, in
+    /// which case the span is empty.
+    pub span: Span,
+    /// A description of the problem, without its location.
+    pub message: String,
+    /// A suggestion on how to fix the problem, if there is one.
+    pub hint: Option<&'static str>,
+}
+
+impl Diagnostic {
+    fn from_error(error: &LanguageError) -> Option<Self> {
+        let (module_name, span) = error.span()?;
+        Some(Self {
+            severity: DiagnosticSeverity::Error,
+            module_name: Rc::clone(module_name),
+            span,
+            message: error.kind().to_string(),
+            hint: error.kind().hint(),
+        })
+    }
+}
+
+impl From<&LanguageWarning> for Diagnostic {
+    fn from(warning: &LanguageWarning) -> Self {
+        Self {
+            severity: DiagnosticSeverity::Warning,
+            module_name: Rc::clone(&warning.module_name),
+            span: Span::point(warning.location),
+            message: warning.kind.to_string(),
+            hint: None,
         }
     }
 }
 
+/// An error that occured while running a script.
+///
+/// Apart from the [kind][Self::kind] of the error, this carries the value passed to `raise`, if
+/// the error was raised by the script, and the stack trace at the point the error occured. These
+/// let hosts report errors their own way, rather than by displaying the error.
+///
+/// # Examples
+/// ```
+/// use mica::{Engine, Error, Value};
+///
+/// let mut engine = Engine::new();
+/// let error = engine
+///     .start(
+///         "example.mi",
+///         r#"
+///             func validate(age) =
+///                 if age < 0 do raise ("age", "must not be negative") end
+///             validate(-1)
+///         "#,
+///     )?
+///     .trampoline::<Value>()
+///     .unwrap_err();
+///
+/// let Error::Runtime(error) = error else { panic!("runtime error expected") };
+/// let raised = error.raised().expect("the error was raised by the script");
+/// assert_eq!(raised.display(&mut engine)?, r#"("age", "must not be negative")"#);
+/// let innermost = error.stack_trace().last().unwrap();
+/// assert_eq!(&*innermost.function_name, "validate");
+/// assert_eq!(innermost.location.line, 3);
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug)]
+pub struct RuntimeError {
+    error: LanguageError,
+    raised: Option<Value>,
+}
+
+impl RuntimeError {
+    /// Returns the kind of the error. Values passed to `raise` produce
+    /// [`LanguageErrorKind::Raised`].
+    pub fn kind(&self) -> &LanguageErrorKind {
+        self.error.kind()
+    }
+
+    /// Returns the value passed to `raise`, if the error was raised by a script.
+    pub fn raised(&self) -> Option<&Value> {
+        self.raised.as_ref()
+    }
+
+    /// Returns the exit code, if the error was caused by the script asking to exit.
+    pub fn exit_code(&self) -> Option<u8> {
+        match self.kind() {
+            LanguageErrorKind::Exit(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Returns the call stack at the point the error occured, with the outermost call first.
+    ///
+    /// Foreign functions have an uninitialized [location][Location::is_uninit]. When a foreign
+    /// function calls back into Mica code, such as a function passed to it, and that code fails,
+    /// the frames of the callback follow the foreign function's.
+    pub fn stack_trace(&self) -> &[StackTraceEntry] {
+        match &self.error {
+            LanguageError::Runtime { call_stack, .. } => call_stack,
+            LanguageError::Compile { .. } => &[],
+        }
+    }
+
+    /// Returns the source snippet attached to the error, if any.
+    pub fn snippet(&self) -> Option<&Snippet> {
+        self.error.snippet()
+    }
+
+    /// Returns the underlying language error.
+    pub fn language_error(&self) -> &LanguageError {
+        &self.error
+    }
+
+    /// Converts the error into the underlying language error, dropping the raised value.
+    pub fn into_language_error(self) -> LanguageError {
+        self.error
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Compile(error) | Self::Runtime(error) => error.fmt(f),
+            Self::Compile(error) => error.fmt(f),
+            Self::Runtime(error) => error.fmt(f),
+            Self::CompileMany(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    error.fmt(f)?;
+                }
+                Ok(())
+            }
             Self::TooManyGlobals => f.write_str("too many globals"),
             Self::TooManyFunctions => f.write_str("too many functions"),
+            Self::TooManyStrings => f.write_str("too many interned strings"),
             Self::TooManyMethods => f.write_str("too many methods with different signatures"),
             Self::TooManyArguments => f.write_str("too many arguments passed to a function"),
             Self::TooManyTraits => f.write_str("too many traits"),
@@ -94,12 +393,48 @@ impl fmt::Display for Error {
                 )
             }
             Self::ReentrantMutableBorrow => write!(f, "method receiver is in use already"),
+            Self::WouldBlock => write!(f, "operation would block"),
+            Self::Deadlock => write!(f, "deadlock: all fibers are blocked"),
+            Self::FuelExhausted => write!(f, "out of fuel"),
+            Self::Exit(code) => write!(f, "exited with code {code}"),
+            Self::UnsupportedInImage { type_name } => {
+                write!(
+                    f,
+                    "values of type {type_name} cannot be saved in a heap image"
+                )
+            }
+            Self::IncompatibleImage(reason) => write!(f, "cannot restore heap image: {reason}"),
+            Self::HeapInUse { live_objects } => {
+                write!(
+                    f,
+                    "cannot reset heap: {live_objects} objects are still in use"
+                )
+            }
             Self::User(error) => write!(f, "{error}"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+/// Errors coming from the compiler or the VM have their [`LanguageError`] as the source. User
+/// errors are displayed transparently, so they are not part of the chain themselves.
+///
+/// Note that because the engine is single-threaded, `Error` is neither `Send` nor `Sync`. Hosts
+/// using error types that require it (such as `anyhow::Error`) need to convert the error to a
+/// string, or otherwise extract the information they need, before it leaves the thread. To run
+/// the same scripts on multiple threads, see [`Program`][crate::Program].
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Compile(error) => error.source(),
+            Self::Runtime(error) => error.error.source(),
+            Self::CompileMany(errors) => errors
+                .first()
+                .map(|error| error as &(dyn std::error::Error + 'static)),
+            Self::User(error) => error.source(),
+            _ => None,
+        }
+    }
+}
 
 /// Extensions for converting [`Result`]s into a Mica FFI-friendly structure.
 pub trait MicaResultExt<T, E> {
@@ -144,7 +479,45 @@ pub(crate) fn wrap_in_language_error<T, E>(r: Result<T, E>) -> Result<T, Languag
 where
     E: std::error::Error + 'static,
 {
-    r.map_err(|error| LanguageErrorKind::User(Box::new(error)))
+    r.map_err(|error| {
+        let error: Box<dyn std::error::Error> = Box::new(error);
+        match error.downcast::<Error>() {
+            // Argument type mismatches are passed to the VM as-is, so that it can attach
+            // information about the call to them.
+            Ok(error) => match *error {
+                Error::ArgumentTypeMismatch {
+                    index,
+                    expected,
+                    got,
+                } => LanguageErrorKind::ArgumentTypeMismatch(Box::new(
+                    crate::ll::error::ArgumentTypeMismatch {
+                        index,
+                        expected,
+                        got,
+                        call: None,
+                    },
+                )),
+                Error::WouldBlock => LanguageErrorKind::WouldBlock,
+                Error::FuelExhausted => LanguageErrorKind::FuelExhausted,
+                Error::Exit(code) => LanguageErrorKind::Exit(code),
+                Error::Runtime(RuntimeError { mut error, .. }) => {
+                    // Functions are called back through a chunk that only performs the call, and
+                    // its frame is of no interest to anyone reading the stack trace.
+                    if let LanguageError::Runtime { call_stack, .. } = &mut error {
+                        if call_stack
+                            .first()
+                            .is_some_and(|entry| &*entry.module_name == "(call)")
+                        {
+                            call_stack.remove(0);
+                        }
+                    }
+                    LanguageErrorKind::Nested(Box::new(error))
+                }
+                error => LanguageErrorKind::User(Box::new(error)),
+            },
+            Err(error) => LanguageErrorKind::User(error),
+        }
+    })
 }
 
 /// Extensions for converting [`Result`]s into a `mica-language` FFI-friendly structure.
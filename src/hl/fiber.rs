@@ -1,6 +1,65 @@
-use std::fmt;
+use std::{fmt, rc::Rc};
 
-use crate::{ll::vm, Engine, Error, TryFromValue, Value};
+use crate::{
+    ll::{bytecode::Environment, error::StackTraceEntry, vm},
+    Engine, Error, PausedFiber, TryFromValue, Value,
+};
+
+/// What a fiber is doing, as returned by [`Fiber::state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FiberState {
+    /// The fiber has code left to execute, and continues executing it once resumed. Fibers that
+    /// haven't been resumed yet are in this state.
+    Running,
+    /// The fiber is blocked on an operation that couldn't complete yet, such as receiving from
+    /// an empty channel.
+    Suspended,
+    /// The fiber handed a value to whoever resumed it using `yield`, and continues after the
+    /// `yield` once resumed.
+    Yielded,
+    /// The fiber was [paused][crate::PausedFiber::pause] by a debugger, and continues from the
+    /// instruction it was paused before once resumed.
+    Paused,
+    /// The fiber has executed all of its code.
+    Finished,
+    /// The fiber stopped because of an error.
+    Errored,
+}
+
+impl FiberState {
+    pub(crate) fn of(fiber: &vm::Fiber) -> Self {
+        if fiber.errored() {
+            Self::Errored
+        } else if fiber.halted() {
+            Self::Finished
+        } else if fiber.blocked() {
+            Self::Suspended
+        } else if fiber.paused() {
+            Self::Paused
+        } else if fiber.yielded() {
+            Self::Yielded
+        } else {
+            Self::Running
+        }
+    }
+}
+
+/// Returns the fiber's call stack, starting with the innermost frame. Fibers that have halted
+/// don't have a call stack.
+pub(crate) fn stack_trace(fiber: &vm::Fiber, env: &Environment) -> Vec<StackTraceEntry> {
+    if fiber.halted() {
+        return Vec::new();
+    }
+    fiber
+        .call_frames()
+        .iter()
+        .map(|frame| StackTraceEntry {
+            function_name: frame.function_name(env),
+            module_name: Rc::clone(&frame.chunk().module_name),
+            location: frame.location(),
+        })
+        .collect()
+}
 
 /// A fiber represents an independent, pausable thread of code execution.
 pub struct Fiber<'e> {
@@ -10,6 +69,15 @@ pub struct Fiber<'e> {
 
 impl<'e> Fiber<'e> {
     /// Resumes execution of a fiber. If execution is done already, returns `None`.
+    ///
+    /// If the fiber blocks on an operation that can't complete yet, or is
+    /// [paused][crate::PausedFiber::pause] by a debugger, this returns `nil`, and execution
+    /// continues where it stopped the next time the fiber is resumed. If the fiber calls `yield`, this
+    /// returns the yielded value, and the `yield` evaluates to `nil` once the fiber is resumed;
+    /// use [`resume_with`][Self::resume_with] to pass a different value back.
+    ///
+    /// If the engine runs out of [fuel][Engine::set_fuel], [`Error::FuelExhausted`] is returned.
+    /// The fiber stays suspended, and continues where it left off once resumed after refueling.
     pub fn resume<T>(&mut self) -> Result<Option<T>, Error>
     where
         T: TryFromValue,
@@ -22,9 +90,19 @@ impl<'e> Fiber<'e> {
                 library,
                 globals,
                 gc,
+                sources,
                 ..
             } = &mut self.engine;
-            let result = self.inner.interpret(env, library, globals, gc)?;
+            let result = self
+                .inner
+                .interpret(env, library, globals, gc)
+                .map_err(|mut error| {
+                    sources.attach_snippet(&mut error);
+                    Error::from_fiber(error, &self.inner)
+                })?;
+            if self.inner.out_of_fuel() {
+                return Err(Error::FuelExhausted);
+            }
             Ok(Some(T::try_from_value(
                 &Value::from_raw(result),
                 &self.engine.library,
@@ -32,19 +110,211 @@ impl<'e> Fiber<'e> {
         }
     }
 
+    /// Resumes execution of a fiber, making the `yield` it is suspended on evaluate to the given
+    /// value. If the fiber isn't suspended on a `yield`, the value is discarded.
+    ///
+    /// This lets fibers act as coroutines driven by the host, such as scripts controlling the
+    /// behavior of a game entity, which run until their next `yield` every frame.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, FiberState, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let mut fiber = engine.start(
+    ///     "example.mi",
+    ///     r#"
+    ///         let total = 0
+    ///         while true do
+    ///             total = total + yield(total)
+    ///         end
+    ///     "#,
+    /// )?;
+    /// assert_eq!(fiber.resume::<f64>()?, Some(0.0));
+    /// assert_eq!(fiber.state(), FiberState::Yielded);
+    /// assert_eq!(fiber.resume_with::<f64>(Value::new(2.0))?, Some(2.0));
+    /// assert_eq!(fiber.resume_with::<f64>(Value::new(3.0))?, Some(5.0));
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn resume_with<T>(&mut self, value: Value) -> Result<Option<T>, Error>
+    where
+        T: TryFromValue,
+    {
+        let value = value.to_raw(&mut self.engine.gc);
+        self.inner.set_yield_result(value);
+        self.resume()
+    }
+
+    /// Resumes execution of a fiber, letting it execute at most `budget` instructions. Returns
+    /// whether the fiber ran out of its budget before it could finish or [block][Self::is_blocked].
+    ///
+    /// A fiber that ran out of budget continues where it left off when resumed again, so this
+    /// can be used to spread long-running scripts across multiple frames of a game, for instance.
+    /// The value the fiber evaluates to is discarded; use [`resume`][Self::resume] to get it.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, FiberState};
+    ///
+    /// let mut engine = Engine::new();
+    /// let mut fiber = engine.start("example.mi", "let i = 0\nwhile i < 100 do i = i + 1 end")?;
+    /// let mut frames = 0;
+    /// while fiber.resume_with_budget(50)? {
+    ///     frames += 1;
+    /// }
+    /// assert!(frames > 1);
+    /// assert_eq!(fiber.state(), FiberState::Finished);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn resume_with_budget(&mut self, budget: usize) -> Result<bool, Error> {
+        self.inner.set_budget(Some(budget));
+        let result = self.resume::<Value>();
+        self.inner.set_budget(None);
+        result?;
+        Ok(self.inner.out_of_budget())
+    }
+
+    /// Advances the fiber by executing at most the given number of instructions, and returns what
+    /// it's doing afterwards.
+    ///
+    /// This is meant for hosts that run many fibers a little at a time, such as a game advancing
+    /// the scripts of its entities every frame. The fiber stops early if it yields, blocks, or
+    /// finishes; in the first two cases, it continues where it left off on the next step. Like
+    /// with [`resume_with_budget`][Self::resume_with_budget], values the fiber yields or
+    /// evaluates to are discarded.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, FiberState};
+    ///
+    /// let mut engine = Engine::new();
+    /// let mut fiber = engine.start(
+    ///     "example.mi",
+    ///     "let i = 0\nwhile i < 100 do i = i + 1 end\nyield(i)\ni * 2",
+    /// )?;
+    /// assert_eq!(fiber.step(50)?, FiberState::Running);
+    /// while fiber.step(50)? == FiberState::Running {}
+    /// assert_eq!(fiber.state(), FiberState::Yielded);
+    /// while !fiber.is_finished() {
+    ///     fiber.step(50)?;
+    /// }
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn step(&mut self, instructions: usize) -> Result<FiberState, Error> {
+        self.resume_with_budget(instructions)?;
+        Ok(self.state())
+    }
+
+    /// Returns whether the fiber has stopped executing for good, because it either finished or
+    /// errored out. Resuming a finished fiber does nothing.
+    pub fn is_finished(&self) -> bool {
+        self.inner.halted()
+    }
+
+    /// Refuels the engine the fiber is running in. See [`Engine::set_fuel`].
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.engine.set_fuel(fuel);
+    }
+
+    /// Returns how much fuel the engine the fiber is running in has left. See
+    /// [`Engine::set_fuel`].
+    pub fn fuel(&self) -> Option<u64> {
+        self.engine.fuel()
+    }
+
+    /// Returns whether the fiber is waiting for an operation that couldn't complete when it was
+    /// last resumed, such as receiving from an empty channel.
+    pub fn is_blocked(&self) -> bool {
+        self.inner.blocked()
+    }
+
+    /// Returns what the fiber is currently doing.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, FiberState, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let mut fiber = engine.start("example.mi", "Channel.new().receive()")?;
+    /// assert_eq!(fiber.state(), FiberState::Running);
+    /// let _: Option<Value> = fiber.resume()?;
+    /// assert_eq!(fiber.state(), FiberState::Suspended);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn state(&self) -> FiberState {
+        FiberState::of(&self.inner)
+    }
+
+    /// Returns the fiber's call stack, starting with the innermost frame (the function the fiber
+    /// is executing, or is about to execute once resumed.) The stack is empty once the fiber
+    /// finishes executing or errors out.
+    pub fn stack(&self) -> Vec<StackTraceEntry> {
+        stack_trace(&self.inner, &self.engine.env)
+    }
+
+    /// Returns the innermost frame of the fiber's [call stack][Self::stack], or `None` if the
+    /// fiber isn't executing any code anymore.
+    pub fn current_frame(&self) -> Option<StackTraceEntry> {
+        self.stack().into_iter().next()
+    }
+
+    /// Returns a view of the fiber for inspecting its local variables and call stack, such as
+    /// after it was [paused][FiberState::Paused] by a debugger.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{DebuggerHooks, Engine, FiberState, PausedFiber, Value};
+    ///
+    /// struct Breaker;
+    ///
+    /// impl DebuggerHooks for Breaker {
+    ///     fn on_line(&mut self, _: &PausedFiber<'_>) {}
+    /// }
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_debugger_hooks(Breaker);
+    /// engine.set_breakpoint("example.mi", 3);
+    /// let mut fiber = engine.start("example.mi", "do\n    let x = 1\n    x + 1\nend")?;
+    /// let _: Option<Value> = fiber.resume()?;
+    /// assert_eq!(fiber.state(), FiberState::Paused);
+    /// let locals = fiber.inspect().locals(0);
+    /// assert_eq!(locals[0].0.as_ref(), "x");
+    /// assert_eq!(fiber.resume::<f64>()?, Some(2.0));
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn inspect(&self) -> PausedFiber<'_> {
+        PausedFiber::new(&self.inner, &self.engine.env, &self.engine.globals)
+    }
+
     /// Resumes execution of a fiber until it's done evaluating all code. The last result is
     /// returned and results from intermediate yields are discarded.
     ///
+    /// If the fiber blocks (eg. on receiving from an empty channel,) [`Error::Deadlock`] is
+    /// returned, as there are no other fibers that could unblock it. Use a
+    /// [`Scheduler`][crate::Scheduler] to run fibers that communicate with each other.
+    ///
     /// This consumes the fiber, as calling one that's finished is not useful.
     pub fn trampoline<T>(mut self) -> Result<T, Error>
     where
         T: TryFromValue,
     {
+        let result = self.run_to_completion();
+        // Nothing can resume the fiber anymore, so its stacks are handed back to the engine for
+        // the next fiber to reuse.
+        let stacks = self.inner.take_stacks(&mut self.engine.gc);
+        self.engine.stacks.recycle(stacks);
+        T::try_from_value(&result?, &self.engine.library)
+    }
+
+    fn run_to_completion(&mut self) -> Result<Value, Error> {
         let mut result = Value::Nil;
         while let Some(v) = self.resume()? {
+            if self.is_blocked() {
+                return Err(Error::Deadlock);
+            }
             result = v;
         }
-        T::try_from_value(&result, &self.engine.library)
+        Ok(result)
     }
 }
 
@@ -1,7 +1,7 @@
 pub use crate::ll::bytecode::{FunctionParameterCount, MethodParameterCount};
 use crate::{
     ll::{bytecode::Library, value::RawValue},
-    Error, IntoValue, LanguageErrorKind, RawForeignFunction, TryFromValue, Value,
+    wrap_in_language_error, EngineContext, Error, IntoValue, RawFunctionKind, TryFromValue, Value,
 };
 
 /// Arguments passed to a varargs function.
@@ -16,7 +16,7 @@ pub struct Arguments<'a> {
 
 impl<'a> Arguments<'a> {
     /// Creates a new [`Arguments`] from a raw argument list, as is passed into a
-    /// [raw function][RawForeignFunction].
+    /// [raw function][crate::RawForeignFunction].
     pub fn new(raw_arguments: &'a [RawValue], library: &'a Library) -> Self {
         // Skip the first argument, which is `self` (or the currently called function).
         Self {
@@ -134,6 +134,10 @@ impl std::ops::Deref for RawSelf<'_> {
 ///   - `fn (`[`Arguments`]`) -> Result<R, E>` where
 ///     - `R`: [`Into`]`<`[`Value`]`>`
 ///     - `E`: [`std::error::Error`]
+/// - Functions that call back into the engine
+///   - Bare functions of both kinds above can accept an `&mut `[`EngineContext`] as their first
+///     parameter, eg. `fn (&mut EngineContext, A, B, C, ...) -> R` or
+///     `fn (&mut EngineContext, Arguments) -> R`. The context is not counted as a parameter.
 ///
 /// The generic parameter `V` is not used inside the trait. Its only purpose is to allow for
 /// multiple overlapping implementations of a trait for the same type. See [`ffvariants`] for more
@@ -151,8 +155,9 @@ pub trait ForeignFunction<V> {
     /// The default implementation returns `None`.
     const PARAMETER_COUNT: Self::ParameterCount;
 
-    /// Converts the function to a `RawForeignFunction`.
-    fn into_raw_foreign_function(self) -> RawForeignFunction;
+    /// Converts the function to a [`RawFunctionKind`], which is either a foreign function or a
+    /// contextual foreign function.
+    fn into_raw_function_kind(self) -> RawFunctionKind;
 }
 
 /// Variants of `ForeignFunction`.
@@ -184,6 +189,14 @@ pub mod ffvariants {
     pub enum VarargsFallible {}
     /// A bare varargs infallible function.
     pub enum VarargsInfallible {}
+    /// A bare fallible function accepting an engine context.
+    pub struct ContextFallible<Args>(PhantomData<Args>);
+    /// A bare infallible function accepting an engine context.
+    pub struct ContextInfallible<Args>(PhantomData<Args>);
+    /// A bare varargs fallible function accepting an engine context.
+    pub enum ContextVarargsFallible {}
+    /// A bare varargs infallible function accepting an engine context.
+    pub enum ContextVarargsInfallible {}
 
     mod bare {
         pub trait Sealed {}
@@ -192,6 +205,10 @@ pub mod ffvariants {
         impl<Args> Sealed for super::Infallible<Args> {}
         impl Sealed for super::VarargsFallible {}
         impl Sealed for super::VarargsInfallible {}
+        impl<Args> Sealed for super::ContextFallible<Args> {}
+        impl<Args> Sealed for super::ContextInfallible<Args> {}
+        impl Sealed for super::ContextVarargsFallible {}
+        impl Sealed for super::ContextVarargsInfallible {}
     }
 
     /// Marker trait for all functions that _don't_ accept a `self` reference as the first
@@ -204,6 +221,10 @@ pub mod ffvariants {
     impl<Args> BareMaybeVarargs for Infallible<Args> {}
     impl BareMaybeVarargs for VarargsFallible {}
     impl BareMaybeVarargs for VarargsInfallible {}
+    impl<Args> BareMaybeVarargs for ContextFallible<Args> {}
+    impl<Args> BareMaybeVarargs for ContextInfallible<Args> {}
+    impl BareMaybeVarargs for ContextVarargsFallible {}
+    impl BareMaybeVarargs for ContextVarargsInfallible {}
 
     /// Marker trait for all functions that don't accept a `self` reference as the first
     /// parameter and do not accept a variable number of arguments.
@@ -213,6 +234,8 @@ pub mod ffvariants {
 
     impl<Args> BareExactArgs for Fallible<Args> {}
     impl<Args> BareExactArgs for Infallible<Args> {}
+    impl<Args> BareExactArgs for ContextFallible<Args> {}
+    impl<Args> BareExactArgs for ContextInfallible<Args> {}
 
     /// A fallible function with `RawSelf`.
     pub struct FallibleRawSelf<Args>(PhantomData<Args>);
@@ -279,12 +302,13 @@ where
 
     const PARAMETER_COUNT: Self::ParameterCount = FunctionParameterCount::Varargs;
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
-            self(Arguments::new(args, library))
-                .map(|value| value.into_value_with_engine_state(library, gc).to_raw(gc))
-                .map_err(|error| LanguageErrorKind::User(Box::new(error)))
-        })
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
+            wrap_in_language_error(
+                self(Arguments::new(args, library))
+                    .map(|value| value.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -297,11 +321,54 @@ where
 
     const PARAMETER_COUNT: Self::ParameterCount = FunctionParameterCount::Varargs;
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             Ok(self(Arguments::new(args, library))
                 .into_value_with_engine_state(library, gc)
                 .to_raw(gc))
-        })
+        }))
+    }
+}
+
+impl<Ret, Err, F> ForeignFunction<ffvariants::ContextVarargsFallible> for F
+where
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    F: Fn(&mut EngineContext<'_>, Arguments) -> Result<Ret, Err> + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+
+    const PARAMETER_COUNT: Self::ParameterCount = FunctionParameterCount::Varargs;
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, Arguments::new(args, library))
+            };
+            wrap_in_language_error(
+                result.map(|value| value.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
+    }
+}
+
+impl<Ret, F> ForeignFunction<ffvariants::ContextVarargsInfallible> for F
+where
+    Ret: IntoValue + 'static,
+    F: Fn(&mut EngineContext<'_>, Arguments) -> Ret + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+
+    const PARAMETER_COUNT: Self::ParameterCount = FunctionParameterCount::Varargs;
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, Arguments::new(args, library))
+            };
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
     }
 }
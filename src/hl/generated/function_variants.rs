@@ -7,8 +7,8 @@
 use crate::{
     ffvariants,
     ll::bytecode::{FunctionParameterCount, MethodParameterCount},
-    wrap_in_language_error, Arguments, ForeignFunction, IntoValue, MutSelfFromRawValue,
-    RawForeignFunction, RawSelf, SelfFromRawValue, TryFromValue,
+    wrap_in_language_error, Arguments, EngineContext, ForeignFunction, IntoValue,
+    MutSelfFromRawValue, RawFunctionKind, RawSelf, SelfFromRawValue, TryFromValue,
 };
 
 impl<Fun, Ret> ForeignFunction<ffvariants::Infallible<()>> for Fun
@@ -19,13 +19,13 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(0);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let result = self();
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -38,15 +38,60 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(0);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let result = self();
 
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret> ForeignFunction<ffvariants::ContextInfallible<()>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(0);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context)
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err> ForeignFunction<ffvariants::ContextFallible<()>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(0);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context)
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -58,15 +103,15 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(1);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
 
             let result = self(arg_self);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -79,8 +124,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(1);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
 
@@ -89,7 +134,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -103,8 +148,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(1);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -113,7 +158,7 @@ where
             let result = self(arg_self);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -127,8 +172,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(1);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -137,7 +182,7 @@ where
             let result = self(arg_self);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -152,8 +197,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(1);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -164,7 +209,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -179,8 +224,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(1);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -191,7 +236,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -204,15 +249,15 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(1);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
 
             let result = self(arg_0);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -226,8 +271,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(1);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
 
@@ -236,7 +281,58 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret, A> ForeignFunction<ffvariants::ContextInfallible<(A,)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+    A: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(1);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0)
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err, A> ForeignFunction<ffvariants::ContextFallible<(A,)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    A: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(1);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0)
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -249,8 +345,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(2);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -258,7 +354,7 @@ where
             let result = self(arg_self, arg_0);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -272,8 +368,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(2);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -283,7 +379,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -298,8 +394,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(2);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -309,7 +405,7 @@ where
             let result = self(arg_self, arg_0);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -325,8 +421,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(2);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -336,7 +432,7 @@ where
             let result = self(arg_self, arg_0);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -352,8 +448,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(2);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -365,7 +461,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -381,8 +477,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(2);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -394,7 +490,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -408,8 +504,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(2);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -417,7 +513,7 @@ where
             let result = self(arg_0, arg_1);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -432,8 +528,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(2);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -443,7 +539,62 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret, A, B> ForeignFunction<ffvariants::ContextInfallible<(A, B)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(2);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1)
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err, A, B> ForeignFunction<ffvariants::ContextFallible<(A, B)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(2);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1)
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -457,8 +608,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(3);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -467,7 +618,7 @@ where
             let result = self(arg_self, arg_0, arg_1);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -482,8 +633,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(3);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -494,7 +645,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -511,8 +662,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(3);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -523,7 +674,7 @@ where
             let result = self(arg_self, arg_0, arg_1);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -540,8 +691,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(3);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -552,7 +703,7 @@ where
             let result = self(arg_self, arg_0, arg_1);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -570,8 +721,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(3);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -584,7 +735,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -602,8 +753,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(3);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -616,7 +767,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -631,8 +782,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(3);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -641,7 +792,7 @@ where
             let result = self(arg_0, arg_1, arg_2);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -657,8 +808,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(3);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -669,7 +820,66 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret, A, B, C> ForeignFunction<ffvariants::ContextInfallible<(A, B, C)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(3);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1, arg_2)
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err, A, B, C> ForeignFunction<ffvariants::ContextFallible<(A, B, C)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(3);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1, arg_2)
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -685,8 +895,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(4);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -696,7 +906,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -713,8 +923,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(4);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -726,7 +936,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -744,8 +954,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(4);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -757,7 +967,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -775,8 +985,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(4);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -788,7 +998,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -807,8 +1017,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(4);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -822,7 +1032,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -841,8 +1051,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(4);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -856,7 +1066,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -872,8 +1082,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(4);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -883,7 +1093,7 @@ where
             let result = self(arg_0, arg_1, arg_2, arg_3);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -900,8 +1110,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(4);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -913,7 +1123,70 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret, A, B, C, D> ForeignFunction<ffvariants::ContextInfallible<(A, B, C, D)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(4);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1, arg_2, arg_3)
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err, A, B, C, D> ForeignFunction<ffvariants::ContextFallible<(A, B, C, D)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(4);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1, arg_2, arg_3)
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -930,8 +1203,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(5);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -942,7 +1215,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -960,8 +1233,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(5);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -974,7 +1247,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -994,8 +1267,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(5);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -1008,7 +1281,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1028,8 +1301,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(5);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -1042,7 +1315,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1062,8 +1335,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(5);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -1078,7 +1351,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1099,8 +1372,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(5);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -1115,7 +1388,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1132,8 +1405,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(5);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -1144,7 +1417,7 @@ where
             let result = self(arg_0, arg_1, arg_2, arg_3, arg_4);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1162,8 +1435,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(5);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -1176,7 +1449,76 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret, A, B, C, D, E> ForeignFunction<ffvariants::ContextInfallible<(A, B, C, D, E)>>
+    for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D, E) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+    E: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(5);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+            let arg_4 = wrap_in_language_error(arguments.get(4))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1, arg_2, arg_3, arg_4)
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err, A, B, C, D, E> ForeignFunction<ffvariants::ContextFallible<(A, B, C, D, E)>>
+    for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D, E) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+    E: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(5);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+            let arg_4 = wrap_in_language_error(arguments.get(4))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1, arg_2, arg_3, arg_4)
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -1194,8 +1536,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(6);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -1207,7 +1549,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1226,8 +1568,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(6);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -1241,7 +1583,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1262,8 +1604,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(6);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -1277,7 +1619,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1298,8 +1640,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(6);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -1313,7 +1655,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1335,8 +1677,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(6);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -1352,7 +1694,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1374,8 +1716,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(6);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -1391,7 +1733,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1409,8 +1751,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(6);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -1422,7 +1764,7 @@ where
             let result = self(arg_0, arg_1, arg_2, arg_3, arg_4, arg_5);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1442,8 +1784,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(6);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -1457,7 +1799,80 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret, A, B, C, D, E, F> ForeignFunction<ffvariants::ContextInfallible<(A, B, C, D, E, F)>>
+    for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D, E, F) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+    E: TryFromValue + 'static,
+    F: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(6);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+            let arg_4 = wrap_in_language_error(arguments.get(4))?;
+            let arg_5 = wrap_in_language_error(arguments.get(5))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1, arg_2, arg_3, arg_4, arg_5)
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err, A, B, C, D, E, F>
+    ForeignFunction<ffvariants::ContextFallible<(A, B, C, D, E, F)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D, E, F) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+    E: TryFromValue + 'static,
+    F: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(6);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+            let arg_4 = wrap_in_language_error(arguments.get(4))?;
+            let arg_5 = wrap_in_language_error(arguments.get(5))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(&mut context, arg_0, arg_1, arg_2, arg_3, arg_4, arg_5)
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -1476,8 +1891,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(7);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -1490,7 +1905,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4, arg_5);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1510,8 +1925,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(7);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -1526,7 +1941,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1548,8 +1963,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(7);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -1564,7 +1979,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4, arg_5);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1586,8 +2001,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(7);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -1602,7 +2017,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4, arg_5);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1625,8 +2040,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(7);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -1643,7 +2058,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1666,8 +2081,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(7);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -1684,7 +2099,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1704,8 +2119,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(7);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -1718,7 +2133,7 @@ where
             let result = self(arg_0, arg_1, arg_2, arg_3, arg_4, arg_5, arg_6);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1739,8 +2154,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(7);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -1755,7 +2170,102 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret, A, B, C, D, E, F, G>
+    ForeignFunction<ffvariants::ContextInfallible<(A, B, C, D, E, F, G)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D, E, F, G) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+    E: TryFromValue + 'static,
+    F: TryFromValue + 'static,
+    G: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(7);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+            let arg_4 = wrap_in_language_error(arguments.get(4))?;
+            let arg_5 = wrap_in_language_error(arguments.get(5))?;
+            let arg_6 = wrap_in_language_error(arguments.get(6))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(
+                    &mut context,
+                    arg_0,
+                    arg_1,
+                    arg_2,
+                    arg_3,
+                    arg_4,
+                    arg_5,
+                    arg_6,
+                )
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err, A, B, C, D, E, F, G>
+    ForeignFunction<ffvariants::ContextFallible<(A, B, C, D, E, F, G)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D, E, F, G) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+    E: TryFromValue + 'static,
+    F: TryFromValue + 'static,
+    G: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(7);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+            let arg_4 = wrap_in_language_error(arguments.get(4))?;
+            let arg_5 = wrap_in_language_error(arguments.get(5))?;
+            let arg_6 = wrap_in_language_error(arguments.get(6))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(
+                    &mut context,
+                    arg_0,
+                    arg_1,
+                    arg_2,
+                    arg_3,
+                    arg_4,
+                    arg_5,
+                    arg_6,
+                )
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -1775,8 +2285,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(8);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -1790,7 +2300,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4, arg_5, arg_6);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1811,8 +2321,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(8);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -1828,7 +2338,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1851,8 +2361,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(8);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -1868,7 +2378,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4, arg_5, arg_6);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1891,8 +2401,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(8);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -1908,7 +2418,7 @@ where
             let result = self(arg_self, arg_0, arg_1, arg_2, arg_3, arg_4, arg_5, arg_6);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -1932,8 +2442,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(8);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -1951,7 +2461,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -1975,8 +2485,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(8);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -1994,7 +2504,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -2015,8 +2525,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(8);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -2030,7 +2540,7 @@ where
             let result = self(arg_0, arg_1, arg_2, arg_3, arg_4, arg_5, arg_6, arg_7);
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -2052,8 +2562,8 @@ where
     type ParameterCount = FunctionParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(8);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
             let arg_1 = wrap_in_language_error(arguments.get(1))?;
@@ -2069,7 +2579,108 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
+    }
+}
+
+impl<Fun, Ret, A, B, C, D, E, F, G, H>
+    ForeignFunction<ffvariants::ContextInfallible<(A, B, C, D, E, F, G, H)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D, E, F, G, H) -> Ret + 'static,
+    Ret: IntoValue + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+    E: TryFromValue + 'static,
+    F: TryFromValue + 'static,
+    G: TryFromValue + 'static,
+    H: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(8);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+            let arg_4 = wrap_in_language_error(arguments.get(4))?;
+            let arg_5 = wrap_in_language_error(arguments.get(5))?;
+            let arg_6 = wrap_in_language_error(arguments.get(6))?;
+            let arg_7 = wrap_in_language_error(arguments.get(7))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(
+                    &mut context,
+                    arg_0,
+                    arg_1,
+                    arg_2,
+                    arg_3,
+                    arg_4,
+                    arg_5,
+                    arg_6,
+                    arg_7,
+                )
+            };
+
+            Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
+        }))
+    }
+}
+
+impl<Fun, Ret, Err, A, B, C, D, E, F, G, H>
+    ForeignFunction<ffvariants::ContextFallible<(A, B, C, D, E, F, G, H)>> for Fun
+where
+    Fun: Fn(&mut EngineContext<'_>, A, B, C, D, E, F, G, H) -> Result<Ret, Err> + 'static,
+    Ret: IntoValue + 'static,
+    Err: std::error::Error + 'static,
+    A: TryFromValue + 'static,
+    B: TryFromValue + 'static,
+    C: TryFromValue + 'static,
+    D: TryFromValue + 'static,
+    E: TryFromValue + 'static,
+    F: TryFromValue + 'static,
+    G: TryFromValue + 'static,
+    H: TryFromValue + 'static,
+{
+    type ParameterCount = FunctionParameterCount;
+    const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::Fixed(8);
+
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Contextual(Box::new(move |env, library, globals, gc, args| {
+            let arguments = Arguments::new(args, library);
+            let arg_0 = wrap_in_language_error(arguments.get(0))?;
+            let arg_1 = wrap_in_language_error(arguments.get(1))?;
+            let arg_2 = wrap_in_language_error(arguments.get(2))?;
+            let arg_3 = wrap_in_language_error(arguments.get(3))?;
+            let arg_4 = wrap_in_language_error(arguments.get(4))?;
+            let arg_5 = wrap_in_language_error(arguments.get(5))?;
+            let arg_6 = wrap_in_language_error(arguments.get(6))?;
+            let arg_7 = wrap_in_language_error(arguments.get(7))?;
+
+            let result = {
+                let mut context = EngineContext::new(env, library, globals, gc);
+                self(
+                    &mut context,
+                    arg_0,
+                    arg_1,
+                    arg_2,
+                    arg_3,
+                    arg_4,
+                    arg_5,
+                    arg_6,
+                    arg_7,
+                )
+            };
+
+            wrap_in_language_error(
+                result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
+            )
+        }))
     }
 }
 
@@ -2090,8 +2701,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(9);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -2108,7 +2719,7 @@ where
             );
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -2130,8 +2741,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(9);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let arg_self = RawSelf(arguments.raw_self());
             let arg_0 = wrap_in_language_error(arguments.get(0))?;
@@ -2150,7 +2761,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -2177,8 +2788,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(9);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -2197,7 +2808,7 @@ where
             );
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -2224,8 +2835,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(9);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -2244,7 +2855,7 @@ where
             );
 
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
-        })
+        }))
     }
 }
 
@@ -2269,8 +2880,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(9);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as SelfFromRawValue>::self_from_raw_value(arguments.raw_self())
@@ -2291,7 +2902,7 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
 
@@ -2319,8 +2930,8 @@ where
     type ParameterCount = MethodParameterCount;
     const PARAMETER_COUNT: Self::ParameterCount = Self::ParameterCount::from_count_with_self(9);
 
-    fn into_raw_foreign_function(self) -> RawForeignFunction {
-        Box::new(move |library, gc, args| {
+    fn into_raw_function_kind(self) -> RawFunctionKind {
+        RawFunctionKind::Foreign(Box::new(move |library, gc, args| {
             let arguments = Arguments::new(args, library);
             let (arg_self, _guard) = wrap_in_language_error(unsafe {
                 <Recv as MutSelfFromRawValue>::mut_self_from_raw_value(arguments.raw_self())
@@ -2341,6 +2952,6 @@ where
             wrap_in_language_error(
                 result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)),
             )
-        })
+        }))
     }
 }
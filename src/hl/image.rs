@@ -0,0 +1,1177 @@
+//! Saving the heap of an engine and restoring it later.
+
+use std::{collections::HashMap, fmt, pin::Pin, rc::Rc};
+
+use crate::{
+    ll::{
+        bytecode::{
+            DispatchTable, Environment, FunctionIndex, FunctionKind, FunctionParameterCount,
+            GlobalIndex, MethodIndex, MethodParameterCount, MethodSignature, Opr24, TraitIndex,
+        },
+        gc::GcRaw,
+        value::{
+            Closure, Dict, List, RawValue, Record, Str, Struct, Trait, Tuple, Upvalue, UserData,
+            ValueKind,
+        },
+    },
+    Engine, Error,
+};
+
+const MAGIC: &[u8] = b"mica-image";
+const VERSION: u32 = 1;
+
+/// A serialized image of an engine's globals and all objects reachable from them, created by
+/// [`Engine::snapshot`] and loaded back with [`Engine::restore`].
+///
+/// The image only contains data, such as strings, lists, dicts, struct instances, and closures.
+/// Code is not a part of it: closures refer to the functions they were created from by index,
+/// and restoring the image checks that these functions are the same in the restoring engine.
+///
+/// The bytes of an image can be written to a file and turned back into an image with
+/// [`HeapImage::from_bytes`]. They're only meant to be read by the same version of Mica.
+#[derive(Clone, PartialEq, Eq)]
+pub struct HeapImage {
+    bytes: Vec<u8>,
+}
+
+impl HeapImage {
+    /// Creates an image from bytes previously obtained from [`as_bytes`][Self::as_bytes]. The
+    /// bytes are not validated until the image is restored.
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            bytes: bytes.into(),
+        }
+    }
+
+    /// Returns the serialized representation of the image.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Converts the image into its serialized representation.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl fmt::Debug for HeapImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HeapImage({} bytes)", self.bytes.len())
+    }
+}
+
+/// The kinds of objects stored in an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Tag {
+    String,
+    Upvalue,
+    Closure,
+    DispatchTable,
+    Struct,
+    Trait,
+    List,
+    Dict,
+    Tuple,
+    Record,
+    /// A value the image can't store, which is taken from a global of the same name in the
+    /// restoring engine. This is how types and other values provided by the embedder are saved.
+    Global,
+}
+
+impl Tag {
+    const ALL: [Tag; 11] = [
+        Tag::String,
+        Tag::Upvalue,
+        Tag::Closure,
+        Tag::DispatchTable,
+        Tag::Struct,
+        Tag::Trait,
+        Tag::List,
+        Tag::Dict,
+        Tag::Tuple,
+        Tag::Record,
+        Tag::Global,
+    ];
+
+    /// Returns whether the image stores the contents of objects with this tag separately from the
+    /// objects themselves. This is the case for mutable objects, which are the only objects that
+    /// can take part in reference cycles.
+    fn is_filled_later(self) -> bool {
+        matches!(self, Tag::Upvalue | Tag::Struct | Tag::List | Tag::Dict)
+    }
+
+    /// Returns whether objects with this tag can be referred to by values.
+    fn is_value(self) -> bool {
+        !matches!(self, Tag::Upvalue | Tag::DispatchTable)
+    }
+}
+
+const VALUE_NIL: u8 = 0;
+const VALUE_FALSE: u8 = 1;
+const VALUE_TRUE: u8 = 2;
+const VALUE_NUMBER: u8 = 3;
+const VALUE_OBJECT: u8 = 4;
+
+const FUNCTION_BYTECODE: u8 = 0;
+const FUNCTION_FOREIGN: u8 = 1;
+const FUNCTION_CONTROL: u8 = 2;
+
+/// Hashes bytecode, such that the image can tell whether a function has changed since the image
+/// was saved. This uses FNV-1a, which unlike the standard library's hasher is stable across Rust
+/// versions.
+fn hash_bytecode(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn encode_parameter_count(count: FunctionParameterCount) -> u32 {
+    match count {
+        FunctionParameterCount::Fixed(count) => u32::from(count),
+        // Counts are 16-bit, so the bit above them is free to mark a rest parameter.
+        FunctionParameterCount::AtLeast(count) => 1 << 16 | u32::from(count),
+        FunctionParameterCount::Varargs => u32::MAX,
+    }
+}
+
+fn function_kind_tag(kind: &FunctionKind) -> u8 {
+    match kind {
+        FunctionKind::Bytecode { .. } => FUNCTION_BYTECODE,
+        FunctionKind::Foreign(_) | FunctionKind::Contextual(_) => FUNCTION_FOREIGN,
+        FunctionKind::Control(_) => FUNCTION_CONTROL,
+    }
+}
+
+/// Returns the number of upvalues closures of the given function capture.
+fn capture_count(kind: &FunctionKind) -> usize {
+    match kind {
+        FunctionKind::Bytecode {
+            captured_locals, ..
+        } => captured_locals.len(),
+        _ => 0,
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, x: u32) {
+    out.extend_from_slice(&x.to_le_bytes());
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    write_u32(
+        out,
+        u32::try_from(len).expect("too many elements to fit in a heap image"),
+    );
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// A node in the object graph.
+#[derive(Clone)]
+enum Node {
+    Value(RawValue),
+    Upvalue(Pin<Rc<Upvalue>>),
+    DispatchTable(GcRaw<DispatchTable>),
+}
+
+impl Node {
+    /// Returns the address identifying the node.
+    fn address(&self) -> usize {
+        match self {
+            Node::Value(value) => unsafe {
+                match value.kind() {
+                    ValueKind::String => value.get_raw_string_unchecked().get_raw() as usize,
+                    ValueKind::Function => value.get_raw_function_unchecked().get_raw() as usize,
+                    ValueKind::Struct => value.get_raw_struct_unchecked().get_raw() as usize,
+                    ValueKind::Trait => value.get_raw_trait_unchecked().get_raw() as usize,
+                    ValueKind::UserData => value.get_raw_user_data_unchecked().get_raw() as usize,
+                    ValueKind::Nil | ValueKind::Boolean | ValueKind::Number => {
+                        unreachable!("values that aren't objects are never nodes")
+                    }
+                }
+            },
+            Node::Upvalue(upvalue) => &**upvalue as *const Upvalue as usize,
+            Node::DispatchTable(dtable) => dtable.get_raw() as usize,
+        }
+    }
+}
+
+/// Returns whether the value is stored in the image as an object.
+fn is_object(value: RawValue) -> bool {
+    !matches!(
+        value.kind(),
+        ValueKind::Nil | ValueKind::Boolean | ValueKind::Number
+    )
+}
+
+fn downcast<T: 'static>(user_data: &dyn UserData) -> Option<&T> {
+    user_data.as_any().downcast_ref::<T>()
+}
+
+/// Serializes the object graph reachable from an engine's globals.
+///
+/// Objects are numbered in the order they're written. Immutable objects are written after all
+/// the objects they refer to, such that they can be created in one go when the image is restored.
+/// Mutable objects are written before their contents, which are stored separately as _fills_.
+/// This way reference cycles, which always go through a mutable object, can be restored.
+struct Writer<'e> {
+    env: &'e Environment,
+    /// Values of globals set by the embedder, which can be saved by name.
+    embedder_globals: HashMap<usize, &'e str>,
+    ids: HashMap<usize, u32>,
+    object_count: u32,
+    objects: Vec<u8>,
+    pending_fills: Vec<(u32, Node)>,
+    fill_count: u32,
+    fills: Vec<u8>,
+    methods: HashMap<MethodIndex, u32>,
+}
+
+impl<'e> Writer<'e> {
+    fn id(&self, node: &Node) -> u32 {
+        *self
+            .ids
+            .get(&node.address())
+            .expect("objects must be written before they are referred to")
+    }
+
+    fn write_value(&self, out: &mut Vec<u8>, value: RawValue) {
+        match value.kind() {
+            ValueKind::Nil => out.push(VALUE_NIL),
+            ValueKind::Boolean => {
+                let b = unsafe { value.get_boolean_unchecked() };
+                out.push(if b { VALUE_TRUE } else { VALUE_FALSE });
+            }
+            ValueKind::Number => {
+                out.push(VALUE_NUMBER);
+                let n = unsafe { *value.get_number_unchecked() };
+                out.extend_from_slice(&n.to_bits().to_le_bytes());
+            }
+            _ => {
+                out.push(VALUE_OBJECT);
+                write_u32(out, self.id(&Node::Value(value)));
+            }
+        }
+    }
+
+    fn method_id(&mut self, index: MethodIndex) -> u32 {
+        let next = self.methods.len() as u32;
+        *self.methods.entry(index).or_insert(next)
+    }
+
+    /// Returns the nodes that have to be written before the given node.
+    unsafe fn prerequisites(node: &Node) -> Vec<Node> {
+        let values = |values: &[RawValue]| {
+            values
+                .iter()
+                .copied()
+                .filter(|&value| is_object(value))
+                .map(Node::Value)
+                .collect()
+        };
+        match node {
+            Node::Value(value) => match value.kind() {
+                ValueKind::Function => value
+                    .get_raw_function_unchecked()
+                    .get()
+                    .captures
+                    .iter()
+                    .cloned()
+                    .map(Node::Upvalue)
+                    .collect(),
+                ValueKind::Struct => {
+                    vec![Node::DispatchTable(
+                        *value.get_raw_struct_unchecked().get().dtable.get(),
+                    )]
+                }
+                ValueKind::Trait => {
+                    vec![Node::DispatchTable(
+                        value.get_raw_trait_unchecked().get().dtable,
+                    )]
+                }
+                ValueKind::UserData => {
+                    let user_data = &**value.get_raw_user_data_unchecked().get();
+                    if let Some(tuple) = downcast::<Tuple>(user_data) {
+                        values(&tuple.fields)
+                    } else if let Some(record) = downcast::<Record>(user_data) {
+                        values(&record.fields)
+                    } else {
+                        vec![]
+                    }
+                }
+                _ => vec![],
+            },
+            Node::Upvalue(_) => vec![],
+            Node::DispatchTable(dtable) => {
+                let dtable = dtable.get();
+                dtable
+                    .instance
+                    .map(Node::DispatchTable)
+                    .into_iter()
+                    .chain(dtable.methods().map(|closure| Node::Value(closure.into())))
+                    .collect()
+            }
+        }
+    }
+
+    /// Writes the node and everything it refers to.
+    fn visit(&mut self, root: Node) -> Result<(), Error> {
+        let mut stack = vec![(root, false)];
+        while let Some((node, expanded)) = stack.pop() {
+            if self.ids.contains_key(&node.address()) {
+                continue;
+            }
+            if !expanded {
+                let prerequisites: Vec<_> = unsafe { Self::prerequisites(&node) }
+                    .into_iter()
+                    .filter(|node| !self.ids.contains_key(&node.address()))
+                    .collect();
+                if !prerequisites.is_empty() {
+                    stack.push((node, true));
+                    stack.extend(prerequisites.into_iter().map(|node| (node, false)));
+                    continue;
+                }
+            }
+            unsafe { self.write_object(node)? };
+        }
+        Ok(())
+    }
+
+    fn visit_value(&mut self, value: RawValue) -> Result<(), Error> {
+        if is_object(value) {
+            self.visit(Node::Value(value))?;
+        }
+        Ok(())
+    }
+
+    unsafe fn write_object(&mut self, node: Node) -> Result<(), Error> {
+        let mut out = Vec::new();
+        let tag = match &node {
+            Node::Value(value) => match value.kind() {
+                ValueKind::String => {
+                    write_str(&mut out, value.get_raw_string_unchecked().get());
+                    Tag::String
+                }
+                ValueKind::Function => {
+                    let closure = value.get_raw_function_unchecked().get();
+                    write_str(&mut out, &closure.name);
+                    write_u32(&mut out, u32::from(closure.function_id.to_opr24()));
+                    write_len(&mut out, closure.captures.len());
+                    for upvalue in &closure.captures {
+                        write_u32(&mut out, self.id(&Node::Upvalue(upvalue.clone())));
+                    }
+                    Tag::Closure
+                }
+                ValueKind::Struct => {
+                    let s = value.get_raw_struct_unchecked().get();
+                    write_u32(&mut out, self.id(&Node::DispatchTable(*s.dtable.get())));
+                    out.push(u8::from(s.is_sealed()));
+                    write_len(&mut out, s.field_count());
+                    Tag::Struct
+                }
+                ValueKind::Trait => {
+                    let t = value.get_raw_trait_unchecked().get();
+                    write_u32(&mut out, u32::from(t.id.to_opr24()));
+                    write_u32(&mut out, self.id(&Node::DispatchTable(t.dtable)));
+                    Tag::Trait
+                }
+                ValueKind::UserData => {
+                    let user_data = &**value.get_raw_user_data_unchecked().get();
+                    if downcast::<List>(user_data).is_some() {
+                        Tag::List
+                    } else if downcast::<Dict>(user_data).is_some() {
+                        Tag::Dict
+                    } else if let Some(tuple) = downcast::<Tuple>(user_data) {
+                        write_len(&mut out, tuple.fields.len());
+                        for &field in &tuple.fields {
+                            self.write_value(&mut out, field);
+                        }
+                        Tag::Tuple
+                    } else if let Some(record) = downcast::<Record>(user_data) {
+                        write_str(&mut out, &record.record_type.identifier);
+                        write_len(&mut out, record.fields.len());
+                        for &field in &record.fields {
+                            self.write_value(&mut out, field);
+                        }
+                        Tag::Record
+                    } else if let Some(name) = self.embedder_globals.get(&node.address()) {
+                        write_str(&mut out, name);
+                        Tag::Global
+                    } else {
+                        return Err(Error::UnsupportedInImage {
+                            type_name: user_data.type_name().into_owned().into(),
+                        });
+                    }
+                }
+                ValueKind::Nil | ValueKind::Boolean | ValueKind::Number => unreachable!(),
+            },
+            Node::Upvalue(_) => Tag::Upvalue,
+            Node::DispatchTable(dtable) => {
+                let dtable = dtable.get();
+                write_str(&mut out, &dtable.pretty_name);
+                write_str(&mut out, &dtable.type_name);
+                write_len(&mut out, dtable.fields.len());
+                for field in &dtable.fields {
+                    write_str(&mut out, field);
+                }
+                match dtable.instance {
+                    Some(instance) => {
+                        out.push(1);
+                        write_u32(&mut out, self.id(&Node::DispatchTable(instance)));
+                    }
+                    None => out.push(0),
+                }
+                let methods: Vec<_> = dtable.method_indices().collect();
+                write_len(&mut out, methods.len());
+                for index in methods {
+                    let closure = dtable.get_method(index).unwrap();
+                    let method_id = self.method_id(index);
+                    write_u32(&mut out, method_id);
+                    write_u32(&mut out, self.id(&Node::Value(closure.into())));
+                }
+                Tag::DispatchTable
+            }
+        };
+
+        let id = self.object_count;
+        self.object_count += 1;
+        self.ids.insert(node.address(), id);
+        self.objects.push(tag as u8);
+        self.objects.extend_from_slice(&out);
+        if tag.is_filled_later() {
+            self.pending_fills.push((id, node));
+        }
+        Ok(())
+    }
+
+    /// Writes the contents of mutable objects, until there are no more objects left to fill.
+    fn write_fills(&mut self) -> Result<(), Error> {
+        while let Some((id, node)) = self.pending_fills.pop() {
+            let contents: Vec<RawValue> = unsafe {
+                match &node {
+                    Node::Upvalue(upvalue) => vec![upvalue.get()],
+                    Node::Value(value) if value.kind() == ValueKind::Struct => {
+                        value.get_raw_struct_unchecked().get().fields().collect()
+                    }
+                    Node::Value(value) => {
+                        let user_data = &**value.get_raw_user_data_unchecked().get();
+                        if let Some(list) = downcast::<List>(user_data) {
+                            list.as_slice().to_vec()
+                        } else if let Some(dict) = downcast::<Dict>(user_data) {
+                            dict.iter().flat_map(|(key, value)| [key, value]).collect()
+                        } else {
+                            unreachable!()
+                        }
+                    }
+                    Node::DispatchTable(_) => unreachable!(),
+                }
+            };
+            for &value in &contents {
+                self.visit_value(value)?;
+            }
+            let mut out = Vec::new();
+            write_u32(&mut out, id);
+            write_len(&mut out, contents.len());
+            for value in contents {
+                self.write_value(&mut out, value);
+            }
+            self.fills.extend_from_slice(&out);
+            self.fill_count += 1;
+        }
+        Ok(())
+    }
+
+    fn write_globals(
+        &mut self,
+        out: &mut Vec<u8>,
+        globals: &[(&str, RawValue)],
+    ) -> Result<(), Error> {
+        for &(_, value) in globals {
+            self.visit_value(value)?;
+            self.write_fills()?;
+        }
+        write_len(out, globals.len());
+        for &(name, value) in globals {
+            write_str(out, name);
+            self.write_value(out, value);
+        }
+        Ok(())
+    }
+}
+
+/// Saves the heap of the engine into an image.
+pub(crate) fn save(engine: &Engine) -> Result<HeapImage, Error> {
+    let env = &engine.env;
+    let mut globals: Vec<_> = env
+        .global_names()
+        .map(|name| (name, engine.globals.get(env.get_global(name).unwrap())))
+        .collect();
+    globals.sort_by_key(|&(name, _)| name);
+    let mut modules: Vec<_> = env
+        .modules()
+        .map(|(name, slot)| (name, engine.globals.get(slot)))
+        .collect();
+    modules.sort_by_key(|&(name, _)| name);
+    let embedder_globals = env
+        .global_names()
+        .filter(|&name| env.is_global_builtin(env.get_global(name).unwrap()))
+        .map(|name| (name, engine.globals.get(env.get_global(name).unwrap())))
+        .filter(|&(_, value)| value.kind() == ValueKind::UserData)
+        .map(|(name, value)| (Node::Value(value).address(), name))
+        .collect();
+
+    let mut writer = Writer {
+        env,
+        embedder_globals,
+        ids: HashMap::new(),
+        object_count: 0,
+        objects: Vec::new(),
+        pending_fills: Vec::new(),
+        fill_count: 0,
+        fills: Vec::new(),
+        methods: HashMap::new(),
+    };
+    let mut roots = Vec::new();
+    writer.write_globals(&mut roots, &globals)?;
+    writer.write_globals(&mut roots, &modules)?;
+
+    let mut out = Vec::from(MAGIC);
+    write_u32(&mut out, VERSION);
+
+    write_len(&mut out, env.functions().len());
+    for function in env.functions() {
+        write_str(&mut out, &function.name);
+        write_u32(&mut out, encode_parameter_count(function.parameter_count));
+        out.push(function_kind_tag(&function.kind));
+        if let FunctionKind::Bytecode {
+            chunk,
+            captured_locals,
+        } = &function.kind
+        {
+            out.extend_from_slice(&hash_bytecode(&chunk.bytes()).to_le_bytes());
+            write_len(&mut out, captured_locals.len());
+        }
+    }
+
+    let trait_names: Vec<_> = (0usize..)
+        .map_while(|index| {
+            let index = TraitIndex::from_opr24(Opr24::try_from(index).ok()?);
+            writer.env.get_trait(index).map(|prototype| &prototype.name)
+        })
+        .collect();
+    write_len(&mut out, trait_names.len());
+    for name in trait_names {
+        write_str(&mut out, name);
+    }
+
+    let mut methods: Vec<_> = writer
+        .methods
+        .iter()
+        .map(|(&index, &id)| (id, index))
+        .collect();
+    methods.sort_by_key(|&(id, _)| id);
+    write_len(&mut out, methods.len());
+    for (_, index) in methods {
+        let signature = env.get_method_signature(index).unwrap();
+        write_str(&mut out, &signature.name);
+        out.push(signature.parameter_count.to_count_with_self());
+        match signature.trait_id {
+            Some(trait_id) => {
+                out.push(1);
+                write_u32(&mut out, u32::from(trait_id.to_opr24()));
+            }
+            None => out.push(0),
+        }
+    }
+
+    write_u32(&mut out, writer.object_count);
+    out.extend_from_slice(&writer.objects);
+    write_u32(&mut out, writer.fill_count);
+    out.extend_from_slice(&writer.fills);
+    out.extend_from_slice(&roots);
+
+    Ok(HeapImage { bytes: out })
+}
+
+fn incompatible(reason: impl Into<String>) -> Error {
+    Error::IncompatibleImage(reason.into())
+}
+
+/// Reads the primitive parts of an image.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if n > self.bytes.len() {
+            return Err(incompatible("the image is truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, Error> {
+        let len = self.u32()? as usize;
+        // Every element takes at least one byte, which protects against allocating huge vectors
+        // for damaged images.
+        if len > self.bytes.len() {
+            return Err(incompatible("the image is truncated"));
+        }
+        Ok(len)
+    }
+
+    fn str(&mut self) -> Result<&'a str, Error> {
+        let len = self.len()?;
+        std::str::from_utf8(self.take(len)?)
+            .map_err(|_| incompatible("the image contains a string that is not valid UTF-8"))
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(incompatible("the image is damaged")),
+        }
+    }
+}
+
+/// A value read from an image.
+#[derive(Clone, Copy)]
+enum ImageValue {
+    Immediate(RawValue),
+    Object(u32),
+}
+
+/// An object read from an image, whose references have been checked.
+enum Object {
+    String(Str),
+    Upvalue,
+    Closure {
+        name: Rc<str>,
+        function_id: FunctionIndex,
+        captures: Vec<u32>,
+    },
+    DispatchTable {
+        pretty_name: Rc<str>,
+        type_name: Rc<str>,
+        fields: Vec<Rc<str>>,
+        instance: Option<u32>,
+        methods: Vec<(MethodIndex, u32)>,
+    },
+    Struct {
+        dtable: u32,
+        sealed: bool,
+        field_count: usize,
+    },
+    Trait {
+        id: TraitIndex,
+        dtable: u32,
+    },
+    List,
+    Dict,
+    Tuple(Vec<ImageValue>),
+    Record {
+        identifier: Rc<str>,
+        fields: Vec<ImageValue>,
+    },
+    Global(RawValue),
+}
+
+/// Checks the image against the engine, and reads its objects.
+struct Loader<'a, 'e> {
+    reader: Reader<'a>,
+    engine: &'e Engine,
+    traits: usize,
+    methods: Vec<MethodIndex>,
+    tags: Vec<Tag>,
+}
+
+impl<'a, 'e> Loader<'a, 'e> {
+    fn check_code(&mut self) -> Result<(), Error> {
+        let env = &self.engine.env;
+        let function_count = self.reader.len()?;
+        if function_count > env.functions().len() {
+            return Err(incompatible(format!(
+                "the image was saved by an engine with {function_count} functions, but this engine \
+                 only has {}",
+                env.functions().len()
+            )));
+        }
+        for function in &env.functions()[..function_count] {
+            let name = self.reader.str()?;
+            let parameter_count = self.reader.u32()?;
+            let kind = self.reader.u8()?;
+            let mut same = name == &*function.name
+                && parameter_count == encode_parameter_count(function.parameter_count)
+                && kind == function_kind_tag(&function.kind);
+            if kind == FUNCTION_BYTECODE {
+                let hash = self.reader.u64()?;
+                let capture_count = self.reader.u32()? as usize;
+                if let FunctionKind::Bytecode {
+                    chunk,
+                    captured_locals,
+                } = &function.kind
+                {
+                    same &= hash == hash_bytecode(&chunk.bytes())
+                        && capture_count == captured_locals.len();
+                }
+            }
+            if !same {
+                return Err(incompatible(format!(
+                    "function {name} is not the same as in the engine that saved the image"
+                )));
+            }
+        }
+
+        self.traits = self.reader.len()?;
+        for index in 0..self.traits {
+            let name = self.reader.str()?;
+            let index = TraitIndex::from_opr24(Opr24::try_from(index).unwrap());
+            if env.get_trait(index).map(|prototype| &*prototype.name) != Some(name) {
+                return Err(incompatible(format!(
+                    "trait {name} is not the same as in the engine that saved the image"
+                )));
+            }
+        }
+
+        let method_count = self.reader.len()?;
+        for _ in 0..method_count {
+            let name = self.reader.str()?;
+            let parameter_count = self.reader.u8()?;
+            let trait_id = if self.reader.bool()? {
+                Some(self.trait_index()?)
+            } else {
+                None
+            };
+            let signature = MethodSignature {
+                name: Rc::from(name),
+                parameter_count: MethodParameterCount::from_count_with_self(parameter_count),
+                trait_id,
+            };
+            let index = env.get_method_index(&signature).ok_or_else(|| {
+                incompatible(format!("method {name} does not exist in this engine"))
+            })?;
+            self.methods.push(index);
+        }
+        Ok(())
+    }
+
+    fn trait_index(&mut self) -> Result<TraitIndex, Error> {
+        let index = self.reader.u32()? as usize;
+        if index >= self.traits {
+            return Err(incompatible("the image is damaged"));
+        }
+        Ok(TraitIndex::from_opr24(Opr24::try_from(index).unwrap()))
+    }
+
+    /// Reads a reference to an object of one of the given kinds. Unless the object is mutable,
+    /// it must come before the object currently being read.
+    fn object(&mut self, accept: impl Fn(Tag) -> bool) -> Result<u32, Error> {
+        let id = self.reader.u32()?;
+        match self.tags.get(id as usize) {
+            Some(&tag) if accept(tag) => Ok(id),
+            _ => Err(incompatible("the image is damaged")),
+        }
+    }
+
+    /// Reads a value. `object_count` is the number of objects the value may refer to.
+    fn value(&mut self, object_count: usize) -> Result<ImageValue, Error> {
+        Ok(match self.reader.u8()? {
+            VALUE_NIL => ImageValue::Immediate(RawValue::from(())),
+            VALUE_FALSE => ImageValue::Immediate(RawValue::from(false)),
+            VALUE_TRUE => ImageValue::Immediate(RawValue::from(true)),
+            VALUE_NUMBER => {
+                ImageValue::Immediate(RawValue::from(f64::from_bits(self.reader.u64()?)))
+            }
+            VALUE_OBJECT => {
+                let id = self.reader.u32()?;
+                match self.tags.get(id as usize) {
+                    Some(tag) if tag.is_value() && (id as usize) < object_count => {
+                        ImageValue::Object(id)
+                    }
+                    _ => return Err(incompatible("the image is damaged")),
+                }
+            }
+            _ => return Err(incompatible("the image is damaged")),
+        })
+    }
+
+    fn values(&mut self) -> Result<Vec<ImageValue>, Error> {
+        let len = self.reader.len()?;
+        (0..len).map(|_| self.value(self.tags.len())).collect()
+    }
+
+    /// Reads the values of globals or modules, along with their names.
+    fn roots(&mut self) -> Result<Vec<(&'a str, ImageValue)>, Error> {
+        let count = self.reader.len()?;
+        (0..count)
+            .map(|_| Ok((self.reader.str()?, self.value(self.tags.len())?)))
+            .collect()
+    }
+
+    fn read_object(&mut self) -> Result<Object, Error> {
+        let tag = *Tag::ALL
+            .get(usize::from(self.reader.u8()?))
+            .ok_or_else(|| incompatible("the image is damaged"))?;
+        let object = match tag {
+            Tag::String => Object::String(Str::new(self.reader.str()?)),
+            Tag::Upvalue => Object::Upvalue,
+            Tag::Closure => {
+                let name = Rc::from(self.reader.str()?);
+                let index = self.reader.u32()? as usize;
+                let function = self
+                    .engine
+                    .env
+                    .functions()
+                    .get(index)
+                    .ok_or_else(|| incompatible("the image is damaged"))?;
+                let capture_count = self.reader.len()?;
+                if capture_count != self::capture_count(&function.kind) {
+                    return Err(incompatible("the image is damaged"));
+                }
+                let captures = (0..capture_count)
+                    .map(|_| self.object(|tag| tag == Tag::Upvalue))
+                    .collect::<Result<_, _>>()?;
+                Object::Closure {
+                    name,
+                    function_id: FunctionIndex::from_opr24(Opr24::try_from(index).unwrap()),
+                    captures,
+                }
+            }
+            Tag::DispatchTable => {
+                let pretty_name = Rc::from(self.reader.str()?);
+                let type_name = Rc::from(self.reader.str()?);
+                let field_count = self.reader.len()?;
+                let fields = (0..field_count)
+                    .map(|_| self.reader.str().map(Rc::from))
+                    .collect::<Result<_, _>>()?;
+                let instance = if self.reader.bool()? {
+                    Some(self.object(|tag| tag == Tag::DispatchTable)?)
+                } else {
+                    None
+                };
+                let method_count = self.reader.len()?;
+                let mut methods = Vec::with_capacity(method_count);
+                for _ in 0..method_count {
+                    let method = *self
+                        .methods
+                        .get(self.reader.u32()? as usize)
+                        .ok_or_else(|| incompatible("the image is damaged"))?;
+                    let closure = self.object(|tag| tag == Tag::Closure)?;
+                    methods.push((method, closure));
+                }
+                Object::DispatchTable {
+                    pretty_name,
+                    type_name,
+                    fields,
+                    instance,
+                    methods,
+                }
+            }
+            Tag::Struct => Object::Struct {
+                dtable: self.object(|tag| tag == Tag::DispatchTable)?,
+                sealed: self.reader.bool()?,
+                field_count: self.reader.u32()? as usize,
+            },
+            Tag::Trait => Object::Trait {
+                id: self.trait_index()?,
+                dtable: self.object(|tag| tag == Tag::DispatchTable)?,
+            },
+            Tag::List => Object::List,
+            Tag::Dict => Object::Dict,
+            Tag::Tuple => Object::Tuple(self.values()?),
+            Tag::Record => {
+                let identifier: Rc<str> = Rc::from(self.reader.str()?);
+                let fields = self.values()?;
+                let field_count = if identifier.is_empty() {
+                    0
+                } else {
+                    identifier.split('+').count()
+                };
+                if fields.len() != field_count {
+                    return Err(incompatible("the image is damaged"));
+                }
+                Object::Record { identifier, fields }
+            }
+            Tag::Global => {
+                let name = self.reader.str()?;
+                let slot = self.engine.env.get_global(name).ok_or_else(|| {
+                    incompatible(format!("global {name} does not exist in this engine"))
+                })?;
+                Object::Global(self.engine.globals.get(slot))
+            }
+        };
+        self.tags.push(tag);
+        Ok(object)
+    }
+}
+
+/// An object created while restoring an image.
+enum Restored {
+    Value(RawValue),
+    Upvalue(Pin<Rc<Upvalue>>),
+    DispatchTable(GcRaw<DispatchTable>),
+}
+
+impl Restored {
+    fn value(&self) -> RawValue {
+        match self {
+            Restored::Value(value) => *value,
+            _ => unreachable!("references to objects are checked while loading"),
+        }
+    }
+
+    fn upvalue(&self) -> &Pin<Rc<Upvalue>> {
+        match self {
+            Restored::Upvalue(upvalue) => upvalue,
+            _ => unreachable!("references to objects are checked while loading"),
+        }
+    }
+
+    fn dtable(&self) -> GcRaw<DispatchTable> {
+        match self {
+            Restored::DispatchTable(dtable) => *dtable,
+            _ => unreachable!("references to objects are checked while loading"),
+        }
+    }
+}
+
+/// Restores the heap saved in the image into the engine.
+pub(crate) fn restore(engine: &mut Engine, image: &HeapImage) -> Result<(), Error> {
+    let mut reader = Reader {
+        bytes: &image.bytes,
+    };
+    if reader.take(MAGIC.len()).ok() != Some(MAGIC) {
+        return Err(incompatible("the data is not a heap image"));
+    }
+    if reader.u32()? != VERSION {
+        return Err(incompatible(
+            "the image was saved by an incompatible version of Mica",
+        ));
+    }
+
+    // Read and check everything before touching the engine, such that restoring a bad image
+    // leaves the engine intact.
+    let mut loader = Loader {
+        reader,
+        engine,
+        traits: 0,
+        methods: Vec::new(),
+        tags: Vec::new(),
+    };
+    loader.check_code()?;
+    let object_count = loader.reader.u32()? as usize;
+    let mut objects = Vec::new();
+    for _ in 0..object_count {
+        objects.push(loader.read_object()?);
+    }
+    let fill_count = loader.reader.len()?;
+    let mut fills = Vec::with_capacity(fill_count);
+    for _ in 0..fill_count {
+        let id = loader.object(Tag::is_filled_later)?;
+        let contents = loader.values()?;
+        let valid = match &objects[id as usize] {
+            Object::Upvalue => contents.len() == 1,
+            Object::Struct { field_count, .. } => contents.len() == *field_count,
+            Object::Dict => contents.len() % 2 == 0,
+            _ => true,
+        };
+        if !valid {
+            return Err(incompatible("the image is damaged"));
+        }
+        fills.push((id, contents));
+    }
+    let globals = loader.roots()?;
+    let modules = loader.roots()?;
+    let module_slots = modules
+        .iter()
+        .map(|&(name, _)| {
+            engine.env.get_module(name).ok_or_else(|| {
+                incompatible(format!("module {name} was not imported in this engine"))
+            })
+        })
+        .collect::<Result<Vec<GlobalIndex>, _>>()?;
+    if !loader.reader.bytes.is_empty() {
+        return Err(incompatible("the image is damaged"));
+    }
+
+    let Engine {
+        env,
+        library,
+        gc,
+        globals: engine_globals,
+        ..
+    } = engine;
+    let mut restored: Vec<Restored> = Vec::with_capacity(objects.len());
+    for object in objects {
+        let value = |restored: &[Restored], value: ImageValue| match value {
+            ImageValue::Immediate(value) => value,
+            ImageValue::Object(id) => restored[id as usize].value(),
+        };
+        let object = match object {
+            Object::String(s) => Restored::Value(RawValue::from(gc.allocate(s))),
+            Object::Upvalue => Restored::Upvalue(Upvalue::new_closed(RawValue::from(()))),
+            Object::Closure {
+                name,
+                function_id,
+                captures,
+            } => {
+                let captures = captures
+                    .into_iter()
+                    .map(|id| Pin::clone(restored[id as usize].upvalue()))
+                    .collect();
+                Restored::Value(RawValue::from(gc.allocate(Closure {
+                    name,
+                    function_id,
+                    captures,
+                })))
+            }
+            Object::DispatchTable {
+                pretty_name,
+                type_name,
+                fields,
+                instance,
+                methods,
+            } => {
+                let mut dtable = DispatchTable::new_for_type(type_name);
+                dtable.pretty_name = pretty_name;
+                dtable.fields = fields;
+                dtable.instance = instance.map(|id| restored[id as usize].dtable());
+                for (index, closure) in methods {
+                    let closure = restored[closure as usize].value();
+                    dtable.set_method(index, unsafe { closure.get_raw_function_unchecked() });
+                }
+                Restored::DispatchTable(gc.allocate(dtable))
+            }
+            Object::Struct {
+                dtable,
+                sealed,
+                field_count,
+            } => {
+                let fields = vec![RawValue::from(()); field_count];
+                let dtable = restored[dtable as usize].dtable();
+                let s = RawValue::from(gc.allocate(Struct::from_parts(dtable, sealed, fields)));
+                // Links from instances back to their types aren't saved, because they would make
+                // the type and its dispatch tables depend on each other. Types are the only
+                // structs with an instance dispatch table, so the link can be restored here.
+                if let Some(instance) = unsafe { dtable.get() }.instance {
+                    unsafe { instance.get() }.set_type_value(s);
+                }
+                Restored::Value(s)
+            }
+            Object::Trait { id, dtable } => {
+                let dtable = restored[dtable as usize].dtable();
+                Restored::Value(RawValue::from(gc.allocate(Trait { id, dtable })))
+            }
+            Object::List => {
+                let list: Box<dyn UserData> = Box::new(List::new(Vec::new()));
+                Restored::Value(RawValue::from(gc.allocate(list)))
+            }
+            Object::Dict => {
+                let dict: Box<dyn UserData> = Box::new(Dict::new());
+                Restored::Value(RawValue::from(gc.allocate(dict)))
+            }
+            Object::Tuple(fields) => {
+                let size = fields.len();
+                if library
+                    .builtin_dtables
+                    .tuples
+                    .get(size)
+                    .and_then(|dtable| dtable.as_ref())
+                    .is_none()
+                {
+                    library.generate_tuple(env, gc, size);
+                }
+                let fields = fields
+                    .into_iter()
+                    .map(|field| value(&restored, field))
+                    .collect();
+                let tuple: Box<dyn UserData> = Box::new(Tuple::new(fields));
+                Restored::Value(RawValue::from(gc.allocate(tuple)))
+            }
+            Object::Record { identifier, fields } => {
+                let index = library
+                    .get_or_generate_record(env, gc, &identifier)
+                    .map_err(|_| incompatible("there are too many record types in this engine"))?;
+                let record: Box<dyn UserData> = Box::new(Record {
+                    record_type: Rc::clone(library.builtin_dtables.get_record(index)),
+                    fields: fields
+                        .into_iter()
+                        .map(|field| value(&restored, field))
+                        .collect(),
+                });
+                Restored::Value(RawValue::from(gc.allocate(record)))
+            }
+            Object::Global(value) => Restored::Value(value),
+        };
+        restored.push(object);
+    }
+
+    let value = |value: ImageValue| match value {
+        ImageValue::Immediate(value) => value,
+        ImageValue::Object(id) => restored[id as usize].value(),
+    };
+    // Keys of dicts are hashed as they're inserted, so they have to be filled in after everything
+    // else. Dicts are filled in reverse, because dicts used as keys come after the dicts using
+    // them.
+    let (dict_fills, other_fills): (Vec<_>, Vec<_>) = fills
+        .into_iter()
+        .partition(|&(id, _)| matches!(restored[id as usize], Restored::Value(v) if is_dict(v)));
+    for (id, contents) in other_fills.into_iter().chain(dict_fills.into_iter().rev()) {
+        let contents: Vec<_> = contents.into_iter().map(value).collect();
+        unsafe {
+            match &restored[id as usize] {
+                Restored::Upvalue(upvalue) => upvalue.set(contents[0]),
+                Restored::Value(v) if v.kind() == ValueKind::Struct => {
+                    let s = v.get_raw_struct_unchecked().get();
+                    for (index, field) in contents.into_iter().enumerate() {
+                        s.set_field(index, field);
+                    }
+                }
+                Restored::Value(v) => {
+                    let user_data = &**v.get_raw_user_data_unchecked().get();
+                    if let Some(list) = downcast::<List>(user_data) {
+                        *list.get_mut() = contents;
+                    } else if let Some(dict) = downcast::<Dict>(user_data) {
+                        for pair in contents.chunks_exact(2) {
+                            dict.insert(pair[0], pair[1]);
+                        }
+                    }
+                }
+                Restored::DispatchTable(_) => unreachable!(),
+            }
+        }
+    }
+
+    for (name, v) in globals {
+        let slot = env.create_global(name).map_err(|_| Error::TooManyGlobals)?;
+        engine_globals.set(slot, value(v));
+    }
+    for ((_, v), slot) in modules.into_iter().zip(module_slots) {
+        engine_globals.set(slot, value(v));
+    }
+    crate::corelib::link_option(engine);
+    Ok(())
+}
+
+fn is_dict(value: RawValue) -> bool {
+    value.kind() == ValueKind::UserData
+        && downcast::<Dict>(unsafe { &**value.get_raw_user_data_unchecked().get() }).is_some()
+}
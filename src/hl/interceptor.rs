@@ -0,0 +1,114 @@
+//! Replacing methods with stubs at runtime.
+
+use std::{fmt, rc::Rc};
+
+use crate::{
+    ll::{
+        bytecode::{DispatchTable, MethodIndex},
+        error::LanguageErrorKind,
+        gc::Memory,
+        value::RawValue,
+        vm::CallInterceptor,
+    },
+    Error, MicaLanguageResultExt, Value,
+};
+
+/// What an interceptor installed with [`Engine::intercept`][crate::Engine::intercept] does with a
+/// method call.
+#[derive(Debug)]
+pub enum Interception {
+    /// Let the call through to the method, or to the next matching interceptor.
+    Proceed,
+    /// Skip the method and return the value to the caller instead.
+    Return(Value),
+}
+
+/// Identifies an interceptor, such that it can be removed with
+/// [`Engine::remove_interceptor`][crate::Engine::remove_interceptor].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InterceptorId(u64);
+
+type InterceptorFn = Box<dyn FnMut(&Value, &[Value]) -> Result<Interception, Error>>;
+
+struct Interceptor {
+    id: InterceptorId,
+    type_name: Rc<str>,
+    method_index: MethodIndex,
+    function: InterceptorFn,
+}
+
+/// The interceptors installed in an engine.
+#[derive(Default)]
+pub(crate) struct Interceptors {
+    next_id: u64,
+    interceptors: Vec<Interceptor>,
+}
+
+impl Interceptors {
+    pub(crate) fn add(
+        &mut self,
+        type_name: &str,
+        method_index: MethodIndex,
+        function: InterceptorFn,
+    ) -> InterceptorId {
+        let id = InterceptorId(self.next_id);
+        self.next_id += 1;
+        self.interceptors.push(Interceptor {
+            id,
+            type_name: Rc::from(type_name),
+            method_index,
+            function,
+        });
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: InterceptorId) -> bool {
+        let count = self.interceptors.len();
+        self.interceptors.retain(|interceptor| interceptor.id != id);
+        self.interceptors.len() != count
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.interceptors.is_empty()
+    }
+}
+
+impl fmt::Debug for Interceptors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.interceptors
+                    .iter()
+                    .map(|interceptor| (interceptor.id, &interceptor.type_name)),
+            )
+            .finish()
+    }
+}
+
+impl CallInterceptor for Interceptors {
+    fn intercept(
+        &mut self,
+        method_index: MethodIndex,
+        dtable: &DispatchTable,
+        arguments: &[RawValue],
+        gc: &mut Memory,
+    ) -> Option<Result<RawValue, LanguageErrorKind>> {
+        let mut values: Option<Vec<Value>> = None;
+        // Interceptors installed later get to see the call first, such that a test can override
+        // a more general stub.
+        for interceptor in self.interceptors.iter_mut().rev() {
+            if interceptor.method_index != method_index || interceptor.type_name != dtable.type_name
+            {
+                continue;
+            }
+            let values = values
+                .get_or_insert_with(|| arguments.iter().map(|&raw| Value::from_raw(raw)).collect());
+            match (interceptor.function)(&values[0], &values[1..]) {
+                Ok(Interception::Proceed) => (),
+                Ok(Interception::Return(value)) => return Some(Ok(value.to_raw(gc))),
+                Err(error) => return Some(Err::<RawValue, _>(error).to_language_error()),
+            }
+        }
+        None
+    }
+}
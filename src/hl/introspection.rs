@@ -0,0 +1,248 @@
+//! Read-only views into the globals and types registered in an engine.
+
+use std::rc::Rc;
+
+use crate::{
+    ll::{
+        bytecode::{DispatchTable, MethodSignature},
+        value::{RawValue, ValueKind},
+    },
+    Engine,
+};
+
+/// A read-only view into the globals and types registered in an [`Engine`], for tooling such as
+/// editor integrations.
+///
+/// Created with [`Engine::introspect`].
+#[derive(Debug, Clone, Copy)]
+pub struct Introspection<'e> {
+    engine: &'e Engine,
+}
+
+impl<'e> Introspection<'e> {
+    pub(crate) fn new(engine: &'e Engine) -> Self {
+        Self { engine }
+    }
+
+    /// Returns information about all globals, sorted by name.
+    pub fn globals(&self) -> Vec<GlobalInfo> {
+        let mut globals: Vec<_> = self
+            .engine
+            .env
+            .global_names()
+            .filter_map(|name| self.global(name))
+            .collect();
+        globals.sort_by(|a, b| a.name.cmp(&b.name));
+        globals
+    }
+
+    /// Returns information about the global with the given name, or `None` if there's no such
+    /// global.
+    pub fn global(&self, name: &str) -> Option<GlobalInfo> {
+        let slot = self.engine.env.get_global(name)?;
+        let value = self.engine.globals.get(slot);
+        let name = Rc::from(name);
+        Some(GlobalInfo {
+            is_builtin: self.engine.env.is_global_builtin(slot),
+            kind: self.value_kind(&name, value),
+            name,
+        })
+    }
+
+    /// Returns information about all functions stored in globals, sorted by name.
+    pub fn functions(&self) -> Vec<FunctionInfo> {
+        self.globals()
+            .into_iter()
+            .filter_map(|global| match global.kind {
+                GlobalKind::Function(function) => Some(function),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns information about all types stored in globals, sorted by name. This includes types
+    /// added by the embedder, types declared by scripts, and the built-in types that have a
+    /// global.
+    pub fn types(&self) -> Vec<TypeInfo> {
+        self.globals()
+            .into_iter()
+            .filter_map(|global| match global.kind {
+                GlobalKind::Type(info) => Some(info),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns every method signature known to the engine, sorted by name and arity. A signature
+    /// is known once any type implements a method with it, or any compiled script calls it.
+    ///
+    /// Methods called on values whose type isn't known can be completed from this list.
+    pub fn method_signatures(&self) -> Vec<MethodInfo> {
+        let mut methods: Vec<_> = self
+            .engine
+            .env
+            .method_signatures()
+            .map(|signature| self.method_info(signature))
+            .collect();
+        methods.sort_by(|a, b| {
+            (&a.name, a.parameter_count, &a.trait_name).cmp(&(
+                &b.name,
+                b.parameter_count,
+                &b.trait_name,
+            ))
+        });
+        methods
+    }
+
+    /// Returns information about one of the types built into the language, by its name (eg.
+    /// `Number` or `List`.) Unlike the types available through [`globals`][Self::globals], this
+    /// also includes types that don't have a global, such as `Dict` and `Function`.
+    pub fn builtin_type(&self, name: &str) -> Option<TypeInfo> {
+        let dtables = &self.engine.library.builtin_dtables;
+        let instance_dtable = [
+            &dtables.nil,
+            &dtables.boolean,
+            &dtables.number,
+            &dtables.string,
+            &dtables.function,
+            &dtables.list,
+            &dtables.dict,
+        ]
+        .into_iter()
+        .find(|dtable| &*dtable.type_name == name)?;
+        let type_methods = match self.global(name).map(|global| global.kind) {
+            Some(GlobalKind::Type(info)) => info.type_methods,
+            _ => Vec::new(),
+        };
+        Some(TypeInfo {
+            name: Rc::from(name),
+            type_methods,
+            instance_methods: self.methods(instance_dtable),
+        })
+    }
+
+    fn value_kind(&self, name: &Rc<str>, value: RawValue) -> GlobalKind {
+        match value.kind() {
+            // Safety: the values are kept alive by the engine's globals, and their kinds are
+            // checked before they're accessed.
+            ValueKind::Function => {
+                let closure = unsafe { value.get_raw_function_unchecked().get() };
+                let function =
+                    unsafe { self.engine.env.get_function_unchecked(closure.function_id) };
+                GlobalKind::Function(FunctionInfo {
+                    name: Rc::clone(name),
+                    parameter_count: function.parameter_count.to_fixed(),
+                    parameter_names: function
+                        .declaration
+                        .as_ref()
+                        .map(|declaration| declaration.parameter_names.clone()),
+                })
+            }
+            ValueKind::Struct | ValueKind::UserData => {
+                let dtable = match value.kind() {
+                    ValueKind::Struct => unsafe { value.get_raw_struct_unchecked().get().dtable() },
+                    _ => unsafe {
+                        let user_data = value.get_raw_user_data_unchecked().get();
+                        user_data.dtable_gcraw(Some(&self.engine.library)).get()
+                    },
+                };
+                // Only types have a dispatch table for their instances; other structs and user
+                // data are plain values.
+                match dtable.instance {
+                    Some(instance) => GlobalKind::Type(TypeInfo {
+                        name: Rc::clone(&dtable.type_name),
+                        type_methods: self.methods(dtable),
+                        instance_methods: self.methods(unsafe { instance.get() }),
+                    }),
+                    None => GlobalKind::Value {
+                        type_name: Rc::from(value.type_name()),
+                    },
+                }
+            }
+            _ => GlobalKind::Value {
+                type_name: Rc::from(value.type_name()),
+            },
+        }
+    }
+
+    /// Returns the methods in a dispatch table, sorted by name and arity.
+    fn methods(&self, dtable: &DispatchTable) -> Vec<MethodInfo> {
+        let env = &self.engine.env;
+        let mut methods: Vec<_> = dtable
+            .method_indices()
+            .filter_map(|index| env.get_method_signature(index))
+            .map(|signature| self.method_info(signature))
+            .collect();
+        methods.sort_by(|a, b| (&a.name, a.parameter_count).cmp(&(&b.name, b.parameter_count)));
+        methods
+    }
+
+    fn method_info(&self, signature: &MethodSignature) -> MethodInfo {
+        let rendered = signature.render(&self.engine.env);
+        MethodInfo {
+            name: rendered.name,
+            parameter_count: rendered.parameter_count,
+            trait_name: rendered.trait_name,
+        }
+    }
+}
+
+/// Information about a global variable.
+#[derive(Debug, Clone)]
+pub struct GlobalInfo {
+    /// The name of the global.
+    pub name: Rc<str>,
+    /// Whether the global was set by the embedder, as opposed to being declared by a script.
+    pub is_builtin: bool,
+    /// What the global holds.
+    pub kind: GlobalKind,
+}
+
+/// What kind of value a global holds.
+#[derive(Debug, Clone)]
+pub enum GlobalKind {
+    /// A bare function.
+    Function(FunctionInfo),
+    /// A type, such as a struct declared by a script or a type added with
+    /// [`Engine::add_type`].
+    Type(TypeInfo),
+    /// Any other value.
+    Value {
+        /// The name of the value's type.
+        type_name: Rc<str>,
+    },
+}
+
+/// Information about a bare function.
+#[derive(Debug, Clone)]
+pub struct FunctionInfo {
+    /// The name of the global the function is stored in.
+    pub name: Rc<str>,
+    /// The number of parameters the function accepts, or `None` if it accepts any number of
+    /// arguments.
+    pub parameter_count: Option<u16>,
+    /// The names of the parameters, if the function was declared in Mica.
+    pub parameter_names: Option<Vec<Rc<str>>>,
+}
+
+/// Information about a type and its methods.
+#[derive(Debug, Clone)]
+pub struct TypeInfo {
+    /// The name of the type.
+    pub name: Rc<str>,
+    /// Methods callable on the type itself, such as constructors.
+    pub type_methods: Vec<MethodInfo>,
+    /// Methods callable on instances of the type.
+    pub instance_methods: Vec<MethodInfo>,
+}
+
+/// Information about a method.
+#[derive(Debug, Clone)]
+pub struct MethodInfo {
+    /// The name of the method.
+    pub name: Rc<str>,
+    /// The number of parameters, not including `self`.
+    pub parameter_count: u8,
+    /// The name of the trait the method belongs to, if any.
+    pub trait_name: Option<Rc<str>>,
+}
@@ -0,0 +1,200 @@
+//! Modules registered by the embedder, which scripts can `import`.
+
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    ffvariants, Engine, Error, ForeignFunction, FunctionParameterCount, IntoValue, TypeBuilder,
+    UserData, Value,
+};
+
+/// Types that can become the value of an imported module.
+///
+/// A module is an ordinary value that's bound to a variable by `import`; its functions are
+/// usually exposed as methods. [`ModuleBuilder`]s are the most convenient way to create one, and
+/// [`TypeBuilder`]s work too, as their static functions can be called on the type directly.
+pub trait IntoModule {
+    /// Creates the module's value.
+    fn into_module(self, engine: &mut Engine) -> Result<Value, Error>;
+}
+
+impl IntoModule for Value {
+    fn into_module(self, _engine: &mut Engine) -> Result<Value, Error> {
+        Ok(self)
+    }
+}
+
+/// Module factories can fail, in which case the error is returned by the
+/// [compilation][Engine::compile] of the script importing the module.
+impl<M> IntoModule for Result<M, Error>
+where
+    M: IntoModule,
+{
+    fn into_module(self, engine: &mut Engine) -> Result<Value, Error> {
+        self?.into_module(engine)
+    }
+}
+
+impl<T> IntoModule for TypeBuilder<T>
+where
+    T: UserData,
+{
+    fn into_module(self, engine: &mut Engine) -> Result<Value, Error> {
+        let built = self.build_in_library(&mut engine.env, &mut engine.library, &mut engine.gc)?;
+        Ok(built.make_type(&mut engine.gc))
+    }
+}
+
+impl IntoModule for ModuleBuilder {
+    fn into_module(self, engine: &mut Engine) -> Result<Value, Error> {
+        let mut type_builder = self.type_builder;
+        for (name, make_value) in self.values {
+            let value = make_value(engine)?;
+            type_builder = type_builder.add_static(&name, move || value.clone());
+        }
+        let built = type_builder.build(
+            &mut engine.env,
+            &mut engine.gc,
+            &engine.library.builtin_traits,
+        )?;
+        Ok(built.make_type(&mut engine.gc))
+    }
+}
+
+/// The type of modules created by [`ModuleBuilder`]s. It has no instances.
+struct Module;
+
+impl UserData for Module {}
+
+type MakeValue = Box<dyn FnOnce(&mut Engine) -> Result<Value, Error>>;
+
+/// A builder for a module: a bundle of functions and constants that are accessed through a single
+/// value, such as `audio.play(sound)`.
+///
+/// The module can be made available to scripts as a global through [`Engine::add_module`], or
+/// [`import`][Engine::add_lazy_module]ed on demand.
+///
+/// # Examples
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use mica::{Engine, ModuleBuilder};
+///
+/// let mut engine = Engine::new();
+/// engine.add_module(
+///     "audio",
+///     ModuleBuilder::new("audio")
+///         .add_function("volume_db", |volume: f64| 20.0 * volume.log10())
+///         .add_constant("sample_rate", 48000),
+/// )?;
+///
+/// let db: f64 = engine
+///     .start("audio.mi", "audio.volume_db(audio.sample_rate / 4800)")?
+///     .trampoline()?;
+/// assert_eq!(db, 20.0);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ModuleBuilder {
+    type_builder: TypeBuilder<Module>,
+    values: Vec<(Rc<str>, MakeValue)>,
+}
+
+impl ModuleBuilder {
+    /// Creates a new, empty module. The name is used when the module is printed or shows up in
+    /// error messages.
+    pub fn new(name: impl Into<Rc<str>>) -> Self {
+        Self {
+            type_builder: TypeBuilder::new(name),
+            values: vec![],
+        }
+    }
+
+    /// Adds a function to the module.
+    ///
+    /// Like with [`TypeBuilder::add_static`], the function must accept a fixed number of
+    /// arguments.
+    pub fn add_function<F, V>(mut self, name: &str, f: F) -> Self
+    where
+        V: ffvariants::BareExactArgs,
+        F: ForeignFunction<V, ParameterCount = FunctionParameterCount>,
+    {
+        self.type_builder = self.type_builder.add_static(name, f);
+        self
+    }
+
+    /// Adds a constant to the module. Scripts read it like a function without arguments, using
+    /// `module.name`.
+    pub fn add_constant(mut self, name: &str, value: impl IntoValue + 'static) -> Self {
+        self.values.push((
+            Rc::from(name),
+            Box::new(move |engine| {
+                Ok(value.into_value_with_engine_state(&engine.library, &mut engine.gc))
+            }),
+        ));
+        self
+    }
+
+    /// Nests another module inside of this one, such that it's available as `module.name`.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{Engine, ModuleBuilder};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_module(
+    ///     "game",
+    ///     ModuleBuilder::new("game").add_module(
+    ///         "physics",
+    ///         ModuleBuilder::new("game.physics").add_constant("gravity", 9.81),
+    ///     ),
+    /// )?;
+    ///
+    /// let gravity: f64 = engine.start("game.mi", "game.physics.gravity")?.trampoline()?;
+    /// assert_eq!(gravity, 9.81);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_module(mut self, name: &str, module: impl IntoModule + 'static) -> Self {
+        self.values.push((
+            Rc::from(name),
+            Box::new(move |engine| module.into_module(engine)),
+        ));
+        self
+    }
+}
+
+impl fmt::Debug for ModuleBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModuleBuilder")
+            .field(
+                "values",
+                &self.values.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+type ModuleFactory = Box<dyn FnOnce(&mut Engine) -> Result<Value, Error>>;
+
+/// Modules that were registered, but haven't been imported by any script yet.
+#[derive(Default)]
+pub(crate) struct LazyModules {
+    factories: HashMap<Rc<str>, ModuleFactory>,
+}
+
+impl LazyModules {
+    pub(crate) fn insert(&mut self, name: Rc<str>, factory: ModuleFactory) {
+        self.factories.insert(name, factory);
+    }
+
+    /// Removes the module's factory, such that it can be called.
+    pub(crate) fn take(&mut self, name: &str) -> Option<ModuleFactory> {
+        self.factories.remove(name)
+    }
+}
+
+impl fmt::Debug for LazyModules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.factories.keys()).finish()
+    }
+}
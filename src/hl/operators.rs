@@ -0,0 +1,103 @@
+//! Operators that types can overload using
+//! [`TypeBuilder::add_operator`][crate::TypeBuilder::add_operator].
+//!
+//! Each operator is implemented by a method with a special name, which is called when the left
+//! operand of the operator isn't a number. The markers in this module ensure that the function
+//! implementing an operator accepts the right number of arguments.
+
+use crate::ffvariants::{FallibleSelf, ImmutableSelf, InfallibleSelf, MutableSelf};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// An operator that can be implemented with functions whose signature fits `S`.
+pub trait OperatorFunction<S>: private::Sealed {
+    #[doc(hidden)]
+    const NAME: &'static str;
+}
+
+macro_rules! implement_operator_function {
+    ($T:ty, $name:expr, ($($Args:ident),*)) => {
+        impl private::Sealed for $T {}
+
+        macro_rules! implement_for {
+            ($ST:ty) => {
+                impl<S, $($Args),*> OperatorFunction<$ST> for $T {
+                    const NAME: &'static str = $name;
+                }
+            };
+        }
+
+        implement_for!(FallibleSelf<ImmutableSelf<S>, (&S, $($Args),*)>);
+        implement_for!(InfallibleSelf<ImmutableSelf<S>, (&S, $($Args),*)>);
+        implement_for!(FallibleSelf<MutableSelf<S>, (&mut S, $($Args),*)>);
+        implement_for!(InfallibleSelf<MutableSelf<S>, (&mut S, $($Args),*)>);
+    };
+}
+
+/// `a + b`, implemented with `fn (&self, b) -> T`.
+#[derive(Debug)]
+pub struct Add;
+implement_operator_function!(Add, "add", (B));
+
+/// `a - b`, implemented with `fn (&self, b) -> T`.
+#[derive(Debug)]
+pub struct Sub;
+implement_operator_function!(Sub, "sub", (B));
+
+/// `a * b`, implemented with `fn (&self, b) -> T`.
+#[derive(Debug)]
+pub struct Mul;
+implement_operator_function!(Mul, "mul", (B));
+
+/// `a / b`, implemented with `fn (&self, b) -> T`.
+#[derive(Debug)]
+pub struct Div;
+implement_operator_function!(Div, "div", (B));
+
+/// `a % b`, implemented with `fn (&self, b) -> T`.
+#[derive(Debug)]
+pub struct Mod;
+implement_operator_function!(Mod, "mod", (B));
+
+/// `-a`, implemented with `fn (&self) -> T`.
+#[derive(Debug)]
+pub struct Neg;
+implement_operator_function!(Neg, "neg", ());
+
+/// `a == b` and `a != b`, implemented with `fn (&self, b) -> bool`.
+#[derive(Debug)]
+pub struct Eq;
+implement_operator_function!(Eq, "eq", (B));
+
+/// `a < b`, `a <= b`, `a > b`, and `a >= b`, implemented with `fn (&self, b) -> f64`.
+///
+/// The result should be negative if `a` is less than `b`, zero if they're equal, and positive if
+/// `a` is greater than `b`. The function is also called when only the right operand has it, with
+/// the operands swapped.
+#[derive(Debug)]
+pub struct Cmp;
+implement_operator_function!(Cmp, "cmp", (B));
+
+/// `a[i]`, implemented with `fn (&self, i) -> T`.
+#[derive(Debug)]
+pub struct Index;
+implement_operator_function!(Index, "index", (I));
+
+/// `a[i] = x`, implemented with `fn (&mut self, i, x) -> T`. The result should be `x`, as it
+/// becomes the result of the assignment.
+#[derive(Debug)]
+pub struct SetIndex;
+implement_operator_function!(SetIndex, "set_index", (I, X));
+
+/// `a[s..e]`, implemented with `fn (&self, s, e) -> T`. Omitted bounds are passed as `nil`.
+#[derive(Debug)]
+pub struct Slice;
+implement_operator_function!(Slice, "slice", (S0, E));
+
+/// `a[s..e] = x`, implemented with `fn (&mut self, s, e, x) -> T`. The result should be `x`, as
+/// it becomes the result of the assignment.
+#[derive(Debug)]
+pub struct SetSlice;
+implement_operator_function!(SetSlice, "set_slice", (S0, E, X));
@@ -0,0 +1,873 @@
+//! Persistent, immutable collections that can be shared between engines.
+//!
+//! Regular lists and dicts live inside a single engine's GC and may only be used from the thread
+//! that created them. The collections in this module are instead reference counted with [`Arc`]s
+//! and never change after they're built; "modifying" one returns a new collection that shares most
+//! of its structure with the original. This makes it cheap to hand the same dataset to many
+//! engines, even ones running on different threads.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FromIterator,
+    sync::Arc,
+};
+
+use crate::{
+    hl::userdata::Object,
+    into_value::UsesEngine,
+    ll::{
+        bytecode::Library,
+        gc::Memory,
+        value::{self, Dict, List, RawValue, Str},
+    },
+    Error, Gc, IntoValue, MicaResultExt, TryFromValue, UserData, Value,
+};
+
+/// How many nested collections a value converted into a [`SharedValue`] may have. This also stops
+/// the conversion of lists that contain themselves.
+const MAX_NESTING: usize = 256;
+
+/// A value that can be stored inside of persistent collections.
+///
+/// Unlike [`Value`], a `SharedValue` is not tied to any engine, and can be sent between threads.
+/// Strings are copied when they're converted into a [`Value`], but persistent collections are
+/// shared.
+#[derive(Clone, Debug, Default)]
+pub enum SharedValue {
+    /// The `nil` literal.
+    #[default]
+    Nil,
+    /// A boolean.
+    Boolean(bool),
+    /// A number.
+    Number(f64),
+    /// An immutable string.
+    String(Arc<str>),
+    /// A persistent list.
+    List(PersistentList),
+    /// A persistent dict.
+    Dict(PersistentDict),
+}
+
+impl SharedValue {
+    /// Returns the name of the value's type, as seen by scripts.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Nil => "Nil",
+            Self::Boolean(_) => "Boolean",
+            Self::Number(_) => "Number",
+            Self::String(_) => "String",
+            Self::List(_) => "PersistentList",
+            Self::Dict(_) => "PersistentDict",
+        }
+    }
+
+    /// Returns the hash of the value, as used for keys in [`PersistentDict`]s.
+    fn hash_code(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn from_value(value: &Value, nesting: usize) -> Result<Self, Error> {
+        if nesting > MAX_NESTING {
+            return Err(NestedTooDeeply).mica();
+        }
+        let from_raw = |raw: RawValue| Self::from_value(&Value::from_raw(raw), nesting + 1);
+        Ok(match value {
+            Value::Nil => Self::Nil,
+            Value::False => Self::Boolean(false),
+            Value::True => Self::Boolean(true),
+            Value::Number(x) => Self::Number(*x),
+            Value::String(s) => Self::String(Arc::from(s.as_str())),
+            Value::List(list) => {
+                let list = list.0.as_any().downcast_ref::<List>().unwrap();
+                let elements = unsafe { list.as_slice() };
+                Self::List(
+                    elements
+                        .iter()
+                        .map(|&x| from_raw(x))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            Value::Dict(dict) => {
+                let dict = dict.0.as_any().downcast_ref::<Dict>().unwrap();
+                let pairs = unsafe { dict.iter() };
+                Self::Dict(
+                    pairs
+                        .map(|(key, value)| Ok((from_raw(key)?, from_raw(value)?)))
+                        .collect::<Result<_, Error>>()?,
+                )
+            }
+            Value::UserData(u) => {
+                let u: &dyn value::UserData = (**u).as_ref();
+                if let Some(list) = downcast_object::<PersistentList>(u)? {
+                    Self::List(list)
+                } else if let Some(dict) = downcast_object::<PersistentDict>(u)? {
+                    Self::Dict(dict)
+                } else {
+                    return Err(not_shareable(value));
+                }
+            }
+            _ => return Err(not_shareable(value)),
+        })
+    }
+}
+
+fn downcast_object<T>(user_data: &dyn value::UserData) -> Result<Option<T>, Error>
+where
+    T: UserData + Clone,
+{
+    match user_data.as_any().downcast_ref::<Object<T>>() {
+        Some(object) => {
+            let (object, _guard) = unsafe { object.unsafe_borrow()? };
+            Ok(Some(object.clone()))
+        }
+        None => Ok(None),
+    }
+}
+
+fn not_shareable(value: &Value) -> Error {
+    Error::TypeMismatch {
+        expected: "a value that can be shared between engines".into(),
+        got: value.type_name().to_string().into(),
+    }
+}
+
+impl PartialEq for SharedValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Nil, Self::Nil) => true,
+            (Self::Boolean(a), Self::Boolean(b)) => a == b,
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::String(a), Self::String(b)) => a == b,
+            (Self::List(a), Self::List(b)) => a == b,
+            (Self::Dict(a), Self::Dict(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Hash for SharedValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Nil => (),
+            Self::Boolean(b) => b.hash(state),
+            // Positive and negative zero are equal, so they must hash the same.
+            Self::Number(x) => (if *x == 0.0 { 0.0 } else { *x }).to_bits().hash(state),
+            Self::String(s) => s.hash(state),
+            Self::List(list) => {
+                list.len().hash(state);
+                for element in list {
+                    element.hash(state);
+                }
+            }
+            // The order of pairs in a dict depends on how it was built, so only the length is
+            // hashed.
+            Self::Dict(dict) => dict.len().hash(state),
+        }
+    }
+}
+
+impl From<()> for SharedValue {
+    fn from(_: ()) -> Self {
+        Self::Nil
+    }
+}
+
+impl From<bool> for SharedValue {
+    fn from(b: bool) -> Self {
+        Self::Boolean(b)
+    }
+}
+
+impl From<f64> for SharedValue {
+    fn from(x: f64) -> Self {
+        Self::Number(x)
+    }
+}
+
+impl From<&str> for SharedValue {
+    fn from(s: &str) -> Self {
+        Self::String(Arc::from(s))
+    }
+}
+
+impl From<String> for SharedValue {
+    fn from(s: String) -> Self {
+        Self::String(Arc::from(s))
+    }
+}
+
+impl From<PersistentList> for SharedValue {
+    fn from(list: PersistentList) -> Self {
+        Self::List(list)
+    }
+}
+
+impl From<PersistentDict> for SharedValue {
+    fn from(dict: PersistentDict) -> Self {
+        Self::Dict(dict)
+    }
+}
+
+impl IntoValue for SharedValue {
+    type EngineUse = UsesEngine;
+
+    fn into_value(self, (library, gc): (&Library, &mut Memory)) -> Value {
+        match self {
+            Self::Nil => Value::Nil,
+            Self::Boolean(b) => Value::new(b),
+            Self::Number(x) => Value::Number(x),
+            Self::String(s) => Value::String(Gc::new(Str::new(&s))),
+            Self::List(list) => list.into_value_with_engine_state(library, gc),
+            Self::Dict(dict) => dict.into_value_with_engine_state(library, gc),
+        }
+    }
+}
+
+/// Lists and dicts are converted deeply into persistent collections, which copies them. Functions,
+/// structs, and other values that only make sense inside of one engine cannot be converted.
+impl TryFromValue for SharedValue {
+    fn try_from_value(value: &Value, _: &Library) -> Result<Self, Error> {
+        Self::from_value(value, 0)
+    }
+}
+
+#[derive(Debug)]
+struct NestedTooDeeply;
+
+impl fmt::Display for NestedTooDeeply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "value is nested too deeply to be shared (or contains itself)"
+        )
+    }
+}
+
+impl std::error::Error for NestedTooDeeply {}
+
+/// The number of bits of an index or hash consumed by each level of a trie.
+const BITS: u32 = 5;
+/// The number of children a trie node can have.
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+/// A node in a [`PersistentList`]'s trie.
+#[derive(Debug)]
+enum ListNode {
+    Branch(Vec<Arc<ListNode>>),
+    Leaf(Vec<SharedValue>),
+}
+
+impl ListNode {
+    /// Creates a path of nodes leading down to a leaf with a single element.
+    fn path(shift: u32, element: SharedValue) -> Arc<Self> {
+        if shift == 0 {
+            Arc::new(Self::Leaf(vec![element]))
+        } else {
+            Arc::new(Self::Branch(vec![Self::path(shift - BITS, element)]))
+        }
+    }
+
+    fn push(&self, shift: u32, index: usize, element: SharedValue) -> Arc<Self> {
+        match self {
+            Self::Leaf(elements) => {
+                let mut elements = elements.clone();
+                elements.push(element);
+                Arc::new(Self::Leaf(elements))
+            }
+            Self::Branch(children) => {
+                let mut children = children.clone();
+                let child = (index >> shift) & MASK;
+                if let Some(node) = children.get_mut(child) {
+                    *node = node.push(shift - BITS, index, element);
+                } else {
+                    children.push(Self::path(shift - BITS, element));
+                }
+                Arc::new(Self::Branch(children))
+            }
+        }
+    }
+
+    fn set(&self, shift: u32, index: usize, element: SharedValue) -> Arc<Self> {
+        match self {
+            Self::Leaf(elements) => {
+                let mut elements = elements.clone();
+                elements[index & MASK] = element;
+                Arc::new(Self::Leaf(elements))
+            }
+            Self::Branch(children) => {
+                let mut children = children.clone();
+                let child = &mut children[(index >> shift) & MASK];
+                *child = child.set(shift - BITS, index, element);
+                Arc::new(Self::Branch(children))
+            }
+        }
+    }
+
+    /// Removes the last element from the node, returning `None` if the node becomes empty.
+    fn pop(&self, shift: u32, index: usize) -> Option<Arc<Self>> {
+        match self {
+            Self::Leaf(elements) => {
+                let elements = &elements[..elements.len() - 1];
+                (!elements.is_empty()).then(|| Arc::new(Self::Leaf(elements.to_vec())))
+            }
+            Self::Branch(children) => {
+                let mut children = children.clone();
+                let child = (index >> shift) & MASK;
+                match children[child].pop(shift - BITS, index) {
+                    Some(node) => children[child] = node,
+                    None => {
+                        children.pop();
+                    }
+                }
+                (!children.is_empty()).then(|| Arc::new(Self::Branch(children)))
+            }
+        }
+    }
+}
+
+/// An immutable list with structural sharing.
+///
+/// Elements are stored in a 32-way trie, so lookups and updates take logarithmic time, and updated
+/// versions of a list share all but the modified path with the original. Cloning a list is cheap
+/// and doesn't copy any elements.
+///
+/// Persistent lists are available to scripts as `PersistentList`.
+///
+/// # Examples
+/// ```
+/// use mica::{Engine, PersistentList, SharedValue};
+///
+/// let dataset: PersistentList = (0..1000).map(|i| SharedValue::from(i as f64)).collect();
+/// let mut engine = Engine::new();
+/// engine.set("dataset", dataset.clone())?;
+/// let sum: f64 = engine
+///     .start("example.mi", "dataset.get(10) + dataset.push(5).get(1000)")?
+///     .trampoline()?;
+/// assert_eq!(sum, 15.0);
+/// assert_eq!(dataset.len(), 1000);
+/// # Ok::<(), mica::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PersistentList {
+    root: Option<Arc<ListNode>>,
+    /// The number of index bits consumed above the leaves.
+    shift: u32,
+    len: usize,
+}
+
+impl PersistentList {
+    /// Creates an empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of elements in the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the list has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the element at the given index, or `None` if the index is out of bounds.
+    pub fn get(&self, index: usize) -> Option<&SharedValue> {
+        if index >= self.len {
+            return None;
+        }
+        let mut node = self.root.as_deref()?;
+        let mut shift = self.shift;
+        loop {
+            match node {
+                ListNode::Branch(children) => {
+                    node = &children[(index >> shift) & MASK];
+                    shift -= BITS;
+                }
+                ListNode::Leaf(elements) => return elements.get(index & MASK),
+            }
+        }
+    }
+
+    /// Returns the first element of the list.
+    pub fn first(&self) -> Option<&SharedValue> {
+        self.get(0)
+    }
+
+    /// Returns the last element of the list.
+    pub fn last(&self) -> Option<&SharedValue> {
+        self.len.checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    /// Returns a copy of the list with the element appended to its end.
+    pub fn push(&self, element: impl Into<SharedValue>) -> Self {
+        let element = element.into();
+        let (root, shift) = match &self.root {
+            None => (ListNode::path(0, element), 0),
+            // The trie is full, so it needs to grow another level.
+            Some(root) if self.len == WIDTH << self.shift => (
+                Arc::new(ListNode::Branch(vec![
+                    Arc::clone(root),
+                    ListNode::path(self.shift, element),
+                ])),
+                self.shift + BITS,
+            ),
+            Some(root) => (root.push(self.shift, self.len, element), self.shift),
+        };
+        Self {
+            root: Some(root),
+            shift,
+            len: self.len + 1,
+        }
+    }
+
+    /// Returns a copy of the list with the element at the given index replaced, or `None` if the
+    /// index is out of bounds.
+    pub fn set(&self, index: usize, element: impl Into<SharedValue>) -> Option<Self> {
+        if index >= self.len {
+            return None;
+        }
+        let root = self.root.as_ref()?.set(self.shift, index, element.into());
+        Some(Self {
+            root: Some(root),
+            ..*self
+        })
+    }
+
+    /// Returns a copy of the list with its last element removed. Popping from an empty list
+    /// returns an empty list.
+    pub fn pop(&self) -> Self {
+        let Some(root) = &self.root else {
+            return Self::new();
+        };
+        let mut root = root.pop(self.shift, self.len - 1);
+        let mut shift = self.shift;
+        // Remove levels of the trie that are no longer needed.
+        while let Some(ListNode::Branch(children)) = root.as_deref() {
+            if children.len() > 1 {
+                break;
+            }
+            root = Some(Arc::clone(&children[0]));
+            shift -= BITS;
+        }
+        Self {
+            root,
+            shift: if self.len > 1 { shift } else { 0 },
+            len: self.len - 1,
+        }
+    }
+
+    /// Returns an iterator over the elements of the list.
+    pub fn iter(&self) -> ListIter {
+        ListIter {
+            stack: self.root.iter().map(|node| (Arc::clone(node), 0)).collect(),
+        }
+    }
+}
+
+impl PartialEq for PersistentList {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl FromIterator<SharedValue> for PersistentList {
+    fn from_iter<T: IntoIterator<Item = SharedValue>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |list, element| list.push(element))
+    }
+}
+
+impl IntoIterator for &PersistentList {
+    type Item = SharedValue;
+    type IntoIter = ListIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl UserData for PersistentList {}
+
+/// An iterator over the elements of a [`PersistentList`]. The iterator keeps the list's contents
+/// alive, so it doesn't borrow the list.
+#[derive(Debug, Clone)]
+pub struct ListIter {
+    /// The nodes being iterated over, with the index of the next child to visit.
+    stack: Vec<(Arc<ListNode>, usize)>,
+}
+
+impl Iterator for ListIter {
+    type Item = SharedValue;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, index) = self.stack.last_mut()?;
+            match &**node {
+                ListNode::Leaf(elements) => {
+                    if let Some(element) = elements.get(*index) {
+                        *index += 1;
+                        return Some(element.clone());
+                    }
+                }
+                ListNode::Branch(children) => {
+                    if let Some(child) = children.get(*index) {
+                        *index += 1;
+                        let child = Arc::clone(child);
+                        self.stack.push((child, 0));
+                        continue;
+                    }
+                }
+            }
+            self.stack.pop();
+        }
+    }
+}
+
+/// A node in a [`PersistentDict`]'s hash array mapped trie.
+#[derive(Debug, Default)]
+struct DictNode {
+    /// Which of the node's 32 slots are occupied.
+    bitmap: u32,
+    /// The occupied slots, in order.
+    entries: Vec<DictEntry>,
+}
+
+/// The pairs stored in a `DictEntry::Collision`.
+type CollidingPairs = Vec<(SharedValue, SharedValue)>;
+
+#[derive(Debug, Clone)]
+enum DictEntry {
+    Pair(u64, SharedValue, SharedValue),
+    Node(Arc<DictNode>),
+    /// Pairs whose keys have the same hash.
+    Collision(u64, Arc<CollidingPairs>),
+}
+
+/// The result of removing a key from a [`DictNode`].
+enum Removal {
+    NotFound,
+    Removed(Option<DictEntry>),
+}
+
+impl DictNode {
+    fn slot(hash: u64, shift: u32) -> u32 {
+        1 << ((hash >> shift) as usize & MASK)
+    }
+
+    fn index(&self, slot: u32) -> usize {
+        (self.bitmap & (slot - 1)).count_ones() as usize
+    }
+
+    fn get(&self, hash: u64, shift: u32, key: &SharedValue) -> Option<&SharedValue> {
+        let slot = Self::slot(hash, shift);
+        if self.bitmap & slot == 0 {
+            return None;
+        }
+        match &self.entries[self.index(slot)] {
+            DictEntry::Pair(h, k, v) => (*h == hash && k == key).then_some(v),
+            DictEntry::Node(node) => node.get(hash, shift + BITS, key),
+            DictEntry::Collision(h, pairs) => pairs
+                .iter()
+                .find(|(k, _)| *h == hash && k == key)
+                .map(|(_, v)| v),
+        }
+    }
+
+    /// Returns a copy of the node with the pair inserted, and whether a new key was added.
+    fn insert(
+        &self,
+        hash: u64,
+        shift: u32,
+        key: SharedValue,
+        value: SharedValue,
+    ) -> (Arc<Self>, bool) {
+        let slot = Self::slot(hash, shift);
+        let index = self.index(slot);
+        let mut entries = self.entries.clone();
+        let mut added = true;
+        if self.bitmap & slot == 0 {
+            entries.insert(index, DictEntry::Pair(hash, key, value));
+        } else {
+            let entry = &mut entries[index];
+            *entry = match entry {
+                DictEntry::Pair(h, k, _) if *h == hash && *k == key => {
+                    added = false;
+                    DictEntry::Pair(hash, key, value)
+                }
+                DictEntry::Pair(h, k, v) if *h == hash => {
+                    DictEntry::Collision(hash, Arc::new(vec![(k.clone(), v.clone()), (key, value)]))
+                }
+                DictEntry::Node(node) => {
+                    let (node, was_added) = node.insert(hash, shift + BITS, key, value);
+                    added = was_added;
+                    DictEntry::Node(node)
+                }
+                DictEntry::Collision(h, pairs) if *h == hash => {
+                    let mut pairs = (**pairs).clone();
+                    if let Some(pair) = pairs.iter_mut().find(|(k, _)| *k == key) {
+                        pair.1 = value;
+                        added = false;
+                    } else {
+                        pairs.push((key, value));
+                    }
+                    DictEntry::Collision(hash, Arc::new(pairs))
+                }
+                DictEntry::Pair(h, ..) | DictEntry::Collision(h, _) => {
+                    let existing_hash = *h;
+                    DictEntry::Node(Self::merge(
+                        entry.clone(),
+                        existing_hash,
+                        DictEntry::Pair(hash, key, value),
+                        hash,
+                        shift + BITS,
+                    ))
+                }
+            };
+        }
+        let node = Self {
+            bitmap: self.bitmap | slot,
+            entries,
+        };
+        (Arc::new(node), added)
+    }
+
+    /// Creates a node containing two entries with different hashes.
+    fn merge(a: DictEntry, a_hash: u64, b: DictEntry, b_hash: u64, shift: u32) -> Arc<Self> {
+        let (a_slot, b_slot) = (Self::slot(a_hash, shift), Self::slot(b_hash, shift));
+        let node = if a_slot == b_slot {
+            Self {
+                bitmap: a_slot,
+                entries: vec![DictEntry::Node(Self::merge(
+                    a,
+                    a_hash,
+                    b,
+                    b_hash,
+                    shift + BITS,
+                ))],
+            }
+        } else {
+            Self {
+                bitmap: a_slot | b_slot,
+                entries: if a_slot < b_slot {
+                    vec![a, b]
+                } else {
+                    vec![b, a]
+                },
+            }
+        };
+        Arc::new(node)
+    }
+
+    fn remove(&self, hash: u64, shift: u32, key: &SharedValue) -> Removal {
+        let slot = Self::slot(hash, shift);
+        if self.bitmap & slot == 0 {
+            return Removal::NotFound;
+        }
+        let index = self.index(slot);
+        let replacement = match &self.entries[index] {
+            DictEntry::Pair(h, k, _) if *h == hash && k == key => None,
+            DictEntry::Pair(..) => return Removal::NotFound,
+            DictEntry::Node(node) => match node.remove(hash, shift + BITS, key) {
+                Removal::NotFound => return Removal::NotFound,
+                Removal::Removed(entry) => entry,
+            },
+            DictEntry::Collision(h, pairs) if *h == hash => {
+                let Some(position) = pairs.iter().position(|(k, _)| k == key) else {
+                    return Removal::NotFound;
+                };
+                let mut pairs = (**pairs).clone();
+                pairs.remove(position);
+                if pairs.len() == 1 {
+                    let (k, v) = pairs.pop().unwrap();
+                    Some(DictEntry::Pair(hash, k, v))
+                } else {
+                    Some(DictEntry::Collision(hash, Arc::new(pairs)))
+                }
+            }
+            DictEntry::Collision(..) => return Removal::NotFound,
+        };
+        let mut node = Self {
+            bitmap: self.bitmap,
+            entries: self.entries.clone(),
+        };
+        match replacement {
+            Some(entry) => node.entries[index] = entry,
+            None => {
+                node.entries.remove(index);
+                node.bitmap &= !slot;
+            }
+        }
+        // Nodes that are left with a single pair are collapsed into their parent, so that the
+        // trie doesn't stay deeper than necessary.
+        Removal::Removed(match node.entries.as_slice() {
+            [] => None,
+            [entry @ (DictEntry::Pair(..) | DictEntry::Collision(..))] if shift > 0 => {
+                Some(entry.clone())
+            }
+            _ => Some(DictEntry::Node(Arc::new(node))),
+        })
+    }
+}
+
+/// An immutable dict with structural sharing.
+///
+/// Pairs are stored in a hash array mapped trie, so lookups and updates take logarithmic time, and
+/// updated versions of a dict share all but the modified path with the original. Cloning a dict is
+/// cheap and doesn't copy any pairs.
+///
+/// Persistent dicts are available to scripts as `PersistentDict`.
+///
+/// # Examples
+/// ```
+/// use mica::{Engine, PersistentDict, SharedValue};
+///
+/// let config = PersistentDict::new()
+///     .insert("name", "example")
+///     .insert("version", 2.0);
+/// let mut engine = Engine::new();
+/// engine.set("config", config)?;
+/// let version: f64 = engine.start("example.mi", r#" config.get("version") "#)?.trampoline()?;
+/// assert_eq!(version, 2.0);
+/// # Ok::<(), mica::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PersistentDict {
+    root: Arc<DictNode>,
+    len: usize,
+}
+
+impl PersistentDict {
+    /// Creates an empty dict.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of pairs in the dict.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the dict has no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the value associated with the key, or `None` if there isn't one.
+    pub fn get(&self, key: &SharedValue) -> Option<&SharedValue> {
+        self.root.get(key.hash_code(), 0, key)
+    }
+
+    /// Returns whether the dict contains the key.
+    pub fn contains_key(&self, key: &SharedValue) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns a copy of the dict with the key associated with the value, replacing the key's
+    /// previous value if it had one.
+    pub fn insert(&self, key: impl Into<SharedValue>, value: impl Into<SharedValue>) -> Self {
+        let key = key.into();
+        let (root, added) = self.root.insert(key.hash_code(), 0, key, value.into());
+        Self {
+            root,
+            len: self.len + usize::from(added),
+        }
+    }
+
+    /// Returns a copy of the dict without the key. If the dict doesn't contain the key, the copy is
+    /// identical to the original.
+    pub fn remove(&self, key: &SharedValue) -> Self {
+        match self.root.remove(key.hash_code(), 0, key) {
+            Removal::NotFound => self.clone(),
+            Removal::Removed(entry) => Self {
+                root: match entry {
+                    Some(DictEntry::Node(node)) => node,
+                    _ => Arc::default(),
+                },
+                len: self.len - 1,
+            },
+        }
+    }
+
+    /// Returns an iterator over the pairs in the dict, in an unspecified order.
+    pub fn iter(&self) -> DictIter {
+        DictIter {
+            stack: vec![(Arc::clone(&self.root), 0)],
+            collision: None,
+        }
+    }
+}
+
+impl PartialEq for PersistentDict {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self
+                .iter()
+                .all(|(key, value)| other.get(&key) == Some(&value))
+    }
+}
+
+impl FromIterator<(SharedValue, SharedValue)> for PersistentDict {
+    fn from_iter<T: IntoIterator<Item = (SharedValue, SharedValue)>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |dict, (key, value)| dict.insert(key, value))
+    }
+}
+
+impl IntoIterator for &PersistentDict {
+    type Item = (SharedValue, SharedValue);
+    type IntoIter = DictIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl UserData for PersistentDict {}
+
+/// An iterator over the pairs of a [`PersistentDict`]. The iterator keeps the dict's contents
+/// alive, so it doesn't borrow the dict.
+#[derive(Debug, Clone)]
+pub struct DictIter {
+    /// The nodes being iterated over, with the index of the next entry to visit.
+    stack: Vec<(Arc<DictNode>, usize)>,
+    /// The collision currently being iterated over.
+    collision: Option<(Arc<CollidingPairs>, usize)>,
+}
+
+impl Iterator for DictIter {
+    type Item = (SharedValue, SharedValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((pairs, index)) = &mut self.collision {
+                if let Some(pair) = pairs.get(*index) {
+                    *index += 1;
+                    return Some(pair.clone());
+                }
+                self.collision = None;
+            }
+            let (node, index) = self.stack.last_mut()?;
+            let Some(entry) = node.entries.get(*index) else {
+                self.stack.pop();
+                continue;
+            };
+            *index += 1;
+            match entry {
+                DictEntry::Pair(_, key, value) => return Some((key.clone(), value.clone())),
+                DictEntry::Node(node) => {
+                    let node = Arc::clone(node);
+                    self.stack.push((node, 0));
+                }
+                DictEntry::Collision(_, pairs) => self.collision = Some((Arc::clone(pairs), 0)),
+            }
+        }
+    }
+}
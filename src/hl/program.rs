@@ -0,0 +1,121 @@
+//! Programs that can be instantiated in many engines, including ones on other threads.
+
+use std::{fmt, sync::Arc};
+
+use crate::{Engine, Error, HeapImage, Value};
+
+/// Creates the engine a program is run in, adding the functions and types its scripts use.
+type CreateEngine = dyn Fn() -> Result<Engine, Error> + Send + Sync;
+
+/// A set of scripts that have been run once, such that engines in the same state can be created
+/// from them quickly.
+///
+/// An [`Engine`] and its values are reference counted without synchronization, so they cannot be
+/// moved to another thread. A `Program` on the other hand only holds the scripts' source code and
+/// a [`HeapImage`] of the globals they set, so it is [`Send`] and [`Sync`], and cloning it is
+/// cheap. Servers can build a program once, and [instantiate][Self::instantiate] it in each
+/// worker thread to get an engine of their own.
+///
+/// # Examples
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use mica::{Engine, Program};
+///
+/// let program = Program::new(
+///     || Ok(Engine::new()),
+///     [("greeting.mi", r#"let greeting = "Hello".cat(", world!")"#)],
+/// )?;
+/// let workers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let program = program.clone();
+///         std::thread::spawn(move || {
+///             let mut engine = program.instantiate().unwrap();
+///             engine.get::<String>("greeting").unwrap()
+///         })
+///     })
+///     .collect();
+/// for worker in workers {
+///     assert_eq!(worker.join().unwrap(), "Hello, world!");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Program {
+    create_engine: Arc<CreateEngine>,
+    scripts: Arc<[(String, String)]>,
+    image: Arc<HeapImage>,
+}
+
+impl Program {
+    /// Creates an engine using `create_engine`, runs the given scripts in it in order, and saves
+    /// the globals they set.
+    ///
+    /// `create_engine` is called again by every [`instantiate`][Self::instantiate], so it must
+    /// add the same functions and types each time. Each script is a pair of a filename and
+    /// source code.
+    ///
+    /// # Errors
+    /// Errors from creating the engine or running the scripts are returned, as well as
+    /// [`Error::UnsupportedInImage`] if a global holds a value that can't be saved in a
+    /// [`HeapImage`].
+    pub fn new<F, N, S>(
+        create_engine: F,
+        scripts: impl IntoIterator<Item = (N, S)>,
+    ) -> Result<Self, Error>
+    where
+        F: Fn() -> Result<Engine, Error> + Send + Sync + 'static,
+        N: Into<String>,
+        S: Into<String>,
+    {
+        let scripts: Arc<[(String, String)]> = scripts
+            .into_iter()
+            .map(|(filename, source)| (filename.into(), source.into()))
+            .collect();
+        let mut engine = create_engine()?;
+        for (filename, source) in scripts.iter() {
+            let _: Value = engine.start(filename, source.as_str())?.trampoline()?;
+        }
+        let image = engine.snapshot()?;
+        Ok(Self {
+            create_engine: Arc::new(create_engine),
+            scripts,
+            image: Arc::new(image),
+        })
+    }
+
+    /// Creates a new engine with the program's globals, as they were after running its scripts.
+    ///
+    /// The scripts are compiled again, but they are not run, so their side effects don't happen
+    /// a second time. Changes made to the returned engine don't affect the program or any other
+    /// engine instantiated from it.
+    pub fn instantiate(&self) -> Result<Engine, Error> {
+        let mut engine = (self.create_engine)()?;
+        for (filename, source) in self.scripts.iter() {
+            engine.compile(filename, source.as_str())?;
+        }
+        engine.restore(&self.image)?;
+        Ok(engine)
+    }
+
+    /// Returns the image of the globals the program's scripts set.
+    pub fn image(&self) -> &HeapImage {
+        &self.image
+    }
+}
+
+impl fmt::Debug for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Program")
+            .field(
+                "scripts",
+                &self
+                    .scripts
+                    .iter()
+                    .map(|(filename, _)| filename)
+                    .collect::<Vec<_>>(),
+            )
+            .field("image", &self.image)
+            .finish_non_exhaustive()
+    }
+}
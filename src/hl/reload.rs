@@ -0,0 +1,235 @@
+//! Reloading scripts without losing the state of the engine.
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    ll::{
+        bytecode::{Function, FunctionKind},
+        value::{RawValue, ValueKind},
+        vm,
+    },
+    Engine, Error, Fiber, Value,
+};
+
+/// The functions declared by each module, in the order they were generated.
+///
+/// A module's declarations are matched against the ones from the previous version of the module
+/// by their position in this list. Each entry lists every function that is a version of the same
+/// declaration, as closures of all of them may still be alive.
+#[derive(Debug, Default)]
+pub(crate) struct ModuleFunctions {
+    modules: HashMap<Rc<str>, Vec<Vec<usize>>>,
+}
+
+impl ModuleFunctions {
+    /// Records the functions generated while compiling a module.
+    pub(crate) fn compiled(
+        &mut self,
+        module_name: &Rc<str>,
+        functions: impl Iterator<Item = usize>,
+    ) {
+        self.modules.insert(
+            Rc::clone(module_name),
+            functions.map(|id| vec![id]).collect(),
+        );
+    }
+}
+
+/// Returns whether `new` is a version of the declaration `old`, which can replace its code.
+fn is_same_declaration(old: &Function, new: &Function) -> bool {
+    let (
+        FunctionKind::Bytecode {
+            captured_locals: old_captures,
+            ..
+        },
+        FunctionKind::Bytecode {
+            captured_locals: new_captures,
+            ..
+        },
+    ) = (&old.kind, &new.kind)
+    else {
+        return false;
+    };
+    old.name == new.name
+        && old.parameter_count == new.parameter_count
+        && old.visibility == new.visibility
+        && old.impl_block.is_some() == new.impl_block.is_some()
+        && old_captures == new_captures
+}
+
+/// Returns whether the value is a struct type, rather than an instance.
+fn is_struct_type(value: RawValue) -> bool {
+    value.kind() == ValueKind::Struct
+        && unsafe { value.get_raw_struct_unchecked().get().dtable() }
+            .instance
+            .is_some()
+}
+
+impl Engine {
+    /// Compiles a new version of a script that was run before, and runs it without losing the
+    /// state of the engine.
+    ///
+    /// Functions and methods declared by the old version of the script are replaced in place with
+    /// their new versions, so closures and struct instances created before the reload run the new
+    /// code. A function is considered a new version of an old one if it has the same name,
+    /// parameters, and captured variables, and is declared in the same order relative to other
+    /// functions with the same name. Functions that don't have a counterpart are added as new.
+    ///
+    /// The new version of the script is run from the top, but globals that existed before the
+    /// reload keep their values, with two exceptions:
+    /// - Globals holding functions are set to their new definitions.
+    /// - Globals holding struct types keep the old type, such that existing instances remain
+    ///   instances of it, but methods that were added to the type are added to the old type as
+    ///   well.
+    ///
+    /// Changing the fields of a struct is not supported, as existing instances keep their old
+    /// fields. Code that is running in a fiber while the script is reloaded finishes executing the
+    /// function it was in using the old code.
+    ///
+    /// # Errors
+    /// Errors from compiling the script are returned before any functions are replaced. Runtime
+    /// errors and [`Error::Deadlock`] are returned if running the script fails; functions
+    /// have been replaced by that point already.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{Engine, Value};
+    ///
+    /// let mut engine = Engine::new();
+    /// let v1 = r#"
+    ///     struct Player impl
+    ///         func new() constructor = @health = 100
+    ///         func damage(amount) = do @health = @health - amount end
+    ///     end
+    ///     let player = Player.new()
+    /// "#;
+    /// let _: Value = engine.start("player.mi", v1)?.trampoline()?;
+    /// let _: Value = engine.start("game.mi", "player.damage(10)")?.trampoline()?;
+    ///
+    /// // Armor was added, so damage is halved from now on.
+    /// let v2 = v1.replace("@health - amount", "@health - amount / 2");
+    /// engine.reload("player.mi", v2)?;
+    /// let health: f64 = engine.start("game.mi", "player.damage(10)")?.trampoline()?;
+    /// assert_eq!(health, 85.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reload(
+        &mut self,
+        filename: impl AsRef<str>,
+        source: impl Into<String>,
+    ) -> Result<(), Error> {
+        let module_name: Rc<str> = Rc::from(filename.as_ref());
+        let old_functions = self
+            .module_functions
+            .modules
+            .remove(&module_name)
+            .unwrap_or_default();
+        let old_globals: Vec<_> = self
+            .env
+            .global_names()
+            .filter_map(|name| self.env.get_global(name))
+            .map(|slot| (slot, Value::from_raw(self.globals.get(slot))))
+            .collect();
+
+        let script = match self.compile(filename, source) {
+            Ok(script) => script,
+            Err(error) => {
+                self.module_functions
+                    .modules
+                    .insert(module_name, old_functions);
+                return Err(error);
+            }
+        };
+        let main_chunk = Rc::clone(&script.main_chunk);
+        let new_functions = script.functions.clone();
+
+        // Match up the new functions with the old ones, and replace the old functions' code.
+        let mut matched = vec![false; old_functions.len()];
+        let mut functions = Vec::with_capacity(new_functions.len());
+        for new_id in new_functions {
+            let new = &self.env.functions()[new_id];
+            let counterpart = old_functions.iter().enumerate().position(|(i, versions)| {
+                !matched[i] && is_same_declaration(&self.env.functions()[versions[0]], new)
+            });
+            let Some(index) = counterpart else {
+                functions.push(vec![new_id]);
+                continue;
+            };
+            matched[index] = true;
+            let mut versions = old_functions[index].clone();
+            let FunctionKind::Bytecode {
+                chunk,
+                captured_locals,
+            } = &new.kind
+            else {
+                unreachable!("only bytecode functions are matched");
+            };
+            let (chunk, captured_locals) = (Rc::clone(chunk), captured_locals.clone());
+            let declaration = new.declaration.clone();
+            for &old_id in &versions {
+                let old = &mut self.env.functions_mut()[old_id];
+                old.kind = FunctionKind::Bytecode {
+                    chunk: Rc::clone(&chunk),
+                    captured_locals: captured_locals.clone(),
+                };
+                old.declaration = declaration.clone();
+            }
+            versions.push(new_id);
+            functions.push(versions);
+        }
+        self.module_functions
+            .modules
+            .insert(Rc::clone(&module_name), functions);
+
+        let _: Value = Fiber {
+            inner: vm::Fiber::new(main_chunk, Vec::new()),
+            engine: self,
+        }
+        .trampoline()?;
+
+        for (slot, old) in old_globals {
+            let old = old.to_raw(&mut self.gc);
+            let new = self.globals.get(slot);
+            if new.kind() == ValueKind::Function {
+                continue;
+            }
+            if is_struct_type(old) && is_struct_type(new) {
+                unsafe { merge_methods(old, new) };
+            }
+            self.globals.set(slot, old);
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds the methods of the struct type `new` that `old` doesn't have to `old`, along with the
+/// instance methods.
+///
+/// # Safety
+/// Both values must be struct types.
+unsafe fn merge_methods(old: RawValue, new: RawValue) {
+    let old = old.get_raw_struct_unchecked().get().dtable();
+    let new = new.get_raw_struct_unchecked().get().dtable();
+    if old.type_name != new.type_name {
+        return;
+    }
+    let dtables = [
+        (old, new),
+        (
+            old.instance.unwrap_unchecked().get(),
+            new.instance.unwrap_unchecked().get(),
+        ),
+    ];
+    for (old, new) in dtables {
+        let added: Vec<_> = new
+            .method_indices()
+            .filter(|&index| old.get_method(index).is_none())
+            .collect();
+        for index in added {
+            old.extend_method(index, new.get_method(index).unwrap_unchecked());
+        }
+    }
+}
@@ -0,0 +1,497 @@
+//! Cooperative scheduling of multiple fibers.
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{
+    call_fiber,
+    ll::{error::StackTraceEntry, gc::Memory, value::RawValue, vm},
+    stack_trace, Engine, Error, FiberState, MicaResultExt, TryFromValue, UserData, Value,
+};
+
+/// Identifies a fiber started by a [`Scheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskId(usize);
+
+/// Runs multiple fibers on a single engine, switching between them whenever one of them blocks.
+///
+/// Fibers block on operations that can't complete yet, such as sending to a full
+/// [channel](crate::corelib) or receiving from an empty one. A fiber that's running on its own
+/// can't do anything about that, but a scheduler can run other fibers in the meantime, which may
+/// then do what the blocked fiber is waiting for.
+///
+/// # Examples
+/// ```
+/// use mica::{Engine, Scheduler, Value};
+///
+/// let mut engine = Engine::new();
+/// let _: Value = engine.start("setup.mi", "let numbers = Channel.new()")?.trampoline()?;
+///
+/// let mut scheduler = Scheduler::new(&mut engine);
+/// let consumer = scheduler.start(
+///     "consumer.mi",
+///     "let sum = 0\nfor n in numbers do sum = sum + n end\nsum",
+/// )?;
+/// scheduler.start(
+///     "producer.mi",
+///     "let i = 1\nwhile i <= 10 do numbers.send(i)\ni = i + 1 end\nnumbers.close()",
+/// )?;
+/// scheduler.run()?;
+/// assert_eq!(scheduler.result::<f64>(consumer)?, Some(55.0));
+/// # Ok::<(), mica::Error>(())
+/// ```
+pub struct Scheduler<'e> {
+    engine: &'e mut Engine,
+    tasks: Vec<Rc<Task>>,
+    /// The task to start the next pass with.
+    next_task: usize,
+}
+
+/// A fiber run by a scheduler, shared with the [`TaskHandle`]s that refer to it.
+pub(crate) struct Task {
+    fiber: Rc<RefCell<vm::Fiber>>,
+    outcome: RefCell<Outcome>,
+    /// Tasks spawned by this task's fiber.
+    children: RefCell<Vec<Rc<Task>>>,
+}
+
+enum Outcome {
+    Running,
+    /// The fiber has finished, but the task is still waiting for its children to finish.
+    WaitingForChildren(Value),
+    Finished(Value),
+    Failed,
+    Cancelled,
+}
+
+impl Task {
+    fn new(fiber: vm::Fiber, gc: &mut Memory) -> Rc<Self> {
+        let fiber = Rc::new(RefCell::new(fiber));
+        gc.add_fiber(&fiber);
+        Rc::new(Self {
+            fiber,
+            outcome: RefCell::new(Outcome::Running),
+            children: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Returns whether the task has finished running, either successfully or with an error.
+    pub(crate) fn is_finished(&self) -> bool {
+        matches!(
+            *self.outcome.borrow(),
+            Outcome::Finished(_) | Outcome::Failed | Outcome::Cancelled
+        )
+    }
+
+    /// Returns the value the task evaluated to, or [`Error::WouldBlock`] if it's still running.
+    pub(crate) fn join(&self) -> Result<Value, Error> {
+        match &*self.outcome.borrow() {
+            Outcome::Running | Outcome::WaitingForChildren(_) => Err(Error::WouldBlock),
+            Outcome::Finished(value) => Ok(value.clone()),
+            Outcome::Failed => Err(TaskError::Failed).mica(),
+            Outcome::Cancelled => Err(TaskError::Cancelled).mica(),
+        }
+    }
+
+    /// Finishes a task that was waiting for its children, if they have all finished. Returns
+    /// whether the task finished.
+    fn settle(&self) -> bool {
+        let mut outcome = self.outcome.borrow_mut();
+        if let Outcome::WaitingForChildren(value) = &*outcome {
+            if self
+                .children
+                .borrow()
+                .iter()
+                .all(|child| child.is_finished())
+            {
+                *outcome = Outcome::Finished(value.clone());
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Cancels all of the task's unfinished children, and their children.
+    fn cancel_children(&self) {
+        for child in self.children.borrow().iter() {
+            if !child.is_finished() {
+                *child.outcome.borrow_mut() = Outcome::Cancelled;
+                child.cancel_children();
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum TaskError {
+    Failed,
+    Cancelled,
+    SpawnOutsideScheduler,
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed => f.write_str("the awaited task failed with an error"),
+            Self::Cancelled => f.write_str("the awaited task was cancelled"),
+            Self::SpawnOutsideScheduler => {
+                f.write_str("spawn can only be used in fibers run by a scheduler")
+            }
+        }
+    }
+}
+
+/// A task as seen by scripts, through the `Task` type.
+pub(crate) struct TaskHandle(pub(crate) Rc<Task>);
+
+impl UserData for TaskHandle {}
+
+/// Receives tasks spawned by scripts, until the scheduler running them picks them up.
+#[derive(Default)]
+pub(crate) struct Spawner {
+    /// Whether a scheduler is running. Nothing would ever run tasks spawned outside of one, so
+    /// that's an error.
+    running: bool,
+    spawned: Vec<Rc<Task>>,
+}
+
+impl fmt::Debug for Spawner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Spawner")
+            .field("running", &self.running)
+            .field("spawned", &self.spawned.len())
+            .finish()
+    }
+}
+
+impl Spawner {
+    /// Spawns a task that calls the given function without arguments.
+    pub(crate) fn spawn(&mut self, gc: &mut Memory, function: RawValue) -> Result<Rc<Task>, Error> {
+        if !self.running {
+            return Err(TaskError::SpawnOutsideScheduler).mica();
+        }
+        let task = Task::new(call_fiber(vec![function])?, gc);
+        self.spawned.push(Rc::clone(&task));
+        Ok(task)
+    }
+}
+
+impl<'e> Scheduler<'e> {
+    /// Creates a scheduler that runs fibers on the given engine.
+    pub fn new(engine: &'e mut Engine) -> Self {
+        Self {
+            engine,
+            tasks: Vec::new(),
+            next_task: 0,
+        }
+    }
+
+    /// Returns the engine the scheduler runs fibers on, eg. to set globals shared between them.
+    pub fn engine(&mut self) -> &mut Engine {
+        self.engine
+    }
+
+    /// Compiles a script and starts running it in a new fiber. The fiber doesn't execute any code
+    /// until the scheduler is [run][Self::run].
+    pub fn start(
+        &mut self,
+        filename: impl AsRef<str>,
+        source: impl Into<String>,
+    ) -> Result<TaskId, Error> {
+        let script = self.engine.compile(filename, source)?;
+        let fiber = vm::Fiber::new(Rc::clone(&script.main_chunk), Vec::new());
+        Ok(self.add(fiber))
+    }
+
+    fn add(&mut self, fiber: vm::Fiber) -> TaskId {
+        self.tasks.push(Task::new(fiber, &mut self.engine.gc));
+        TaskId(self.tasks.len() - 1)
+    }
+
+    /// Returns a value referring to the task, which scripts can use to wait for the task to
+    /// finish, using `task.join()` or `select`.
+    pub fn handle(&mut self, task: TaskId) -> Value {
+        let handle = TaskHandle(Rc::clone(&self.tasks[task.0]));
+        self.engine.create_value(handle)
+    }
+
+    /// Returns whether the fiber has finished running, either by evaluating all of its code or
+    /// by failing with an error.
+    ///
+    /// A fiber that spawned other fibers only finishes once all of them finish. If it fails, the
+    /// fibers it spawned are cancelled instead.
+    pub fn is_finished(&self, task: TaskId) -> bool {
+        self.tasks[task.0].is_finished()
+    }
+
+    /// Returns what the fiber is currently doing.
+    ///
+    /// A fiber that finished executing but is still waiting for the fibers it spawned is
+    /// [`Suspended`][FiberState::Suspended], and fibers cancelled because their parent failed are
+    /// [`Errored`][FiberState::Errored].
+    pub fn state(&self, task: TaskId) -> FiberState {
+        let task = &self.tasks[task.0];
+        match &*task.outcome.borrow() {
+            Outcome::Running => FiberState::of(&task.fiber.borrow()),
+            Outcome::WaitingForChildren(_) => FiberState::Suspended,
+            Outcome::Finished(_) => FiberState::Finished,
+            Outcome::Failed | Outcome::Cancelled => FiberState::Errored,
+        }
+    }
+
+    /// Returns the fiber's call stack, starting with the innermost frame. See
+    /// [`Fiber::stack`][crate::Fiber::stack]. Cancelled fibers keep the stack they had when they
+    /// were cancelled.
+    pub fn stack(&self, task: TaskId) -> Vec<StackTraceEntry> {
+        stack_trace(&self.tasks[task.0].fiber.borrow(), &self.engine.env)
+    }
+
+    /// Returns the value the fiber's code evaluated to, or `None` if it hasn't finished yet or
+    /// failed with an error.
+    pub fn result<T>(&self, task: TaskId) -> Result<Option<T>, Error>
+    where
+        T: TryFromValue,
+    {
+        match &*self.tasks[task.0].outcome.borrow() {
+            Outcome::Finished(value) => T::try_from_value(value, &self.engine.library).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Runs all fibers until each one of them finishes.
+    ///
+    /// If all unfinished fibers block without any of them making progress, [`Error::Deadlock`] is
+    /// returned. See [`run_until_blocked`][Self::run_until_blocked] for details.
+    pub fn run(&mut self) -> Result<(), Error> {
+        if self.run_until_blocked()? {
+            Ok(())
+        } else {
+            Err(Error::Deadlock)
+        }
+    }
+
+    /// Runs fibers until they all finish, or until all the unfinished ones are blocked without
+    /// being able to make progress. Returns whether all fibers finished.
+    ///
+    /// Fibers run one at a time, in the order they were started. A fiber runs until it blocks,
+    /// yields, or finishes, after which the next fiber gets to run. Fibers spawned by scripts using `spawn`
+    /// are run along with the rest.
+    ///
+    /// Fibers waiting for something the host provides, such as a [`Completion`], can be unblocked
+    /// by the host and resumed by calling this again.
+    ///
+    /// If a fiber fails with an error, the error is returned immediately. The failed fiber is
+    /// considered finished, so calling this again continues running the remaining fibers. If the
+    /// engine runs out of [fuel][Engine::set_fuel], [`Error::FuelExhausted`] is returned, and the
+    /// interrupted fiber is the first to run once the engine is refueled.
+    pub fn run_until_blocked(&mut self) -> Result<bool, Error> {
+        self.engine.spawner.borrow_mut().running = true;
+        let result = self.run_tasks(None);
+        self.engine.spawner.borrow_mut().running = false;
+        result
+    }
+
+    /// Runs fibers for at most `budget` instructions in total, for instance during a single frame
+    /// of a game. Returns whether all fibers finished.
+    ///
+    /// The budget is split evenly between the fibers that are able to run, such that each of them
+    /// gets to make progress. Fibers that finish or block before using up their share leave the
+    /// rest of it to the others. If the budget is smaller than the number of fibers, the fibers
+    /// that didn't get to run are the first to run during the next tick.
+    ///
+    /// Like with [`run_until_blocked`][Self::run_until_blocked], ticking stops early if all
+    /// unfinished fibers are blocked, and errors are returned as soon as a fiber fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{Engine, Scheduler};
+    ///
+    /// let mut engine = Engine::new();
+    /// let mut scheduler = Scheduler::new(&mut engine);
+    /// for i in 0..100 {
+    ///     scheduler.start(format!("entity{i}.mi"), "let i = 0\nwhile i < 100 do i = i + 1 end")?;
+    /// }
+    /// let mut frames = 0;
+    /// while !scheduler.tick(1000)? {
+    ///     frames += 1;
+    /// }
+    /// assert!(frames > 1);
+    /// # Ok::<(), mica::Error>(())
+    /// ```
+    pub fn tick(&mut self, budget: usize) -> Result<bool, Error> {
+        self.engine.spawner.borrow_mut().running = true;
+        let result = self.run_tasks(Some(budget));
+        self.engine.spawner.borrow_mut().running = false;
+        result
+    }
+
+    fn run_tasks(&mut self, mut budget: Option<usize>) -> Result<bool, Error> {
+        loop {
+            let runnable = self
+                .tasks
+                .iter()
+                .filter(|task| matches!(*task.outcome.borrow(), Outcome::Running))
+                .count();
+            let share = budget.map(|budget| (budget / runnable.max(1)).max(1));
+
+            let mut all_finished = true;
+            let mut stalled = true;
+            // Passes start where the previous one ran out of budget. Tasks spawned during the
+            // pass are appended to the list, and run in the same pass.
+            let count = self.tasks.len();
+            let mut position = 0;
+            while position < self.tasks.len() {
+                let index = if position < count {
+                    (self.next_task + position) % count
+                } else {
+                    position
+                };
+                position += 1;
+                let task = Rc::clone(&self.tasks[index]);
+                if task.is_finished() {
+                    continue;
+                }
+                all_finished = false;
+                if task.settle() {
+                    stalled = false;
+                    continue;
+                }
+                if !matches!(*task.outcome.borrow(), Outcome::Running) {
+                    continue;
+                }
+                if budget == Some(0) {
+                    self.next_task = index;
+                    return Ok(false);
+                }
+
+                let task_budget = share.zip(budget).map(|(share, budget)| share.min(budget));
+                task.fiber.borrow_mut().set_budget(task_budget);
+                let result = self.resume(&task);
+                let mut fiber = task.fiber.borrow_mut();
+                if let (Some(budget), Some(task_budget)) = (&mut budget, task_budget) {
+                    *budget -= task_budget - fiber.budget().unwrap_or(0);
+                }
+                fiber.set_budget(None);
+                drop(fiber);
+                self.adopt_spawned(&task);
+                task.settle();
+                result?;
+                let fiber = task.fiber.borrow();
+                if fiber.out_of_fuel() {
+                    self.next_task = index;
+                    return Err(Error::FuelExhausted);
+                }
+                stalled &= fiber.blocked() && fiber.stalled();
+            }
+            if all_finished {
+                return Ok(true);
+            }
+            if stalled {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Runs a single fiber until it blocks, finishes, or runs out of budget.
+    fn resume(&mut self, task: &Task) -> Result<(), Error> {
+        let Engine {
+            env,
+            library,
+            globals,
+            gc,
+            sources,
+            ..
+        } = &mut self.engine;
+        let mut fiber = task.fiber.borrow_mut();
+        let result = fiber.interpret(env, library, globals, gc);
+        if fiber.halted() {
+            *task.outcome.borrow_mut() = match &result {
+                Ok(value) => Outcome::WaitingForChildren(Value::from_raw(*value)),
+                Err(_) => Outcome::Failed,
+            };
+        }
+        result.map_err(|mut error| {
+            sources.attach_snippet(&mut error);
+            Error::from_fiber(error, &fiber)
+        })?;
+        Ok(())
+    }
+
+    /// Schedules the tasks the parent spawned while it was running.
+    fn adopt_spawned(&mut self, parent: &Task) {
+        let spawned = std::mem::take(&mut self.engine.spawner.borrow_mut().spawned);
+        parent.children.borrow_mut().extend(spawned.iter().cloned());
+        self.tasks.extend(spawned);
+        // Tasks spawned by a failed fiber are cancelled along with the ones it spawned earlier.
+        if matches!(*parent.outcome.borrow(), Outcome::Failed) {
+            parent.cancel_children();
+        }
+    }
+}
+
+impl fmt::Debug for Scheduler<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("tasks", &self.tasks.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A value provided by the host at some point in the future, which scripts can wait for.
+///
+/// Completions let fibers wait for work done outside of the engine, such as I/O. Scripts wait for
+/// the completion using `completion.wait()` or `select`, which block the fiber until the host
+/// [completes][Self::complete] it.
+///
+/// # Examples
+/// ```
+/// use mica::{Completion, Engine, Scheduler, Value};
+///
+/// let mut engine = Engine::new();
+/// let download = Completion::new();
+/// engine.set("download", download.clone())?;
+///
+/// let mut scheduler = Scheduler::new(&mut engine);
+/// let task = scheduler.start("example.mi", "download.wait().cat(\"!\")")?;
+/// assert!(!scheduler.run_until_blocked()?);
+///
+/// download.complete(Value::new("done"));
+/// assert!(scheduler.run_until_blocked()?);
+/// assert_eq!(scheduler.result::<String>(task)?.as_deref(), Some("done!"));
+/// # Ok::<(), mica::Error>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct Completion(Rc<RefCell<Option<Value>>>);
+
+impl Completion {
+    /// Creates a new completion that isn't complete yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Completes the completion with the given value, unblocking any fibers waiting for it.
+    /// Completing it again replaces the value.
+    pub fn complete(&self, value: Value) {
+        *self.0.borrow_mut() = Some(value);
+    }
+
+    /// Returns whether the completion has been completed.
+    pub fn is_complete(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    /// Returns the value the completion was completed with, or [`Error::WouldBlock`] if it isn't
+    /// complete yet.
+    pub(crate) fn wait(&self) -> Result<Value, Error> {
+        self.0.borrow().clone().ok_or(Error::WouldBlock)
+    }
+}
+
+impl UserData for Completion {}
+
+impl fmt::Debug for Completion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Completion")
+            .field("is_complete", &self.is_complete())
+            .finish()
+    }
+}
@@ -0,0 +1,115 @@
+//! Conversions between Rust data structures and Mica values, using [`serde`](::serde).
+//!
+//! Structs and maps are converted into dicts, and sequences and tuples into lists. Enums use the
+//! same representation as `serde_json` by default: unit variants become strings with the variant's
+//! name, and other variants become dicts with the variant's name as their only key.
+//!
+//! This module is only available with the `serde` feature.
+//!
+//! # Examples
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use mica::Engine;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize)]
+//! struct Config {
+//!     name: String,
+//!     retries: u32,
+//! }
+//!
+//! #[derive(Deserialize, Debug, PartialEq)]
+//! struct Report {
+//!     greeting: String,
+//!     attempts: Vec<u32>,
+//! }
+//!
+//! let mut engine = Engine::new();
+//! let config = Config { name: "world".into(), retries: 2 };
+//! let config = mica::serde::to_value(&mut engine, &config)?;
+//! engine.set("config", config)?;
+//!
+//! let report = engine
+//!     .start(
+//!         "example.mi",
+//!         r#" ["greeting": config["name"].to_uppercase, "attempts": [1, config["retries"]]] "#,
+//!     )?
+//!     .trampoline()?;
+//! let report: Report = mica::serde::from_value(&report)?;
+//! assert_eq!(report, Report { greeting: "WORLD".into(), attempts: vec![1, 2] });
+//! # Ok(())
+//! # }
+//! ```
+
+mod de;
+mod ser;
+
+use std::fmt;
+
+use ::serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Engine, Value};
+
+/// How many nested lists and dicts a value may have to be deserialized. This also stops the
+/// deserialization of lists that contain themselves.
+const MAX_NESTING: usize = 256;
+
+/// Converts a Rust value into a Mica value.
+///
+/// The conversion needs an engine, because it allocates strings, lists, and dicts inside of its
+/// garbage collector.
+pub fn to_value<T>(engine: &mut Engine, value: &T) -> Result<Value, crate::Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut serializer = ser::Serializer { gc: &mut engine.gc };
+    let value = value
+        .serialize(&mut serializer)
+        .map_err(|error| crate::Error::User(Box::new(error)))?;
+    Ok(Value::from_raw(value))
+}
+
+/// Converts a Mica value into a Rust value.
+///
+/// Tuples are accepted wherever a sequence is expected, and records wherever a struct or map is.
+/// Functions, structs, traits, and user data cannot be converted.
+pub fn from_value<T>(value: &Value) -> Result<T, crate::Error>
+where
+    T: DeserializeOwned,
+{
+    let deserializer = de::Deserializer {
+        value: value.to_raw_unmanaged(),
+        nesting: 0,
+    };
+    T::deserialize(deserializer).map_err(|error| crate::Error::User(Box::new(error)))
+}
+
+/// An error that occurred while converting between a Rust value and a Mica value.
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ::serde::ser::Error for Error {
+    fn custom<T>(message: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        Self(message.to_string())
+    }
+}
+
+impl ::serde::de::Error for Error {
+    fn custom<T>(message: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        Self(message.to_string())
+    }
+}
@@ -0,0 +1,302 @@
+//! Deserializing Rust values from Mica values.
+
+use ::serde::{
+    de::{self, IntoDeserializer, Visitor},
+    forward_to_deserialize_any,
+};
+
+use super::{Error, MAX_NESTING};
+use crate::ll::value::{Dict, List, RawValue, Record, Tuple, ValueKind};
+
+/// Deserializes values out of a raw value.
+///
+/// The value must stay alive while it's being deserialized, which [`from_value`][super::from_value]
+/// ensures by borrowing the [`Value`][crate::Value] it's given.
+pub(super) struct Deserializer {
+    pub(super) value: RawValue,
+    /// How many lists and dicts the value is nested in.
+    pub(super) nesting: usize,
+}
+
+impl Deserializer {
+    fn nested(&self, value: RawValue) -> Result<Self, Error> {
+        if self.nesting >= MAX_NESTING {
+            return Err(de::Error::custom(
+                "value is nested too deeply to be deserialized (or contains itself)",
+            ));
+        }
+        Ok(Self {
+            value,
+            nesting: self.nesting + 1,
+        })
+    }
+
+    fn unsupported(&self) -> Error {
+        de::Error::custom(format_args!(
+            "value of type {} cannot be deserialized",
+            self.value.type_name()
+        ))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        unsafe {
+            match self.value.kind() {
+                ValueKind::Nil => visitor.visit_unit(),
+                ValueKind::Boolean => visitor.visit_bool(self.value.get_boolean_unchecked()),
+                ValueKind::Number => {
+                    let x = *self.value.get_number_unchecked();
+                    // Integers are visited as such, so that they can be deserialized into Rust's
+                    // integer types.
+                    if x.fract() == 0.0 && x.abs() < i64::MAX as f64 {
+                        visitor.visit_i64(x as i64)
+                    } else {
+                        visitor.visit_f64(x)
+                    }
+                }
+                ValueKind::String => visitor.visit_str(self.value.get_raw_string_unchecked().get()),
+                ValueKind::UserData => {
+                    let user_data = self.value.get_raw_user_data_unchecked().get();
+                    let any = user_data.as_any();
+                    if let Some(list) = any.downcast_ref::<List>() {
+                        visitor.visit_seq(Elements {
+                            parent: &self,
+                            elements: list.as_slice().iter(),
+                        })
+                    } else if let Some(tuple) = any.downcast_ref::<Tuple>() {
+                        visitor.visit_seq(Elements {
+                            parent: &self,
+                            elements: tuple.fields.iter(),
+                        })
+                    } else if let Some(dict) = any.downcast_ref::<Dict>() {
+                        let entries = dict.iter().map(|(key, value)| (Key::Value(key), value));
+                        visitor.visit_map(Entries {
+                            parent: &self,
+                            entries: entries.collect::<Vec<_>>().into_iter(),
+                            value: None,
+                        })
+                    } else if let Some(record) = any.downcast_ref::<Record>() {
+                        let names = record.record_type.identifier.split('+').map(Key::Name);
+                        let entries = names.zip(record.fields.iter().copied());
+                        visitor.visit_map(Entries {
+                            parent: &self,
+                            entries: entries.collect::<Vec<_>>().into_iter(),
+                            value: None,
+                        })
+                    } else {
+                        Err(self.unsupported())
+                    }
+                }
+                ValueKind::Function | ValueKind::Struct | ValueKind::Trait => {
+                    Err(self.unsupported())
+                }
+            }
+        }
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.kind() {
+            ValueKind::Number => visitor.visit_f64(unsafe { *self.value.get_number_unchecked() }),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.kind() {
+            ValueKind::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        unsafe {
+            if self.value.kind() == ValueKind::String {
+                let variant = self.value.get_raw_string_unchecked().get().as_str();
+                return visitor.visit_enum(variant.into_deserializer());
+            }
+            if self.value.kind() == ValueKind::UserData {
+                let user_data = self.value.get_raw_user_data_unchecked().get();
+                if let Some(dict) = user_data.as_any().downcast_ref::<Dict>() {
+                    let mut entries = dict.iter();
+                    if let (Some((key, value)), None) = (entries.next(), entries.next()) {
+                        if key.kind() == ValueKind::String {
+                            return visitor.visit_enum(Variant {
+                                name: key.get_raw_string_unchecked().get().as_str(),
+                                value: self.nested(value)?,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Err(de::Error::custom(
+            "enums must be strings or dicts with a single string key",
+        ))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Gives out the elements of a list or tuple.
+struct Elements<'a> {
+    parent: &'a Deserializer,
+    elements: std::slice::Iter<'a, RawValue>,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.elements.next() {
+            Some(&element) => seed.deserialize(self.parent.nested(element)?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+/// A key of a dict, or the name of a record's field.
+enum Key<'a> {
+    Value(RawValue),
+    Name(&'a str),
+}
+
+/// Gives out the entries of a dict or record.
+struct Entries<'a> {
+    parent: &'a Deserializer,
+    entries: std::vec::IntoIter<(Key<'a>, RawValue)>,
+    /// The value of the entry whose key was given out last.
+    value: Option<RawValue>,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        match key {
+            Key::Value(key) => seed.deserialize(self.parent.nested(key)?).map(Some),
+            Key::Name(name) => seed.deserialize(name.into_deserializer()).map(Some),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .expect("next_value_seed must be called after next_key_seed");
+        seed.deserialize(self.parent.nested(value)?)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// An enum variant stored as a dict with a single entry.
+struct Variant<'a> {
+    name: &'a str,
+    value: Deserializer,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant<'_> {
+    type Error = Error;
+    type Variant = Deserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Deserializer), Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.name.into_deserializer())?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -0,0 +1,397 @@
+//! Serializing Rust values into Mica values.
+
+use ::serde::{ser, Serialize};
+
+use super::Error;
+use crate::{
+    ll::{
+        gc::Memory,
+        value::{Dict, List, RawValue, Str, UserData},
+    },
+    IntoValue,
+};
+
+/// Serializes values into raw values allocated in a GC.
+///
+/// The allocated values aren't reachable from anywhere until the outermost one is returned, which
+/// is fine because nothing can trigger a collection while serializing.
+pub(super) struct Serializer<'gc> {
+    pub(super) gc: &'gc mut Memory,
+}
+
+impl Serializer<'_> {
+    fn string(&mut self, s: &str) -> RawValue {
+        RawValue::from(self.gc.allocate_string(Str::new(s)))
+    }
+
+    fn list(&mut self, elements: Vec<RawValue>) -> RawValue {
+        let list: Box<dyn UserData> = Box::new(List::new(elements));
+        RawValue::from(self.gc.allocate(list))
+    }
+
+    fn dict(&mut self, dict: Dict) -> RawValue {
+        dict.into_value(()).to_raw(self.gc)
+    }
+
+    /// Wraps the value carried by an enum variant into a dict, whose only key is the variant's
+    /// name.
+    fn variant(&mut self, variant: &str, value: RawValue) -> RawValue {
+        let dict = Dict::new();
+        dict.insert(self.string(variant), value);
+        self.dict(dict)
+    }
+}
+
+macro_rules! serialize_number {
+    ($($method:ident: $T:ty),*) => {
+        $(
+            fn $method(self, v: $T) -> Result<RawValue, Error> {
+                Ok(RawValue::from(v as f64))
+            }
+        )*
+    };
+}
+
+impl<'a, 'gc> ser::Serializer for &'a mut Serializer<'gc> {
+    type Ok = RawValue;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList<'a, 'gc>;
+    type SerializeTuple = SerializeList<'a, 'gc>;
+    type SerializeTupleStruct = SerializeList<'a, 'gc>;
+    type SerializeTupleVariant = SerializeList<'a, 'gc>;
+    type SerializeMap = SerializeDict<'a, 'gc>;
+    type SerializeStruct = SerializeDict<'a, 'gc>;
+    type SerializeStructVariant = SerializeDict<'a, 'gc>;
+
+    fn serialize_bool(self, v: bool) -> Result<RawValue, Error> {
+        Ok(RawValue::from(v))
+    }
+
+    serialize_number! {
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64
+    }
+
+    fn serialize_char(self, v: char) -> Result<RawValue, Error> {
+        Ok(self.string(v.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<RawValue, Error> {
+        Ok(self.string(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<RawValue, Error> {
+        let bytes = v
+            .iter()
+            .map(|&byte| RawValue::from(f64::from(byte)))
+            .collect();
+        Ok(self.list(bytes))
+    }
+
+    fn serialize_none(self) -> Result<RawValue, Error> {
+        Ok(RawValue::from(()))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<RawValue, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<RawValue, Error> {
+        Ok(RawValue::from(()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<RawValue, Error> {
+        Ok(RawValue::from(()))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<RawValue, Error> {
+        Ok(self.string(variant))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<RawValue, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<RawValue, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(&mut *self)?;
+        Ok(self.variant(variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(SerializeList {
+            serializer: self,
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Ok(SerializeList {
+            serializer: self,
+            elements: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(SerializeDict {
+            serializer: self,
+            dict: Dict::new(),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Ok(SerializeDict {
+            serializer: self,
+            dict: Dict::new(),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Serializes sequences, tuples, and tuple variants into lists.
+pub(super) struct SerializeList<'a, 'gc> {
+    serializer: &'a mut Serializer<'gc>,
+    elements: Vec<RawValue>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeList<'_, '_> {
+    fn push<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(&mut *self.serializer)?;
+        self.elements.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<RawValue, Error> {
+        let list = self.serializer.list(self.elements);
+        Ok(match self.variant {
+            Some(variant) => self.serializer.variant(variant, list),
+            None => list,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeList<'_, '_> {
+    type Ok = RawValue;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<RawValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList<'_, '_> {
+    type Ok = RawValue;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<RawValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList<'_, '_> {
+    type Ok = RawValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<RawValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList<'_, '_> {
+    type Ok = RawValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<RawValue, Error> {
+        self.finish()
+    }
+}
+
+/// Serializes maps, structs, and struct variants into dicts.
+pub(super) struct SerializeDict<'a, 'gc> {
+    serializer: &'a mut Serializer<'gc>,
+    dict: Dict,
+    /// The key passed to `serialize_key`, waiting for its value.
+    key: Option<RawValue>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeDict<'_, '_> {
+    fn insert<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self.serializer.string(key);
+        let value = value.serialize(&mut *self.serializer)?;
+        self.dict.insert(key, value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<RawValue, Error> {
+        let dict = self.serializer.dict(self.dict);
+        Ok(match self.variant {
+            Some(variant) => self.serializer.variant(variant, dict),
+            None => dict,
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeDict<'_, '_> {
+    type Ok = RawValue;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(key.serialize(&mut *self.serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value must be called after serialize_key");
+        let value = value.serialize(&mut *self.serializer)?;
+        self.dict.insert(key, value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<RawValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeDict<'_, '_> {
+    type Ok = RawValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<RawValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeDict<'_, '_> {
+    type Ok = RawValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<RawValue, Error> {
+        self.finish()
+    }
+}
@@ -0,0 +1,109 @@
+//! Evaluating snippets of code one after another, as done by REPLs.
+
+use std::fmt;
+
+use crate::{
+    ll::error::{LanguageError, LanguageErrorKind, LanguageWarning},
+    Engine, Error, TryFromValue,
+};
+
+/// An interactive session, which evaluates snippets of code one after another in the same engine.
+///
+/// Variables declared at the top level of a snippet are globals, so later snippets can use them,
+/// along with the functions and types declared by earlier snippets. Apart from that, a session
+/// takes care of the things REPLs and in-game consoles need: telling apart input that is
+/// incomplete from input that is invalid, and keeping the source code of every snippet around,
+/// so that errors in functions declared by earlier snippets point to the right code.
+///
+/// # Examples
+/// ```
+/// use mica::{Engine, Session, Value};
+///
+/// let mut engine = Engine::new();
+/// let mut session = Session::new(&mut engine, "(console)");
+/// let _: Value = session.eval("let health = 100")?;
+/// let _: Value = session.eval("func damage(amount) = do health = health - amount end")?;
+/// assert!(!Session::is_complete("damage(10"));
+/// let health: f64 = session.eval("damage(10)\ndamage(15)")?;
+/// assert_eq!(health, 75.0);
+/// # Ok::<(), mica::Error>(())
+/// ```
+pub struct Session<'e> {
+    engine: &'e mut Engine,
+    module_name: String,
+    warnings: Vec<LanguageWarning>,
+}
+
+impl<'e> Session<'e> {
+    /// Starts a new session in the given engine. Snippets are compiled as modules with the given
+    /// name, which shows up in error messages and stack traces.
+    pub fn new(engine: &'e mut Engine, module_name: impl Into<String>) -> Self {
+        Self {
+            engine,
+            module_name: module_name.into(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Compiles and runs a snippet until it's done executing, and returns what it evaluated to.
+    ///
+    /// Values the snippet `yield`s are discarded. If it blocks, [`Error::Deadlock`] is returned,
+    /// like with [`Fiber::trampoline`][crate::Fiber::trampoline].
+    pub fn eval<T>(&mut self, source: impl Into<String>) -> Result<T, Error>
+    where
+        T: TryFromValue,
+    {
+        self.warnings.clear();
+        let script = self.engine.compile(&self.module_name, source)?;
+        self.warnings = script.warnings().to_vec();
+        script.into_fiber().trampoline()
+    }
+
+    /// Returns the warnings emitted while compiling the last snippet passed to
+    /// [`eval`][Self::eval].
+    pub fn warnings(&self) -> &[LanguageWarning] {
+        &self.warnings
+    }
+
+    /// Returns whether the snippet is complete, or whether more input is needed to make sense of
+    /// it, such as when a `do` block is missing its `end`.
+    ///
+    /// Snippets with errors are considered complete, unless the error could be fixed by adding
+    /// more code to the end. The snippet is compiled in a separate engine, so checking it doesn't
+    /// have any side effects.
+    pub fn is_complete(source: &str) -> bool {
+        let mut engine = Engine::new();
+        let Err(error) = engine.compile("(input)", source) else {
+            return true;
+        };
+        // Errors about unclosed blocks point to where the block starts. Other errors reported at
+        // the very end of the input were caused by the parser running out of tokens, no matter
+        // what it was expecting to see.
+        let end = source.trim_end().len();
+        !error.compile_errors().iter().any(|error| match error {
+            LanguageError::Compile { kind, span, .. } => {
+                matches!(
+                    kind,
+                    LanguageErrorKind::MissingEnd
+                        | LanguageErrorKind::MissingClosingQuote
+                        | LanguageErrorKind::MissingRightParen
+                        | LanguageErrorKind::MissingRightBracket
+                ) || span.start.byte >= end
+            }
+            LanguageError::Runtime { .. } => false,
+        })
+    }
+
+    /// Returns the engine the session evaluates snippets in.
+    pub fn engine(&mut self) -> &mut Engine {
+        self.engine
+    }
+}
+
+impl fmt::Debug for Session<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("module_name", &self.module_name)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,134 @@
+//! Running tests declared with the core library's `Test` module.
+
+use std::fmt;
+
+use crate::{Error, Value};
+
+/// Tests declared by scripts that haven't been run yet.
+#[derive(Debug, Default)]
+pub(crate) struct TestSuite {
+    pub(crate) cases: Vec<(String, Value)>,
+    pub(crate) setup: Vec<Value>,
+    pub(crate) teardown: Vec<Value>,
+}
+
+/// The destination of text written by `print` and `debug`.
+#[derive(Debug, Default)]
+pub(crate) struct Output {
+    captured: Option<String>,
+}
+
+impl Output {
+    pub(crate) fn write(&mut self, text: &str) {
+        match &mut self.captured {
+            Some(captured) => captured.push_str(text),
+            None => print!("{text}"),
+        }
+    }
+
+    pub(crate) fn start_capturing(&mut self) {
+        self.captured = Some(String::new());
+    }
+
+    pub(crate) fn stop_capturing(&mut self) -> String {
+        self.captured.take().unwrap_or_default()
+    }
+}
+
+/// The outcome of a single test case.
+#[derive(Debug)]
+pub struct TestResult {
+    name: String,
+    output: String,
+    error: Option<Error>,
+}
+
+impl TestResult {
+    /// Returns the name the test case was declared with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns everything the test printed while running, including its setup and teardown.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Returns the error the test failed with, or `None` if it passed.
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    /// Returns whether the test passed.
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The results of running tests with [`Engine::run_tests`][crate::Engine::run_tests].
+///
+/// The [`Display`][fmt::Display] implementation prints a summary of the results, along with the
+/// output and stack trace of each failed test.
+#[derive(Debug, Default)]
+pub struct TestReport {
+    results: Vec<TestResult>,
+}
+
+impl TestReport {
+    pub(crate) fn push(&mut self, name: String, output: String, error: Option<Error>) {
+        self.results.push(TestResult {
+            name,
+            output,
+            error,
+        });
+    }
+
+    /// Returns the results of the individual tests, in the order they were declared in.
+    pub fn results(&self) -> &[TestResult] {
+        &self.results
+    }
+
+    /// Returns the number of tests that passed.
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|result| result.passed()).count()
+    }
+
+    /// Returns the number of tests that failed.
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    /// Returns whether all tests passed.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(TestResult::passed)
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            let status = if result.passed() { "ok" } else { "FAILED" };
+            writeln!(f, "test {} ... {status}", result.name)?;
+        }
+
+        if !self.is_success() {
+            write!(f, "\nfailures:\n")?;
+            for result in &self.results {
+                if let Some(error) = &result.error {
+                    write!(f, "\n---- {} ----\n", result.name)?;
+                    f.write_str(&result.output)?;
+                    let message = format!("{error:#}");
+                    writeln!(f, "{}", message.trim_end())?;
+                }
+            }
+        }
+
+        let status = if self.is_success() { "ok" } else { "FAILED" };
+        write!(
+            f,
+            "\ntest result: {status}. {} passed; {} failed",
+            self.passed(),
+            self.failed()
+        )
+    }
+}
@@ -0,0 +1,223 @@
+//! The language's front end, for tools that analyze scripts without running them.
+//!
+//! Linters, formatters, and documentation generators can use the same parser the engine compiles
+//! scripts with, instead of re-implementing one. [`parse`] produces an abstract syntax tree, which
+//! can be walked with a [`Visitor`]; every node knows the [`Span`] of source code it was parsed
+//! from. For tools that need to preserve comments and whitespace, such as formatters, [`cst`]
+//! provides a lossless syntax tree instead, and syntax highlighters can use [`tokenize`] to split
+//! source code into tokens.
+//!
+//! This module is only available with the `tooling` feature.
+//!
+//! # Examples
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use mica::tooling::{Ast, NodeId, NodeKind, Visit, Visitor};
+//!
+//! /// Finds the string literals in a script.
+//! #[derive(Default)]
+//! struct Strings(Vec<(String, String)>);
+//!
+//! impl Visitor for Strings {
+//!     fn enter(&mut self, ast: &Ast, node: NodeId) -> Visit {
+//!         if ast.kind(node) == NodeKind::String {
+//!             let location = ast.location(node);
+//!             let string = ast.string(node).unwrap();
+//!             self.0.push((location.to_string(), string.to_string()));
+//!         }
+//!         Visit::Children
+//!     }
+//! }
+//!
+//! let (ast, root) = mica::tooling::parse("greeting.mi", "let name = \"world\"\nprint(\"Hello, \".cat(name))")?;
+//! let mut strings = Strings::default();
+//! ast.visit(root, &mut strings);
+//! assert_eq!(strings.0, [("1:12".into(), "world".into()), ("2:7".into(), "Hello, ".into())]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::rc::Rc;
+
+pub use crate::ll::{
+    ast::{query, Ast, DumpAst, NodeId, NodeKind, Visit, Visitor},
+    cst,
+    error::{Location, Span},
+};
+use crate::{
+    ll::{
+        lexer::{self, Lexer, TokenKind as RawTokenKind},
+        parser::Parser,
+    },
+    Error,
+};
+
+/// Parses source code into a syntax tree, returning the tree along with its root node. The module
+/// name is used in error messages.
+///
+/// # Errors
+/// Syntax errors are returned the same way [`Engine::compile`][crate::Engine::compile] reports
+/// them. Errors that can only be detected while compiling, such as assignments to undeclared
+/// variables, are not reported.
+pub fn parse(module_name: &str, source: impl Into<String>) -> Result<(Ast, NodeId), Error> {
+    let source: String = source.into();
+    let source_for_snippets: Rc<str> = Rc::from(source.as_str());
+    let lexer = Lexer::new(Rc::from(module_name), source);
+    Parser::new(lexer).parse().map_err(|mut errors| {
+        for error in &mut errors {
+            error.attach_snippet(&source_for_snippets);
+        }
+        Error::from(errors)
+    })
+}
+
+/// The kind of a token produced by [`tokenize`], grouped into categories that syntax highlighters
+/// color differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// A keyword, such as `func` or `while`. This includes the `and` and `or` operators.
+    Keyword,
+    /// `nil`, `true`, or `false`.
+    Constant,
+    /// A number literal.
+    Number,
+    /// A string literal, including long strings (`\\`).
+    String,
+    /// The name of a variable, function, method, or type, or the `_` pattern.
+    Identifier,
+    /// A loop label, such as `'outer`.
+    Label,
+    /// An operator, such as `+`, `==`, `=`, or `.`.
+    Operator,
+    /// Parentheses, brackets, braces, commas, colons, and `@`.
+    Punctuation,
+    /// A comment, or the front matter block at the start of a script.
+    Comment,
+    /// A `##` doc comment.
+    DocComment,
+    /// Source code that could not be lexed, such as an unterminated string.
+    Error,
+}
+
+/// Splits source code into tokens, for syntax highlighting. Tokens are lexed lazily, as the
+/// iterator is advanced.
+///
+/// Unlike compiling, tokenizing never fails: source code that can't be lexed becomes
+/// [`TokenKind::Error`] tokens, and tokenizing continues after them. Comments are included, but
+/// whitespace is not.
+///
+/// # Examples
+/// ```
+/// use mica::tooling::{tokenize, TokenKind};
+///
+/// let source = "print(\"hi\") # Greet.";
+/// let tokens: Vec<_> = tokenize(source)
+///     .map(|(kind, span)| (kind, &source[span.start.byte..span.end.byte]))
+///     .collect();
+/// assert_eq!(
+///     tokens,
+///     [
+///         (TokenKind::Identifier, "print"),
+///         (TokenKind::Punctuation, "("),
+///         (TokenKind::String, "\"hi\""),
+///         (TokenKind::Punctuation, ")"),
+///         (TokenKind::Comment, "# Greet."),
+///     ]
+/// );
+/// ```
+pub fn tokenize(source: impl Into<String>) -> Tokens {
+    Tokens {
+        inner: Lexer::new(Rc::from("(tokens)"), source.into()).into_tokens(),
+    }
+}
+
+/// An iterator over the tokens in source code. Returned by [`tokenize`].
+#[derive(Debug)]
+pub struct Tokens {
+    inner: lexer::Tokens,
+}
+
+impl Iterator for Tokens {
+    type Item = (TokenKind, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (kind, span) = self.inner.next()?;
+        let kind = match kind {
+            RawTokenKind::Comment => {
+                let text = &self.inner.source()[span.start.byte..span.end.byte];
+                if text.starts_with("##") {
+                    TokenKind::DocComment
+                } else {
+                    TokenKind::Comment
+                }
+            }
+            RawTokenKind::Number(_) => TokenKind::Number,
+            RawTokenKind::String(_) | RawTokenKind::LongString(_) => TokenKind::String,
+            RawTokenKind::Identifier(_) | RawTokenKind::Underscore => TokenKind::Identifier,
+            RawTokenKind::Label(_) => TokenKind::Label,
+            RawTokenKind::Nil | RawTokenKind::True | RawTokenKind::False => TokenKind::Constant,
+            RawTokenKind::Let
+            | RawTokenKind::Const
+            | RawTokenKind::Do
+            | RawTokenKind::If
+            | RawTokenKind::Elif
+            | RawTokenKind::Else
+            | RawTokenKind::While
+            | RawTokenKind::For
+            | RawTokenKind::In
+            | RawTokenKind::Func
+            | RawTokenKind::End
+            | RawTokenKind::Break
+            | RawTokenKind::Continue
+            | RawTokenKind::Return
+            | RawTokenKind::Try
+            | RawTokenKind::Catch
+            | RawTokenKind::Raise
+            | RawTokenKind::Defer
+            | RawTokenKind::Struct
+            | RawTokenKind::Enum
+            | RawTokenKind::Trait
+            | RawTokenKind::Impl
+            | RawTokenKind::As
+            | RawTokenKind::Import
+            | RawTokenKind::Implements
+            | RawTokenKind::Constructor
+            | RawTokenKind::Static
+            | RawTokenKind::Pub
+            | RawTokenKind::Priv
+            | RawTokenKind::And
+            | RawTokenKind::Or => TokenKind::Keyword,
+            RawTokenKind::Plus
+            | RawTokenKind::Minus
+            | RawTokenKind::Star
+            | RawTokenKind::Slash
+            | RawTokenKind::SlashSlash
+            | RawTokenKind::Percent
+            | RawTokenKind::Bang
+            | RawTokenKind::Equal
+            | RawTokenKind::NotEqual
+            | RawTokenKind::Less
+            | RawTokenKind::Greater
+            | RawTokenKind::LessEqual
+            | RawTokenKind::GreaterEqual
+            | RawTokenKind::Assign
+            | RawTokenKind::Dot
+            | RawTokenKind::QuestionDot
+            | RawTokenKind::DotDot
+            | RawTokenKind::Ellipsis => TokenKind::Operator,
+            RawTokenKind::Colon
+            | RawTokenKind::At
+            | RawTokenKind::LeftParen
+            | RawTokenKind::RightParen
+            | RawTokenKind::LeftBracket
+            | RawTokenKind::RightBracket
+            | RawTokenKind::LeftBrace
+            | RawTokenKind::RightBrace
+            | RawTokenKind::Comma => TokenKind::Punctuation,
+            RawTokenKind::Error(_) => TokenKind::Error,
+            RawTokenKind::Eof => unreachable!("the token iterator ends instead of producing Eof"),
+        };
+        Some((kind, span))
+    }
+}
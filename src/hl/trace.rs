@@ -0,0 +1,489 @@
+//! Recording and replaying execution traces.
+
+use std::{fmt, rc::Rc, str::FromStr};
+
+use crate::{
+    ll::{
+        bytecode::{Function, Library},
+        error::LanguageErrorKind,
+        gc::Memory,
+        value::{Dict, List, RawValue, Str, Tuple, ValueKind},
+        vm::TraceHook,
+    },
+    Gc, Hidden, IntoValue, Value,
+};
+
+/// How deeply nested values returned by traced functions may be for them to be recorded.
+const MAX_NESTING: usize = 64;
+
+/// The largest tuple that can be replayed. Larger tuples don't have their types generated up front.
+const MAX_TUPLE_SIZE: usize = 8;
+
+/// A value returned by a traced function.
+#[derive(Debug, Clone, PartialEq)]
+enum TracedValue {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Str),
+    List(Vec<TracedValue>),
+    Dict(Vec<(TracedValue, TracedValue)>),
+    Tuple(Vec<TracedValue>),
+}
+
+impl TracedValue {
+    /// Records a value, or returns `None` if the value cannot be recorded.
+    fn record(value: RawValue, nesting: usize) -> Option<Self> {
+        if nesting > MAX_NESTING {
+            return None;
+        }
+        let record_all = |values: &[RawValue]| {
+            values
+                .iter()
+                .map(|&value| Self::record(value, nesting + 1))
+                .collect::<Option<Vec<_>>>()
+        };
+        unsafe {
+            Some(match value.kind() {
+                ValueKind::Nil => Self::Nil,
+                ValueKind::Boolean => Self::Boolean(value.get_boolean_unchecked()),
+                ValueKind::Number => Self::Number(*value.get_number_unchecked()),
+                ValueKind::String => Self::String(value.get_raw_string_unchecked().get().clone()),
+                ValueKind::UserData => {
+                    let user_data = value.get_raw_user_data_unchecked().get().as_any();
+                    if let Some(list) = user_data.downcast_ref::<List>() {
+                        Self::List(record_all(list.as_slice())?)
+                    } else if let Some(dict) = user_data.downcast_ref::<Dict>() {
+                        let pairs = dict
+                            .iter()
+                            .map(|(key, value)| {
+                                Some((
+                                    Self::record(key, nesting + 1)?,
+                                    Self::record(value, nesting + 1)?,
+                                ))
+                            })
+                            .collect::<Option<_>>()?;
+                        Self::Dict(pairs)
+                    } else if let Some(tuple) = user_data.downcast_ref::<Tuple>() {
+                        if tuple.fields.len() > MAX_TUPLE_SIZE {
+                            return None;
+                        }
+                        Self::Tuple(record_all(&tuple.fields)?)
+                    } else {
+                        return None;
+                    }
+                }
+                ValueKind::Function | ValueKind::Struct | ValueKind::Trait => return None,
+            })
+        }
+    }
+}
+
+impl IntoValue for TracedValue {
+    type EngineUse = crate::into_value::UsesEngine;
+
+    fn into_value(self, (library, gc): (&Library, &mut Memory)) -> Value {
+        let mut replay_all = |values: Vec<TracedValue>| -> Vec<RawValue> {
+            values
+                .into_iter()
+                .map(|value| value.into_value_with_engine_state(library, gc).to_raw(gc))
+                .collect()
+        };
+        match self {
+            Self::Nil => Value::Nil,
+            Self::Boolean(b) => Value::new(b),
+            Self::Number(x) => Value::Number(x),
+            Self::String(s) => Value::String(Gc::new(s)),
+            Self::List(elements) => replay_all(elements).into_value((library, gc)),
+            Self::Dict(pairs) => {
+                let dict = Dict::new();
+                for (key, value) in pairs {
+                    let key = key.into_value_with_engine_state(library, gc).to_raw(gc);
+                    let value = value.into_value_with_engine_state(library, gc).to_raw(gc);
+                    dict.insert(key, value);
+                }
+                dict.into_value(())
+            }
+            Self::Tuple(fields) => {
+                Value::Tuple(Hidden(Gc::new(Box::new(Tuple::new(replay_all(fields))))))
+            }
+        }
+    }
+}
+
+/// What a traced function call returned.
+#[derive(Debug, Clone, PartialEq)]
+enum TracedResult {
+    Value(TracedValue),
+    Error(String),
+    /// The result couldn't be recorded, so the function is called again during replay.
+    Live,
+}
+
+/// A single call to a traced function.
+#[derive(Debug, Clone, PartialEq)]
+struct TraceEvent {
+    function: String,
+    result: TracedResult,
+}
+
+/// A log of calls scripts made to functions registered by the embedder, together with the values
+/// the functions returned.
+///
+/// Functions registered by the embedder are the only source of nondeterminism in a script's
+/// execution, so replaying a trace with [`Engine::start_replaying`][crate::Engine::start_replaying]
+/// makes a script observe exactly the same values it observed while the trace was recorded.
+///
+/// Traces can be saved to text using [`Display`][fmt::Display], and loaded back using
+/// [`FromStr`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    /// The first line of a trace saved as text.
+    const HEADER: &'static str = "mica-trace 1";
+
+    /// Creates an empty trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of calls in the trace.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns whether the trace has no calls in it.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns the names of the called functions, in the order they were called.
+    pub fn functions(&self) -> impl Iterator<Item = &str> {
+        self.events.iter().map(|event| event.function.as_str())
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"{}:{s}", s.len())
+}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: &TracedValue) -> fmt::Result {
+    let write_all = |f: &mut fmt::Formatter<'_>, values: &[TracedValue]| {
+        values.iter().try_for_each(|value| {
+            f.write_str(" ")?;
+            write_value(f, value)
+        })
+    };
+    match value {
+        TracedValue::Nil => f.write_str("n"),
+        TracedValue::Boolean(true) => f.write_str("t"),
+        TracedValue::Boolean(false) => f.write_str("f"),
+        // Numbers are saved as their bits so that they're replayed exactly.
+        TracedValue::Number(x) => write!(f, "#{:016x}", x.to_bits()),
+        TracedValue::String(s) => write_string(f, s),
+        TracedValue::List(elements) => {
+            f.write_str("[")?;
+            write_all(f, elements)?;
+            f.write_str(" ]")
+        }
+        TracedValue::Dict(pairs) => {
+            f.write_str("{")?;
+            for (key, value) in pairs {
+                f.write_str(" ")?;
+                write_value(f, key)?;
+                f.write_str(" ")?;
+                write_value(f, value)?;
+            }
+            f.write_str(" }")
+        }
+        TracedValue::Tuple(fields) => {
+            f.write_str("(")?;
+            write_all(f, fields)?;
+            f.write_str(" )")
+        }
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", Self::HEADER)?;
+        for event in &self.events {
+            write_string(f, &event.function)?;
+            match &event.result {
+                TracedResult::Value(value) => {
+                    f.write_str(" = ")?;
+                    write_value(f, value)?;
+                }
+                TracedResult::Error(message) => {
+                    f.write_str(" ! ")?;
+                    write_string(f, message)?;
+                }
+                TracedResult::Live => f.write_str(" ~")?,
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error that occurred while loading a trace from text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParseError {
+    /// The byte offset at which the error occurred.
+    pub position: usize,
+    message: &'static str,
+}
+
+impl fmt::Display for TraceParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid trace at byte {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl std::error::Error for TraceParseError {}
+
+/// Reads traces saved as text.
+struct TraceParser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> TraceParser<'a> {
+    fn error<T>(&self, message: &'static str) -> Result<T, TraceParseError> {
+        Err(TraceParseError {
+            position: self.position,
+            message,
+        })
+    }
+
+    /// Skips whitespace and returns the next character without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.input[self.position..];
+        let trimmed = rest.trim_start();
+        self.position += rest.len() - trimmed.len();
+        trimmed.chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char, message: &'static str) -> Result<(), TraceParseError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += c.len_utf8();
+                Ok(())
+            }
+            _ => self.error(message),
+        }
+    }
+
+    /// Reads bytes up to the given character, consuming it.
+    fn take_until(&mut self, end: char, message: &'static str) -> Result<&'a str, TraceParseError> {
+        let rest = &self.input[self.position..];
+        let Some(length) = rest.find(end) else {
+            return self.error(message);
+        };
+        self.position += length + end.len_utf8();
+        Ok(&rest[..length])
+    }
+
+    fn string(&mut self) -> Result<String, TraceParseError> {
+        self.expect('"', "string expected")?;
+        let length: usize = self
+            .take_until(':', "string length expected")?
+            .parse()
+            .or_else(|_| self.error("invalid string length"))?;
+        let end = self.position + length;
+        match self.input.get(self.position..end) {
+            Some(s) => {
+                self.position = end;
+                Ok(s.to_owned())
+            }
+            None => self.error("string length is out of bounds"),
+        }
+    }
+
+    fn values_until(&mut self, end: char) -> Result<Vec<TracedValue>, TraceParseError> {
+        let mut values = Vec::new();
+        loop {
+            match self.peek() {
+                Some(c) if c == end => {
+                    self.position += 1;
+                    return Ok(values);
+                }
+                Some(_) => values.push(self.value()?),
+                None => return self.error("unterminated collection"),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<TracedValue, TraceParseError> {
+        Ok(match self.peek() {
+            Some('"') => TracedValue::String(Str::from(self.string()?)),
+            Some(c) => {
+                self.position += c.len_utf8();
+                match c {
+                    'n' => TracedValue::Nil,
+                    't' => TracedValue::Boolean(true),
+                    'f' => TracedValue::Boolean(false),
+                    '#' => {
+                        let bits = self.input.get(self.position..self.position + 16);
+                        let Some(Ok(bits)) = bits.map(|bits| u64::from_str_radix(bits, 16)) else {
+                            return self.error("invalid number");
+                        };
+                        self.position += 16;
+                        TracedValue::Number(f64::from_bits(bits))
+                    }
+                    '[' => TracedValue::List(self.values_until(']')?),
+                    '(' => TracedValue::Tuple(self.values_until(')')?),
+                    '{' => {
+                        let values = self.values_until('}')?;
+                        if values.len() % 2 != 0 {
+                            return self.error("dict is missing a value");
+                        }
+                        let mut values = values.into_iter();
+                        let mut pairs = Vec::new();
+                        while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                            pairs.push((key, value));
+                        }
+                        TracedValue::Dict(pairs)
+                    }
+                    _ => {
+                        self.position -= c.len_utf8();
+                        return self.error("value expected");
+                    }
+                }
+            }
+            None => return self.error("value expected"),
+        })
+    }
+
+    fn event(&mut self) -> Result<TraceEvent, TraceParseError> {
+        let function = self.string()?;
+        let result = match self.next() {
+            Some('=') => TracedResult::Value(self.value()?),
+            Some('!') => TracedResult::Error(self.string()?),
+            Some('~') => TracedResult::Live,
+            _ => return self.error("'=', '!', or '~' expected after function name"),
+        };
+        Ok(TraceEvent { function, result })
+    }
+}
+
+impl FromStr for Trace {
+    type Err = TraceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = TraceParser {
+            input: s,
+            position: 0,
+        };
+        if !s.starts_with(Self::HEADER) {
+            return parser.error("trace header expected");
+        }
+        parser.position = Self::HEADER.len();
+        let mut events = Vec::new();
+        while parser.peek().is_some() {
+            events.push(parser.event()?);
+        }
+        Ok(Self { events })
+    }
+}
+
+/// An error recorded in a trace, returned again by a replayed function.
+#[derive(Debug)]
+struct ReplayedError(String);
+
+impl fmt::Display for ReplayedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ReplayedError {}
+
+/// Records or replays calls to traced functions.
+#[derive(Debug)]
+pub(crate) enum Tracer {
+    Recording(Trace),
+    Replaying { trace: Trace, position: usize },
+}
+
+impl Tracer {
+    /// Returns the trace being recorded or replayed.
+    pub(crate) fn into_trace(self) -> Trace {
+        match self {
+            Self::Recording(trace) | Self::Replaying { trace, .. } => trace,
+        }
+    }
+}
+
+impl TraceHook for Tracer {
+    fn on_foreign_call(
+        &mut self,
+        function: &Function,
+        library: &Library,
+        gc: &mut Memory,
+        call: &dyn Fn(&Library, &mut Memory) -> Result<RawValue, LanguageErrorKind>,
+    ) -> Result<RawValue, LanguageErrorKind> {
+        match self {
+            Self::Recording(trace) => {
+                let result = call(library, gc);
+                let recorded = match &result {
+                    Ok(value) => TracedValue::record(*value, 0)
+                        .map_or(TracedResult::Live, TracedResult::Value),
+                    // Blocked calls are retried later, and only the final call is recorded.
+                    Err(LanguageErrorKind::WouldBlock) => return result,
+                    // Argument type mismatches depend only on the arguments, and the error is
+                    // completed with information about the call after it's returned, so the
+                    // function is simply called again.
+                    Err(LanguageErrorKind::ArgumentTypeMismatch(_)) => TracedResult::Live,
+                    Err(error) => TracedResult::Error(error.to_string()),
+                };
+                trace.events.push(TraceEvent {
+                    function: function.name.to_string(),
+                    result: recorded,
+                });
+                result
+            }
+            Self::Replaying { trace, position } => {
+                let Some(event) = trace.events.get(*position) else {
+                    return Err(LanguageErrorKind::ReplayDiverged {
+                        expected: None,
+                        called: Rc::clone(&function.name),
+                    });
+                };
+                if event.function != *function.name {
+                    return Err(LanguageErrorKind::ReplayDiverged {
+                        expected: Some(Rc::from(event.function.as_str())),
+                        called: Rc::clone(&function.name),
+                    });
+                }
+                let result = match &event.result {
+                    TracedResult::Value(value) => Ok(value
+                        .clone()
+                        .into_value_with_engine_state(library, gc)
+                        .to_raw(gc)),
+                    TracedResult::Error(message) => Err(LanguageErrorKind::User(Box::new(
+                        ReplayedError(message.clone()),
+                    ))),
+                    TracedResult::Live => call(library, gc),
+                };
+                if !matches!(result, Err(LanguageErrorKind::WouldBlock)) {
+                    *position += 1;
+                }
+                result
+            }
+        }
+    }
+}
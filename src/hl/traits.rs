@@ -58,9 +58,9 @@ use crate::{
 ///
 /// let expected_tick_count = 10_usize;
 /// for _ in 0..expected_tick_count {
-///     let _: Value = engine.call_method(my_game.clone(), game_tick, [])?;
+///     let _: Value = engine.call_method(my_game.clone(), game_tick, ())?;
 /// }
-/// let got_tick_count: usize = engine.call_method(my_game, game_tick_count, [])?;
+/// let got_tick_count: usize = engine.call_method(my_game, game_tick_count, ())?;
 /// assert_eq!(got_tick_count, expected_tick_count);
 /// # Ok(())
 /// # }
@@ -79,13 +79,19 @@ impl<'e> TraitBuilder<'e> {
         self.inner
             .add_method(Rc::from(name), arity)
             .map(MethodId)
-            .map_err(|e| match e {
-                LanguageErrorKind::TooManyTraits => Error::TooManyTraits,
-                LanguageErrorKind::TooManyFunctions => Error::TooManyFunctions,
-                LanguageErrorKind::TooManyMethods => Error::TooManyMethods,
-                LanguageErrorKind::TooManyParameters => Error::TooManyParametersInTraitMethod,
-                _ => unreachable!(),
-            })
+            .map_err(map_trait_builder_error)
+    }
+
+    /// Adds a new static function requirement into the trait and returns its method ID. Static
+    /// functions are implemented with `static` functions in `as` blocks, and are called on the
+    /// implementing type itself rather than its instances.
+    pub fn add_static_function(&mut self, name: &str, arity: u8) -> Result<MethodId, Error> {
+        let arity = MethodParameterCount::from_count_without_self(arity)
+            .map_err(|_| Error::TooManyParametersInTraitMethod)?;
+        self.inner
+            .add_static_method(Rc::from(name), arity)
+            .map(MethodId)
+            .map_err(map_trait_builder_error)
     }
 
     /// Finishes building the trait and wraps it into a value.
@@ -101,6 +107,16 @@ impl<'e> fmt::Debug for TraitBuilder<'e> {
     }
 }
 
+fn map_trait_builder_error(error: LanguageErrorKind) -> Error {
+    match error {
+        LanguageErrorKind::TooManyTraits => Error::TooManyTraits,
+        LanguageErrorKind::TooManyFunctions => Error::TooManyFunctions,
+        LanguageErrorKind::TooManyMethods => Error::TooManyMethods,
+        LanguageErrorKind::TooManyParameters => Error::TooManyParametersInTraitMethod,
+        _ => unreachable!(),
+    }
+}
+
 pub(crate) fn create_trait_value(
     env: &mut Environment,
     gc: &mut Memory,
@@ -0,0 +1,251 @@
+use std::{fmt, marker::PhantomData, rc::Rc};
+
+use crate::{
+    call_chunk,
+    ll::{
+        bytecode::{Chunk, FunctionParameterCount, Library},
+        gc::Memory,
+        value::RawValue,
+        vm,
+    },
+    Engine, Error, Fiber, IntoValue, OptionalGlobalName, TryFromValue, Value,
+};
+
+/// Lists of values that can be passed as the arguments of a [`TypedFunction`], or to
+/// [`Engine::call`] and friends.
+///
+/// This is implemented for tuples of up to 8 [`IntoValue`]s, including the empty tuple `()`, as
+/// well as arrays of [`IntoValue`]s.
+///
+/// # Examples
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use mica::{Engine, Value};
+///
+/// let mut engine = Engine::new();
+/// let f: Value = engine
+///     .start("example.mi", r#" (func (n, s, b) = (n, s, b)) "#)?
+///     .trampoline()?;
+/// let result: (f64, String, bool) = engine.call(f, (42, "hi", true))?;
+/// assert_eq!(result, (42.0, "hi".to_owned(), true));
+/// # Ok(())
+/// # }
+/// ```
+pub trait IntoArguments {
+    /// The number of arguments in the tuple.
+    const COUNT: usize;
+
+    /// Converts the arguments and pushes them onto the stack.
+    #[doc(hidden)]
+    fn push_arguments(self, library: &Library, gc: &mut Memory, stack: &mut Vec<RawValue>);
+}
+
+macro_rules! into_arguments {
+    (count = $count:tt $(, $args:tt)*) => {
+        impl<$($args,)*> IntoArguments for ($($args,)*)
+        where
+            $($args: IntoValue,)*
+        {
+            const COUNT: usize = $count;
+
+            #[allow(non_snake_case, unused_variables)]
+            fn push_arguments(self, library: &Library, gc: &mut Memory, stack: &mut Vec<RawValue>) {
+                let ($($args,)*) = self;
+                $(stack.push($args.into_value_with_engine_state(library, gc).to_raw(gc));)*
+            }
+        }
+    };
+}
+
+into_arguments!(count = 0);
+into_arguments!(count = 1, A);
+into_arguments!(count = 2, A, B);
+into_arguments!(count = 3, A, B, C);
+into_arguments!(count = 4, A, B, C, D);
+into_arguments!(count = 5, A, B, C, D, E);
+into_arguments!(count = 6, A, B, C, D, E, F);
+into_arguments!(count = 7, A, B, C, D, E, F, G);
+into_arguments!(count = 8, A, B, C, D, E, F, G, H);
+
+impl<T, const N: usize> IntoArguments for [T; N]
+where
+    T: IntoValue,
+{
+    const COUNT: usize = N;
+
+    fn push_arguments(self, library: &Library, gc: &mut Memory, stack: &mut Vec<RawValue>) {
+        for argument in self {
+            stack.push(
+                argument
+                    .into_value_with_engine_state(library, gc)
+                    .to_raw(gc),
+            );
+        }
+    }
+}
+
+/// Creates the stack for calling `callee` (a function, or the receiver of a method) with the given
+/// arguments.
+pub(crate) fn argument_stack<Args>(
+    callee: Value,
+    arguments: Args,
+    library: &Library,
+    gc: &mut Memory,
+) -> Vec<RawValue>
+where
+    Args: IntoArguments,
+{
+    let mut stack = Vec::with_capacity(Args::COUNT + 1);
+    push_arguments(&mut stack, callee, arguments, library, gc);
+    stack
+}
+
+/// Pushes `callee` and the given arguments onto an existing stack, such as one reused from an
+/// earlier call.
+pub(crate) fn push_arguments<Args>(
+    stack: &mut Vec<RawValue>,
+    callee: Value,
+    arguments: Args,
+    library: &Library,
+    gc: &mut Memory,
+) where
+    Args: IntoArguments,
+{
+    stack.push(callee.to_raw(gc));
+    arguments.push_arguments(library, gc, stack);
+}
+
+/// A handle to a Mica function, which accepts the arguments `Args` and returns an `R`.
+///
+/// Typed functions are created using [`Engine::get_function`] or [`Value::into_typed_fn`], which
+/// check that the value is a function accepting that many arguments. Calling them is cheaper than
+/// using [`Engine::call`], as the function and the code that calls it are only prepared once.
+///
+/// # Examples
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use mica::{Engine, Value};
+///
+/// let mut engine = Engine::new();
+/// let _: Value = engine.start("example.mi", "func add(a, b) = a + b")?.trampoline()?;
+/// let add = engine.get_function::<(f64, f64), f64>("add")?;
+/// assert_eq!(add.call(&mut engine, (1.0, 2.0))?, 3.0);
+/// assert_eq!(add.call(&mut engine, (3.0, 4.0))?, 7.0);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TypedFunction<Args, R> {
+    function: Value,
+    chunk: Rc<Chunk>,
+    _signature: PhantomData<fn(Args) -> R>,
+}
+
+impl<Args, R> TypedFunction<Args, R>
+where
+    Args: IntoArguments,
+    R: TryFromValue,
+{
+    fn new(engine: &Engine, function: Value) -> Result<Self, Error> {
+        let Value::Function(closure) = &function else {
+            return Err(Error::TypeMismatch {
+                expected: "Function".into(),
+                got: function.type_name().into_owned().into(),
+            });
+        };
+        let parameter_count = unsafe {
+            engine
+                .env
+                .get_function_unchecked(closure.0.function_id)
+                .parameter_count
+        };
+        let expected = match parameter_count {
+            FunctionParameterCount::Fixed(n) => (Args::COUNT != usize::from(n)).then_some(n),
+            FunctionParameterCount::AtLeast(n) => (Args::COUNT < usize::from(n)).then_some(n),
+            FunctionParameterCount::Varargs => None,
+        };
+        if let Some(expected) = expected {
+            return Err(Error::ArgumentCount {
+                expected: usize::from(expected),
+                got: Args::COUNT,
+            });
+        }
+        Ok(Self {
+            chunk: call_chunk(Args::COUNT)?,
+            function,
+            _signature: PhantomData,
+        })
+    }
+
+    /// Calls the function with the given arguments, and returns what it evaluated to.
+    ///
+    /// Like [`Engine::call`], this runs the function until it finishes, and returns
+    /// [`Error::Deadlock`] if it blocks.
+    pub fn call(&self, engine: &mut Engine, arguments: Args) -> Result<R, Error> {
+        let mut stacks = engine.stacks.take();
+        push_arguments(
+            stacks.values_mut(),
+            self.function.clone(),
+            arguments,
+            &engine.library,
+            &mut engine.gc,
+        );
+        let fiber = Fiber {
+            inner: vm::Fiber::with_stacks(Rc::clone(&self.chunk), stacks),
+            engine,
+        };
+        fiber.trampoline()
+    }
+
+    /// Returns the function as a dynamically typed value.
+    pub fn as_value(&self) -> &Value {
+        &self.function
+    }
+}
+
+impl<Args, R> Clone for TypedFunction<Args, R> {
+    fn clone(&self) -> Self {
+        Self {
+            function: self.function.clone(),
+            chunk: Rc::clone(&self.chunk),
+            _signature: PhantomData,
+        }
+    }
+}
+
+impl<Args, R> fmt::Debug for TypedFunction<Args, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedFunction")
+            .field("function", &self.function)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Engine {
+    /// Returns a [`TypedFunction`] calling the function stored in the given global.
+    ///
+    /// Returns an error if the global doesn't hold a function, or the function doesn't accept the
+    /// number of arguments in `Args`.
+    pub fn get_function<Args, R>(
+        &self,
+        id: impl OptionalGlobalName,
+    ) -> Result<TypedFunction<Args, R>, Error>
+    where
+        Args: IntoArguments,
+        R: TryFromValue,
+    {
+        let function: Value = self.get(id)?;
+        TypedFunction::new(self, function)
+    }
+}
+
+impl Value {
+    /// Converts the value into a [`TypedFunction`], checking that it's a function accepting the
+    /// number of arguments in `Args`. See [`Engine::get_function`].
+    pub fn into_typed_fn<Args, R>(self, engine: &Engine) -> Result<TypedFunction<Args, R>, Error>
+    where
+        Args: IntoArguments,
+        R: TryFromValue,
+    {
+        TypedFunction::new(engine, self)
+    }
+}
@@ -1,18 +1,20 @@
 use std::{any::Any, fmt, marker::PhantomData, rc::Rc};
 
 use crate::{
-    builtin_traits::{BuiltinTrait, BuiltinTraitFunction},
+    builtin_traits::{iterator, BuiltinTrait, BuiltinTraitFunction},
     ffvariants,
     hl::userdata::Type,
     ll::{
         bytecode::{
             BuiltinTraits, DispatchTable, Environment, Function, FunctionKind, Library,
-            MethodSignature,
+            MethodSignature, UserFinalizer, Visibility,
         },
         gc::{Gc, Memory},
-        value::{self, Closure},
+        value::{self, Closure, RawValue},
     },
-    Error, ForeignFunction, FunctionParameterCount, MethodParameterCount, Value,
+    operators::OperatorFunction,
+    Error, ForeignFunction, FunctionParameterCount, IntoValue, MethodParameterCount, TryFromValue,
+    UserData, Value,
 };
 
 struct UnresolvedMethodSignature {
@@ -59,6 +61,10 @@ impl DispatchTableDescriptor {
                 )),
                 kind: f,
                 hidden_in_stack_traces: false,
+                traced: env.trace_foreign_functions,
+                declaration: None,
+                visibility: Visibility::Public,
+                impl_block: None,
             })
             .map_err(|_| Error::TooManyFunctions)?;
         let signature = signature.resolve(builtin_traits);
@@ -112,6 +118,7 @@ where
     type_name: Rc<str>,
     type_dtable: DispatchTableDescriptor,
     instance_dtable: DispatchTableDescriptor,
+    finalizer: Option<UserFinalizer<T>>,
     _data: PhantomData<T>,
 }
 
@@ -150,6 +157,7 @@ where
             type_dtable: Default::default(),
             instance_dtable: Default::default(),
             type_name,
+            finalizer: None,
             _data: PhantomData,
         }
     }
@@ -199,7 +207,7 @@ where
         self.add_raw_static(
             name,
             Self::function_to_method_parameter_count(F::PARAMETER_COUNT),
-            FunctionKind::Foreign(f.into_raw_foreign_function()),
+            f.into_raw_function_kind(),
         )
     }
 
@@ -255,11 +263,61 @@ where
         V: ffvariants::Method<T>,
         F: ForeignFunction<V, ParameterCount = MethodParameterCount>,
     {
-        self.add_raw_function(
-            name,
-            F::PARAMETER_COUNT,
-            FunctionKind::Foreign(f.into_raw_foreign_function()),
-        )
+        self.add_raw_function(name, F::PARAMETER_COUNT, f.into_raw_function_kind())
+    }
+
+    /// Adds a field to the type, which is read with `value.name` and written with
+    /// `value.name = x`.
+    ///
+    /// This adds a getter `name` and a setter `set_name`, which is what property assignment in
+    /// scripts calls.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{Engine, TypeBuilder, UserData};
+    ///
+    /// struct Monster {
+    ///     hp: f64,
+    /// }
+    ///
+    /// impl UserData for Monster {}
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_type(
+    ///     TypeBuilder::<Monster>::new("Monster")
+    ///         .add_static("new", || Monster { hp: 10.0 })
+    ///         .add_field("hp", |m: &Monster| m.hp, |m: &mut Monster, hp| m.hp = hp),
+    /// )?;
+    ///
+    /// let hp: f64 = engine
+    ///     .start("monster.mi", "let m = Monster.new()\nm.hp = m.hp - 3\nm.hp")?
+    ///     .trampoline()?;
+    /// assert_eq!(hp, 7.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_field<V, G, S>(self, name: &str, get: G, set: S) -> Self
+    where
+        T: UserData + Sized,
+        V: IntoValue + TryFromValue + 'static,
+        G: Fn(&T) -> V + 'static,
+        S: Fn(&mut T, V) + 'static,
+    {
+        self.add_readonly_field(name, get)
+            .add_function(&format!("set_{name}"), set)
+    }
+
+    /// Adds a field that scripts can read, but not assign to.
+    ///
+    /// Like with [`add_field`][Self::add_field], the field is read with `value.name`.
+    pub fn add_readonly_field<V, G>(self, name: &str, get: G) -> Self
+    where
+        T: UserData + Sized,
+        V: IntoValue + 'static,
+        G: Fn(&T) -> V + 'static,
+    {
+        self.add_function(name, get)
     }
 
     /// Adds a function that's part of a built-in trait implementation.
@@ -324,11 +382,166 @@ where
                 parameter_count: F::PARAMETER_COUNT,
                 builtin_trait: which.owning_trait(),
             },
-            FunctionKind::Foreign(f.into_raw_foreign_function()),
+            f.into_raw_function_kind(),
         ));
         self
     }
 
+    /// Implements the built-in `Iterator` trait for the type, such that its instances can be
+    /// iterated over with `for` loops.
+    ///
+    /// This is a shorthand for adding [`iterator::HasNext`] and [`iterator::Next`] using
+    /// [`add_builtin_trait_function`][Self::add_builtin_trait_function], which makes it
+    /// impossible to forget about either of them.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{Engine, TypeBuilder, UserData};
+    ///
+    /// struct Rows {
+    ///     rows: Vec<String>,
+    ///     index: usize,
+    /// }
+    ///
+    /// impl UserData for Rows {}
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_type(
+    ///     TypeBuilder::<Rows>::new("Rows")
+    ///         .add_static("query", || Rows {
+    ///             rows: vec!["Alice".into(), "Bob".into()],
+    ///             index: 0,
+    ///         })
+    ///         .implement_iterator(
+    ///             |rows: &Rows| rows.index < rows.rows.len(),
+    ///             |rows: &mut Rows| {
+    ///                 rows.index += 1;
+    ///                 rows.rows[rows.index - 1].clone()
+    ///             },
+    ///         ),
+    /// )?;
+    ///
+    /// let names: String = engine
+    ///     .start(
+    ///         "rows.mi",
+    ///         r#" let names = ""
+    ///             for name in Rows.query do names = names.cat(name) end
+    ///             names "#,
+    ///     )?
+    ///     .trampoline()?;
+    /// assert_eq!(names, "AliceBob");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn implement_iterator<HS, NS, H, N>(self, has_next: H, next: N) -> Self
+    where
+        iterator::HasNext: BuiltinTraitFunction<HS>,
+        iterator::Next: BuiltinTraitFunction<NS>,
+        H: ForeignFunction<HS, ParameterCount = MethodParameterCount>,
+        N: ForeignFunction<NS, ParameterCount = MethodParameterCount>,
+    {
+        self.add_builtin_trait_function(iterator::HasNext, has_next)
+            .add_builtin_trait_function(iterator::Next, next)
+    }
+
+    /// Adds a function that overloads an operator for the type's instances.
+    ///
+    /// The function must have a signature that's compatible with the operator in question; see
+    /// the [`operators`][crate::operators] module for the operators and their signatures.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use mica::{operators, Engine, TypeBuilder, UserData};
+    ///
+    /// #[derive(Clone, Copy, PartialEq)]
+    /// struct Vec2 {
+    ///     x: f64,
+    ///     y: f64,
+    /// }
+    ///
+    /// impl UserData for Vec2 {}
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.add_type(
+    ///     TypeBuilder::<Vec2>::new("Vec2")
+    ///         .add_static("new", |x, y| Vec2 { x, y })
+    ///         .add_readonly_field("x", |v: &Vec2| v.x)
+    ///         .add_operator(operators::Add, |a: &Vec2, b: Vec2| Vec2 {
+    ///             x: a.x + b.x,
+    ///             y: a.y + b.y,
+    ///         })
+    ///         .add_operator(operators::Eq, |a: &Vec2, b: Vec2| *a == b),
+    /// )?;
+    ///
+    /// let x: f64 = engine
+    ///     .start(
+    ///         "vec2.mi",
+    ///         r#" let v = Vec2.new(1, 2) + Vec2.new(3, 4)
+    ///             assert(v == Vec2.new(4, 6))
+    ///             v.x "#,
+    ///     )?
+    ///     .trampoline()?;
+    /// assert_eq!(x, 4.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_operator<S, O, F>(self, _which: O, f: F) -> Self
+    where
+        O: OperatorFunction<S>,
+        F: ForeignFunction<S, ParameterCount = MethodParameterCount>,
+    {
+        self.add_raw_function(O::NAME, F::PARAMETER_COUNT, f.into_raw_function_kind())
+    }
+
+    /// Sets a function that's called with each value of the type right before it's dropped, which
+    /// usually happens when the garbage collector finds that the value is no longer reachable,
+    /// or when the engine is dropped.
+    ///
+    /// This is useful for releasing resources held by values of types that can't implement
+    /// [`Drop`] themselves, or that need to be released using some outside state, such as the
+    /// device a GPU buffer was allocated on.
+    ///
+    /// The finalizer is not called for values that were created before the type was added to the
+    /// engine.
+    ///
+    /// # Examples
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::{cell::RefCell, rc::Rc};
+    ///
+    /// use mica::{Engine, TypeBuilder, UserData, Value};
+    ///
+    /// struct Buffer {
+    ///     id: u32,
+    /// }
+    ///
+    /// impl UserData for Buffer {}
+    ///
+    /// let freed = Rc::new(RefCell::new(Vec::new()));
+    /// let mut engine = Engine::new();
+    /// engine.add_type(
+    ///     TypeBuilder::<Buffer>::new("Buffer")
+    ///         .add_static("new", |id| Buffer { id })
+    ///         .finalizer({
+    ///             let freed = Rc::clone(&freed);
+    ///             move |buffer: &mut Buffer| freed.borrow_mut().push(buffer.id)
+    ///         }),
+    /// )?;
+    ///
+    /// let _: Value = engine
+    ///     .start("buffer.mi", "let b = Buffer.new(1)\nb = nil\nGc.collect()")?
+    ///     .trampoline()?;
+    /// assert_eq!(*freed.borrow(), [1]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn finalizer(mut self, finalizer: impl Fn(&mut T) + 'static) -> Self {
+        self.finalizer = Some(Rc::new(finalizer));
+        self
+    }
+
     /// Adds a _raw_ instance function to the type.
     ///
     /// You should generally prefer [`add_function`][`Self::add_function`] instead of this.
@@ -415,7 +628,7 @@ where
     }
 
     pub(crate) fn build_in_library(
-        self,
+        mut self,
         env: &mut Environment,
         library: &mut Library,
         gc: &mut Memory,
@@ -423,6 +636,9 @@ where
     where
         T: Any + Sized,
     {
+        if let Some(finalizer) = self.finalizer.take() {
+            library.set_user_finalizer::<T>(finalizer);
+        }
         let built_type = self.build(env, gc, &library.builtin_traits)?;
         library.add_user_dtable::<T>(Gc::clone(&built_type.instance_dtable));
         Ok(built_type)
@@ -435,6 +651,62 @@ impl<T> fmt::Debug for TypeBuilder<T> {
     }
 }
 
+/// A type that can describe itself to Mica with a [`TypeBuilder`].
+///
+/// Rather than implementing this by hand, you'll usually want to use `#[derive(MicaType)]`, which
+/// is available with the `derive` feature. The derive macro generates getters and setters for the
+/// type's fields and a `new` constructor, and can be asked to include functions from an `impl`
+/// block marked with `#[mica::methods]`; see [`MicaMethods`].
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "derive")]
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use mica::{Engine, MicaType, UserData};
+///
+/// #[derive(MicaType)]
+/// #[mica(methods)]
+/// struct Vector {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// #[mica::methods]
+/// impl Vector {
+///     #[mica]
+///     fn len(&self) -> f64 {
+///         self.x.hypot(self.y)
+///     }
+/// }
+///
+/// impl UserData for Vector {}
+///
+/// let mut engine = Engine::new();
+/// engine.add_type(Vector::type_builder())?;
+/// let len: f64 = engine
+///     .start("vector.mi", "let v = Vector.new(3, 0) v.set_y(v.x + 1) v.len")?
+///     .trampoline()?;
+/// assert_eq!(len, 5.0);
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "derive"))]
+/// # fn main() {}
+/// ```
+pub trait MicaType: Any + Sized {
+    /// Returns a type builder describing the type.
+    fn type_builder() -> TypeBuilder<Self>;
+}
+
+/// Functions from an `impl` block that are exposed to Mica, generated by `#[mica::methods]`.
+///
+/// Functions marked with `#[mica]` are added to the type builder, as instance functions if they
+/// take `&self` or `&mut self`, and as static functions otherwise. `#[mica(name = "...")]` changes
+/// the name a function is available under.
+pub trait MicaMethods: Sized {
+    /// Adds the functions to the type builder.
+    fn add_methods(builder: TypeBuilder<Self>) -> TypeBuilder<Self>;
+}
+
 /// Dispatch tables for a finished type.
 pub(crate) struct BuiltType<T>
 where
@@ -456,6 +728,11 @@ impl<T> BuiltType<T> {
         gc.manage(&self.instance_dtable);
         let user_data: Box<dyn value::UserData> =
             Box::new(Type::<T>::new(Gc::clone(&self.type_dtable)));
-        Value::UserData(Gc::new(user_data))
+        let user_data = Gc::new(user_data);
+        // The type is kept alive by its instance dispatch table from now on, so it has to be
+        // managed by the GC.
+        let raw = gc.manage(&user_data);
+        self.instance_dtable.set_type_value(RawValue::from(raw));
+        Value::UserData(user_data)
     }
 }
@@ -10,12 +10,12 @@ use std::{
 
 use crate::{
     ll::{
-        bytecode::{DispatchTable, Library},
+        bytecode::{DispatchTable, Library, UserFinalizer},
         error::LanguageErrorKind,
         gc::{Gc, GcRaw},
         value::{self, RawValue},
     },
-    Error,
+    Error, Value,
 };
 
 /// Marker trait for all user data types.
@@ -33,7 +33,8 @@ pub trait UserData: Any {
     /// that the GC can mark the references as reachable.
     ///
     /// It's usually better/easier to deal with strong [`Value`][crate::Value]s which use
-    /// reference counting to manage allocations.
+    /// reference counting to manage allocations, and report them from
+    /// [`visit_values`][Self::visit_values] instead.
     ///
     /// # Examples
     /// ```
@@ -53,6 +54,37 @@ pub trait UserData: Any {
     /// ```
     #[allow(unused_variables)]
     fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {}
+
+    /// Used to let the GC know of any [`Value`]s owned by the data.
+    ///
+    /// Values held by Rust code are always kept alive by the GC, along with everything they
+    /// refer to. This also applies to values stored in user data, which means that a reference
+    /// cycle going through user data - such as an object holding a callback that captures the
+    /// object - will never be collected. Reporting the values here lets the GC treat them as
+    /// references from the user data instead, so that they are only kept alive for as long as
+    /// the user data itself is reachable.
+    ///
+    /// `visit` should be called for each [`Value`] stored inside the user data. Forgetting to
+    /// report a value is safe, but it can lead to memory leaks.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::{UserData, Value};
+    ///
+    /// struct Button {
+    ///     on_click: Option<Value>,
+    /// }
+    ///
+    /// impl UserData for Button {
+    ///     fn visit_values(&self, visit: &mut dyn FnMut(&Value)) {
+    ///         if let Some(on_click) = &self.on_click {
+    ///             visit(on_click);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[allow(unused_variables)]
+    fn visit_values(&self, visit: &mut dyn FnMut(&Value)) {}
 }
 
 /// A type. This is used to represent user-defined Rust types in the VM (but not their instances).
@@ -89,7 +121,8 @@ where
     }
 
     fn partial_eq(&self, other: &dyn value::UserData) -> bool {
-        self as *const dyn value::UserData == other
+        // Only the addresses are compared, because the same type can have more than one vtable.
+        std::ptr::addr_eq(self as *const Self, other as *const dyn value::UserData)
     }
 
     fn try_partial_cmp(
@@ -105,7 +138,6 @@ where
 
     // This does return the correct name, clippy.
     #[allow(clippy::misnamed_getters)]
-
     fn type_name(&self) -> Cow<'_, str> {
         Cow::Borrowed(&self.dtable.pretty_name)
     }
@@ -123,15 +155,21 @@ pub(crate) struct Object<T> {
     shared_borrows: Cell<usize>,
     borrowed_mutably: Cell<bool>,
     data: UnsafeCell<T>,
+    finalizer: Option<UserFinalizer<T>>,
 }
 
 impl<T> Object<T> {
-    pub(crate) fn new(dtable: GcRaw<DispatchTable>, data: T) -> Self {
+    pub(crate) fn new(
+        dtable: GcRaw<DispatchTable>,
+        data: T,
+        finalizer: Option<UserFinalizer<T>>,
+    ) -> Self {
         Self {
             dtable: unsafe { Gc::from_raw(dtable) },
             shared_borrows: Cell::new(0),
             borrowed_mutably: Cell::new(false),
             data: UnsafeCell::new(data),
+            finalizer,
         }
     }
 
@@ -161,6 +199,7 @@ impl<T> Object<T> {
     /// This function is unsafe because the guard can be dropped accidentally while keeping the
     /// reference alive, which can lead to two mutable references to the data existing at once.
     #[doc(hidden)]
+    #[allow(clippy::mut_from_ref)]
     pub(crate) unsafe fn unsafe_borrow_mut(&self) -> Result<(&mut T, UnsafeMutGuard<T>), Error> {
         if self.shared_borrows.get() > 0 {
             return Err(Error::ReentrantMutableBorrow);
@@ -182,9 +221,17 @@ impl<T> fmt::Debug for Object<T> {
     }
 }
 
+impl<T> Drop for Object<T> {
+    fn drop(&mut self) {
+        if let Some(finalizer) = &self.finalizer {
+            finalizer(self.data.get_mut());
+        }
+    }
+}
+
 impl<T> value::UserData for Object<T>
 where
-    T: Any,
+    T: UserData,
 {
     fn dtable_gcraw(&self, _: Option<&Library>) -> GcRaw<DispatchTable> {
         Gc::as_raw(&self.dtable)
@@ -195,7 +242,8 @@ where
     }
 
     fn partial_eq(&self, other: &dyn value::UserData) -> bool {
-        self as *const dyn value::UserData == other
+        // Only the addresses are compared, because the same type can have more than one vtable.
+        std::ptr::addr_eq(self as *const Self, other as *const dyn value::UserData)
     }
 
     fn try_partial_cmp(
@@ -214,6 +262,24 @@ where
     fn type_name(&self) -> Cow<'_, str> {
         Cow::Borrowed(&self.dtable.pretty_name)
     }
+
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        // The data cannot be borrowed mutably while the GC is running, as collections only happen
+        // inside the VM and not in the middle of a foreign function call.
+        let data = unsafe { &*self.data.get() };
+        data.visit_references(visit);
+    }
+
+    fn visit_owned_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        // A foreign function that's called back into the VM may still be holding a mutable
+        // reference to the data. Its values are then treated like any other foreign references,
+        // which keeps them alive.
+        if self.borrowed_mutably.get() {
+            return;
+        }
+        let data = unsafe { &*self.data.get() };
+        data.visit_values(&mut |value| visit(value.to_raw_unmanaged()));
+    }
 }
 
 /// An _unsafe_ guard for a `&T` borrowed from an `Object<T>`.
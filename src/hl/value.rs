@@ -1,7 +1,10 @@
+mod maps;
+mod option;
 mod raw;
 
 use std::{any::type_name, borrow::Cow, fmt};
 
+pub use option::*;
 pub use raw::*;
 
 use self::into_value::{DoesNotUseEngine, EngineUse, UsesEngine};
@@ -9,7 +12,7 @@ use crate::{
     ll::{
         bytecode::{DispatchTable, Library},
         gc::{Gc, Memory},
-        value::{self, Closure, Dict, List, RawValue, Struct, Trait},
+        value::{self, Closure, Dict, List, RawValue, Str, Struct, Trait},
     },
     Error, Object, UserData,
 };
@@ -38,8 +41,8 @@ pub enum Value {
     True,
     /// A `Number` value.
     Number(f64),
-    /// A GC'd `String`.
-    String(Gc<String>),
+    /// A GC'd string.
+    String(Gc<Str>),
     /// A function.
     ///
     /// Functions can be called with [`Engine::call`][crate::Engine::call].
@@ -54,7 +57,8 @@ pub enum Value {
     List(Hidden<Box<dyn value::UserData>>),
     /// A dict.
     ///
-    /// Dicts are opaque to the Rust API, no conversion function currently exists for them.
+    /// Dicts are opaque to the Rust API and must be converted into a typed `HashMap<K, V>` or
+    /// `BTreeMap<K, V>`.
     Dict(Hidden<Box<dyn value::UserData>>),
     /// A tuple.
     ///
@@ -268,7 +272,7 @@ impl IntoValue for char {
     type EngineUse = DoesNotUseEngine;
 
     fn into_value(self, _: ()) -> Value {
-        Value::String(Gc::new(self.to_string()))
+        Value::String(Gc::new(Str::from(self)))
     }
 }
 
@@ -276,19 +280,27 @@ impl IntoValue for &str {
     type EngineUse = DoesNotUseEngine;
 
     fn into_value(self, _: ()) -> Value {
-        Value::String(Gc::new(self.to_string()))
+        Value::String(Gc::new(Str::new(self)))
     }
 }
 
 impl IntoValue for String {
     type EngineUse = DoesNotUseEngine;
 
+    fn into_value(self, _: ()) -> Value {
+        Value::String(Gc::new(Str::from(self)))
+    }
+}
+
+impl IntoValue for Str {
+    type EngineUse = DoesNotUseEngine;
+
     fn into_value(self, _: ()) -> Value {
         Value::String(Gc::new(self))
     }
 }
 
-impl IntoValue for Gc<String> {
+impl IntoValue for Gc<Str> {
     type EngineUse = DoesNotUseEngine;
 
     fn into_value(self, _: ()) -> Value {
@@ -310,20 +322,40 @@ where
     }
 }
 
-/// **NOTE:** You should generally avoid dealing with raw values. This method in particular could
-/// cause you a bad time if you feed temporary `Value`s converted into `RawValue`s into the
-/// vector.
-#[doc(hidden)]
-impl IntoValue for Vec<RawValue> {
-    type EngineUse = DoesNotUseEngine;
+/// Vectors are converted into lists, converting each element.
+///
+/// **NOTE:** When converting a `Vec<RawValue>`, you could have a bad time if you feed temporary
+/// `Value`s converted into `RawValue`s into the vector.
+impl<T> IntoValue for Vec<T>
+where
+    T: IntoValue,
+{
+    /// Like with tuples, whether the elements need the engine isn't known.
+    type EngineUse = UsesEngine;
 
-    fn into_value(self, _: ()) -> Value {
-        Value::List(Hidden(Gc::new(Box::new(List::new(self)))))
+    fn into_value(self, (library, gc): (&Library, &mut Memory)) -> Value {
+        let elements = self
+            .into_iter()
+            .map(|element| element.into_value_with_engine_state(library, gc).to_raw(gc))
+            .collect();
+        Value::List(Hidden(Gc::new(Box::new(List::new(elements)))))
+    }
+}
+
+/// Slices are converted into lists of clones of their elements.
+impl<T> IntoValue for &[T]
+where
+    T: IntoValue + Clone,
+{
+    type EngineUse = UsesEngine;
+
+    fn into_value(self, (library, gc): (&Library, &mut Memory)) -> Value {
+        self.to_vec().into_value((library, gc))
     }
 }
 
-/// **NOTE:** Again, you should avoid dealing with raw values. See comment above
-/// (for `From<Vec<RawValue>>`).
+/// **NOTE:** You should generally avoid dealing with raw values, as their lifetimes aren't tracked
+/// by the dict.
 #[doc(hidden)]
 impl IntoValue for Dict {
     type EngineUse = DoesNotUseEngine;
@@ -344,14 +376,11 @@ where
     type EngineUse = UsesEngine;
 
     fn into_value(self, (library, _): (&Library, &mut Memory)) -> Value {
-        let dtable = library
-            .get_user_dtable::<T>()
-            .map(Gc::clone)
-            .unwrap_or_else(|| {
-                let ad_hoc_dtable = DispatchTable::new_for_instance(type_name::<T>());
-                Gc::new(ad_hoc_dtable)
-            });
-        let object = Object::new(Gc::as_raw(&dtable), self);
+        let dtable = library.get_user_dtable::<T>().cloned().unwrap_or_else(|| {
+            let ad_hoc_dtable = DispatchTable::new_for_instance(type_name::<T>());
+            Gc::new(ad_hoc_dtable)
+        });
+        let object = Object::new(Gc::as_raw(&dtable), self, library.get_user_finalizer::<T>());
         Value::UserData(Gc::new(Box::new(object)))
     }
 }
@@ -437,7 +466,7 @@ try_from_value_numeric!(isize);
 try_from_value_numeric!(f32);
 try_from_value_numeric!(f64);
 
-impl TryFromValue for Gc<String> {
+impl TryFromValue for Gc<Str> {
     fn try_from_value(value: &Value, _: &Library) -> Result<Self, Error> {
         if let Value::String(s) = value {
             Ok(Gc::clone(s))
@@ -447,9 +476,15 @@ impl TryFromValue for Gc<String> {
     }
 }
 
+impl TryFromValue for Str {
+    fn try_from_value(value: &Value, library: &Library) -> Result<Self, Error> {
+        <Gc<Str>>::try_from_value(value, library).map(|s| Str::clone(&s))
+    }
+}
+
 impl TryFromValue for String {
     fn try_from_value(value: &Value, library: &Library) -> Result<Self, Error> {
-        <Gc<String>>::try_from_value(value, library).map(|s| s.to_string())
+        <Gc<Str>>::try_from_value(value, library).map(|s| s.to_string())
     }
 }
 
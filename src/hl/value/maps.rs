@@ -0,0 +1,101 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+use crate::{
+    hl::value::{type_mismatch, UsesEngine},
+    ll::{bytecode::Library, gc::Memory, value::Dict},
+    Error, Hidden, IntoValue, TryFromValue, Value,
+};
+
+/// Converts key-value pairs into a dict, converting each key and value.
+fn dict_from_pairs<K, V>(
+    pairs: impl Iterator<Item = (K, V)>,
+    library: &Library,
+    gc: &mut Memory,
+) -> Value
+where
+    K: IntoValue,
+    V: IntoValue,
+{
+    let dict = Dict::new();
+    for (key, value) in pairs {
+        let key = key.into_value_with_engine_state(library, gc).to_raw(gc);
+        let value = value.into_value_with_engine_state(library, gc).to_raw(gc);
+        dict.insert(key, value);
+    }
+    dict.into_value(())
+}
+
+/// Converts each key-value pair of a dict, and collects them into a map.
+fn pairs_from_dict<K, V, M>(value: &Value, library: &Library) -> Result<M, Error>
+where
+    K: TryFromValue,
+    V: TryFromValue,
+    M: FromIterator<(K, V)>,
+{
+    let Value::Dict(Hidden(d)) = value else {
+        return Err(type_mismatch("Dict", value));
+    };
+    let dict = d
+        .as_any()
+        .downcast_ref::<Dict>()
+        .expect("Value::Dict must contain a dict");
+    // The pairs are copied out first, so that the dict isn't borrowed during the conversions.
+    let pairs: Vec<_> = unsafe { dict.iter() }.collect();
+    pairs
+        .into_iter()
+        .map(|(key, value)| {
+            Ok((
+                K::try_from_value(&Value::from_raw(key), library)?,
+                V::try_from_value(&Value::from_raw(value), library)?,
+            ))
+        })
+        .collect()
+}
+
+impl<K, V, S> IntoValue for HashMap<K, V, S>
+where
+    K: IntoValue,
+    V: IntoValue,
+{
+    type EngineUse = UsesEngine;
+
+    fn into_value(self, (library, gc): (&Library, &mut Memory)) -> Value {
+        dict_from_pairs(self.into_iter(), library, gc)
+    }
+}
+
+impl<K, V> IntoValue for BTreeMap<K, V>
+where
+    K: IntoValue,
+    V: IntoValue,
+{
+    type EngineUse = UsesEngine;
+
+    fn into_value(self, (library, gc): (&Library, &mut Memory)) -> Value {
+        dict_from_pairs(self.into_iter(), library, gc)
+    }
+}
+
+impl<K, V, S> TryFromValue for HashMap<K, V, S>
+where
+    K: TryFromValue + Eq + Hash,
+    V: TryFromValue,
+    S: BuildHasher + Default,
+{
+    fn try_from_value(value: &Value, library: &Library) -> Result<Self, Error> {
+        pairs_from_dict(value, library)
+    }
+}
+
+impl<K, V> TryFromValue for BTreeMap<K, V>
+where
+    K: TryFromValue + Ord,
+    V: TryFromValue,
+{
+    fn try_from_value(value: &Value, library: &Library) -> Result<Self, Error> {
+        pairs_from_dict(value, library)
+    }
+}
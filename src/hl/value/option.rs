@@ -0,0 +1,205 @@
+use crate::{
+    hl::value::{type_mismatch, UsesEngine},
+    ll::{
+        bytecode::{DispatchTable, Library},
+        gc::{Gc, Memory},
+        value::{RawValue, Str, Struct, Tuple},
+    },
+    Error, Hidden, IntoValue, TryFromValue, Value,
+};
+
+/// Converts an [`Option`] into a variant of the core library's `Option` enum.
+///
+/// Plain [`Option`]s convert `None` into `nil`, which can't be told apart from a `Some(nil)`. This
+/// wrapper converts into `Option.Some(value)` and `Option.None` instead.
+///
+/// If the engine's core library doesn't declare the enum, the conversion falls back to the one for
+/// plain `Option`s.
+///
+/// # Examples
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use mica::{Engine, OptionValue};
+///
+/// let mut engine = Engine::new();
+/// engine.add_function("find", |x: f64| OptionValue((x > 0.0).then_some(x)))?;
+/// let found: bool = engine.start("example.mi", "find(1).is_some and find(-1) == None")?.trampoline()?;
+/// assert!(found);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OptionValue<T>(pub Option<T>);
+
+impl<T> From<Option<T>> for OptionValue<T> {
+    fn from(option: Option<T>) -> Self {
+        Self(option)
+    }
+}
+
+impl<T> IntoValue for OptionValue<T>
+where
+    T: IntoValue,
+{
+    type EngineUse = UsesEngine;
+
+    fn into_value(self, (library, gc): (&Library, &mut Memory)) -> Value {
+        let Some(dtable) = &library.option_dtable else {
+            return self.0.into_value_with_engine_state(library, gc);
+        };
+        match self.0 {
+            Some(value) => {
+                let value = value.into_value_with_engine_state(library, gc).to_raw(gc);
+                variant(gc, dtable, "Some", vec![value])
+            }
+            None => variant(gc, dtable, "None", vec![]),
+        }
+    }
+}
+
+/// Converts `Option.Some(value)` and `Option.None` back into an [`Option`]. Like with the other
+/// direction, plain values and `nil` are accepted if the engine doesn't declare the enum.
+impl<T> TryFromValue for OptionValue<T>
+where
+    T: TryFromValue,
+{
+    fn try_from_value(value: &Value, library: &Library) -> Result<Self, Error> {
+        let Some(dtable) = &library.option_dtable else {
+            return Option::<T>::try_from_value(value, library).map(Self);
+        };
+        match variant_of(value, dtable) {
+            Some(("Some", &[inner])) => Ok(Self(Some(T::try_from_value(
+                &Value::from_raw(inner),
+                library,
+            )?))),
+            Some(("None", [])) => Ok(Self(None)),
+            _ => Err(type_mismatch("Option", value)),
+        }
+    }
+}
+
+/// Converts a [`Result`] into a variant of the core library's `Result` enum.
+///
+/// Foreign functions returning plain [`Result`]s raise their errors. This wrapper returns them as
+/// `Result.Err(error)` values instead, and successful results as `Result.Ok(value)`.
+///
+/// If the engine's core library doesn't declare the enum, the value or error is returned as is.
+///
+/// # Examples
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use mica::{Engine, ResultValue};
+///
+/// let mut engine = Engine::new();
+/// engine.add_function("parse", |s: String| {
+///     ResultValue(s.parse::<f64>().map_err(|e| e.to_string()))
+/// })?;
+/// let x: f64 = engine.start("example.mi", r#" parse("x").unwrap_or(1) + parse("2").unwrap "#)?.trampoline()?;
+/// assert_eq!(x, 3.0);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResultValue<T, E>(pub Result<T, E>);
+
+impl<T, E> From<Result<T, E>> for ResultValue<T, E> {
+    fn from(result: Result<T, E>) -> Self {
+        Self(result)
+    }
+}
+
+impl<T, E> IntoValue for ResultValue<T, E>
+where
+    T: IntoValue,
+    E: IntoValue,
+{
+    type EngineUse = UsesEngine;
+
+    fn into_value(self, (library, gc): (&Library, &mut Memory)) -> Value {
+        let dtable = library.result_dtable.as_ref();
+        let (name, value) = match self.0 {
+            Ok(value) => ("Ok", value.into_value_with_engine_state(library, gc)),
+            Err(error) => ("Err", error.into_value_with_engine_state(library, gc)),
+        };
+        match dtable {
+            Some(dtable) => {
+                let value = value.to_raw(gc);
+                variant(gc, dtable, name, vec![value])
+            }
+            None => value,
+        }
+    }
+}
+
+impl<T, E> TryFromValue for ResultValue<T, E>
+where
+    T: TryFromValue,
+    E: TryFromValue,
+{
+    fn try_from_value(value: &Value, library: &Library) -> Result<Self, Error> {
+        Result::try_from_value(value, library).map(Self)
+    }
+}
+
+/// Converts `Result.Ok(value)` and `Result.Err(error)` into a [`Result`].
+///
+/// There is no conversion in the other direction, because foreign functions returning [`Result`]s
+/// raise their errors. [`ResultValue`] can be returned instead.
+impl<T, E> TryFromValue for Result<T, E>
+where
+    T: TryFromValue,
+    E: TryFromValue,
+{
+    fn try_from_value(value: &Value, library: &Library) -> Result<Self, Error> {
+        let variant = library
+            .result_dtable
+            .as_ref()
+            .and_then(|dtable| variant_of(value, dtable));
+        match variant {
+            Some(("Ok", &[inner])) => Ok(Ok(T::try_from_value(&Value::from_raw(inner), library)?)),
+            Some(("Err", &[inner])) => {
+                Ok(Err(E::try_from_value(&Value::from_raw(inner), library)?))
+            }
+            _ => Err(type_mismatch("Result", value)),
+        }
+    }
+}
+
+/// Returns the name of the variant and the values carried by the given value, if it's an instance
+/// of the enum with the given instance dispatch table.
+fn variant_of<'v>(
+    value: &'v Value,
+    dtable: &Gc<DispatchTable>,
+) -> Option<(&'v str, &'v [RawValue])> {
+    let Value::Struct(Hidden(instance)) = value else {
+        return None;
+    };
+    // SAFETY: The value keeps the instance, and therefore its dispatch table and fields, alive.
+    unsafe {
+        if !std::ptr::eq(instance.dtable(), &**dtable) {
+            return None;
+        }
+        instance.enum_variant()
+    }
+}
+
+/// Creates an instance of an enum with the given instance dispatch table, laid out like the ones
+/// created by the enum's variant constructors.
+fn variant(
+    gc: &mut Memory,
+    dtable: &Gc<DispatchTable>,
+    name: &str,
+    values: Vec<RawValue>,
+) -> Value {
+    let values = Value::Tuple(Hidden(Gc::new(Box::new(Tuple::new(values))))).to_raw(gc);
+    let name = RawValue::from(gc.allocate_string(Str::new(name)));
+    let instance = Gc::new(Struct::from_parts(
+        Gc::as_raw(dtable),
+        true,
+        vec![name, values],
+    ));
+    // Managing the instance right away keeps its fields alive, should a collection happen before
+    // the value is used.
+    gc.manage(&instance);
+    Value::Struct(Hidden(instance))
+}
@@ -3,7 +3,7 @@ use std::hint::unreachable_unchecked;
 use crate::{
     ll::{
         gc::{Gc, Memory},
-        value::{Dict, List, RawValue, Record, Tuple, ValueKind},
+        value::{Dict, List, RawValue, Record, Str, Tuple, ValueKind},
     },
     Error, Hidden, Object, UnsafeMutGuard, UnsafeRefGuard, UserData, Value,
 };
@@ -16,13 +16,13 @@ impl Value {
             Value::False => RawValue::from(false),
             Value::True => RawValue::from(true),
             Value::Number(x) => RawValue::from(*x),
-            Value::String(s) => RawValue::from(gc.manage(s)),
+            Value::String(s) => RawValue::from(gc.manage_string(s)),
             Value::Function(f) => RawValue::from(gc.manage(&f.0)),
             Value::Struct(s) => RawValue::from(gc.manage(&s.0)),
             Value::Trait(t) => RawValue::from(gc.manage(&t.0)),
             Value::List(l) => RawValue::from(gc.manage(&l.0)),
             Value::Dict(d) => RawValue::from(gc.manage(&d.0)),
-            Value::Tuple(t) => RawValue::from(gc.manage(&t.0)),
+            Value::Tuple(t) => RawValue::from(unsafe { gc.manage_tuple(&t.0) }),
             Value::Record(r) => RawValue::from(gc.manage(&r.0)),
             Value::UserData(u) => RawValue::from(gc.manage(u)),
         }
@@ -136,7 +136,7 @@ impl SelfFromRawValue for f64 {
     }
 }
 
-impl SelfFromRawValue for String {
+impl SelfFromRawValue for Str {
     type Guard = ();
 
     unsafe fn self_from_raw_value(v: &RawValue) -> Result<(&Self, Self::Guard), Error> {
@@ -213,6 +213,7 @@ where
     ///
     /// Because GATs aren't stable yet, `Self::Guard` can keep a **raw pointer** to the value
     /// and as such must not outlive the value.
+    #[allow(clippy::mut_from_ref)]
     unsafe fn mut_self_from_raw_value(value: &RawValue) -> Result<(&mut Self, Self::Guard), Error>;
 }
 
@@ -1,6 +1,8 @@
 #![allow(clippy::or_fun_call)]
 #![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
 #![doc = include_str!("lib.md")]
+#![cfg_attr(feature = "threaded-dispatch", feature(explicit_tail_calls))]
+#![cfg_attr(feature = "threaded-dispatch", allow(incomplete_features))]
 
 pub mod corelib;
 mod hl;
@@ -10,9 +10,11 @@
 pub mod ast;
 pub mod bytecode;
 pub mod codegen;
+pub mod cst;
 pub mod error;
 pub mod gc;
 pub mod lexer;
+pub mod lint;
 pub mod parser;
 pub mod value;
 pub mod vm;
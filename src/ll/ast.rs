@@ -5,7 +5,12 @@ use std::{
     rc::Rc,
 };
 
-use crate::ll::error::{LanguageError, LanguageErrorKind, Location};
+use crate::ll::{
+    error::{LanguageError, LanguageErrorKind, Location, Span},
+    lexer::FrontMatter,
+};
+
+pub mod query;
 
 /// A lightweight handle to a node.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -20,9 +25,13 @@ impl NodeId {
 /// An abstract syntax tree.
 pub struct Ast {
     module_name: Rc<str>,
+    /// The source code the tree was parsed from. This is empty for trees that were built by hand.
+    source: Rc<str>,
+    front_matter: FrontMatter,
 
     nodes: Vec<(NodeKind, (u32, u32))>,
-    locations: Vec<Location>,
+    /// The spans of the tokens the nodes were created from. These do not include child nodes.
+    spans: Vec<Span>,
 
     data: Vec<Option<NodeData>>,
 }
@@ -39,19 +48,102 @@ impl Ast {
     pub fn new(module_name: Rc<str>) -> Self {
         let mut ast = Self {
             module_name,
+            source: Rc::from(""),
+            front_matter: FrontMatter::default(),
             nodes: Vec::new(),
-            locations: Vec::new(),
+            spans: Vec::new(),
             data: Vec::new(),
         };
         let _empty = ast.create_node(NodeKind::Empty, ());
         ast
     }
 
+    /// Returns the name of the module the syntax tree was parsed from.
+    pub fn module_name(&self) -> &Rc<str> {
+        &self.module_name
+    }
+
+    /// Sets the source code the syntax tree is parsed from.
+    pub(crate) fn set_source(&mut self, source: Rc<str>) {
+        self.source = source;
+    }
+
+    /// Returns the metadata declared in the front matter of the source code.
+    pub fn front_matter(&self) -> &FrontMatter {
+        &self.front_matter
+    }
+
+    /// Sets the front matter of the source code the syntax tree is parsed from.
+    pub(crate) fn set_front_matter(&mut self, front_matter: FrontMatter) {
+        self.front_matter = front_matter;
+    }
+
+    /// Returns the source code the syntax tree was parsed from.
+    pub(crate) fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Copies the subtree rooted at `root` into a new syntax tree, whose source code is `source`.
+    /// Returns the new tree along with the root of the copy.
+    ///
+    /// The copy doesn't share any strings with this tree, so making it only reads this tree. This
+    /// makes it fine to copy from another thread, as long as this tree isn't modified meanwhile.
+    pub(crate) fn copy_subtree(&self, root: NodeId, source: Rc<str>) -> (Self, NodeId) {
+        let mut copy = Ast::new(Rc::from(&*self.module_name));
+        copy.source = source;
+        let mut nodes = Vec::new();
+        self.walk(root, |node, _| nodes.push(node));
+        // Subtrees are mostly made of nodes created one after another, so a table spanning their
+        // IDs stays small.
+        let first = nodes.iter().map(|node| node.0).min().unwrap_or(0);
+        let last = nodes.iter().map(|node| node.0).max().unwrap_or(0);
+        let mut ids = vec![NodeId::EMPTY; (last - first + 1) as usize];
+        let mut count = 0;
+        nodes.retain(|node| {
+            let id = &mut ids[(node.0 - first) as usize];
+            let is_new = *id == NodeId::EMPTY;
+            if is_new {
+                count += 1;
+                *id = NodeId(count);
+            }
+            is_new
+        });
+        let copied = |node: NodeId| match node {
+            NodeId::EMPTY => NodeId::EMPTY,
+            _ => ids[(node.0 - first) as usize],
+        };
+        for node in nodes {
+            let (left, right) = self.node_pair(node);
+            let data = self.data(node).map(|data| match data {
+                NodeData::Number(number) => NodeData::Number(*number),
+                NodeData::String(string) => NodeData::String(Rc::from(&**string)),
+                NodeData::Children(children) => {
+                    NodeData::Children(children.iter().map(|&child| copied(child)).collect())
+                }
+            });
+            copy.nodes
+                .push((self.kind(node), (copied(left).0, copied(right).0)));
+            copy.spans.push(self.spans[node.0 as usize]);
+            copy.data.push(data);
+        }
+        (copy, copied(root))
+    }
+
+    /// Returns the source code a node (including its children) was parsed from, or `None` if the
+    /// tree doesn't know its source code.
+    pub fn source_text(&self, node: NodeId) -> Option<&str> {
+        let span = self.span(node);
+        if span.is_uninit() {
+            return None;
+        }
+        self.source.get(span.start.byte..span.end.byte)
+    }
+
     /// Appends a node to the syntax tree.
     fn create_node(&mut self, kind: NodeKind, pair: impl ToNodePair) -> NodeId {
         let id = self.nodes.len();
         self.nodes.push((kind, pair.to_node_pair()));
-        self.locations.push(Location::UNINIT);
+        self.spans.push(Span::UNINIT);
         self.data.push(None);
         NodeId(id as u32)
     }
@@ -67,6 +159,14 @@ impl Ast {
         self.nodes[node.0 as usize].0
     }
 
+    /// Returns all nodes of the given kind, in the order they were created. Unlike walking the
+    /// tree, this works in constant stack space regardless of how deeply nested the tree is.
+    pub fn nodes_of_kind(&self, kind: NodeKind) -> impl Iterator<Item = NodeId> + '_ {
+        (0..self.nodes.len() as u32)
+            .map(NodeId)
+            .filter(move |&node| self.kind(node) == kind)
+    }
+
     /// Returns the raw pair of a node.
     pub fn pair(&self, node: NodeId) -> (u32, u32) {
         self.nodes[node.0 as usize].1
@@ -78,7 +178,26 @@ impl Ast {
 
     /// Returns the source location of a node.
     pub fn location(&self, node: NodeId) -> Location {
-        self.locations[node.0 as usize]
+        self.spans[node.0 as usize].start
+    }
+
+    /// Returns the span of source code covered by a node, including all of its children.
+    pub fn span(&self, node: NodeId) -> Span {
+        // Syntax trees can be very deep, so this walks the tree using an explicit stack rather
+        // than recursion.
+        let mut span = Span::UNINIT;
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if node == NodeId::EMPTY {
+                continue;
+            }
+            span = span.union(self.spans[node.0 as usize]);
+            let (left, right) = self.node_pair(node);
+            stack.push(left);
+            stack.push(right);
+            stack.extend_from_slice(self.children(node).unwrap_or(&[]));
+        }
+        span
     }
 
     /// Returns the number data of a node, or `None` if the node carries a different type of data.
@@ -117,12 +236,115 @@ impl Ast {
         (NodeId(left), NodeId(right))
     }
 
+    /// Calls `f` on every node of the tree rooted at `root_node`, along with the node's parent
+    /// (`None` for the root). Parents are visited before their children, and siblings in source
+    /// order. Syntax trees can be very deep, so this uses an explicit stack rather than recursion.
+    pub fn walk(&self, root_node: NodeId, mut f: impl FnMut(NodeId, Option<NodeId>)) {
+        let mut stack = vec![(root_node, None)];
+        while let Some((node, parent)) = stack.pop() {
+            if node == NodeId::EMPTY {
+                continue;
+            }
+            f(node, parent);
+            let (left, right) = self.node_pair(node);
+            let children = self.children(node).unwrap_or(&[]);
+            // Pushed in reverse, so that nodes are visited in source order.
+            for &child in children.iter().rev() {
+                stack.push((child, Some(node)));
+            }
+            stack.push((right, Some(node)));
+            stack.push((left, Some(node)));
+        }
+    }
+
+    /// Walks the tree rooted at `root_node` with a visitor, calling
+    /// [`enter`][Visitor::enter] on every node before its children and
+    /// [`leave`][Visitor::leave] after them. Like [`walk`][Self::walk], this uses an explicit
+    /// stack, so it works with arbitrarily deep trees.
+    pub fn visit(&self, root_node: NodeId, visitor: &mut impl Visitor) {
+        enum Step {
+            Enter(NodeId),
+            Leave(NodeId),
+        }
+
+        let mut stack = vec![Step::Enter(root_node)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(node) => {
+                    if node == NodeId::EMPTY {
+                        continue;
+                    }
+                    let visit = visitor.enter(self, node);
+                    stack.push(Step::Leave(node));
+                    if visit == Visit::Children {
+                        let (left, right) = self.node_pair(node);
+                        let children = self.children(node).unwrap_or(&[]);
+                        for &child in children.iter().rev() {
+                            stack.push(Step::Enter(child));
+                        }
+                        stack.push(Step::Enter(right));
+                        stack.push(Step::Enter(left));
+                    }
+                }
+                Step::Leave(node) => visitor.leave(self, node),
+            }
+        }
+    }
+
+    /// Formats a destructuring pattern the way it'd be written in source code, for use in
+    /// function signatures. Nodes that aren't valid patterns are formatted as `?`.
+    pub fn pattern_to_string(&self, pattern: NodeId) -> String {
+        let elements = |open: &str, close: &str| {
+            let elements: Vec<_> = self
+                .children(pattern)
+                .unwrap_or(&[])
+                .iter()
+                .map(|&element| self.pattern_to_string(element))
+                .collect();
+            format!("{open}{}{close}", elements.join(", "))
+        };
+        match self.kind(pattern) {
+            NodeKind::Identifier => self.string(pattern).unwrap().to_string(),
+            NodeKind::Underscore => "_".into(),
+            NodeKind::Tuple => elements("(", ")"),
+            NodeKind::Record => elements("{", "}"),
+            NodeKind::Rest => "..".into(),
+            NodeKind::RestParameter => {
+                format!("...{}", self.pattern_to_string(self.node_pair(pattern).0))
+            }
+            NodeKind::Implements => {
+                let (pattern, implemented_trait) = self.node_pair(pattern);
+                let trait_name = self.string(implemented_trait).map_or("?", |name| name);
+                format!(
+                    "{} implements {trait_name}",
+                    self.pattern_to_string(pattern)
+                )
+            }
+            NodeKind::StructPattern => {
+                let (implementee, fields) = self.node_pair(pattern);
+                let type_name = self.string(implementee).map_or("?", |name| name);
+                format!("{type_name} {}", self.pattern_to_string(fields))
+            }
+            NodeKind::Pair => {
+                let (key, value) = self.node_pair(pattern);
+                let key = self.pattern_to_string(key);
+                if value == NodeId::EMPTY {
+                    key
+                } else {
+                    format!("{key}: {}", self.pattern_to_string(value))
+                }
+            }
+            _ => "?".into(),
+        }
+    }
+
     /// Constructs a compile error at the given node.
     pub fn error(&self, node: NodeId, kind: LanguageErrorKind) -> LanguageError {
         LanguageError::Compile {
             module_name: Rc::clone(&self.module_name),
             kind,
-            location: self.location(node),
+            span: self.span(node),
+            snippet: None,
         }
     }
 }
@@ -143,8 +365,13 @@ pub struct NodeBuilder<'a> {
 impl<'a> NodeBuilder<'a> {
     /// Sets the location of the node.
     pub fn with_location(self, location: Location) -> Self {
+        self.with_span(Span::point(location))
+    }
+
+    /// Sets the span of the token the node was created from.
+    pub fn with_span(self, span: Span) -> Self {
         unsafe {
-            *self.ast.locations.get_unchecked_mut(self.node.0 as usize) = location;
+            *self.ast.spans.get_unchecked_mut(self.node.0 as usize) = span;
         }
         self
     }
@@ -240,6 +467,16 @@ pub enum NodeKind {
     List,
     /// A dict literal.
     Dict,
+    /// A list comprehension `[x * 2 for x in xs if x > 0]`. The LHS is the element, and the
+    /// children are the `for` and `if` clauses, in order.
+    ListComprehension,
+    /// A dict comprehension `[k: v for (k, v) in pairs]`. The LHS is a `Pair`, and the children
+    /// are the clauses like in `ListComprehension`.
+    DictComprehension,
+    /// A `for binding in iterable` clause of a comprehension.
+    ComprehensionFor,
+    /// An `if condition` clause of a comprehension.
+    ComprehensionIf,
     /// An n-tuple `(1, 2, 3)`.
     Tuple,
     /// A record `{ a: 1, b: 2 }`.
@@ -248,6 +485,9 @@ pub enum NodeKind {
     Pair,
     /// The `..` token at the end of a record.
     Rest,
+    /// A struct pattern `Type { a, b: c }`. The left-hand side is the type, and the right-hand side
+    /// is a record pattern listing the destructured fields.
+    StructPattern,
 
     /// Negation operator (prefix `-`).
     Negate,
@@ -259,6 +499,10 @@ pub enum NodeKind {
     Multiply,
     /// Division operator `/`.
     Divide,
+    /// Floor division operator `//`.
+    FloorDivide,
+    /// Modulo operator `%`.
+    Modulo,
 
     /// Boolean NOT `!`.
     Not,
@@ -278,11 +522,24 @@ pub enum NodeKind {
     LessEqual,
     /// Greater-than-or-equal-to operator `>=`.
     GreaterEqual,
+    /// Trait check `value implements Trait`. Also used as a pattern, which checks the trait before
+    /// destructuring the value with the pattern on its left.
+    Implements,
 
     /// Assignment operator `=`.
     Assign,
     /// Method call operator `.`.
     Dot,
+    /// Nil-safe method call operator `?.`, which skips the call if the receiver is `nil`.
+    NilSafeDot,
+    /// Cascaded method call `receiver..method(arguments)`, which evaluates to the receiver.
+    Cascade,
+    /// Subscript `receiver[index]`.
+    Index,
+    /// Slice `receiver[start..end]`, whose right-hand side is a `SliceBounds` node.
+    Slice,
+    /// The bounds `start..end` of a slice. Either bound may be empty.
+    SliceBounds,
     /// Field reference `@x`.
     Field,
 
@@ -290,6 +547,9 @@ pub enum NodeKind {
     Main,
     /// `let` expression.
     Let,
+    /// `const` expression. Same as `let`, except that the declared variables cannot be assigned
+    /// to afterwards.
+    Const,
     /// `do` expression.
     Do,
     /// `if..do..elif..else..end` expression.
@@ -302,33 +562,122 @@ pub enum NodeKind {
     While,
     /// `for` loop.
     For,
-    /// `break` expression.
+    /// `break` expression. LHS is the value, RHS is the label of the loop to break out of.
     Break,
+    /// `continue` expression. LHS is the label of the loop to continue.
+    Continue,
+    /// Loop label, `'name`.
+    Label,
+    /// Labeled loop. LHS is the label, RHS is the loop.
+    Labeled,
+    /// `try..catch..end` expression. LHS is the guarded `Do` block, RHS is the `Catch` block.
+    Try,
+    /// The `catch` block of a `try` expression. LHS is the pattern the error value is bound to, RHS
+    /// is the pattern the stack trace is bound to. Both may be empty.
+    Catch,
+    /// `raise` expression.
+    Raise,
+    /// `defer` expression. LHS is the expression run when the enclosing block is exited.
+    Defer,
 
     /// Function (item or anonymous.)
     Func,
     /// The head of a function - LHS is the name, RHS are the parameters.
     /// This is the LHS of `Func`.
     FunctionHead,
-    /// Function parameters.
+    /// Function parameters. The LHS is the function's kind, and the RHS is its visibility.
     Parameters,
     /// `static` keyword (the LHS of `Parameters`).
     Static,
     /// `constructor` keyword (the LHS of `Parameters`).
     Constructor,
+    /// `pub` keyword (the RHS of `Parameters`).
+    Pub,
+    /// `priv` keyword (the RHS of `Parameters`).
+    Priv,
+    /// A rest parameter `...name`, which collects all remaining arguments into a list. The LHS is
+    /// the parameter's name.
+    RestParameter,
     /// A function call.
     Call,
+    /// An argument `...list`, whose elements are spread into the arguments of a call. The LHS is
+    /// the spread list.
+    Spread,
     /// `return` expression.
     Return,
 
     /// A struct declaration.
     Struct,
+    /// An enum declaration. The children are the enum's variants and methods.
+    Enum,
     /// An `impl` block.
     Impl,
     /// A trait declaration.
     Trait,
     /// `as` block in an `impl`.
     ImplAs,
+    /// `import` of a module registered by the embedder. The left-hand side is the module's name,
+    /// which is also the name of the variable it's imported into.
+    Import,
+}
+
+/// Whether [`Ast::visit`] should descend into a node's children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+    /// Visit the node's children.
+    Children,
+    /// Skip the node's children and continue with its next sibling. The node is still left.
+    SkipChildren,
+}
+
+/// Receives the nodes of a syntax tree walked with [`Ast::visit`].
+///
+/// A node's children are its left and right nodes (unless empty), followed by its children data,
+/// which matches the order they appear in source code.
+///
+/// # Examples
+/// ```
+/// use mica::ll::{
+///     ast::{Ast, NodeId, NodeKind, Visit, Visitor},
+///     lexer::Lexer,
+///     parser::Parser,
+/// };
+///
+/// /// Collects the names of functions, without looking inside their bodies.
+/// struct FunctionNames(Vec<String>);
+///
+/// impl Visitor for FunctionNames {
+///     fn enter(&mut self, ast: &Ast, node: NodeId) -> Visit {
+///         if ast.kind(node) == NodeKind::Func {
+///             let (head, _) = ast.node_pair(node);
+///             let (name, _) = ast.node_pair(head);
+///             self.0.extend(ast.string(name).map(|name| name.to_string()));
+///             return Visit::SkipChildren;
+///         }
+///         Visit::Children
+///     }
+/// }
+///
+/// let source = "func a() = do func inner() = 1 end\nfunc b(x) = x";
+/// let (ast, root) = Parser::new(Lexer::new("example.mi".into(), source.into()))
+///     .parse()
+///     .unwrap();
+/// let mut names = FunctionNames(Vec::new());
+/// ast.visit(root, &mut names);
+/// assert_eq!(names.0, ["a", "b"]);
+/// ```
+pub trait Visitor {
+    /// Called when a node is entered, before any of its children. The return value decides
+    /// whether the children are visited.
+    fn enter(&mut self, ast: &Ast, node: NodeId) -> Visit {
+        let _ = (ast, node);
+        Visit::Children
+    }
+
+    /// Called when a node is left, after all of its children.
+    fn leave(&mut self, ast: &Ast, node: NodeId) {
+        let _ = (ast, node);
+    }
 }
 
 /// A `Debug` formatter that pretty-prints ASTs.
@@ -0,0 +1,456 @@
+//! Queries over syntax trees, for tools that need to know what a script does without running it.
+//!
+//! A [`Query`] resolves variables the same way the compiler does, so it can tell apart globals
+//! from local variables of the same name. This makes it possible to, for instance, check a script
+//! against an allow-list of functions and methods before it's executed:
+//!
+//! ```
+//! use std::rc::Rc;
+//!
+//! use mica::ll::{ast::query::Query, lexer::Lexer, parser::Parser};
+//!
+//! let source = r#"
+//!     func greet(name) = print("Hello, " + name.cat("!"))
+//!     greet("world")
+//! "#;
+//! let lexer = Lexer::new(Rc::from("greet.mi"), source.to_owned());
+//! let (ast, root_node) = Parser::new(lexer).parse().unwrap();
+//! let query = Query::new(&ast, root_node);
+//!
+//! let allowed_globals = ["print", "greet"];
+//! assert!(query
+//!     .globals_read()
+//!     .iter()
+//!     .all(|global| allowed_globals.contains(&&*global.name)));
+//! assert_eq!(&*query.method_calls()[0].name, "cat");
+//! ```
+
+use std::{collections::HashSet, rc::Rc};
+
+use super::{Ast, NodeId, NodeKind};
+use crate::ll::error::Location;
+
+/// What kind of function a [`FunctionItem`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionItemKind {
+    /// A bare function, either named or anonymous.
+    Function,
+    /// An instance method declared in an `impl` block.
+    Method,
+    /// A `static` method declared in an `impl` block.
+    StaticMethod,
+    /// A constructor declared in an `impl` block.
+    Constructor,
+    /// A method required by a trait.
+    TraitMethod,
+}
+
+/// A function declared in a script.
+#[derive(Debug, Clone)]
+pub struct FunctionItem {
+    /// The name of the function, or `None` if the function is anonymous.
+    pub name: Option<Rc<str>>,
+    pub kind: FunctionItemKind,
+    /// For methods, the name of the type or trait the method belongs to, if it's known.
+    /// Implementations for types that aren't referred to by name directly don't have this set.
+    pub owner: Option<Rc<str>>,
+    pub parameters: Vec<Rc<str>>,
+    /// The `Func` node the function was declared with.
+    pub node: NodeId,
+    pub location: Location,
+}
+
+/// A mention of a variable by name.
+#[derive(Debug, Clone)]
+pub struct VariableReference {
+    pub name: Rc<str>,
+    /// The identifier node referring to the variable.
+    pub node: NodeId,
+    pub location: Location,
+}
+
+/// A call to a method.
+#[derive(Debug, Clone)]
+pub struct MethodCall {
+    pub name: Rc<str>,
+    /// The number of arguments passed to the method, not counting the receiver.
+    pub argument_count: usize,
+    /// The identifier node naming the method.
+    pub node: NodeId,
+    pub location: Location,
+}
+
+/// The results of querying a syntax tree. All results are listed in source order.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    functions: Vec<FunctionItem>,
+    globals_read: Vec<VariableReference>,
+    globals_written: Vec<VariableReference>,
+    method_calls: Vec<MethodCall>,
+    imports: Vec<VariableReference>,
+}
+
+impl Query {
+    /// Queries the tree rooted at the given node.
+    pub fn new(ast: &Ast, root_node: NodeId) -> Self {
+        let mut resolver = Resolver {
+            ast,
+            scopes: Vec::new(),
+            query: Self::default(),
+        };
+        resolver.node(root_node);
+        resolver.query
+    }
+
+    /// Returns all functions declared in the tree, including methods and anonymous functions.
+    pub fn functions(&self) -> &[FunctionItem] {
+        &self.functions
+    }
+
+    /// Returns all reads of global variables, including calls to global functions.
+    pub fn globals_read(&self) -> &[VariableReference] {
+        &self.globals_read
+    }
+
+    /// Returns all writes to global variables. This includes declarations of global variables,
+    /// functions, structs, and traits, as well as assignments to existing globals.
+    pub fn globals_written(&self) -> &[VariableReference] {
+        &self.globals_written
+    }
+
+    /// Returns all method calls.
+    pub fn method_calls(&self) -> &[MethodCall] {
+        &self.method_calls
+    }
+
+    /// Returns all imports of modules. Each reference refers to the name of the imported module,
+    /// which also becomes a variable.
+    pub fn imports(&self) -> &[VariableReference] {
+        &self.imports
+    }
+}
+
+/// Walks a syntax tree keeping track of local variables, in a way that mirrors the scoping rules
+/// of the code generator.
+struct Resolver<'a> {
+    ast: &'a Ast,
+    scopes: Vec<HashSet<Rc<str>>>,
+    query: Query,
+}
+
+impl Resolver<'_> {
+    fn node(&mut self, node: NodeId) {
+        let ast = self.ast;
+        let (left, right) = ast.node_pair(node);
+        let children = ast.children(node).unwrap_or(&[]);
+        match ast.kind(node) {
+            NodeKind::Empty | NodeKind::Field => (),
+            NodeKind::Identifier => self.read(node),
+
+            NodeKind::Let | NodeKind::Const if ast.kind(left) == NodeKind::Assign => {
+                let (pattern, value) = ast.node_pair(left);
+                self.node(value);
+                self.declare_pattern(pattern);
+            }
+            NodeKind::Assign => {
+                self.node(right);
+                self.bind_pattern(left, Self::write);
+            }
+
+            NodeKind::Do | NodeKind::ElseBranch => self.scoped(|r| r.nodes(children)),
+            NodeKind::Defer => self.scoped(|r| r.node(left)),
+            NodeKind::IfBranch | NodeKind::While => self.scoped(|r| {
+                r.node(left);
+                r.nodes(children);
+            }),
+            NodeKind::And | NodeKind::Or => self.scoped(|r| {
+                r.node(left);
+                r.node(right);
+            }),
+            NodeKind::For => {
+                self.node(right);
+                self.scoped(|r| {
+                    r.declare_pattern(left);
+                    r.nodes(children);
+                });
+            }
+            NodeKind::ListComprehension | NodeKind::DictComprehension => self.scoped(|r| {
+                for &clause in children {
+                    let (left, right) = ast.node_pair(clause);
+                    match ast.kind(clause) {
+                        NodeKind::ComprehensionFor => {
+                            r.node(right);
+                            r.declare_pattern(left);
+                        }
+                        _ => r.node(left),
+                    }
+                }
+                r.node(left);
+            }),
+
+            NodeKind::Record => {
+                for &pair in children {
+                    let (key, value) = ast.node_pair(pair);
+                    match ast.kind(key) {
+                        NodeKind::Rest => (),
+                        // `{ x }` is shorthand for `{ x: x }`.
+                        _ if value == NodeId::EMPTY => self.read(key),
+                        _ => self.node(value),
+                    }
+                }
+            }
+
+            NodeKind::Dot | NodeKind::NilSafeDot => {
+                self.node(left);
+                self.method_call(right, 0);
+            }
+            NodeKind::Call if matches!(ast.kind(left), NodeKind::Dot | NodeKind::NilSafeDot) => {
+                let (receiver, name) = ast.node_pair(left);
+                self.node(receiver);
+                self.method_call(name, children.len());
+                self.nodes(children);
+            }
+
+            NodeKind::Cascade => {
+                self.node(left);
+                match ast.kind(right) {
+                    NodeKind::Call => {
+                        let (name, _) = ast.node_pair(right);
+                        let arguments = ast.children(right).unwrap_or(&[]);
+                        self.method_call(name, arguments.len());
+                        self.nodes(arguments);
+                    }
+                    _ => self.method_call(right, 0),
+                }
+            }
+
+            NodeKind::Func => self.function(node, FunctionItemKind::Function, None),
+            NodeKind::Struct => self.declare(left),
+            NodeKind::Import => {
+                if let Some(reference) = self.reference(left) {
+                    self.query.imports.push(reference);
+                }
+                self.declare(left);
+            }
+            NodeKind::Trait => {
+                self.declare(left);
+                let owner = ast.string(left).cloned();
+                for &item in children {
+                    if ast.kind(item) == NodeKind::Func {
+                        self.function(item, FunctionItemKind::TraitMethod, owner.clone());
+                    }
+                }
+            }
+            NodeKind::Enum => {
+                self.declare(left);
+                let owner = ast.string(left).cloned();
+                for &item in children {
+                    // Variants only name the values they carry, so there's nothing to resolve in
+                    // them.
+                    if !matches!(ast.kind(item), NodeKind::Identifier | NodeKind::Call) {
+                        self.impl_item(item, &owner);
+                    }
+                }
+            }
+            NodeKind::Impl => {
+                self.node(left);
+                let owner = match ast.kind(left) {
+                    NodeKind::Identifier => ast.string(left).cloned(),
+                    // `struct S impl ... end`
+                    NodeKind::Struct => ast.string(ast.node_pair(left).0).cloned(),
+                    _ => None,
+                };
+                for &item in children {
+                    self.impl_item(item, &owner);
+                }
+            }
+
+            _ => {
+                self.node(left);
+                self.node(right);
+                self.nodes(children);
+            }
+        }
+    }
+
+    fn nodes(&mut self, nodes: &[NodeId]) {
+        for &node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashSet::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn impl_item(&mut self, item: NodeId, owner: &Option<Rc<str>>) {
+        let ast = self.ast;
+        match ast.kind(item) {
+            NodeKind::Func => {
+                let (head, _) = ast.node_pair(item);
+                let (_, parameters) = ast.node_pair(head);
+                let (function_kind, _) = ast.node_pair(parameters);
+                let kind = match ast.kind(function_kind) {
+                    NodeKind::Static => FunctionItemKind::StaticMethod,
+                    NodeKind::Constructor => FunctionItemKind::Constructor,
+                    _ => FunctionItemKind::Method,
+                };
+                self.function(item, kind, owner.clone());
+            }
+            NodeKind::ImplAs => {
+                let (implemented_trait, _) = ast.node_pair(item);
+                self.node(implemented_trait);
+                for &item in ast.children(item).unwrap_or(&[]) {
+                    self.impl_item(item, owner);
+                }
+            }
+            _ => self.node(item),
+        }
+    }
+
+    fn function(&mut self, node: NodeId, kind: FunctionItemKind, owner: Option<Rc<str>>) {
+        let ast = self.ast;
+        let (head, body) = ast.node_pair(node);
+        let (name, parameters) = ast.node_pair(head);
+        let parameters = ast.children(parameters).unwrap_or(&[]);
+        if kind == FunctionItemKind::Function && name != NodeId::EMPTY {
+            // Named functions are declared before their bodies, such that they can recurse.
+            self.declare(name);
+        }
+        self.query.functions.push(FunctionItem {
+            name: ast.string(name).cloned(),
+            kind,
+            owner,
+            parameters: parameters
+                .iter()
+                .map(|&parameter| match ast.string(parameter) {
+                    Some(name) => Rc::clone(name),
+                    None => Rc::from(ast.pattern_to_string(parameter)),
+                })
+                .collect(),
+            node,
+            location: ast.location(node),
+        });
+        if body != NodeId::EMPTY {
+            self.scoped(|r| {
+                if kind != FunctionItemKind::Function {
+                    r.scopes.last_mut().unwrap().insert(Rc::from("self"));
+                }
+                for &parameter in parameters {
+                    r.declare_pattern(parameter);
+                }
+                r.node(body);
+            });
+        }
+    }
+
+    fn declare_pattern(&mut self, pattern: NodeId) {
+        self.bind_pattern(pattern, Self::declare);
+    }
+
+    /// Calls `bind` with each of the identifiers a pattern binds to, visiting the expressions
+    /// inside of it along the way.
+    fn bind_pattern(&mut self, pattern: NodeId, bind: fn(&mut Self, NodeId)) {
+        let ast = self.ast;
+        match ast.kind(pattern) {
+            NodeKind::Identifier => bind(self, pattern),
+            NodeKind::Tuple => {
+                for &element in ast.children(pattern).unwrap_or(&[]) {
+                    self.bind_pattern(element, bind);
+                }
+            }
+            NodeKind::Record => {
+                for &pair in ast.children(pattern).unwrap_or(&[]) {
+                    let (key, value) = ast.node_pair(pair);
+                    match ast.kind(key) {
+                        NodeKind::Rest => (),
+                        _ if value == NodeId::EMPTY => bind(self, key),
+                        _ => self.bind_pattern(value, bind),
+                    }
+                }
+            }
+            NodeKind::StructPattern => {
+                let (implementee, fields) = ast.node_pair(pattern);
+                self.node(implementee);
+                self.bind_pattern(fields, bind);
+            }
+            NodeKind::Implements => {
+                let (inner, implemented_trait) = ast.node_pair(pattern);
+                self.node(implemented_trait);
+                self.bind_pattern(inner, bind);
+            }
+            NodeKind::RestParameter => self.bind_pattern(ast.node_pair(pattern).0, bind),
+            // Variant patterns `Enum.Variant(a, b)`.
+            NodeKind::Dot => self.node(ast.node_pair(pattern).0),
+            NodeKind::Call => {
+                self.bind_pattern(ast.node_pair(pattern).0, bind);
+                for &value in ast.children(pattern).unwrap_or(&[]) {
+                    self.bind_pattern(value, bind);
+                }
+            }
+            // Assigning to a subscript only reads the variables used in it.
+            NodeKind::Index | NodeKind::Slice => self.node(pattern),
+            _ => (),
+        }
+    }
+
+    fn reference(&self, node: NodeId) -> Option<VariableReference> {
+        Some(VariableReference {
+            name: Rc::clone(self.ast.string(node)?),
+            node,
+            location: self.ast.location(node),
+        })
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    /// Declares a variable. Outside of any scope, this declares a global.
+    fn declare(&mut self, node: NodeId) {
+        let Some(reference) = self.reference(node) else {
+            return;
+        };
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(reference.name);
+            }
+            None => self.query.globals_written.push(reference),
+        }
+    }
+
+    fn read(&mut self, node: NodeId) {
+        if let Some(reference) = self.reference(node) {
+            if !self.is_local(&reference.name) {
+                self.query.globals_read.push(reference);
+            }
+        }
+    }
+
+    fn write(&mut self, node: NodeId) {
+        if let Some(reference) = self.reference(node) {
+            if !self.is_local(&reference.name) {
+                self.query.globals_written.push(reference);
+            }
+        }
+    }
+
+    fn method_call(&mut self, name: NodeId, argument_count: usize) {
+        // Methods called by `receiver.(name)` aren't known until runtime, but the expression
+        // computing the name may still refer to variables.
+        if self.ast.kind(name) == NodeKind::Paren {
+            self.node(name);
+            return;
+        }
+        if let Some(method_name) = self.ast.string(name) {
+            self.query.method_calls.push(MethodCall {
+                name: Rc::clone(method_name),
+                argument_count,
+                node: name,
+                location: self.ast.location(name),
+            });
+        }
+    }
+}
@@ -6,10 +6,11 @@ mod environment;
 mod function;
 mod impls;
 mod library;
+mod limits;
 mod opcode;
 mod opr24;
 
 pub use self::{
-    chunk::*, dispatch_table::*, environment::*, function::*, impls::*, library::*, opcode::*,
-    opr24::*,
+    chunk::*, dispatch_table::*, environment::*, function::*, impls::*, library::*, limits::*,
+    opcode::*, opr24::*,
 };
@@ -1,22 +1,50 @@
 //! Chunks of bytecode.
 
-use std::{fmt, mem::size_of, rc::Rc};
+use std::{cell::Cell, fmt, mem::size_of, ops::Range, rc::Rc};
 
-use super::{EncodeInstruction, Opcode, Opr24};
+use super::{EncodeInstruction, JumpTooFar, Opcode, Opr24, Opr24OutOfRange};
 use crate::ll::error::Location;
 
 /// A chunk of bytecode.
 pub struct Chunk {
     /// The name of the module where the chunk is located.
     pub module_name: Rc<str>,
-    /// The actual bytecode.
-    bytes: Vec<u8>,
+    /// The actual bytecode. Opcodes can be [quickened][Self::quicken] while the chunk is running,
+    /// which is why the bytes are in cells.
+    bytes: Vec<Cell<u8>>,
     /// Locations. These are placed at multiples of four bytes (Opcode::INSTRUCTION_SIZE).
     locations: Vec<Location>,
+    /// Offsets of jumps that are too far to be encoded in an instruction's operand.
+    long_jumps: Vec<usize>,
     /// The location emitted for each quad-byte on calls to `push`.
     pub codegen_location: Location,
     /// How many stack slots to preallocate with `nil` values for variable lookups.
     pub preallocate_stack_slots: u32,
+    /// Local variables declared in the chunk, for debuggers.
+    local_variables: Vec<LocalVariableInfo>,
+    /// The names of the variables captured by the closure the chunk belongs to, in the order of
+    /// their upvalue indices.
+    pub upvalue_names: Vec<Rc<str>>,
+}
+
+/// Debug information about a local variable: its name, stack slot, and the range of the bytecode
+/// in which it's in scope.
+#[derive(Debug, Clone)]
+pub struct LocalVariableInfo {
+    /// The name of the variable.
+    pub name: Rc<str>,
+    /// The stack slot the variable is stored in, relative to the bottom of the call frame.
+    pub stack_slot: u32,
+    /// The range of program counters at which the variable is in scope.
+    pub scope: Range<usize>,
+}
+
+/// The length of a chunk and its tables at some point during code generation.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChunkCheckpoint {
+    len: usize,
+    long_jumps: usize,
+    local_variables: usize,
 }
 
 impl Chunk {
@@ -26,16 +54,18 @@ impl Chunk {
             module_name,
             bytes: Vec::new(),
             locations: Vec::new(),
+            long_jumps: Vec::new(),
             codegen_location: Location::UNINIT,
             preallocate_stack_slots: 0,
+            local_variables: Vec::new(),
+            upvalue_names: Vec::new(),
         }
     }
 
     /// Pushes an encodable piece of data into the chunk. Returns where it's located.
     pub fn emit(&mut self, instruction: impl EncodeInstruction) -> usize {
         let position = self.bytes.len();
-        self.bytes
-            .extend_from_slice(&instruction.encode_instruction());
+        self.extend_bytes(&instruction.encode_instruction());
         // Only push one location, since all encoded instructions are 4 bytes long.
         self.locations.push(self.codegen_location);
         position
@@ -43,8 +73,7 @@ impl Chunk {
 
     /// Pushes a number into the chunk.
     pub fn emit_number(&mut self, number: f64) {
-        let bytes = number.to_le_bytes();
-        self.bytes.extend_from_slice(&bytes);
+        self.extend_bytes(&number.to_le_bytes());
         // 8 bytes, so push twice.
         self.locations.push(self.codegen_location);
         self.locations.push(self.codegen_location);
@@ -52,8 +81,7 @@ impl Chunk {
 
     /// Emits a concrete `u32`.
     pub fn emit_u32(&mut self, x: u32) {
-        let bytes = x.to_le_bytes();
-        self.bytes.extend_from_slice(&bytes);
+        self.extend_bytes(&x.to_le_bytes());
         self.locations.push(self.codegen_location);
     }
 
@@ -66,13 +94,12 @@ impl Chunk {
         // I don't know of any 128-bit targets so this cast should be OK. Also, it isn't physically
         // possible to store a string as large as 2^64 bytes.
         let len = string.len() as u64;
-        let len_bytes = len.to_le_bytes();
-        self.bytes.extend_from_slice(&len_bytes);
-        self.bytes.extend(string.as_bytes());
+        self.extend_bytes(&len.to_le_bytes());
+        self.extend_bytes(string.as_bytes());
         let padded_len = (string.len() + 3) & !3;
         let padding = padded_len - string.len();
         for _ in 0..padding {
-            self.bytes.push(0);
+            self.bytes.push(Cell::new(0));
         }
 
         let end = self.len();
@@ -81,24 +108,136 @@ impl Chunk {
         }
     }
 
+    /// Appends raw bytes to the chunk.
+    fn extend_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend(bytes.iter().copied().map(Cell::new));
+    }
+
     /// Patches the instruction at the given position.
     pub fn patch(&mut self, position: usize, instruction: impl EncodeInstruction) {
         let bytes = instruction.encode_instruction();
-        self.bytes[position..position + Opcode::INSTRUCTION_SIZE].copy_from_slice(&bytes);
+        let cells = &mut self.bytes[position..position + Opcode::INSTRUCTION_SIZE];
+        for (cell, byte) in cells.iter_mut().zip(bytes) {
+            *cell.get_mut() = byte;
+        }
+    }
+
+    /// Adds `offset` to the function index of every `CreateClosure` instruction, for when the
+    /// functions the chunk creates are moved to a different environment. Returns an error if one
+    /// of the new indices doesn't fit in an operand.
+    pub(crate) fn offset_closures(&mut self, offset: usize) -> Result<(), Opr24OutOfRange> {
+        let closures: Vec<_> = self
+            .instructions()
+            .filter(|instruction| instruction.opcode == Opcode::CreateClosure)
+            .map(|instruction| match instruction.operands {
+                Operands::Opr24(function) => (instruction.offset, usize::from(function)),
+                _ => unreachable!("CreateClosure only has an Opr24 operand"),
+            })
+            .collect();
+        for (position, function) in closures {
+            let function = Opr24::try_from(function + offset)?;
+            self.patch(position, (Opcode::CreateClosure, function));
+        }
+        Ok(())
+    }
+
+    /// Replaces the opcode of the instruction at the given position with a superinstruction,
+    /// keeping its operand and any data stored after it. Nothing is replaced if the instruction
+    /// isn't the one the superinstruction is meant to replace. Returns whether it was replaced.
+    pub(crate) fn fuse(&mut self, position: usize, replaced: Opcode, fused: Opcode) -> bool {
+        let opcode = self.bytes[position].get_mut();
+        let matches = *opcode == replaced as u8;
+        if matches {
+            *opcode = fused as u8;
+        }
+        matches
+    }
+
+    /// Replaces the opcode of the instruction at the given position with its quickened or generic
+    /// variant. Unlike [`fuse`][Self::fuse], this can be done while the chunk is running.
+    ///
+    /// # Safety
+    /// Assumes that `position` is the position of an instruction within the chunk's bounds, and
+    /// that `opcode` has the same [generic variant][Opcode::generic] as the instruction's opcode.
+    pub(crate) unsafe fn quicken(&self, position: usize, opcode: Opcode) {
+        self.bytes.get_unchecked(position).set(opcode as u8);
+    }
+
+    /// Returns whether the instruction at the given position has the given opcode.
+    pub(crate) fn is_opcode_at(&self, position: usize, opcode: Opcode) -> bool {
+        self.bytes.get(position).map(Cell::get) == Some(opcode as u8)
+    }
+
+    /// Encodes a jump with the given opcode and offset. If the offset does not fit in an `Opr24`,
+    /// the jump is turned into its long variant, whose offset is stored in the chunk's long jump
+    /// table.
+    fn encode_jump(
+        &mut self,
+        opcode: Opcode,
+        offset: usize,
+    ) -> Result<(Opcode, Opr24), JumpTooFar> {
+        if let Ok(offset) = Opr24::try_from(offset) {
+            Ok((opcode, offset))
+        } else {
+            let index = Opr24::try_from(self.long_jumps.len()).map_err(|_| JumpTooFar(()))?;
+            self.long_jumps.push(offset);
+            Ok((opcode.long_jump(), index))
+        }
+    }
+
+    /// Constructs a `JumpForward` instruction.
+    pub fn jump_forward(&mut self, from: usize, to: usize) -> Result<(Opcode, Opr24), JumpTooFar> {
+        self.encode_jump(Opcode::JumpForward, Opcode::forward_jump_offset(from, to))
+    }
+
+    /// Constructs a `JumpForwardIfFalsy` instruction.
+    pub fn jump_forward_if_falsy(
+        &mut self,
+        from: usize,
+        to: usize,
+    ) -> Result<(Opcode, Opr24), JumpTooFar> {
+        self.encode_jump(
+            Opcode::JumpForwardIfFalsy,
+            Opcode::forward_jump_offset(from, to),
+        )
+    }
+
+    /// Constructs a `JumpForwardIfTruthy` instruction.
+    pub fn jump_forward_if_truthy(
+        &mut self,
+        from: usize,
+        to: usize,
+    ) -> Result<(Opcode, Opr24), JumpTooFar> {
+        self.encode_jump(
+            Opcode::JumpForwardIfTruthy,
+            Opcode::forward_jump_offset(from, to),
+        )
+    }
+
+    /// Constructs a `JumpBackward` instruction.
+    pub fn jump_backward(&mut self, from: usize, to: usize) -> Result<(Opcode, Opr24), JumpTooFar> {
+        self.encode_jump(Opcode::JumpBackward, Opcode::backward_jump_offset(from, to))
+    }
+
+    /// Returns the offset of the long jump with the given index.
+    ///
+    /// # Safety
+    /// Assumes the index was produced by the chunk's own jump constructors.
+    pub unsafe fn long_jump_offset(&self, index: Opr24) -> usize {
+        *self.long_jumps.get_unchecked(usize::from(index))
     }
 
     /// Reads an instruction.
     ///
+    /// The instruction is loaded as a single little-endian `u32`, out of which the opcode and
+    /// operand are extracted with shifts, so decoding doesn't branch.
+    ///
     /// # Safety
     /// Assumes that `pc` is within the chunk's bounds and that the opcode at `pc` is valid.
     pub unsafe fn read_instruction(&self, pc: &mut usize) -> (Opcode, Opr24) {
-        let bytes = &self.bytes[*pc..*pc + Opcode::INSTRUCTION_SIZE];
-        let mut bytes = <[u8; Opcode::INSTRUCTION_SIZE]>::try_from(bytes).unwrap();
-        let opcode: Opcode = std::mem::transmute(bytes[0]);
-        bytes[0] = 0;
-        let operand = Opr24 {
-            bytes: [bytes[1], bytes[2], bytes[3]],
-        };
+        let word = u32::from_le_bytes(self.read_array(*pc));
+        let opcode: Opcode = std::mem::transmute(word as u8);
+        let operand = Opr24::from_u32_truncated(word >> 8);
         *pc += Opcode::INSTRUCTION_SIZE;
         (opcode, operand)
     }
@@ -108,24 +247,41 @@ impl Chunk {
     /// # Safety
     /// Assumes that `pc` is within the chunk's bounds, skipping any checks.
     pub unsafe fn read_number(&self, pc: &mut usize) -> f64 {
-        const SIZE: usize = std::mem::size_of::<f64>();
-        let bytes = &self.bytes[*pc..*pc + SIZE];
-        let bytes: [u8; SIZE] = bytes.try_into().unwrap();
-        let number = f64::from_le_bytes(bytes);
-        *pc += SIZE;
+        let number = f64::from_le_bytes(self.read_array(*pc));
+        *pc += size_of::<f64>();
         number
     }
 
+    /// Reads `N` bytes starting at `position`. Bounds are only checked in debug builds and with
+    /// the `forbid-unsafe` feature.
+    ///
+    /// # Safety
+    /// Assumes that the bytes are within the chunk's bounds.
+    #[inline(always)]
+    unsafe fn read_array<const N: usize>(&self, position: usize) -> [u8; N] {
+        #[cfg(any(debug_assertions, feature = "forbid-unsafe"))]
+        {
+            let bytes = &self.bytes[position..position + N];
+            std::array::from_fn(|i| bytes[i].get())
+        }
+        // `Cell<u8>` has the same in-memory representation as `u8`.
+        #[cfg(not(any(debug_assertions, feature = "forbid-unsafe")))]
+        {
+            self.bytes
+                .as_ptr()
+                .add(position)
+                .cast::<[u8; N]>()
+                .read_unaligned()
+        }
+    }
+
     /// Reads a concrete `u32`.
     ///
     /// # Safety
     /// Assumes that `pc` is within the chunk's bounds, skipping any checks.
     pub unsafe fn read_u32(&self, pc: &mut usize) -> u32 {
-        const SIZE: usize = std::mem::size_of::<u32>();
-        let bytes = &self.bytes[*pc..*pc + SIZE];
-        let bytes: [u8; SIZE] = bytes.try_into().unwrap();
-        let x = u32::from_le_bytes(bytes);
-        *pc += SIZE;
+        let x = u32::from_le_bytes(self.read_array(*pc));
+        *pc += size_of::<u32>();
         x
     }
 
@@ -135,15 +291,27 @@ impl Chunk {
     /// This assumes the original string was encoded as proper UTF-8 (which it should
     /// have been considering the only way to write a string is to use a `&str` in the first place).
     pub unsafe fn read_string(&self, pc: &mut usize) -> &str {
-        let len_bytes: [u8; 8] = self.bytes[*pc..*pc + size_of::<u64>()].try_into().unwrap();
-        let len = u64::from_le_bytes(len_bytes);
+        let len = u64::from_le_bytes(self.read_array(*pc));
         *pc += size_of::<u64>();
         let string = &self.bytes[*pc..*pc + len as usize];
+        // Only opcodes are ever modified after the chunk is generated, so the bytes of the string
+        // stay the same for as long as it's borrowed.
+        let string = std::slice::from_raw_parts(string.as_ptr().cast::<u8>(), string.len());
         let padded_len = (len + 3) & !3;
         *pc += padded_len as usize;
         std::str::from_utf8_unchecked(string)
     }
 
+    /// Returns the bytecode of the chunk, as it was generated. Quickened instructions are
+    /// reverted to their generic variants, so the bytes don't change as the chunk runs.
+    pub(crate) fn bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<_> = self.bytes.iter().map(Cell::get).collect();
+        for instruction in self.instructions() {
+            bytes[instruction.offset] = instruction.opcode.generic() as u8;
+        }
+        bytes
+    }
+
     /// Returns the length of the chunk (in bytes).
     pub fn len(&self) -> usize {
         self.bytes.len()
@@ -154,6 +322,24 @@ impl Chunk {
         self.len() == 0
     }
 
+    /// Returns a checkpoint that the chunk can be [rolled back][Self::rollback] to later.
+    pub(crate) fn checkpoint(&self) -> ChunkCheckpoint {
+        ChunkCheckpoint {
+            len: self.bytes.len(),
+            long_jumps: self.long_jumps.len(),
+            local_variables: self.local_variables.len(),
+        }
+    }
+
+    /// Removes everything that was emitted into the chunk after the checkpoint was taken.
+    pub(crate) fn rollback(&mut self, checkpoint: ChunkCheckpoint) {
+        self.bytes.truncate(checkpoint.len);
+        self.locations
+            .truncate(checkpoint.len / Opcode::INSTRUCTION_SIZE);
+        self.long_jumps.truncate(checkpoint.long_jumps);
+        self.local_variables.truncate(checkpoint.local_variables);
+    }
+
     /// Returns the location (in file) of the program counter.
     pub fn location(&self, pc: usize) -> Location {
         let index = pc >> 2;
@@ -163,6 +349,33 @@ impl Chunk {
             .unwrap_or(Location::UNINIT)
     }
 
+    /// Records that a local variable comes into scope at the current end of the chunk. Returns an
+    /// index to be passed to [`end_local_variable`][Self::end_local_variable] once the variable
+    /// goes out of scope.
+    pub fn begin_local_variable(&mut self, name: Rc<str>, stack_slot: u32) -> usize {
+        let index = self.local_variables.len();
+        self.local_variables.push(LocalVariableInfo {
+            name,
+            stack_slot,
+            scope: self.len()..usize::MAX,
+        });
+        index
+    }
+
+    /// Records that a local variable goes out of scope at the current end of the chunk.
+    pub fn end_local_variable(&mut self, index: usize) {
+        let end = self.len();
+        let scope = &mut self.local_variables[index].scope;
+        scope.end = scope.end.min(end);
+    }
+
+    /// Returns the local variables that are in scope at the given program counter.
+    pub fn local_variables_at(&self, pc: usize) -> impl Iterator<Item = &LocalVariableInfo> {
+        self.local_variables
+            .iter()
+            .filter(move |variable| variable.scope.contains(&pc))
+    }
+
     /// Returns whether the given program counter is at the end of the chunk.
     pub fn at_end(&self, pc: usize) -> bool {
         pc >= self.bytes.len()
@@ -176,55 +389,174 @@ impl fmt::Debug for Chunk {
             .field("preallocate_stack_slots", &self.preallocate_stack_slots)
             .finish()?;
         writeln!(f)?;
+        for instruction in self.instructions() {
+            writeln!(f, "{instruction}")?;
+        }
+        Ok(())
+    }
+}
 
-        let mut pc = 0;
-        while !self.at_end(pc) {
-            let location = self.location(pc);
-            let show_pc = pc;
-            let (opcode, operand) = unsafe { self.read_instruction(&mut pc) };
-            write!(f, "{show_pc:06x} {location} {opcode:?}({operand:?}) ")?;
-
-            match opcode {
-                Opcode::PushNumber => write!(f, "{}", unsafe { self.read_number(&mut pc) })?,
-                Opcode::PushString | Opcode::CreateType => {
-                    write!(f, "{:?}", unsafe { self.read_string(&mut pc) })?
-                }
-                Opcode::CreateRecord => {
-                    f.write_str("{ ")?;
-                    let mut i = 0;
-                    while let field_index @ ..=0xFFFF_FFFE = unsafe { self.read_u32(&mut pc) } {
-                        if i > 0 {
-                            f.write_str(", ")?;
-                        }
-                        write!(f, "{field_index}")?;
-                        i += 1;
-                    }
-                    f.write_str(" }")?;
-                }
-                Opcode::JumpForward | Opcode::JumpForwardIfFalsy | Opcode::JumpForwardIfTruthy => {
-                    write!(
-                        f,
-                        "-> {:06x}",
-                        pc + u32::from(operand) as usize + Opcode::INSTRUCTION_SIZE
-                    )?;
-                }
-                Opcode::JumpBackward => {
-                    write!(
-                        f,
-                        "-> {:06x}",
-                        pc - u32::from(operand) as usize + Opcode::INSTRUCTION_SIZE
-                    )?;
+/// An instruction decoded from a chunk's bytecode, along with any data stored after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction<'c> {
+    /// The offset of the instruction in the chunk's bytecode.
+    pub offset: usize,
+    /// The instruction's opcode.
+    pub opcode: Opcode,
+    /// The instruction's operands.
+    pub operands: Operands<'c>,
+    /// Where in the source code the instruction was generated from.
+    pub location: Location,
+}
+
+/// The operands of an [`Instruction`].
+///
+/// Every instruction has a 24-bit operand. Instructions that don't use it have it set to zero.
+/// Some instructions store extra data after them, in which case it's decoded too, and operands
+/// that encode something other than a plain number are unpacked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operands<'c> {
+    /// The instruction only has its 24-bit operand.
+    Opr24(Opr24),
+    /// A `PushNumber` and the number it pushes.
+    Number(f64),
+    /// An instruction followed by a string, such as a `CreateType`.
+    String(Opr24, &'c str),
+    /// A `CreateRecord`'s record type index and the indices of the fields the record is
+    /// created from.
+    Record(Opr24, Vec<u32>),
+    /// The offset in the chunk a jump leads to. Long jumps are resolved through the chunk's long
+    /// jump table.
+    Jump(usize),
+    /// A `CallMethod`'s method index and argument count.
+    Method { index: u16, argument_count: u8 },
+}
+
+impl Chunk {
+    /// Returns an iterator over the instructions in the chunk, in order.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::ll::bytecode::{Chunk, Opcode, Operands, Opr24};
+    ///
+    /// let mut chunk = Chunk::new("example.mi".into());
+    /// chunk.emit(Opcode::PushNil);
+    /// chunk.emit(Opcode::PushNumber);
+    /// chunk.emit_number(4.0);
+    /// chunk.emit(Opcode::Return);
+    ///
+    /// let instructions: Vec<_> = chunk.instructions().collect();
+    /// assert_eq!(instructions[1].offset, 4);
+    /// assert_eq!(instructions[1].operands, Operands::Number(4.0));
+    /// assert_eq!(instructions[2].offset, 16);
+    /// assert_eq!(instructions[2].opcode, Opcode::Return);
+    /// ```
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions { chunk: self, pc: 0 }
+    }
+}
+
+/// An iterator over the instructions in a chunk. Returned by [`Chunk::instructions`].
+#[derive(Debug, Clone)]
+pub struct Instructions<'c> {
+    chunk: &'c Chunk,
+    pc: usize,
+}
+
+impl<'c> Iterator for Instructions<'c> {
+    type Item = Instruction<'c>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunk;
+        if chunk.at_end(self.pc) {
+            return None;
+        }
+        let offset = self.pc;
+        let location = chunk.location(offset);
+        // SAFETY: Chunks are only ever filled with complete instructions, so as long as the
+        // instruction's data is skipped over, the program counter lands at the next opcode.
+        let (opcode, operand) = unsafe { chunk.read_instruction(&mut self.pc) };
+        // Quickening is an implementation detail of the VM, so instructions are listed the way
+        // they were generated.
+        let opcode = opcode.generic();
+        // Jumps are relative to the end of the jump instruction.
+        let pc = self.pc;
+        let operands = match opcode {
+            Opcode::PushNumber | Opcode::LessNumberJump | Opcode::LessEqualNumberJump => {
+                Operands::Number(unsafe { chunk.read_number(&mut self.pc) })
+            }
+            Opcode::CreateType | Opcode::DestructureVariant | Opcode::MatchesVariant => {
+                Operands::String(operand, unsafe { chunk.read_string(&mut self.pc) })
+            }
+            Opcode::CreateRecord => {
+                let mut fields = Vec::new();
+                while let field_index @ ..=0xFFFF_FFFE = unsafe { chunk.read_u32(&mut self.pc) } {
+                    fields.push(field_index);
                 }
-                Opcode::CallMethod => {
-                    let (method_index, argument_count) = operand.unpack();
-                    let operand = u32::from(operand);
-                    write!(f, "{operand:06x}:[mi={method_index}, ac={argument_count}]")?;
+                Operands::Record(operand, fields)
+            }
+            Opcode::JumpForward | Opcode::JumpForwardIfFalsy | Opcode::JumpForwardIfTruthy => {
+                Operands::Jump(pc + usize::from(operand))
+            }
+            Opcode::JumpBackward => Operands::Jump(pc - usize::from(operand)),
+            Opcode::JumpForwardLong
+            | Opcode::JumpForwardIfFalsyLong
+            | Opcode::JumpForwardIfTruthyLong => {
+                let offset = unsafe { chunk.long_jump_offset(operand) };
+                Operands::Jump(pc + offset)
+            }
+            Opcode::JumpBackwardLong => {
+                let offset = unsafe { chunk.long_jump_offset(operand) };
+                Operands::Jump(pc - offset)
+            }
+            Opcode::CallMethod => {
+                let (index, argument_count) = operand.unpack();
+                Operands::Method {
+                    index,
+                    argument_count,
                 }
-                _ => (),
             }
+            _ => Operands::Opr24(operand),
+        };
+        Some(Instruction {
+            offset,
+            opcode,
+            operands,
+            location,
+        })
+    }
+}
 
-            writeln!(f)?;
+/// Formats the instruction the way chunks' [`Debug`][fmt::Debug] output lists them: the offset in
+/// hexadecimal, the source location, the opcode, and its operands.
+impl fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            offset,
+            opcode,
+            location,
+            ..
+        } = self;
+        write!(f, "{offset:06x} {location} {opcode:?}")?;
+        match &self.operands {
+            Operands::Opr24(operand) => write!(f, "({operand:?})"),
+            Operands::Number(number) => write!(f, " {number}"),
+            Operands::String(operand, string) => write!(f, "({operand:?}) {string:?}"),
+            Operands::Record(operand, fields) => {
+                write!(f, "({operand:?}) {{ ")?;
+                for (i, field_index) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{field_index}")?;
+                }
+                f.write_str(" }")
+            }
+            Operands::Jump(target) => write!(f, " -> {target:06x}"),
+            Operands::Method {
+                index,
+                argument_count,
+            } => write!(f, " [mi={index}, ac={argument_count}]"),
         }
-        Ok(())
     }
 }
@@ -1,7 +1,19 @@
-use std::{fmt::Debug, rc::Rc};
+use std::{
+    cell::{Cell, UnsafeCell},
+    fmt::Debug,
+    rc::Rc,
+};
 
-use super::MethodIndex;
-use crate::ll::{gc::GcRaw, value::Closure};
+use super::{MethodIndex, TraitPrototype};
+use crate::ll::{
+    gc::GcRaw,
+    value::{Closure, RawValue},
+};
+
+/// The name of the hidden field holding the name of an enum instance's variant.
+pub(crate) const VARIANT_FIELD: &str = "<variant>";
+/// The name of the hidden field holding the tuple of values carried by an enum instance.
+pub(crate) const VALUES_FIELD: &str = "<values>";
 
 /// A dispatch table containing functions bound to an instance of a value.
 #[derive(Debug)]
@@ -11,8 +23,15 @@ pub struct DispatchTable {
     pub type_name: Rc<str>,
     /// The "child" dispatch table that holds instance methods.
     pub instance: Option<GcRaw<DispatchTable>>,
-    /// The functions in this dispatch table.
-    methods: Vec<Option<GcRaw<Closure>>>,
+    /// The names of the fields of struct instances using this dispatch table, ordered by field
+    /// index. Struct patterns use this to read fields directly instead of calling getters.
+    pub fields: Vec<Rc<str>>,
+    /// The value representing the type, if this is an instance dispatch table. This is what
+    /// `type_of` returns for instances.
+    type_value: Cell<Option<RawValue>>,
+    /// The functions in this dispatch table. These are behind an `UnsafeCell` such that built-in
+    /// types can be extended by scripts, whose dispatch tables are shared with the library.
+    methods: UnsafeCell<Vec<Option<GcRaw<Closure>>>>,
 }
 
 impl DispatchTable {
@@ -22,7 +41,9 @@ impl DispatchTable {
             pretty_name: pretty_name.into(),
             type_name: type_name.into(),
             instance: None,
-            methods: Vec::new(),
+            fields: Vec::new(),
+            type_value: Cell::new(None),
+            methods: UnsafeCell::new(Vec::new()),
         }
     }
 
@@ -38,9 +59,19 @@ impl DispatchTable {
         Self::new(Rc::clone(&type_name), type_name)
     }
 
+    /// Returns the value representing the type of instances using this dispatch table.
+    pub(crate) fn type_value(&self) -> Option<RawValue> {
+        self.type_value.get()
+    }
+
+    /// Sets the value representing the type of instances using this dispatch table.
+    pub(crate) fn set_type_value(&self, value: RawValue) {
+        self.type_value.set(Some(value));
+    }
+
     /// Returns a reference to the method at the given index.
     pub fn get_method(&self, index: MethodIndex) -> Option<GcRaw<Closure>> {
-        self.methods
+        self.method_slots()
             .get(index.to_usize())
             .into_iter()
             .flatten()
@@ -50,15 +81,64 @@ impl DispatchTable {
 
     /// Adds a method into the dispatch table.
     pub fn set_method(&mut self, index: MethodIndex, closure: GcRaw<Closure>) {
+        Self::insert_method(self.methods.get_mut(), index, closure);
+    }
+
+    /// Adds a method into a dispatch table that may be shared.
+    ///
+    /// # Safety
+    /// No references to the dispatch table's methods (such as iterators returned by
+    /// [`DispatchTable::methods`]) may be alive while the method is being added.
+    pub(crate) unsafe fn extend_method(&self, index: MethodIndex, closure: GcRaw<Closure>) {
+        Self::insert_method(&mut *self.methods.get(), index, closure);
+    }
+
+    fn insert_method(
+        methods: &mut Vec<Option<GcRaw<Closure>>>,
+        index: MethodIndex,
+        closure: GcRaw<Closure>,
+    ) {
         let index = index.to_usize();
-        if index >= self.methods.len() {
-            self.methods.resize(index + 1, None);
+        if index >= methods.len() {
+            methods.resize(index + 1, None);
         }
-        self.methods[index] = Some(closure);
+        methods[index] = Some(closure);
+    }
+
+    fn method_slots(&self) -> &[Option<GcRaw<Closure>>] {
+        // Safety: the methods are only ever mutated through `&mut self`, or by `extend_method`,
+        // whose caller guarantees there are no references to them.
+        unsafe { &*self.methods.get() }
+    }
+
+    /// Returns whether the dispatch table has all methods required by the given trait.
+    pub fn implements_trait(&self, trait_prototype: &TraitPrototype) -> bool {
+        trait_prototype
+            .required
+            .iter()
+            .all(|&method_id| self.get_method(method_id).is_some())
+    }
+
+    /// Returns whether this is the instance dispatch table of an enum. Enum instances are structs
+    /// whose first two fields hold their variant and values; these fields can't be named from
+    /// source code, so regular structs never have them.
+    pub fn is_enum(&self) -> bool {
+        self.fields
+            .first()
+            .is_some_and(|field| field.as_ref() == VARIANT_FIELD)
     }
 
     /// Returns an iterator over all methods in this dispatch table.
     pub(crate) fn methods(&self) -> impl Iterator<Item = GcRaw<Closure>> + '_ {
-        self.methods.iter().copied().flatten()
+        self.method_slots().iter().copied().flatten()
+    }
+
+    /// Returns an iterator over the indices of all methods present in this dispatch table.
+    pub(crate) fn method_indices(&self) -> impl Iterator<Item = MethodIndex> + '_ {
+        self.method_slots()
+            .iter()
+            .enumerate()
+            .filter(|(_, method)| method.is_some())
+            .map(|(index, _)| MethodIndex::from_u16(index as u16))
     }
 }
@@ -2,6 +2,7 @@
 
 use std::{
     collections::{HashMap, HashSet},
+    mem,
     rc::Rc,
 };
 
@@ -9,6 +10,8 @@ use super::{Function, Opr24, Prototype, TraitPrototype};
 use crate::ll::{
     bytecode::MethodParameterCount,
     error::{LanguageErrorKind, RenderedSignature},
+    gc::{Gc, GcRaw, Memory},
+    value::Str,
 };
 
 /// The unique index of a function.
@@ -134,6 +137,14 @@ impl TraitIndex {
 pub struct Environment {
     /// Mapping from global names to global slots.
     globals: HashMap<String, GlobalIndex>,
+    /// Globals that scripts are not allowed to assign to.
+    sealed_globals: HashSet<GlobalIndex>,
+    /// Globals that were declared by scripts rather than by the embedder.
+    script_globals: HashSet<GlobalIndex>,
+    /// Globals declared with `const`.
+    const_globals: HashSet<GlobalIndex>,
+    /// Mapping from names of imported modules to the (unnamed) global slots holding their values.
+    modules: HashMap<Rc<str>, GlobalIndex>,
 
     /// Functions in the environment.
     functions: Vec<Function>,
@@ -147,6 +158,16 @@ pub struct Environment {
     prototypes: Vec<Option<Prototype>>,
     /// Trait prototypes.
     traits: Vec<TraitPrototype>,
+
+    /// Interned string constants, indexed by the operands of `PushString` instructions. Every
+    /// literal with the same contents pushes the same string.
+    strings: Vec<Gc<Str>>,
+    /// Mapping from the contents of string constants to their indices.
+    string_indices: HashMap<Rc<str>, Opr24>,
+
+    /// Whether foreign functions created from now on should be recorded in execution traces.
+    /// This is disabled while the core library is being loaded.
+    pub(crate) trace_foreign_functions: bool,
 }
 
 impl Environment {
@@ -161,19 +182,135 @@ impl Environment {
         if self.globals.contains_key(name) {
             Ok(*self.globals.get(name).unwrap())
         } else {
-            let slot = Opr24::try_from(self.globals.len())
-                .map_err(|_| LanguageErrorKind::TooManyGlobals)?;
-            let slot = GlobalIndex(slot);
+            let slot = self.next_global_slot()?;
             self.globals.insert(name.to_owned(), slot);
             Ok(slot)
         }
     }
 
+    /// Returns the slot the next created global is going to occupy.
+    fn next_global_slot(&self) -> Result<GlobalIndex, LanguageErrorKind> {
+        let slot = Opr24::try_from(self.globals.len() + self.modules.len())
+            .map_err(|_| LanguageErrorKind::TooManyGlobals)?;
+        Ok(GlobalIndex(slot))
+    }
+
+    /// Returns the number of global slots, including the slots of imported modules.
+    pub(crate) fn global_slot_count(&self) -> usize {
+        self.globals.len() + self.modules.len()
+    }
+
+    /// Creates a global on behalf of a script. Globals that already exist are not marked as
+    /// declared by scripts, such that they're still considered builtins.
+    pub(crate) fn create_script_global(
+        &mut self,
+        name: &str,
+    ) -> Result<GlobalIndex, LanguageErrorKind> {
+        let is_new = !self.globals.contains_key(name);
+        let slot = self.create_global(name)?;
+        if is_new {
+            self.script_globals.insert(slot);
+        }
+        Ok(slot)
+    }
+
+    /// Marks all globals declared by scripts so far as builtins. This is used for parts of the core
+    /// library that are written in Mica.
+    pub(crate) fn mark_script_globals_builtin(&mut self) {
+        self.script_globals.clear();
+    }
+
+    /// Returns whether the global was declared by the embedder, as opposed to by a script.
+    pub fn is_global_builtin(&self, slot: GlobalIndex) -> bool {
+        !self.script_globals.contains(&slot)
+    }
+
     /// Tries to look up a global. Returns `None` if the global doesn't exist.
     pub fn get_global(&self, name: &str) -> Option<GlobalIndex> {
         self.globals.get(name).copied()
     }
 
+    /// Creates the global slot holding the value of an imported module. The slot doesn't have a
+    /// name, so it can only be accessed through `import`. If the module already has a slot, that
+    /// slot is returned.
+    pub fn create_module(&mut self, name: &str) -> Result<GlobalIndex, LanguageErrorKind> {
+        if let Some(&slot) = self.modules.get(name) {
+            return Ok(slot);
+        }
+        let slot = self.next_global_slot()?;
+        self.modules.insert(Rc::from(name), slot);
+        Ok(slot)
+    }
+
+    /// Returns the global slot holding the value of an imported module, or `None` if the module
+    /// hasn't been imported yet.
+    pub fn get_module(&self, name: &str) -> Option<GlobalIndex> {
+        self.modules.get(name).copied()
+    }
+
+    /// Returns an iterator over the names of all imported modules, along with their slots.
+    pub(crate) fn modules(&self) -> impl Iterator<Item = (&str, GlobalIndex)> {
+        self.modules.iter().map(|(name, &slot)| (&**name, slot))
+    }
+
+    /// Returns an iterator over the names of all declared globals.
+    pub fn global_names(&self) -> impl Iterator<Item = &str> {
+        self.globals.keys().map(|name| name.as_str())
+    }
+
+    /// Returns the name of the global in the given slot. This is a linear search and should only
+    /// be used for reporting errors.
+    pub fn global_name(&self, slot: GlobalIndex) -> Option<&str> {
+        self.globals
+            .iter()
+            .find(|&(_, &index)| index == slot)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Seals a global, such that scripts can no longer declare or assign to it.
+    pub fn seal_global(&mut self, slot: GlobalIndex) {
+        self.sealed_globals.insert(slot);
+    }
+
+    /// Returns whether the global is sealed.
+    pub fn is_global_sealed(&self, slot: GlobalIndex) -> bool {
+        self.sealed_globals.contains(&slot)
+    }
+
+    /// Sets whether the global was declared with `const`. Redeclaring a global with `let` makes it
+    /// assignable again.
+    pub(crate) fn set_global_const(&mut self, slot: GlobalIndex, is_const: bool) {
+        if is_const {
+            self.const_globals.insert(slot);
+        } else {
+            self.const_globals.remove(&slot);
+        }
+    }
+
+    /// Returns whether the global was declared with `const`.
+    pub fn is_global_const(&self, slot: GlobalIndex) -> bool {
+        self.const_globals.contains(&slot)
+    }
+
+    /// Returns the set of globals declared with `const`.
+    pub(crate) fn const_globals(&self) -> &HashSet<GlobalIndex> {
+        &self.const_globals
+    }
+
+    /// Returns an error if the global is sealed.
+    pub(crate) fn ensure_global_not_sealed(
+        &self,
+        slot: GlobalIndex,
+    ) -> Result<(), LanguageErrorKind> {
+        if self.is_global_sealed(slot) {
+            Err(LanguageErrorKind::GlobalIsSealed(Rc::from(
+                self.global_name(slot).unwrap_or_default(),
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Creates a function and returns its ID.
     pub fn create_function(
         &mut self,
@@ -194,6 +331,68 @@ impl Environment {
         self.functions.get_unchecked(u32::from(id) as usize)
     }
 
+    /// Returns all functions in the environment, in the order they were created.
+    pub(crate) fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    /// Returns all functions in the environment mutably, such that their code can be replaced.
+    pub(crate) fn functions_mut(&mut self) -> &mut [Function] {
+        &mut self.functions
+    }
+
+    /// Removes all functions from the environment, such that the next function created gets
+    /// index 0.
+    pub(crate) fn take_functions(&mut self) -> Vec<Function> {
+        mem::take(&mut self.functions)
+    }
+
+    /// Returns the index of the interned string with the given contents, allocating it if it
+    /// hasn't been interned yet. Returns `Err` if there are too many interned strings.
+    pub(crate) fn get_or_create_string(
+        &mut self,
+        gc: &mut Memory,
+        string: &str,
+    ) -> Result<Opr24, LanguageErrorKind> {
+        if let Some(&index) = self.string_indices.get(string) {
+            return Ok(index);
+        }
+        let index =
+            Opr24::try_from(self.strings.len()).map_err(|_| LanguageErrorKind::TooManyStrings)?;
+        // Interned strings live as long as the environment, so they're kept out of the arena.
+        let raw = gc.outside_arena(|gc| gc.allocate_string(Str::new(string)));
+        self.strings.push(unsafe { Gc::from_raw(raw) });
+        self.string_indices.insert(Rc::from(string), index);
+        Ok(index)
+    }
+
+    /// Returns an iterator over the contents of the interned strings with indices starting from
+    /// `start`, in the order of their indices.
+    pub(crate) fn strings(&self, start: usize) -> impl ExactSizeIterator<Item = &str> {
+        self.strings[start..].iter().map(|string| string.as_str())
+    }
+
+    /// Forgets the interned strings created after the first `len`, such that the next string
+    /// interned gets index `len`.
+    pub(crate) fn truncate_strings(&mut self, len: usize) {
+        for string in self.strings.drain(len..) {
+            self.string_indices.remove(string.as_str());
+        }
+    }
+
+    /// Returns the interned string with the given index, as returned by `get_or_create_string`.
+    pub(crate) fn get_string(&self, index: Opr24) -> &Gc<Str> {
+        &self.strings[u32::from(index) as usize]
+    }
+
+    /// Returns the interned string with the given index without checking that it exists.
+    ///
+    /// # Safety
+    /// The index must have been returned by `get_or_create_string`.
+    pub(crate) unsafe fn get_string_unchecked(&self, index: Opr24) -> GcRaw<Str> {
+        Gc::as_raw(self.strings.get_unchecked(u32::from(index) as usize))
+    }
+
     /// Tries to look up the index of a method, based on a function signature. Creates a new method
     /// index if there isn't one for the given signature. Returns `Err` if there are too many
     /// function signatures in this environment.
@@ -227,19 +426,37 @@ impl Environment {
         self.method_signatures.get(usize::from(method_index.0))
     }
 
-    /// Creates a prototype and returns its ID.
-    pub(crate) fn create_prototype(
-        &mut self,
-        proto: Prototype,
-    ) -> Result<PrototypeIndex, LanguageErrorKind> {
+    /// Returns an iterator over all method signatures, in the order their IDs were assigned.
+    pub fn method_signatures(&self) -> impl Iterator<Item = &MethodSignature> {
+        self.method_signatures.iter()
+    }
+
+    /// Forgets the method indices created after the first `len`, such that the next method index
+    /// created is `len`.
+    pub(crate) fn truncate_methods(&mut self, len: usize) {
+        for signature in self.method_signatures.drain(len..) {
+            self.method_indices.remove(&signature);
+        }
+    }
+
+    /// Reserves an ID for a prototype, such that functions declared in an `impl` block can know
+    /// which block they belong to before the prototype is complete. The prototype must be filled
+    /// in with `set_prototype` afterwards.
+    pub(crate) fn reserve_prototype(&mut self) -> Result<PrototypeIndex, LanguageErrorKind> {
         let slot =
             Opr24::try_from(self.prototypes.len()).map_err(|_| LanguageErrorKind::TooManyImpls)?;
         let slot = PrototypeIndex(slot);
-        self.prototypes.push(Some(proto));
+        self.prototypes.push(None);
         Ok(slot)
     }
 
-    /// Takes out the prototype with the given ID, as returned by `create_prototype`.
+    /// Fills in a prototype reserved with `reserve_prototype`.
+    pub(crate) fn set_prototype(&mut self, id: PrototypeIndex, proto: Prototype) {
+        let PrototypeIndex(id) = id;
+        self.prototypes[usize::from(id)] = Some(proto);
+    }
+
+    /// Takes out the prototype with the given ID, as returned by `reserve_prototype`.
     /// This function is for internal use in the VM and does not perform any checks, thus is marked
     /// `unsafe`.
     pub(crate) unsafe fn get_prototype_unchecked(&self, id: PrototypeIndex) -> &Prototype {
@@ -260,6 +477,7 @@ impl Environment {
         self.traits.push(TraitPrototype {
             name,
             required: HashSet::new(),
+            required_statics: HashSet::new(),
             shims: vec![],
         });
         Ok(slot)
@@ -2,12 +2,13 @@
 
 use std::rc::Rc;
 
-use super::{Chunk, Library};
+use super::{Chunk, Environment, Library, PrototypeIndex};
 use crate::ll::{
     codegen::variables::{LocalIndex, UpvalueIndex},
-    error::LanguageErrorKind,
+    error::{LanguageErrorKind, Location},
     gc::Memory,
     value::RawValue,
+    vm::Globals,
 };
 
 /// The kind of an upvalue capture.
@@ -23,10 +24,32 @@ pub enum CaptureKind {
 pub type ForeignFunction =
     Box<dyn Fn(&Library, &mut Memory, &[RawValue]) -> Result<RawValue, LanguageErrorKind>>;
 
+/// The signature of a raw foreign function that also receives the environment and globals, which
+/// lets it call back into the VM.
+pub type ContextualForeignFunction = Box<
+    dyn Fn(
+        &Environment,
+        &Library,
+        &mut Globals,
+        &mut Memory,
+        &[RawValue],
+    ) -> Result<RawValue, LanguageErrorKind>,
+>;
+
 /// The kind of a controlling function.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Control {
     GcCollect,
+    /// `List.sort_by`, which calls a comparator function for pairs of elements.
+    SortBy,
+    /// `List.sort_by_key`, which calls a function to get the key of each element.
+    SortByKey,
+    /// `methods_of`, which needs the environment to look up the signatures of methods.
+    MethodsOf,
+    /// `yield`, which suspends the fiber and hands a value to whoever resumed it.
+    Yield,
+    /// `Fiber.resume`, which runs another fiber until it yields or finishes.
+    Resume,
 }
 
 /// The kind of the function (bytecode or FFI).
@@ -36,6 +59,9 @@ pub enum FunctionKind {
         captured_locals: Vec<CaptureKind>,
     },
     Foreign(ForeignFunction),
+    /// A foreign function that may call other functions. Its caller's stack is kept alive while
+    /// it runs, as collections can happen during the calls.
+    Contextual(ContextualForeignFunction),
     Control(Control),
 }
 
@@ -51,6 +77,7 @@ impl std::fmt::Debug for FunctionKind {
                 .field("captured_locals", captured_locals)
                 .finish(),
             Self::Foreign(..) => f.debug_struct("Foreign").finish_non_exhaustive(),
+            Self::Contextual(..) => f.debug_struct("Contextual").finish_non_exhaustive(),
             Self::Control(ctl) => f.debug_tuple("Control").field(ctl).finish(),
         }
     }
@@ -66,6 +93,10 @@ pub enum FunctionParameterCount {
     // Implementation note: Since this count always comes from the syntactic parameter list,
     // this count never includes `self`, even in methods.
     Fixed(u16),
+    /// Accept at least the given number of arguments. Any arguments past that are collected into
+    /// a list, which is passed as one extra argument. This is what functions with a rest parameter
+    /// use.
+    AtLeast(u16),
     /// Accept any amount of arguments.
     Varargs,
 }
@@ -92,11 +123,13 @@ impl std::fmt::Debug for FunctionParameterCount {
         if f.alternate() {
             match self {
                 Self::Fixed(num) => write!(f, "Fixed({num})"),
+                Self::AtLeast(num) => write!(f, "AtLeast({num})"),
                 Self::Varargs => write!(f, "Varargs"),
             }
         } else {
             match self {
                 Self::Fixed(num) => write!(f, "{num}"),
+                Self::AtLeast(num) => write!(f, "{num}+"),
                 Self::Varargs => write!(f, "..."),
             }
         }
@@ -160,6 +193,23 @@ impl std::fmt::Debug for MethodParameterCount {
     }
 }
 
+/// Where a function written in Mica was declared, and the names of its parameters.
+#[derive(Debug, Clone)]
+pub struct FunctionDeclaration {
+    pub module_name: Rc<str>,
+    pub location: Location,
+    pub parameter_names: Vec<Rc<str>>,
+}
+
+/// Which functions are allowed to call a method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// The method can be called from anywhere.
+    Public,
+    /// The method can only be called by functions declared in the same `impl` block.
+    Private,
+}
+
 /// A function prototype.
 #[derive(Debug)]
 pub struct Function {
@@ -172,4 +222,33 @@ pub struct Function {
     ///
     /// This is useful for functions that are implementation details, such as trait function shims.
     pub hidden_in_stack_traces: bool,
+
+    /// Set to `true` if calls to the function are recorded in execution traces. This is only the
+    /// case for foreign functions registered by the embedder, as those are the source of any
+    /// nondeterminism in a script's execution.
+    pub traced: bool,
+
+    /// The function's declaration, used in error messages. This is `None` for functions that don't
+    /// come from source code.
+    pub declaration: Option<Rc<FunctionDeclaration>>,
+
+    pub visibility: Visibility,
+    /// The `impl` block the function was declared in. Functions nested inside of methods belong
+    /// to the same block as the method, such that they can call its private methods.
+    pub impl_block: Option<PrototypeIndex>,
+}
+
+impl Function {
+    /// Renders the function's signature for use in error messages. Functions with a declaration
+    /// list their parameter names, like `f(x, y)`; others only list their arity, like `f/2`.
+    pub fn render_signature(&self) -> String {
+        match (&self.declaration, self.parameter_count) {
+            (Some(declaration), _) => {
+                format!("{}({})", self.name, declaration.parameter_names.join(", "))
+            }
+            (None, FunctionParameterCount::Fixed(count)) => format!("{}/{count}", self.name),
+            (None, FunctionParameterCount::AtLeast(count)) => format!("{}/{count}+", self.name),
+            (None, FunctionParameterCount::Varargs) => format!("{}(...)", self.name),
+        }
+    }
 }
@@ -35,13 +35,21 @@ pub(crate) struct Prototype {
 
     /// Same as `instance`, but lists methods that are added to the type dtable rather than the
     /// instance dtable.
-    ///
-    /// Even though we don't support `static` methods in traits, the type is kept the same to
-    /// reduce code duplication.
     pub(crate) statics: HashMap<MethodIndex, FunctionIndex>,
 
+    /// Same as `trait_instance`, but lists static trait functions, which are added to the type
+    /// dtable.
+    pub(crate) trait_statics:
+        HashMap<(Rc<str>, MethodParameterCount, ImplementedTraitIndex), FunctionIndex>,
+
     /// The total number of traits implemented by this struct.
     pub(crate) implemented_trait_count: u16,
+
+    /// The names of the struct's fields, ordered by field index.
+    pub(crate) fields: Vec<Rc<str>>,
+
+    /// Whether the `impl` block declares any constructors.
+    pub(crate) has_constructor: bool,
 }
 
 impl Prototype {
@@ -63,6 +71,9 @@ pub struct TraitPrototype {
     pub name: Rc<str>,
     /// List of method IDs that this trait requires.
     pub required: HashSet<MethodIndex>,
+    /// List of method IDs of static functions that this trait requires. Unlike instance methods,
+    /// these are looked up in the dtable of the implementing type itself.
+    pub required_statics: HashSet<MethodIndex>,
     /// List of `(method_id, function_id)` mappings that make up the dtable of shims for the trait.
     pub shims: Vec<(MethodIndex, FunctionIndex)>,
 }
@@ -1,16 +1,67 @@
 use std::{
     any::{Any, TypeId},
+    cell::{Cell, RefCell},
     collections::HashMap,
     fmt::Debug,
     rc::Rc,
 };
 
-use super::{DispatchTable, Environment, MethodIndex, Opr24, Opr24OutOfRange, TraitIndex};
+use super::{DispatchTable, Environment, Limits, MethodIndex, Opr24, Opr24OutOfRange, TraitIndex};
 use crate::{
-    ll::{codegen::TraitBuilder, error::LanguageErrorKind, gc::Memory},
+    ll::{
+        codegen::TraitBuilder,
+        error::LanguageErrorKind,
+        gc::{GcRaw, Memory},
+        vm::{CallInterceptor, DebugHook, TraceHook},
+    },
     Gc, MethodParameterCount,
 };
 
+/// A function called with a value of a user-defined type right before it's dropped.
+pub type UserFinalizer<T> = Rc<dyn Fn(&mut T)>;
+
+/// How arithmetic operators treat operations that do not have a meaningful numeric result, such as
+/// division by zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arithmetic {
+    /// Follow IEEE 754 semantics: `1 / 0` evaluates to infinity, and `0 / 0` evaluates to NaN.
+    #[default]
+    Ieee,
+    /// Raise a runtime error instead of producing infinity or NaN.
+    ///
+    /// This applies to the binary operators `+`, `-`, `*`, `/`, `//`, and `%`, and to the
+    /// `Number` methods `div`, `div_floor`, `div_euclid`, `mod`, and `rem_euclid`. Dividing by zero
+    /// is reported as such; any other result that isn't finite, such as `1e308 * 10` overflowing
+    /// or `Number.infinity - Number.infinity` being NaN, is reported as a non-finite result.
+    /// Other methods, such as `pow` or `ln`, still follow IEEE 754 semantics.
+    Checked,
+}
+
+impl Arithmetic {
+    /// Returns the result of an arithmetic operation, or an error if it isn't finite and
+    /// arithmetic is checked.
+    pub(crate) fn check(self, result: f64) -> Result<f64, LanguageErrorKind> {
+        if self == Self::Checked && !result.is_finite() {
+            Err(LanguageErrorKind::NonFiniteResult)
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Like [`check`][Self::check], but reports division by zero as such.
+    pub(crate) fn check_division(
+        self,
+        divisor: f64,
+        result: f64,
+    ) -> Result<f64, LanguageErrorKind> {
+        if self == Self::Checked && divisor == 0.0 {
+            Err(LanguageErrorKind::DivisionByZero)
+        } else {
+            self.check(result)
+        }
+    }
+}
+
 /// Aggregate of all dispatch tables and traits available to a VM.
 #[derive(Debug)]
 pub struct Library {
@@ -26,9 +77,69 @@ pub struct Library {
 
     /// Dispatch tables for user types.
     user_dtables: HashMap<TypeId, Gc<DispatchTable>>,
+    /// Finalizers for user types. Each one is a [`UserFinalizer<T>`], where `T` is the type the
+    /// finalizer is keyed by.
+    user_finalizers: HashMap<TypeId, Rc<dyn Any>>,
+
+    /// How arithmetic operators behave when their result is not a finite number.
+    pub arithmetic: Arithmetic,
+
+    /// The maximum number of nested function calls a fiber can make before raising a stack
+    /// overflow error. Calls made by fibers that are waiting for another fiber to return, such as
+    /// a fiber calling a contextual function that calls back into Mica, count toward the limit.
+    pub max_call_depth: usize,
+    /// The number of calls made by fibers that are waiting for another fiber to return.
+    outer_call_depth: Cell<usize>,
+    /// The maximum number of fibers that can wait for another fiber to return at once before
+    /// raising a stack overflow error. Calls within a fiber only use memory on the heap, but each
+    /// fiber running from within another uses up the native stack, which is much smaller.
+    pub max_reentry_depth: usize,
+    /// The number of fibers that are waiting for another fiber to return.
+    reentry_depth: Cell<usize>,
+    /// The maximum number of bytes of native stack fibers running from within other fibers may
+    /// use before raising a stack overflow error.
+    pub max_reentry_stack: usize,
+    /// The position of the native stack when the outermost waiting fiber started waiting.
+    reentry_stack_base: Cell<usize>,
+
+    /// Limits on the size of values scripts can construct.
+    pub limits: Limits,
+
+    /// The number of instructions fibers may still execute, or `None` if there's no limit. All
+    /// fibers draw from this same amount, and suspend once it reaches zero. Setting a limit while
+    /// fibers are running only affects them once they're interpreted again.
+    pub fuel: Cell<Option<u64>>,
+
+    /// The hook fibers call as they execute code, if a debugger is attached.
+    pub debug_hook: Option<Rc<RefCell<dyn DebugHook>>>,
+
+    /// The hook that records or replays calls to traced foreign functions.
+    pub trace_hook: Option<Rc<RefCell<dyn TraceHook>>>,
+
+    /// The hook that can take over method calls before they're dispatched.
+    pub call_interceptor: Option<Rc<RefCell<dyn CallInterceptor>>>,
+
+    /// Whether scripts are allowed to add methods to built-in types using `impl`.
+    pub builtin_extensions: bool,
+
+    /// Dispatch tables for instances of the core library's `Option` and `Result` enums, used when
+    /// converting [`OptionValue`][crate::OptionValue]s and [`ResultValue`][crate::ResultValue]s.
+    /// These are `None` when the core library doesn't declare the enums.
+    pub(crate) option_dtable: Option<Gc<DispatchTable>>,
+    pub(crate) result_dtable: Option<Gc<DispatchTable>>,
 }
 
 impl Library {
+    /// The default value of [`max_call_depth`][Self::max_call_depth].
+    pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+    /// The default value of [`max_reentry_depth`][Self::max_reentry_depth].
+    pub const DEFAULT_MAX_REENTRY_DEPTH: usize = 1000;
+
+    /// The default value of [`max_reentry_stack`][Self::max_reentry_stack]. It leaves room for the
+    /// embedder's own frames within the 2 MiB native stack Rust gives new threads by default.
+    pub const DEFAULT_MAX_REENTRY_STACK: usize = 1024 * 1024;
+
     pub fn new(
         builtin_dtables: BuiltinDispatchTables,
         builtin_dtable_generator: Box<dyn BuiltinDispatchTableGenerator>,
@@ -39,7 +150,80 @@ impl Library {
             builtin_dtable_generator,
             builtin_traits,
             user_dtables: HashMap::new(),
+            user_finalizers: HashMap::new(),
+            arithmetic: Arithmetic::default(),
+            max_call_depth: Self::DEFAULT_MAX_CALL_DEPTH,
+            outer_call_depth: Cell::new(0),
+            max_reentry_depth: Self::DEFAULT_MAX_REENTRY_DEPTH,
+            reentry_depth: Cell::new(0),
+            max_reentry_stack: Self::DEFAULT_MAX_REENTRY_STACK,
+            reentry_stack_base: Cell::new(0),
+            limits: Limits::default(),
+            fuel: Cell::new(None),
+            debug_hook: None,
+            trace_hook: None,
+            call_interceptor: None,
+            builtin_extensions: false,
+            option_dtable: None,
+            result_dtable: None,
+        }
+    }
+
+    /// Returns the number of calls made by fibers that are waiting for another fiber to return.
+    pub(crate) fn outer_call_depth(&self) -> usize {
+        self.outer_call_depth.get()
+    }
+
+    /// Counts a fiber that made `call_depth` calls as waiting for another fiber to return, until
+    /// [`pop_outer_fiber`][Self::pop_outer_fiber] is called with the returned marker.
+    ///
+    /// Returns a stack overflow error if too many fibers are already waiting, or if they're using
+    /// up too much of the native stack.
+    pub(crate) fn push_outer_fiber(&self, call_depth: usize) -> Result<usize, LanguageErrorKind> {
+        // The address of a local tells how far the native stack has grown. Unlike counting the
+        // waiting fibers, this also accounts for how much each one uses, which varies with what
+        // it's waiting on and with how optimized the build is.
+        let position = &call_depth as *const usize as usize;
+        if self.reentry_depth.get() == 0 {
+            self.reentry_stack_base.set(position);
+        }
+        if self.reentry_depth.get() >= self.max_reentry_depth
+            || self.reentry_stack_base.get().abs_diff(position) > self.max_reentry_stack
+        {
+            return Err(LanguageErrorKind::StackOverflow);
         }
+        let marker = self.outer_call_depth.get();
+        self.outer_call_depth.set(marker + call_depth);
+        self.reentry_depth.set(self.reentry_depth.get() + 1);
+        Ok(marker)
+    }
+
+    /// Stops counting the fiber that was pushed when `marker` was returned.
+    pub(crate) fn pop_outer_fiber(&self, marker: usize) {
+        self.outer_call_depth.set(marker);
+        self.reentry_depth.set(self.reentry_depth.get() - 1);
+    }
+
+    /// Returns all dispatch tables owned by the library. The garbage collector treats these as
+    /// roots, as they must stay usable even while no values of their types exist.
+    pub(crate) fn dtables(&self) -> impl Iterator<Item = GcRaw<DispatchTable>> + '_ {
+        let builtin = &self.builtin_dtables;
+        [
+            &builtin.nil,
+            &builtin.boolean,
+            &builtin.number,
+            &builtin.string,
+            &builtin.function,
+            &builtin.list,
+            &builtin.dict,
+        ]
+        .into_iter()
+        .chain(builtin.tuples.iter().flatten())
+        .chain(builtin.records.iter().map(|record| &record.dtable))
+        .chain(self.user_dtables.values())
+        .chain(&self.option_dtable)
+        .chain(&self.result_dtable)
+        .map(Gc::as_raw)
     }
 
     /// Adds a dispatch table for a user-defined type.
@@ -59,16 +243,39 @@ impl Library {
         self.user_dtables.get(&TypeId::of::<T>())
     }
 
+    /// Sets the function that's called with values of a user-defined type right before they're
+    /// dropped.
+    pub fn set_user_finalizer<T>(&mut self, finalizer: UserFinalizer<T>)
+    where
+        T: Any,
+    {
+        self.user_finalizers
+            .insert(TypeId::of::<T>(), Rc::new(finalizer));
+    }
+
+    /// Returns the finalizer of a user-defined type, if it has one.
+    pub fn get_user_finalizer<T>(&self) -> Option<UserFinalizer<T>>
+    where
+        T: Any,
+    {
+        self.user_finalizers
+            .get(&TypeId::of::<T>())
+            .and_then(|finalizer| finalizer.downcast_ref::<UserFinalizer<T>>())
+            .cloned()
+    }
+
     /// Generates the dtable for tuple of the given size if it doesn't exist yet.
     pub(crate) fn generate_tuple(&mut self, env: &mut Environment, gc: &mut Memory, size: usize) {
         if size >= self.builtin_dtables.tuples.len() {
             self.builtin_dtables.tuples.resize(size + 1, None);
         }
-        self.builtin_dtables.tuples[size] =
-            Some(
-                self.builtin_dtable_generator
-                    .generate_tuple(env, gc, &self.builtin_traits, size),
-            );
+        // Generated dtables are kept for as long as the library lives, so they can't be allocated
+        // in an arena that's reset between scripts.
+        let dtable = gc.outside_arena(|gc| {
+            self.builtin_dtable_generator
+                .generate_tuple(env, gc, &self.builtin_traits, size)
+        });
+        self.builtin_dtables.tuples[size] = Some(dtable);
     }
 
     /// Returns the record type index of the record with the given identifier, generating it if it's
@@ -88,16 +295,18 @@ impl Library {
             Ok(index)
         } else {
             let index = RecordTypeIndex(Opr24::try_from(self.builtin_dtables.records.len())?);
-            let dtable = self.builtin_dtable_generator.generate_record(
-                env,
-                gc,
-                &self.builtin_traits,
-                identifier,
-            );
+            let dtable = gc.outside_arena(|gc| {
+                self.builtin_dtable_generator.generate_record(
+                    env,
+                    gc,
+                    &self.builtin_traits,
+                    identifier,
+                )
+            });
             self.builtin_dtables.records.push(Rc::new(RecordType {
                 dtable,
                 identifier: Rc::clone(identifier),
-                field_count: if identifier.len() > 0 {
+                field_count: if !identifier.is_empty() {
                     identifier.chars().filter(|&c| c == '+').count() + 1
                 } else {
                     0
@@ -0,0 +1,212 @@
+//! Limits on the size of values scripts are allowed to construct.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ll::{
+    error::LanguageErrorKind,
+    value::{Dict, List, RawValue, ValueKind},
+};
+
+/// Limits on the size of values a script can construct.
+///
+/// These are meant for running untrusted scripts, which could otherwise build structures large or
+/// deep enough to stall operations like printing and comparison. Exceeding a limit raises a
+/// runtime error in the operation that produced the offending value. Note that the value may
+/// already be modified by the time the error is raised; eg. a list will hold one element too many
+/// after a `push` that exceeded the length limit.
+///
+/// All limits are disabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum number of elements a list can hold.
+    pub max_list_len: usize,
+    /// The maximum number of key-value pairs a dict can hold.
+    pub max_dict_len: usize,
+    /// The maximum length of a string, in bytes.
+    pub max_string_len: usize,
+    /// The maximum number of containers (lists, dicts, tuples, records, struct instances, and user
+    /// data that exposes its references) that can be nested inside each other. A list of numbers
+    /// has a depth of 1, a list of such lists has a depth of 2, and so on.
+    ///
+    /// Values that contain themselves are infinitely deep, and thus always exceed this limit.
+    ///
+    /// This is checked when a container is created, when a container is assigned to a struct
+    /// field, and when a container is passed as an argument to a foreign function (such as
+    /// `List.push`.) Each check walks through the entire value being modified, so this limit is
+    /// considerably more expensive to enforce than the length limits.
+    pub max_nesting_depth: usize,
+}
+
+impl Limits {
+    /// Limits that never trigger.
+    pub const UNLIMITED: Self = Self {
+        max_list_len: usize::MAX,
+        max_dict_len: usize::MAX,
+        max_string_len: usize::MAX,
+        max_nesting_depth: usize::MAX,
+    };
+
+    /// Returns whether none of the limits are enabled.
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::UNLIMITED
+    }
+
+    /// Checks the given value's length and nesting depth against the limits.
+    pub(crate) fn check(&self, value: RawValue) -> Result<(), LanguageErrorKind> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+        self.check_len(value)?;
+        self.check_depth(value)
+    }
+
+    /// Checks the result of a foreign function call, as well as its receiver, which may have been
+    /// modified in place. Walking through the values to find their depth is expensive, so that
+    /// is only done if a container was passed in as an argument, as the function cannot make
+    /// anything deeper otherwise.
+    pub(crate) fn check_call(
+        &self,
+        arguments: &[RawValue],
+        result: RawValue,
+    ) -> Result<(), LanguageErrorKind> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+        self.check_len(arguments[0])?;
+        self.check_len(result)?;
+        if arguments[1..].iter().copied().any(is_container) {
+            self.check_depth(arguments[0])?;
+            self.check_depth(result)?;
+        }
+        Ok(())
+    }
+
+    /// Checks a struct after one of its fields was assigned the given value.
+    pub(crate) fn check_field_assignment(
+        &self,
+        struct_v: RawValue,
+        value: RawValue,
+    ) -> Result<(), LanguageErrorKind> {
+        if is_container(value) {
+            self.check_depth(struct_v)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks that a value of the given type with the given length would not exceed the limits.
+    /// This is used to reject operations before they allocate a large amount of memory.
+    pub(crate) fn check_len_of(
+        &self,
+        type_name: &'static str,
+        len: usize,
+    ) -> Result<(), LanguageErrorKind> {
+        let max = match type_name {
+            "List" => self.max_list_len,
+            "Dict" => self.max_dict_len,
+            "String" => self.max_string_len,
+            _ => usize::MAX,
+        };
+        if len > max {
+            Err(LanguageErrorKind::LengthLimitExceeded {
+                type_name,
+                len,
+                max,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_len(&self, value: RawValue) -> Result<(), LanguageErrorKind> {
+        match value.kind() {
+            ValueKind::String => {
+                let s = unsafe { value.get_raw_string_unchecked().get() };
+                self.check_len_of("String", s.len())
+            }
+            ValueKind::UserData => {
+                let user_data = unsafe { value.get_raw_user_data_unchecked().get() }.as_any();
+                if let Some(list) = user_data.downcast_ref::<List>() {
+                    self.check_len_of("List", unsafe { list.as_slice() }.len())
+                } else if let Some(dict) = user_data.downcast_ref::<Dict>() {
+                    self.check_len_of("Dict", dict.len())
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_depth(&self, value: RawValue) -> Result<(), LanguageErrorKind> {
+        if self.max_nesting_depth == usize::MAX {
+            return Ok(());
+        }
+        let mut walk = DepthWalk {
+            depths: HashMap::new(),
+            in_progress: HashSet::new(),
+        };
+        match walk.depth(value, self.max_nesting_depth) {
+            Some(_) => Ok(()),
+            None => Err(LanguageErrorKind::NestingLimitExceeded {
+                max: self.max_nesting_depth,
+            }),
+        }
+    }
+}
+
+/// Returns whether the value can contain other values.
+fn is_container(value: RawValue) -> bool {
+    matches!(value.kind(), ValueKind::Struct | ValueKind::UserData)
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::UNLIMITED
+    }
+}
+
+/// State for computing the nesting depth of a value. Containers are identified by their address,
+/// so that values shared between many containers are only walked through once.
+struct DepthWalk {
+    depths: HashMap<usize, usize>,
+    in_progress: HashSet<usize>,
+}
+
+impl DepthWalk {
+    /// Returns the depth of the value, or `None` if it's deeper than `max`.
+    fn depth(&mut self, value: RawValue, max: usize) -> Option<usize> {
+        let (address, children) = match value.kind() {
+            ValueKind::Struct => {
+                let raw = unsafe { value.get_raw_struct_unchecked() };
+                let children: Vec<_> = unsafe { raw.get().fields() }.collect();
+                (raw.get_raw() as usize, children)
+            }
+            ValueKind::UserData => {
+                let raw = unsafe { value.get_raw_user_data_unchecked() };
+                let mut children = Vec::new();
+                unsafe { raw.get() }.visit_references(&mut |value| children.push(value));
+                (raw.get_raw() as usize, children)
+            }
+            _ => return Some(0),
+        };
+        if children.is_empty() {
+            return (max > 0).then_some(1);
+        }
+        if let Some(&depth) = self.depths.get(&address) {
+            return (depth <= max).then_some(depth);
+        }
+        // Finding a container that's still being walked means the value contains itself.
+        if max == 0 || !self.in_progress.insert(address) {
+            return None;
+        }
+        let mut depth = 0;
+        for child in children {
+            depth = depth.max(self.depth(child, max - 1)?);
+        }
+        self.in_progress.remove(&address);
+        let depth = depth + 1;
+        self.depths.insert(address, depth);
+        Some(depth)
+    }
+}
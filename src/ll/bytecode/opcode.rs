@@ -19,7 +19,7 @@ pub enum Opcode {
     PushFalse,
     /// Pushes a number onto the stack. Must be followed by an f64.
     PushNumber,
-    /// Pushes a string onto the stack. Must be followed by a string.
+    /// Pushes the interned string with the given index onto the stack.
     PushString,
     /// Creates a closure from the function with the given ID and pushes it onto the stack.
     CreateClosure,
@@ -73,6 +73,14 @@ pub enum Opcode {
     /// Loads a field from the struct on the top of the stack.
     /// Assumes the value on top is a struct and not something else.
     GetField,
+    /// Indexes into the second value from the top of the stack with the value on top (`a[i]`).
+    /// Lists and dicts are indexed directly; other values are indexed by calling their `index`
+    /// method, whose index and argument count are packed into the operand like in `CallMethod`.
+    Index,
+    /// Assigns the value on top of the stack to an index of the value third from the top
+    /// (`a[i] = x`), leaving the assigned value on the stack. Values other than lists and dicts are
+    /// assigned to by calling their `set_index` method.
+    IndexAssign,
 
     /// Destructures a tuple at the top of the stack into its individual components. The operand
     /// signifies how many elements the tuple must have to be successfully destructured; if the
@@ -87,6 +95,43 @@ pub enum Opcode {
     /// of the stack is a record.
     DestructureRecordNonExhaustive,
 
+    /// Checks whether the value at the top of the stack is a tuple with as many elements as
+    /// specified by the operand, and pushes the result of the check as a boolean. The value being
+    /// checked is left on the stack.
+    MatchesTuple,
+    /// Checks whether the value at the top of the stack is a record of the type specified by the
+    /// operand, and pushes the result of the check as a boolean. The value being checked is left on
+    /// the stack.
+    MatchesRecord,
+    /// Checks whether the value at the top of the stack is a record that has at least the fields
+    /// of the record type specified by the operand, and pushes the result of the check as a
+    /// boolean. The value being checked is left on the stack.
+    MatchesRecordNonExhaustive,
+
+    /// Pops a type off the top of the stack, and checks that the value below it is an instance of
+    /// that type. If it's not, an error is thrown. The value is left on the stack, for subsequent
+    /// `GetPatternField` instructions to extract fields from.
+    DestructureStruct,
+    /// Pops a type off the top of the stack, and pushes a boolean signifying whether the value below
+    /// it is an instance of that type. The value being checked is left on the stack.
+    MatchesStruct,
+    /// Pops an enum type off the top of the stack, and checks that the value below it is an
+    /// instance of that enum, of the variant whose name follows the instruction as a string. If
+    /// it's not, an error is thrown. Otherwise the value is replaced with the tuple of values
+    /// carried by the variant.
+    DestructureVariant,
+    /// Pops an enum type off the top of the stack, and pushes a boolean signifying whether the value
+    /// below it is an instance of the enum's variant whose name follows the instruction as a string.
+    /// The value being checked is left on the stack.
+    MatchesVariant,
+    /// Replaces the value at the top of the stack with one of its fields. The operand is packed
+    /// like in `CallMethod`; if the value is a struct with a field named like the method, the field
+    /// is read directly. Otherwise the method is called.
+    GetPatternField,
+    /// Pops a trait off the top of the stack, and checks that the value below it implements that
+    /// trait. If it doesn't, an error is thrown. The value is left on the stack.
+    EnsureImplements,
+
     /// Swaps the two values at the top of the stack.
     Swap,
     /// Duplicates the value at the top of the stack.
@@ -108,42 +153,139 @@ pub enum Opcode {
     /// Jumps the program counter backward by an amount of bytes.
     /// Due to how the VM increments the program counter, the actual amount is `operand - 4`.
     JumpBackward,
+    // The long variants of jumps are used when the offset does not fit in the 24-bit operand.
+    // Instead of storing the offset directly, their operand is an index into the chunk's table of
+    // long jump offsets.
+    /// Long variant of `JumpForward`.
+    JumpForwardLong,
+    /// Long variant of `JumpForwardIfFalsy`.
+    JumpForwardIfFalsyLong,
+    /// Long variant of `JumpForwardIfTruthy`.
+    JumpForwardIfTruthyLong,
+    /// Long variant of `JumpBackward`.
+    JumpBackwardLong,
     /// Enters a breakable block by pushing the break sentinel value onto the stack.
     EnterBreakableBlock,
     /// Exits the n-th breakable block (counted from innermost) by popping values off the stack
     /// until `.0` sentinels are removed.
     ExitBreakableBlock,
+    /// Installs an error handler for a `try` expression. If an error occurs before the handler is
+    /// removed, the stack is unwound to the height it had when the handler was installed, the
+    /// error value and stack trace are pushed onto it, and execution continues `.0 + 4` bytes
+    /// after this instruction.
+    EnterTry,
+    /// Removes `.0` error handlers.
+    ExitTry,
+    /// Raises the value at the top of the stack as an error.
+    Raise,
+    /// Installs an error handler for a `defer` expression, whose deferred code begins `.0 - 4`
+    /// bytes before this instruction. If an error occurs before the handler is removed, the stack is
+    /// unwound like with `EnterTry`, and the deferred code is run before the error continues to
+    /// propagate.
+    EnterDefer,
+    /// Runs the deferred code beginning `.0 - 4` bytes before this instruction, and continues
+    /// after this instruction once it's done.
+    RunDeferred,
+    /// Ends deferred code. Execution continues where the code was run from, or if it was run by an
+    /// error handler, the error continues to propagate.
+    ExitDeferred,
 
     /// Calls a function with `.0` arguments.
     Call,
+    /// Calls a function with `.0` arguments, followed by the elements of the list at the top of
+    /// the stack.
+    CallSpread,
     /// Calls the `n`th method with `a` arguments, where `a` is encoded in the lower 8 bits, and
     /// `n` is encoded in the upper 16 bits of `.0`.
     CallMethod,
+    /// Calls a method whose name is only known at runtime, with `.0` arguments (including the
+    /// receiver). The name is a string located right below the receiver; it is left on the stack,
+    /// below the call's result.
+    CallMethodDynamic,
     /// Returns to the calling function.
     Return,
 
+    /// Fails an assertion. The stack holds the source code of the asserted condition, followed by
+    /// `operand` pairs of an operand's source code and its value, followed by the assertion's
+    /// message, or `nil` if there isn't one. Source code that isn't known is `nil` as well.
+    AssertionFailed,
+
     /// Implements a struct according to a prototype identified by the operand.
     Implement,
 
     /// Negates a number (prefix `-`).
+    ///
+    /// This and the other arithmetic and comparison instructions that take an operand are
+    /// overloadable: the operand packs a method index and argument count like in `CallMethod`, and
+    /// if the left operand is not a number, that method is called on it, with the right operand
+    /// as the argument.
     Negate,
     /// Adds two numbers together (infix `+`).
     Add,
     /// Subtracts a number from another number (infix `-`).
     Subtract,
-    /// Multiplies two numbers together (infix `*`).
+    /// Multiplies two numbers together (infix `*`). Unlike with other operators, if the left
+    /// operand is a number and the right one isn't, the method is called on the right operand,
+    /// such that `3 * x` means `x * 3`.
     Multiply,
     /// Divides a number by another number (infix `/`).
     Divide,
+    /// Divides a number by another number, rounding the result towards negative infinity
+    /// (infix `//`).
+    FloorDivide,
+    /// Computes the remainder of dividing a number by another number (infix `%`). The result has
+    /// the same sign as the divisor, such that `a == (a // b) * b + a % b`.
+    Modulo,
 
     /// Flips a boolean-like value (truthy values become `false` and falsy values become `true`).
     Not,
-    /// Compares two values for equality.
+    /// Compares two values for equality. Only structs and user data can overload equality.
     Equal,
-    /// Compares two values for less-than relation.
+    /// Compares two values for less-than relation. The overloading method returns a number that
+    /// is negative, zero, or positive if the receiver is less than, equal to, or greater than the
+    /// argument. If only the right operand overloads comparisons, the method is called on it
+    /// instead, and its result is flipped.
     Less,
-    /// Compares two values for less-than-or-equal relation.
+    /// Compares two values for less-than-or-equal relation, overloaded like `Less`.
     LessEqual,
+    /// Checks whether a value implements the trait at the top of the stack, by looking up all of
+    /// the trait's methods in the value's dispatch table.
+    Implements,
+
+    // Superinstructions replace the first instruction of a sequence that's common in hot code,
+    // and perform the whole sequence at once when their operands are numbers. Otherwise they only
+    // do what the instruction they replace would, and the rest of the sequence runs as usual,
+    // which keeps overloading, retrying blocked calls, and error reporting the same as without
+    // them.
+    /// Replaces the first `GetLocal` of a `GetLocal, GetLocal, Add` sequence. If both locals are
+    /// numbers, their sum is pushed and the two instructions after this one are skipped.
+    AddLocals,
+    /// Replaces the `PushNumber` of a `PushNumber, Less, JumpForwardIfFalsy, Discard` sequence
+    /// generated for an `if` or `while` condition. If the value at the top of the stack is a
+    /// number, it's compared against the number following the instruction; if it's less, the
+    /// value is discarded, and otherwise it's replaced with `false` and the jump is taken.
+    LessNumberJump,
+    /// Like `LessNumberJump`, but for a sequence that compares with `LessEqual`.
+    LessEqualNumberJump,
+    /// Replaces a `GetLocal` immediately followed by a `Call`. The local is pushed, and the call
+    /// is made without dispatching the `Call` separately.
+    GetLocalCall,
+
+    // Quickened instructions are never generated. Instead, the VM replaces a generic instruction
+    // with its quickened variant once it sees the instruction run with numbers, and puts the
+    // generic instruction back if the quickened one is run with anything else. Quickened
+    // instructions only check that their operands are numbers, and otherwise behave exactly like
+    // the instructions they replace.
+    /// `Add` quickened for numbers.
+    AddNumber,
+    /// `Subtract` quickened for numbers.
+    SubtractNumber,
+    /// `Multiply` quickened for numbers.
+    MultiplyNumber,
+    /// `Less` quickened for numbers.
+    LessNumber,
+    /// `LessEqual` quickened for numbers.
+    LessEqualNumber,
 
     /// Halts the interpreter loop.
     Halt,
@@ -151,54 +293,48 @@ pub enum Opcode {
 
 /// A jump was constructed whose offset stretched too far.
 #[derive(Debug)]
-pub struct JumpTooFar(());
+pub struct JumpTooFar(pub(super) ());
 
 impl Opcode {
     /// The size of an instruction (1 byte opcode + 3 bytes operand).
     pub const INSTRUCTION_SIZE: usize = 4;
 
     /// Returns the offset of a forward jump instruction.
-    fn forward_jump_offset(from: usize, to: usize) -> Result<Opr24, JumpTooFar> {
+    pub(crate) fn forward_jump_offset(from: usize, to: usize) -> usize {
         assert!(to >= from);
-        let offset = to - from - Self::INSTRUCTION_SIZE;
-        if u32::try_from(offset).is_err() {
-            return Err(JumpTooFar(()));
-        }
-        Opr24::new(offset as u32).map_err(|_| JumpTooFar(()))
-    }
-
-    /// Constructs a `JumpForward` instruction.
-    pub fn jump_forward(from: usize, to: usize) -> Result<(Self, Opr24), JumpTooFar> {
-        let offset = Self::forward_jump_offset(from, to)?;
-        Ok((Self::JumpForward, offset))
-    }
-
-    /// Constructs a `JumpForwardIfFalsy` instruction.
-    pub fn jump_forward_if_falsy(from: usize, to: usize) -> Result<(Self, Opr24), JumpTooFar> {
-        let offset = Self::forward_jump_offset(from, to)?;
-        Ok((Self::JumpForwardIfFalsy, offset))
-    }
-
-    /// Constructs a `JumpForwardIfTruthy` instruction.
-    pub fn jump_forward_if_truthy(from: usize, to: usize) -> Result<(Self, Opr24), JumpTooFar> {
-        let offset = Self::forward_jump_offset(from, to)?;
-        Ok((Self::JumpForwardIfTruthy, offset))
+        to - from - Self::INSTRUCTION_SIZE
     }
 
     /// Returns the offset of a backward jump instruction.
-    fn backward_jump_offset(from: usize, to: usize) -> Result<Opr24, JumpTooFar> {
+    pub(crate) fn backward_jump_offset(from: usize, to: usize) -> usize {
         assert!(to <= from);
-        let offset = from - to + Self::INSTRUCTION_SIZE;
-        if u32::try_from(offset).is_err() {
-            return Err(JumpTooFar(()));
+        from - to + Self::INSTRUCTION_SIZE
+    }
+
+    /// Returns the long variant of a jump opcode.
+    ///
+    /// # Panics
+    /// If the opcode is not a short jump.
+    pub(crate) fn long_jump(self) -> Self {
+        match self {
+            Self::JumpForward => Self::JumpForwardLong,
+            Self::JumpForwardIfFalsy => Self::JumpForwardIfFalsyLong,
+            Self::JumpForwardIfTruthy => Self::JumpForwardIfTruthyLong,
+            Self::JumpBackward => Self::JumpBackwardLong,
+            _ => panic!("{self:?} is not a short jump opcode"),
         }
-        Opr24::new(offset as u32).map_err(|_| JumpTooFar(()))
     }
 
-    /// Constructs a `JumpBackward` instruction.
-    pub fn jump_backward(from: usize, to: usize) -> Result<(Self, Opr24), JumpTooFar> {
-        let offset = Self::backward_jump_offset(from, to)?;
-        Ok((Self::JumpBackward, offset))
+    /// Returns the generic opcode a quickened opcode replaced. Other opcodes are returned as is.
+    pub fn generic(self) -> Self {
+        match self {
+            Self::AddNumber => Self::Add,
+            Self::SubtractNumber => Self::Subtract,
+            Self::MultiplyNumber => Self::Multiply,
+            Self::LessNumber => Self::Less,
+            Self::LessEqualNumber => Self::LessEqual,
+            _ => self,
+        }
     }
 }
 
@@ -28,6 +28,12 @@ impl Opr24 {
         }
     }
 
+    /// Constructs an `Opr24` from the lower 24 bits of `x`, ignoring the rest.
+    pub(crate) fn from_u32_truncated(x: u32) -> Self {
+        let [a, b, c, _] = x.to_le_bytes();
+        Self { bytes: [a, b, c] }
+    }
+
     /// Packs `T` into an `Opr24`.
     pub fn pack<T>(x: T) -> Self
     where
@@ -3,12 +3,17 @@
 use std::{collections::HashSet, fmt, rc::Rc};
 
 pub use self::traits::TraitBuilder;
-use self::{control_flow::BreakableBlock, structs::StructData, variables::Locals};
+use self::{
+    control_flow::{BreakableBlock, Defer},
+    parallel::{DeferredFunction, GlobalView},
+    structs::StructData,
+    variables::Locals,
+};
 use super::{bytecode::Library, gc::Memory};
 use crate::ll::{
     ast::{Ast, NodeId, NodeKind},
-    bytecode::{Chunk, Environment, Opcode},
-    error::{LanguageError, LanguageErrorKind},
+    bytecode::{Chunk, Environment, Opcode, PrototypeIndex},
+    error::{LanguageError, LanguageErrorKind, LanguageWarning, LanguageWarningKind, Location},
 };
 
 pub struct CodeGenerator<'e> {
@@ -20,14 +25,41 @@ pub struct CodeGenerator<'e> {
 
     locals: Box<Locals>,
     breakable_blocks: Vec<BreakableBlock>,
+    /// How many `try` blocks and `defer` expressions the code being generated is nested in.
+    try_depth: usize,
+    /// The `defer` expressions whose deferred code has to run when their blocks are exited.
+    defers: Vec<Defer>,
+    /// When generating deferred code, the number of breakable blocks outside of it.
+    defer_barrier: Option<usize>,
     struct_data: Option<Box<StructData>>,
+    /// The `impl` block functions generated by this generator belong to.
+    impl_block: Option<PrototypeIndex>,
 
     allow_new_fields: bool,
     is_constructor: bool,
     assigned_fields: HashSet<Rc<str>>,
+    /// What the identifiers in the pattern currently being destructured do.
+    pattern_binding: PatternBinding,
+
+    warnings: Vec<LanguageWarning>,
+
+    /// How many nodes are currently being generated recursively, including ones in enclosing
+    /// functions.
+    depth: usize,
+
+    /// Top-level functions whose bodies are generated after the rest of the module, or `None`
+    /// if functions are generated in place. See [`parallel`].
+    deferred_functions: Option<Vec<DeferredFunction>>,
+    /// When generating a deferred function, the globals it can see.
+    global_view: Option<&'e GlobalView>,
 }
 
 impl<'e> CodeGenerator<'e> {
+    /// The maximum depth of nodes that can be generated. Although the parser limits how deeply
+    /// expressions can nest, chains of left-associative operators such as `1 + 1 + 1` are parsed
+    /// in a loop, but still result in deep syntax trees.
+    const MAX_DEPTH: usize = 256;
+
     /// Constructs a new code generator with an empty chunk.
     pub fn new(
         module_name: Rc<str>,
@@ -43,22 +75,51 @@ impl<'e> CodeGenerator<'e> {
 
             locals: Default::default(),
             breakable_blocks: Vec::new(),
+            try_depth: 0,
+            defers: Vec::new(),
+            defer_barrier: None,
             struct_data: None,
+            impl_block: None,
 
             allow_new_fields: false,
             is_constructor: false,
             assigned_fields: HashSet::new(),
+            pattern_binding: PatternBinding::Let,
+
+            warnings: Vec::new(),
+
+            depth: 0,
+
+            deferred_functions: None,
+            global_view: None,
         }
     }
 
+    /// Emits a warning at the given location.
+    fn warn(&mut self, location: Location, kind: LanguageWarningKind) {
+        self.warnings.push(LanguageWarning {
+            kind,
+            module_name: Rc::clone(&self.chunk.module_name),
+            location,
+        });
+    }
+
     /// Generates code for a list of nodes. The last node's value is the one left on the stack.
     ///
     /// If there are no nodes in the list, this is equivalent to a `nil` literal.
     fn generate_node_list(&mut self, ast: &Ast, nodes: &[NodeId]) -> Result<(), LanguageError> {
+        let defer_count = self.defers.len();
         if nodes.is_empty() {
             let _ = self.generate_nil();
         } else {
             for (i, &node) in nodes.iter().enumerate() {
+                if ast.kind(node) == NodeKind::Defer {
+                    self.generate_defer(ast, node)?;
+                    if i == nodes.len() - 1 {
+                        let _ = self.generate_nil();
+                    }
+                    continue;
+                }
                 self.generate_node(
                     ast,
                     node,
@@ -68,8 +129,19 @@ impl<'e> CodeGenerator<'e> {
                         Expression::Used
                     },
                 )?;
+                if matches!(
+                    ast.kind(node),
+                    NodeKind::Break | NodeKind::Continue | NodeKind::Return | NodeKind::Raise
+                ) {
+                    if let Some(&next) = nodes.get(i + 1) {
+                        self.warn(ast.location(next), LanguageWarningKind::UnreachableCode);
+                    }
+                }
             }
         }
+        if self.defers.len() > defer_count {
+            self.generate_defer_exits(ast, defer_count)?;
+        }
         Ok(())
     }
 
@@ -81,98 +153,147 @@ impl<'e> CodeGenerator<'e> {
         ast: &Ast,
         node: NodeId,
         expr: Expression,
+    ) -> Result<(), LanguageError> {
+        if self.depth >= Self::MAX_DEPTH {
+            return Err(ast.error(node, LanguageErrorKind::NestingTooDeep));
+        }
+        self.depth += 1;
+        let result = self.generate_node_unchecked(ast, node, expr);
+        self.depth -= 1;
+        result
+    }
+
+    /// Generates code for a valid expression node without checking how deeply it's nested.
+    fn generate_node_unchecked(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        expr: Expression,
     ) -> Result<(), LanguageError> {
         let previous_codegen_location = self.chunk.codegen_location;
         self.chunk.codegen_location = ast.location(node);
+        // In debug builds, every `?` inside of this match would get its own stack slot, which
+        // adds up quickly when generating deeply nested code. Hence the arms only produce results
+        // and the error is propagated once.
         let result = match ast.kind(node) {
             NodeKind::Empty => panic!("empty nodes must never be generated"),
 
-            NodeKind::Nil => self.generate_nil(),
-            NodeKind::False | NodeKind::True => self.generate_boolean(ast, node),
-            NodeKind::Number => self.generate_number(ast, node),
+            NodeKind::Nil => Ok(self.generate_nil()),
+            NodeKind::False | NodeKind::True => Ok(self.generate_boolean(ast, node)),
+            NodeKind::Number => Ok(self.generate_number(ast, node)),
             NodeKind::String => self.generate_string(ast, node),
 
-            NodeKind::Identifier => self.generate_variable(ast, node)?,
+            NodeKind::Identifier => self.generate_variable(ast, node),
             NodeKind::Underscore => {
-                return Err(ast.error(node, LanguageErrorKind::CannotAccessDiscardPattern))
+                Err(ast.error(node, LanguageErrorKind::CannotAccessDiscardPattern))
+            }
+            NodeKind::StructPattern => {
+                Err(ast.error(node, LanguageErrorKind::StructPatternInExpression))
             }
 
             NodeKind::Paren => {
                 let (inner, _) = ast.node_pair(node);
-                self.generate_node(ast, inner, expr)?;
-                ExpressionResult::Present
+                self.generate_node(ast, inner, expr)
+                    .map(|_| ExpressionResult::Present)
             }
 
-            NodeKind::List => self.generate_list(ast, node)?,
-            NodeKind::Dict => self.generate_dict(ast, node)?,
-            NodeKind::Tuple => self.generate_tuple(ast, node)?,
-            NodeKind::Record => self.generate_record(ast, node)?,
+            NodeKind::List => self.generate_list(ast, node),
+            NodeKind::Dict => self.generate_dict(ast, node),
+            NodeKind::ListComprehension | NodeKind::DictComprehension => {
+                self.generate_comprehension(ast, node)
+            }
+            NodeKind::Tuple => self.generate_tuple(ast, node),
+            NodeKind::Record => self.generate_record(ast, node),
 
-            NodeKind::Negate | NodeKind::Not => self.generate_unary(ast, node)?,
+            NodeKind::Negate | NodeKind::Not => self.generate_unary(ast, node),
 
             NodeKind::Add
             | NodeKind::Subtract
             | NodeKind::Multiply
             | NodeKind::Divide
+            | NodeKind::FloorDivide
+            | NodeKind::Modulo
             | NodeKind::Equal
             | NodeKind::NotEqual
             | NodeKind::Less
             | NodeKind::Greater
             | NodeKind::LessEqual
-            | NodeKind::GreaterEqual => self.generate_binary(ast, node)?,
+            | NodeKind::GreaterEqual
+            | NodeKind::Implements => self.generate_binary(ast, node),
 
-            NodeKind::And => self.generate_and(ast, node)?,
-            NodeKind::Or => self.generate_or(ast, node)?,
+            NodeKind::And => self.generate_and(ast, node),
+            NodeKind::Or => self.generate_or(ast, node),
 
-            NodeKind::Let => self.generate_let(ast, node, expr)?,
-            NodeKind::Assign => self.generate_assignment(ast, node, expr)?,
-            NodeKind::Dot => self.generate_dot(ast, node)?,
-            NodeKind::Field => self.generate_field(ast, node)?,
+            NodeKind::Let | NodeKind::Const => self.generate_let(ast, node, expr),
+            NodeKind::Assign => self.generate_assignment(ast, node, expr),
+            NodeKind::Dot | NodeKind::NilSafeDot => self.generate_dot(ast, node),
+            NodeKind::Cascade => self.generate_cascade(ast, node),
+            NodeKind::Index => self.generate_index(ast, node),
+            NodeKind::Slice => self.generate_slice(ast, node),
+            NodeKind::Field => self.generate_field(ast, node),
 
-            NodeKind::Main => {
-                self.generate_node_list(ast, ast.children(node).unwrap())?;
-                ExpressionResult::Present
-            }
+            NodeKind::Main => self
+                .generate_node_list(ast, ast.children(node).unwrap())
+                .map(|_| ExpressionResult::Present),
 
-            NodeKind::Do => self.generate_do(ast, node)?,
-            NodeKind::If => self.generate_if(ast, node)?,
-            NodeKind::While => self.generate_while(ast, node)?,
-            NodeKind::For => self.generate_for(ast, node)?,
-            NodeKind::Break => self.generate_break(ast, node)?,
+            NodeKind::Do => self.generate_do(ast, node),
+            NodeKind::If => self.generate_if(ast, node),
+            NodeKind::While => self.generate_while(ast, node, None),
+            NodeKind::For => self.generate_for(ast, node, None),
+            NodeKind::Labeled => self.generate_labeled(ast, node),
+            NodeKind::Try => self.generate_try(ast, node),
+            NodeKind::Raise => self.generate_raise(ast, node),
+            NodeKind::Defer => Err(ast.error(node, LanguageErrorKind::DeferOutsideOfBlock)),
+            NodeKind::Break => self.generate_break(ast, node),
+            NodeKind::Continue => self.generate_continue(ast, node),
 
             NodeKind::Func => {
                 let (head, _) = ast.node_pair(node);
                 let (name, _) = ast.node_pair(head);
                 if name != NodeId::EMPTY {
-                    self.generate_function_declaration(ast, node)?
+                    self.generate_function_declaration(ast, node)
                 } else {
-                    self.generate_function_expression(ast, node)?
+                    self.generate_function_expression(ast, node)
                 }
             }
-            NodeKind::Call => self.generate_call(ast, node)?,
-            NodeKind::Return => self.generate_return(ast, node)?,
+            NodeKind::Call => self.generate_call(ast, node),
+            NodeKind::Spread => Err(ast.error(node, LanguageErrorKind::InvalidSpread)),
+            NodeKind::Return => self.generate_return(ast, node),
 
-            NodeKind::Struct => self.generate_struct(ast, node)?,
-            NodeKind::Impl => self.generate_impl(ast, node)?,
-            NodeKind::Trait => self.generate_trait(ast, node)?,
-            NodeKind::ImplAs => return Err(ast.error(node, LanguageErrorKind::AsOutsideOfImpl)),
+            NodeKind::Struct => self.generate_struct(ast, node),
+            NodeKind::Enum => self.generate_enum(ast, node),
+            NodeKind::Impl => self.generate_impl(ast, node),
+            NodeKind::Trait => self.generate_trait(ast, node),
+            NodeKind::ImplAs => Err(ast.error(node, LanguageErrorKind::AsOutsideOfImpl)),
+            NodeKind::Import => self.generate_import(ast, node),
 
             NodeKind::Pair
             | NodeKind::Rest
+            | NodeKind::Label
+            | NodeKind::Catch
             | NodeKind::IfBranch
             | NodeKind::ElseBranch
             | NodeKind::FunctionHead
             | NodeKind::Parameters
             | NodeKind::Static
-            | NodeKind::Constructor => {
+            | NodeKind::Constructor
+            | NodeKind::Pub
+            | NodeKind::Priv
+            | NodeKind::RestParameter
+            | NodeKind::ComprehensionFor
+            | NodeKind::ComprehensionIf
+            | NodeKind::SliceBounds => {
                 unreachable!("AST implementation detail")
             }
-        };
+        }?;
         match (result, expr) {
             (ExpressionResult::Absent, Expression::Used) => {
                 let _ = self.generate_nil();
             }
             (ExpressionResult::Present, Expression::Discarded) => {
+                if Self::has_no_side_effects(ast.kind(node)) {
+                    self.warn(ast.location(node), LanguageWarningKind::UnusedResult);
+                }
                 let _ = self.chunk.emit(Opcode::Discard);
             }
             _ => (),
@@ -181,11 +302,46 @@ impl<'e> CodeGenerator<'e> {
         Ok(())
     }
 
-    /// Generates code for the given AST.
-    pub fn generate(mut self, ast: &Ast, root_node: NodeId) -> Result<Rc<Chunk>, LanguageError> {
+    /// Returns whether evaluating a node of the given kind can only produce a value, so
+    /// discarding its result is most likely a mistake.
+    fn has_no_side_effects(kind: NodeKind) -> bool {
+        matches!(
+            kind,
+            NodeKind::Nil
+                | NodeKind::False
+                | NodeKind::True
+                | NodeKind::Number
+                | NodeKind::String
+                | NodeKind::Identifier
+                | NodeKind::Field
+                | NodeKind::Negate
+                | NodeKind::Not
+                | NodeKind::Add
+                | NodeKind::Subtract
+                | NodeKind::Multiply
+                | NodeKind::Divide
+                | NodeKind::FloorDivide
+                | NodeKind::Modulo
+                | NodeKind::Equal
+                | NodeKind::NotEqual
+                | NodeKind::Less
+                | NodeKind::Greater
+                | NodeKind::LessEqual
+                | NodeKind::GreaterEqual
+                | NodeKind::Implements
+        )
+    }
+
+    /// Generates code for the given AST. Returns the generated chunk, along with any warnings
+    /// emitted along the way.
+    pub fn generate(
+        mut self,
+        ast: &Ast,
+        root_node: NodeId,
+    ) -> Result<(Rc<Chunk>, Vec<LanguageWarning>), LanguageError> {
         self.generate_node(ast, root_node, Expression::Used)?;
         self.chunk.emit(Opcode::Halt);
-        Ok(Rc::new(self.chunk))
+        Ok((Rc::new(self.chunk), self.warnings))
     }
 }
 
@@ -204,6 +360,17 @@ enum Expression {
     Used,
 }
 
+/// What the identifiers in a destructured pattern do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatternBinding {
+    /// Declare new variables, as in `let`.
+    Let,
+    /// Declare new variables that cannot be assigned to, as in `const`.
+    Const,
+    /// Assign to existing variables and fields, as in `(a, b) = (b, a)`.
+    Assign,
+}
+
 #[must_use]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ExpressionResult {
@@ -217,14 +384,22 @@ enum ExpressionResult {
 
 // The actual implementation is split into multiple files so as not to have to maintain a
 // multi-thousand line behemoth.
+mod assertions;
 mod assignment;
 mod calls;
+mod comprehensions;
+mod constants;
 mod control_flow;
+mod enums;
 mod functions;
 mod impls;
+mod imports;
 mod literals;
 mod operators;
+mod parallel;
 mod structs;
+mod subscripts;
+mod superinstructions;
 mod traits;
 mod tuples;
 pub mod variables;
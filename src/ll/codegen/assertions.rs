@@ -0,0 +1,137 @@
+//! Code generation for `assert`.
+//!
+//! Calls to `assert` are compiled into bytecode that checks the condition in place, such that
+//! failed assertions can report the source code of the asserted expression. If the expression is
+//! a comparison, the values of its operands are reported too.
+
+use super::{
+    variables::{VariableAllocation, VariablePlace},
+    CodeGenerator, Expression, ExpressionResult,
+};
+use crate::ll::{
+    ast::{Ast, NodeId, NodeKind},
+    bytecode::{Opcode, Opr24},
+    error::{LanguageError, LanguageErrorKind},
+};
+
+impl<'e> CodeGenerator<'e> {
+    /// Returns whether a call node is a call to `assert` that should be compiled as an assertion.
+    ///
+    /// This is the case when the called function is the `assert` global (and not a local variable
+    /// shadowing it), and it's called with the condition and optionally a message.
+    pub(super) fn is_assertion(&mut self, ast: &Ast, node: NodeId) -> bool {
+        let (function, _) = ast.node_pair(node);
+        let argument_count = ast.len(node).unwrap_or(0);
+        ast.kind(function) == NodeKind::Identifier
+            && ast.string(function).map(|name| &**name) == Some("assert")
+            && (1..=2).contains(&argument_count)
+            && matches!(
+                self.lookup_variable("assert"),
+                Ok(Some(VariablePlace::Global(_)))
+            )
+    }
+
+    /// Generates code for an assertion. The result of the assertion is the value of the condition.
+    pub(super) fn generate_assertion(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let arguments = ast.children(node).unwrap();
+        let condition = arguments[0];
+        let message = arguments.get(1).copied();
+
+        // The operands of comparisons are saved into hidden variables, so that their values can be
+        // reported if the assertion fails.
+        self.push_scope();
+        let mut operands = Vec::new();
+        if Self::is_comparison(ast.kind(condition)) {
+            let (left, right) = ast.node_pair(condition);
+            for (index, operand) in [left, right].into_iter().enumerate() {
+                self.generate_node(ast, operand, Expression::Used)?;
+                if !Self::is_literal(ast, operand) {
+                    let variable = self
+                        .create_variable(
+                            &format!("<assert operand {index}>"),
+                            VariableAllocation::Allocate,
+                        )
+                        .map_err(|kind| ast.error(operand, kind))?;
+                    self.generate_variable_assign(variable);
+                    operands.push((operand, variable));
+                }
+            }
+            self.chunk.codegen_location = ast.location(condition);
+            self.generate_binary_operation(ast, condition)?;
+        } else {
+            self.generate_node(ast, condition, Expression::Used)?;
+        }
+        let jump_past_failure = self.chunk.emit(Opcode::Nop);
+
+        self.chunk.codegen_location = ast.location(node);
+        self.generate_source_text(ast, condition)?;
+        for &(operand, variable) in &operands {
+            self.generate_source_text(ast, operand)?;
+            self.generate_variable_load(variable);
+        }
+        match message {
+            Some(message) => self.generate_node(ast, message, Expression::Used)?,
+            None => {
+                let _ = self.generate_nil();
+            }
+        }
+        self.chunk.codegen_location = ast.location(node);
+        self.chunk.emit((
+            Opcode::AssertionFailed,
+            Opr24::try_from(operands.len()).unwrap(),
+        ));
+
+        let jump = self
+            .chunk
+            .jump_forward_if_truthy(jump_past_failure, self.chunk.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::OperatorRhsTooLarge))?;
+        self.chunk.patch(jump_past_failure, jump);
+        self.pop_scope();
+
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Pushes the source code of a node onto the stack as a string, or `nil` if the source code is
+    /// not known.
+    fn generate_source_text(&mut self, ast: &Ast, node: NodeId) -> Result<(), LanguageError> {
+        match ast.source_text(node) {
+            Some(text) => self
+                .emit_push_string(text)
+                .map_err(|kind| ast.error(node, kind))?,
+            None => {
+                let _ = self.generate_nil();
+            }
+        }
+        Ok(())
+    }
+
+    fn is_comparison(kind: NodeKind) -> bool {
+        matches!(
+            kind,
+            NodeKind::Equal
+                | NodeKind::NotEqual
+                | NodeKind::Less
+                | NodeKind::Greater
+                | NodeKind::LessEqual
+                | NodeKind::GreaterEqual
+        )
+    }
+
+    /// Returns whether the value of an operand is obvious from its source code, in which case it
+    /// isn't worth reporting.
+    fn is_literal(ast: &Ast, node: NodeId) -> bool {
+        match ast.kind(node) {
+            NodeKind::Nil
+            | NodeKind::False
+            | NodeKind::True
+            | NodeKind::Number
+            | NodeKind::String => true,
+            NodeKind::Negate | NodeKind::Paren => Self::is_literal(ast, ast.node_pair(node).0),
+            _ => false,
+        }
+    }
+}
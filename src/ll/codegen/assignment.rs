@@ -1,11 +1,15 @@
 //! Code generation for assignment and pattern matching.
 
-use std::rc::Rc;
+use std::{ops::Deref, rc::Rc};
 
-use super::{variables::VariableAllocation, CodeGenerator, Expression, ExpressionResult};
+use super::{
+    variables::VariablePlace, CodeGenerator, Expression, ExpressionResult, PatternBinding,
+};
 use crate::ll::{
     ast::{Ast, NodeId, NodeKind},
-    bytecode::{Opcode, Opr24},
+    bytecode::{
+        make_record_identifier, MethodIndex, MethodParameterCount, MethodSignature, Opcode, Opr24,
+    },
     error::{LanguageError, LanguageErrorKind},
 };
 
@@ -21,11 +25,17 @@ impl<'e> CodeGenerator<'e> {
         result: Expression,
     ) -> Result<(), LanguageError> {
         match ast.kind(node) {
+            NodeKind::Identifier
+            | NodeKind::Field
+            | NodeKind::Index
+            | NodeKind::Slice
+            | NodeKind::Dot
+                if self.pattern_binding == PatternBinding::Assign =>
+            {
+                self.generate_assignment_target(ast, node, result)?;
+            }
             NodeKind::Identifier => {
-                let name = ast.string(node).unwrap();
-                let variable = self
-                    .create_variable(name, VariableAllocation::Allocate)
-                    .map_err(|kind| ast.error(node, kind))?;
+                let variable = self.declare_variable(ast, node)?;
                 match result {
                     Expression::Used => self.generate_variable_assign(variable),
                     Expression::Discarded => self.generate_variable_sink(variable),
@@ -33,33 +43,336 @@ impl<'e> CodeGenerator<'e> {
             }
             NodeKind::Tuple => {
                 let fields = ast.children(node).unwrap();
+                self.generate_tuple_destructuring(ast, node, fields, result)?;
+            }
+            NodeKind::Underscore => {
+                if result == Expression::Discarded {
+                    self.chunk.emit(Opcode::Discard);
+                }
+            }
+            NodeKind::Record => self.generate_record_destructuring(ast, node, result)?,
+            NodeKind::StructPattern => self.generate_struct_destructuring(ast, node, result)?,
+            NodeKind::Implements => {
+                let (pattern, implemented_trait) = ast.node_pair(node);
+                self.generate_node(ast, implemented_trait, Expression::Used)?;
+                self.chunk.emit(Opcode::EnsureImplements);
+                self.generate_pattern_destructuring(ast, pattern, result)?;
+            }
+            NodeKind::Dot | NodeKind::Call => {
+                let (enum_type, variant, values) = variant_pattern(ast, node)?;
                 if result == Expression::Used {
-                    // In case the result is used, we wanna duplicate the value so that the
-                    // destructuring doesn't eat away the tuple resulting from the assignment.
                     self.chunk.emit(Opcode::Duplicate);
                 }
-                self.chunk.emit((
-                    Opcode::DestructureTuple,
-                    Opr24::try_from(fields.len())
-                        .map_err(|_| ast.error(node, LanguageErrorKind::TupleHasTooManyElements))?,
-                ));
-                // Iterate through the fields in reverse, since going from the top of the stack
-                // that's the order we want to destructure our variables in.
-                for &field in fields.iter().rev() {
-                    self.generate_pattern_destructuring(ast, field, Expression::Discarded)?;
-                }
+                self.generate_node(ast, enum_type, Expression::Used)?;
+                self.chunk.emit(Opcode::DestructureVariant);
+                self.chunk.emit_string(variant);
+                self.generate_tuple_destructuring(ast, node, values, Expression::Discarded)?;
+            }
+            _ => return Err(ast.error(node, LanguageErrorKind::InvalidPattern)),
+        }
+        Ok(())
+    }
+
+    /// Generates code for destructuring a tuple with the given elements.
+    fn generate_tuple_destructuring(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        fields: &[NodeId],
+        result: Expression,
+    ) -> Result<(), LanguageError> {
+        if result == Expression::Used {
+            // In case the result is used, we wanna duplicate the value so that the
+            // destructuring doesn't eat away the tuple resulting from the assignment.
+            self.chunk.emit(Opcode::Duplicate);
+        }
+        self.chunk.emit((
+            Opcode::DestructureTuple,
+            Opr24::try_from(fields.len())
+                .map_err(|_| ast.error(node, LanguageErrorKind::TupleHasTooManyElements))?,
+        ));
+        // Iterate through the fields in reverse, since going from the top of the stack
+        // that's the order we want to destructure our variables in.
+        for &field in fields.iter().rev() {
+            self.generate_pattern_destructuring(ast, field, Expression::Discarded)?;
+        }
+        Ok(())
+    }
+
+    /// Generates code for destructuring a struct pattern. Unlike records, struct patterns never
+    /// have to list all of the struct's fields.
+    fn generate_struct_destructuring(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        result: Expression,
+    ) -> Result<(), LanguageError> {
+        let (implementee, fields) = ast.node_pair(node);
+        self.generate_node(ast, implementee, Expression::Used)?;
+        self.chunk.emit(Opcode::DestructureStruct);
+        for (key, value) in struct_pattern_fields(ast, fields) {
+            let method_index = self.getter_method_index(ast, key)?;
+            self.chunk.emit(Opcode::Duplicate);
+            self.chunk.emit((
+                Opcode::GetPatternField,
+                Opr24::pack((method_index.to_u16(), 1)),
+            ));
+            let pattern = if value == NodeId::EMPTY { key } else { value };
+            self.generate_pattern_destructuring(ast, pattern, Expression::Discarded)?;
+        }
+        if result == Expression::Discarded {
+            self.chunk.emit(Opcode::Discard);
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the getter method named like the given identifier.
+    fn getter_method_index(
+        &mut self,
+        ast: &Ast,
+        key: NodeId,
+    ) -> Result<MethodIndex, LanguageError> {
+        let signature = MethodSignature::new(
+            Rc::clone(ast.string(key).unwrap()),
+            MethodParameterCount::from_count_with_self(1),
+        );
+        self.env
+            .get_or_create_method_index(&signature)
+            .map_err(|e| ast.error(key, e))
+    }
+
+    /// Generates code that checks whether the scrutinee at the top of the stack matches a pattern.
+    ///
+    /// The scrutinee is left on the stack, and a boolean signifying whether the pattern matched is
+    /// pushed on top of it. Variables match anything but `nil`, and `_` matches anything. Tuples and
+    /// records match if they have the right shape, and their elements match the nested patterns.
+    /// `implements` patterns match values implementing the trait that also match the inner pattern.
+    fn generate_pattern_test(&mut self, ast: &Ast, node: NodeId) -> Result<(), LanguageError> {
+        match ast.kind(node) {
+            NodeKind::Identifier => {
+                self.chunk.emit(Opcode::Duplicate);
+                self.chunk.emit(Opcode::PushNil);
+                self.chunk.emit(Opcode::Equal);
+                self.chunk.emit(Opcode::Not);
             }
             NodeKind::Underscore => {
-                if result == Expression::Discarded {
+                self.chunk.emit(Opcode::PushTrue);
+            }
+            NodeKind::Tuple => {
+                let elements = ast.children(node).unwrap();
+                self.generate_tuple_test(ast, node, elements)?;
+            }
+            NodeKind::Record => {
+                let pairs = ast.children(node).unwrap();
+                let is_non_exhaustive =
+                    !pairs.is_empty() && ast.kind(*pairs.last().unwrap()) == NodeKind::Rest;
+                let mut fields: Vec<_> = pairs
+                    .iter()
+                    .filter(|&&pair| ast.kind(pair) != NodeKind::Rest)
+                    .map(|&pair| ast.node_pair(pair))
+                    .collect();
+                fields.sort_by_key(|&(key, _)| ast.string(key));
+                let identifier = make_record_identifier(
+                    fields
+                        .iter()
+                        .map(|&(key, _)| ast.string(key).unwrap().deref()),
+                );
+                let record_type_index = self
+                    .library
+                    .get_or_generate_record(self.env, self.gc, &identifier)
+                    .map_err(|_| ast.error(node, LanguageErrorKind::TooManyRecords))?;
+                let opcode = if is_non_exhaustive {
+                    Opcode::MatchesRecordNonExhaustive
+                } else {
+                    Opcode::MatchesRecord
+                };
+                self.chunk.emit((opcode, record_type_index.to_opr24()));
+                let mut jumps_to_end = Vec::new();
+                for (key, value) in fields {
+                    let pattern = if value == NodeId::EMPTY { key } else { value };
+                    let name = Rc::clone(ast.string(key).unwrap());
+                    self.generate_element_test(
+                        ast,
+                        key,
+                        name,
+                        pattern,
+                        Opcode::CallMethod,
+                        &mut jumps_to_end,
+                    )?;
+                }
+                self.patch_pattern_test_jumps(ast, node, jumps_to_end)?;
+            }
+            NodeKind::StructPattern => {
+                let (implementee, fields) = ast.node_pair(node);
+                self.generate_node(ast, implementee, Expression::Used)?;
+                self.chunk.emit(Opcode::MatchesStruct);
+                let mut jumps_to_end = Vec::new();
+                for (key, value) in struct_pattern_fields(ast, fields) {
+                    let pattern = if value == NodeId::EMPTY { key } else { value };
+                    let name = Rc::clone(ast.string(key).unwrap());
+                    self.generate_element_test(
+                        ast,
+                        key,
+                        name,
+                        pattern,
+                        Opcode::GetPatternField,
+                        &mut jumps_to_end,
+                    )?;
+                }
+                self.patch_pattern_test_jumps(ast, node, jumps_to_end)?;
+            }
+            NodeKind::Implements => {
+                let (pattern, implemented_trait) = ast.node_pair(node);
+                self.chunk.emit(Opcode::Duplicate);
+                self.generate_node(ast, implemented_trait, Expression::Used)?;
+                self.chunk.emit(Opcode::Implements);
+                // Only test the inner pattern if the trait is implemented.
+                let jump_to_end = self.chunk.emit(Opcode::Nop);
+                self.chunk.emit(Opcode::Discard);
+                self.generate_pattern_test(ast, pattern)?;
+                self.patch_pattern_test_jumps(ast, node, vec![jump_to_end])?;
+            }
+            NodeKind::Dot | NodeKind::Call => {
+                let (enum_type, variant, values) = variant_pattern(ast, node)?;
+                self.generate_node(ast, enum_type, Expression::Used)?;
+                self.chunk.emit(Opcode::MatchesVariant);
+                self.chunk.emit_string(variant);
+                if !values.is_empty() {
+                    // Only test the values if the variant matches.
+                    let jump_to_end = self.chunk.emit(Opcode::Nop);
+                    self.chunk.emit(Opcode::Discard);
+                    self.chunk.emit(Opcode::Duplicate);
+                    // The values are always stored in the second field of enum instances.
+                    self.chunk.emit((Opcode::GetField, Opr24::from(1_u8)));
+                    self.generate_tuple_test(ast, node, values)?;
+                    self.chunk.emit(Opcode::Swap);
                     self.chunk.emit(Opcode::Discard);
+                    self.patch_pattern_test_jumps(ast, node, vec![jump_to_end])?;
                 }
             }
-            NodeKind::Record => self.generate_record_destructuring(ast, node, result)?,
             _ => return Err(ast.error(node, LanguageErrorKind::InvalidPattern)),
         }
         Ok(())
     }
 
+    /// Generates code that checks whether the scrutinee is a tuple whose elements match the given
+    /// patterns.
+    fn generate_tuple_test(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        elements: &[NodeId],
+    ) -> Result<(), LanguageError> {
+        self.chunk.emit((
+            Opcode::MatchesTuple,
+            Opr24::try_from(elements.len())
+                .map_err(|_| ast.error(node, LanguageErrorKind::TupleHasTooManyElements))?,
+        ));
+        let mut jumps_to_end = Vec::new();
+        for (index, &element) in elements.iter().enumerate() {
+            let name = Rc::from(format!("_{index}"));
+            self.generate_element_test(
+                ast,
+                element,
+                name,
+                element,
+                Opcode::CallMethod,
+                &mut jumps_to_end,
+            )?;
+        }
+        self.patch_pattern_test_jumps(ast, node, jumps_to_end)
+    }
+
+    /// Generates code that tests a single element of a tuple, record, or struct pattern, after the
+    /// shape of the scrutinee has been checked. The element is obtained from the scrutinee using
+    /// the `access` opcode, which is given the getter method with the given name.
+    fn generate_element_test(
+        &mut self,
+        ast: &Ast,
+        key: NodeId,
+        getter: Rc<str>,
+        pattern: NodeId,
+        access: Opcode,
+        jumps_to_end: &mut Vec<usize>,
+    ) -> Result<(), LanguageError> {
+        // `_` always matches, so there's nothing to check.
+        if ast.kind(pattern) == NodeKind::Underscore {
+            return Ok(());
+        }
+        let signature = MethodSignature::new(getter, MethodParameterCount::from_count_with_self(1));
+        let method_index = self
+            .env
+            .get_or_create_method_index(&signature)
+            .map_err(|e| ast.error(key, e))?;
+        // Stop checking as soon as one of the elements doesn't match.
+        jumps_to_end.push(self.chunk.emit(Opcode::Nop));
+        self.chunk.emit(Opcode::Discard);
+        self.chunk.emit(Opcode::Duplicate);
+        self.chunk
+            .emit((access, Opr24::pack((method_index.to_u16(), 1))));
+        self.generate_pattern_test(ast, pattern)?;
+        // Drop the element, leaving the result of the check on top of the scrutinee.
+        self.chunk.emit(Opcode::Swap);
+        self.chunk.emit(Opcode::Discard);
+        Ok(())
+    }
+
+    fn patch_pattern_test_jumps(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        jumps_to_end: Vec<usize>,
+    ) -> Result<(), LanguageError> {
+        for jump in jumps_to_end {
+            let jump_to_end = self
+                .chunk
+                .jump_forward_if_falsy(jump, self.chunk.len())
+                .map_err(|_| ast.error(node, LanguageErrorKind::PatternTooLarge))?;
+            self.chunk.patch(jump, jump_to_end);
+        }
+        Ok(())
+    }
+
+    /// Generates code for a `let` used as the condition of an `if` branch or a `while` loop.
+    ///
+    /// Rather than producing the assigned value, the condition produces whether the value matched
+    /// the pattern. The pattern's variables are only assigned if it did.
+    pub(super) fn generate_let_condition(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<(), LanguageError> {
+        let (assignment, _) = ast.node_pair(node);
+        if ast.kind(assignment) != NodeKind::Assign {
+            return Err(ast.error(assignment, LanguageErrorKind::LetRhsMustBeAssignment));
+        }
+        let (pattern, value) = ast.node_pair(assignment);
+        self.generate_node(ast, value, Expression::Used)?;
+        self.generate_pattern_test(ast, pattern)?;
+
+        let jump_to_mismatch = self.chunk.emit(Opcode::Nop);
+        self.chunk.emit(Opcode::Discard);
+        self.generate_pattern_destructuring(ast, pattern, Expression::Discarded)?;
+        self.chunk.emit(Opcode::PushTrue);
+        let jump_to_end = self.chunk.emit(Opcode::Nop);
+
+        let mismatch = self
+            .chunk
+            .jump_forward_if_falsy(jump_to_mismatch, self.chunk.len())
+            .map_err(|_| ast.error(pattern, LanguageErrorKind::PatternTooLarge))?;
+        self.chunk.patch(jump_to_mismatch, mismatch);
+        // Leave the `false` from the test as the condition's result.
+        self.chunk.emit(Opcode::Swap);
+        self.chunk.emit(Opcode::Discard);
+
+        let end = self
+            .chunk
+            .jump_forward(jump_to_end, self.chunk.len())
+            .map_err(|_| ast.error(pattern, LanguageErrorKind::PatternTooLarge))?;
+        self.chunk.patch(jump_to_end, end);
+        Ok(())
+    }
+
     /// Generates code for a `let` expression.
     pub(super) fn generate_let(
         &mut self,
@@ -73,13 +386,32 @@ impl<'e> CodeGenerator<'e> {
         }
         let (place, value) = ast.node_pair(assignment);
         self.generate_node(ast, value, Expression::Used)?;
-        self.generate_pattern_destructuring(ast, place, result)?;
+        let binding = match ast.kind(node) {
+            NodeKind::Const => PatternBinding::Const,
+            _ => PatternBinding::Let,
+        };
+        self.generate_pattern_binding(ast, place, result, binding)?;
         Ok(match result {
             Expression::Discarded => ExpressionResult::Absent,
             Expression::Used => ExpressionResult::Present,
         })
     }
 
+    /// Generates code for destructuring a pattern, whose identifiers bind to variables in the
+    /// given way.
+    fn generate_pattern_binding(
+        &mut self,
+        ast: &Ast,
+        pattern: NodeId,
+        result: Expression,
+        binding: PatternBinding,
+    ) -> Result<(), LanguageError> {
+        let outer_binding = std::mem::replace(&mut self.pattern_binding, binding);
+        let destructured = self.generate_pattern_destructuring(ast, pattern, result);
+        self.pattern_binding = outer_binding;
+        destructured
+    }
+
     /// Generates code for an assignment.
     pub(super) fn generate_assignment(
         &mut self,
@@ -90,6 +422,38 @@ impl<'e> CodeGenerator<'e> {
         let (target, value) = ast.node_pair(node);
         self.generate_node(ast, value, Expression::Used)?;
 
+        match ast.kind(target) {
+            NodeKind::Identifier
+            | NodeKind::Field
+            | NodeKind::Index
+            | NodeKind::Slice
+            | NodeKind::Dot => {
+                self.generate_assignment_target(ast, target, result)?;
+            }
+            NodeKind::Tuple
+            | NodeKind::Record
+            | NodeKind::StructPattern
+            | NodeKind::Implements
+            | NodeKind::Underscore => {
+                self.generate_pattern_binding(ast, target, result, PatternBinding::Assign)?;
+            }
+            _ => return Err(ast.error(target, LanguageErrorKind::InvalidAssignment)),
+        }
+
+        Ok(match result {
+            Expression::Discarded => ExpressionResult::Absent,
+            Expression::Used => ExpressionResult::Present,
+        })
+    }
+
+    /// Generates code for assigning the value at the top of the stack to an existing variable,
+    /// field, subscript, or property.
+    fn generate_assignment_target(
+        &mut self,
+        ast: &Ast,
+        target: NodeId,
+        result: Expression,
+    ) -> Result<(), LanguageError> {
         match ast.kind(target) {
             NodeKind::Identifier => {
                 let name = ast.string(target).unwrap();
@@ -99,11 +463,20 @@ impl<'e> CodeGenerator<'e> {
                 {
                     slot
                 } else {
+                    return Err(ast.error(target, self.variable_does_not_exist(name)));
+                };
+                if let VariablePlace::Global(slot) = variable {
+                    self.env
+                        .ensure_global_not_sealed(slot)
+                        .map_err(|kind| ast.error(target, kind))?;
+                }
+                if self.is_variable_const(name, variable) {
                     return Err(ast.error(
                         target,
-                        LanguageErrorKind::VariableDoesNotExist(Rc::clone(name)),
+                        LanguageErrorKind::CannotAssignConst(Rc::clone(name)),
                     ));
-                };
+                }
+                self.record_assignment(name, variable, ast.location(target));
                 match result {
                     Expression::Used => self.generate_variable_assign(variable),
                     Expression::Discarded => self.generate_variable_sink(variable),
@@ -116,7 +489,7 @@ impl<'e> CodeGenerator<'e> {
                     let field = if self.allow_new_fields {
                         struct_data
                             .get_or_create_field(name)
-                            .map_err(|kind| ast.error(node, kind))?
+                            .map_err(|kind| ast.error(target, kind))?
                     } else {
                         struct_data.get_field(name).ok_or_else(|| {
                             ast.error(
@@ -142,12 +515,56 @@ impl<'e> CodeGenerator<'e> {
                     self.assigned_fields.insert(Rc::clone(name));
                 }
             }
-            _ => return Err(ast.error(target, LanguageErrorKind::InvalidAssignment)),
+            NodeKind::Index | NodeKind::Slice => {
+                self.generate_subscript_assignment(ast, target, result)?;
+            }
+            NodeKind::Dot => {
+                // Assigning to a property `a.x = v` calls the setter `a.set_x(v)`. The setter's
+                // result is discarded, so that assignments evaluate to the assigned value.
+                let (receiver, name) = ast.node_pair(target);
+                let name = ast
+                    .string(name)
+                    .ok_or_else(|| ast.error(target, LanguageErrorKind::InvalidAssignment))?;
+                if result == Expression::Used {
+                    self.chunk.emit(Opcode::Duplicate);
+                }
+                self.generate_node(ast, receiver, Expression::Used)?;
+                self.chunk.emit(Opcode::Swap);
+                let operand = self.operator_method(ast, target, &format!("set_{name}"), 2)?;
+                self.chunk.codegen_location = ast.location(target);
+                self.chunk.emit((Opcode::CallMethod, operand));
+                self.chunk.emit(Opcode::Discard);
+            }
+            _ => unreachable!(),
         }
+        Ok(())
+    }
+}
 
-        Ok(match result {
-            Expression::Discarded => ExpressionResult::Absent,
-            Expression::Used => ExpressionResult::Present,
-        })
+/// Splits a variant pattern like `Shape.Circle(r)` or `Shape.Empty` into the enum, the name of the
+/// variant, and the patterns matching the variant's values.
+fn variant_pattern(ast: &Ast, node: NodeId) -> Result<(NodeId, &str, &[NodeId]), LanguageError> {
+    let (dot, values) = match ast.kind(node) {
+        NodeKind::Call => (ast.node_pair(node).0, ast.children(node).unwrap()),
+        _ => (node, &[][..]),
+    };
+    if ast.kind(dot) != NodeKind::Dot {
+        return Err(ast.error(node, LanguageErrorKind::InvalidPattern));
+    }
+    let (enum_type, variant) = ast.node_pair(dot);
+    match ast.string(variant) {
+        Some(variant) => Ok((enum_type, variant, values)),
+        None => Err(ast.error(node, LanguageErrorKind::InvalidPattern)),
     }
 }
+
+/// Returns the `(key, value)` pairs of the fields listed in a struct pattern, skipping the `..`
+/// that may optionally end it.
+fn struct_pattern_fields(ast: &Ast, fields: NodeId) -> Vec<(NodeId, NodeId)> {
+    ast.children(fields)
+        .unwrap()
+        .iter()
+        .filter(|&&pair| ast.kind(pair) != NodeKind::Rest)
+        .map(|&pair| ast.node_pair(pair))
+        .collect()
+}
@@ -20,53 +20,125 @@ impl<'e> CodeGenerator<'e> {
         node: NodeId,
     ) -> Result<ExpressionResult, LanguageError> {
         let (function, _) = ast.node_pair(node);
+        if self.is_assertion(ast, node) {
+            return self.generate_assertion(ast, node);
+        }
         match ast.kind(function) {
             // Method calls need special treatment.
-            NodeKind::Dot => {
+            NodeKind::Dot | NodeKind::NilSafeDot => {
                 let (receiver, name) = ast.node_pair(function);
-                if ast.kind(name) != NodeKind::Identifier {
-                    return Err(ast.error(name, LanguageErrorKind::InvalidMethodName));
-                }
-                let name = ast.string(name).unwrap();
-
-                // Generate the receiver and arguments.
                 self.generate_node(ast, receiver, Expression::Used)?;
-                let arguments = ast.children(node).unwrap();
-                for &argument in arguments {
-                    self.generate_node(ast, argument, Expression::Used)?;
+                let jump_if_nil =
+                    (ast.kind(function) == NodeKind::NilSafeDot).then(|| self.generate_nil_check());
+                match ast.kind(name) {
+                    NodeKind::Identifier => self.generate_method_call(ast, node, name)?,
+                    NodeKind::Paren => {
+                        let arguments = ast.children(node).unwrap();
+                        self.generate_dynamic_method_call(ast, node, name, arguments)?;
+                    }
+                    _ => return Err(ast.error(name, LanguageErrorKind::InvalidMethodName)),
+                }
+                if let Some(jump_if_nil) = jump_if_nil {
+                    self.patch_nil_check(ast, function, jump_if_nil)?;
                 }
-
-                // Construct the call.
-                let parameter_count =
-                    MethodParameterCount::from_count_without_self(arguments.len())
-                        .map_err(|_| ast.error(node, LanguageErrorKind::TooManyArguments))?;
-                let signature = MethodSignature::new(Rc::clone(name), parameter_count);
-                let method_index = self
-                    .env
-                    .get_or_create_method_index(&signature)
-                    .map_err(|kind| ast.error(node, kind))?;
-                self.chunk.emit((
-                    Opcode::CallMethod,
-                    Opr24::pack((method_index.to_u16(), parameter_count.to_count_with_self())),
-                ));
             }
             _ => {
+                let mut last_operand = self.chunk.len();
                 self.generate_node(ast, function, Expression::Used)?;
-                let arguments = ast.children(node).unwrap();
+                let mut arguments = ast.children(node).unwrap();
+                // A spread list is left on the stack after the other arguments, and expanded by
+                // the VM once the call is made.
+                let spread = match arguments.split_last() {
+                    Some((&last, rest)) if ast.kind(last) == NodeKind::Spread => {
+                        arguments = rest;
+                        Some(last)
+                    }
+                    _ => None,
+                };
                 for &argument in arguments {
+                    last_operand = self.chunk.len();
                     self.generate_node(ast, argument, Expression::Used)?;
                 }
-                self.chunk.emit((
-                    Opcode::Call,
-                    Opr24::try_from(arguments.len())
-                        .map_err(|_| ast.error(node, LanguageErrorKind::TooManyArguments))?,
-                ));
+                let last_operand = last_operand..self.chunk.len();
+                let argument_count = Opr24::try_from(arguments.len())
+                    .map_err(|_| ast.error(node, LanguageErrorKind::TooManyArguments))?;
+                if let Some(spread) = spread {
+                    let (list, _) = ast.node_pair(spread);
+                    self.generate_node(ast, list, Expression::Used)?;
+                    self.chunk.codegen_location = ast.location(node);
+                    self.chunk.emit((Opcode::CallSpread, argument_count));
+                } else {
+                    self.chunk.emit((Opcode::Call, argument_count));
+                    self.fuse_local_call(last_operand);
+                }
             }
         }
         Ok(ExpressionResult::Present)
     }
 
     /// Generates code for a bare dot call (without parentheses).
+    /// Generates code for calling the method `name` with the arguments of the `Call` node,
+    /// assuming the receiver is already on the stack.
+    fn generate_method_call(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        name: NodeId,
+    ) -> Result<(), LanguageError> {
+        let name = ast.string(name).unwrap();
+        let arguments = ast.children(node).unwrap();
+        Self::ensure_no_spread(ast, arguments)?;
+        for &argument in arguments {
+            self.generate_node(ast, argument, Expression::Used)?;
+        }
+
+        // Construct the call.
+        let parameter_count = MethodParameterCount::from_count_without_self(arguments.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::TooManyArguments))?;
+        let signature = MethodSignature::new(Rc::clone(name), parameter_count);
+        let method_index = self
+            .env
+            .get_or_create_method_index(&signature)
+            .map_err(|kind| ast.error(node, kind))?;
+        self.chunk.emit((
+            Opcode::CallMethod,
+            Opr24::pack((method_index.to_u16(), parameter_count.to_count_with_self())),
+        ));
+        Ok(())
+    }
+
+    /// Ensures none of the arguments of a method call are spread.
+    fn ensure_no_spread(ast: &Ast, arguments: &[NodeId]) -> Result<(), LanguageError> {
+        match arguments
+            .iter()
+            .find(|&&argument| ast.kind(argument) == NodeKind::Spread)
+        {
+            Some(&spread) => Err(ast.error(spread, LanguageErrorKind::SpreadInMethodCall)),
+            None => Ok(()),
+        }
+    }
+
+    /// Generates code for a cascaded method call. The result of the call is discarded, and the
+    /// receiver is left on the stack in its place.
+    pub(super) fn generate_cascade(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (receiver, call) = ast.node_pair(node);
+        self.generate_node(ast, receiver, Expression::Used)?;
+        self.chunk.emit(Opcode::Duplicate);
+        if ast.kind(call) == NodeKind::Call {
+            let (name, _) = ast.node_pair(call);
+            self.generate_method_call(ast, call, name)?;
+        } else {
+            // Without parentheses, the method is called without arguments, like with `.`.
+            self.generate_dot_call(ast, call)?;
+        }
+        self.chunk.emit(Opcode::Discard);
+        Ok(ExpressionResult::Present)
+    }
+
     pub(super) fn generate_dot(
         &mut self,
         ast: &Ast,
@@ -74,7 +146,56 @@ impl<'e> CodeGenerator<'e> {
     ) -> Result<ExpressionResult, LanguageError> {
         let (left, method) = ast.node_pair(node);
         self.generate_node(ast, left, Expression::Used)?;
+        let jump_if_nil =
+            (ast.kind(node) == NodeKind::NilSafeDot).then(|| self.generate_nil_check());
+        self.generate_dot_call(ast, method)?;
+        if let Some(jump_if_nil) = jump_if_nil {
+            self.patch_nil_check(ast, node, jump_if_nil)?;
+        }
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates the check made by `?.` before calling a method, assuming the receiver is on top
+    /// of the stack. Returns the jump to be patched by `patch_nil_check` after the call.
+    fn generate_nil_check(&mut self) -> usize {
+        self.chunk.emit(Opcode::Duplicate);
+        self.chunk.emit(Opcode::PushNil);
+        self.chunk.emit(Opcode::Equal);
+        let jump_if_nil = self.chunk.emit(Opcode::Nop);
+        self.chunk.emit(Opcode::Discard);
+        jump_if_nil
+    }
+
+    /// Makes the check generated by `generate_nil_check` skip over the method call, leaving the
+    /// `nil` receiver on the stack as the result.
+    fn patch_nil_check(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        jump_if_nil: usize,
+    ) -> Result<(), LanguageError> {
+        let jump_past_discard = self.chunk.emit(Opcode::Nop);
+        let jump = self
+            .chunk
+            .jump_forward_if_truthy(jump_if_nil, self.chunk.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::OperatorRhsTooLarge))?;
+        self.chunk.patch(jump_if_nil, jump);
+        // Discard the result of the comparison.
+        self.chunk.emit(Opcode::Discard);
+        let jump = self
+            .chunk
+            .jump_forward(jump_past_discard, self.chunk.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::OperatorRhsTooLarge))?;
+        self.chunk.patch(jump_past_discard, jump);
+        Ok(())
+    }
 
+    /// Generates a call to the argument-less method named by the `method` node, assuming the
+    /// receiver is already on the stack.
+    fn generate_dot_call(&mut self, ast: &Ast, method: NodeId) -> Result<(), LanguageError> {
+        if ast.kind(method) == NodeKind::Paren {
+            return self.generate_dynamic_method_call(ast, method, method, &[]);
+        }
         if ast.kind(method) != NodeKind::Identifier {
             return Err(ast.error(method, LanguageErrorKind::InvalidMethodName));
         }
@@ -85,10 +206,38 @@ impl<'e> CodeGenerator<'e> {
         let method_index = self
             .env
             .get_or_create_method_index(&signature)
-            .map_err(|kind| ast.error(node, kind))?;
+            .map_err(|kind| ast.error(method, kind))?;
         self.chunk
             .emit((Opcode::CallMethod, Opr24::pack((method_index.to_u16(), 1))));
+        Ok(())
+    }
 
-        Ok(ExpressionResult::Present)
+    /// Generates code for calling a method whose name is computed at runtime by the `name`
+    /// expression (`receiver.(name)`), assuming the receiver is already on the stack.
+    fn generate_dynamic_method_call(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        name: NodeId,
+        arguments: &[NodeId],
+    ) -> Result<(), LanguageError> {
+        // The name is kept on the stack below the receiver for the duration of the call, and is
+        // removed once the call returns.
+        Self::ensure_no_spread(ast, arguments)?;
+        self.generate_node(ast, name, Expression::Used)?;
+        self.chunk.emit(Opcode::Swap);
+        for &argument in arguments {
+            self.generate_node(ast, argument, Expression::Used)?;
+        }
+        let parameter_count = MethodParameterCount::from_count_without_self(arguments.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::TooManyArguments))?;
+        self.chunk.codegen_location = ast.location(node);
+        self.chunk.emit((
+            Opcode::CallMethodDynamic,
+            Opr24::from(parameter_count.to_count_with_self()),
+        ));
+        self.chunk.emit(Opcode::Swap);
+        self.chunk.emit(Opcode::Discard);
+        Ok(())
     }
 }
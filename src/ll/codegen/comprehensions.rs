@@ -0,0 +1,158 @@
+//! Code generation for list and dict comprehensions.
+
+use std::rc::Rc;
+
+use super::{
+    variables::{VariableAllocation, VariablePlace},
+    CodeGenerator, Expression, ExpressionResult,
+};
+use crate::ll::{
+    ast::{Ast, NodeId, NodeKind},
+    bytecode::{MethodParameterCount, MethodSignature, Opcode, Opr24},
+    error::{LanguageError, LanguageErrorKind},
+};
+
+/// What a comprehension's clauses need to know about the collection being built.
+struct Collection {
+    variable: VariablePlace,
+    /// The `CallMethod` operand adding an element to the collection.
+    add: Opr24,
+}
+
+impl<'e> CodeGenerator<'e> {
+    /// Generates code for a list or dict comprehension.
+    ///
+    /// Comprehensions are lowered to nested loops, which add elements to an initially empty
+    /// collection directly; no closures or intermediate collections are created.
+    pub(super) fn generate_comprehension(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (element, _) = ast.node_pair(node);
+        let clauses = ast.children(node).unwrap();
+
+        let (create, add, argument_count) = match ast.kind(node) {
+            NodeKind::ListComprehension => (Opcode::CreateList, "push", 2),
+            NodeKind::DictComprehension => (Opcode::CreateDict, "insert", 3),
+            _ => unreachable!(),
+        };
+        let signature = MethodSignature::new(
+            Rc::from(add),
+            MethodParameterCount::from_count_with_self(argument_count),
+        );
+        let add = self
+            .env
+            .get_or_create_method_index(&signature)
+            .map_err(|kind| ast.error(node, kind))?;
+
+        self.push_scope();
+        let variable = self
+            .create_variable("<collection>", VariableAllocation::Allocate)
+            .map_err(|kind| ast.error(node, kind))?;
+        self.chunk.emit((create, Opr24::from(0u8)));
+        self.generate_variable_sink(variable);
+
+        let collection = Collection {
+            variable,
+            add: Opr24::pack((add.to_u16(), argument_count)),
+        };
+        self.generate_comprehension_clauses(ast, node, element, clauses, &collection)?;
+
+        self.generate_variable_load(variable);
+        self.pop_scope();
+
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates code for the first clause of a comprehension, with the rest of the clauses
+    /// nested inside of it. The innermost clause adds the element to the collection.
+    fn generate_comprehension_clauses(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        element: NodeId,
+        clauses: &[NodeId],
+        collection: &Collection,
+    ) -> Result<(), LanguageError> {
+        let Some((&clause, rest)) = clauses.split_first() else {
+            self.generate_variable_load(collection.variable);
+            if ast.kind(element) == NodeKind::Pair {
+                let (key, value) = ast.node_pair(element);
+                self.generate_node(ast, key, Expression::Used)?;
+                self.generate_node(ast, value, Expression::Used)?;
+            } else {
+                self.generate_node(ast, element, Expression::Used)?;
+            }
+            self.chunk.emit((Opcode::CallMethod, collection.add));
+            self.chunk.emit(Opcode::Discard);
+            return Ok(());
+        };
+
+        match ast.kind(clause) {
+            NodeKind::ComprehensionFor => {
+                let (binding, iterable) = ast.node_pair(clause);
+                self.push_scope();
+
+                let iterator = self
+                    .create_variable("<iterator>", VariableAllocation::Allocate)
+                    .map_err(|kind| ast.error(iterable, kind))?;
+                self.generate_node(ast, iterable, Expression::Used)?;
+                self.generate_variable_sink(iterator);
+
+                let start = self.chunk.len();
+                self.generate_variable_load(iterator);
+                self.chunk.emit((
+                    Opcode::CallMethod,
+                    Opr24::pack((self.library.builtin_traits.iterator_has_next.to_u16(), 1)),
+                ));
+                let jump_to_end = self.chunk.emit(Opcode::Nop);
+                self.chunk.emit(Opcode::Discard);
+
+                self.generate_variable_load(iterator);
+                self.chunk.emit((
+                    Opcode::CallMethod,
+                    Opr24::pack((self.library.builtin_traits.iterator_next.to_u16(), 1)),
+                ));
+                self.generate_pattern_destructuring(ast, binding, Expression::Discarded)?;
+                self.generate_comprehension_clauses(ast, node, element, rest, collection)?;
+
+                let jump_to_start = self
+                    .chunk
+                    .jump_backward(self.chunk.len(), start)
+                    .map_err(|_| ast.error(node, LanguageErrorKind::LoopTooLarge))?;
+                self.chunk.emit(jump_to_start);
+                let jump = self
+                    .chunk
+                    .jump_forward_if_falsy(jump_to_end, self.chunk.len())
+                    .map_err(|_| ast.error(node, LanguageErrorKind::LoopTooLarge))?;
+                self.chunk.patch(jump_to_end, jump);
+                self.chunk.emit(Opcode::Discard);
+
+                self.pop_scope();
+            }
+            NodeKind::ComprehensionIf => {
+                let (condition, _) = ast.node_pair(clause);
+                self.generate_node(ast, condition, Expression::Used)?;
+                let jump_if_falsy = self.chunk.emit(Opcode::Nop);
+                self.chunk.emit(Opcode::Discard);
+                self.generate_comprehension_clauses(ast, node, element, rest, collection)?;
+                let jump_to_end = self.chunk.emit(Opcode::Nop);
+
+                let jump = self
+                    .chunk
+                    .jump_forward_if_falsy(jump_if_falsy, self.chunk.len())
+                    .map_err(|_| ast.error(node, LanguageErrorKind::IfBranchTooLarge))?;
+                self.chunk.patch(jump_if_falsy, jump);
+                self.chunk.emit(Opcode::Discard);
+                let jump = self
+                    .chunk
+                    .jump_forward(jump_to_end, self.chunk.len())
+                    .map_err(|_| ast.error(node, LanguageErrorKind::IfBranchTooLarge))?;
+                self.chunk.patch(jump_to_end, jump);
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
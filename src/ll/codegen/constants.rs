@@ -0,0 +1,144 @@
+//! Evaluating constant expressions at compile time.
+
+use std::rc::Rc;
+
+use super::{CodeGenerator, ExpressionResult};
+use crate::ll::{
+    ast::{Ast, NodeId, NodeKind},
+    bytecode::Opcode,
+    error::LanguageError,
+    vm::floored_remainder,
+};
+
+/// The value of an expression that can be computed without running any code.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Constant {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Rc<str>),
+}
+
+impl Constant {
+    /// Returns whether the value counts as true in conditions.
+    pub(super) fn is_truthy(&self) -> bool {
+        !matches!(self, Constant::Nil | Constant::Boolean(false))
+    }
+
+    fn number(&self) -> Option<f64> {
+        match *self {
+            Constant::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+
+impl<'e> CodeGenerator<'e> {
+    /// Returns the value of the expression if it can be computed at compile time.
+    ///
+    /// Only operations whose result doesn't depend on the engine are folded: arithmetic and
+    /// comparisons on numbers never call overloads, and equality only does for structs and user
+    /// data. Methods on strings, `cat` included, come from the core library, which embedders may
+    /// replace, so calls to them are always left to run time. Arithmetic whose result isn't finite,
+    /// such as division by zero, is also left alone, since it may raise an error depending on the
+    /// engine's arithmetic mode.
+    pub(super) fn constant_value(&self, ast: &Ast, node: NodeId) -> Option<Constant> {
+        Self::fold(ast, node, self.depth)
+    }
+
+    fn fold(ast: &Ast, node: NodeId, depth: usize) -> Option<Constant> {
+        // Expressions nested too deeply are left for `generate_node` to report.
+        if depth >= Self::MAX_DEPTH {
+            return None;
+        }
+        let depth = depth + 1;
+        let (left, right) = ast.node_pair(node);
+        let number_operands = || {
+            Some((
+                Self::fold(ast, left, depth)?.number()?,
+                Self::fold(ast, right, depth)?.number()?,
+            ))
+        };
+        Some(match ast.kind(node) {
+            NodeKind::Nil => Constant::Nil,
+            NodeKind::False => Constant::Boolean(false),
+            NodeKind::True => Constant::Boolean(true),
+            NodeKind::Number => Constant::Number(ast.number(node)?),
+            NodeKind::String => Constant::String(Rc::clone(ast.string(node)?)),
+            NodeKind::Paren => Self::fold(ast, left, depth)?,
+
+            NodeKind::Negate => Constant::Number(-Self::fold(ast, left, depth)?.number()?),
+            NodeKind::Not => Constant::Boolean(!Self::fold(ast, left, depth)?.is_truthy()),
+
+            NodeKind::Add
+            | NodeKind::Subtract
+            | NodeKind::Multiply
+            | NodeKind::Divide
+            | NodeKind::FloorDivide
+            | NodeKind::Modulo => {
+                let (l, r) = number_operands()?;
+                let result = match ast.kind(node) {
+                    NodeKind::Add => l + r,
+                    NodeKind::Subtract => l - r,
+                    NodeKind::Multiply => l * r,
+                    NodeKind::Divide => l / r,
+                    NodeKind::FloorDivide => (l / r).floor(),
+                    _ => floored_remainder(l, r),
+                };
+                Constant::Number(Some(result).filter(|result| result.is_finite())?)
+            }
+
+            NodeKind::Equal | NodeKind::NotEqual => {
+                let equal = Self::fold(ast, left, depth)? == Self::fold(ast, right, depth)?;
+                Constant::Boolean(equal == (ast.kind(node) == NodeKind::Equal))
+            }
+            NodeKind::Less => number_operands().map(|(l, r)| Constant::Boolean(l < r))?,
+            NodeKind::LessEqual => number_operands().map(|(l, r)| Constant::Boolean(l <= r))?,
+            NodeKind::Greater => number_operands().map(|(l, r)| Constant::Boolean(l > r))?,
+            NodeKind::GreaterEqual => number_operands().map(|(l, r)| Constant::Boolean(l >= r))?,
+
+            // Both operands have to be constant, so that the one that isn't evaluated is still
+            // checked for errors by generating it.
+            NodeKind::And | NodeKind::Or => {
+                let left = Self::fold(ast, left, depth)?;
+                let right = Self::fold(ast, right, depth)?;
+                if left.is_truthy() == (ast.kind(node) == NodeKind::And) {
+                    right
+                } else {
+                    left
+                }
+            }
+
+            _ => return None,
+        })
+    }
+
+    /// Generates code that pushes a constant onto the stack.
+    pub(super) fn generate_constant(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        constant: &Constant,
+    ) -> Result<ExpressionResult, LanguageError> {
+        match constant {
+            Constant::Nil => {
+                self.chunk.emit(Opcode::PushNil);
+            }
+            Constant::Boolean(false) => {
+                self.chunk.emit(Opcode::PushFalse);
+            }
+            Constant::Boolean(true) => {
+                self.chunk.emit(Opcode::PushTrue);
+            }
+            Constant::Number(number) => {
+                self.chunk.emit(Opcode::PushNumber);
+                self.chunk.emit_number(*number);
+            }
+            Constant::String(string) => {
+                self.emit_push_string(string)
+                    .map_err(|kind| ast.error(node, kind))?;
+            }
+        }
+        Ok(ExpressionResult::Present)
+    }
+}
@@ -1,6 +1,10 @@
 //! Control flow expressions.
 
-use super::{variables::VariableAllocation, CodeGenerator, Expression, ExpressionResult};
+use std::rc::Rc;
+
+use super::{
+    constants::Constant, variables::VariableAllocation, CodeGenerator, Expression, ExpressionResult,
+};
 use crate::ll::{
     ast::{Ast, NodeId, NodeKind},
     bytecode::{Opcode, Opr24},
@@ -11,15 +15,38 @@ use crate::ll::{
 pub(super) struct BreakableBlock {
     /// A list of offsets where `breaks` should be backpatched.
     pub(super) breaks: Vec<usize>,
+    /// Whether the block needs to remember the stack height it was entered with, because a
+    /// `break` or `continue` leaves it early.
+    entered: bool,
+    /// The label of the loop, if it has one.
+    label: Option<Rc<str>>,
+    /// How many `try` blocks the loop is nested in. Jumping out of the loop also jumps out of the
+    /// `try` blocks inside of it, so their error handlers have to be removed.
+    try_depth: usize,
+    start: usize,
+}
+
+#[derive(Debug)]
+pub(super) struct Defer {
+    /// The `try_depth` of the error handler running the deferred code.
+    try_depth: usize,
+    /// Where the deferred code begins.
     start: usize,
+    node: NodeId,
 }
 
+/// Generates the condition or the body of a loop.
+type GenerateLoopPart<'a> = dyn Fn(&mut CodeGenerator<'_>) -> Result<(), LanguageError> + 'a;
+
 impl<'e> CodeGenerator<'e> {
     /// Pushes a new breakable block.
-    pub(super) fn push_breakable_block(&mut self) {
+    pub(super) fn push_breakable_block(&mut self, label: Option<Rc<str>>) {
         let start = self.chunk.emit(Opcode::Nop);
         self.breakable_blocks.push(BreakableBlock {
             breaks: Vec::new(),
+            entered: false,
+            label,
+            try_depth: self.try_depth,
             start,
         });
     }
@@ -27,17 +54,71 @@ impl<'e> CodeGenerator<'e> {
     /// Pops the topmost breakable block.
     pub(super) fn pop_breakable_block(&mut self) {
         let block = self.breakable_blocks.pop().unwrap();
-        if !block.breaks.is_empty() {
+        if block.entered {
             self.chunk.patch(block.start, Opcode::EnterBreakableBlock);
             for jump in block.breaks {
                 // Unwrapping is safe here because if the loop is too large the error was caught
                 // already before `pop_breakable_block` was called.
-                self.chunk
-                    .patch(jump, Opcode::jump_forward(jump, self.chunk.len()).unwrap());
+                let jump_to_end = self.chunk.jump_forward(jump, self.chunk.len()).unwrap();
+                self.chunk.patch(jump, jump_to_end);
             }
             self.chunk.emit((Opcode::ExitBreakableBlock, 1));
         }
     }
+
+    /// Finds the breakable block a `break` or `continue` with the given label jumps out of, and
+    /// marks it and all blocks nested in it as entered. Returns the index of the block and how
+    /// many blocks are nested inside of it.
+    fn target_breakable_block(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        label: NodeId,
+        outside_of_loop: LanguageErrorKind,
+    ) -> Result<(usize, usize), LanguageError> {
+        let index = if label != NodeId::EMPTY {
+            let name = ast.string(label).unwrap();
+            self.breakable_blocks
+                .iter()
+                .rposition(|block| block.label.as_ref() == Some(name))
+                .ok_or_else(|| {
+                    ast.error(label, LanguageErrorKind::LabelDoesNotExist(Rc::clone(name)))
+                })?
+        } else {
+            self.breakable_blocks
+                .len()
+                .checked_sub(1)
+                .ok_or_else(|| ast.error(node, outside_of_loop))?
+        };
+        if self.defer_barrier.is_some_and(|barrier| index < barrier) {
+            return Err(ast.error(node, LanguageErrorKind::JumpOutOfDefer));
+        }
+        for block in &mut self.breakable_blocks[index..] {
+            block.entered = true;
+        }
+        Ok((index, self.breakable_blocks.len() - index - 1))
+    }
+
+    /// Generates code that can never run, such as the branches of an `if` whose condition is
+    /// constant. The code is checked for errors and warnings, and then thrown away.
+    pub(super) fn generate_dead_code(
+        &mut self,
+        generate: impl FnOnce(&mut Self) -> Result<(), LanguageError>,
+    ) -> Result<(), LanguageError> {
+        let checkpoint = self.chunk.checkpoint();
+        let blocks: Vec<_> = self
+            .breakable_blocks
+            .iter()
+            .map(|block| (block.breaks.len(), block.entered))
+            .collect();
+        let result = generate(self);
+        self.chunk.rollback(checkpoint);
+        for (block, (breaks, entered)) in self.breakable_blocks.iter_mut().zip(blocks) {
+            block.breaks.truncate(breaks);
+            block.entered = entered;
+        }
+        result
+    }
 }
 
 impl<'e> CodeGenerator<'e> {
@@ -54,6 +135,21 @@ impl<'e> CodeGenerator<'e> {
         Ok(ExpressionResult::Present)
     }
 
+    /// Generates code for the condition of an `if` branch or a `while` loop. A `let` condition is
+    /// true when the value matches its pattern.
+    ///
+    /// The condition must be followed by a `JumpForwardIfFalsy` and a `Discard`, which comparisons
+    /// with numbers are fused with.
+    fn generate_condition(&mut self, ast: &Ast, node: NodeId) -> Result<(), LanguageError> {
+        if ast.kind(node) == NodeKind::Let {
+            self.generate_let_condition(ast, node)
+        } else {
+            self.generate_node(ast, node, Expression::Used)?;
+            self.fuse_number_comparison(ast, node);
+            Ok(())
+        }
+    }
+
     /// Generates code for an `if..elif..else..end` expression.
     pub(super) fn generate_if(
         &mut self,
@@ -62,20 +158,52 @@ impl<'e> CodeGenerator<'e> {
     ) -> Result<ExpressionResult, LanguageError> {
         let branches = ast.children(node).unwrap();
         let mut jumps_to_end = Vec::new();
+        // Whether the condition of the previous branch is left on the stack.
+        let mut condition_on_stack = false;
+        // Whether one of the branches generated so far is taken whenever it's reached, which
+        // makes the ones after it dead.
+        let mut always_taken = false;
+
+        for &branch in branches {
+            let then = ast.children(branch).unwrap();
+            let (condition, _) = ast.node_pair(branch);
+            let constant_condition = match ast.kind(branch) {
+                NodeKind::IfBranch if ast.kind(condition) != NodeKind::Let => {
+                    self.constant_value(ast, condition)
+                }
+                _ => None,
+            };
+            if always_taken || constant_condition.as_ref().is_some_and(|c| !c.is_truthy()) {
+                self.generate_dead_code(|generator| {
+                    generator.push_scope();
+                    if ast.kind(branch) == NodeKind::IfBranch {
+                        generator.generate_condition(ast, condition)?;
+                    }
+                    generator.generate_node_list(ast, then)?;
+                    generator.pop_scope();
+                    Ok(())
+                })?;
+                continue;
+            }
 
-        for (i, &branch) in branches.iter().enumerate() {
             // We need to discard the previous branch's condition (if there was a previous branch).
-            if i > 0 {
+            if condition_on_stack {
                 self.chunk.emit(Opcode::Discard);
+                condition_on_stack = false;
             }
 
-            let then = ast.children(branch).unwrap();
             match ast.kind(branch) {
+                NodeKind::IfBranch if constant_condition.is_some() => {
+                    self.push_scope();
+                    self.generate_node_list(ast, then)?;
+                    self.pop_scope();
+                    always_taken = true;
+                }
+
                 NodeKind::IfBranch => {
                     // Generate the condition.
-                    let (condition, _) = ast.node_pair(branch);
                     self.push_scope();
-                    self.generate_node(ast, condition, Expression::Used)?;
+                    self.generate_condition(ast, condition)?;
                     // Generate a Nop that is later backpatched with a ConditionalJumpForward.
                     let jump = self.chunk.emit(Opcode::Nop);
                     self.chunk.emit(Opcode::Discard); // The condition has to be discarded.
@@ -83,17 +211,19 @@ impl<'e> CodeGenerator<'e> {
                     self.pop_scope();
                     let jump_to_end = self.chunk.emit(Opcode::Nop);
                     jumps_to_end.push(jump_to_end);
-                    self.chunk.patch(
-                        jump,
-                        Opcode::jump_forward_if_falsy(jump, self.chunk.len())
-                            .map_err(|_| ast.error(branch, LanguageErrorKind::IfBranchTooLarge))?,
-                    );
+                    let jump_to_next_branch = self
+                        .chunk
+                        .jump_forward_if_falsy(jump, self.chunk.len())
+                        .map_err(|_| ast.error(branch, LanguageErrorKind::IfBranchTooLarge))?;
+                    self.chunk.patch(jump, jump_to_next_branch);
+                    condition_on_stack = true;
                 }
 
                 NodeKind::ElseBranch => {
                     self.push_scope();
                     self.generate_node_list(ast, then)?;
                     self.pop_scope();
+                    always_taken = true;
                 }
 
                 _ => unreachable!(),
@@ -101,18 +231,20 @@ impl<'e> CodeGenerator<'e> {
         }
 
         // If there was no `else` branch, we need to patch in an implicit one that returns `nil`.
-        if ast.kind(*branches.last().unwrap()) != NodeKind::ElseBranch {
-            self.chunk.emit(Opcode::Discard);
+        if !always_taken {
+            if condition_on_stack {
+                self.chunk.emit(Opcode::Discard);
+            }
             self.chunk.emit(Opcode::PushNil);
         }
 
         // Backpatch all jumps to end with an unconditional jump forward.
         for jump in jumps_to_end {
-            self.chunk.patch(
-                jump,
-                Opcode::jump_forward(jump, self.chunk.len())
-                    .map_err(|_| ast.error(node, LanguageErrorKind::IfExpressionTooLarge))?,
-            );
+            let jump_to_end = self
+                .chunk
+                .jump_forward(jump, self.chunk.len())
+                .map_err(|_| ast.error(node, LanguageErrorKind::IfExpressionTooLarge))?;
+            self.chunk.patch(jump, jump_to_end);
         }
 
         Ok(ExpressionResult::Present)
@@ -125,16 +257,20 @@ impl<'e> CodeGenerator<'e> {
         node: NodeId,
     ) -> Result<ExpressionResult, LanguageError> {
         let (left, right) = ast.node_pair(node);
+        if let Some(constant) = self.constant_value(ast, left) {
+            let short_circuits = !constant.is_truthy();
+            return self.generate_constant_operand(ast, left, &constant, right, short_circuits);
+        }
         self.push_scope();
         self.generate_node(ast, left, Expression::Used)?;
         let jump_past_right = self.chunk.emit(Opcode::Nop);
         self.chunk.emit(Opcode::Discard);
         self.generate_node(ast, right, Expression::Used)?;
-        self.chunk.patch(
-            jump_past_right,
-            Opcode::jump_forward_if_falsy(jump_past_right, self.chunk.len())
-                .map_err(|_| ast.error(node, LanguageErrorKind::OperatorRhsTooLarge))?,
-        );
+        let jump = self
+            .chunk
+            .jump_forward_if_falsy(jump_past_right, self.chunk.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::OperatorRhsTooLarge))?;
+        self.chunk.patch(jump_past_right, jump);
         self.pop_scope();
         Ok(ExpressionResult::Present)
     }
@@ -146,53 +282,93 @@ impl<'e> CodeGenerator<'e> {
         node: NodeId,
     ) -> Result<ExpressionResult, LanguageError> {
         let (left, right) = ast.node_pair(node);
+        if let Some(constant) = self.constant_value(ast, left) {
+            let short_circuits = constant.is_truthy();
+            return self.generate_constant_operand(ast, left, &constant, right, short_circuits);
+        }
         self.push_scope();
         self.generate_node(ast, left, Expression::Used)?;
         let jump_past_right = self.chunk.emit(Opcode::Nop);
         self.chunk.emit(Opcode::Discard);
         self.generate_node(ast, right, Expression::Used)?;
-        self.chunk.patch(
-            jump_past_right,
-            Opcode::jump_forward_if_truthy(jump_past_right, self.chunk.len())
-                .map_err(|_| ast.error(node, LanguageErrorKind::OperatorRhsTooLarge))?,
-        );
+        let jump = self
+            .chunk
+            .jump_forward_if_truthy(jump_past_right, self.chunk.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::OperatorRhsTooLarge))?;
+        self.chunk.patch(jump_past_right, jump);
+        self.pop_scope();
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates code for an `and` or `or` operator whose left operand is constant. If the left
+    /// operand short-circuits, it's the result and the right one is dead; otherwise the result is
+    /// the right operand.
+    fn generate_constant_operand(
+        &mut self,
+        ast: &Ast,
+        left: NodeId,
+        constant: &Constant,
+        right: NodeId,
+        short_circuits: bool,
+    ) -> Result<ExpressionResult, LanguageError> {
+        self.push_scope();
+        if short_circuits {
+            let _ = self.generate_constant(ast, left, constant)?;
+            self.generate_dead_code(|generator| {
+                generator.generate_node(ast, right, Expression::Used)
+            })?;
+        } else {
+            self.generate_node(ast, right, Expression::Used)?;
+        }
         self.pop_scope();
         Ok(ExpressionResult::Present)
     }
 
+    /// Generates code for a loop that runs its body for as long as its condition is true. Loops
+    /// without a condition only ever end by `break`ing out of them.
     pub(super) fn generate_conditional_loop(
         &mut self,
         ast: &Ast,
         node: NodeId,
-        generate_condition: &dyn Fn(&mut CodeGenerator<'_>) -> Result<(), LanguageError>,
-        generate_body: &dyn Fn(&mut CodeGenerator<'_>) -> Result<(), LanguageError>,
+        label: Option<Rc<str>>,
+        generate_condition: Option<&GenerateLoopPart<'_>>,
+        generate_body: &GenerateLoopPart<'_>,
     ) -> Result<(), LanguageError> {
         // The outer scope, so that variables can be declared in the condition.
         self.push_scope();
         // The breakable block.
-        self.push_breakable_block();
+        self.push_breakable_block(label);
 
         let start = self.chunk.len();
-        generate_condition(self)?;
-        let jump_to_end = self.chunk.emit(Opcode::Nop);
-        // Discard the condition if it's true.
-        self.chunk.emit(Opcode::Discard);
+        let jump_to_end = match generate_condition {
+            Some(generate_condition) => {
+                generate_condition(self)?;
+                let jump_to_end = self.chunk.emit(Opcode::Nop);
+                // Discard the condition if it's true.
+                self.chunk.emit(Opcode::Discard);
+                Some(jump_to_end)
+            }
+            None => None,
+        };
 
-        generate_body(self)?;
+        self.generate_loop_body(generate_body)?;
         // While loops don't yield a value.
         self.chunk.emit(Opcode::Discard);
 
-        self.chunk.emit(
-            Opcode::jump_backward(self.chunk.len(), start)
-                .map_err(|_| ast.error(node, LanguageErrorKind::LoopTooLarge))?,
-        );
-        self.chunk.patch(
-            jump_to_end,
-            Opcode::jump_forward_if_falsy(jump_to_end, self.chunk.len())
-                .map_err(|_| ast.error(node, LanguageErrorKind::LoopTooLarge))?,
-        );
-        // Discard the condition if it's false.
-        self.chunk.emit(Opcode::Discard);
+        let jump_to_start = self
+            .chunk
+            .jump_backward(self.chunk.len(), start)
+            .map_err(|_| ast.error(node, LanguageErrorKind::LoopTooLarge))?;
+        self.chunk.emit(jump_to_start);
+        if let Some(jump_to_end) = jump_to_end {
+            let jump = self
+                .chunk
+                .jump_forward_if_falsy(jump_to_end, self.chunk.len())
+                .map_err(|_| ast.error(node, LanguageErrorKind::LoopTooLarge))?;
+            self.chunk.patch(jump_to_end, jump);
+            // Discard the condition if it's false.
+            self.chunk.emit(Opcode::Discard);
+        }
 
         // Because loops are expressions, they must produce a value. That value is `nil`.
         self.chunk.emit(Opcode::PushNil);
@@ -210,16 +386,47 @@ impl<'e> CodeGenerator<'e> {
         &mut self,
         ast: &Ast,
         node: NodeId,
+        label: Option<Rc<str>>,
     ) -> Result<ExpressionResult, LanguageError> {
         let (condition, _) = ast.node_pair(node);
         let body = ast.children(node).unwrap();
 
-        self.generate_conditional_loop(
-            ast,
-            node,
-            &|generator| generator.generate_node(ast, condition, Expression::Used),
-            &|generator| generator.generate_node_list(ast, body),
-        )?;
+        let constant_condition = match ast.kind(condition) {
+            NodeKind::Let => None,
+            _ => self.constant_value(ast, condition),
+        };
+        let generate_condition =
+            |generator: &mut CodeGenerator<'_>| generator.generate_condition(ast, condition);
+        let generate_body =
+            |generator: &mut CodeGenerator<'_>| generator.generate_node_list(ast, body);
+        match constant_condition {
+            // A loop whose condition is always false never runs its body.
+            Some(condition) if !condition.is_truthy() => {
+                self.generate_dead_code(|generator| {
+                    generator.generate_conditional_loop(
+                        ast,
+                        node,
+                        label,
+                        Some(&generate_condition),
+                        &generate_body,
+                    )
+                })?;
+                self.chunk.emit(Opcode::PushNil);
+            }
+            // A loop whose condition is always true doesn't need to check it.
+            Some(_) => {
+                self.generate_conditional_loop(ast, node, label, None, &generate_body)?;
+            }
+            None => {
+                self.generate_conditional_loop(
+                    ast,
+                    node,
+                    label,
+                    Some(&generate_condition),
+                    &generate_body,
+                )?;
+            }
+        }
 
         Ok(ExpressionResult::Present)
     }
@@ -229,6 +436,7 @@ impl<'e> CodeGenerator<'e> {
         &mut self,
         ast: &Ast,
         node: NodeId,
+        label: Option<Rc<str>>,
     ) -> Result<ExpressionResult, LanguageError> {
         let (binding, iterator) = ast.node_pair(node);
         let body = ast.children(node).unwrap();
@@ -244,7 +452,8 @@ impl<'e> CodeGenerator<'e> {
         self.generate_conditional_loop(
             ast,
             node,
-            &|generator| {
+            label,
+            Some(&|generator| {
                 generator.generate_variable_load(iterator_var);
                 generator.chunk.emit((
                     Opcode::CallMethod,
@@ -254,7 +463,7 @@ impl<'e> CodeGenerator<'e> {
                     )),
                 ));
                 Ok(())
-            },
+            }),
             &|generator| {
                 generator.generate_variable_load(iterator_var);
                 generator.chunk.emit((
@@ -272,24 +481,73 @@ impl<'e> CodeGenerator<'e> {
         Ok(ExpressionResult::Present)
     }
 
+    /// Generates code for a labeled loop.
+    pub(super) fn generate_labeled(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (label, looped) = ast.node_pair(node);
+        let label = Some(Rc::clone(ast.string(label).unwrap()));
+        match ast.kind(looped) {
+            NodeKind::While => self.generate_while(ast, looped, label),
+            NodeKind::For => self.generate_for(ast, looped, label),
+            _ => unreachable!("the parser only allows labeling loops"),
+        }
+    }
+
     /// Generates a `break` expression.
     pub(super) fn generate_break(
         &mut self,
         ast: &Ast,
         node: NodeId,
     ) -> Result<ExpressionResult, LanguageError> {
-        let (right, _) = ast.node_pair(node);
+        let (right, label) = ast.node_pair(node);
+        let (index, nested) =
+            self.target_breakable_block(ast, node, label, LanguageErrorKind::BreakOutsideOfLoop)?;
         if right != NodeId::EMPTY {
             self.generate_node(ast, right, Expression::Used)?;
         } else {
             let _ = self.generate_nil();
         }
-        let jump = self.chunk.emit(Opcode::Nop);
-        if let Some(block) = self.breakable_blocks.last_mut() {
-            block.breaks.push(jump);
-        } else {
-            return Err(ast.error(node, LanguageErrorKind::BreakOutsideOfLoop));
+        self.generate_try_exits(ast, self.breakable_blocks[index].try_depth)?;
+        // The target block exits itself at its end, but the blocks nested inside of it have to be
+        // exited before jumping there.
+        if nested > 0 {
+            self.chunk.emit((Opcode::ExitBreakableBlock, nested as u16));
         }
+        let jump = self.chunk.emit(Opcode::Nop);
+        self.breakable_blocks[index].breaks.push(jump);
+        Ok(ExpressionResult::NoReturn)
+    }
+
+    /// Generates a `continue` expression.
+    pub(super) fn generate_continue(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (label, _) = ast.node_pair(node);
+        let (index, nested) = self.target_breakable_block(
+            ast,
+            node,
+            label,
+            LanguageErrorKind::ContinueOutsideOfLoop,
+        )?;
+        let start = self.breakable_blocks[index].start;
+        self.generate_try_exits(ast, self.breakable_blocks[index].try_depth)?;
+        // Exiting the breakable block gets rid of any temporaries left on the stack by the
+        // expression `continue` appears in. The jump then lands on the instruction that enters the
+        // block again, which is immediately followed by the loop's condition.
+        let _ = self.generate_nil();
+        self.chunk
+            .emit((Opcode::ExitBreakableBlock, nested as u16 + 1));
+        self.chunk.emit(Opcode::Discard);
+        let jump = self
+            .chunk
+            .jump_backward(self.chunk.len(), start)
+            .map_err(|_| ast.error(node, LanguageErrorKind::LoopTooLarge))?;
+        self.chunk.emit(jump);
         Ok(ExpressionResult::NoReturn)
     }
 
@@ -300,6 +558,9 @@ impl<'e> CodeGenerator<'e> {
         node: NodeId,
     ) -> Result<ExpressionResult, LanguageError> {
         let (value, _) = ast.node_pair(node);
+        if self.defer_barrier.is_some() {
+            return Err(ast.error(node, LanguageErrorKind::JumpOutOfDefer));
+        }
         // Unlike breaking out of a loop, returning is super simple, as it implies that all locals
         // are taken off the stack.
         if value == NodeId::EMPTY {
@@ -307,7 +568,152 @@ impl<'e> CodeGenerator<'e> {
         } else {
             self.generate_node(ast, value, Expression::Used)?;
         }
+        self.generate_try_exits(ast, 0)?;
         self.chunk.emit(Opcode::Return);
         Ok(ExpressionResult::NoReturn)
     }
+
+    /// Removes the error handlers of the `try` blocks and `defer` expressions being jumped out of,
+    /// such that only `try_depth` of them remain. Deferred code is run along the way.
+    fn generate_try_exits(&mut self, ast: &Ast, try_depth: usize) -> Result<(), LanguageError> {
+        let mut exits = 0_u16;
+        for depth in (try_depth + 1..=self.try_depth).rev() {
+            exits += 1;
+            if let Some(defer) = self.defers.iter().rfind(|defer| defer.try_depth == depth) {
+                let (start, node) = (defer.start, defer.node);
+                // The handler has to be removed first, so that an error in the deferred code
+                // doesn't run it again.
+                self.chunk.emit((Opcode::ExitTry, exits));
+                exits = 0;
+                self.generate_deferred_jump(ast, node, Opcode::RunDeferred, start)?;
+            }
+        }
+        if exits > 0 {
+            self.chunk.emit((Opcode::ExitTry, exits));
+        }
+        Ok(())
+    }
+
+    /// Generates code for a `defer` expression. The deferred code is generated in place, but is
+    /// jumped over; it runs when the block the `defer` is in is exited, or an error propagates
+    /// out of it.
+    pub(super) fn generate_defer(&mut self, ast: &Ast, node: NodeId) -> Result<(), LanguageError> {
+        let (deferred, _) = ast.node_pair(node);
+
+        let jump_past_deferred = self.chunk.emit(Opcode::Nop);
+        let start = self.chunk.len();
+        let outer_barrier = self.defer_barrier.replace(self.breakable_blocks.len());
+        // The deferred code may run after variables declared later in the block are created, so it
+        // must not reuse their stack slots.
+        let result = self.generate_in_reserved_scope(|generator| {
+            generator.generate_deferred_code(|generator| {
+                generator.generate_node(ast, deferred, Expression::Discarded)
+            })
+        });
+        self.defer_barrier = outer_barrier;
+        result?;
+        self.chunk.emit(Opcode::ExitDeferred);
+        let jump = self
+            .chunk
+            .jump_forward(jump_past_deferred, self.chunk.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::DeferTooLarge))?;
+        self.chunk.patch(jump_past_deferred, jump);
+
+        self.generate_deferred_jump(ast, node, Opcode::EnterDefer, start)?;
+        self.try_depth += 1;
+        self.defers.push(Defer {
+            try_depth: self.try_depth,
+            start,
+            node,
+        });
+        Ok(())
+    }
+
+    /// Runs the deferred code of all `defer` expressions in a block that's about to end, starting
+    /// with the last one. `defer_count` is the number of `defer`s from outer blocks.
+    pub(super) fn generate_defer_exits(
+        &mut self,
+        ast: &Ast,
+        defer_count: usize,
+    ) -> Result<(), LanguageError> {
+        while self.defers.len() > defer_count {
+            let Defer { start, node, .. } = self.defers.pop().unwrap();
+            self.chunk.emit((Opcode::ExitTry, 1_u16));
+            self.generate_deferred_jump(ast, node, Opcode::RunDeferred, start)?;
+            self.try_depth -= 1;
+        }
+        Ok(())
+    }
+
+    /// Emits an `EnterDefer` or `RunDeferred` instruction pointing back to deferred code.
+    fn generate_deferred_jump(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        opcode: Opcode,
+        start: usize,
+    ) -> Result<(), LanguageError> {
+        let offset = Opr24::try_from(Opcode::backward_jump_offset(self.chunk.len(), start))
+            .map_err(|_| ast.error(node, LanguageErrorKind::DeferTooLarge))?;
+        self.chunk.emit((opcode, offset));
+        Ok(())
+    }
+
+    /// Generates code for a `try..catch..end` expression.
+    pub(super) fn generate_try(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (body, catch) = ast.node_pair(node);
+        let (error, trace) = ast.node_pair(catch);
+        let handler = ast.children(catch).unwrap();
+
+        // Generate a Nop that is later backpatched with an EnterTry pointing to the catch block.
+        let enter = self.chunk.emit(Opcode::Nop);
+        self.try_depth += 1;
+        self.generate_node(ast, body, Expression::Used)?;
+        self.try_depth -= 1;
+        self.chunk.emit((Opcode::ExitTry, 1_u16));
+        let jump_past_catch = self.chunk.emit(Opcode::Nop);
+
+        let catch_offset = Opr24::try_from(Opcode::forward_jump_offset(enter, self.chunk.len()))
+            .map_err(|_| ast.error(node, LanguageErrorKind::TryTooLarge))?;
+        self.chunk.patch(enter, (Opcode::EnterTry, catch_offset));
+
+        // When an error is caught, the VM pushes the error value followed by the stack trace.
+        self.push_scope();
+        if trace != NodeId::EMPTY {
+            self.generate_pattern_destructuring(ast, trace, Expression::Discarded)?;
+        } else {
+            self.chunk.emit(Opcode::Discard);
+        }
+        if error != NodeId::EMPTY {
+            self.generate_pattern_destructuring(ast, error, Expression::Discarded)?;
+        } else {
+            self.chunk.emit(Opcode::Discard);
+        }
+        self.generate_node_list(ast, handler)?;
+        self.pop_scope();
+
+        let jump = self
+            .chunk
+            .jump_forward(jump_past_catch, self.chunk.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::TryTooLarge))?;
+        self.chunk.patch(jump_past_catch, jump);
+
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates code for a `raise` expression.
+    pub(super) fn generate_raise(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (value, _) = ast.node_pair(node);
+        self.generate_node(ast, value, Expression::Used)?;
+        self.chunk.emit(Opcode::Raise);
+        Ok(ExpressionResult::NoReturn)
+    }
 }
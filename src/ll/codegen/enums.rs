@@ -0,0 +1,199 @@
+//! Code generation for enums.
+
+use std::rc::Rc;
+
+use super::{variables::VariableAllocation, CodeGenerator, ExpressionResult};
+use crate::{
+    ll::{
+        ast::{Ast, NodeId, NodeKind},
+        bytecode::{
+            Chunk, Function, FunctionDeclaration, FunctionKind, MethodParameterCount,
+            MethodSignature, Opcode, Opr24, Prototype, Visibility, VALUES_FIELD, VARIANT_FIELD,
+        },
+        error::{LanguageError, LanguageErrorKind, RenderedSignature},
+        value::{RawValue, ValueKind},
+    },
+    FunctionParameterCount,
+};
+
+impl<'e> CodeGenerator<'e> {
+    /// Generates code for an enum declaration.
+    ///
+    /// An enum is a struct, whose variants are static functions creating instances with two hidden
+    /// fields: the name of the variant, and a tuple of the values it carries.
+    pub(super) fn generate_enum(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (name, _) = ast.node_pair(node);
+        let name = ast.string(name).unwrap();
+        let items = ast.children(node).unwrap();
+
+        self.chunk.emit(Opcode::CreateType);
+        self.chunk.emit_string(name);
+        let variable = self
+            .create_variable(name, VariableAllocation::Allocate)
+            .map_err(|kind| ast.error(node, kind))?;
+        self.generate_variable_assign(variable);
+        self.generate_implementation(ast, node, items, true)?;
+
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates the constructor of an enum variant, and adds it to the enum's static functions.
+    pub(super) fn generate_enum_variant(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        proto: &mut Prototype,
+    ) -> Result<(), LanguageError> {
+        let (name_node, fields) = match ast.kind(node) {
+            NodeKind::Call => (ast.node_pair(node).0, ast.children(node).unwrap()),
+            _ => (node, &[][..]),
+        };
+        if ast.kind(name_node) != NodeKind::Identifier {
+            return Err(ast.error(node, LanguageErrorKind::InvalidEnumItem));
+        }
+        if let Some(&field) = fields
+            .iter()
+            .find(|&&field| ast.kind(field) != NodeKind::Identifier)
+        {
+            return Err(ast.error(field, LanguageErrorKind::InvalidEnumItem));
+        }
+        let name = Rc::clone(ast.string(name_node).unwrap());
+        let field_count = Opr24::try_from(fields.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::TupleHasTooManyElements))?;
+
+        let struct_data = self.struct_data.as_deref().unwrap();
+        let variant_field = struct_data.get_field(VARIANT_FIELD).unwrap();
+        let values_field = struct_data.get_field(VALUES_FIELD).unwrap();
+
+        // The constructor is called with the enum in its receiver slot, followed by the variant's
+        // values. The receiver slot is reused for the instance once it's created.
+        let mut chunk = Chunk::new(Rc::clone(&self.chunk.module_name));
+        chunk.codegen_location = ast.location(node);
+        chunk.emit((Opcode::CreateTuple, field_count));
+        chunk.emit((Opcode::GetLocal, Opr24::from(0_u8)));
+        chunk.emit((Opcode::CreateStruct, Opr24::from(2_u8)));
+        chunk.emit((Opcode::AssignLocal, Opr24::from(0_u8)));
+        chunk.emit((Opcode::SinkField, values_field));
+        let name_index = self
+            .env
+            .get_or_create_string(self.gc, &name)
+            .map_err(|kind| ast.error(node, kind))?;
+        chunk.emit((Opcode::PushString, name_index));
+        chunk.emit((Opcode::GetLocal, Opr24::from(0_u8)));
+        chunk.emit((Opcode::SinkField, variant_field));
+        chunk.emit(Opcode::Return);
+
+        let parameter_count = MethodParameterCount::from_count_without_self(fields.len())
+            .map_err(|_| ast.error(node, LanguageErrorKind::TooManyParameters))?;
+        let function_id = self
+            .env
+            .create_function(Function {
+                name: Rc::clone(&name),
+                parameter_count: FunctionParameterCount::Fixed(u16::from(
+                    parameter_count.to_count_without_self(),
+                )),
+                kind: FunctionKind::Bytecode {
+                    chunk: Rc::new(chunk),
+                    captured_locals: vec![],
+                },
+                hidden_in_stack_traces: false,
+                traced: false,
+                declaration: Some(Rc::new(FunctionDeclaration {
+                    module_name: Rc::clone(&self.chunk.module_name),
+                    location: ast.location(node),
+                    parameter_names: fields
+                        .iter()
+                        .map(|&field| Rc::clone(ast.string(field).unwrap()))
+                        .collect(),
+                })),
+                visibility: Visibility::Public,
+                impl_block: self.impl_block,
+            })
+            .map_err(|kind| ast.error(node, kind))?;
+
+        let signature = MethodSignature::new(name, parameter_count);
+        let method_id = self
+            .env
+            .get_or_create_method_index(&signature)
+            .map_err(|kind| ast.error(node, kind))?;
+        if proto.statics.insert(method_id, function_id).is_some() {
+            return Err(ast.error(
+                name_node,
+                LanguageErrorKind::MethodAlreadyImplemented(RenderedSignature {
+                    name: signature.name,
+                    parameter_count: parameter_count.to_count_without_self(),
+                    trait_name: None,
+                }),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Adds an `eq` method comparing enum instances by their variants and values, unless the enum
+    /// declares its own.
+    pub(super) fn generate_enum_equality(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        proto: &mut Prototype,
+    ) -> Result<(), LanguageError> {
+        let signature = MethodSignature::new(
+            Rc::from("eq"),
+            MethodParameterCount::from_count_without_self(1_usize).unwrap(),
+        );
+        let method_id = self
+            .env
+            .get_or_create_method_index(&signature)
+            .map_err(|kind| ast.error(node, kind))?;
+        if proto.instance.contains_key(&method_id) {
+            return Ok(());
+        }
+        let function_id = self
+            .env
+            .create_function(Function {
+                name: signature.name,
+                parameter_count: FunctionParameterCount::Fixed(1),
+                kind: FunctionKind::Foreign(Box::new(|_, _, arguments| {
+                    Ok(RawValue::from(variants_equal(arguments[0], arguments[1])))
+                })),
+                hidden_in_stack_traces: false,
+                traced: false,
+                declaration: None,
+                visibility: Visibility::Public,
+                impl_block: None,
+            })
+            .map_err(|kind| ast.error(node, kind))?;
+        proto.instance.insert(method_id, function_id);
+        Ok(())
+    }
+}
+
+/// Compares two values, where enum instances are equal if they're the same variant of the same
+/// enum and carry equal values.
+fn variants_equal(a: RawValue, b: RawValue) -> bool {
+    if a.kind() == ValueKind::Struct && b.kind() == ValueKind::Struct {
+        let (a, b) = unsafe {
+            (
+                a.get_raw_struct_unchecked().get(),
+                b.get_raw_struct_unchecked().get(),
+            )
+        };
+        if let (Some((a_variant, a_values)), Some((b_variant, b_values))) =
+            unsafe { (a.enum_variant(), b.enum_variant()) }
+        {
+            return std::ptr::eq(unsafe { a.dtable() }, unsafe { b.dtable() })
+                && a_variant == b_variant
+                && a_values.len() == b_values.len()
+                && a_values
+                    .iter()
+                    .zip(b_values)
+                    .all(|(&a, &b)| variants_equal(a, b));
+        }
+    }
+    a == b
+}
@@ -5,8 +5,10 @@ use std::{mem, rc::Rc};
 use super::{variables::VariableAllocation, CodeGenerator, Expression, ExpressionResult};
 use crate::{
     ll::{
-        ast::{Ast, NodeId},
-        bytecode::{Function, FunctionIndex, FunctionKind, Opcode, Opr24},
+        ast::{Ast, NodeId, NodeKind},
+        bytecode::{
+            Function, FunctionDeclaration, FunctionIndex, FunctionKind, Opcode, Opr24, Visibility,
+        },
         error::{LanguageError, LanguageErrorKind},
     },
     FunctionParameterCount,
@@ -54,6 +56,7 @@ impl FunctionCallConv {
 pub(super) struct GenerateFunctionOptions {
     pub(crate) name: Rc<str>,
     pub(crate) call_conv: FunctionCallConv,
+    pub(crate) visibility: Visibility,
 }
 
 pub(super) struct GeneratedFunction {
@@ -71,11 +74,21 @@ impl<'e> CodeGenerator<'e> {
         &mut self,
         ast: &Ast,
         node: NodeId,
-        GenerateFunctionOptions { name, call_conv }: GenerateFunctionOptions,
+        GenerateFunctionOptions {
+            name,
+            call_conv,
+            visibility,
+        }: GenerateFunctionOptions,
     ) -> Result<GeneratedFunction, LanguageError> {
         let (head, body) = ast.node_pair(node);
+        if body == NodeId::EMPTY {
+            // Methods in `impl` blocks get here without being checked for a body, such as when a
+            // misspelled function kind makes the parser end the function early.
+            return Err(ast.error(node, LanguageErrorKind::MissingFunctionBody));
+        }
         let (_, parameters) = ast.node_pair(head);
         let parameter_list = ast.children(parameters).unwrap();
+        let rest_parameter = Self::find_rest_parameter(ast, parameter_list, call_conv)?;
 
         let mut generator = CodeGenerator::new(
             Rc::clone(&self.chunk.module_name),
@@ -83,6 +96,9 @@ impl<'e> CodeGenerator<'e> {
             self.library,
             self.gc,
         );
+        generator.depth = self.depth;
+        generator.global_view = self.global_view;
+        generator.impl_block = self.impl_block;
         // NOTE: Hopefully the allocation from this mem::take gets optimized out.
         generator.locals.parent = Some(mem::take(&mut self.locals));
         if call_conv.has_field_access() {
@@ -108,11 +124,32 @@ impl<'e> CodeGenerator<'e> {
                 .create_variable("<receiver>", VariableAllocation::Inherit)
                 .unwrap()
         };
-        for &parameter in parameter_list {
-            let parameter_name = ast.string(parameter).unwrap();
-            generator
-                .create_variable(parameter_name, VariableAllocation::Inherit)
-                .map_err(|kind| ast.error(parameter, kind))?;
+        let mut patterns = Vec::new();
+        for (index, &parameter) in parameter_list.iter().enumerate() {
+            // The rest parameter receives the list of surplus arguments in its slot, like any
+            // other parameter.
+            let parameter = match ast.kind(parameter) {
+                NodeKind::RestParameter => ast.node_pair(parameter).0,
+                _ => parameter,
+            };
+            if ast.kind(parameter) == NodeKind::Identifier {
+                let parameter_name = ast.string(parameter).unwrap();
+                generator
+                    .create_variable(parameter_name, VariableAllocation::Inherit)
+                    .map_err(|kind| ast.error(parameter, kind))?;
+            } else {
+                // Arguments passed to pattern parameters are stored in hidden variables first,
+                // and destructured once all arguments have their slots.
+                let variable = generator
+                    .create_variable(&format!("<parameter {index}>"), VariableAllocation::Inherit)
+                    .map_err(|kind| ast.error(parameter, kind))?;
+                patterns.push((variable, parameter));
+            }
+        }
+        for (variable, pattern) in patterns {
+            generator.chunk.codegen_location = ast.location(pattern);
+            generator.generate_variable_load(variable);
+            generator.generate_pattern_destructuring(ast, pattern, Expression::Discarded)?;
         }
 
         // In constructors, we have to create `self` explicitly.
@@ -173,21 +210,41 @@ impl<'e> CodeGenerator<'e> {
 
         // Take back what was taken from the parent generator.
         self.locals = generator.locals.parent.take().unwrap();
+        self.warnings.append(&mut generator.warnings);
         if call_conv.has_field_access() {
             self.struct_data = generator.struct_data;
         }
 
         // Construct the function.
+        generator.chunk.upvalue_names = generator.locals.capture_names;
         let parameter_count = u16::try_from(parameter_list.len())
             .map_err(|_| ast.error(parameters, LanguageErrorKind::TooManyParameters))?;
         let function = Function {
             name,
-            parameter_count: FunctionParameterCount::Fixed(parameter_count),
+            parameter_count: if rest_parameter.is_some() {
+                FunctionParameterCount::AtLeast(parameter_count - 1)
+            } else {
+                FunctionParameterCount::Fixed(parameter_count)
+            },
             kind: FunctionKind::Bytecode {
                 chunk: Rc::new(generator.chunk),
                 captured_locals: generator.locals.captures,
             },
             hidden_in_stack_traces: false,
+            traced: false,
+            declaration: Some(Rc::new(FunctionDeclaration {
+                module_name: Rc::clone(&self.chunk.module_name),
+                location: ast.location(node),
+                parameter_names: parameter_list
+                    .iter()
+                    .map(|&parameter| match ast.string(parameter) {
+                        Some(name) => Rc::clone(name),
+                        None => Rc::from(ast.pattern_to_string(parameter)),
+                    })
+                    .collect(),
+            })),
+            visibility,
+            impl_block: self.impl_block,
         };
         let function_id = self
             .env
@@ -200,15 +257,42 @@ impl<'e> CodeGenerator<'e> {
         })
     }
 
+    /// Returns the function's rest parameter, if it has one, ensuring it's the last parameter of a
+    /// bare function. Methods can't have rest parameters, because they're dispatched by their
+    /// exact number of arguments.
+    fn find_rest_parameter(
+        ast: &Ast,
+        parameter_list: &[NodeId],
+        call_conv: FunctionCallConv,
+    ) -> Result<Option<NodeId>, LanguageError> {
+        let Some(index) = parameter_list
+            .iter()
+            .position(|&parameter| ast.kind(parameter) == NodeKind::RestParameter)
+        else {
+            return Ok(None);
+        };
+        let rest_parameter = parameter_list[index];
+        if index != parameter_list.len() - 1 {
+            return Err(ast.error(rest_parameter, LanguageErrorKind::RestParameterNotLast));
+        }
+        if !matches!(call_conv, FunctionCallConv::Bare) {
+            return Err(ast.error(rest_parameter, LanguageErrorKind::RestParameterInMethod));
+        }
+        Ok(Some(rest_parameter))
+    }
+
     /// Ensures that a `Func` node is a valid bare function - without a kind, and with a body.
     fn ensure_valid_bare_function(ast: &Ast, node: NodeId) -> Result<(), LanguageError> {
         let (head, body) = ast.node_pair(node);
         let (_name, parameters) = ast.node_pair(head);
 
-        let function_kind = ast.node_pair(parameters).0;
+        let (function_kind, visibility) = ast.node_pair(parameters);
         if function_kind != NodeId::EMPTY {
             return Err(ast.error(function_kind, LanguageErrorKind::FunctionKindOutsideImpl));
         }
+        if visibility != NodeId::EMPTY {
+            return Err(ast.error(visibility, LanguageErrorKind::VisibilityOutsideImpl));
+        }
         if body == NodeId::EMPTY {
             return Err(ast.error(node, LanguageErrorKind::MissingFunctionBody));
         }
@@ -231,6 +315,9 @@ impl<'e> CodeGenerator<'e> {
         let variable = self
             .create_variable(name, VariableAllocation::Allocate)
             .map_err(|kind| ast.error(name_node, kind))?;
+        if let Some(result) = self.try_defer_function(ast, node, variable) {
+            return Ok(result);
+        }
 
         let function = self.generate_function(
             ast,
@@ -238,6 +325,7 @@ impl<'e> CodeGenerator<'e> {
             GenerateFunctionOptions {
                 name: Rc::clone(name),
                 call_conv: FunctionCallConv::Bare,
+                visibility: Visibility::Public,
             },
         )?;
 
@@ -262,6 +350,7 @@ impl<'e> CodeGenerator<'e> {
             GenerateFunctionOptions {
                 name: Rc::from("<anonymous>"),
                 call_conv: FunctionCallConv::Bare,
+                visibility: Visibility::Public,
             },
         )?;
 
@@ -1,4 +1,4 @@
-//! Code generation for `impl` blocks.
+//! Code generation for `impl` blocks, and the methods of enums.
 
 use std::rc::Rc;
 
@@ -8,7 +8,10 @@ use super::{
 };
 use crate::ll::{
     ast::{Ast, NodeId, NodeKind},
-    bytecode::{ImplementedTraitIndex, MethodParameterCount, MethodSignature, Opcode, Prototype},
+    bytecode::{
+        ImplementedTraitIndex, MethodParameterCount, MethodSignature, Opcode, Prototype,
+        Visibility, VALUES_FIELD, VARIANT_FIELD,
+    },
     error::{LanguageError, LanguageErrorKind, RenderedSignature},
 };
 
@@ -17,6 +20,9 @@ struct ImplGenerationState<'p> {
     proto: &'p mut Prototype,
     has_constructor: bool,
     implemented_trait_index: Option<ImplementedTraitIndex>,
+    /// Whether the items belong to an enum rather than an `impl` block, which means they may also
+    /// be variants.
+    is_enum: bool,
 }
 
 impl<'e> CodeGenerator<'e> {
@@ -31,31 +37,72 @@ impl<'e> CodeGenerator<'e> {
 
         // Push the implementee onto the stack first. Implemented traits follow.
         self.generate_node(ast, implementee, Expression::Used)?;
+        self.generate_implementation(ast, node, items, false)?;
 
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates the items of an `impl` block or an enum, and implements the type at the top of
+    /// the stack with them.
+    pub(super) fn generate_implementation(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        items: &[NodeId],
+        is_enum: bool,
+    ) -> Result<(), LanguageError> {
         // There's no need to save any old struct data because `impl` blocks don't nest freely.
         // Yes, you can create an `impl` block in a function inside this `impl` block, but that
         // function is handled by a different generator.
         self.struct_data = Some(Box::default());
+        if is_enum {
+            // The hidden fields of enums must come first, such that they have the indices variant
+            // constructors expect.
+            let struct_data = self.struct_data.as_deref_mut().unwrap();
+            for field in [VARIANT_FIELD, VALUES_FIELD] {
+                struct_data
+                    .get_or_create_field(field)
+                    .map_err(|kind| ast.error(node, kind))?;
+            }
+        }
+        // The enclosing block however does need to be saved, because that function's generator
+        // inherits it so that closures can call private methods.
+        let proto_id = self
+            .env
+            .reserve_prototype()
+            .map_err(|kind| ast.error(node, kind))?;
+        let enclosing_impl_block = self.impl_block.replace(proto_id);
 
         let mut proto = Prototype::default();
         let mut state = ImplGenerationState {
             proto: &mut proto,
             has_constructor: false,
             implemented_trait_index: None,
+            is_enum,
         };
         for &node in items {
             self.generate_impl_item(ast, node, &mut state, true)?;
         }
+        proto.has_constructor = state.has_constructor;
+        if is_enum {
+            self.generate_enum_equality(ast, node, &mut proto)?;
+        }
 
-        let proto_id = self
-            .env
-            .create_prototype(proto)
-            .map_err(|kind| ast.error(node, kind))?;
+        let struct_data = self.struct_data.as_deref().unwrap();
+        let mut fields: Vec<_> = struct_data.fields.iter().collect();
+        fields.sort_by_key(|&(_, &index)| usize::from(index));
+        proto.fields = fields
+            .into_iter()
+            .map(|(name, _)| Rc::clone(name))
+            .collect();
+
+        self.env.set_prototype(proto_id, proto);
         self.chunk.emit((Opcode::Implement, proto_id.to_opr24()));
 
         self.struct_data = None;
+        self.impl_block = enclosing_impl_block;
 
-        Ok(ExpressionResult::Present)
+        Ok(())
     }
 
     /// Generates code for a single item in an `impl` block.
@@ -74,11 +121,14 @@ impl<'e> CodeGenerator<'e> {
                     return Err(ast.error(node, LanguageErrorKind::MissingMethodName));
                 }
                 let name = Rc::clone(ast.string(name_node).unwrap());
-                let (kind, _) = ast.node_pair(parameters);
+                let (kind, visibility) = ast.node_pair(parameters);
 
                 let call_conv = match ast.kind(kind) {
                     NodeKind::Empty => FunctionCallConv::Instance,
                     NodeKind::Static => FunctionCallConv::Static,
+                    NodeKind::Constructor if state.is_enum => {
+                        return Err(ast.error(kind, LanguageErrorKind::ConstructorInEnum))
+                    }
                     NodeKind::Constructor => {
                         let allow_new_fields = !state.has_constructor;
                         state.has_constructor = true;
@@ -86,12 +136,21 @@ impl<'e> CodeGenerator<'e> {
                     }
                     _ => unreachable!(),
                 };
+                let visibility = match ast.kind(visibility) {
+                    NodeKind::Empty | NodeKind::Pub => Visibility::Public,
+                    NodeKind::Priv if state.implemented_trait_index.is_some() => {
+                        return Err(ast.error(visibility, LanguageErrorKind::PrivateTraitMethod))
+                    }
+                    NodeKind::Priv => Visibility::Private,
+                    _ => unreachable!(),
+                };
                 let function = self.generate_function(
                     ast,
                     node,
                     GenerateFunctionOptions {
                         name: Rc::clone(&name),
                         call_conv,
+                        visibility,
                     },
                 )?;
 
@@ -99,14 +158,14 @@ impl<'e> CodeGenerator<'e> {
                     MethodParameterCount::from_count_without_self(function.parameter_count)
                         .map_err(|_| ast.error(parameters, LanguageErrorKind::TooManyParameters))?;
                 if let Some(trait_index) = state.implemented_trait_index {
-                    // For trait instance methods, method ID resolution is performed at runtime.
+                    // For trait methods, method ID resolution is performed at runtime.
                     let key = (Rc::clone(&name), parameter_count, trait_index);
-                    if state
-                        .proto
-                        .trait_instance
-                        .insert(key, function.id)
-                        .is_some()
-                    {
+                    let map = match ast.kind(kind) {
+                        NodeKind::Empty => &mut state.proto.trait_instance,
+                        NodeKind::Static | NodeKind::Constructor => &mut state.proto.trait_statics,
+                        _ => unreachable!(),
+                    };
+                    if map.insert(key, function.id).is_some() {
                         return Err(ast.error(
                             name_node,
                             LanguageErrorKind::MethodAlreadyImplemented(RenderedSignature {
@@ -156,18 +215,26 @@ impl<'e> CodeGenerator<'e> {
                     .proto
                     .implement_next_trait()
                     .map_err(|e| ast.error(node, e))?;
-                let mut state = ImplGenerationState {
+                let mut as_state = ImplGenerationState {
                     proto: state.proto,
                     implemented_trait_index: Some(trait_index),
                     ..*state
                 };
                 for &item in as_items {
-                    self.generate_impl_item(ast, item, &mut state, false)?;
+                    self.generate_impl_item(ast, item, &mut as_state, false)?;
                 }
+                // Static trait functions may be implemented by constructors, after which other
+                // constructors must not declare new fields.
+                state.has_constructor = as_state.has_constructor;
+            }
+
+            NodeKind::Identifier | NodeKind::Call if state.is_enum && allow_as => {
+                self.generate_enum_variant(ast, node, state.proto)?;
             }
 
             // NB: If other item types are allowed, don't forget to change the error message for
             // InvalidImplItem.
+            _ if state.is_enum => return Err(ast.error(node, LanguageErrorKind::InvalidEnumItem)),
             _ => return Err(ast.error(node, LanguageErrorKind::InvalidImplItem)),
         }
 
@@ -0,0 +1,36 @@
+//! Code generation for `import`.
+
+use std::rc::Rc;
+
+use super::{
+    variables::{VariableAllocation, VariablePlace},
+    CodeGenerator, ExpressionResult,
+};
+use crate::ll::{
+    ast::{Ast, NodeId},
+    error::{LanguageError, LanguageErrorKind},
+};
+
+impl<'e> CodeGenerator<'e> {
+    /// Generates code for an `import` expression. The module must have been imported into the
+    /// environment before the code is generated; this is taken care of by the engine.
+    pub(super) fn generate_import(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (name, _) = ast.node_pair(node);
+        let name = ast.string(name).unwrap();
+
+        let module = self.env.get_module(name).ok_or_else(|| {
+            ast.error(node, LanguageErrorKind::ModuleDoesNotExist(Rc::clone(name)))
+        })?;
+        self.generate_variable_load(VariablePlace::Global(module));
+        let variable = self
+            .create_variable(name, VariableAllocation::Allocate)
+            .map_err(|kind| ast.error(node, kind))?;
+        self.generate_variable_assign(variable);
+
+        Ok(ExpressionResult::Present)
+    }
+}
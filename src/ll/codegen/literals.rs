@@ -33,11 +33,23 @@ impl<'e> CodeGenerator<'e> {
     }
 
     /// Generates code for a string literal.
-    pub(super) fn generate_string(&mut self, ast: &Ast, node: NodeId) -> ExpressionResult {
-        self.chunk.emit(Opcode::PushString);
+    pub(super) fn generate_string(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
         let string = ast.string(node).unwrap();
-        self.chunk.emit_string(string);
-        ExpressionResult::Present
+        self.emit_push_string(string)
+            .map_err(|kind| ast.error(node, kind))?;
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Emits an instruction pushing the given string, which is interned in the environment such
+    /// that it's only allocated once.
+    pub(super) fn emit_push_string(&mut self, string: &str) -> Result<(), LanguageErrorKind> {
+        let index = self.env.get_or_create_string(self.gc, string)?;
+        self.chunk.emit((Opcode::PushString, index));
+        Ok(())
     }
 
     /// Generates code for a list literal.
@@ -1,23 +1,51 @@
 //! Code generation for unary and binary operators.
 
+use std::rc::Rc;
+
 use super::{CodeGenerator, Expression, ExpressionResult};
 use crate::ll::{
     ast::{Ast, NodeId, NodeKind},
-    bytecode::Opcode,
+    bytecode::{MethodParameterCount, MethodSignature, Opcode, Opr24},
     error::LanguageError,
 };
 
 impl<'e> CodeGenerator<'e> {
+    /// Returns the operand of an operator instruction, which packs the index of the method
+    /// overloading the operator along with the method's argument count (including `self`.)
+    pub(super) fn operator_method(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        name: &str,
+        argument_count: u8,
+    ) -> Result<Opr24, LanguageError> {
+        let signature = MethodSignature::new(
+            Rc::from(name),
+            MethodParameterCount::from_count_with_self(argument_count),
+        );
+        let method_index = self
+            .env
+            .get_or_create_method_index(&signature)
+            .map_err(|kind| ast.error(node, kind))?;
+        Ok(Opr24::pack((method_index.to_u16(), argument_count)))
+    }
+
     /// Generates code for a unary operator.
     pub(super) fn generate_unary(
         &mut self,
         ast: &Ast,
         node: NodeId,
     ) -> Result<ExpressionResult, LanguageError> {
+        if let Some(constant) = self.constant_value(ast, node) {
+            return self.generate_constant(ast, node, &constant);
+        }
         let (left, _) = ast.node_pair(node);
         self.generate_node(ast, left, Expression::Used)?;
         match ast.kind(node) {
-            NodeKind::Negate => self.chunk.emit(Opcode::Negate),
+            NodeKind::Negate => {
+                let operand = self.operator_method(ast, node, "neg", 1)?;
+                self.chunk.emit((Opcode::Negate, operand))
+            }
             NodeKind::Not => self.chunk.emit(Opcode::Not),
             _ => unreachable!(),
         };
@@ -30,34 +58,90 @@ impl<'e> CodeGenerator<'e> {
         ast: &Ast,
         node: NodeId,
     ) -> Result<ExpressionResult, LanguageError> {
+        if let Some(constant) = self.constant_value(ast, node) {
+            return self.generate_constant(ast, node, &constant);
+        }
         let (left, right) = ast.node_pair(node);
+        let left_start = self.chunk.len();
         self.generate_node(ast, left, Expression::Used)?;
+        let right_start = self.chunk.len();
         self.generate_node(ast, right, Expression::Used)?;
+        let right_end = self.chunk.len();
+        self.generate_binary_operation(ast, node)?;
+        if ast.kind(node) == NodeKind::Add {
+            self.fuse_add_locals(left_start..right_start, right_start..right_end);
+        }
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates the instructions performing a binary operation, assuming both of its operands are
+    /// already on the stack.
+    pub(super) fn generate_binary_operation(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<(), LanguageError> {
+        // Operands other than numbers can overload operators by implementing methods with special
+        // names, which is also how strings and lists implement repetition with `*`.
         match ast.kind(node) {
-            NodeKind::Negate => self.chunk.emit(Opcode::Negate),
+            NodeKind::Negate => {
+                let operand = self.operator_method(ast, node, "neg", 1)?;
+                self.chunk.emit((Opcode::Negate, operand))
+            }
 
-            NodeKind::Add => self.chunk.emit(Opcode::Add),
-            NodeKind::Subtract => self.chunk.emit(Opcode::Subtract),
-            NodeKind::Multiply => self.chunk.emit(Opcode::Multiply),
-            NodeKind::Divide => self.chunk.emit(Opcode::Divide),
+            NodeKind::Add => {
+                let operand = self.operator_method(ast, node, "add", 2)?;
+                self.chunk.emit((Opcode::Add, operand))
+            }
+            NodeKind::Subtract => {
+                let operand = self.operator_method(ast, node, "sub", 2)?;
+                self.chunk.emit((Opcode::Subtract, operand))
+            }
+            NodeKind::Multiply => {
+                let operand = self.operator_method(ast, node, "mul", 2)?;
+                self.chunk.emit((Opcode::Multiply, operand))
+            }
+            NodeKind::Divide => {
+                let operand = self.operator_method(ast, node, "div", 2)?;
+                self.chunk.emit((Opcode::Divide, operand))
+            }
+            NodeKind::FloorDivide => self.chunk.emit(Opcode::FloorDivide),
+            NodeKind::Modulo => {
+                let operand = self.operator_method(ast, node, "mod", 2)?;
+                self.chunk.emit((Opcode::Modulo, operand))
+            }
 
-            NodeKind::Equal => self.chunk.emit(Opcode::Equal),
+            NodeKind::Equal => {
+                let operand = self.operator_method(ast, node, "eq", 2)?;
+                self.chunk.emit((Opcode::Equal, operand))
+            }
             NodeKind::NotEqual => {
-                self.chunk.emit(Opcode::Equal);
+                let operand = self.operator_method(ast, node, "eq", 2)?;
+                self.chunk.emit((Opcode::Equal, operand));
                 self.chunk.emit(Opcode::Not)
             }
-            NodeKind::Less => self.chunk.emit(Opcode::Less),
-            NodeKind::LessEqual => self.chunk.emit(Opcode::LessEqual),
+            // All ordering operators are implemented by a single `cmp` method.
+            NodeKind::Less => {
+                let operand = self.operator_method(ast, node, "cmp", 2)?;
+                self.chunk.emit((Opcode::Less, operand))
+            }
+            NodeKind::LessEqual => {
+                let operand = self.operator_method(ast, node, "cmp", 2)?;
+                self.chunk.emit((Opcode::LessEqual, operand))
+            }
             NodeKind::Greater => {
+                let operand = self.operator_method(ast, node, "cmp", 2)?;
                 self.chunk.emit(Opcode::Swap);
-                self.chunk.emit(Opcode::Less)
+                self.chunk.emit((Opcode::Less, operand))
             }
             NodeKind::GreaterEqual => {
+                let operand = self.operator_method(ast, node, "cmp", 2)?;
                 self.chunk.emit(Opcode::Swap);
-                self.chunk.emit(Opcode::LessEqual)
+                self.chunk.emit((Opcode::LessEqual, operand))
             }
+            NodeKind::Implements => self.chunk.emit(Opcode::Implements),
             _ => unreachable!(),
         };
-        Ok(ExpressionResult::Present)
+        Ok(())
     }
 }
@@ -0,0 +1,761 @@
+//! Generating the bodies of top-level functions on multiple threads.
+//!
+//! Once the names of a module's top-level functions are declared, their bodies can be generated
+//! independently of each other. In parallel mode, the module's code is first generated as usual,
+//! except that the bodies of top-level functions are skipped: their globals are declared, and a
+//! `Nop` is emitted in place of each one's `CreateClosure` instruction. The bodies are then
+//! generated by worker threads, each of which has its own copy of the syntax tree and of the
+//! parts of the environment code generation reads. Finally, the functions are moved into the
+//! environment in declaration order, and the `Nop`s are patched with their indices.
+//!
+//! Workers can't create method indices, interned strings, or record types in the main
+//! environment. When a function needs ones that don't exist yet, the worker reports them instead,
+//! and the function is generated again once the main thread has created them.
+
+use std::{
+    cell::RefCell,
+    collections::{HashSet, VecDeque},
+    mem,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use super::{
+    functions::{FunctionCallConv, GenerateFunctionOptions},
+    variables::VariablePlace,
+    CodeGenerator, Expression, ExpressionResult,
+};
+use crate::ll::{
+    ast::{Ast, NodeId, NodeKind},
+    bytecode::{
+        BuiltinDispatchTableGenerator, BuiltinDispatchTables, BuiltinTraits, Chunk, DispatchTable,
+        Environment, Function, FunctionIndex, FunctionKind, GlobalIndex, Library, MethodIndex,
+        MethodParameterCount, MethodSignature, Opcode, TraitIndex, Visibility,
+    },
+    error::{LanguageError, LanguageErrorKind, LanguageWarning},
+    gc::{Gc, Memory},
+};
+
+/// The globals visible to the body of a top-level function.
+#[derive(Debug)]
+pub(super) struct GlobalView {
+    /// The number of global slots when the function was declared. Globals declared after the
+    /// function are not visible to it.
+    slot_count: usize,
+    /// The globals that were declared with `const` when the function was declared.
+    const_globals: HashSet<GlobalIndex>,
+}
+
+impl GlobalView {
+    fn new(env: &Environment) -> Self {
+        Self {
+            slot_count: env.global_slot_count(),
+            const_globals: env.const_globals().clone(),
+        }
+    }
+
+    /// Returns whether the global is visible.
+    pub(super) fn contains(&self, slot: GlobalIndex) -> bool {
+        slot.to_usize() < self.slot_count
+    }
+
+    /// Returns whether the global was declared with `const`.
+    pub(super) fn is_const(&self, slot: GlobalIndex) -> bool {
+        self.const_globals.contains(&slot)
+    }
+}
+
+/// A top-level function whose body is generated after the rest of the module.
+#[derive(Debug)]
+pub(super) struct DeferredFunction {
+    node: NodeId,
+    /// The position of the `Nop` to replace with the function's `CreateClosure`.
+    closure: usize,
+    globals: GlobalView,
+    /// The depth of the code generator when the function was declared.
+    depth: usize,
+    /// The number of warnings emitted before the function was declared.
+    warning_count: usize,
+}
+
+impl<'e> CodeGenerator<'e> {
+    /// The number of times workers try to generate a function. Each time a worker reports that
+    /// the function needs something the environment doesn't have, it's created, so another try
+    /// is only needed if creating it made more things necessary, such as the getter methods of
+    /// records. Functions that still need more are generated on the calling thread.
+    const MAX_ATTEMPTS: usize = 3;
+
+    /// How many times bigger each batch of functions handed out to workers is than the previous.
+    const BATCH_GROWTH: usize = 8;
+
+    /// Generates code for the given AST like [`generate`][Self::generate], but generates the
+    /// bodies of top-level functions on up to `threads` threads. The result is the same as
+    /// `generate`'s, except that method indices, interned strings, and record types may be
+    /// created in a different order.
+    pub fn generate_parallel(
+        mut self,
+        ast: &Ast,
+        root_node: NodeId,
+        threads: NonZeroUsize,
+    ) -> Result<(Rc<Chunk>, Vec<LanguageWarning>), LanguageError> {
+        self.deferred_functions = Some(Vec::new());
+        let result = self.generate_node(ast, root_node, Expression::Used);
+        let deferred = self.deferred_functions.take().unwrap();
+        // The deferred functions were declared before any error in the rest of the code, so
+        // their errors take precedence.
+        self.generate_deferred_functions(ast, &deferred, threads)?;
+        result?;
+        self.chunk.emit(Opcode::Halt);
+        Ok((Rc::new(self.chunk), self.warnings))
+    }
+
+    /// Defers generating the body of the function declared by `node` if it's a top-level function
+    /// and parallel code generation is enabled. Returns `None` if the function has to be
+    /// generated in place.
+    pub(super) fn try_defer_function(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+        variable: VariablePlace,
+    ) -> Option<ExpressionResult> {
+        if self.deferred_functions.is_none()
+            || !self.is_at_top_level()
+            || !Self::can_generate_on_worker(ast, node)
+        {
+            return None;
+        }
+        // The function's index isn't known until its body is generated, so a `Nop` is emitted in
+        // place of the `CreateClosure` for now.
+        let closure = self.chunk.emit(Opcode::Nop);
+        let function = DeferredFunction {
+            node,
+            closure,
+            globals: GlobalView::new(self.env),
+            depth: self.depth,
+            warning_count: self.warnings.len(),
+        };
+        self.deferred_functions.as_mut().unwrap().push(function);
+        self.generate_variable_sink(variable);
+        // Nearly every string literal is unique, so they're interned up front rather than
+        // reported missing by the workers. Errors are left for the workers to report.
+        ast.walk(node, |node, _| {
+            if ast.kind(node) == NodeKind::String {
+                let _ = self
+                    .env
+                    .get_or_create_string(self.gc, ast.string(node).unwrap());
+            }
+        });
+        Some(ExpressionResult::Absent)
+    }
+
+    /// Returns whether the function can be generated by a worker. Functions that declare types or
+    /// import modules can't, since those are registered in the environment.
+    fn can_generate_on_worker(ast: &Ast, node: NodeId) -> bool {
+        let mut possible = true;
+        ast.walk(node, |node, _| {
+            possible &= !matches!(
+                ast.kind(node),
+                NodeKind::Struct
+                    | NodeKind::Enum
+                    | NodeKind::Impl
+                    | NodeKind::Trait
+                    | NodeKind::ImplAs
+                    | NodeKind::Import
+            );
+        });
+        possible
+    }
+
+    /// Generates the bodies of deferred functions and patches in their `CreateClosure`s.
+    fn generate_deferred_functions(
+        &mut self,
+        ast: &Ast,
+        deferred: &[DeferredFunction],
+        threads: NonZeroUsize,
+    ) -> Result<(), LanguageError> {
+        let mut outcomes: Vec<Option<Outcome>> = deferred.iter().map(|_| None).collect();
+        if deferred.is_empty() {
+            return Ok(());
+        }
+        let snapshot = Snapshot::new(self.env, self.library);
+        let shared_ast = SharedAst(ast);
+        thread::scope(|scope| {
+            let (outcome_sender, outcome_receiver) = mpsc::channel();
+            let workers = (0..threads.get().min(deferred.len())).map_while(|_| {
+                let (batch_sender, batch_receiver) = mpsc::channel();
+                let outcome_sender = outcome_sender.clone();
+                let (snapshot, shared_ast) = (&snapshot, &shared_ast);
+                let work = move || {
+                    work(
+                        snapshot,
+                        shared_ast,
+                        deferred,
+                        batch_receiver,
+                        outcome_sender,
+                    )
+                };
+                thread::Builder::new()
+                    .name("mica-codegen".into())
+                    .spawn_scoped(scope, work)
+                    .ok()
+                    .map(|_| batch_sender)
+            });
+            let workers: Vec<_> = workers.collect();
+            drop(outcome_sender);
+            if workers.is_empty() {
+                return Ok(());
+            }
+
+            // Functions are handed out in batches of increasing size. Most things functions need
+            // from the environment, such as the methods operators call, are needed by many of
+            // them, so the first few batches create most of them for the ones that follow.
+            let mut queued: VecDeque<usize> = (0..deferred.len()).collect();
+            let mut attempts = vec![0; deferred.len()];
+            let mut batch_size = workers.len();
+            let mut counts = Counts::of(self.env, self.library);
+            while !queued.is_empty() {
+                let batch = Batch {
+                    additions: Additions::since(self.env, self.library, &mut counts),
+                    queue: Arc::new(Queue {
+                        functions: queued.drain(..batch_size.min(queued.len())).collect(),
+                        next: AtomicUsize::new(0),
+                    }),
+                };
+                for worker in &workers {
+                    // Workers only stop once there are no more batches, unless they panicked,
+                    // which is dealt with below.
+                    let _ = worker.send(batch.clone());
+                }
+                let mut generated = Vec::new();
+                for _ in &workers {
+                    match outcome_receiver.recv() {
+                        Ok(Ok(WorkerOutcomes(outcomes))) => generated.extend(outcomes),
+                        Ok(Err(panic)) => panic::resume_unwind(panic),
+                        Err(_) => unreachable!("workers only stop early when they panic"),
+                    }
+                }
+                generated.sort_unstable_by_key(|&(index, _)| index);
+                let mut retried = Vec::new();
+                for (index, outcome) in generated {
+                    if let Outcome::Incomplete(missing) = outcome {
+                        self.create_missing(ast, &deferred[index], missing)?;
+                        attempts[index] += 1;
+                        if attempts[index] < Self::MAX_ATTEMPTS {
+                            retried.push(index);
+                        }
+                    } else {
+                        outcomes[index] = Some(outcome);
+                    }
+                }
+                for index in retried.into_iter().rev() {
+                    queued.push_front(index);
+                }
+                batch_size = batch_size.saturating_mul(Self::BATCH_GROWTH);
+            }
+            Ok(())
+        })?;
+
+        let mut warnings = mem::take(&mut self.warnings).into_iter();
+        let mut emitted_warnings = 0;
+        for (function, outcome) in deferred.iter().zip(outcomes) {
+            let (id, function_warnings) = match outcome {
+                Some(Outcome::Generated {
+                    functions,
+                    mut warnings,
+                    tuples,
+                }) => {
+                    let id = self.add_generated_functions(ast, function, functions)?;
+                    for size in tuples {
+                        self.library.generate_tuple(self.env, self.gc, size);
+                    }
+                    for warning in &mut warnings {
+                        warning.module_name = Rc::clone(&self.chunk.module_name);
+                    }
+                    (id, warnings)
+                }
+                Some(Outcome::Failed(mut error)) => {
+                    if let LanguageError::Compile { module_name, .. } = &mut error {
+                        *module_name = Rc::clone(&self.chunk.module_name);
+                    }
+                    return Err(error);
+                }
+                // Functions that the workers didn't manage to generate are generated here.
+                Some(Outcome::Incomplete(_)) | None => {
+                    self.generate_deferred_function_here(ast, function)?
+                }
+            };
+            self.chunk
+                .patch(function.closure, (Opcode::CreateClosure, id.to_opr24()));
+            let preceding = function.warning_count - emitted_warnings;
+            self.warnings.extend(warnings.by_ref().take(preceding));
+            emitted_warnings = function.warning_count;
+            self.warnings.extend(function_warnings);
+        }
+        self.warnings.extend(warnings);
+
+        Ok(())
+    }
+
+    /// Creates the method indices, interned strings, and record types a worker reported missing.
+    fn create_missing(
+        &mut self,
+        ast: &Ast,
+        function: &DeferredFunction,
+        missing: Additions,
+    ) -> Result<(), LanguageError> {
+        for (name, parameter_count, trait_id) in missing.methods {
+            let signature = MethodSignature {
+                name: Rc::from(name),
+                parameter_count,
+                trait_id,
+            };
+            self.env
+                .get_or_create_method_index(&signature)
+                .map_err(|kind| ast.error(function.node, kind))?;
+        }
+        for string in missing.strings {
+            self.env
+                .get_or_create_string(self.gc, &string)
+                .map_err(|kind| ast.error(function.node, kind))?;
+        }
+        for identifier in missing.records {
+            self.library
+                .get_or_generate_record(self.env, self.gc, &Rc::from(identifier))
+                .map_err(|_| ast.error(function.node, LanguageErrorKind::TooManyRecords))?;
+        }
+        Ok(())
+    }
+
+    /// Moves functions generated by a worker into the environment. Returns the index of the
+    /// deferred function, which is the last of them.
+    fn add_generated_functions(
+        &mut self,
+        ast: &Ast,
+        function: &DeferredFunction,
+        functions: Vec<Function>,
+    ) -> Result<FunctionIndex, LanguageError> {
+        let too_many_functions = || ast.error(function.node, LanguageErrorKind::TooManyFunctions);
+        // The worker's environment had no other functions, so the indices of the closures
+        // created by the functions are off by the number of functions in this one.
+        let offset = self.env.functions().len();
+        let mut id = None;
+        for mut generated in functions {
+            // Modules are identified by the address of their name, so the functions must point to
+            // the same name as the rest of the module rather than the worker's copy of it.
+            if let FunctionKind::Bytecode { chunk, .. } = &mut generated.kind {
+                let chunk = Rc::get_mut(chunk).expect("generated chunks must not be shared yet");
+                chunk.module_name = Rc::clone(&self.chunk.module_name);
+                chunk
+                    .offset_closures(offset)
+                    .map_err(|_| too_many_functions())?;
+            }
+            if let Some(declaration) = &mut generated.declaration {
+                Rc::get_mut(declaration)
+                    .expect("declarations of generated functions must not be shared yet")
+                    .module_name = Rc::clone(&self.chunk.module_name);
+            }
+            id = Some(
+                self.env
+                    .create_function(generated)
+                    .map_err(|kind| ast.error(function.node, kind))?,
+            );
+        }
+        id.ok_or_else(too_many_functions)
+    }
+
+    /// Generates a deferred function on the calling thread, for when the workers couldn't.
+    fn generate_deferred_function_here(
+        &mut self,
+        ast: &Ast,
+        function: &DeferredFunction,
+    ) -> Result<(FunctionIndex, Vec<LanguageWarning>), LanguageError> {
+        let mut generator = CodeGenerator::new(
+            Rc::clone(&self.chunk.module_name),
+            self.env,
+            self.library,
+            self.gc,
+        );
+        generator.depth = function.depth;
+        generator.global_view = Some(&function.globals);
+        let generated = generator.generate_function(
+            ast,
+            function.node,
+            function_options(ast, function.node),
+        )?;
+        Ok((generated.id, generator.warnings))
+    }
+}
+
+/// Returns the options top-level functions are generated with.
+fn function_options(ast: &Ast, node: NodeId) -> GenerateFunctionOptions {
+    let (head, _) = ast.node_pair(node);
+    let (name, _) = ast.node_pair(head);
+    GenerateFunctionOptions {
+        name: Rc::clone(ast.string(name).unwrap()),
+        call_conv: FunctionCallConv::Bare,
+        visibility: Visibility::Public,
+    }
+}
+
+/// The parts of the environment and library that code generation reads, in a form that can be
+/// sent to workers.
+#[derive(Debug)]
+struct Snapshot {
+    /// Globals in the order of their slots, along with whether they're sealed.
+    globals: Vec<(Global, bool)>,
+    additions: Additions,
+    iterator: TraitIndex,
+    iterator_has_next: MethodIndex,
+    iterator_next: MethodIndex,
+}
+
+/// A global slot.
+#[derive(Debug)]
+enum Global {
+    Named(String),
+    Module(String),
+}
+
+impl Snapshot {
+    fn new(env: &Environment, library: &Library) -> Self {
+        let mut globals: Vec<_> = (0..env.global_slot_count()).map(|_| None).collect();
+        for name in env.global_names() {
+            let slot = env.get_global(name).unwrap();
+            let global = Global::Named(name.to_owned());
+            globals[slot.to_usize()] = Some((global, env.is_global_sealed(slot)));
+        }
+        for (name, slot) in env.modules() {
+            globals[slot.to_usize()] = Some((Global::Module(name.to_owned()), false));
+        }
+        Self {
+            globals: globals
+                .into_iter()
+                .map(|global| global.expect("global slots are never left empty"))
+                .collect(),
+            additions: Additions::since(env, library, &mut Counts::default()),
+            iterator: library.builtin_traits.iterator,
+            iterator_has_next: library.builtin_traits.iterator_has_next,
+            iterator_next: library.builtin_traits.iterator_next,
+        }
+    }
+}
+
+/// Method indices, interned strings, and record types, in the order they were created in.
+#[derive(Debug, Clone, Default)]
+struct Additions {
+    methods: Vec<(String, MethodParameterCount, Option<TraitIndex>)>,
+    strings: Vec<String>,
+    /// Identifiers of record types.
+    records: Vec<String>,
+}
+
+/// The numbers of method indices, interned strings, and record types.
+#[derive(Debug, Clone, Copy, Default)]
+struct Counts {
+    methods: usize,
+    strings: usize,
+    records: usize,
+}
+
+impl Counts {
+    fn of(env: &Environment, library: &Library) -> Self {
+        Self {
+            methods: env.method_signatures().count(),
+            strings: env.strings(0).len(),
+            records: library.builtin_dtables.records.len(),
+        }
+    }
+}
+
+impl Additions {
+    /// Returns what was created since there were `counts` of everything, and updates `counts`.
+    fn since(env: &Environment, library: &Library, counts: &mut Counts) -> Self {
+        let additions = Self {
+            methods: env
+                .method_signatures()
+                .skip(counts.methods)
+                .map(|signature| {
+                    (
+                        signature.name.to_string(),
+                        signature.parameter_count,
+                        signature.trait_id,
+                    )
+                })
+                .collect(),
+            strings: env.strings(counts.strings).map(String::from).collect(),
+            records: library.builtin_dtables.records[counts.records..]
+                .iter()
+                .map(|record| record.identifier.to_string())
+                .collect(),
+        };
+        *counts = Counts::of(env, library);
+        additions
+    }
+
+    fn is_empty(&self) -> bool {
+        self.methods.is_empty() && self.strings.is_empty() && self.records.is_empty()
+    }
+}
+
+/// What happened when a worker generated a function.
+#[derive(Debug)]
+enum Outcome {
+    /// The function was generated. The functions are in the order they were created in: closures
+    /// created by the function come first, numbered from zero, and the function itself is last.
+    Generated {
+        functions: Vec<Function>,
+        warnings: Vec<LanguageWarning>,
+        /// The sizes of the tuples the function creates, whose dispatch tables have to be
+        /// generated.
+        tuples: Vec<usize>,
+    },
+    /// The function needs things the environment doesn't have yet.
+    Incomplete(Additions),
+    Failed(LanguageError),
+}
+
+/// Functions handed out to workers, along with what was created in the environment since the
+/// previous batch.
+#[derive(Debug, Clone)]
+struct Batch {
+    additions: Additions,
+    queue: Arc<Queue>,
+}
+
+/// The indices of deferred functions to generate. Workers take them from the front until none are
+/// left.
+#[derive(Debug)]
+struct Queue {
+    functions: Vec<usize>,
+    next: AtomicUsize,
+}
+
+impl Queue {
+    fn take(&self) -> Option<usize> {
+        self.functions
+            .get(self.next.fetch_add(1, Ordering::Relaxed))
+            .copied()
+    }
+}
+
+/// The loop of a worker thread: generates functions from each batch it receives and sends back
+/// their outcomes, until there are no more batches.
+fn work(
+    snapshot: &Snapshot,
+    ast: &SharedAst,
+    deferred: &[DeferredFunction],
+    batches: Receiver<Batch>,
+    outcomes: Sender<thread::Result<WorkerOutcomes>>,
+) {
+    // Panics are sent to the calling thread rather than left for the scope to propagate, since it
+    // would otherwise keep waiting for the worker's outcomes.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let source = Rc::from(ast.get().source());
+        let mut worker = Worker::new(snapshot);
+        for batch in batches {
+            worker.add(&batch.additions);
+            let mut generated = Vec::new();
+            while let Some(index) = batch.queue.take() {
+                let function = &deferred[index];
+                let (ast, root) = ast.get().copy_subtree(function.node, Rc::clone(&source));
+                generated.push((index, worker.generate(&ast, root, function)));
+            }
+            if outcomes.send(Ok(WorkerOutcomes(generated))).is_err() {
+                break;
+            }
+        }
+    }));
+    if let Err(payload) = result {
+        let _ = outcomes.send(Err(payload));
+    }
+}
+
+/// A syntax tree shared with workers.
+struct SharedAst<'a>(&'a Ast);
+
+impl SharedAst<'_> {
+    fn get(&self) -> &Ast {
+        self.0
+    }
+}
+
+// SAFETY: The syntax tree isn't modified while workers run, and workers only read it to make
+// copies, which never touch the reference counts of its strings.
+unsafe impl Sync for SharedAst<'_> {}
+
+/// The outcomes of the functions generated by a worker.
+struct WorkerOutcomes(Vec<(usize, Outcome)>);
+
+// SAFETY: Outcomes contain `Rc`s whose reference counts aren't atomic. They're either created for
+// the outcome, or come from the copy of the syntax tree made for the function, which is dropped
+// before the outcome is sent. Nothing else the worker keeps refers to them, since everything
+// created in its environment while generating the function is removed afterwards. The outcomes
+// are therefore never accessed by two threads at once.
+unsafe impl Send for WorkerOutcomes {}
+
+/// A worker's copy of the environment and library.
+struct Worker {
+    // NOTE: The environment and library must be dropped before the memory their strings and
+    // dispatch tables are allocated in.
+    env: Environment,
+    library: Library,
+    gc: Memory,
+    /// The sizes of the tuples created since the last function was generated.
+    tuples: Rc<RefCell<Vec<usize>>>,
+    /// The number of things in the environment that are also in the calling thread's.
+    counts: Counts,
+}
+
+impl Worker {
+    fn new(snapshot: &Snapshot) -> Self {
+        let mut env = Environment::new();
+        for (global, is_sealed) in &snapshot.globals {
+            let slot = match global {
+                Global::Named(name) => env.create_global(name),
+                Global::Module(name) => env.create_module(name),
+            }
+            .expect("the environment the snapshot was made from has as many globals");
+            if *is_sealed {
+                env.seal_global(slot);
+            }
+        }
+
+        let tuples = Rc::new(RefCell::new(Vec::new()));
+        let dtable = || Gc::new(DispatchTable::new_for_instance("<worker>"));
+        let library = Library::new(
+            BuiltinDispatchTables {
+                nil: dtable(),
+                boolean: dtable(),
+                number: dtable(),
+                string: dtable(),
+                function: dtable(),
+                list: dtable(),
+                dict: dtable(),
+                tuples: Vec::new(),
+                records: Vec::new(),
+                records_by_identifier: Default::default(),
+            },
+            Box::new(WorkerDtableGenerator {
+                tuples: Rc::clone(&tuples),
+            }),
+            BuiltinTraits {
+                iterator: snapshot.iterator,
+                iterator_has_next: snapshot.iterator_has_next,
+                iterator_next: snapshot.iterator_next,
+            },
+        );
+
+        let mut worker = Self {
+            env,
+            library,
+            gc: Memory::new(),
+            tuples,
+            counts: Counts::default(),
+        };
+        worker.add(&snapshot.additions);
+        worker
+    }
+
+    /// Creates things that were created in the calling thread's environment.
+    fn add(&mut self, additions: &Additions) {
+        for (name, parameter_count, trait_id) in &additions.methods {
+            let signature = MethodSignature {
+                name: Rc::from(name.as_str()),
+                parameter_count: *parameter_count,
+                trait_id: *trait_id,
+            };
+            self.env
+                .get_or_create_method_index(&signature)
+                .expect("the calling thread's environment has as many methods");
+        }
+        for string in &additions.strings {
+            self.env
+                .get_or_create_string(&mut self.gc, string)
+                .expect("the calling thread's environment has as many strings");
+        }
+        for identifier in &additions.records {
+            self.library
+                .get_or_generate_record(&mut self.env, &mut self.gc, &Rc::from(identifier.as_str()))
+                .expect("the calling thread's library has as many records");
+        }
+        self.tuples.borrow_mut().clear();
+        self.counts = Counts::of(&self.env, &self.library);
+    }
+
+    /// Generates a deferred function from a copy of its syntax tree, leaving the environment as it
+    /// was before.
+    fn generate(&mut self, ast: &Ast, root: NodeId, function: &DeferredFunction) -> Outcome {
+        let mut generator = CodeGenerator::new(
+            Rc::clone(ast.module_name()),
+            &mut self.env,
+            &mut self.library,
+            &mut self.gc,
+        );
+        generator.depth = function.depth;
+        generator.global_view = Some(&function.globals);
+        let result = generator.generate_function(ast, root, function_options(ast, root));
+        let warnings = mem::take(&mut generator.warnings);
+
+        let functions = self.env.take_functions();
+        let tuples = mem::take(&mut *self.tuples.borrow_mut());
+        let missing = self.take_missing();
+        match result {
+            Err(error) => Outcome::Failed(error),
+            Ok(_) if !missing.is_empty() => Outcome::Incomplete(missing),
+            Ok(_) => Outcome::Generated {
+                functions,
+                warnings,
+                tuples,
+            },
+        }
+    }
+
+    /// Removes everything created in the environment since it was last in sync with the calling
+    /// thread's, and returns it.
+    fn take_missing(&mut self) -> Additions {
+        let missing = Additions::since(&self.env, &self.library, &mut self.counts.clone());
+        self.env.truncate_methods(self.counts.methods);
+        self.env.truncate_strings(self.counts.strings);
+        let dtables = &mut self.library.builtin_dtables;
+        for record in dtables.records.drain(self.counts.records..) {
+            dtables.records_by_identifier.remove(&record.identifier);
+        }
+        missing
+    }
+}
+
+/// Generates placeholder dispatch tables for workers, recording which tuples were created.
+#[derive(Debug)]
+struct WorkerDtableGenerator {
+    tuples: Rc<RefCell<Vec<usize>>>,
+}
+
+impl BuiltinDispatchTableGenerator for WorkerDtableGenerator {
+    fn generate_tuple(
+        &self,
+        _env: &mut Environment,
+        _gc: &mut Memory,
+        _builtin_traits: &BuiltinTraits,
+        size: usize,
+    ) -> Gc<DispatchTable> {
+        self.tuples.borrow_mut().push(size);
+        Gc::new(DispatchTable::new_for_instance("<worker>"))
+    }
+
+    fn generate_record(
+        &self,
+        _env: &mut Environment,
+        _gc: &mut Memory,
+        _builtin_traits: &BuiltinTraits,
+        _identifier: &str,
+    ) -> Gc<DispatchTable> {
+        Gc::new(DispatchTable::new_for_instance("<worker>"))
+    }
+}
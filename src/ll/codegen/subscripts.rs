@@ -0,0 +1,85 @@
+//! Code generation for subscripts `a[i]` and slices `a[start..end]`.
+
+use super::{CodeGenerator, Expression, ExpressionResult};
+use crate::ll::{
+    ast::{Ast, NodeId, NodeKind},
+    bytecode::Opcode,
+    error::LanguageError,
+};
+
+impl<'e> CodeGenerator<'e> {
+    /// Generates code for reading a subscript.
+    pub(super) fn generate_index(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (receiver, index) = ast.node_pair(node);
+        self.generate_node(ast, receiver, Expression::Used)?;
+        self.generate_node(ast, index, Expression::Used)?;
+        let operand = self.operator_method(ast, node, "index", 2)?;
+        self.chunk.emit((Opcode::Index, operand));
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates code for reading a slice, which calls the receiver's `slice` method.
+    pub(super) fn generate_slice(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<ExpressionResult, LanguageError> {
+        let (receiver, bounds) = ast.node_pair(node);
+        let (start, end) = ast.node_pair(bounds);
+        self.generate_node(ast, receiver, Expression::Used)?;
+        self.generate_slice_bound(ast, start)?;
+        self.generate_slice_bound(ast, end)?;
+        let operand = self.operator_method(ast, node, "slice", 3)?;
+        self.chunk.emit((Opcode::CallMethod, operand));
+        Ok(ExpressionResult::Present)
+    }
+
+    /// Generates code for assigning the value at the top of the stack to a subscript or slice.
+    ///
+    /// The value is evaluated before the subscript, so each operand of the assignment is swapped
+    /// beneath it as soon as it's pushed.
+    pub(super) fn generate_subscript_assignment(
+        &mut self,
+        ast: &Ast,
+        target: NodeId,
+        result: Expression,
+    ) -> Result<(), LanguageError> {
+        let (receiver, index) = ast.node_pair(target);
+        self.generate_node(ast, receiver, Expression::Used)?;
+        self.chunk.emit(Opcode::Swap);
+        if ast.kind(target) == NodeKind::Slice {
+            let (start, end) = ast.node_pair(index);
+            self.generate_slice_bound(ast, start)?;
+            self.chunk.emit(Opcode::Swap);
+            self.generate_slice_bound(ast, end)?;
+            self.chunk.emit(Opcode::Swap);
+            let operand = self.operator_method(ast, target, "set_slice", 4)?;
+            self.chunk.codegen_location = ast.location(target);
+            self.chunk.emit((Opcode::CallMethod, operand));
+        } else {
+            self.generate_node(ast, index, Expression::Used)?;
+            self.chunk.emit(Opcode::Swap);
+            let operand = self.operator_method(ast, target, "set_index", 3)?;
+            self.chunk.codegen_location = ast.location(target);
+            self.chunk.emit((Opcode::IndexAssign, operand));
+        }
+        if result == Expression::Discarded {
+            self.chunk.emit(Opcode::Discard);
+        }
+        Ok(())
+    }
+
+    /// Generates code for one bound of a slice, which is `nil` if it was omitted.
+    fn generate_slice_bound(&mut self, ast: &Ast, bound: NodeId) -> Result<(), LanguageError> {
+        if bound == NodeId::EMPTY {
+            self.chunk.emit(Opcode::PushNil);
+        } else {
+            self.generate_node(ast, bound, Expression::Used)?;
+        }
+        Ok(())
+    }
+}
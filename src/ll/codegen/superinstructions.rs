@@ -0,0 +1,63 @@
+//! Fusing common sequences of instructions into superinstructions.
+//!
+//! The sequences are generated as usual first, and then their first instruction is replaced. Since
+//! superinstructions fall back to running the rest of the sequence, nothing else needs to change.
+
+use std::{mem::size_of, ops::Range};
+
+use super::{constants::Constant, CodeGenerator};
+use crate::ll::{
+    ast::{Ast, NodeId, NodeKind},
+    bytecode::Opcode,
+};
+
+impl<'e> CodeGenerator<'e> {
+    /// Returns whether the code in the given range is a single `GetLocal`.
+    fn is_get_local(&self, code: &Range<usize>) -> bool {
+        code.len() == Opcode::INSTRUCTION_SIZE
+            && self.chunk.is_opcode_at(code.start, Opcode::GetLocal)
+    }
+
+    /// Fuses the `Add` that was just generated with its operands, if both of them are locals.
+    pub(super) fn fuse_add_locals(&mut self, left: Range<usize>, right: Range<usize>) {
+        if self.is_get_local(&left) && self.is_get_local(&right) {
+            self.chunk
+                .fuse(left.start, Opcode::GetLocal, Opcode::AddLocals);
+        }
+    }
+
+    /// Fuses the `Call` that was just generated with its last operand (the last argument, or the
+    /// function if there are no arguments), if it's a local.
+    pub(super) fn fuse_local_call(&mut self, last_operand: Range<usize>) {
+        if self.is_get_local(&last_operand)
+            && last_operand.end + Opcode::INSTRUCTION_SIZE == self.chunk.len()
+        {
+            self.chunk
+                .fuse(last_operand.start, Opcode::GetLocal, Opcode::GetLocalCall);
+        }
+    }
+
+    /// Fuses the condition that was just generated with the conditional jump that follows it, if
+    /// it compares a value with a constant number. The jump and the `Discard` after it must be
+    /// generated right after.
+    pub(super) fn fuse_number_comparison(&mut self, ast: &Ast, condition: NodeId) {
+        let (comparison, fused) = match ast.kind(condition) {
+            NodeKind::Less => (Opcode::Less, Opcode::LessNumberJump),
+            NodeKind::LessEqual => (Opcode::LessEqual, Opcode::LessEqualNumberJump),
+            _ => return,
+        };
+        let (_, right) = ast.node_pair(condition);
+        if self.constant_value(ast, condition).is_some()
+            || !matches!(self.constant_value(ast, right), Some(Constant::Number(_)))
+        {
+            return;
+        }
+        // The number is pushed right before it's compared.
+        let len = self.chunk.len();
+        let compare = len - Opcode::INSTRUCTION_SIZE;
+        let push = compare - Opcode::INSTRUCTION_SIZE - size_of::<f64>();
+        if self.chunk.is_opcode_at(compare, comparison) {
+            self.chunk.fuse(push, Opcode::PushNumber, fused);
+        }
+    }
+}
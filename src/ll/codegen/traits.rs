@@ -8,7 +8,7 @@ use crate::{
         ast::{Ast, NodeId, NodeKind},
         bytecode::{
             Chunk, Environment, Function, FunctionIndex, FunctionKind, MethodIndex,
-            MethodSignature, Opcode, Opr24, TraitIndex,
+            MethodSignature, Opcode, Opr24, TraitIndex, Visibility,
         },
         error::{LanguageError, LanguageErrorKind, Location, RenderedSignature},
     },
@@ -20,6 +20,7 @@ pub struct TraitBuilder<'b> {
     parent_chunk: Option<&'b Chunk>,
     trait_id: TraitIndex,
     required_methods: HashSet<MethodIndex>,
+    required_statics: HashSet<MethodIndex>,
     shims: Vec<(MethodIndex, FunctionIndex)>,
 }
 
@@ -35,6 +36,7 @@ impl<'b> TraitBuilder<'b> {
             parent_chunk,
             trait_id,
             required_methods: HashSet::new(),
+            required_statics: HashSet::new(),
             shims: vec![],
         })
     }
@@ -73,14 +75,18 @@ impl<'b> TraitBuilder<'b> {
         let chunk = Rc::new(chunk);
         let function_id = self.env.create_function(Function {
             name: shim_name,
-            parameter_count: FunctionParameterCount::Fixed(
-                method_signature.parameter_count.to_count_without_self() as u16,
-            ),
+            // The shim receives the instance as an explicit argument, so the parameter count
+            // includes `self`.
+            parameter_count: FunctionParameterCount::Fixed(u16::from(parameter_count)),
             kind: FunctionKind::Bytecode {
                 chunk,
                 captured_locals: vec![],
             },
             hidden_in_stack_traces: true,
+            traced: false,
+            declaration: None,
+            visibility: Visibility::Public,
+            impl_block: None,
         })?;
 
         Ok(function_id)
@@ -93,6 +99,28 @@ impl<'b> TraitBuilder<'b> {
         &mut self,
         name: Rc<str>,
         parameter_count: MethodParameterCount,
+    ) -> Result<MethodIndex, LanguageErrorKind> {
+        self.add_requirement(name, parameter_count, false)
+    }
+
+    /// Adds a static function into the trait and returns its method ID. Static functions are
+    /// called on the implementing type rather than its instances, so the trait's shim receives the
+    /// type as its first argument.
+    ///
+    /// The parameter count does not include the `self` parameter.
+    pub fn add_static_method(
+        &mut self,
+        name: Rc<str>,
+        parameter_count: MethodParameterCount,
+    ) -> Result<MethodIndex, LanguageErrorKind> {
+        self.add_requirement(name, parameter_count, true)
+    }
+
+    fn add_requirement(
+        &mut self,
+        name: Rc<str>,
+        parameter_count: MethodParameterCount,
+        is_static: bool,
     ) -> Result<MethodIndex, LanguageErrorKind> {
         let trait_name = Rc::clone(&self.env.get_trait(self.trait_id).unwrap().name);
         let signature = MethodSignature {
@@ -117,7 +145,14 @@ impl<'b> TraitBuilder<'b> {
         let shim_function_id = self.generate_method_shim(&trait_name, method_id, &signature)?;
         self.shims.push((shim_method_id, shim_function_id));
 
-        if !self.required_methods.insert(method_id) {
+        let is_duplicate = self.required_methods.contains(&method_id)
+            || self.required_statics.contains(&method_id);
+        let required = if is_static {
+            &mut self.required_statics
+        } else {
+            &mut self.required_methods
+        };
+        if is_duplicate || !required.insert(method_id) {
             return Err(LanguageErrorKind::TraitAlreadyHasMethod(
                 RenderedSignature {
                     name: signature.name,
@@ -136,6 +171,7 @@ impl<'b> TraitBuilder<'b> {
     pub fn build(self) -> (TraitIndex, &'b mut Environment) {
         let prototype = self.env.get_trait_mut(self.trait_id).unwrap();
         prototype.required = self.required_methods;
+        prototype.required_statics = self.required_statics;
         prototype.shims = self.shims;
         (self.trait_id, self.env)
     }
@@ -172,17 +208,33 @@ impl<'e> CodeGenerator<'e> {
                     if name == NodeId::EMPTY {
                         return Err(ast.error(head, LanguageErrorKind::MissingMethodName));
                     }
-                    if ast.node_pair(params).0 != NodeId::EMPTY {
-                        return Err(ast.error(head, LanguageErrorKind::FunctionKindInTrait));
+                    let (function_kind, visibility) = ast.node_pair(params);
+                    let is_static = match ast.kind(function_kind) {
+                        NodeKind::Empty => false,
+                        NodeKind::Static => true,
+                        _ => return Err(ast.error(head, LanguageErrorKind::FunctionKindInTrait)),
+                    };
+                    if ast.kind(visibility) == NodeKind::Priv {
+                        return Err(ast.error(visibility, LanguageErrorKind::PrivateTraitMethod));
+                    }
+                    let parameters = ast.children(params).unwrap_or(&[]);
+                    if let Some(&rest_parameter) = parameters
+                        .iter()
+                        .find(|&&parameter| ast.kind(parameter) == NodeKind::RestParameter)
+                    {
+                        return Err(
+                            ast.error(rest_parameter, LanguageErrorKind::RestParameterInMethod)
+                        );
                     }
 
                     let _method_id = builder
-                        .add_method(
+                        .add_requirement(
                             Rc::clone(ast.string(name).unwrap()),
                             MethodParameterCount::from_count_without_self(ast.len(params).unwrap())
                                 .map_err(|_| {
                                     ast.error(params, LanguageErrorKind::TooManyParameters)
                                 })?,
+                            is_static,
                         )
                         .map_err(|e| ast.error(item, e))?;
                 }
@@ -119,6 +119,10 @@ impl<'e> CodeGenerator<'e> {
                 let pattern = if value == NodeId::EMPTY { key } else { value };
                 self.generate_pattern_destructuring(ast, pattern, Expression::Discarded)?;
             }
+            // The record is still on the stack after all the fields have been extracted.
+            if result == Expression::Discarded {
+                self.chunk.emit(Opcode::Discard);
+            }
         } else {
             let mut fields: Vec<_> = pairs.iter().map(|&pair| ast.node_pair(pair)).collect();
             fields.sort_by_key(|&(key, _)| ast.string(key));
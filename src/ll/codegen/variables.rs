@@ -1,12 +1,15 @@
 //! Low-level operations on variables and scopes.
 
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
-use super::{CodeGenerator, ExpressionResult};
+use super::{CodeGenerator, ExpressionResult, PatternBinding};
 use crate::ll::{
     ast::{Ast, NodeId},
     bytecode::{CaptureKind, GlobalIndex, Opcode, Opr24},
-    error::{LanguageError, LanguageErrorKind},
+    error::{closest_match, LanguageError, LanguageErrorKind, LanguageWarningKind, Location},
 };
 
 /// The index of a local on the stack.
@@ -36,6 +39,20 @@ impl UpvalueIndex {
 struct Variable {
     stack_slot: LocalIndex,
     is_captured: bool,
+    is_used: bool,
+    /// Whether the variable was declared with `const`.
+    is_const: bool,
+    /// Where the variable was declared in user code. Variables created implicitly by the
+    /// compiler (such as parameters) don't have this set, and are not subject to lints.
+    declared_at: Option<Location>,
+    /// The index of the variable's debug info in the chunk.
+    debug_index: usize,
+    /// Where the variable was last assigned to, if the assigned value hasn't been read since,
+    /// along with the number of assignments recorded before it.
+    unread_assignment: Option<(Location, usize)>,
+    /// Whether the variable is read by a `defer` expression, which can happen after any
+    /// assignment to it.
+    is_read_on_exit: bool,
 }
 
 #[derive(Debug, Default)]
@@ -43,6 +60,11 @@ struct Scope {
     /// Mapping from variable names to stack slots.
     variables_by_name: HashMap<String, Variable>,
     allocated_variable_count: u32,
+    /// Stack slots reserved without a variable, which are freed along with the scope.
+    reserved_slot_count: u32,
+    /// Declared variables that were replaced in `variables_by_name` by a variable with the same
+    /// name, kept around to lint them once the scope is popped.
+    redeclared_variables: Vec<(String, Variable)>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -74,9 +96,23 @@ pub(super) struct Locals {
     /// The total amount of locals currently allocated. This is used to populate the
     /// `preallocate_stack_slots` field in chunks, to provide more efficient allocations
     allocated_local_count: u32,
+    /// The highest `local_count` reached since it was last reset.
+    peak_local_count: u32,
 
     /// Variables captured from parent scopes.
     pub(super) captures: Vec<CaptureKind>,
+    /// Names of the captured variables, in the same order as `captures`.
+    pub(super) capture_names: Vec<Rc<str>>,
+
+    /// The number of assignments to local variables recorded so far.
+    assignment_count: usize,
+    /// Whether the code being generated is deferred. Variables it reads are read when the block
+    /// containing the `defer` exits.
+    in_deferred_code: bool,
+
+    /// Names of globals declared by `let` in the module being compiled. The top level of a module
+    /// acts as its outermost scope, except that its variables are stored in globals.
+    declared_globals: HashSet<String>,
 }
 
 impl Locals {
@@ -85,18 +121,30 @@ impl Locals {
         &mut self,
         name: &str,
         allocation: VariableAllocation,
+        debug_index: usize,
     ) -> Result<VariablePlace, LanguageErrorKind> {
         let slot = Opr24::new(self.local_count).map_err(|_| LanguageErrorKind::TooManyLocals)?;
         let slot = LocalIndex(slot);
         let scope = self.scopes.last_mut().unwrap();
-        scope.variables_by_name.insert(
+        if let Some(previous) = scope.variables_by_name.insert(
             name.to_owned(),
             Variable {
                 stack_slot: slot,
                 is_captured: false,
+                is_used: false,
+                is_const: false,
+                declared_at: None,
+                debug_index,
+                unread_assignment: None,
+                is_read_on_exit: false,
             },
-        );
+        ) {
+            if previous.declared_at.is_some() {
+                scope.redeclared_variables.push((name.to_owned(), previous));
+            }
+        }
         self.local_count += 1;
+        self.peak_local_count = self.peak_local_count.max(self.local_count);
         if allocation == VariableAllocation::Allocate {
             self.allocated_local_count += 1;
             scope.allocated_variable_count += 1;
@@ -114,7 +162,11 @@ impl Locals {
     }
 
     /// Returns the index of the given capture.
-    fn capture_index(&mut self, capture: CaptureKind) -> Result<UpvalueIndex, LanguageErrorKind> {
+    fn capture_index(
+        &mut self,
+        name: &str,
+        capture: CaptureKind,
+    ) -> Result<UpvalueIndex, LanguageErrorKind> {
         // Iterating over captures maybe isn't most efficient here but it's not like we have
         // thousands of them anyways. Unless somebody absolutely crazy starts writing Mica code.
         // Then all I can say is: I hate you.
@@ -125,6 +177,7 @@ impl Locals {
             .unwrap_or_else(|| {
                 let index = self.captures.len();
                 self.captures.push(capture);
+                self.capture_names.push(Rc::from(name));
                 index
             });
         Ok(UpvalueIndex(
@@ -135,8 +188,11 @@ impl Locals {
     /// Performs a local variable lookup. This may modify parent Locals and capture upvalues.
     fn lookup(&mut self, name: &str) -> Result<Option<VariablePlace>, LanguageErrorKind> {
         // Work inside out: try innermost scopes (own locals) first.
-        for scope in self.scopes.iter().rev() {
-            if let Some(var) = scope.variables_by_name.get(name) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(var) = scope.variables_by_name.get_mut(name) {
+                var.is_used = true;
+                var.unread_assignment = None;
+                var.is_read_on_exit |= self.in_deferred_code;
                 return Ok(Some(VariablePlace::Local(var.stack_slot)));
             }
         }
@@ -152,11 +208,13 @@ impl Locals {
                             .unwrap();
                         variable.is_captured = true;
                         let stack_slot = variable.stack_slot;
-                        let upvalue_index = self.capture_index(CaptureKind::Local(stack_slot))?;
+                        let upvalue_index =
+                            self.capture_index(name, CaptureKind::Local(stack_slot))?;
                         return Ok(Some(VariablePlace::Upvalue(upvalue_index)));
                     }
                     VariablePlace::Upvalue(upvalue_index) => {
-                        let own_index = self.capture_index(CaptureKind::Upvalue(upvalue_index))?;
+                        let own_index =
+                            self.capture_index(name, CaptureKind::Upvalue(upvalue_index))?;
                         return Ok(Some(VariablePlace::Upvalue(own_index)));
                     }
                     VariablePlace::Global(_) => unreachable!(),
@@ -166,6 +224,80 @@ impl Locals {
         Ok(None)
     }
 
+    /// Remembers that the local variable with the given name was assigned to at the given
+    /// location, and the value has not been read yet.
+    fn record_assignment(&mut self, name: &str, location: Location) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(variable) = scope.variables_by_name.get_mut(name) {
+                variable.unread_assignment = Some((location, self.assignment_count));
+                self.assignment_count += 1;
+                return;
+            }
+        }
+    }
+
+    /// Treats the values assigned since `assignment_count` was the given number as read. Loops
+    /// call this once their body is generated, as the next iteration can read values assigned
+    /// at the end of the previous one.
+    fn forget_unread_assignments(&mut self, since: usize) {
+        for (_, variable) in self.variables_in_scope_mut() {
+            if variable
+                .unread_assignment
+                .is_some_and(|(_, index)| index >= since)
+            {
+                variable.unread_assignment = None;
+            }
+        }
+    }
+
+    /// Returns whether the local variable or upvalue with the given name was declared with
+    /// `const`.
+    fn is_const(&self, name: &str) -> bool {
+        for scope in self.scopes.iter().rev() {
+            if let Some(variable) = scope.variables_by_name.get(name) {
+                return variable.is_const;
+            }
+        }
+        self.parent
+            .as_ref()
+            .is_some_and(|parent| parent.is_const(name))
+    }
+
+    /// Returns whether a variable with the given name exists in a scope other than the innermost
+    /// one. Variables from parent functions are not taken into account.
+    fn is_declared_in_outer_scope(&self, name: &str) -> bool {
+        let outer_scopes = &self.scopes[..self.scopes.len().saturating_sub(1)];
+        outer_scopes
+            .iter()
+            .any(|scope| scope.variables_by_name.contains_key(name))
+    }
+
+    /// Returns an iterator over the names of all variables visible from the current scope,
+    /// including ones from parent functions.
+    fn visible_names(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        let own = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.variables_by_name.keys())
+            .map(|name| name.as_str());
+        match &self.parent {
+            Some(parent) => Box::new(own.chain(parent.visible_names())),
+            None => Box::new(own),
+        }
+    }
+
+    /// Returns whether a variable with the given name was declared in the innermost scope.
+    /// Variables created implicitly by the compiler are not taken into account.
+    fn is_declared_in_current_scope(&self, name: &str) -> bool {
+        match self.scopes.last() {
+            Some(scope) => scope
+                .variables_by_name
+                .get(name)
+                .is_some_and(|variable| variable.declared_at.is_some()),
+            None => self.declared_globals.contains(name),
+        }
+    }
+
     /// Pushes a new scope onto the scope stack.
     fn push_scope(&mut self) {
         self.scopes.push(Default::default());
@@ -174,10 +306,21 @@ impl Locals {
     /// Pops the topmost scope off the scope stack and frees storage of any variables.
     fn pop_scope(&mut self) -> Scope {
         let scope = self.scopes.pop().expect("no scopes left on the stack");
-        self.local_count -= scope.variables_by_name.len() as u32;
-        self.allocated_local_count -= scope.allocated_variable_count;
+        self.local_count -= scope.variables_by_name.len() as u32 + scope.reserved_slot_count;
+        self.allocated_local_count -= scope.allocated_variable_count + scope.reserved_slot_count;
         scope
     }
+
+    /// Reserves stack slots up to `peak_local_count` in the innermost scope, such that variables
+    /// declared later do not reuse them.
+    fn reserve_slots_up_to_peak(&mut self) {
+        let count = self.peak_local_count - self.local_count;
+        self.local_count += count;
+        self.allocated_local_count += count;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.reserved_slot_count += count;
+        }
+    }
 }
 
 impl<'e> CodeGenerator<'e> {
@@ -188,19 +331,72 @@ impl<'e> CodeGenerator<'e> {
         name: &str,
         allocation: VariableAllocation,
     ) -> Result<VariablePlace, LanguageErrorKind> {
-        if !self.locals.scopes.is_empty() {
-            let place = self.locals.create_local(name, allocation)?;
+        if let Some(scope) = self.locals.scopes.last() {
+            // A variable redeclared in the same scope is no longer visible to debuggers.
+            if let Some(previous) = scope.variables_by_name.get(name) {
+                self.chunk.end_local_variable(previous.debug_index);
+            }
+            let debug_index = self
+                .chunk
+                .begin_local_variable(Rc::from(name), self.locals.local_count);
+            let place = self.locals.create_local(name, allocation, debug_index)?;
             self.chunk.preallocate_stack_slots = self
                 .chunk
                 .preallocate_stack_slots
                 .max(self.locals.allocated_local_count);
             Ok(place)
         } else {
-            let slot = self.env.create_global(name)?;
+            let slot = self.env.create_script_global(name)?;
+            self.env.ensure_global_not_sealed(slot)?;
             Ok(VariablePlace::Global(slot))
         }
     }
 
+    /// Declares a variable named by an identifier node in user code. Unlike
+    /// [`create_variable`][Self::create_variable], this checks the declaration against lints.
+    pub(super) fn declare_variable(
+        &mut self,
+        ast: &Ast,
+        node: NodeId,
+    ) -> Result<VariablePlace, LanguageError> {
+        let name = ast.string(node).unwrap();
+        let location = ast.location(node);
+        let is_linted = !name.starts_with('_');
+        if is_linted && self.locals.is_declared_in_current_scope(name) {
+            self.warn(
+                location,
+                LanguageWarningKind::RedeclaredVariable(Rc::clone(name)),
+            );
+        } else if is_linted && self.locals.is_declared_in_outer_scope(name) {
+            self.warn(
+                location,
+                LanguageWarningKind::ShadowedVariable(Rc::clone(name)),
+            );
+        }
+        let place = self
+            .create_variable(name, VariableAllocation::Allocate)
+            .map_err(|kind| ast.error(node, kind))?;
+        let is_const = self.pattern_binding == PatternBinding::Const;
+        match place {
+            VariablePlace::Local(_) => {
+                let scope = self.locals.scopes.last_mut().unwrap();
+                let variable = scope.variables_by_name.get_mut(&**name).unwrap();
+                variable.is_const = is_const;
+                if is_linted {
+                    variable.declared_at = Some(location);
+                }
+            }
+            VariablePlace::Global(slot) => {
+                self.env.set_global_const(slot, is_const);
+                if is_linted {
+                    self.locals.declared_globals.insert(name.to_string());
+                }
+            }
+            VariablePlace::Upvalue(_) => (),
+        }
+        Ok(place)
+    }
+
     /// Performs a variable lookup. Returns the stack slot of the variable if it exists.
     /// Otherwise returns `None`.
     pub(super) fn lookup_variable(
@@ -212,7 +408,92 @@ impl<'e> CodeGenerator<'e> {
             return Ok(Some(place));
         }
         // Lastly check globals.
-        Ok(self.env.get_global(name).map(VariablePlace::Global))
+        Ok(self
+            .env
+            .get_global(name)
+            .filter(|&slot| self.is_global_visible(slot))
+            .map(VariablePlace::Global))
+    }
+
+    /// Returns whether the global can be accessed by the code being generated. When the bodies of
+    /// top-level functions are generated separately, globals declared after the function are not
+    /// visible yet.
+    fn is_global_visible(&self, slot: GlobalIndex) -> bool {
+        self.global_view.is_none_or(|view| view.contains(slot))
+    }
+
+    /// Returns whether code is being generated at the top level of a module, outside of any
+    /// function or block.
+    pub(super) fn is_at_top_level(&self) -> bool {
+        self.locals.parent.is_none() && self.locals.scopes.is_empty()
+    }
+
+    /// Returns whether the variable found by [`lookup_variable`][Self::lookup_variable] was
+    /// declared with `const`.
+    pub(super) fn is_variable_const(&self, name: &str, place: VariablePlace) -> bool {
+        match place {
+            VariablePlace::Global(slot) => match self.global_view {
+                Some(view) => view.is_const(slot),
+                None => self.env.is_global_const(slot),
+            },
+            VariablePlace::Local(_) | VariablePlace::Upvalue(_) => self.locals.is_const(name),
+        }
+    }
+
+    /// Remembers that a value was assigned to a variable in user code, to lint the assignment if
+    /// the value ends up never being read.
+    pub(super) fn record_assignment(
+        &mut self,
+        name: &str,
+        place: VariablePlace,
+        location: Location,
+    ) {
+        if let VariablePlace::Local(_) = place {
+            self.locals.record_assignment(name, location);
+        }
+    }
+
+    /// Generates a loop body, such that values assigned in it are not linted as unread, since
+    /// the loop's next iteration can read them.
+    pub(super) fn generate_loop_body(
+        &mut self,
+        generate: impl FnOnce(&mut Self) -> Result<(), LanguageError>,
+    ) -> Result<(), LanguageError> {
+        let since = self.locals.assignment_count;
+        generate(self)?;
+        self.locals.forget_unread_assignments(since);
+        Ok(())
+    }
+
+    /// Generates the code of a `defer` expression, such that variables it reads are exempt from
+    /// linting unread assignments.
+    pub(super) fn generate_deferred_code(
+        &mut self,
+        generate: impl FnOnce(&mut Self) -> Result<(), LanguageError>,
+    ) -> Result<(), LanguageError> {
+        let outer = std::mem::replace(&mut self.locals.in_deferred_code, true);
+        let result = generate(self);
+        self.locals.in_deferred_code = outer;
+        result
+    }
+
+    /// Creates a `VariableDoesNotExist` error, suggesting a similarly named variable if there is
+    /// one in scope.
+    pub(super) fn variable_does_not_exist(&self, name: &Rc<str>) -> LanguageErrorKind {
+        let candidates = self
+            .locals
+            .visible_names()
+            .chain(self.env.global_names().filter(|name| {
+                self.env
+                    .get_global(name)
+                    .is_some_and(|slot| self.is_global_visible(slot))
+            }))
+            // Filter out compiler-internal variables such as `<receiver>`.
+            .filter(|candidate| !candidate.starts_with('<'));
+        LanguageErrorKind::VariableDoesNotExist {
+            name: Rc::clone(name),
+            did_you_mean: closest_match(name, candidates).map(Rc::from),
+        }
     }
 
     /// Pushes a new scope onto the scope stack.
@@ -220,13 +501,58 @@ impl<'e> CodeGenerator<'e> {
         self.locals.push_scope();
     }
 
+    /// Generates code in a scope of its own, whose variables keep their stack slots after the
+    /// scope ends. Code generated this way can run while variables declared after it are alive.
+    pub(super) fn generate_in_reserved_scope(
+        &mut self,
+        generate: impl FnOnce(&mut Self) -> Result<(), LanguageError>,
+    ) -> Result<(), LanguageError> {
+        let outer_peak = self.locals.peak_local_count;
+        self.locals.peak_local_count = self.locals.local_count;
+        self.push_scope();
+        let result = generate(self);
+        self.pop_scope();
+        let peak = self.locals.peak_local_count;
+        self.locals.reserve_slots_up_to_peak();
+        self.locals.peak_local_count = outer_peak.max(peak);
+        result
+    }
+
     /// Pops the topmost scope off the scope stack and frees storage of any variables.
     pub(super) fn pop_scope(&mut self) {
         let scope = self.locals.pop_scope();
-        for variable in scope.variables_by_name.into_values() {
+        for (name, variable) in scope.variables_by_name {
+            self.chunk.end_local_variable(variable.debug_index);
             if variable.is_captured {
                 self.chunk.emit((Opcode::CloseLocal, variable.stack_slot.0));
             }
+            self.lint_unused_variable(name, &variable);
+        }
+        for (name, variable) in scope.redeclared_variables {
+            self.lint_unused_variable(name, &variable);
+        }
+    }
+
+    /// Emits a warning if the variable was declared in user code but never used, or if the last
+    /// value assigned to it is never read.
+    fn lint_unused_variable(&mut self, name: String, variable: &Variable) {
+        if variable.declared_at.is_none() {
+            return;
+        }
+        if !variable.is_used {
+            self.warn(
+                variable.declared_at.unwrap(),
+                LanguageWarningKind::UnusedVariable(Rc::from(name)),
+            );
+        } else if let (Some((location, _)), false, false) = (
+            variable.unread_assignment,
+            variable.is_captured,
+            variable.is_read_on_exit,
+        ) {
+            self.warn(
+                location,
+                LanguageWarningKind::UnusedAssignment(Rc::from(name)),
+            );
         }
     }
 
@@ -273,10 +599,7 @@ impl<'e> CodeGenerator<'e> {
             self.generate_variable_load(variable);
             Ok(ExpressionResult::Present)
         } else {
-            Err(ast.error(
-                node,
-                LanguageErrorKind::VariableDoesNotExist(name.to_owned()),
-            ))
+            Err(ast.error(node, self.variable_does_not_exist(name)))
         }
     }
 }
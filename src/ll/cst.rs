@@ -0,0 +1,540 @@
+//! Lossless concrete syntax trees, for editor tooling.
+//!
+//! Unlike the [abstract syntax tree][crate::ll::ast], a concrete syntax tree holds every single
+//! character of the source code, including whitespace and comments, and can be built from source
+//! code that doesn't parse. Its structure is shallower though: the tree consists of top-level
+//! items, which are made up of tokens, blocks (such as `do..end`), and bracketed groups. This is
+//! enough for features like code folding, bracket matching, or outlines, while being cheap to
+//! update on every edit.
+//!
+//! A new top-level item begins at each token that is written at the start of a line without
+//! indentation, outside of any block or group, unless it continues the previous line's
+//! expression (eg. because that line ended with an operator.) To recover from unterminated
+//! blocks, `func`, `struct`, `enum`, and `trait` written at the start of a line also begin a new item.
+
+use std::{collections::HashMap, ops::Range, rc::Rc};
+
+use crate::ll::lexer::{Lexer, TokenKind, Tokens};
+
+/// The kind of a [`SyntaxNode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxNodeKind {
+    /// The root of the tree, holding the items and the trivia between them.
+    Root,
+    /// A top-level item.
+    Item,
+    /// A block, starting with `do`, `impl`, `enum`, `trait`, or `as`, and ending with `end`. The branches
+    /// of an `if` expression all belong to the block started by the first `do`.
+    Block,
+    /// A group of tokens surrounded by parentheses, brackets, or braces.
+    Group,
+}
+
+/// The kind of a [`SyntaxToken`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxTokenKind {
+    /// A token produced by the lexer. Comments and source code that could not be lexed are
+    /// represented by [`TokenKind::Comment`] and [`TokenKind::Error`].
+    Token(TokenKind),
+    /// A run of spaces and tabs.
+    Whitespace,
+    /// A line break.
+    Newline,
+}
+
+impl SyntaxTokenKind {
+    /// Returns whether the token is trivia, ie. whitespace or a comment.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            Self::Whitespace | Self::Newline | Self::Token(TokenKind::Comment)
+        )
+    }
+}
+
+/// A token in a concrete syntax tree. Tokens only store their length; their position in the
+/// source code is known once the tree is traversed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    pub kind: SyntaxTokenKind,
+    pub len: usize,
+}
+
+/// A node in a concrete syntax tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    kind: SyntaxNodeKind,
+    len: usize,
+    children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    /// Returns the kind of the node.
+    pub fn kind(&self) -> SyntaxNodeKind {
+        self.kind
+    }
+
+    /// Returns the length of the source code covered by the node, in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the node's children.
+    pub fn children(&self) -> &[SyntaxElement] {
+        &self.children
+    }
+}
+
+/// Either a node or a token.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxElement {
+    /// Returns the length of the source code covered by the element, in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Node(node) => node.len,
+            Self::Token(token) => token.len,
+        }
+    }
+}
+
+/// A concrete syntax tree, along with the source code it was built from.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    source: String,
+    root: SyntaxNode,
+}
+
+impl SyntaxTree {
+    /// Builds a syntax tree from source code. This never fails; source code that cannot be
+    /// lexed ends up in [error tokens][TokenKind::Error].
+    pub fn parse(source: impl Into<String>) -> Self {
+        let mut tree = Self {
+            source: source.into(),
+            root: SyntaxNode {
+                kind: SyntaxNodeKind::Root,
+                len: 0,
+                children: Vec::new(),
+            },
+        };
+        tree.reparse(0, 0, 0, &HashMap::new());
+        tree
+    }
+
+    /// Returns the source code the tree was built from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the root node of the tree.
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    /// Replaces the given byte range of the source code with new text, and updates the tree.
+    ///
+    /// Only the items surrounding the edit are lexed and parsed again, so this is much faster
+    /// than building a new tree for large files. The resulting tree is the same as if it were
+    /// built from scratch.
+    ///
+    /// # Panics
+    /// If the range is out of bounds or does not lie on character boundaries.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) {
+        self.source.replace_range(range.clone(), replacement);
+
+        let mut item_starts = Vec::new();
+        let mut offset = 0;
+        for (index, child) in self.root.children.iter().enumerate() {
+            if matches!(child, SyntaxElement::Node(_)) {
+                item_starts.push((index, offset));
+            }
+            offset += child.len();
+        }
+
+        // Parsing starts one item before the edited one, as edits at the very start of an item
+        // can change whether that item is a continuation of the previous one.
+        let edited_item = item_starts
+            .iter()
+            .rposition(|&(_, start)| start <= range.start);
+        let (first_child, start) = match edited_item {
+            Some(i) if i > 0 => item_starts[i - 1],
+            _ => (0, 0),
+        };
+        // Items that start after the edit are kept as they are, as soon as parsing reaches one of
+        // them.
+        let edit_end = range.start + replacement.len();
+        let resume_points: HashMap<usize, usize> = item_starts
+            .iter()
+            .filter(|&&(_, start)| start >= range.end)
+            .map(|&(index, start)| (start - range.end + edit_end, index))
+            .collect();
+        self.reparse(first_child, start, edit_end, &resume_points);
+    }
+
+    /// Parses the source code starting from the given byte offset, and replaces the root's
+    /// children starting at `first_child` with the result. Parsing stops upon reaching one of the
+    /// resume points (new item start offsets mapped to the old children they correspond to) after
+    /// `edit_end`, reusing the rest of the old children.
+    fn reparse(
+        &mut self,
+        first_child: usize,
+        start: usize,
+        edit_end: usize,
+        resume_points: &HashMap<usize, usize>,
+    ) {
+        let mut scanner = Scanner::new(&self.source, start);
+        let mut builder = Builder::default();
+        let mut resume_child = self.root.children.len();
+        while let Some((kind, range)) = scanner.next_token() {
+            if let Some(token_kind) = significant_token(&kind) {
+                if range.start >= edit_end && builder.starts_item(token_kind) {
+                    if let Some(&child) = resume_points.get(&range.start) {
+                        resume_child = child;
+                        break;
+                    }
+                }
+            }
+            builder.push(kind, range.len());
+        }
+        let reused = self.root.children.split_off(resume_child);
+        self.root.children.truncate(first_child);
+        self.root.children.extend(builder.finish());
+        self.root.children.extend(reused);
+        self.root.len = self.source.len();
+    }
+
+    /// Returns an iterator over all tokens in the tree, in source order, along with their byte
+    /// ranges in the source code.
+    pub fn tokens(&self) -> impl Iterator<Item = (Range<usize>, &SyntaxToken)> + '_ {
+        let mut stack = vec![self.root.children.iter()];
+        let mut offset = 0;
+        std::iter::from_fn(move || loop {
+            let element = match stack.last_mut()?.next() {
+                Some(element) => element,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            match element {
+                SyntaxElement::Node(node) => stack.push(node.children.iter()),
+                SyntaxElement::Token(token) => {
+                    let range = offset..offset + token.len;
+                    offset = range.end;
+                    return Some((range, token));
+                }
+            }
+        })
+    }
+
+    /// Returns the token containing the byte at the given offset, along with its range.
+    pub fn token_at(&self, offset: usize) -> Option<(Range<usize>, &SyntaxToken)> {
+        self.tokens().find(|(range, _)| range.contains(&offset))
+    }
+}
+
+/// Splits source code into tokens and whitespace.
+struct Scanner<'s> {
+    source: &'s str,
+    tokens: Tokens,
+    /// The offset the lexer started at.
+    base: usize,
+    /// The offset up to which the source code has been scanned.
+    position: usize,
+    /// A token that's been lexed, but not returned yet because of the whitespace before it.
+    pending: Option<(SyntaxTokenKind, Range<usize>)>,
+}
+
+impl<'s> Scanner<'s> {
+    fn new(source: &'s str, start: usize) -> Self {
+        Self {
+            source,
+            tokens: Lexer::new(Rc::from(""), source[start..].to_owned()).into_tokens(),
+            base: start,
+            position: start,
+            pending: None,
+        }
+    }
+
+    fn next_token(&mut self) -> Option<(SyntaxTokenKind, Range<usize>)> {
+        if self.pending.is_none() {
+            self.pending = self.tokens.next().map(|(kind, span)| {
+                let range = self.base + span.start.byte..self.base + span.end.byte;
+                (SyntaxTokenKind::Token(kind), range)
+            });
+        }
+        let gap_end = match &self.pending {
+            Some((_, range)) => range.start,
+            None => self.source.len(),
+        };
+        if self.position < gap_end {
+            let rest = &self.source[self.position..gap_end];
+            let (kind, len) = match rest.as_bytes()[0] {
+                b'\n' => (SyntaxTokenKind::Newline, 1),
+                _ => (
+                    SyntaxTokenKind::Whitespace,
+                    rest.find('\n').unwrap_or(rest.len()),
+                ),
+            };
+            let range = self.position..self.position + len;
+            self.position = range.end;
+            return Some((kind, range));
+        }
+        let (kind, range) = self.pending.take()?;
+        self.position = range.end;
+        Some((kind, range))
+    }
+}
+
+/// A node that's still being built.
+struct OpenNode {
+    kind: SyntaxNodeKind,
+    children: Vec<SyntaxElement>,
+    len: usize,
+    /// The token that closes the node.
+    closer: Option<TokenKind>,
+    /// Whether an `elif` was seen, and the `do` that follows it does not start a new block.
+    elif_pending: bool,
+}
+
+impl OpenNode {
+    fn new(kind: SyntaxNodeKind, closer: Option<TokenKind>) -> Self {
+        Self {
+            kind,
+            children: Vec::new(),
+            len: 0,
+            closer,
+            elif_pending: false,
+        }
+    }
+
+    fn push(&mut self, element: SyntaxElement) {
+        self.len += element.len();
+        self.children.push(element);
+    }
+
+    fn finish(self) -> SyntaxNode {
+        SyntaxNode {
+            kind: self.kind,
+            len: self.len,
+            children: self.children,
+        }
+    }
+}
+
+/// Builds the root's children from a stream of tokens.
+#[derive(Default)]
+struct Builder {
+    children: Vec<SyntaxElement>,
+    /// The nodes being built. The first one, if any, is the current item.
+    open: Vec<OpenNode>,
+    /// The last token that's meaningful to the parser.
+    previous: Option<TokenKind>,
+    /// Whether the current line has no tokens or indentation yet.
+    at_line_start: bool,
+}
+
+impl Builder {
+    /// Returns whether the token begins a new item.
+    fn starts_item(&self, kind: &TokenKind) -> bool {
+        match self.open.len() {
+            0 => true,
+            _ if !self.at_line_start => false,
+            1 => !continues_expression(self.previous.as_ref(), kind),
+            _ => matches!(
+                kind,
+                TokenKind::Func | TokenKind::Struct | TokenKind::Enum | TokenKind::Trait
+            ),
+        }
+    }
+
+    fn push(&mut self, kind: SyntaxTokenKind, len: usize) {
+        let Some(token_kind) = significant_token(&kind).cloned() else {
+            self.at_line_start = kind == SyntaxTokenKind::Newline;
+            self.push_element(SyntaxElement::Token(SyntaxToken { kind, len }));
+            return;
+        };
+        if self.starts_item(&token_kind) {
+            self.finish_item();
+            self.open.push(OpenNode::new(SyntaxNodeKind::Item, None));
+        }
+        let token = SyntaxElement::Token(SyntaxToken { kind, len });
+
+        // Closing tokens close the innermost node they match, along with any unterminated nodes
+        // inside of it.
+        if let Some(index) = self.open[1..]
+            .iter()
+            .rposition(|node| node.closer.as_ref() == Some(&token_kind))
+        {
+            let index = index + 1;
+            while self.open.len() > index + 1 {
+                self.close_node();
+            }
+            self.push_element(token);
+            self.close_node();
+        } else {
+            let innermost = self.open.last_mut().unwrap();
+            let opens = match token_kind {
+                TokenKind::Do if innermost.elif_pending => {
+                    innermost.elif_pending = false;
+                    None
+                }
+                TokenKind::Do
+                | TokenKind::Try
+                | TokenKind::Impl
+                | TokenKind::Enum
+                | TokenKind::Trait
+                | TokenKind::As => Some((SyntaxNodeKind::Block, TokenKind::End)),
+                TokenKind::LeftParen => Some((SyntaxNodeKind::Group, TokenKind::RightParen)),
+                TokenKind::LeftBracket => Some((SyntaxNodeKind::Group, TokenKind::RightBracket)),
+                TokenKind::LeftBrace => Some((SyntaxNodeKind::Group, TokenKind::RightBrace)),
+                TokenKind::Elif => {
+                    innermost.elif_pending = true;
+                    None
+                }
+                _ => None,
+            };
+            if let Some((kind, closer)) = opens {
+                self.open.push(OpenNode::new(kind, Some(closer)));
+            }
+            self.push_element(token);
+        }
+        self.previous = Some(token_kind);
+        self.at_line_start = false;
+    }
+
+    fn push_element(&mut self, element: SyntaxElement) {
+        match self.open.last_mut() {
+            Some(node) => node.push(element),
+            None => self.children.push(element),
+        }
+    }
+
+    /// Finishes the innermost open node and adds it to its parent.
+    fn close_node(&mut self) {
+        let node = self.open.pop().unwrap().finish();
+        self.push_element(SyntaxElement::Node(node));
+    }
+
+    /// Finishes the current item, if there is one. Trivia at the end of the item is moved out
+    /// of it, into the root.
+    fn finish_item(&mut self) {
+        // Trivia always ends up in the innermost node, so that's where the item's trailing
+        // trivia is.
+        let Some(innermost) = self.open.last_mut() else {
+            return;
+        };
+        let trivia_start = innermost
+            .children
+            .iter()
+            .rposition(
+                |element| !matches!(element, SyntaxElement::Token(token) if token.kind.is_trivia()),
+            )
+            .map_or(0, |index| index + 1);
+        let trivia = innermost.children.split_off(trivia_start);
+        innermost.len -= trivia.iter().map(SyntaxElement::len).sum::<usize>();
+        while !self.open.is_empty() {
+            self.close_node();
+        }
+        self.children.extend(trivia);
+    }
+
+    fn finish(mut self) -> Vec<SyntaxElement> {
+        self.finish_item();
+        self.children
+    }
+}
+
+/// Returns the kind of the token if it's meaningful to the parser, as opposed to being trivia or
+/// an error.
+fn significant_token(kind: &SyntaxTokenKind) -> Option<&TokenKind> {
+    match kind {
+        SyntaxTokenKind::Token(TokenKind::Comment | TokenKind::Error(_)) => None,
+        SyntaxTokenKind::Token(token_kind) => Some(token_kind),
+        SyntaxTokenKind::Whitespace | SyntaxTokenKind::Newline => None,
+    }
+}
+
+/// Returns whether a token written at the start of a line continues the expression from the
+/// previous line, rather than beginning a new item.
+fn continues_expression(previous: Option<&TokenKind>, next: &TokenKind) -> bool {
+    let previous_expects_more = previous.is_some_and(|previous| {
+        matches!(
+            previous,
+            TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Star
+                | TokenKind::Slash
+                | TokenKind::SlashSlash
+                | TokenKind::Percent
+                | TokenKind::Bang
+                | TokenKind::And
+                | TokenKind::Or
+                | TokenKind::Equal
+                | TokenKind::NotEqual
+                | TokenKind::Less
+                | TokenKind::Greater
+                | TokenKind::LessEqual
+                | TokenKind::GreaterEqual
+                | TokenKind::Implements
+                | TokenKind::Assign
+                | TokenKind::Dot
+                | TokenKind::QuestionDot
+                | TokenKind::Colon
+                | TokenKind::At
+                | TokenKind::Comma
+                | TokenKind::Let
+                | TokenKind::Const
+                | TokenKind::If
+                | TokenKind::Elif
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::In
+                | TokenKind::Raise
+                | TokenKind::Defer
+                | TokenKind::Func
+                | TokenKind::Struct
+                | TokenKind::Enum
+                | TokenKind::Trait
+                | TokenKind::Impl
+                | TokenKind::As
+                | TokenKind::Import
+        )
+    });
+    let next_is_infix = matches!(
+        next,
+        TokenKind::Plus
+            | TokenKind::Minus
+            | TokenKind::Star
+            | TokenKind::Slash
+            | TokenKind::SlashSlash
+            | TokenKind::Percent
+            | TokenKind::And
+            | TokenKind::Or
+            | TokenKind::Equal
+            | TokenKind::NotEqual
+            | TokenKind::Less
+            | TokenKind::Greater
+            | TokenKind::LessEqual
+            | TokenKind::GreaterEqual
+            | TokenKind::Implements
+            | TokenKind::Assign
+            | TokenKind::Dot
+            | TokenKind::QuestionDot
+            | TokenKind::DotDot
+            | TokenKind::Impl
+            | TokenKind::Constructor
+            | TokenKind::Static
+            | TokenKind::Pub
+            | TokenKind::Priv
+    );
+    let continues_long_string = matches!(previous, Some(TokenKind::LongString(_)))
+        && matches!(next, TokenKind::LongString(_));
+    previous_expects_more || next_is_infix || continues_long_string
+}
@@ -1,9 +1,9 @@
 //! Common things, mostly error handling-related.
 
-use std::{borrow::Cow, fmt, rc::Rc};
+use std::{borrow::Cow, fmt, ops::Range, rc::Rc};
 
 /// A source location.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Location {
     pub byte: usize,
     pub line: u32,
@@ -36,12 +36,140 @@ impl Default for Location {
     }
 }
 
+/// A range of source code. The start location is inclusive, and the end location is exclusive.
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    /// The "uninitialized" span, spanning between two uninitialized locations.
+    pub const UNINIT: Self = Self::point(Location::UNINIT);
+
+    /// Creates an empty span at the given location.
+    pub const fn point(location: Location) -> Self {
+        Self {
+            start: location,
+            end: location,
+        }
+    }
+
+    /// Returns whether this span is the uninitialized span.
+    pub fn is_uninit(&self) -> bool {
+        self.start.is_uninit()
+    }
+
+    /// Returns the smallest span containing both `self` and `other`. Uninitialized spans are
+    /// ignored.
+    pub fn union(self, other: Self) -> Self {
+        if self.is_uninit() {
+            other
+        } else if other.is_uninit() {
+            self
+        } else {
+            Self {
+                start: if other.start.byte < self.start.byte {
+                    other.start
+                } else {
+                    self.start
+                },
+                end: if other.end.byte > self.end.byte {
+                    other.end
+                } else {
+                    self.end
+                },
+            }
+        }
+    }
+}
+
 impl std::fmt::Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}:{}", self.line, self.column)
     }
 }
 
+/// Details about a function call that failed because of the arguments passed to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallInfo {
+    /// The rendered signature of the called function, such as `f(x, y)` or `String.repeat/1`.
+    pub callee: Rc<str>,
+    /// The module and location the called function was declared at. This is `None` for foreign
+    /// functions.
+    pub declared_at: Option<(Rc<str>, Location)>,
+    /// The type names of the arguments that were passed, not including `self`.
+    pub argument_types: Vec<Cow<'static, str>>,
+}
+
+impl CallInfo {
+    fn fmt_argument_types(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(")?;
+        for (i, type_name) in self.argument_types.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(type_name)?;
+        }
+        f.write_str(")")
+    }
+
+    fn fmt_declared_at(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((module_name, location)) = &self.declared_at {
+            write!(f, " (declared at {module_name}:{location})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A type mismatch in one of the arguments passed to a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentTypeMismatch {
+    /// The index of the argument, not counting `self`.
+    pub index: usize,
+    pub expected: Cow<'static, str>,
+    pub got: Cow<'static, str>,
+    /// Details about the call. This is filled in by the VM when the error is returned from a
+    /// foreign function.
+    pub call: Option<CallInfo>,
+}
+
+/// Details about a failed assertion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionFailure {
+    /// The source code of the asserted condition, if it's known.
+    pub expression: Option<Rc<str>>,
+    /// The source code of the condition's operands, along with their values rendered in the same
+    /// way as by `debug`. Only operands of comparisons whose values aren't obvious from their source
+    /// code are listed.
+    pub operands: Vec<(Rc<str>, String)>,
+    /// The message passed to `assert`.
+    pub message: Option<String>,
+}
+
+impl fmt::Display for AssertionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("assertion failed")?;
+        if let Some(expression) = &self.expression {
+            write!(f, ": {expression}")?;
+        }
+        if !self.operands.is_empty() {
+            f.write_str(" (")?;
+            for (i, (operand, value)) in self.operands.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{operand} was {value}")?;
+            }
+            f.write_str(")")?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {message}")?;
+        }
+        Ok(())
+    }
+}
+
 /// A [`MethodSignature`][crate::ll::bytecode::MethodSignature] that can be rendered into text. One
 /// can be obtained by calling
 /// [`MethodSignature::render`][crate::ll::bytecode::MethodSignature::render].
@@ -99,6 +227,7 @@ pub enum LanguageErrorKind {
     // Lexer
     InvalidCharacter(char),
     MissingDigitsAfterDecimalPoint,
+    MissingDigitsAfterRadixPrefix,
     MissingExponent,
     UnderscoresWithoutDigits,
     MissingClosingQuote,
@@ -108,19 +237,26 @@ pub enum LanguageErrorKind {
     UEscapeMissingRightBrace,
     UEscapeEmpty,
     UEscapeOutOfRange,
+    XEscapeInvalid,
+    XEscapeOutOfRange,
     InvalidBackslashLiteral(char),
     RawStringMissingOpeningQuote,
+    UnterminatedFrontMatter,
+    InvalidFrontMatterEntry,
+    DuplicateFrontMatterKey(Rc<str>),
     IntLiteralOutOfRange,
     IntRadixOutOfRange,
     ColonExpectedAfterRadix,
     CharacterMissingOpeningApostrophe,
     CharacterMissingClosingApostrophe,
+    LabelNameExpected,
 
     // Parser
     InvalidPrefixToken,
     InvalidInfixToken,
     MissingDo,
     MissingRightParen,
+    MissingRightBracket,
     MissingEnd,
     InvalidIfBranchToken,
     BranchAfterElse,
@@ -128,14 +264,24 @@ pub enum LanguageErrorKind {
     LeftParenExpected,
     UnexpectedEof,
     CommaExpected,
+    InvalidSpread,
+    SpreadInMethodCall,
+    ParallelAssignmentExpected,
     ColonExpectedAfterDictKey,
     RightBracketExpectedToCloseEmptyDict,
     InExpectedAfterForBinding,
     RestMustBeFollowedByRightBrace,
+    ColonExpectedAfterLabel,
+    LoopExpectedAfterLabel,
+    MissingCatch,
 
     // Code generator
-    VariableDoesNotExist(Rc<str>),
+    VariableDoesNotExist {
+        name: Rc<str>,
+        did_you_mean: Option<Rc<str>>,
+    },
     InvalidAssignment,
+    CannotAssignConst(Rc<str>),
     TooManyLocals,
     TooManyGlobals,
     TooManyCaptures,
@@ -143,15 +289,27 @@ pub enum LanguageErrorKind {
     IfExpressionTooLarge,
     OperatorRhsTooLarge,
     LoopTooLarge,
+    TryTooLarge,
+    DeferTooLarge,
+    DeferOutsideOfBlock,
+    JumpOutOfDefer,
     BreakOutsideOfLoop,
+    ContinueOutsideOfLoop,
+    LabelDoesNotExist(Rc<str>),
     TooManyFunctions,
+    TooManyStrings,
     TooManyArguments,
     TooManyParameters,
+    RestParameterNotLast,
+    RestParameterInMethod,
     TooManyMethods,
     InvalidMethodName,
     FunctionKindOutsideImpl,
+    VisibilityOutsideImpl,
     MissingFunctionBody,
     InvalidImplItem,
+    InvalidEnumItem,
+    ConstructorInEnum,
     MissingMethodName,
     TooManyImpls,
     MethodAlreadyImplemented(RenderedSignature),
@@ -160,16 +318,20 @@ pub enum LanguageErrorKind {
     FieldOutsideOfImpl,
     MissingFields(Vec<Rc<str>>),
     ListIsTooLong,
+    ComprehensionHasMultipleElements,
     DictIsTooLarge,
     TooManyTraits,
     InvalidTraitItem,
     TraitMethodCannotHaveBody,
     TraitAlreadyHasMethod(RenderedSignature),
     AsOutsideOfImpl,
+    ModuleDoesNotExist(Rc<str>),
     TooManyTraitsInImpl,
     AsCannotNest,
     FunctionKindInTrait,
+    PrivateTraitMethod,
     InvalidPattern,
+    PatternTooLarge,
     LetRhsMustBeAssignment,
     TupleHasTooManyElements,
     RecordHasTooManyFields,
@@ -177,26 +339,93 @@ pub enum LanguageErrorKind {
     TooManyRecords,
     RestInRecordConstructor,
     CannotAccessDiscardPattern,
+    StructPatternInExpression,
+    NestingTooDeep,
+    DeniedWarning(LanguageWarningKind),
 
     // Runtime
     TypeError {
         expected: Cow<'static, str>,
         got: Cow<'static, str>,
     },
+    ArgumentCount {
+        expected: u16,
+        call: Box<CallInfo>,
+    },
+    TooFewArguments {
+        expected: u16,
+        call: Box<CallInfo>,
+    },
+    ArgumentTypeMismatch(Box<ArgumentTypeMismatch>),
     MethodDoesNotExist {
         type_name: Rc<str>,
-        signature: RenderedSignature,
+        signature: Box<RenderedSignature>,
+        did_you_mean: Option<Box<RenderedSignature>>,
+    },
+    PrivateMethod(Rc<str>),
+    AssertionFailed(Box<AssertionFailure>),
+    DivisionByZero,
+    NonFiniteResult,
+    IndexOutOfBounds {
+        index: f64,
+        len: usize,
+    },
+    InvalidSlice {
+        start: usize,
+        end: usize,
+    },
+    GlobalIsSealed(Rc<str>),
+    StackOverflow,
+    LengthLimitExceeded {
+        type_name: &'static str,
+        len: usize,
+        max: usize,
+    },
+    NestingLimitExceeded {
+        max: usize,
+    },
+    MemoryLimitExceeded {
+        allocated: usize,
+        limit: usize,
     },
     StructAlreadyImplemented,
+    BuiltinExtensionsDisabled(Rc<str>),
+    InvalidBuiltinExtension(Rc<str>),
     UserDataAlreadyBorrowed,
+    SortFunctionBlocked,
+    FiberAlreadyRunning,
+    FiberFinished,
     DoubleMethodImplementation {
         type_name: Rc<str>,
-        signature: RenderedSignature,
+        signature: Box<RenderedSignature>,
     },
     MethodsUnimplemented {
         type_name: Rc<str>,
         methods: Vec<RenderedSignature>,
     },
+    TraitMethodKindMismatch {
+        signature: Box<RenderedSignature>,
+        is_static: bool,
+    },
+    ReplayDiverged {
+        expected: Option<Rc<str>>,
+        called: Rc<str>,
+    },
+    // Returned by foreign functions that can't complete yet. Rather than failing, the fiber
+    // suspends and retries the call when it's resumed.
+    WouldBlock,
+    // Returned by foreign functions whose calls back into the VM ran out of fuel. The fiber
+    // suspends like it does when it runs out of fuel itself.
+    FuelExhausted,
+    /// A value raised with `raise` that wasn't caught. Holds the value rendered as a string; the
+    /// value itself is available from [`RuntimeError::raised`][crate::RuntimeError::raised].
+    Raised(Rc<str>),
+    /// The script asked to stop running with the given exit code, using `Process.exit`.
+    Exit(u8),
+    /// A runtime error in code that a foreign function called back into. The VM merges the
+    /// error's stack trace into the trace of the fiber that called the foreign function, and
+    /// reports the error under its own kind, so errors of this kind never reach the host.
+    Nested(Box<LanguageError>),
 
     User(Box<dyn std::error::Error>),
 }
@@ -206,6 +435,7 @@ impl std::fmt::Display for LanguageErrorKind {
         match self {
             Self::InvalidCharacter(c) => write!(f, "invalid character: {c:?}"),
             Self::MissingDigitsAfterDecimalPoint => write!(f, "missing digits after decimal point"),
+            Self::MissingDigitsAfterRadixPrefix => write!(f, "missing digits after radix prefix"),
             Self::MissingExponent => write!(f, "number exponent expected"),
             Self::UnderscoresWithoutDigits => write!(f, "at least one digit expected, got only underscores"),
             Self::MissingClosingQuote => write!(f, "missing closing quote '\"'"),
@@ -220,18 +450,25 @@ impl std::fmt::Display for LanguageErrorKind {
                 f,
                 "Unicode scalar value in \\u escape is out of range (must be <= 10FFFF and outside of D800..DFFF)"
             ),
+            Self::XEscapeInvalid => write!(f, "\\x escape must be followed by two hexadecimal digits"),
+            Self::XEscapeOutOfRange => write!(f, "character in \\x escape is out of range (must be <= 7F; use \\u{{...}} for other characters)"),
             Self::InvalidBackslashLiteral(c) => write!(f, "invalid extended literal: \\{c}"),
             Self::RawStringMissingOpeningQuote => write!(f, "missing opening quote '\"' in \\r string"),
+            Self::UnterminatedFrontMatter => write!(f, "front matter is missing its closing '---'"),
+            Self::InvalidFrontMatterEntry => write!(f, "front matter entries must be of the form 'key = value'"),
+            Self::DuplicateFrontMatterKey(key) => write!(f, "front matter key '{key}' is declared more than once"),
             Self::IntLiteralOutOfRange => write!(f, "integer literal out of range"),
             Self::IntRadixOutOfRange => write!(f, "integer radix out of range; must be >= 2 and <= 36"),
             Self::ColonExpectedAfterRadix => write!(f, "colon ':' expected after integer radix"),
             Self::CharacterMissingOpeningApostrophe => write!(f, "apostrophe ' expected to begin character literal"),
             Self::CharacterMissingClosingApostrophe => write!(f, "apostrophe ' expected to end character literal"),
+            Self::LabelNameExpected => write!(f, "loop label name expected after apostrophe '"),
 
             Self::InvalidPrefixToken => write!(f, "invalid token in prefix position"),
             Self::InvalidInfixToken => write!(f, "invalid token in infix position"),
             Self::MissingDo => write!(f, "'do' expected"),
             Self::MissingRightParen => write!(f, "missing right parenthesis ')'"),
+            Self::MissingRightBracket => write!(f, "missing right bracket ']'"),
             Self::MissingEnd => write!(f, "missing 'end'"),
             Self::InvalidIfBranchToken => write!(f, "'elif', 'else', or 'end' expected"),
             Self::BranchAfterElse => write!(f, "extraneous branch after 'else'"),
@@ -239,15 +476,34 @@ impl std::fmt::Display for LanguageErrorKind {
             Self::LeftParenExpected => write!(f, "left parenthesis '(' expected"),
             Self::UnexpectedEof => write!(f, "unexpected end of file"),
             Self::CommaExpected => write!(f, "comma ',' expected"),
+            Self::InvalidSpread => {
+                write!(f, "'...' can only be used on the last argument of a function call")
+            }
+            Self::SpreadInMethodCall => write!(f, "arguments cannot be spread into method calls, because methods are looked up by their number of arguments"),
+            Self::ParallelAssignmentExpected => {
+                write!(f, "'=' expected after the targets of a parallel assignment")
+            }
             Self::ColonExpectedAfterDictKey => write!(f, "colon ':' expected after dict key"),
             Self::RightBracketExpectedToCloseEmptyDict => write!(f, "right bracket ']' expected to close empty dict literal [:]"),
             Self::MissingFunctionBody => write!(f, "missing function body ('= expression')"),
             Self::InExpectedAfterForBinding => write!(f, "'in' expected after 'for' loop variable binding"),
             Self::RestMustBeFollowedByRightBrace => write!(f, "'..' in record pattern cannot be followed by any elements"),
+            Self::ColonExpectedAfterLabel => write!(f, "colon ':' expected after loop label"),
+            Self::LoopExpectedAfterLabel => write!(f, "'while' or 'for' loop expected after label"),
+            Self::MissingCatch => write!(f, "'try' must be followed by a 'catch' block"),
             Self::RestInRecordConstructor => write!(f, "'..' may only appear in record patterns"),
 
-            Self::VariableDoesNotExist(name) => write!(f, "variable '{name}' does not exist"),
+            Self::VariableDoesNotExist { name, did_you_mean } => {
+                write!(f, "variable '{name}' does not exist")?;
+                if let Some(suggestion) = did_you_mean {
+                    write!(f, " (did you mean '{suggestion}'?)")?;
+                }
+                Ok(())
+            }
             Self::InvalidAssignment => write!(f, "invalid left hand side of assignment"),
+            Self::CannotAssignConst(name) => {
+                write!(f, "cannot assign to '{name}', which was declared with 'const'")
+            }
             Self::TooManyLocals => write!(f, "too many local variables"),
             Self::TooManyGlobals => write!(f, "too many global variables"),
             Self::TooManyCaptures => write!(f, "too many variables captured in the closure"),
@@ -255,17 +511,35 @@ impl std::fmt::Display for LanguageErrorKind {
             Self::IfExpressionTooLarge => write!(f, "'if' expression is too large"),
             Self::OperatorRhsTooLarge => write!(f, "the right-hand side of the operator is too large"),
             Self::LoopTooLarge => write!(f, "loop is too large"),
+            Self::TryTooLarge => write!(f, "'try' block is too large"),
+            Self::DeferTooLarge => write!(f, "block with 'defer' is too large"),
+            Self::DeferOutsideOfBlock => write!(f, "'defer' can only be used as a statement inside of a block"),
+            Self::JumpOutOfDefer => write!(f, "'return', 'break', and 'continue' cannot jump out of a deferred expression"),
             Self::BreakOutsideOfLoop => write!(f, "'break' cannot be used outside of a loop"),
+            Self::ContinueOutsideOfLoop => write!(f, "'continue' cannot be used outside of a loop"),
+            Self::LabelDoesNotExist(name) => write!(f, "no enclosing loop is labeled '{name}"),
             Self::TooManyFunctions => write!(f, "too many unique functions"),
+            Self::TooManyStrings => write!(f, "too many unique string literals"),
             Self::TooManyArguments => write!(f, "too many arguments"),
             Self::TooManyParameters => write!(f, "too many parameters"),
+            Self::RestParameterNotLast => write!(f, "rest parameter must be the last parameter"),
+            Self::RestParameterInMethod => write!(f, "methods cannot have rest parameters"),
             Self::TooManyMethods => write!(f, "too many instance functions with different signatures"),
             Self::InvalidMethodName => write!(f, "method name must be an identifier"),
             Self::FunctionKindOutsideImpl => write!(
                 f,
                 "function kinds (static, constructor) can only be used in 'impl' blocks"
             ),
+            Self::VisibilityOutsideImpl => write!(
+                f,
+                "visibility (pub, priv) can only be specified for functions in 'impl' blocks"
+            ),
             Self::InvalidImplItem => write!(f, "only functions and 'as' blocks are allowed in 'impl' blocks"),
+            Self::InvalidEnumItem => write!(
+                f,
+                "only variants, functions, and 'as' blocks are allowed in enums; variants are written like 'Name' or 'Name(a, b)'"
+            ),
+            Self::ConstructorInEnum => write!(f, "enums cannot have constructors; their values are created by variants"),
             Self::MissingMethodName => write!(f, "missing method name"),
             Self::TooManyImpls => write!(f, "too many 'impl' blocks"),
             Self::MethodAlreadyImplemented(signature) => {
@@ -285,6 +559,9 @@ impl std::fmt::Display for LanguageErrorKind {
                 )
             }
             Self::ListIsTooLong => write!(f, "list literal has too many elements"),
+            Self::ComprehensionHasMultipleElements => {
+                write!(f, "comprehensions can only have a single element")
+            }
             Self::DictIsTooLarge => write!(f, "dict literal has too many pairs"),
             Self::TooManyTraits => write!(f, "too many traits"),
             Self::InvalidTraitItem => write!(f, "only function prototypes are allowed in traits"),
@@ -293,10 +570,13 @@ impl std::fmt::Display for LanguageErrorKind {
                 write!(f, "trait already declares the method {signature}")
             }
             Self::AsOutsideOfImpl => write!(f, "'as' is not allowed outside of 'impl' blocks"),
+            Self::ModuleDoesNotExist(name) => write!(f, "module '{name}' does not exist"),
             Self::TooManyTraitsInImpl => write!(f, "too many 'as' blocks in 'impl'"),
             Self::AsCannotNest => write!(f, "'as' blocks cannot nest"),
-            Self::FunctionKindInTrait => write!(f, "trait functions must be instance methods (cannot be constructors nor statics)"),
+            Self::FunctionKindInTrait => write!(f, "trait functions cannot be constructors; use 'static' to require a static function"),
+            Self::PrivateTraitMethod => write!(f, "trait methods cannot be private"),
             Self::InvalidPattern => write!(f, "invalid pattern for destructuring into variables"),
+            Self::PatternTooLarge => write!(f, "pattern is too large"),
             Self::LetRhsMustBeAssignment => write!(f, "the right hand side of 'let' must be an assignment, like 'let x = y'"),
             Self::TupleHasTooManyElements => write!(f, "tuple has too many elements"),
             Self::RecordHasTooManyFields => write!(f, "record has too many fields"),
@@ -306,9 +586,76 @@ impl std::fmt::Display for LanguageErrorKind {
             Self::TypeError { expected, got } => {
                 write!(f, "type mismatch, expected {expected} but got {got}")
             }
-            Self::MethodDoesNotExist { type_name, signature } => write!(f, "method {signature} is not defined for {type_name}"),
+            Self::ArgumentCount { expected, call } => {
+                write!(f, "wrong number of arguments to {}", call.callee)?;
+                call.fmt_declared_at(f)?;
+                write!(f, ", expected {expected} but got {} ", call.argument_types.len())?;
+                call.fmt_argument_types(f)
+            }
+            Self::TooFewArguments { expected, call } => {
+                write!(f, "wrong number of arguments to {}", call.callee)?;
+                call.fmt_declared_at(f)?;
+                write!(f, ", expected at least {expected} but got {} ", call.argument_types.len())?;
+                call.fmt_argument_types(f)
+            }
+            Self::ArgumentTypeMismatch(mismatch) => {
+                let ArgumentTypeMismatch { index, expected, got, call } = &**mismatch;
+                write!(f, "type mismatch at argument {}, expected {expected} but got {got}", index + 1)?;
+                if let Some(call) = call {
+                    write!(f, " in call to {}", call.callee)?;
+                    call.fmt_declared_at(f)?;
+                    f.write_str(" with arguments ")?;
+                    call.fmt_argument_types(f)?;
+                }
+                Ok(())
+            }
+            Self::MethodDoesNotExist { type_name, signature, did_you_mean } => {
+                write!(f, "method {signature} is not defined for {type_name}")?;
+                if let Some(suggestion) = did_you_mean {
+                    write!(f, " (did you mean {suggestion}?)")?;
+                }
+                Ok(())
+            }
+            Self::AssertionFailed(failure) => write!(f, "{failure}"),
+            Self::PrivateMethod(name) => {
+                write!(f, "{name} is private and can only be called from within its 'impl' block")
+            }
+            Self::DivisionByZero => write!(f, "attempt to divide by zero"),
+            Self::NonFiniteResult => write!(f, "result of arithmetic is not a finite number"),
+            Self::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} is out of bounds (the length is {len})")
+            }
+            Self::InvalidSlice { start, end } => {
+                write!(f, "slice starts at {start}, which is past its end at {end}")
+            }
+            Self::StackOverflow => write!(f, "stack overflow"),
+            Self::LengthLimitExceeded { type_name, len, max } => {
+                write!(f, "{type_name} of length {len} exceeds the limit of {max}")
+            }
+            Self::NestingLimitExceeded { max } => {
+                write!(f, "value is nested more than {max} levels deep")
+            }
+            Self::MemoryLimitExceeded { allocated, limit } => {
+                write!(f, "{allocated} bytes allocated exceeds the memory limit of {limit}")
+            }
+            Self::GlobalIsSealed(name) => {
+                write!(f, "global '{name}' is sealed and cannot be reassigned")
+            }
             Self::StructAlreadyImplemented => write!(f, "this struct is already implemented"),
+            Self::BuiltinExtensionsDisabled(type_name) => write!(
+                f,
+                "{type_name} is a built-in type, and extending built-in types is not enabled"
+            ),
+            Self::InvalidBuiltinExtension(type_name) => write!(
+                f,
+                "extensions of built-in type {type_name} can only contain instance and static functions"
+            ),
             Self::UserDataAlreadyBorrowed => write!(f, "this user data is already borrowed"),
+            Self::SortFunctionBlocked => {
+                write!(f, "the function passed to a sort cannot suspend the fiber")
+            }
+            Self::FiberAlreadyRunning => write!(f, "cannot resume a fiber that's already running"),
+            Self::FiberFinished => write!(f, "cannot resume a fiber that has finished"),
             Self::DoubleMethodImplementation { type_name, signature } => {
                 write!(f, "method {signature} is already implemented by {type_name}")
             }
@@ -322,17 +669,145 @@ impl std::fmt::Display for LanguageErrorKind {
                 }
                 Ok(())
             }
+            Self::TraitMethodKindMismatch { signature, is_static: true } => {
+                write!(f, "method {signature} must be implemented as a static function")
+            }
+            Self::TraitMethodKindMismatch { signature, is_static: false } => {
+                write!(f, "method {signature} must be implemented as an instance method")
+            }
             Self::CannotAccessDiscardPattern => {
                 write!(f, "'_' is a used for discarding values in variable declarations and cannot be used in expressions")
             }
+            Self::StructPatternInExpression => {
+                write!(f, "struct patterns can only be used for destructuring values, not in expressions")
+            }
+            Self::DeniedWarning(warning) => write!(f, "{warning}"),
+            Self::NestingTooDeep => write!(f, "expression is nested too deeply"),
+            Self::ReplayDiverged { expected: Some(expected), called } => write!(
+                f,
+                "execution diverged from the replayed trace: expected a call to {expected}, but {called} was called"
+            ),
+            Self::ReplayDiverged { expected: None, called } => write!(
+                f,
+                "execution diverged from the replayed trace: the trace has ended, but {called} was called"
+            ),
+            Self::WouldBlock => write!(f, "operation would block"),
+            Self::FuelExhausted => write!(f, "out of fuel"),
+            Self::Raised(value) => write!(f, "{value}"),
+            Self::Exit(code) => write!(f, "exited with code {code}"),
+            Self::Nested(error) => write!(f, "{}", error.kind()),
 
             Self::User(error) => write!(f, "{error}"),
         }
     }
 }
 
+impl LanguageErrorKind {
+    /// Returns whether a `try` expression can catch a runtime error of this kind. Errors that
+    /// signal a problem with the host rather than the script, such as a replay diverging from its
+    /// trace, always propagate to the host, and so do requests to exit.
+    pub fn is_catchable(&self) -> bool {
+        match self {
+            Self::ReplayDiverged { .. } | Self::Exit(_) => false,
+            Self::Nested(error) => error.kind().is_catchable(),
+            _ => true,
+        }
+    }
+
+    /// Returns a suggestion on how to fix the error, for errors that are commonly caused by
+    /// mistakes whose fix isn't obvious from the message alone.
+    pub fn hint(&self) -> Option<&'static str> {
+        Some(match self {
+            Self::InvalidPrefixToken => "an expression was expected here",
+            Self::InvalidInfixToken => {
+                "an operator, method call, or the end of the line was expected here"
+            }
+            Self::MissingDo => {
+                "the bodies of loops and branches are written like 'while condition do ... end'"
+            }
+            Self::MissingEnd => "the block that starts here must be closed with 'end'",
+            Self::MissingRightParen => "the parenthesis that was opened here must be closed",
+            Self::MissingRightBracket => "the bracket that was opened here must be closed",
+            Self::CommaExpected => {
+                "arguments and elements are separated with commas, like '(a, b)'"
+            }
+            Self::MissingCatch => "errors are caught like 'try expression catch error handler end'",
+            Self::MissingFunctionBody => {
+                "functions are declared like 'func name(parameters) = expression'"
+            }
+            Self::LetRhsMustBeAssignment => {
+                "to declare a variable without a value, assign 'nil' to it"
+            }
+            Self::VariableDoesNotExist {
+                did_you_mean: None, ..
+            } => "variables must be declared with 'let' before they are used",
+            Self::InvalidAssignment => {
+                "only variables, fields, and indexed elements can be assigned to"
+            }
+            Self::CannotAssignConst(_) => "declare the variable with 'let' to make it reassignable",
+            Self::BreakOutsideOfLoop => "to leave a function early, use 'return'",
+            Self::FieldOutsideOfImpl => {
+                "fields can be exposed to other code through methods declared in the 'impl' block"
+            }
+            Self::MissingFields(_) => {
+                "every field must be assigned in each constructor, even if only to nil"
+            }
+            Self::FunctionKindOutsideImpl | Self::VisibilityOutsideImpl => {
+                "methods are declared inside 'impl' blocks, like 'struct Name impl ... end'"
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// User errors are displayed transparently, so their source is the user error's own source.
+impl std::error::Error for LanguageErrorKind {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::User(error) => error.source(),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the candidate most similar to `name`, for use in "did you mean" suggestions.
+/// Candidates that are too different from `name` to plausibly be a typo are not considered.
+pub(crate) fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        // Candidates usually come from hash maps, so ties are broken by name to make the
+        // suggestion deterministic.
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Computes the Levenshtein distance between two strings.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+    let mut current_row = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current_row[0] = i + 1;
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution_cost = usize::from(a_char != b_char);
+            current_row[j + 1] = (previous_row[j] + substitution_cost)
+                .min(previous_row[j + 1] + 1)
+                .min(current_row[j] + 1);
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+    previous_row[b.len()]
+}
+
 /// An entry of a stack trace.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct StackTraceEntry {
     /// The name of the current function.
     pub function_name: Rc<str>,
@@ -342,47 +817,204 @@ pub struct StackTraceEntry {
     pub location: Location,
 }
 
+/// How many identical stack trace entries in a row are displayed before the rest are elided.
+const MAX_REPEATED_ENTRIES: usize = 3;
+
+/// Writes how many times the previous stack trace entry was repeated, if it was repeated too many
+/// times to be displayed in full.
+fn write_repeats(f: &mut fmt::Formatter<'_>, repeats: usize) -> fmt::Result {
+    if repeats >= MAX_REPEATED_ENTRIES {
+        let hidden = repeats - MAX_REPEATED_ENTRIES + 1;
+        write!(f, "\n    ... (previous entry repeated {hidden} more times)")?;
+    }
+    Ok(())
+}
+
+/// A line of source code with a range of characters underlined, displayed alongside errors.
+#[derive(Debug, Clone)]
+pub struct Snippet {
+    /// The 1-based number of the line.
+    pub line: u32,
+    /// The text of the line, without the line terminator.
+    pub text: String,
+    /// The range of characters (not bytes) within the line that should be underlined.
+    pub underline: Range<usize>,
+}
+
+impl Snippet {
+    /// Extracts the snippet pointed to by `span` from the given source code. Returns `None` if
+    /// the span does not point inside the source.
+    ///
+    /// Only the first line of the span is included in the snippet. Empty spans underline a single
+    /// character.
+    pub fn new(source: &str, span: Span) -> Option<Self> {
+        if span.is_uninit() {
+            return None;
+        }
+        let Span { start, end } = span;
+        let before = source.get(..start.byte)?;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = source[start.byte..]
+            .find('\n')
+            .map(|i| start.byte + i)
+            .unwrap_or(source.len());
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        let underline_start = before[line_start..].chars().count();
+        let underline_len = source
+            .get(start.byte..end.byte.clamp(start.byte, text.len() + line_start))
+            .map(|underlined| underlined.chars().count())
+            .unwrap_or(0)
+            .max(1);
+        Some(Self {
+            line: start.line,
+            text: text.to_owned(),
+            underline: underline_start..underline_start + underline_len,
+        })
+    }
+}
+
+impl fmt::Display for Snippet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{line_number} | {}", self.text)?;
+        write!(f, "{gutter} | ")?;
+        // Tabs are preserved so that the underline stays aligned with the text above it.
+        for c in self.text.chars().take(self.underline.start) {
+            f.write_str(if c == '\t' { "\t" } else { " " })?;
+        }
+        let underline_len = self.underline.len().max(1);
+        write!(f, "{}", "^".repeat(underline_len))
+    }
+}
+
 /// An error.
+///
+/// Formatting an error with the alternate flag (`{:#}`) includes the snippet of source code the
+/// error points to, if one is available, along with the kind's [hint][LanguageErrorKind::hint].
 #[derive(Debug)]
 pub enum LanguageError {
     /// A compile-time error.
     Compile {
         kind: LanguageErrorKind,
         module_name: Rc<str>,
-        location: Location,
+        /// The span of the offending code. The error message reports the span's starting location.
+        span: Span,
+        snippet: Option<Box<Snippet>>,
     },
     /// A runtime error.
     Runtime {
         kind: LanguageErrorKind,
         call_stack: Vec<StackTraceEntry>,
+        snippet: Option<Box<Snippet>>,
     },
 }
 
-impl std::fmt::Display for LanguageError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        struct FileLocation<'a>(&'a str, Location);
+impl LanguageError {
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> &LanguageErrorKind {
+        match self {
+            LanguageError::Compile { kind, .. } | LanguageError::Runtime { kind, .. } => kind,
+        }
+    }
 
-        impl std::fmt::Display for FileLocation<'_> {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                let Self(file, location) = self;
-                if location.is_uninit() {
-                    write!(f, "{file}")
-                } else {
-                    write!(f, "{file}:{location}")
-                }
+    /// Returns the module and location the error points to. For runtime errors, this is the
+    /// innermost location in the call stack that's within a module.
+    pub fn location(&self) -> Option<(&Rc<str>, Location)> {
+        match self {
+            LanguageError::Compile {
+                module_name, span, ..
+            } => Some((module_name, span.start)),
+            LanguageError::Runtime { call_stack, .. } => call_stack
+                .iter()
+                .rev()
+                .find(|entry| !entry.location.is_uninit())
+                .map(|entry| (&entry.module_name, entry.location)),
+        }
+    }
+
+    /// Returns the span of code the error points to. For runtime errors, this is an empty span
+    /// at the error's [location][Self::location].
+    pub fn span(&self) -> Option<(&Rc<str>, Span)> {
+        match self {
+            LanguageError::Compile {
+                module_name, span, ..
+            } => Some((module_name, *span)),
+            LanguageError::Runtime { .. } => self
+                .location()
+                .map(|(module_name, location)| (module_name, Span::point(location))),
+        }
+    }
+
+    /// Returns the source snippet attached to the error, if any.
+    pub fn snippet(&self) -> Option<&Snippet> {
+        match self {
+            LanguageError::Compile { snippet, .. } | LanguageError::Runtime { snippet, .. } => {
+                snippet.as_deref()
             }
         }
+    }
 
+    /// Attaches a source snippet to the error, extracted from the source code of the module the
+    /// error points to.
+    pub fn attach_snippet(&mut self, source: &str) {
+        let new_snippet = self
+            .span()
+            .and_then(|(_, span)| Snippet::new(source, span))
+            .map(Box::new);
+        match self {
+            LanguageError::Compile { snippet, .. } | LanguageError::Runtime { snippet, .. } => {
+                *snippet = new_snippet
+            }
+        }
+    }
+}
+
+/// Displays a location in a file as `file:line:column`, or just `file` if the location is not
+/// known.
+pub(crate) struct FileLocation<'a>(pub(crate) &'a str, pub(crate) Location);
+
+impl std::fmt::Display for FileLocation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self(file, location) = self;
+        if location.is_uninit() {
+            write!(f, "{file}")
+        } else {
+            write!(f, "{file}:{location}")
+        }
+    }
+}
+
+impl std::fmt::Display for LanguageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             LanguageError::Compile {
                 kind,
                 module_name,
-                location,
+                span,
+                snippet,
             } => {
-                write!(f, "{module_name}:{location}: error: {kind}")
+                write!(f, "{module_name}:{}: error: {kind}", span.start)?;
+                if f.alternate() {
+                    if let Some(snippet) = snippet {
+                        write!(f, "\n{snippet}")?;
+                    }
+                    if let Some(hint) = kind.hint() {
+                        write!(f, "\n  = hint: {hint}")?;
+                    }
+                }
+                Ok(())
             }
-            LanguageError::Runtime { kind, call_stack } => {
+            LanguageError::Runtime {
+                kind,
+                call_stack,
+                snippet,
+            } => {
                 writeln!(f, "error: {kind}")?;
+                if let (true, Some(snippet)) = (f.alternate(), snippet) {
+                    writeln!(f, "{snippet}")?;
+                }
                 write!(f, "stack traceback (most recent call first):")?;
                 let file_location_width = call_stack
                     .iter()
@@ -393,7 +1025,21 @@ impl std::fmt::Display for LanguageError {
                     })
                     .max()
                     .unwrap_or(20);
+                // Deep recursion produces long runs of identical entries, so only the first few of
+                // them are displayed.
+                let mut repeats = 0;
+                let mut previous: Option<&StackTraceEntry> = None;
                 for entry in call_stack.iter().rev() {
+                    if previous == Some(entry) {
+                        repeats += 1;
+                        if repeats >= MAX_REPEATED_ENTRIES {
+                            continue;
+                        }
+                    } else {
+                        write_repeats(f, repeats)?;
+                        repeats = 0;
+                    }
+                    previous = Some(entry);
                     write!(
                         f,
                         "\n    {:width$}  {}",
@@ -403,8 +1049,215 @@ impl std::fmt::Display for LanguageError {
                         width = file_location_width,
                     )?;
                 }
+                write_repeats(f, repeats)?;
                 Ok(())
             }
         }
     }
 }
+
+/// The source of an error is its kind, without the location and stack trace. Errors raised by
+/// user code (such as foreign functions) have the user's error as their source instead, so that
+/// it can be recovered by walking the source chain.
+impl std::error::Error for LanguageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.kind() {
+            LanguageErrorKind::User(error) => Some(&**error),
+            kind => Some(kind),
+        }
+    }
+}
+
+/// The kind of a warning.
+#[derive(Debug, Clone)]
+pub enum LanguageWarningKind {
+    UnusedVariable(Rc<str>),
+    UnusedAssignment(Rc<str>),
+    UnusedResult,
+    UnreachableCode,
+    ShadowedVariable(Rc<str>),
+    RedeclaredVariable(Rc<str>),
+    ConstantCondition(bool),
+    ShadowedBuiltin(Rc<str>),
+    FunctionComparison,
+    Custom {
+        lint: &'static str,
+        message: Rc<str>,
+    },
+}
+
+impl LanguageWarningKind {
+    /// Returns the lint this warning belongs to.
+    pub fn lint(&self) -> Lint {
+        match self {
+            Self::UnusedVariable(_) => Lint::UnusedVariable,
+            Self::UnusedAssignment(_) => Lint::UnusedAssignment,
+            Self::UnusedResult => Lint::UnusedResult,
+            Self::UnreachableCode => Lint::UnreachableCode,
+            Self::ShadowedVariable(_) => Lint::ShadowedVariable,
+            Self::RedeclaredVariable(_) => Lint::RedeclaredVariable,
+            Self::ConstantCondition(_) => Lint::ConstantCondition,
+            Self::ShadowedBuiltin(_) => Lint::ShadowedBuiltin,
+            Self::FunctionComparison => Lint::FunctionComparison,
+            Self::Custom { lint, .. } => Lint::Custom(lint),
+        }
+    }
+}
+
+impl fmt::Display for LanguageWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnusedVariable(name) => write!(f, "unused variable '{name}'"),
+            Self::UnusedAssignment(name) => {
+                write!(f, "the value assigned to '{name}' is never read")
+            }
+            Self::UnusedResult => write!(f, "the result of this expression is unused"),
+            Self::UnreachableCode => write!(f, "unreachable code"),
+            Self::ShadowedVariable(name) => {
+                write!(
+                    f,
+                    "variable '{name}' shadows a variable from an outer scope"
+                )
+            }
+            Self::RedeclaredVariable(name) => {
+                write!(f, "variable '{name}' is already declared in this scope")
+            }
+            Self::ConstantCondition(true) => write!(f, "this condition is always true"),
+            Self::ConstantCondition(false) => write!(f, "this condition is always false"),
+            Self::ShadowedBuiltin(name) => write!(f, "variable '{name}' shadows a builtin"),
+            Self::FunctionComparison => write!(
+                f,
+                "functions are compared by identity; did you mean to call the function?"
+            ),
+            Self::Custom { message, .. } => write!(f, "{message}"),
+        }
+    }
+}
+
+/// A class of warnings whose severity can be configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A local variable was declared but never used. Variables whose names start with an
+    /// underscore `_` are exempt from this lint.
+    UnusedVariable,
+    /// A value was assigned to a local variable, but the variable goes out of scope before the
+    /// value is read. This usually means the assignment is left over from refactoring, or that
+    /// the value was meant to be used afterwards.
+    ///
+    /// Only the last assignment before the variable's scope ends is checked. Variables captured
+    /// by closures or read by `defer` expressions are exempt, as they may be read at any time.
+    UnusedAssignment,
+    /// The result of an expression without side effects was discarded.
+    UnusedResult,
+    /// Code following a `break` or `return` can never be executed.
+    UnreachableCode,
+    /// A local variable was declared with the same name as a variable in an outer scope.
+    ShadowedVariable,
+    /// A variable was declared with the same name as another variable in the same scope, which
+    /// makes the previous variable inaccessible for the rest of the scope.
+    ///
+    /// Redeclaring variables is a common idiom, so this lint is allowed by default. Denying it
+    /// makes every variable name unique within its block.
+    RedeclaredVariable,
+    /// The condition of an `if`, `elif`, or `while` is a literal, so only one of the branches can
+    /// ever execute. `while true` is exempt from this lint, as it's the idiomatic infinite loop.
+    ConstantCondition,
+    /// A variable was declared with the same name as a global set by the embedder, such as
+    /// `print`. Top-level declarations overwrite the builtin for all scripts.
+    ShadowedBuiltin,
+    /// A function was compared using `==` or `!=`. Functions are only equal to themselves, so
+    /// this usually means a call was forgotten, eg. `if get_count == 0`.
+    FunctionComparison,
+    /// A lint emitted by a [lint pass][crate::ll::lint::LintPass] registered by the embedder.
+    Custom(&'static str),
+}
+
+impl Lint {
+    /// All lints built into the compiler.
+    pub const BUILTIN: &'static [Lint] = &[
+        Self::UnusedVariable,
+        Self::UnusedAssignment,
+        Self::UnusedResult,
+        Self::UnreachableCode,
+        Self::ShadowedVariable,
+        Self::RedeclaredVariable,
+        Self::ConstantCondition,
+        Self::ShadowedBuiltin,
+        Self::FunctionComparison,
+    ];
+
+    /// Returns the lint's name, in `snake_case`. Custom lints are named by their lint pass.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UnusedVariable => "unused_variable",
+            Self::UnusedAssignment => "unused_assignment",
+            Self::UnusedResult => "unused_result",
+            Self::UnreachableCode => "unreachable_code",
+            Self::ShadowedVariable => "shadowed_variable",
+            Self::RedeclaredVariable => "redeclared_variable",
+            Self::ConstantCondition => "constant_condition",
+            Self::ShadowedBuiltin => "shadowed_builtin",
+            Self::FunctionComparison => "function_comparison",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// Returns the [builtin][Self::BUILTIN] lint with the given [name][Self::name].
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::BUILTIN
+            .iter()
+            .copied()
+            .find(|lint| lint.name() == name)
+    }
+
+    /// Returns the severity the lint has unless configured otherwise.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Self::RedeclaredVariable => Severity::Allow,
+            _ => Severity::Warn,
+        }
+    }
+}
+
+/// How severe a lint is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Severity {
+    /// Warnings are not reported.
+    Allow,
+    /// Warnings are reported but do not prevent compilation from succeeding.
+    #[default]
+    Warn,
+    /// Warnings are turned into compilation errors.
+    Deny,
+}
+
+/// A warning emitted during compilation.
+#[derive(Debug, Clone)]
+pub struct LanguageWarning {
+    pub kind: LanguageWarningKind,
+    pub module_name: Rc<str>,
+    pub location: Location,
+}
+
+impl LanguageWarning {
+    /// Converts the warning into an error, for when its lint is denied.
+    pub fn into_error(self) -> LanguageError {
+        LanguageError::Compile {
+            kind: LanguageErrorKind::DeniedWarning(self.kind),
+            module_name: self.module_name,
+            span: Span::point(self.location),
+            snippet: None,
+        }
+    }
+}
+
+impl fmt::Display for LanguageWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            kind,
+            module_name,
+            location,
+        } = self;
+        write!(f, "{module_name}:{location}: warning: {kind}")
+    }
+}
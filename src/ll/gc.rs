@@ -2,18 +2,30 @@
 
 use std::{
     alloc::{handle_alloc_error, Layout},
+    any,
+    backtrace::Backtrace,
     borrow::Borrow,
-    cell::Cell,
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
     fmt, mem,
     ops::Deref,
     ptr,
+    rc::{Rc, Weak},
+    time::{Duration, Instant},
 };
 
+pub use self::interner::Interning;
+use self::{arena::Arena, interner::Interner};
 use crate::ll::{
-    bytecode::DispatchTable,
-    value::{RawValue, ValueKind},
+    bytecode::{DispatchTable, Library},
+    error::LanguageErrorKind,
+    value::{Closure, RawValue, Str, Struct, Trait, Tuple, UserData, ValueKind},
+    vm::Fiber,
 };
 
+mod arena;
+mod interner;
+
 /// The strategy used for running the GC automatically.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AutoStrategy {
@@ -52,11 +64,81 @@ impl AutoStrategy {
     }
 }
 
+/// How the GC performs collections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GcMode {
+    /// Every collection marks and sweeps the whole heap at once, pausing the program until it's
+    /// done.
+    #[default]
+    StopTheWorld,
+    /// Collections are split into steps interleaved with the program's execution, such that no
+    /// single pause has to process the whole heap. Each step marks or sweeps at most `step_size`
+    /// objects, and one step is taken at every point where a stop-the-world collection could have
+    /// happened. Finishing the mark phase still requires one pause that rescans the roots and the
+    /// objects modified since they were marked.
+    Incremental { step_size: usize },
+}
+
+/// The phase of an incremental collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// No collection is in progress.
+    Idle,
+    /// Objects reachable from the roots are being marked, starting from the ones on the gray
+    /// stack.
+    Marking,
+    /// Unreachable objects are being freed, starting from the allocation at `cursor`.
+    Sweeping { cursor: usize },
+}
+
+/// Statistics about the collections a GC has performed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// The number of collections performed.
+    pub collections: usize,
+    /// The number of times the program was paused to collect garbage. This is the same as the
+    /// number of collections, unless [incremental collection][GcMode::Incremental] is used.
+    pub pauses: usize,
+    /// The total amount of time spent collecting garbage.
+    pub total_pause: Duration,
+    /// The duration of the longest pause.
+    pub max_pause: Duration,
+    /// The total amount of bytes freed by all collections.
+    pub freed_bytes: usize,
+}
+
+impl GcStats {
+    /// Returns the average duration of a pause, or zero if there were none.
+    pub fn average_pause(&self) -> Duration {
+        if self.pauses == 0 {
+            Duration::ZERO
+        } else {
+            self.total_pause / self.pauses as u32
+        }
+    }
+}
+
+/// A function called with a report of leaked objects.
+type LeakHandler = Box<dyn FnOnce(&LeakReport)>;
+
+/// Live object counts and sizes, keyed by the kind of object.
+type AllocationTracker = HashMap<String, (usize, usize)>;
+
 /// An allocator and garbage collector for memory.
 pub struct Memory {
     /// Determines when the next GC cycle should run.
     pub auto_strategy: AutoStrategy,
+    /// When enabled, every automatic collection point runs a full collection regardless of the
+    /// `auto_strategy`, and in debug builds freed memory is poisoned before being returned to the
+    /// allocator. This makes values that aren't properly rooted get freed (and misbehave) as early
+    /// as possible, which is useful for testing foreign functions.
+    pub stress: bool,
+    mode: GcMode,
+    phase: Phase,
     allocated_bytes: usize,
+    /// The number of allocated bytes past which allocating fails, if any.
+    limit: Option<usize>,
+    stats: GcStats,
 
     /// Things managed by the GC.
     allocations: Vec<GcRaw<()>>,
@@ -64,6 +146,40 @@ pub struct Memory {
     /// The "gray stack". Without going too much into what colors mean in GCs, it's used as a way
     /// of combatting stack overflows by doing actual work on the heap.
     gray_stack: Vec<RawValue>,
+    /// Objects that were modified after being marked during an incremental collection. They're
+    /// marked again when the mark phase finishes.
+    gray_again: Vec<RawValue>,
+    /// Dispatch tables allocated during the mark phase of an incremental collection. Their methods
+    /// can be set after they're allocated, so they're only marked when the mark phase finishes.
+    gray_dtables: Vec<GcRaw<DispatchTable>>,
+
+    /// Dispatch tables that were marked during the current cycle but aren't managed by the GC
+    /// (such as the library's builtin dtables.) These are not unmarked by the sweep phase, so
+    /// they need to be unmarked separately; otherwise their methods would never get traced again.
+    /// They're held onto, so that they aren't freed while incremental marking is in progress.
+    marked_unmanaged_dtables: Vec<Gc<DispatchTable>>,
+
+    /// Fibers whose stacks are roots, in addition to the ones passed to `collect`. This lets
+    /// multiple fibers exist at once; the one that's currently running is borrowed, and is skipped
+    /// since its roots are passed to `collect` directly.
+    fibers: Vec<Weak<RefCell<Fiber>>>,
+    /// Roots of fibers that are waiting for a coroutine they resumed to yield. Their stacks can't
+    /// change until the coroutine yields, so a copy of the roots taken before resuming it is
+    /// enough to keep their values alive.
+    suspended_roots: Vec<RawValue>,
+
+    /// Called with a report of leaked objects when the GC is dropped, if any objects leaked.
+    leak_handler: Option<LeakHandler>,
+    /// Tracks live objects per kind while allocation tracking is enabled.
+    allocation_tracker: Option<AllocationTracker>,
+    /// Interned strings and tuples, while interning is enabled.
+    interner: Option<Interner>,
+    /// The arena objects are allocated in, while arena allocation is enabled or objects allocated
+    /// in it are still alive.
+    arena: Option<Arena>,
+    /// Backtraces of allocations made while a leak handler is set, keyed by address.
+    #[cfg(debug_assertions)]
+    allocation_backtraces: HashMap<usize, Backtrace>,
 }
 
 impl Memory {
@@ -74,7 +190,12 @@ impl Memory {
                 next_run: 64 * 1024, // 64 KiB
                 growth_factor: 384,  // = 1.5 * 256
             },
+            stress: false,
+            mode: GcMode::StopTheWorld,
+            phase: Phase::Idle,
             allocated_bytes: 0,
+            limit: None,
+            stats: GcStats::default(),
 
             allocations: Vec::new(),
 
@@ -83,83 +204,642 @@ impl Memory {
             // The value of 32 was picked as a sweet spot. Having less or more causes collection
             // times to be slower for some reason.
             gray_stack: Vec::with_capacity(32),
+            gray_again: Vec::new(),
+            gray_dtables: Vec::new(),
+            marked_unmanaged_dtables: Vec::new(),
+            fibers: Vec::new(),
+            suspended_roots: Vec::new(),
+
+            leak_handler: None,
+            allocation_tracker: None,
+            interner: None,
+            arena: None,
+            #[cfg(debug_assertions)]
+            allocation_backtraces: HashMap::new(),
         }
     }
 
-    /// Returns the amount of bytes currently allocated by the GC.
+    /// Creates a new GC that lets at most `limit` bytes be allocated. See
+    /// [`set_limit`][Self::set_limit].
+    pub fn with_limit(limit: usize) -> Self {
+        let mut gc = Self::new();
+        gc.limit = Some(limit);
+        gc
+    }
+
+    /// Creates a new GC that allocates objects in an arena made of chunks of `chunk_size` bytes.
+    /// See [`set_arena`][Self::set_arena].
+    pub fn with_arena(chunk_size: usize) -> Self {
+        let mut gc = Self::new();
+        gc.set_arena(Some(chunk_size));
+        gc
+    }
+
+    /// Enables allocating objects in an arena made of chunks of the given size, or disables it if
+    /// `None` is passed.
+    ///
+    /// Objects in the arena are allocated by bumping a pointer, and their memory isn't reclaimed
+    /// when they're collected; only their finalizers run. Instead, all of the arena's memory is
+    /// reclaimed at once by [`reset_arena`][Self::reset_arena]. This makes allocation cheap and
+    /// avoids fragmentation when running short-lived scripts one after another.
+    ///
+    /// Only objects allocated while the arena is enabled are put in it. If it's disabled while some
+    /// of them are still alive, the arena is kept until it's reset.
+    pub fn set_arena(&mut self, chunk_size: Option<usize>) {
+        match (&mut self.arena, chunk_size) {
+            (Some(arena), Some(chunk_size)) => {
+                arena.chunk_size = chunk_size;
+                arena.enabled = true;
+            }
+            (arena @ None, Some(chunk_size)) => *arena = Some(Arena::new(chunk_size)),
+            (Some(arena), None) => {
+                arena.enabled = false;
+                if self.live_arena_objects() == 0 {
+                    self.arena = None;
+                }
+            }
+            (None, None) => (),
+        }
+    }
+
+    /// Returns the size of the arena's chunks, or `None` if objects are not allocated in an arena.
+    pub fn arena_chunk_size(&self) -> Option<usize> {
+        self.arena
+            .as_ref()
+            .filter(|arena| arena.enabled)
+            .map(|arena| arena.chunk_size)
+    }
+
+    /// Returns how many objects allocated in the arena are still alive, either because they
+    /// haven't been collected yet or because they're referenced from outside of the GC.
+    pub fn live_arena_objects(&self) -> usize {
+        self.arena.as_ref().map_or(0, |arena| {
+            arena
+                .objects
+                .iter()
+                .filter(|memory| unsafe { !memory.get_mem().freed.get() })
+                .count()
+        })
+    }
+
+    /// Returns how many objects allocated in the arena would still be
+    /// [alive][Self::live_arena_objects] after collecting garbage with the given roots. Nothing is
+    /// freed, so this can be used to find out whether the arena could be reset before changing
+    /// any roots.
+    ///
+    /// # Safety
+    /// All root pointers in values yielded by the iterator must be valid.
+    pub(crate) unsafe fn reachable_arena_objects(
+        &mut self,
+        roots: impl Iterator<Item = RawValue>,
+        library: &Library,
+    ) -> usize {
+        if self.arena.is_none() {
+            return 0;
+        }
+        self.abandon_cycle();
+        self.begin_marking(library);
+        self.push_roots(roots);
+        self.mark_all_gray_reachable(library);
+        for dtable in self.marked_unmanaged_dtables.drain(..) {
+            Gc::as_raw(&dtable).get_mem().reachable.set(false);
+        }
+        // Objects the GC doesn't manage anymore are only referenced from outside of it, and aren't
+        // marked.
+        self.arena.as_ref().map_or(0, |arena| {
+            arena
+                .objects
+                .iter()
+                .filter(|memory| {
+                    let mem = memory.get_mem();
+                    !mem.freed.get() && (mem.reachable.get() || !mem.managed_by_gc.get())
+                })
+                .count()
+        })
+    }
+
+    /// Returns the number of bytes reserved by the arena, including memory that's free for
+    /// allocating more objects.
+    pub fn arena_capacity(&self) -> usize {
+        self.arena.as_ref().map_or(0, |arena| arena.capacity())
+    }
+
+    /// Reclaims all of the arena's memory at once, so that it can be reused by objects allocated
+    /// later. Nothing is done and `false` is returned if any object in the arena is still
+    /// [alive][Self::live_arena_objects]; collecting garbage beforehand frees objects that aren't
+    /// reachable anymore.
+    pub fn reset_arena(&mut self) -> bool {
+        if self.live_arena_objects() > 0 {
+            return false;
+        }
+        if let Some(arena) = &mut self.arena {
+            // SAFETY: None of the objects are alive, so nothing can refer to their memory.
+            unsafe { arena.reset() }
+            if !arena.enabled {
+                self.arena = None;
+            }
+        }
+        true
+    }
+
+    /// Runs `f` with arena allocation temporarily disabled. This is used for objects that live as
+    /// long as the engine, such as the methods of types generated on demand.
+    pub(crate) fn outside_arena<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let enabled = self.arena.as_ref().is_some_and(|arena| arena.enabled);
+        if let Some(arena) = &mut self.arena {
+            arena.enabled = false;
+        }
+        let result = f(self);
+        if let Some(arena) = &mut self.arena {
+            arena.enabled = enabled;
+        }
+        result
+    }
+
+    /// Returns whether the value is an object allocated in the arena.
+    ///
+    /// # Safety
+    /// The value must be valid.
+    pub(crate) unsafe fn is_in_arena(value: RawValue) -> bool {
+        memory_of(value).is_some_and(|memory| memory.get_mem().in_arena)
+    }
+
+    /// Sets the number of bytes that can be allocated at once, or removes the limit if `None`.
+    ///
+    /// Once more memory than this is allocated, the next allocation point in the VM performs a
+    /// collection, and raises a runtime error if that didn't free enough memory.
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        // Objects that grow in place are only remeasured while there's a limit, so their sizes
+        // need to be brought up to date once a limit is set.
+        if self.limit.is_none() && limit.is_some() {
+            for i in 0..self.allocations.len() {
+                unsafe { self.update_heap_size(self.allocations[i]) }
+            }
+        }
+        self.limit = limit;
+    }
+
+    /// Returns the number of bytes that can be allocated at once, or `None` if there's no limit.
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Sets the function that is called when the GC is dropped while some of its objects are still
+    /// referenced by [`Gc`] handles. Such objects outlive the GC and are reported as leaked.
+    ///
+    /// In debug builds, allocations made after the handler is set will also have a backtrace
+    /// included in the report.
+    pub fn set_leak_handler(&mut self, handler: impl FnOnce(&LeakReport) + 'static) {
+        self.leak_handler = Some(Box::new(handler));
+    }
+
+    /// Enables or disables tracking of live objects by kind. While enabled, every allocation and
+    /// deallocation updates per-kind counters, which can be read with
+    /// [`allocation_snapshot`][Self::allocation_snapshot].
+    ///
+    /// Objects that already exist when tracking is enabled are counted as well.
+    pub fn set_allocation_tracking(&mut self, enabled: bool) {
+        if !enabled {
+            self.allocation_tracker = None;
+        } else if self.allocation_tracker.is_none() {
+            let mut tracker = AllocationTracker::new();
+            for &memory in &self.allocations {
+                unsafe { track_allocation(&mut tracker, memory) }
+            }
+            self.allocation_tracker = Some(tracker);
+        }
+    }
+
+    /// Returns whether allocation tracking is enabled.
+    pub fn allocation_tracking(&self) -> bool {
+        self.allocation_tracker.is_some()
+    }
+
+    /// Returns the objects currently managed by the GC grouped by kind, or `None` if allocation
+    /// tracking is disabled.
+    pub fn allocation_snapshot(&self) -> Option<AllocationSnapshot> {
+        let tracker = self.allocation_tracker.as_ref()?;
+        let mut kinds: Vec<_> = tracker
+            .iter()
+            .filter(|(_, &(count, _))| count > 0)
+            .map(|(kind, &(count, bytes))| AllocationStats {
+                kind: kind.clone(),
+                count,
+                bytes,
+            })
+            .collect();
+        kinds.sort_by(|a, b| a.kind.cmp(&b.kind));
+        Some(AllocationSnapshot { kinds })
+    }
+
+    /// Enables interning of strings and tuples with the given settings, or disables it if `None` is
+    /// passed. Only values created after interning is enabled are interned.
+    pub fn set_interning(&mut self, interning: Option<Interning>) {
+        match (&mut self.interner, interning) {
+            (Some(interner), Some(settings)) => interner.settings = settings,
+            (interner, settings) => *interner = settings.map(Interner::new),
+        }
+    }
+
+    /// Returns the interning settings, or `None` if interning is disabled.
+    pub fn interning(&self) -> Option<Interning> {
+        self.interner.as_ref().map(|interner| interner.settings)
+    }
+
+    /// Makes the fiber's stack a GC root for as long as the fiber is alive.
+    pub fn add_fiber(&mut self, fiber: &Rc<RefCell<Fiber>>) {
+        self.fibers.push(Rc::downgrade(fiber));
+    }
+
+    /// Treats the given values as roots until [`pop_suspended_roots`][Self::pop_suspended_roots]
+    /// is called with the returned marker.
+    pub(crate) fn push_suspended_roots(&mut self, roots: impl Iterator<Item = RawValue>) -> usize {
+        let marker = self.suspended_roots.len();
+        self.suspended_roots.extend(roots);
+        marker
+    }
+
+    /// Stops treating the roots pushed since `marker` was returned as roots.
+    pub(crate) fn pop_suspended_roots(&mut self, marker: usize) {
+        self.suspended_roots.truncate(marker);
+    }
+
+    /// Returns the amount of bytes currently allocated by the GC. This includes memory owned by
+    /// the objects, such as the contents of strings and the elements of lists.
     pub fn allocated_bytes(&self) -> usize {
         self.allocated_bytes
     }
 
-    /// Marks and sweeps unused allocations.
+    /// Updates the allocated byte count after the given value may have grown or shrunk in place,
+    /// such as a list that had elements pushed to it.
+    ///
+    /// Measuring isn't free, so this does nothing if there's no memory limit to enforce.
     ///
     /// # Safety
-    /// All root pointers in values yielded by the iterator must be valid.
-    pub(crate) unsafe fn collect(&mut self, roots: impl Iterator<Item = RawValue>) {
-        unsafe fn mark_all_unreachable<T>(memories: impl Iterator<Item = GcRaw<T>>) {
-            for memory in memories {
-                let mem = memory.get_mem();
-                mem.reachable.set(false);
+    /// The value must be valid.
+    pub(crate) unsafe fn remeasure(&mut self, value: RawValue) {
+        if self.limit.is_some() && value.kind() == ValueKind::UserData {
+            let memory = value.get_raw_user_data_unchecked().erase_type();
+            if memory.get_mem().managed_by_gc.get() {
+                self.update_heap_size(memory);
             }
         }
+    }
 
-        unsafe fn sweep_unreachable<T>(memories: &mut Vec<GcRaw<T>>, allocated_bytes: &mut usize) {
-            let mut i = 0;
-            while i < memories.len() {
-                let memory = memories[i];
-                let mem = memory.get_mem();
-                if !mem.reachable.get() {
-                    let data_size = mem.data_size;
-                    GcMem::release(memory);
-                    *allocated_bytes -= data_size;
-                    #[cfg(feature = "trace-gc")]
-                    {
-                        println!(
-                            "gc | freed {} bytes ({:p}), now at {}",
-                            data_size, memory.0, *allocated_bytes
-                        );
-                    }
-                    memories.swap_remove(i);
+    /// Measures the memory owned by the object again, and updates the allocated byte count to
+    /// match.
+    ///
+    /// # Safety
+    /// The memory must be managed by this GC and must not be deallocated.
+    unsafe fn update_heap_size(&mut self, memory: GcRaw<()>) {
+        let heap_size = &memory.get_mem().heap_size;
+        self.allocated_bytes -= heap_size.get();
+        heap_size.set(measure_heap(memory));
+        self.allocated_bytes += heap_size.get();
+    }
+
+    /// Returns statistics about the collections performed so far.
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    /// Sets how collections are performed. Switching to [`GcMode::StopTheWorld`] abandons the
+    /// incremental collection in progress, if any.
+    pub fn set_mode(&mut self, mode: GcMode) {
+        if mode == GcMode::StopTheWorld {
+            unsafe { self.abandon_cycle() }
+        }
+        self.mode = mode;
+    }
+
+    /// Returns how collections are performed.
+    pub fn mode(&self) -> GcMode {
+        self.mode
+    }
+
+    /// Returns whether an incremental collection is in progress.
+    pub fn is_collecting(&self) -> bool {
+        self.phase != Phase::Idle
+    }
+
+    /// Marks and sweeps unused allocations. Dispatch tables owned by the library are always treated
+    /// as roots. An incremental collection in progress is abandoned in favor of this one.
+    ///
+    /// # Safety
+    /// All root pointers in values yielded by the iterator must be valid.
+    pub(crate) unsafe fn collect(
+        &mut self,
+        roots: impl Iterator<Item = RawValue>,
+        library: &Library,
+    ) {
+        let start = Instant::now();
+        let allocated_before = self.allocated_bytes;
+
+        self.abandon_cycle();
+        self.begin_marking(library);
+        self.push_roots(roots);
+        self.mark_all_gray_reachable(library);
+        self.end_marking();
+        self.sweep(0, usize::MAX);
+
+        self.stats.collections += 1;
+        self.record_pause(start, allocated_before);
+    }
+
+    /// Performs one step of an incremental collection, starting a new one if none is in progress.
+    /// Returns whether the collection finished.
+    ///
+    /// # Safety
+    /// All root pointers in values yielded by the iterator must be valid.
+    unsafe fn step(
+        &mut self,
+        roots: impl Iterator<Item = RawValue>,
+        library: &Library,
+        step_size: usize,
+    ) -> bool {
+        let start = Instant::now();
+        let allocated_before = self.allocated_bytes;
+
+        let mut finished = false;
+        match self.phase {
+            Phase::Idle => {
+                self.begin_marking(library);
+                self.push_roots(roots);
+                self.phase = Phase::Marking;
+            }
+            Phase::Marking => {
+                if self.mark_gray_reachable(library, step_size) {
+                    self.finish_marking(roots, library);
+                    self.phase = Phase::Sweeping { cursor: 0 };
+                }
+            }
+            Phase::Sweeping { cursor } => {
+                let cursor = self.sweep(cursor, step_size);
+                if cursor < self.allocations.len() {
+                    self.phase = Phase::Sweeping { cursor };
                 } else {
-                    i += 1;
+                    self.phase = Phase::Idle;
+                    self.stats.collections += 1;
+                    finished = true;
                 }
             }
         }
+        #[cfg(feature = "trace-gc")]
+        {
+            println!("gc | incremental step done, now in phase {:?}", self.phase);
+        }
+
+        self.record_pause(start, allocated_before);
+        finished
+    }
+
+    /// Abandons the incremental collection in progress, if any. Objects that weren't swept yet are
+    /// left for the next collection to free.
+    unsafe fn abandon_cycle(&mut self) {
+        self.gray_stack.clear();
+        self.gray_again.clear();
+        self.gray_dtables.clear();
+        for dtable in self.marked_unmanaged_dtables.drain(..) {
+            Gc::as_raw(&dtable).get_mem().reachable.set(false);
+        }
+        self.phase = Phase::Idle;
+    }
 
+    /// Marks every object unreachable, and pushes the objects that are always reachable onto the
+    /// gray stack.
+    unsafe fn begin_marking(&mut self, library: &Library) {
         // NOTE: Marking all objects as unreachable beforehand is *somehow* faster than doing it
         // during the sweep phase. I believe it might have something to do with the objects being
         // loaded into the CPU cache but I'm really not sure.
-        mark_all_unreachable(self.allocations.iter().copied());
-        for value in roots {
+        for memory in &self.allocations {
+            memory.get_mem().reachable.set(false);
+        }
+        self.mark_foreign_roots(false);
+        for dtable in library.dtables() {
+            self.mark_dtable_reachable_rec(dtable);
+        }
+    }
+
+    /// Pushes the given roots, the roots of suspended fibers, and the stacks of other fibers onto
+    /// the gray stack.
+    unsafe fn push_roots(&mut self, roots: impl Iterator<Item = RawValue>) {
+        self.gray_stack.extend(roots);
+        self.gray_stack.extend_from_slice(&self.suspended_roots);
+        self.fibers.retain(|fiber| fiber.strong_count() > 0);
+        for fiber in &self.fibers {
+            if let Some(fiber) = fiber.upgrade() {
+                if let Ok(fiber) = fiber.try_borrow() {
+                    self.gray_stack.extend(fiber.stack_roots());
+                }
+            }
+        }
+    }
+
+    /// Finishes the mark phase of an incremental collection in one go.
+    ///
+    /// Stacks and globals are modified without barriers, so they're scanned again, along with
+    /// objects the embedder can reach and modify directly and objects that were modified after
+    /// being marked.
+    unsafe fn finish_marking(&mut self, roots: impl Iterator<Item = RawValue>, library: &Library) {
+        self.push_roots(roots);
+        self.mark_foreign_roots(true);
+        for value in mem::take(&mut self.gray_again) {
+            if let Some(memory) = memory_of(value) {
+                memory.get_mem().reachable.set(false);
+            }
+            self.gray_stack.push(value);
+        }
+        for dtable in mem::take(&mut self.gray_dtables) {
+            dtable.get_mem().reachable.set(false);
+            self.mark_dtable_reachable_rec(dtable);
+        }
+        self.mark_all_gray_reachable(library);
+        self.end_marking();
+    }
+
+    /// Cleans up after everything reachable was marked, before unreachable objects are swept.
+    unsafe fn end_marking(&mut self) {
+        if let Some(interner) = &mut self.interner {
+            interner.forget_unreachable();
+        }
+        #[cfg(debug_assertions)]
+        if !self.allocation_backtraces.is_empty() {
+            for memory in &self.allocations {
+                if !memory.get_mem().reachable.get() {
+                    self.allocation_backtraces.remove(&(memory.0 as usize));
+                }
+            }
+        }
+        for dtable in self.marked_unmanaged_dtables.drain(..) {
+            Gc::as_raw(&dtable).get_mem().reachable.set(false);
+        }
+    }
+
+    /// Frees unreachable objects, looking at no more than `budget` allocations starting from the
+    /// one at `cursor`. Returns the index of the first allocation that wasn't looked at.
+    unsafe fn sweep(&mut self, mut cursor: usize, budget: usize) -> usize {
+        let mut tracker = self.allocation_tracker.as_mut();
+        for _ in 0..budget {
+            let Some(&memory) = self.allocations.get(cursor) else {
+                break;
+            };
+            let mem = memory.get_mem();
+            if mem.reachable.get() {
+                cursor += 1;
+                continue;
+            }
+            let data_size = mem.data_size + mem.heap_size.get();
+            if let Some(tracker) = tracker.as_deref_mut() {
+                untrack_allocation(tracker, memory);
+            }
+            GcMem::release(memory, self.stress);
+            self.allocated_bytes -= data_size;
+            #[cfg(feature = "trace-gc")]
+            {
+                println!(
+                    "gc | freed {} bytes ({:p}), now at {}",
+                    data_size, memory.0, self.allocated_bytes
+                );
+            }
+            self.allocations.swap_remove(cursor);
+        }
+        cursor
+    }
+
+    /// Adds a pause that began at `start` to the statistics.
+    fn record_pause(&mut self, start: Instant, allocated_before: usize) {
+        let pause = start.elapsed();
+        self.stats.pauses += 1;
+        self.stats.total_pause += pause;
+        self.stats.max_pause = self.stats.max_pause.max(pause);
+        self.stats.freed_bytes += allocated_before.saturating_sub(self.allocated_bytes);
+    }
+
+    /// Records that `value` was stored in an object on the heap. While an incremental collection
+    /// is marking objects, the value is marked too, as the object might've been marked already.
+    pub(crate) fn shade(&mut self, value: RawValue) {
+        if self.phase == Phase::Marking {
             self.gray_stack.push(value);
-            self.mark_all_gray_reachable();
         }
-        sweep_unreachable(&mut self.allocations, &mut self.allocated_bytes);
     }
 
-    /// Recursively (as in, actually recursively) marks the dtable and its methods reachable.
+    /// Records that `object` may have been modified in place, such as by a foreign method it was
+    /// passed to. If it was marked during the incremental collection in progress, it's marked again
+    /// once the mark phase finishes.
+    ///
+    /// # Safety
+    /// The value must be valid.
+    pub(crate) unsafe fn rescan_later(&mut self, object: RawValue) {
+        if self.phase == Phase::Marking && object.kind() == ValueKind::UserData {
+            let raw = object.get_raw_user_data_unchecked();
+            let mem = raw.get_mem();
+            // Unmarking the object keeps it from being queued more than once, until something
+            // else marks it again.
+            if mem.reachable.get() {
+                mem.reachable.set(false);
+                self.gray_again.push(object);
+            }
+        }
+    }
+
+    /// Marks values that are referenced from outside of the GC, such as [`Value`][crate::Value]s
+    /// held by the embedder, as reachable.
+    ///
+    /// References owned by user data (reported by [`UserData::visit_owned_references`]) are not
+    /// counted as foreign, so that reference cycles going through user data can be collected.
+    /// Values they refer to that aren't managed by the GC yet become managed.
+    ///
+    /// With `rescan`, objects that were marked already are marked again, since the embedder may
+    /// have modified them without a barrier.
+    unsafe fn mark_foreign_roots(&mut self, rescan: bool) {
+        let mut owned_references: HashMap<*const (), usize> = HashMap::new();
+        let mut unmanaged = Vec::new();
+        // Registering memory appends to the list of allocations, so any user data that becomes
+        // managed is visited too.
+        let mut i = 0;
+        while i < self.allocations.len() {
+            let memory = self.allocations[i];
+            i += 1;
+            let Some(value) = as_value(memory) else {
+                continue;
+            };
+            if value.kind() != ValueKind::UserData {
+                continue;
+            }
+            let user_data = value.get_raw_user_data_unchecked().get();
+            user_data.visit_owned_references(&mut |owned| {
+                if let Some(owned) = memory_of(owned) {
+                    *owned_references.entry(owned.0 as *const ()).or_default() += 1;
+                    let mem = owned.get_mem();
+                    if !mem.managed_by_gc.get() {
+                        mem.managed_by_gc.set(true);
+                        mem.reachable.set(false);
+                        unmanaged.push(owned);
+                    }
+                }
+            });
+            for memory in unmanaged.drain(..) {
+                self.add_allocation(memory);
+            }
+        }
+
+        for &memory in &self.allocations {
+            let mem = memory.get_mem();
+            let references = mem.rc.get();
+            if references == 0 {
+                continue;
+            }
+            let Some(value) = as_value(memory) else {
+                continue;
+            };
+            if rescan && mem.reachable.get() {
+                mem.reachable.set(false);
+                self.gray_stack.push(value);
+                continue;
+            }
+            let owned = owned_references
+                .get(&(memory.0 as *const ()))
+                .copied()
+                .unwrap_or(0);
+            if references > owned {
+                self.gray_stack.push(value);
+            }
+        }
+    }
+
+    /// Recursively (as in, actually recursively) marks the dtable reachable, and pushes its methods
+    /// onto the gray stack.
     unsafe fn mark_dtable_reachable_rec(&mut self, mem: GcRaw<DispatchTable>) {
         if !mem.get_mem().reachable.get() {
             mem.mark_reachable();
+            if !mem.get_mem().managed_by_gc.get() {
+                self.marked_unmanaged_dtables.push(Gc::from_raw(mem));
+            }
             let dtable = mem.get();
             if let Some(instance) = dtable.instance {
                 // NOTE: Recurring here is okay because we never have dtables that are more than two
                 // levels deep.
                 self.mark_dtable_reachable_rec(instance);
             }
-            for method in dtable.methods() {
-                self.gray_stack.push(RawValue::from(method));
-                self.mark_all_gray_reachable();
-            }
+            self.gray_stack.extend(dtable.methods().map(RawValue::from));
+            self.gray_stack.extend(dtable.type_value());
         }
     }
 
-    /// Recursively marks all values on the gray stack reachable, beginning with the bottom-most
-    /// value.
-    unsafe fn mark_all_gray_reachable(&mut self) {
+    /// Recursively marks all values on the gray stack reachable.
+    unsafe fn mark_all_gray_reachable(&mut self, library: &Library) {
+        self.mark_gray_reachable(library, usize::MAX);
+    }
+
+    /// Marks values on the gray stack reachable, beginning with the top-most value, until `budget`
+    /// values were taken off the stack. Returns whether the gray stack is empty.
+    unsafe fn mark_gray_reachable(&mut self, library: &Library, budget: usize) -> bool {
         // NOTE: Unlike `mark_dtable_reachable_rec` this function does not actually recur.
         // This is to prevent scripters from trivially causing a stack overflow.
-        while let Some(value) = self.gray_stack.pop() {
+        for _ in 0..budget {
+            let Some(value) = self.gray_stack.pop() else {
+                break;
+            };
             match value.kind() {
                 ValueKind::Nil | ValueKind::Boolean | ValueKind::Number => (),
                 ValueKind::String => {
@@ -199,22 +879,34 @@ impl Memory {
                     let raw = value.get_raw_user_data_unchecked();
                     if !raw.get_mem().reachable.get() {
                         raw.mark_reachable();
-                        let dtable = raw.get().dtable_gcraw(None);
+                        let dtable = raw.get().dtable_gcraw(Some(library));
                         self.mark_dtable_reachable_rec(dtable);
                         raw.get().visit_references(&mut |value| {
                             self.gray_stack.push(value);
                         });
+                        raw.get().visit_owned_references(&mut |value| {
+                            self.gray_stack.push(value);
+                        });
                     }
                 }
             }
         }
+        self.gray_stack.is_empty()
     }
 
     /// Performs an _automatic_ collection.
     ///
     /// Automatic collections only trigger upon specific conditions, such as a specific amount of
-    /// generations passing.
-    pub(crate) unsafe fn auto_collect(&mut self, roots: impl Iterator<Item = RawValue>) {
+    /// generations passing, or the memory limit being exceeded. An error is returned if the limit
+    /// is still exceeded after collecting.
+    ///
+    /// In incremental mode, this performs the next step of the collection in progress instead.
+    /// Exceeding the memory limit still performs a full collection.
+    pub(crate) unsafe fn auto_collect(
+        &mut self,
+        roots: impl Iterator<Item = RawValue>,
+        library: &Library,
+    ) -> Result<(), LanguageErrorKind> {
         #[cfg(feature = "trace-gc")]
         {
             println!(
@@ -222,37 +914,222 @@ impl Memory {
                 self.auto_strategy
             );
         }
-        if self.auto_strategy.satisfied(self) {
+        if self.stress {
+            #[cfg(feature = "trace-gc")]
+            {
+                println!("gc | stress mode is enabled, collecting");
+            }
+            self.collect(roots, library);
+        } else if let Some(step_size) = self
+            .incremental_step_size()
+            .filter(|_| self.phase != Phase::Idle || self.auto_strategy.satisfied(self))
+        {
+            if self.step(roots, library, step_size) {
+                self.auto_strategy = self.auto_strategy.update(self);
+            }
+        } else if self.auto_strategy.satisfied(self) {
             #[cfg(feature = "trace-gc")]
             {
                 println!("gc | strategy satisfied, collecting");
             }
-            self.collect(roots);
+            self.collect(roots, library);
             self.auto_strategy = self.auto_strategy.update(self);
+        } else {
+            return self.enforce_limit(roots, library);
+        }
+        self.check_limit()
+    }
+
+    /// Performs a collection if the memory limit is exceeded, and returns an error if it's still
+    /// exceeded afterwards.
+    pub(crate) unsafe fn enforce_limit(
+        &mut self,
+        roots: impl Iterator<Item = RawValue>,
+        library: &Library,
+    ) -> Result<(), LanguageErrorKind> {
+        if self.limit.is_some_and(|limit| self.allocated_bytes > limit) {
+            #[cfg(feature = "trace-gc")]
+            {
+                println!("gc | memory limit exceeded, collecting");
+            }
+            self.collect(roots, library);
+        }
+        self.check_limit()
+    }
+
+    /// Returns an error if allocating the given number of bytes could never fit within the memory
+    /// limit.
+    ///
+    /// This is meant for functions that can allocate a lot of memory in one go, so that they can
+    /// fail before attempting the allocation. Memory that's already allocated is not taken into
+    /// account, because it may be garbage that the next collection frees; allocations that fit
+    /// but still exceed the limit are caught by the regular check after the function returns.
+    pub(crate) fn reserve(&self, bytes: usize) -> Result<(), LanguageErrorKind> {
+        match self.limit {
+            Some(limit) if bytes > limit => Err(LanguageErrorKind::MemoryLimitExceeded {
+                allocated: self.allocated_bytes.saturating_add(bytes),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the size of incremental steps, or `None` if a step shouldn't be taken, either
+    /// because collections are not incremental or because the memory limit calls for a full
+    /// collection.
+    fn incremental_step_size(&self) -> Option<usize> {
+        match self.mode {
+            GcMode::Incremental { step_size } if self.check_limit().is_ok() => {
+                Some(step_size.max(1))
+            }
+            _ => None,
+        }
+    }
+
+    fn check_limit(&self) -> Result<(), LanguageErrorKind> {
+        match self.limit {
+            Some(limit) if self.allocated_bytes > limit => {
+                Err(LanguageErrorKind::MemoryLimitExceeded {
+                    allocated: self.allocated_bytes,
+                    limit,
+                })
+            }
+            _ => Ok(()),
         }
     }
 
     /// Registers `mem` inside this GC.
     fn register<T>(&mut self, mem: GcRaw<T>) {
+        self.add_allocation(mem);
+        let memory = mem.erase_type();
+        // Objects allocated during an incremental collection must survive it, since the collection
+        // might never see them otherwise.
+        match self.phase {
+            Phase::Idle => (),
+            Phase::Marking => unsafe {
+                if let Some(value) = as_value(memory) {
+                    self.gray_stack.push(value);
+                } else if (memory.get_mem().vtable.type_name)() == any::type_name::<DispatchTable>()
+                {
+                    self.gray_dtables
+                        .push(mem::transmute::<GcRaw<()>, GcRaw<DispatchTable>>(memory));
+                } else {
+                    memory.mark_reachable();
+                }
+            },
+            Phase::Sweeping { .. } => unsafe { memory.mark_reachable() },
+        }
+    }
+
+    /// Adds `mem` to the list of allocations and counts its size, without regard for a collection
+    /// in progress.
+    fn add_allocation<T>(&mut self, mem: GcRaw<T>) {
         self.allocations.push(mem.erase_type());
-        self.allocated_bytes += std::mem::size_of::<T>();
+        if let Some(tracker) = &mut self.allocation_tracker {
+            unsafe { track_allocation(tracker, mem.erase_type()) }
+        }
+        #[cfg(debug_assertions)]
+        if self.leak_handler.is_some() {
+            self.allocation_backtraces
+                .insert(mem.0 as usize, Backtrace::force_capture());
+        }
+        let heap_size = unsafe { measure_heap(mem.erase_type()) };
+        let data_size = unsafe { mem.get_mem().data_size };
+        unsafe { mem.get_mem().heap_size.set(heap_size) };
+        self.allocated_bytes += data_size + heap_size;
         #[cfg(feature = "trace-gc")]
         {
             println!(
                 "gc | allocated {} bytes, now at {}",
-                std::mem::size_of::<T>(),
-                self.allocated_bytes
+                data_size, self.allocated_bytes
             );
         }
     }
 
     /// Allocates a new `GcRaw<T>` managed by this GC.
     pub fn allocate<T>(&mut self, data: T) -> GcRaw<T> {
-        let gcmem = GcMem::allocate(data, drop_finalizer::<T>);
+        let gcmem = GcMem::allocate_in(data, enabled_arena(&mut self.arena));
         self.register(gcmem);
         gcmem
     }
 
+    /// Allocates a string, or returns an existing string with the same contents if the string
+    /// should be interned.
+    pub fn allocate_string(&mut self, s: Str) -> GcRaw<Str> {
+        match &mut self.interner {
+            Some(interner) if interner.should_intern_string(&s) => unsafe {
+                if let Some(interned) = interner.find_string(&s) {
+                    return interned;
+                }
+                let memory = GcMem::allocate_in(s, enabled_arena(&mut self.arena));
+                interner.insert_string(memory);
+                self.register(memory);
+                memory
+            },
+            _ => self.allocate(s),
+        }
+    }
+
+    /// Like [`manage`][Self::manage], but returns an existing string with the same contents if
+    /// the string isn't managed yet and should be interned.
+    pub fn manage_string(&mut self, s: &Gc<Str>) -> GcRaw<Str> {
+        let is_managed = unsafe { s.mem.get_mem().managed_by_gc.get() };
+        match &mut self.interner {
+            Some(interner) if !is_managed && interner.should_intern_string(s) => unsafe {
+                if let Some(interned) = interner.find_string(s) {
+                    return interned;
+                }
+                interner.insert_string(s.mem);
+                self.manage(s)
+            },
+            _ => self.manage(s),
+        }
+    }
+
+    /// Allocates a tuple, or returns an existing tuple with the same fields if the tuple should be
+    /// interned.
+    ///
+    /// # Safety
+    /// The tuple's fields must be valid.
+    pub unsafe fn allocate_tuple(&mut self, tuple: Tuple) -> GcRaw<Box<dyn UserData>> {
+        match &mut self.interner {
+            Some(interner) if interner.should_intern_tuple(&tuple) => {
+                if let Some(interned) = interner.find_tuple(&tuple) {
+                    return interned;
+                }
+                let tuple = Box::new(tuple) as Box<dyn UserData>;
+                let memory = GcMem::allocate_in(tuple, enabled_arena(&mut self.arena));
+                interner.insert_tuple(memory);
+                self.register(memory);
+                memory
+            }
+            _ => self.allocate(Box::new(tuple)),
+        }
+    }
+
+    /// Like [`manage`][Self::manage], but returns an existing tuple with the same fields if the
+    /// tuple isn't managed yet and should be interned.
+    ///
+    /// # Safety
+    /// The value must be a tuple, and its fields must be valid.
+    pub unsafe fn manage_tuple(
+        &mut self,
+        tuple: &Gc<Box<dyn UserData>>,
+    ) -> GcRaw<Box<dyn UserData>> {
+        let is_managed = tuple.mem.get_mem().managed_by_gc.get();
+        let fields = tuple.as_any().downcast_ref::<Tuple>().unwrap();
+        match &mut self.interner {
+            Some(interner) if !is_managed && interner.should_intern_tuple(fields) => {
+                if let Some(interned) = interner.find_tuple(fields) {
+                    return interned;
+                }
+                interner.insert_tuple(tuple.mem);
+                self.manage(tuple)
+            }
+            _ => self.manage(tuple),
+        }
+    }
+
     /// If the provided `Gc<T>` isn't managed by a GC, makes it managed by this GC.
     /// Otherwise does nothing.
     pub fn manage<T>(&mut self, gc: &Gc<T>) -> GcRaw<T> {
@@ -267,6 +1144,11 @@ impl Memory {
     }
 }
 
+/// Returns the arena, if objects should be allocated in it.
+fn enabled_arena(arena: &mut Option<Arena>) -> Option<&mut Arena> {
+    arena.as_mut().filter(|arena| arena.enabled)
+}
+
 impl Default for Memory {
     fn default() -> Self {
         Self::new()
@@ -275,7 +1157,74 @@ impl Default for Memory {
 
 impl Drop for Memory {
     fn drop(&mut self) {
-        unsafe { self.collect(std::iter::empty()) }
+        self.release_all();
+        // Objects that outlive the GC may still be in the arena, in which case its memory must
+        // stay around. They're only ever freed by leaking memory.
+        if self.live_arena_objects() > 0 {
+            if let Some(arena) = &mut self.arena {
+                arena.leak();
+            }
+        }
+    }
+}
+
+impl Memory {
+    fn release_all(&mut self) {
+        // Nothing is reachable anymore, so everything can be released without marking.
+        let Some(leak_handler) = self.leak_handler.take() else {
+            for memory in mem::take(&mut self.allocations) {
+                unsafe { GcMem::release(memory, false) }
+            }
+            return;
+        };
+
+        // Objects that are still referenced when they're released may only be referenced by other
+        // objects released later, so whether they actually leaked can only be determined after
+        // everything has been released. Until then, they must not be deallocated.
+        let mut survivors = Vec::new();
+        for memory in mem::take(&mut self.allocations) {
+            unsafe {
+                let mem = memory.get_mem();
+                if mem.rc.get() > 0 {
+                    mem.managed_by_gc.set(false);
+                    mem.awaiting_leak_check.set(true);
+                    survivors.push(memory);
+                } else {
+                    GcMem::deallocate(memory, false);
+                }
+            }
+        }
+
+        let mut report = LeakReport { leaks: Vec::new() };
+        for memory in survivors {
+            unsafe {
+                let mem = memory.get_mem();
+                mem.awaiting_leak_check.set(false);
+                if mem.rc.get() == 0 {
+                    GcMem::deallocate(memory, false);
+                    continue;
+                }
+                let kind = describe(memory);
+                let leak = if let Some(leak) = report.leaks.iter_mut().find(|l| l.kind == kind) {
+                    leak
+                } else {
+                    report.leaks.push(Leak {
+                        kind,
+                        count: 0,
+                        backtraces: Vec::new(),
+                    });
+                    report.leaks.last_mut().unwrap()
+                };
+                leak.count += 1;
+                #[cfg(debug_assertions)]
+                if let Some(backtrace) = self.allocation_backtraces.remove(&(memory.0 as usize)) {
+                    leak.backtraces.push(backtrace);
+                }
+            }
+        }
+        if !report.leaks.is_empty() {
+            leak_handler(&report);
+        }
     }
 }
 
@@ -287,6 +1236,285 @@ impl fmt::Debug for Memory {
     }
 }
 
+/// Returns a human-readable name for the kind of object stored in the memory.
+///
+/// # Safety
+/// The memory must not be deallocated.
+unsafe fn describe(memory: GcRaw<()>) -> String {
+    let type_name = (memory.get_mem().vtable.type_name)();
+    if type_name == any::type_name::<Box<dyn UserData>>() {
+        let user_data: GcRaw<Box<dyn UserData>> = mem::transmute(memory);
+        user_data.get().type_name().into_owned()
+    } else if type_name == any::type_name::<Str>() {
+        "String".to_owned()
+    } else if type_name == any::type_name::<Closure>() {
+        "Function".to_owned()
+    } else if type_name == any::type_name::<Struct>() {
+        "Struct".to_owned()
+    } else if type_name == any::type_name::<Trait>() {
+        "Trait".to_owned()
+    } else if type_name == any::type_name::<DispatchTable>() {
+        "DispatchTable".to_owned()
+    } else {
+        type_name.to_owned()
+    }
+}
+
+/// Returns the value pointing to the memory, or `None` if values can't point to this type of
+/// memory.
+///
+/// # Safety
+/// The memory must not be deallocated.
+unsafe fn as_value(memory: GcRaw<()>) -> Option<RawValue> {
+    let type_name = (memory.get_mem().vtable.type_name)();
+    Some(if type_name == any::type_name::<Box<dyn UserData>>() {
+        RawValue::from(mem::transmute::<GcRaw<()>, GcRaw<Box<dyn UserData>>>(
+            memory,
+        ))
+    } else if type_name == any::type_name::<Str>() {
+        RawValue::from(mem::transmute::<GcRaw<()>, GcRaw<Str>>(memory))
+    } else if type_name == any::type_name::<Closure>() {
+        RawValue::from(mem::transmute::<GcRaw<()>, GcRaw<Closure>>(memory))
+    } else if type_name == any::type_name::<Struct>() {
+        RawValue::from(mem::transmute::<GcRaw<()>, GcRaw<Struct>>(memory))
+    } else if type_name == any::type_name::<Trait>() {
+        RawValue::from(mem::transmute::<GcRaw<()>, GcRaw<Trait>>(memory))
+    } else {
+        return None;
+    })
+}
+
+/// Returns the memory the value points to, if any.
+///
+/// # Safety
+/// The value must be valid.
+unsafe fn memory_of(value: RawValue) -> Option<GcRaw<()>> {
+    Some(match value.kind() {
+        ValueKind::Nil | ValueKind::Boolean | ValueKind::Number => return None,
+        ValueKind::String => value.get_raw_string_unchecked().erase_type(),
+        ValueKind::Function => value.get_raw_function_unchecked().erase_type(),
+        ValueKind::Struct => value.get_raw_struct_unchecked().erase_type(),
+        ValueKind::Trait => value.get_raw_trait_unchecked().erase_type(),
+        ValueKind::UserData => value.get_raw_user_data_unchecked().erase_type(),
+    })
+}
+
+/// Returns the number of bytes the object owns outside of its `GcMem`.
+///
+/// # Safety
+/// The memory must not be deallocated.
+unsafe fn measure_heap(memory: GcRaw<()>) -> usize {
+    let type_name = (memory.get_mem().vtable.type_name)();
+    if type_name == any::type_name::<Box<dyn UserData>>() {
+        let user_data: GcRaw<Box<dyn UserData>> = mem::transmute(memory);
+        user_data.get().heap_size()
+    } else if type_name == any::type_name::<Str>() {
+        let string: GcRaw<Str> = mem::transmute(memory);
+        string.get().heap_size()
+    } else {
+        0
+    }
+}
+
+/// Counts a newly managed object in the tracker.
+///
+/// # Safety
+/// The memory must not be deallocated.
+unsafe fn track_allocation(tracker: &mut AllocationTracker, memory: GcRaw<()>) {
+    let (count, bytes) = tracker.entry(describe(memory)).or_default();
+    *count += 1;
+    *bytes += memory.get_mem().data_size;
+}
+
+/// Removes an object that's about to be released from the tracker.
+///
+/// # Safety
+/// The memory must not be deallocated.
+unsafe fn untrack_allocation(tracker: &mut AllocationTracker, memory: GcRaw<()>) {
+    if let Some((count, bytes)) = tracker.get_mut(&describe(memory)) {
+        *count = count.saturating_sub(1);
+        *bytes = bytes.saturating_sub(memory.get_mem().data_size);
+    }
+}
+
+/// The objects managed by a GC at some point in time, grouped by kind.
+///
+/// Snapshots taken at different times can be compared with [`diff`][Self::diff] to find out which
+/// kinds of objects are accumulating.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllocationSnapshot {
+    /// The live objects, grouped by kind and sorted by kind name.
+    pub kinds: Vec<AllocationStats>,
+}
+
+impl AllocationSnapshot {
+    /// Returns the statistics for objects of the given kind, or `None` if there are no live
+    /// objects of that kind.
+    pub fn get(&self, kind: &str) -> Option<&AllocationStats> {
+        self.kinds.iter().find(|stats| stats.kind == kind)
+    }
+
+    /// Returns the total number of live objects.
+    pub fn count(&self) -> usize {
+        self.kinds.iter().map(|stats| stats.count).sum()
+    }
+
+    /// Returns the total number of bytes taken up by live objects.
+    pub fn bytes(&self) -> usize {
+        self.kinds.iter().map(|stats| stats.bytes).sum()
+    }
+
+    /// Returns how the live objects changed since the `earlier` snapshot. Kinds whose count and
+    /// size didn't change are omitted.
+    pub fn diff(&self, earlier: &AllocationSnapshot) -> AllocationDiff {
+        let mut changes = BTreeMap::<&str, (isize, isize)>::new();
+        for stats in &self.kinds {
+            let change = changes.entry(&stats.kind).or_default();
+            change.0 += stats.count as isize;
+            change.1 += stats.bytes as isize;
+        }
+        for stats in &earlier.kinds {
+            let change = changes.entry(&stats.kind).or_default();
+            change.0 -= stats.count as isize;
+            change.1 -= stats.bytes as isize;
+        }
+        AllocationDiff {
+            changes: changes
+                .into_iter()
+                .filter(|&(_, change)| change != (0, 0))
+                .map(|(kind, (count, bytes))| AllocationChange {
+                    kind: kind.to_owned(),
+                    count,
+                    bytes,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for AllocationSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} live object(s) taking up {} bytes:",
+            self.count(),
+            self.bytes()
+        )?;
+        for stats in &self.kinds {
+            writeln!(
+                f,
+                "  {} × {} ({} bytes)",
+                stats.count, stats.kind, stats.bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Live objects of a single kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationStats {
+    /// The kind of the objects, such as `String` or `List`. For user data, this is the name of the
+    /// type.
+    pub kind: String,
+    /// How many objects of this kind are alive.
+    pub count: usize,
+    /// How many bytes the objects take up. This only includes the objects themselves and not any
+    /// memory they own, such as the elements of a list.
+    pub bytes: usize,
+}
+
+/// The difference between two [`AllocationSnapshot`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AllocationDiff {
+    /// The kinds of objects whose count or size changed, sorted by kind name.
+    pub changes: Vec<AllocationChange>,
+}
+
+impl AllocationDiff {
+    /// Returns the change for objects of the given kind, or `None` if it didn't change.
+    pub fn get(&self, kind: &str) -> Option<&AllocationChange> {
+        self.changes.iter().find(|change| change.kind == kind)
+    }
+
+    /// Returns whether no kind of object changed between the snapshots.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for AllocationDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(
+                f,
+                "{:+} × {} ({:+} bytes)",
+                change.count, change.kind, change.bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The change in live objects of a single kind between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationChange {
+    /// The kind of the objects.
+    pub kind: String,
+    /// By how many objects the count changed.
+    pub count: isize,
+    /// By how many bytes the size changed.
+    pub bytes: isize,
+}
+
+/// A report of objects that were still referenced by [`Gc`] handles after their GC was dropped.
+#[derive(Debug)]
+pub struct LeakReport {
+    /// The leaked objects, grouped by kind.
+    pub leaks: Vec<Leak>,
+}
+
+impl LeakReport {
+    /// Returns the total number of leaked objects.
+    pub fn count(&self) -> usize {
+        self.leaks.iter().map(|leak| leak.count).sum()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} object(s) were still referenced after the engine was dropped:",
+            self.count()
+        )?;
+        for leak in &self.leaks {
+            writeln!(f, "  {} × {}", leak.count, leak.kind)?;
+            for backtrace in &leak.backtraces {
+                writeln!(f, "    allocated at:\n{backtrace}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Leaked objects of a single kind.
+#[derive(Debug)]
+pub struct Leak {
+    /// The kind of the objects, such as `String` or `List`. For user data, this is the name of the
+    /// type.
+    pub kind: String,
+    /// How many objects of this kind leaked.
+    pub count: usize,
+    /// Where the leaked objects were allocated. Backtraces are only captured in debug builds, and
+    /// only for objects allocated while a leak handler was set.
+    pub backtraces: Vec<Backtrace>,
+}
+
+/// The byte freed memory is filled with in stress mode.
+#[cfg(debug_assertions)]
+const POISON: u8 = 0xDB;
+
 /// An allocation with metadata.
 #[repr(C, align(8))]
 pub(crate) struct GcMem<T> {
@@ -295,12 +1523,26 @@ pub(crate) struct GcMem<T> {
     reachable: Cell<bool>,
     /// Whether the memory is still being managed by the garbage collector.
     managed_by_gc: Cell<bool>,
+    /// Set while the GC is being dropped, to keep the memory from being deallocated before it's
+    /// checked for leaks.
+    awaiting_leak_check: Cell<bool>,
+    /// Whether the memory belongs to an arena, in which case it's not deallocated by itself.
+    // NOTE: Flags are kept together at the start, such that the data comes right after the
+    // (8-byte aligned) header no matter the type. The type is erased in many places, so the data's
+    // offset must be the same for all `T`s.
+    in_arena: bool,
+    /// Set once memory in an arena has been finalized. The memory stays valid until the arena is
+    /// reset.
+    freed: Cell<bool>,
     /// Foreign references to this memory.
     rc: Cell<usize>,
-    /// The "finalizer", its task is to deinitialize the data stored in the `GcMem<T>`.
-    finalizer: unsafe fn(*mut u8),
+    /// Operations that depend on `T`, needed after the type has been erased.
+    vtable: &'static GcVtable,
     /// The size of the allocated data.
     data_size: usize,
+    /// The number of bytes owned by the data outside of this `GcMem<T>`, as of the last time it
+    /// was measured.
+    heap_size: Cell<usize>,
     /// The layout that was used for allocating this `GcMem<T>`; this is needed to deallocate
     /// without triggering undefined behavior.
     layout: Layout,
@@ -314,32 +1556,64 @@ impl<T> fmt::Debug for GcMem<T> {
             .field("reachable", &self.reachable)
             .field("managed_by_gc", &self.managed_by_gc)
             .field("rc", &self.rc)
-            .field("finalizer", &self.finalizer)
+            .field("type_name", &(self.vtable.type_name)())
             .finish_non_exhaustive()
     }
 }
 
+/// Type-specific operations on a `GcMem<T>`.
+struct GcVtable {
+    /// The "finalizer", its task is to deinitialize the data stored in the `GcMem<T>` it's given a
+    /// pointer to.
+    finalizer: unsafe fn(*mut u8),
+    /// Returns the name of `T`.
+    type_name: fn() -> &'static str,
+}
+
 impl<T> GcMem<T> {
+    const VTABLE: &'static GcVtable = &GcVtable {
+        finalizer: drop_finalizer::<T>,
+        type_name: any::type_name::<T>,
+    };
+
     /// Returns the allocation layout of a `GcMem<T>`.
     fn layout() -> Layout {
         Layout::new::<Self>()
     }
 
     /// Allocates a `GcMem<T>`.
-    fn allocate(data: T, finalizer: unsafe fn(*mut u8)) -> GcRaw<T> {
+    fn allocate(data: T) -> GcRaw<T> {
+        Self::allocate_in(data, None)
+    }
+
+    /// Allocates a `GcMem<T>` in the given arena, or using the global allocator if there's no
+    /// arena or the arena can't fit `T`.
+    fn allocate_in(data: T, arena: Option<&mut Arena>) -> GcRaw<T> {
         let layout = Self::layout();
+        let (allocation, in_arena) = match arena.and_then(|arena| {
+            let allocation = arena.allocate(layout)?;
+            arena.objects.push(GcRaw(allocation as *const GcMem<()>));
+            Some(allocation)
+        }) {
+            Some(allocation) => (allocation, true),
+            None => (unsafe { std::alloc::alloc(layout) }, false),
+        };
         let mem = Self {
             // NOTE: `reachable` is initially set to `false` because reachability is only determined
             // during the marking phase.
             reachable: Cell::new(false),
             managed_by_gc: Cell::new(true),
+            awaiting_leak_check: Cell::new(false),
             rc: Cell::new(0),
-            finalizer,
+            vtable: Self::VTABLE,
             data_size: std::mem::size_of::<T>(),
+            heap_size: Cell::new(0),
             layout,
+            in_arena,
+            freed: Cell::new(false),
             data,
         };
-        let allocation = unsafe { std::alloc::alloc(layout) } as *mut Self;
+        let allocation = allocation as *mut Self;
         if allocation.is_null() {
             handle_alloc_error(layout);
         }
@@ -355,28 +1629,44 @@ impl<T> GcMem<T> {
         GcRaw(allocation as *const _)
     }
 
-    /// Deallocates a `GcMem<T>`.
+    /// Deallocates a `GcMem<T>`. If `poison` is true and debug assertions are enabled, the memory
+    /// is filled with [`POISON`] before being deallocated.
     ///
     /// # Safety
     /// `mem` must be a pointer returned by [`allocate`][`Self::allocate`].
-    unsafe fn deallocate(mem: GcRaw<T>) {
+    unsafe fn deallocate(mem: GcRaw<T>, poison: bool) {
         let mem = mem.0 as *mut GcMem<T>;
         #[cfg(feature = "trace-gc")]
         {
             println!("gcmem | deallocating {:p}", mem);
         }
-        let layout;
-        {
-            let mem = &mut *mem;
-            (mem.finalizer)(&mut mem.data as *mut T as *mut u8);
-            layout = mem.layout;
+        // The memory is only accessed through `mem` here, since `T` may have been erased: a
+        // reference to a `GcMem<()>` doesn't cover the data, so pointers derived from it can't be
+        // used to drop or overwrite it.
+        ((*mem).vtable.finalizer)(mem as *mut u8);
+        let layout = (*mem).layout;
+        if (*mem).in_arena {
+            // The header has to stay intact, so that the arena can tell the memory is free.
+            #[cfg(debug_assertions)]
+            if poison {
+                let data = (mem as *mut u8).add(mem::offset_of!(GcMem<()>, data));
+                ptr::write_bytes(data, POISON, (*mem).data_size);
+            }
+            (*mem).freed.set(true);
+            return;
+        }
+        #[cfg(debug_assertions)]
+        if poison {
+            ptr::write_bytes(mem as *mut u8, POISON, layout.size());
         }
+        #[cfg(not(debug_assertions))]
+        let _ = poison;
         // Ugh, that cast from *const to *mut hurts.
         std::alloc::dealloc(mem as *mut u8, layout)
     }
 
     /// Deallocates the given memory or marks it as unmanaged if there are foreign references to it.
-    unsafe fn release(memory: GcRaw<T>) {
+    unsafe fn release(memory: GcRaw<T>, poison: bool) {
         #[cfg(feature = "trace-gc")]
         {
             println!("gcmem | releasing {:p}", memory.0);
@@ -385,18 +1675,18 @@ impl<T> GcMem<T> {
         if mem.rc.get() > 0 {
             mem.managed_by_gc.set(false);
         } else {
-            GcMem::deallocate(memory);
+            GcMem::deallocate(memory, poison);
         }
     }
 }
 
-unsafe fn drop_finalizer<T>(x: *mut u8) {
+unsafe fn drop_finalizer<T>(mem: *mut u8) {
     #[cfg(feature = "trace-gc")]
     {
         println!("drop | T: {}", std::any::type_name::<T>());
     }
-    let x = x as *mut T;
-    ptr::drop_in_place(x);
+    let mem = mem as *mut GcMem<T>;
+    ptr::drop_in_place(ptr::addr_of_mut!((*mem).data));
 }
 
 /// An unmanaged reference to GC memory.
@@ -417,6 +1707,10 @@ impl<T> GcRaw<T> {
         &mem.data
     }
 
+    #[cfg_attr(
+        any(not(target_arch = "x86_64"), feature = "forbid-unsafe"),
+        allow(dead_code)
+    )]
     pub(crate) fn from_raw(raw: *const GcMem<T>) -> Self {
         Self(raw)
     }
@@ -474,7 +1768,7 @@ pub struct Gc<T> {
 impl<T> Gc<T> {
     /// Creates a new `Gc` that is not managed by a garbage collector.
     pub fn new(data: T) -> Self {
-        let mem = GcMem::allocate(data, drop_finalizer::<T>);
+        let mem = GcMem::allocate(data);
         unsafe {
             let mem = mem.get_mem();
             mem.managed_by_gc.set(false);
@@ -531,8 +1825,8 @@ impl<T> Drop for Gc<T> {
     fn drop(&mut self) {
         let mem = unsafe { &*self.mem.0 };
         mem.rc.set(mem.rc.get() - 1);
-        if mem.rc.get() == 0 && !mem.managed_by_gc.get() {
-            unsafe { GcMem::deallocate(self.mem) }
+        if mem.rc.get() == 0 && !mem.managed_by_gc.get() && !mem.awaiting_leak_check.get() {
+            unsafe { GcMem::deallocate(self.mem, false) }
         }
     }
 }
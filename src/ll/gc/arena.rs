@@ -0,0 +1,110 @@
+//! Bump allocation of objects that are all freed at once.
+
+use std::{
+    alloc::{handle_alloc_error, Layout},
+    mem,
+};
+
+use super::GcRaw;
+
+/// The alignment of every chunk. Objects that need a bigger alignment than this are not allocated
+/// in the arena.
+const CHUNK_ALIGN: usize = 16;
+
+/// A block of memory objects are bump-allocated from.
+struct Chunk {
+    memory: *mut u8,
+    layout: Layout,
+}
+
+impl Chunk {
+    fn new(size: usize) -> Self {
+        let layout = Layout::from_size_align(size, CHUNK_ALIGN).expect("arena chunk is too big");
+        let memory = unsafe { std::alloc::alloc(layout) };
+        if memory.is_null() {
+            handle_alloc_error(layout);
+        }
+        Self { memory, layout }
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.memory, self.layout) }
+    }
+}
+
+/// An arena that allocates objects by bumping a pointer through chunks of memory. Objects are
+/// never returned to the arena one by one; instead, the whole arena is reset once none of them
+/// are alive anymore, and its chunks are reused.
+pub(super) struct Arena {
+    pub(super) chunk_size: usize,
+    /// Whether new objects should be allocated in the arena. The arena is kept around after it's
+    /// disabled, as long as some of the objects in it are alive.
+    pub(super) enabled: bool,
+    chunks: Vec<Chunk>,
+    /// The chunk that's currently being allocated from.
+    current: usize,
+    /// The offset of the first free byte in the current chunk.
+    offset: usize,
+    /// Every object allocated since the last reset, including ones that were freed since.
+    pub(super) objects: Vec<GcRaw<()>>,
+}
+
+impl Arena {
+    pub(super) fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            enabled: true,
+            chunks: Vec::new(),
+            current: 0,
+            offset: 0,
+            objects: Vec::new(),
+        }
+    }
+
+    /// Allocates memory with the given layout, or returns `None` if the layout's alignment is
+    /// too big for the arena.
+    pub(super) fn allocate(&mut self, layout: Layout) -> Option<*mut u8> {
+        if layout.align() > CHUNK_ALIGN {
+            return None;
+        }
+        while let Some(chunk) = self.chunks.get(self.current) {
+            let start = (self.offset + layout.align() - 1) & !(layout.align() - 1);
+            if start + layout.size() <= chunk.layout.size() {
+                self.offset = start + layout.size();
+                return Some(unsafe { chunk.memory.add(start) });
+            }
+            self.current += 1;
+            self.offset = 0;
+        }
+        // Objects bigger than a chunk get a chunk of their own.
+        let chunk = Chunk::new(self.chunk_size.max(layout.size()));
+        let memory = chunk.memory;
+        self.chunks.push(chunk);
+        self.current = self.chunks.len() - 1;
+        self.offset = layout.size();
+        Some(memory)
+    }
+
+    /// Makes all of the arena's memory available for allocation again.
+    ///
+    /// # Safety
+    /// None of the objects allocated in the arena may be used afterwards.
+    pub(super) unsafe fn reset(&mut self) {
+        self.current = 0;
+        self.offset = 0;
+        self.objects.clear();
+    }
+
+    /// Returns the total size of the arena's chunks.
+    pub(super) fn capacity(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.layout.size()).sum()
+    }
+
+    /// Leaks the arena's chunks, so that objects that are still alive when the arena is dropped
+    /// stay valid.
+    pub(super) fn leak(&mut self) {
+        mem::forget(mem::take(&mut self.chunks));
+    }
+}
@@ -0,0 +1,188 @@
+//! Interning of small immutable values.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use hashbrown::raw::RawTable;
+
+use super::GcRaw;
+use crate::ll::value::{RawValue, Str, Tuple, UserData, ValueKind};
+
+/// Determines which values get interned by a [`Memory`][super::Memory].
+///
+/// An interned value is allocated only once; creating an equal value reuses the existing
+/// allocation. This saves memory when lots of copies of the same strings or tuples are created,
+/// and makes comparing them for equality a pointer comparison.
+///
+/// Interned values are still garbage collected once nothing references them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interning {
+    /// Strings whose length in bytes is at most this are interned.
+    pub max_string_len: usize,
+    /// Whether tuples are interned. Only tuples that are made up of immutable values (nil,
+    /// booleans, numbers, strings, and tuples of those) are interned.
+    pub tuples: bool,
+}
+
+impl Default for Interning {
+    fn default() -> Self {
+        Self {
+            max_string_len: 64,
+            tuples: true,
+        }
+    }
+}
+
+/// The tables of interned values.
+pub(super) struct Interner {
+    pub(super) settings: Interning,
+    state: RandomState,
+    strings: RawTable<GcRaw<Str>>,
+    tuples: RawTable<GcRaw<Box<dyn UserData>>>,
+}
+
+impl Interner {
+    pub(super) fn new(settings: Interning) -> Self {
+        Self {
+            settings,
+            state: RandomState::new(),
+            strings: RawTable::new(),
+            tuples: RawTable::new(),
+        }
+    }
+
+    pub(super) fn should_intern_string(&self, s: &str) -> bool {
+        s.len() <= self.settings.max_string_len
+    }
+
+    fn hash_string(state: &RandomState, s: &str) -> u64 {
+        state.hash_one(s)
+    }
+
+    /// Returns the interned string equal to `s`, if there is one.
+    ///
+    /// # Safety
+    /// All interned strings must still be allocated.
+    pub(super) unsafe fn find_string(&self, s: &str) -> Option<GcRaw<Str>> {
+        let hash = Self::hash_string(&self.state, s);
+        self.strings
+            .get(hash, |interned| interned.get() == s)
+            .copied()
+    }
+
+    /// # Safety
+    /// All interned strings must still be allocated.
+    pub(super) unsafe fn insert_string(&mut self, s: GcRaw<Str>) {
+        let state = &self.state;
+        let hash = Self::hash_string(state, s.get());
+        self.strings
+            .insert(hash, s, |interned| Self::hash_string(state, interned.get()));
+    }
+
+    /// Returns whether the tuple should be interned.
+    ///
+    /// # Safety
+    /// The tuple's fields must be valid.
+    pub(super) unsafe fn should_intern_tuple(&self, tuple: &Tuple) -> bool {
+        self.settings.tuples && tuple.fields.iter().all(|&field| is_frozen(field))
+    }
+
+    fn hash_tuple(state: &RandomState, tuple: &dyn UserData) -> u64 {
+        let mut hasher = state.build_hasher();
+        tuple.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the interned tuple identical to `tuple`, if there is one.
+    ///
+    /// # Safety
+    /// All interned tuples must still be allocated, and the tuple's fields must be valid.
+    pub(super) unsafe fn find_tuple(&self, tuple: &Tuple) -> Option<GcRaw<Box<dyn UserData>>> {
+        let hash = Self::hash_tuple(&self.state, tuple);
+        self.tuples
+            .get(hash, |interned| {
+                let interned = interned.get().as_any().downcast_ref::<Tuple>().unwrap();
+                same_fields(interned, tuple)
+            })
+            .copied()
+    }
+
+    /// # Safety
+    /// All interned tuples must still be allocated, and `tuple` must be a tuple.
+    pub(super) unsafe fn insert_tuple(&mut self, tuple: GcRaw<Box<dyn UserData>>) {
+        let state = &self.state;
+        let hash = Self::hash_tuple(state, &**tuple.get());
+        self.tuples.insert(hash, tuple, |interned| {
+            Self::hash_tuple(state, &**interned.get())
+        });
+    }
+
+    /// Removes values that the GC is about to free from the tables.
+    ///
+    /// # Safety
+    /// Must be called after the mark phase and before the sweep phase of a collection.
+    pub(super) unsafe fn forget_unreachable(&mut self) {
+        for bucket in self.strings.iter() {
+            if !bucket.as_ref().get_mem().reachable.get() {
+                self.strings.erase(bucket);
+            }
+        }
+        for bucket in self.tuples.iter() {
+            if !bucket.as_ref().get_mem().reachable.get() {
+                self.tuples.erase(bucket);
+            }
+        }
+    }
+}
+
+/// Returns the tuple if the value is one.
+///
+/// # Safety
+/// The value must be valid.
+unsafe fn as_tuple<'a>(value: RawValue) -> Option<&'a Tuple> {
+    if value.kind() == ValueKind::UserData {
+        value
+            .get_raw_user_data_unchecked()
+            .get()
+            .as_any()
+            .downcast_ref::<Tuple>()
+    } else {
+        None
+    }
+}
+
+/// Returns whether the value can never change.
+///
+/// # Safety
+/// The value must be valid.
+unsafe fn is_frozen(value: RawValue) -> bool {
+    match value.kind() {
+        ValueKind::Nil | ValueKind::Boolean | ValueKind::Number | ValueKind::String => true,
+        _ => {
+            as_tuple(value).is_some_and(|tuple| tuple.fields.iter().all(|&field| is_frozen(field)))
+        }
+    }
+}
+
+/// Returns whether two tuples of frozen values are indistinguishable. This is stricter than `==`,
+/// which considers `0` and `-0` equal.
+///
+/// # Safety
+/// The tuples' fields must be valid.
+unsafe fn same_fields(a: &Tuple, b: &Tuple) -> bool {
+    a.fields.len() == b.fields.len()
+        && a.fields
+            .iter()
+            .zip(&b.fields)
+            .all(|(&a, &b)| match (a.kind(), b.kind()) {
+                (ValueKind::Number, ValueKind::Number) => {
+                    a.get_number_unchecked().to_bits() == b.get_number_unchecked().to_bits()
+                }
+                _ => match (as_tuple(a), as_tuple(b)) {
+                    (Some(a), Some(b)) => same_fields(a, b),
+                    _ => a == b,
+                },
+            })
+}
@@ -2,7 +2,7 @@
 
 use std::{fmt, rc::Rc};
 
-use crate::ll::error::{LanguageError, LanguageErrorKind, Location};
+use crate::ll::error::{LanguageError, LanguageErrorKind, Location, Span};
 
 /// The kind of a token.
 #[derive(Debug, Clone, PartialEq)]
@@ -13,12 +13,15 @@ pub enum TokenKind {
     LongString(Rc<str>),
 
     Identifier(Rc<str>),
+    /// A loop label, `'name`.
+    Label(Rc<str>),
 
     Nil,
     True,
     False,
 
     Let,
+    Const,
     Do,
     If,
     Elif,
@@ -29,19 +32,31 @@ pub enum TokenKind {
     Func,
     End,
     Break,
+    Continue,
     Return,
+    Try,
+    Catch,
+    Raise,
+    Defer,
 
     Struct,
+    Enum,
     Trait,
     Impl,
     As,
+    Import,
+    Implements,
     Constructor,
     Static,
+    Pub,
+    Priv,
 
-    Plus,  // +
-    Minus, // -
-    Star,  // *
-    Slash, // /
+    Plus,       // +
+    Minus,      // -
+    Star,       // *
+    Slash,      // /
+    SlashSlash, // //
+    Percent,    // %
 
     Bang,         // !
     And,          // and
@@ -53,11 +68,12 @@ pub enum TokenKind {
     LessEqual,    // <=
     GreaterEqual, // >=
 
-    Assign,     // =
-    Dot,        // .
-    Colon,      // :
-    At,         // @
-    Underscore, // _
+    Assign,      // =
+    Dot,         // .
+    QuestionDot, // ?.
+    Colon,       // :
+    At,          // @
+    Underscore,  // _
 
     LeftParen,    // (
     RightParen,   // )
@@ -67,6 +83,13 @@ pub enum TokenKind {
     RightBrace,   // }
     Comma,        // ,
     DotDot,       // ..
+    Ellipsis,     // ...
+
+    /// A comment. Comments are skipped by [`Lexer::next_token`], and only produced by [`Tokens`].
+    Comment,
+    /// Source code that could not be lexed, along with a message describing the problem. Only
+    /// produced by [`Tokens`]; [`Lexer::next_token`] returns an error instead.
+    Error(Rc<str>),
 
     Eof,
 }
@@ -76,6 +99,70 @@ pub enum TokenKind {
 pub struct Token {
     pub kind: TokenKind,
     pub location: Location,
+    /// The location right after the token's last character.
+    pub end: Location,
+}
+
+impl Token {
+    /// Returns the span of source code the token was lexed from.
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.location,
+            end: self.end,
+        }
+    }
+}
+
+/// Metadata declared in the front matter block at the start of a script.
+///
+/// The front matter is delimited by `---` lines, and may only be preceded by a shebang line.
+/// Each line within it is a `key = value` pair, a blank line, or a `#` comment. Values surrounded
+/// with double quotes have their quotes removed; no escape sequences are processed.
+///
+/// ```text
+/// #!/usr/bin/env mica
+/// ---
+/// name = "greeter"
+/// version = 1.0
+/// ---
+/// print("Hello!")
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FrontMatter {
+    entries: Vec<(Rc<str>, Rc<str>)>,
+    span: Option<Span>,
+}
+
+impl FrontMatter {
+    /// Returns the value associated with the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| &**k == key)
+            .map(|(_, value)| &**value)
+    }
+
+    /// Returns an iterator over all key-value pairs, in the order they were declared.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(key, value)| (&**key, &**value))
+    }
+
+    /// Returns the span of the front matter block, including its `---` lines and the line break
+    /// after the closing `---`. This is `None` if there's no front matter.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Returns the number of key-value pairs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether there are no key-value pairs, which is also the case when the script has no
+    /// front matter at all.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
 }
 
 /// Lexer state.
@@ -84,6 +171,7 @@ pub struct Lexer {
     input: String,
     location: Location,
     token_start: Location,
+    front_matter: FrontMatter,
 }
 
 impl Lexer {
@@ -97,9 +185,27 @@ impl Lexer {
             input,
             location: Default::default(),
             token_start: Default::default(),
+            front_matter: Default::default(),
         }
     }
 
+    /// Returns the source code being lexed.
+    pub fn source(&self) -> &str {
+        &self.input
+    }
+
+    /// Returns the front matter of the source code. This is only filled in once the lexer gets past
+    /// it.
+    pub fn front_matter(&self) -> &FrontMatter {
+        &self.front_matter
+    }
+
+    /// Returns the current location of the lexer in the input. After an error, this is where
+    /// lexing would resume.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
     /// Emits an error.
     fn error(&self, kind: LanguageErrorKind) -> LanguageError {
         self.error_at(self.location, kind)
@@ -110,7 +216,8 @@ impl Lexer {
         LanguageError::Compile {
             module_name: Rc::clone(&self.module_name),
             kind,
-            location,
+            span: Span::point(location),
+            snippet: None,
         }
     }
 
@@ -119,6 +226,7 @@ impl Lexer {
         Token {
             kind,
             location: self.token_start,
+            end: self.location,
         }
     }
 
@@ -130,10 +238,12 @@ impl Lexer {
             .unwrap_or(Self::EOF)
     }
 
-    /// Advances the current position by a character.
+    /// Advances the current position by a character. Does nothing at the end of input.
     fn advance(&mut self) {
-        self.location.byte += self.get().len_utf8();
-        self.location.column += 1;
+        if self.location.byte < self.input.len() {
+            self.location.byte += self.get().len_utf8();
+            self.location.column += 1;
+        }
     }
 
     /// Advances the source location to the next line.
@@ -142,18 +252,28 @@ impl Lexer {
         self.location.column = 1;
     }
 
-    /// Skips whitespace characters.
-    fn skip_whitespace(&mut self) {
+    /// Skips whitespace characters, including comments and front matter.
+    fn skip_whitespace(&mut self) -> Result<(), LanguageError> {
+        loop {
+            self.skip_blanks();
+            if self.is_at_front_matter() {
+                self.front_matter_block()?;
+            } else if self.get() == '#' {
+                self.skip_comment();
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips spaces, tabs, and line breaks.
+    fn skip_blanks(&mut self) {
         loop {
             match self.get() {
                 ' ' | '\t' => {
                     self.advance();
                 }
-                '#' => {
-                    while self.get() != '\n' {
-                        self.advance();
-                    }
-                }
                 '\n' => {
                     self.advance();
                     self.advance_line();
@@ -163,6 +283,92 @@ impl Lexer {
         }
     }
 
+    /// Skips a comment, up to the end of the line.
+    fn skip_comment(&mut self) {
+        while !matches!(self.get(), '\n' | Self::EOF) {
+            self.advance();
+        }
+    }
+
+    /// Skips the rest of the current line, including the line break.
+    fn skip_line(&mut self) {
+        self.skip_comment();
+        if self.get() == '\n' {
+            self.advance();
+            self.advance_line();
+        }
+    }
+
+    /// Returns the rest of the current line, without the line break.
+    fn rest_of_line(&self) -> &str {
+        let rest = &self.input[self.location.byte..];
+        &rest[..rest.find('\n').unwrap_or(rest.len())]
+    }
+
+    /// Returns whether the lexer is at the opening `---` of a front matter block. Front matter
+    /// must be at the very start of the input, or on the line right after a shebang.
+    fn is_at_front_matter(&self) -> bool {
+        let at_start = match (self.location.line, self.location.column) {
+            (1, 1) => true,
+            (2, 1) => self.input.starts_with("#!"),
+            _ => false,
+        };
+        at_start && self.rest_of_line().trim_end() == "---"
+    }
+
+    /// Parses a front matter block, starting at its opening `---`.
+    fn front_matter_block(&mut self) -> Result<(), LanguageError> {
+        let start = self.location;
+        self.front_matter = FrontMatter::default();
+        self.skip_line();
+        // Errors are reported only after the whole block is skipped, so that lexing doesn't resume
+        // in the middle of it.
+        let mut error = None;
+        loop {
+            if self.location.byte >= self.input.len() {
+                return Err(self.error_at(start, LanguageErrorKind::UnterminatedFrontMatter));
+            }
+            let line = self.rest_of_line().trim();
+            if line == "---" {
+                self.skip_line();
+                self.front_matter.span = Some(Span {
+                    start,
+                    end: self.location,
+                });
+                break;
+            }
+            if !line.is_empty() && !line.starts_with('#') {
+                let entry = match line.split_once('=') {
+                    Some((key, value)) if !key.trim().is_empty() => {
+                        let value = value.trim();
+                        let value = value
+                            .strip_prefix('"')
+                            .and_then(|value| value.strip_suffix('"'))
+                            .unwrap_or(value);
+                        Ok((Rc::from(key.trim()), Rc::from(value)))
+                    }
+                    _ => Err(LanguageErrorKind::InvalidFrontMatterEntry),
+                };
+                match entry {
+                    Ok((key, _)) if self.front_matter.get(&key).is_some() => {
+                        error.get_or_insert(
+                            self.error(LanguageErrorKind::DuplicateFrontMatterKey(key)),
+                        );
+                    }
+                    Ok(entry) => self.front_matter.entries.push(entry),
+                    Err(kind) => {
+                        error.get_or_insert(self.error(kind));
+                    }
+                }
+            }
+            self.skip_line();
+        }
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
     /// Returns whether the character is a digit that's part of a number literal.
     fn is_digit_or_underscore(c: char, radix: u32) -> bool {
         c.is_digit(radix) || c == '_'
@@ -191,13 +397,14 @@ impl Lexer {
 
         self.collect_digits(&mut number, 10)?;
         if self.get() == '.' {
-            let dot = self.location.byte;
+            let dot = self.location;
             number.push(self.get());
             self.advance();
-            if Self::is_identifier_start_char(self.get()) {
-                // Special case: backtrack to the dot if we find an identifier after the decimal
-                // point. We want to parse this as a method call.
-                self.location.byte = dot;
+            if Self::is_identifier_start_char(self.get()) || self.get() == '.' {
+                // Special case: backtrack to the dot if we find an identifier or another dot
+                // after the decimal point. We want to parse this as a method call, or a `..` in a
+                // slice or cascade.
+                self.location = dot;
             } else if Self::is_digit_or_underscore(self.get(), 10) {
                 self.collect_digits(&mut number, 10)?;
             } else {
@@ -222,6 +429,31 @@ impl Lexer {
         Ok(number)
     }
 
+    /// Parses a `0x`, `0b`, or `0o`-prefixed integer, if the `0` at the current position begins
+    /// one. Unlike backslash literals, these are not limited to 32 bits, and may be as large as any
+    /// integer a number can represent exactly.
+    fn prefixed_integer(&mut self) -> Result<Option<f64>, LanguageError> {
+        let radix = match self.input[self.location.byte + 1..].chars().next() {
+            Some('x' | 'X') => 16,
+            Some('b' | 'B') => 2,
+            Some('o') => 8,
+            _ => return Ok(None),
+        };
+        self.advance(); // Skip over the 0
+        self.advance(); // and the radix character.
+        let start_location = self.location;
+        let mut number = String::new();
+        if !Self::is_digit_or_underscore(self.get(), radix) {
+            return Err(self.error(LanguageErrorKind::MissingDigitsAfterRadixPrefix));
+        }
+        self.collect_digits(&mut number, radix)?;
+        const MAX_EXACT_INTEGER: u64 = 1 << f64::MANTISSA_DIGITS;
+        match u64::from_str_radix(&number, radix) {
+            Ok(integer) if integer <= MAX_EXACT_INTEGER => Ok(Some(integer as f64)),
+            _ => Err(self.error_at(start_location, LanguageErrorKind::IntLiteralOutOfRange)),
+        }
+    }
+
     /// Parses a 32-bit integer with the specified radix.
     fn integer(&mut self, radix: u32) -> Result<u32, LanguageError> {
         let start_location = self.location;
@@ -263,6 +495,9 @@ impl Lexer {
             '\\' => {
                 let escape = self.get();
                 let escape_char_location = self.location;
+                if escape == Self::EOF && self.location.byte >= self.input.len() {
+                    return Err(self.error(LanguageErrorKind::MissingClosingQuote));
+                }
                 self.advance();
                 match escape {
                     '\'' => '\'',
@@ -271,6 +506,25 @@ impl Lexer {
                     'n' => '\n',
                     'r' => '\r',
                     't' => '\t',
+                    '0' => '\0',
+                    'x' => {
+                        let digits_location = self.location;
+                        let mut code = 0;
+                        for _ in 0..2 {
+                            let digit = self.get().to_digit(16).ok_or_else(|| {
+                                self.error_at(digits_location, LanguageErrorKind::XEscapeInvalid)
+                            })?;
+                            code = code * 16 + digit;
+                            self.advance();
+                        }
+                        // Strings are UTF-8, so only bytes that are complete characters on their
+                        // own can be escaped this way.
+                        if code > 0x7F {
+                            return Err(self
+                                .error_at(digits_location, LanguageErrorKind::XEscapeOutOfRange));
+                        }
+                        char::from(code as u8)
+                    }
                     'u' => {
                         let left_brace_location = self.location;
                         if self.get() != '{' {
@@ -313,7 +567,7 @@ impl Lexer {
     }
 
     /// Parses a string.
-    fn string(&mut self, raw: bool) -> Result<String, LanguageError> {
+    fn string(&mut self) -> Result<String, LanguageError> {
         self.advance();
         let mut result = String::new();
         while self.get() != '"' {
@@ -323,18 +577,53 @@ impl Lexer {
             if self.get() == '\n' {
                 return Err(self.error(LanguageErrorKind::LineBreakInStringIsNotAllowed));
             }
-            result.push(if !raw {
-                self.string_char()?
-            } else {
-                let c = self.get();
-                self.advance();
-                c
-            });
+            result.push(self.string_char()?);
         }
         self.advance();
         Ok(result)
     }
 
+    /// Parses a raw string, right after its `\r` prefix.
+    ///
+    /// The opening quote can be preceded by any number of `#`, in which case the string only ends
+    /// at a quote followed by the same number of `#`. This allows quotes inside of raw strings.
+    fn raw_string(&mut self) -> Result<String, LanguageError> {
+        let mut hashes = 0;
+        while self.get() == '#' {
+            hashes += 1;
+            self.advance();
+        }
+        if self.get() != '"' {
+            return Err(self.error(LanguageErrorKind::RawStringMissingOpeningQuote));
+        }
+        self.advance();
+        let mut result = String::new();
+        loop {
+            match self.get() {
+                '"' if self.input[self.location.byte + 1..]
+                    .bytes()
+                    .take_while(|&b| b == b'#')
+                    .count()
+                    >= hashes =>
+                {
+                    break;
+                }
+                '\n' => return Err(self.error(LanguageErrorKind::LineBreakInStringIsNotAllowed)),
+                Self::EOF if self.location.byte >= self.input.len() => {
+                    return Err(self.error(LanguageErrorKind::MissingClosingQuote));
+                }
+                c => {
+                    result.push(c);
+                    self.advance();
+                }
+            }
+        }
+        for _ in 0..=hashes {
+            self.advance();
+        }
+        Ok(result)
+    }
+
     /// Parses a character literal 'a'.
     fn character(&mut self) -> Result<char, LanguageError> {
         if self.get() != '\'' {
@@ -369,7 +658,7 @@ impl Lexer {
         match self.get() {
             'r' => {
                 self.advance();
-                let content = self.string(true)?;
+                let content = self.raw_string()?;
                 Ok(self.token(TokenKind::String(Rc::from(content))))
             }
             'u' => {
@@ -449,6 +738,7 @@ impl Lexer {
             "or" => TokenKind::Or,
 
             "let" => TokenKind::Let,
+            "const" => TokenKind::Const,
             "do" => TokenKind::Do,
             "if" => TokenKind::If,
             "elif" => TokenKind::Elif,
@@ -459,14 +749,24 @@ impl Lexer {
             "func" => TokenKind::Func,
             "end" => TokenKind::End,
             "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
             "return" => TokenKind::Return,
+            "try" => TokenKind::Try,
+            "catch" => TokenKind::Catch,
+            "raise" => TokenKind::Raise,
+            "defer" => TokenKind::Defer,
 
             "struct" => TokenKind::Struct,
+            "enum" => TokenKind::Enum,
             "impl" => TokenKind::Impl,
             "trait" => TokenKind::Trait,
             "as" => TokenKind::As,
+            "import" => TokenKind::Import,
+            "implements" => TokenKind::Implements,
             "constructor" => TokenKind::Constructor,
             "static" => TokenKind::Static,
+            "pub" => TokenKind::Pub,
+            "priv" => TokenKind::Priv,
 
             "_" => TokenKind::Underscore,
 
@@ -476,19 +776,30 @@ impl Lexer {
 
     /// Parses the next token and returns it.
     pub fn next_token(&mut self) -> Result<Token, LanguageError> {
-        self.skip_whitespace();
+        self.skip_whitespace()?;
         self.token_start = self.location;
 
         match self.get() {
             '0'..='9' => {
-                let number = self.number()?;
+                let number = match self.prefixed_integer()? {
+                    Some(integer) => integer,
+                    None => self.number()?,
+                };
                 Ok(self.token(TokenKind::Number(number)))
             }
             '"' => {
-                let string = self.string(false)?;
+                let string = self.string()?;
                 Ok(self.token(TokenKind::String(Rc::from(string))))
             }
             '\\' => Ok(self.extended_literal()?),
+            '\'' => {
+                self.advance();
+                if !Self::is_identifier_start_char(self.get()) {
+                    return Err(self.error(LanguageErrorKind::LabelNameExpected));
+                }
+                let label = Rc::from(self.identifier());
+                Ok(self.token(TokenKind::Label(label)))
+            }
 
             c if Self::is_identifier_start_char(c) => {
                 let identifier = self.identifier();
@@ -503,7 +814,10 @@ impl Lexer {
             '+' => Ok(self.single_char_token(TokenKind::Plus)),
             '-' => Ok(self.single_char_token(TokenKind::Minus)),
             '*' => Ok(self.single_char_token(TokenKind::Star)),
-            '/' => Ok(self.single_char_token(TokenKind::Slash)),
+            '/' => {
+                Ok(self.single_or_double_char_token(TokenKind::Slash, '/', TokenKind::SlashSlash))
+            }
+            '%' => Ok(self.single_char_token(TokenKind::Percent)),
 
             '=' => Ok(self.single_or_double_char_token(TokenKind::Assign, '=', TokenKind::Equal)),
             '!' => Ok(self.single_or_double_char_token(TokenKind::Bang, '=', TokenKind::NotEqual)),
@@ -514,7 +828,25 @@ impl Lexer {
                 TokenKind::GreaterEqual,
             )),
 
-            '.' => Ok(self.single_or_double_char_token(TokenKind::Dot, '.', TokenKind::DotDot)),
+            '.' => {
+                let token =
+                    self.single_or_double_char_token(TokenKind::Dot, '.', TokenKind::DotDot);
+                if token.kind == TokenKind::DotDot && self.get() == '.' {
+                    self.advance();
+                    Ok(self.token(TokenKind::Ellipsis))
+                } else {
+                    Ok(token)
+                }
+            }
+            '?' => {
+                self.advance();
+                if self.get() == '.' {
+                    self.advance();
+                    Ok(self.token(TokenKind::QuestionDot))
+                } else {
+                    Err(self.error(LanguageErrorKind::InvalidCharacter('?')))
+                }
+            }
             ':' => Ok(self.single_char_token(TokenKind::Colon)),
             '@' => Ok(self.single_char_token(TokenKind::At)),
 
@@ -530,12 +862,108 @@ impl Lexer {
         }
     }
 
+    /// Skips the character at the current position. This is used by the parser to make progress
+    /// after a lexing error.
+    pub fn skip_char(&mut self) {
+        match self.get() {
+            '\n' => {
+                self.advance();
+                self.advance_line();
+            }
+            Self::EOF if self.location.byte >= self.input.len() => (),
+            _ => self.advance(),
+        }
+    }
+
     /// Peeks at what the next token's going to be without advancing the lexer's position.
     pub fn peek_token(&mut self) -> Result<Token, LanguageError> {
         let location = self.location;
-        let token = self.next_token()?;
+        let token = self.next_token();
         self.location = location;
-        Ok(token)
+        token
+    }
+}
+
+impl Lexer {
+    /// Turns the lexer into an iterator over tokens that never fails.
+    pub fn into_tokens(self) -> Tokens {
+        Tokens { lexer: self }
+    }
+}
+
+/// An iterator over the tokens in source code, meant for tools such as syntax highlighters.
+///
+/// Unlike [`Lexer::next_token`], the iterator never fails: source code that cannot be lexed is
+/// turned into [`TokenKind::Error`] tokens, and lexing resumes after them. Comments are also
+/// produced as [`TokenKind::Comment`] tokens, and so is the [front matter][FrontMatter] block. Whitespace is skipped, and the iterator ends without
+/// producing [`TokenKind::Eof`].
+///
+/// Created with [`Lexer::into_tokens`].
+#[derive(Debug)]
+pub struct Tokens {
+    lexer: Lexer,
+}
+
+impl Tokens {
+    /// Returns the source code being tokenized.
+    pub fn source(&self) -> &str {
+        self.lexer.source()
+    }
+}
+
+impl Iterator for Tokens {
+    type Item = (TokenKind, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let lexer = &mut self.lexer;
+        lexer.skip_blanks();
+        if lexer.is_at_front_matter() {
+            lexer.token_start = lexer.location;
+            return Some(match lexer.front_matter_block() {
+                Ok(()) => (TokenKind::Comment, lexer.token(TokenKind::Comment).span()),
+                Err(error) => lexer.error_token(error),
+            });
+        }
+        if lexer.get() == '#' {
+            lexer.token_start = lexer.location;
+            lexer.skip_comment();
+            return Some((TokenKind::Comment, lexer.token(TokenKind::Comment).span()));
+        }
+        match lexer.next_token() {
+            Ok(Token {
+                kind: TokenKind::Eof,
+                ..
+            }) => None,
+            Ok(token) => {
+                let span = token.span();
+                Some((token.kind, span))
+            }
+            Err(error) => {
+                // The lexer stops at the point where the error occurred, which is usually in the
+                // middle of the token. If it made no progress though, the offending character is
+                // skipped so that lexing can continue.
+                if lexer.location.byte == lexer.token_start.byte {
+                    lexer.skip_char();
+                }
+                Some(lexer.error_token(error))
+            }
+        }
+    }
+}
+
+impl Lexer {
+    /// Turns an error into an error token spanning from the start of the current token to the
+    /// lexer's location.
+    fn error_token(&self, error: LanguageError) -> (TokenKind, Span) {
+        let message = match error {
+            LanguageError::Compile { kind, .. } => Rc::from(kind.to_string()),
+            LanguageError::Runtime { .. } => unreachable!("the lexer only emits compile errors"),
+        };
+        let span = Span {
+            start: self.token_start,
+            end: self.location,
+        };
+        (TokenKind::Error(message), span)
     }
 }
 
@@ -0,0 +1,273 @@
+//! Lint passes, which check syntax trees for suspicious code before it's compiled.
+
+use std::{collections::HashSet, fmt::Debug, rc::Rc};
+
+use crate::ll::{
+    ast::{Ast, NodeId, NodeKind},
+    bytecode::Environment,
+    error::{LanguageWarning, LanguageWarningKind},
+};
+
+/// A check run over the syntax tree of every script before it's compiled.
+///
+/// Apart from the lints built into the language, embedders can implement their own passes to
+/// enforce rules specific to their scripting API, reporting [custom
+/// warnings][LanguageWarningKind::Custom].
+pub trait LintPass: Debug {
+    /// Checks a single node. This is called for every node in the syntax tree, parents before
+    /// their children.
+    fn check_node(&mut self, cx: &mut LintContext<'_>, node: NodeId);
+}
+
+/// The state lint passes have access to while checking a syntax tree.
+#[derive(Debug)]
+pub struct LintContext<'a> {
+    ast: &'a Ast,
+    env: &'a Environment,
+    module_name: Rc<str>,
+    parent: Option<NodeId>,
+    warnings: Vec<LanguageWarning>,
+}
+
+impl<'a> LintContext<'a> {
+    /// Returns the syntax tree being checked.
+    pub fn ast(&self) -> &'a Ast {
+        self.ast
+    }
+
+    /// Returns the parent of the node being checked, or `None` if the node is the root of the
+    /// syntax tree.
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// Returns whether a global with the given name was set by the embedder, as opposed to being
+    /// declared by a script.
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.env
+            .get_global(name)
+            .is_some_and(|slot| self.env.is_global_builtin(slot))
+    }
+
+    /// Emits a warning at the given node.
+    pub fn warn(&mut self, node: NodeId, kind: LanguageWarningKind) {
+        self.warnings.push(LanguageWarning {
+            kind,
+            module_name: Rc::clone(&self.module_name),
+            location: self.ast.location(node),
+        });
+    }
+}
+
+/// Runs the lint passes over a syntax tree and returns the warnings they emitted.
+pub fn run_lint_passes(
+    passes: &mut [Box<dyn LintPass>],
+    env: &Environment,
+    ast: &Ast,
+    root_node: NodeId,
+) -> Vec<LanguageWarning> {
+    let mut cx = LintContext {
+        ast,
+        env,
+        module_name: Rc::clone(ast.module_name()),
+        parent: None,
+        warnings: Vec::new(),
+    };
+    ast.walk(root_node, |node, parent| {
+        cx.parent = parent;
+        for pass in passes.iter_mut() {
+            pass.check_node(&mut cx, node);
+        }
+    });
+    cx.warnings
+}
+
+/// Returns the lint passes built into the language.
+pub fn builtin_lint_passes() -> Vec<Box<dyn LintPass>> {
+    vec![
+        Box::new(ConstantCondition),
+        Box::new(ShadowedBuiltin),
+        Box::new(FunctionComparison::default()),
+    ]
+}
+
+/// Calls `f` with the identifiers of the variables declared by the node, along with whether the
+/// variable is a function item.
+fn declared_variables(
+    ast: &Ast,
+    node: NodeId,
+    parent: Option<NodeId>,
+    f: &mut impl FnMut(NodeId, bool),
+) {
+    match ast.kind(node) {
+        NodeKind::Let | NodeKind::Const => {
+            let (assignment, _) = ast.node_pair(node);
+            if ast.kind(assignment) == NodeKind::Assign {
+                let (pattern, _) = ast.node_pair(assignment);
+                pattern_variables(ast, pattern, f);
+            }
+        }
+        NodeKind::For | NodeKind::ComprehensionFor => {
+            let (binding, _) = ast.node_pair(node);
+            pattern_variables(ast, binding, f);
+        }
+        NodeKind::Func => {
+            let (head, _) = ast.node_pair(node);
+            let (name, parameters) = ast.node_pair(head);
+            // Functions inside of `impl` blocks and traits are methods rather than variables.
+            let is_method = parent.is_some_and(|parent| {
+                matches!(
+                    ast.kind(parent),
+                    NodeKind::Impl | NodeKind::ImplAs | NodeKind::Enum | NodeKind::Trait
+                )
+            });
+            if name != NodeId::EMPTY && !is_method {
+                f(name, true);
+            }
+            for &parameter in ast.children(parameters).unwrap_or(&[]) {
+                pattern_variables(ast, parameter, f);
+            }
+        }
+        NodeKind::Struct | NodeKind::Enum | NodeKind::Trait | NodeKind::Import => {
+            let (name, _) = ast.node_pair(node);
+            f(name, false);
+        }
+        _ => (),
+    }
+}
+
+/// Calls `f` with the identifiers of the variables declared by a destructuring pattern.
+fn pattern_variables(ast: &Ast, pattern: NodeId, f: &mut impl FnMut(NodeId, bool)) {
+    match ast.kind(pattern) {
+        NodeKind::Identifier => f(pattern, false),
+        // Calls are variant patterns, like `Shape.Circle(r)`.
+        NodeKind::Tuple | NodeKind::Record | NodeKind::Call => {
+            for &element in ast.children(pattern).unwrap_or(&[]) {
+                pattern_variables(ast, element, f);
+            }
+        }
+        NodeKind::Pair => {
+            let (_, value) = ast.node_pair(pattern);
+            pattern_variables(ast, value, f);
+        }
+        NodeKind::StructPattern => {
+            let (_, fields) = ast.node_pair(pattern);
+            pattern_variables(ast, fields, f);
+        }
+        NodeKind::Implements | NodeKind::RestParameter => {
+            let (inner, _) = ast.node_pair(pattern);
+            pattern_variables(ast, inner, f);
+        }
+        _ => (),
+    }
+}
+
+/// Returns whether the expression is always truthy or always falsy, or `None` if that can't be
+/// known without running the code.
+fn constant_truthiness(ast: &Ast, node: NodeId) -> Option<bool> {
+    match ast.kind(node) {
+        NodeKind::Nil | NodeKind::False => Some(false),
+        NodeKind::True | NodeKind::Number | NodeKind::String => Some(true),
+        NodeKind::Paren => constant_truthiness(ast, ast.node_pair(node).0),
+        NodeKind::Not => constant_truthiness(ast, ast.node_pair(node).0).map(|truthy| !truthy),
+        _ => None,
+    }
+}
+
+/// Implements [`Lint::ConstantCondition`][crate::ll::error::Lint::ConstantCondition].
+#[derive(Debug)]
+struct ConstantCondition;
+
+impl LintPass for ConstantCondition {
+    fn check_node(&mut self, cx: &mut LintContext<'_>, node: NodeId) {
+        let ast = cx.ast();
+        let condition = match ast.kind(node) {
+            NodeKind::IfBranch => ast.node_pair(node).0,
+            NodeKind::While => {
+                let (condition, _) = ast.node_pair(node);
+                if ast.kind(condition) == NodeKind::True {
+                    return;
+                }
+                condition
+            }
+            _ => return,
+        };
+        if let Some(truthy) = constant_truthiness(ast, condition) {
+            cx.warn(condition, LanguageWarningKind::ConstantCondition(truthy));
+        }
+    }
+}
+
+/// Implements [`Lint::ShadowedBuiltin`][crate::ll::error::Lint::ShadowedBuiltin].
+#[derive(Debug)]
+struct ShadowedBuiltin;
+
+impl LintPass for ShadowedBuiltin {
+    fn check_node(&mut self, cx: &mut LintContext<'_>, node: NodeId) {
+        let mut shadowing = Vec::new();
+        declared_variables(cx.ast(), node, cx.parent(), &mut |identifier, _| {
+            let name = cx.ast().string(identifier).unwrap();
+            if cx.is_builtin(name) {
+                shadowing.push((identifier, Rc::clone(name)));
+            }
+        });
+        for (identifier, name) in shadowing {
+            cx.warn(identifier, LanguageWarningKind::ShadowedBuiltin(name));
+        }
+    }
+}
+
+/// Implements [`Lint::FunctionComparison`][crate::ll::error::Lint::FunctionComparison].
+#[derive(Debug, Default)]
+struct FunctionComparison {
+    /// Names of variables that are only ever declared as function items in the current module.
+    function_names: HashSet<Rc<str>>,
+}
+
+impl FunctionComparison {
+    fn is_function(&self, ast: &Ast, node: NodeId) -> bool {
+        match ast.kind(node) {
+            NodeKind::Func => true,
+            NodeKind::Identifier => self.function_names.contains(ast.string(node).unwrap()),
+            NodeKind::Paren => self.is_function(ast, ast.node_pair(node).0),
+            _ => false,
+        }
+    }
+}
+
+impl LintPass for FunctionComparison {
+    fn check_node(&mut self, cx: &mut LintContext<'_>, node: NodeId) {
+        let ast = cx.ast();
+        if cx.parent().is_none() {
+            // Before checking the module, find out which names refer to functions. Names that
+            // are also used by other variables are skipped, since we can't tell which variable
+            // a comparison refers to without resolving scopes.
+            let mut functions = HashSet::new();
+            let mut other_variables = HashSet::new();
+            ast.walk(node, |node, parent| {
+                declared_variables(ast, node, parent, &mut |identifier, is_function| {
+                    let name = Rc::clone(ast.string(identifier).unwrap());
+                    if is_function {
+                        functions.insert(name);
+                    } else {
+                        other_variables.insert(name);
+                    }
+                });
+                if ast.kind(node) == NodeKind::Assign {
+                    let (target, _) = ast.node_pair(node);
+                    if let Some(name) = ast.string(target) {
+                        other_variables.insert(Rc::clone(name));
+                    }
+                }
+            });
+            self.function_names = functions.difference(&other_variables).cloned().collect();
+        }
+
+        if matches!(ast.kind(node), NodeKind::Equal | NodeKind::NotEqual) {
+            let (left, right) = ast.node_pair(node);
+            if self.is_function(ast, left) || self.is_function(ast, right) {
+                cx.warn(node, LanguageWarningKind::FunctionComparison);
+            }
+        }
+    }
+}
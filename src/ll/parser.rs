@@ -1,6 +1,6 @@
 //! The parser.
 
-use std::{fmt, rc::Rc};
+use std::{fmt, mem, rc::Rc};
 
 use crate::ll::{
     ast::{Ast, NodeId, NodeKind},
@@ -12,14 +12,28 @@ use crate::ll::{
 pub struct Parser {
     lexer: Lexer,
     ast: Ast,
+    /// How many expressions are currently being parsed recursively.
+    depth: usize,
+    /// Whether the bounds of a slice `a[start..end]` are being parsed, in which case `..` ends the
+    /// expression instead of starting a cascade. Parentheses and blocks nested inside the bounds
+    /// reset this.
+    in_slice_bounds: bool,
 }
 
 impl Parser {
+    /// The maximum number of expressions that can be nested inside each other. Exceeding this
+    /// would risk overflowing the native stack.
+    const MAX_DEPTH: usize = 128;
+
     /// Constructs a new parser from a lexer.
     pub fn new(lexer: Lexer) -> Self {
+        let mut ast = Ast::new(Rc::clone(&lexer.module_name));
+        ast.set_source(Rc::from(lexer.source()));
         Self {
-            ast: Ast::new(Rc::clone(&lexer.module_name)),
+            ast,
             lexer,
+            depth: 0,
+            in_slice_bounds: false,
         }
     }
 
@@ -28,7 +42,8 @@ impl Parser {
         LanguageError::Compile {
             module_name: Rc::clone(&self.lexer.module_name),
             kind,
-            location: token.location,
+            span: token.span(),
+            snippet: None,
         }
     }
 
@@ -68,10 +83,17 @@ impl Parser {
             | TokenKind::Less
             | TokenKind::Greater
             | TokenKind::LessEqual
-            | TokenKind::GreaterEqual => 4,
+            | TokenKind::GreaterEqual
+            | TokenKind::Implements => 4,
             TokenKind::Plus | TokenKind::Minus => 5,
-            TokenKind::Star | TokenKind::Slash => 6,
-            TokenKind::LeftParen | TokenKind::Dot | TokenKind::Impl => 7,
+            TokenKind::Star | TokenKind::Slash | TokenKind::SlashSlash | TokenKind::Percent => 6,
+            TokenKind::LeftParen
+            | TokenKind::LeftBracket
+            | TokenKind::LeftBrace
+            | TokenKind::Dot
+            | TokenKind::QuestionDot
+            | TokenKind::DotDot
+            | TokenKind::Impl => 7,
             _ => 0,
         }
     }
@@ -87,10 +109,7 @@ impl Parser {
     /// Parses a "unit literal". This is used for all literals that are uniquely identified by a
     /// single token's kind (such as `nil`.)
     fn parse_unit(&mut self, token: Token, kind: NodeKind) -> NodeId {
-        self.ast
-            .build_node(kind, ())
-            .with_location(token.location)
-            .done()
+        self.ast.build_node(kind, ()).with_span(token.span()).done()
     }
 
     /// Parses a number literal.
@@ -98,7 +117,7 @@ impl Parser {
         if let &TokenKind::Number(x) = &token.kind {
             self.ast
                 .build_node(NodeKind::Number, ())
-                .with_location(token.location)
+                .with_span(token.span())
                 .with_number(x)
                 .done()
         } else {
@@ -108,10 +127,11 @@ impl Parser {
 
     /// Parses a string literal.
     fn parse_string(&mut self, token: Token) -> NodeId {
+        let span = token.span();
         if let TokenKind::String(s) = token.kind {
             self.ast
                 .build_node(NodeKind::String, ())
-                .with_location(token.location)
+                .with_span(span)
                 .with_string(s)
                 .done()
         } else {
@@ -122,13 +142,16 @@ impl Parser {
     /// Parses a sequence of long string literals.
     fn parse_long_string(&mut self, first: Token) -> Result<NodeId, LanguageError> {
         let mut content = String::new();
+        let mut span = first.span();
         if let TokenKind::LongString(s) = first.kind {
             content.push_str(&s);
         } else {
             panic!("first token must be a long string")
         }
         while let TokenKind::LongString(_) = self.lexer.peek_token()?.kind {
-            let s = match self.lexer.next_token()?.kind {
+            let token = self.lexer.next_token()?;
+            span = span.union(token.span());
+            let s = match token.kind {
                 TokenKind::LongString(s) => s,
                 _ => unreachable!(),
             };
@@ -138,18 +161,19 @@ impl Parser {
         Ok(self
             .ast
             .build_node(NodeKind::String, ())
-            .with_location(first.location)
+            .with_span(span)
             .with_string(Rc::from(content))
             .done())
     }
 
     /// Parses an identifier.
     fn parse_identifier(&mut self, token: Token) -> Result<NodeId, LanguageError> {
-        if let TokenKind::Identifier(i) = token.kind {
+        if let TokenKind::Identifier(i) = &token.kind {
+            let i = Rc::clone(i);
             Ok(self
                 .ast
                 .build_node(NodeKind::Identifier, ())
-                .with_location(token.location)
+                .with_span(token.span())
                 .with_string(i)
                 .done())
         } else {
@@ -163,7 +187,7 @@ impl Parser {
         Ok(self
             .ast
             .build_node(kind, right)
-            .with_location(token.location)
+            .with_span(token.span())
             .done())
     }
 
@@ -174,12 +198,14 @@ impl Parser {
         children: &mut Vec<NodeId>,
         is_terminator: impl Fn(&TokenKind) -> bool,
     ) -> Result<(), LanguageError> {
+        let in_slice_bounds = mem::replace(&mut self.in_slice_bounds, false);
         while !is_terminator(&self.lexer.peek_token()?.kind) {
             if self.lexer.peek_token()?.kind == TokenKind::Eof {
                 return Err(self.error(token, LanguageErrorKind::MissingEnd));
             }
             children.push(self.parse_item()?);
         }
+        self.in_slice_bounds = in_slice_bounds;
         Ok(())
     }
 
@@ -224,41 +250,43 @@ impl Parser {
     /// Parses a parenthesized expression `(x)` or a tuple - `()`, `(x,)`, or `(x, y)`.
     fn parse_paren_or_tuple(&mut self, token: Token) -> Result<NodeId, LanguageError> {
         if self.lexer.peek_token()?.kind == TokenKind::RightParen {
-            let _right_paren = self.lexer.next_token();
+            let right_paren = self.lexer.next_token()?;
             return Ok(self
                 .ast
                 .build_node(NodeKind::Tuple, ())
                 .with_children(vec![])
-                .with_location(token.location)
+                .with_span(token.span().union(right_paren.span()))
                 .done());
         }
+        let in_slice_bounds = mem::replace(&mut self.in_slice_bounds, false);
         let inner = self.parse_expression(0)?;
-        match self.lexer.next_token()?.kind {
-            TokenKind::RightParen => {
-                let location = self.ast.location(inner);
-                Ok(self
-                    .ast
-                    .build_node(NodeKind::Paren, inner)
-                    .with_location(location)
-                    .done())
-            }
+        let right_paren = self.lexer.next_token()?;
+        let result = match right_paren.kind {
+            TokenKind::RightParen => Ok(self
+                .ast
+                .build_node(NodeKind::Paren, inner)
+                .with_span(token.span().union(right_paren.span()))
+                .done()),
             TokenKind::Comma => {
                 let mut elements = vec![inner];
-                self.parse_comma_separated(&mut elements, TokenKind::RightParen, |p| {
-                    p.parse_expression(0)
-                })?;
+                let right_paren =
+                    self.parse_comma_separated(&mut elements, TokenKind::RightParen, |p| {
+                        p.parse_expression(0)
+                    })?;
                 Ok(self
                     .ast
                     .build_node(NodeKind::Tuple, ())
                     .with_children(elements)
-                    .with_location(token.location)
+                    .with_span(token.span().union(right_paren.span()))
                     .done())
             }
             _ => Err(self.error(&token, LanguageErrorKind::MissingRightParen)),
-        }
+        };
+        self.in_slice_bounds = in_slice_bounds;
+        result
     }
 
-    /// Parses a list or dict literal.
+    /// Parses a list or dict literal, or a comprehension.
     fn parse_list_or_dict(&mut self, token: Token) -> Result<NodeId, LanguageError> {
         #[derive(Clone, Copy, PartialEq, Eq)]
         enum Mode {
@@ -269,42 +297,54 @@ impl Parser {
 
         let mut elements = Vec::new();
         let mut mode = Mode::Unknown;
-        if self.lexer.peek_token()?.kind == TokenKind::Colon {
+        let right_bracket = if self.lexer.peek_token()?.kind == TokenKind::Colon {
             self.lexer.next_token()?;
+            mode = Mode::Dict;
             self.expect(TokenKind::RightBracket, |_| {
                 LanguageErrorKind::RightBracketExpectedToCloseEmptyDict
-            })?;
-            mode = Mode::Dict;
+            })?
         } else {
-            self.parse_comma_separated(&mut elements, TokenKind::RightBracket, |p| match mode {
-                Mode::Unknown => {
-                    let key = p.parse_expression(0)?;
-                    if p.lexer.peek_token()?.kind == TokenKind::Colon {
-                        mode = Mode::Dict;
-                        let colon = p.lexer.next_token()?;
+            self.parse_comma_separated_ending_with(
+                &mut elements,
+                |k| matches!(k, TokenKind::RightBracket | TokenKind::For),
+                |p| match mode {
+                    Mode::Unknown => {
+                        let key = p.parse_expression(0)?;
+                        if p.lexer.peek_token()?.kind == TokenKind::Colon {
+                            mode = Mode::Dict;
+                            let colon = p.lexer.next_token()?;
+                            let value = p.parse_expression(0)?;
+                            Ok(p.ast
+                                .build_node(NodeKind::Pair, (key, value))
+                                .with_span(colon.span())
+                                .done())
+                        } else {
+                            mode = Mode::List;
+                            Ok(key)
+                        }
+                    }
+                    Mode::Dict => {
+                        let key = p.parse_expression(0)?;
+                        let colon = p.expect(TokenKind::Colon, |_| {
+                            LanguageErrorKind::ColonExpectedAfterDictKey
+                        })?;
                         let value = p.parse_expression(0)?;
                         Ok(p.ast
                             .build_node(NodeKind::Pair, (key, value))
-                            .with_location(colon.location)
+                            .with_span(colon.span())
                             .done())
-                    } else {
-                        mode = Mode::List;
-                        Ok(key)
                     }
-                }
-                Mode::Dict => {
-                    let key = p.parse_expression(0)?;
-                    let colon = p.expect(TokenKind::Colon, |_| {
-                        LanguageErrorKind::ColonExpectedAfterDictKey
-                    })?;
-                    let value = p.parse_expression(0)?;
-                    Ok(p.ast
-                        .build_node(NodeKind::Pair, (key, value))
-                        .with_location(colon.location)
-                        .done())
-                }
-                Mode::List => p.parse_expression(0),
-            })?;
+                    Mode::List => p.parse_expression(0),
+                },
+            )?
+        };
+
+        if right_bracket.kind == TokenKind::For {
+            let kind = match mode {
+                Mode::Dict => NodeKind::DictComprehension,
+                _ => NodeKind::ListComprehension,
+            };
+            return self.parse_comprehension(token, kind, &elements, right_bracket);
         }
 
         Ok(self
@@ -316,11 +356,74 @@ impl Parser {
                 },
                 (),
             )
-            .with_location(token.location)
+            .with_span(token.span().union(right_bracket.span()))
             .with_children(elements)
             .done())
     }
 
+    /// Parses the `for` and `if` clauses of a comprehension, starting with the first `for`
+    /// token.
+    fn parse_comprehension(
+        &mut self,
+        left_bracket: Token,
+        kind: NodeKind,
+        elements: &[NodeId],
+        for_token: Token,
+    ) -> Result<NodeId, LanguageError> {
+        let element = match elements {
+            [] => return Err(self.error(&for_token, LanguageErrorKind::InvalidPrefixToken)),
+            &[element] => element,
+            _ => {
+                return Err(self.error(
+                    &for_token,
+                    LanguageErrorKind::ComprehensionHasMultipleElements,
+                ))
+            }
+        };
+
+        let mut clauses = Vec::new();
+        let mut clause_token = Some(for_token);
+        while let Some(token) = clause_token {
+            let clause = if token.kind == TokenKind::For {
+                let binding = self.parse_expression(0)?;
+                let _in_token = self.expect(TokenKind::In, |_| {
+                    LanguageErrorKind::InExpectedAfterForBinding
+                })?;
+                let iterable = self.parse_expression(0)?;
+                self.ast
+                    .build_node(NodeKind::ComprehensionFor, (binding, iterable))
+                    .with_span(token.span())
+                    .done()
+            } else {
+                let condition = self.parse_expression(0)?;
+                self.ast
+                    .build_node(NodeKind::ComprehensionIf, condition)
+                    .with_span(token.span())
+                    .done()
+            };
+            clauses.push(clause);
+            clause_token = match self.lexer.peek_token()?.kind {
+                TokenKind::For | TokenKind::If => Some(self.lexer.next_token()?),
+                _ => None,
+            };
+        }
+
+        let right_bracket = self.lexer.next_token()?;
+        match right_bracket.kind {
+            TokenKind::RightBracket => Ok(self
+                .ast
+                .build_node(kind, element)
+                .with_span(left_bracket.span().union(right_bracket.span()))
+                .with_children(clauses)
+                .done()),
+            TokenKind::Comma => Err(self.error(
+                &right_bracket,
+                LanguageErrorKind::ComprehensionHasMultipleElements,
+            )),
+            _ => Err(self.error(&right_bracket, LanguageErrorKind::CommaExpected)),
+        }
+    }
+
     fn parse_record(&mut self, token: Token) -> Result<NodeId, LanguageError> {
         let mut fields = vec![];
         let end_token = self.parse_comma_separated_ending_with(
@@ -342,31 +445,38 @@ impl Parser {
                     .done())
             },
         )?;
+        let mut span = token.span().union(end_token.span());
         if let TokenKind::DotDot = &end_token.kind {
             fields.push(
                 self.ast
                     .build_node(NodeKind::Rest, ())
-                    .with_location(end_token.location)
+                    .with_span(end_token.span())
                     .done(),
             );
-            let _right_brace = self.expect(TokenKind::RightBrace, |_| {
+            let right_brace = self.expect(TokenKind::RightBrace, |_| {
                 LanguageErrorKind::RestMustBeFollowedByRightBrace
             })?;
+            span = span.union(right_brace.span());
         }
         Ok(self
             .ast
             .build_node(NodeKind::Record, ())
             .with_children(fields)
-            .with_location(token.location)
+            .with_span(span)
             .done())
     }
 
-    fn parse_let_expression(&mut self, token: Token) -> Result<NodeId, LanguageError> {
+    /// Parses a `let` or `const` expression, producing a node of the given kind.
+    fn parse_let_expression(
+        &mut self,
+        token: Token,
+        kind: NodeKind,
+    ) -> Result<NodeId, LanguageError> {
         let right = self.parse_expression(0)?;
         Ok(self
             .ast
-            .build_node(NodeKind::Let, right)
-            .with_location(token.location)
+            .build_node(kind, right)
+            .with_span(token.span())
             .done())
     }
 
@@ -374,11 +484,11 @@ impl Parser {
     fn parse_do_block(&mut self, token: Token) -> Result<NodeId, LanguageError> {
         let mut children = Vec::new();
         self.parse_terminated_block(&token, &mut children, |k| *k == TokenKind::End)?;
-        let _end = self.lexer.next_token();
+        let end = self.lexer.next_token()?;
         Ok(self
             .ast
             .build_node(NodeKind::Do, ())
-            .with_location(token.location)
+            .with_span(token.span().union(end.span()))
             .with_children(children)
             .done())
     }
@@ -410,7 +520,7 @@ impl Parser {
                         .build_node(NodeKind::ElseBranch, ())
                         .with_children(branch)
                 }
-                .with_location(do_token.location)
+                .with_span(do_token.span())
                 .done(),
             );
 
@@ -433,7 +543,7 @@ impl Parser {
         Ok(self
             .ast
             .build_node(NodeKind::If, ())
-            .with_location(if_token.location)
+            .with_span(if_token.span())
             .with_children(branches)
             .done())
     }
@@ -448,7 +558,7 @@ impl Parser {
         Ok(self
             .ast
             .build_node(NodeKind::While, condition)
-            .with_location(token.location)
+            .with_span(token.span())
             .with_children(body)
             .done())
     }
@@ -467,11 +577,74 @@ impl Parser {
         Ok(self
             .ast
             .build_node(NodeKind::For, (binding, iterator))
-            .with_location(token.location)
+            .with_span(token.span())
             .with_children(body)
             .done())
     }
 
+    /// Parses a `try..catch..end` expression.
+    fn parse_try_expression(&mut self, try_token: Token) -> Result<NodeId, LanguageError> {
+        let mut body = Vec::new();
+        self.parse_terminated_block(&try_token, &mut body, |k| {
+            matches!(k, TokenKind::Catch | TokenKind::End)
+        })?;
+        let catch_token = self.lexer.next_token()?;
+        if catch_token.kind != TokenKind::Catch {
+            return Err(self.error(&catch_token, LanguageErrorKind::MissingCatch));
+        }
+        let body = self
+            .ast
+            .build_node(NodeKind::Do, ())
+            .with_span(try_token.span())
+            .with_children(body)
+            .done();
+
+        // The patterns the error is bound to must be on the same line as `catch`, as the
+        // handler's body begins on the next line.
+        let (mut error, mut trace) = (NodeId::EMPTY, NodeId::EMPTY);
+        if self.lexer.peek_token()?.location.line == catch_token.location.line {
+            error = self.parse_expression(0)?;
+            if self.try_next(TokenKind::Comma)?.is_some() {
+                trace = self.parse_expression(0)?;
+            }
+        }
+        let mut handler = Vec::new();
+        self.parse_terminated_block(&catch_token, &mut handler, |k| *k == TokenKind::End)?;
+        let _end = self.lexer.next_token();
+        let catch = self
+            .ast
+            .build_node(NodeKind::Catch, (error, trace))
+            .with_span(catch_token.span())
+            .with_children(handler)
+            .done();
+
+        Ok(self
+            .ast
+            .build_node(NodeKind::Try, (body, catch))
+            .with_span(try_token.span())
+            .done())
+    }
+
+    /// Parses a `raise` expression.
+    fn parse_raise(&mut self, token: Token) -> Result<NodeId, LanguageError> {
+        let value = self.parse_expression(0)?;
+        Ok(self
+            .ast
+            .build_node(NodeKind::Raise, value)
+            .with_span(token.span())
+            .done())
+    }
+
+    /// Parses a `defer` expression.
+    fn parse_defer(&mut self, token: Token) -> Result<NodeId, LanguageError> {
+        let deferred = self.parse_expression(0)?;
+        Ok(self
+            .ast
+            .build_node(NodeKind::Defer, deferred)
+            .with_span(token.span())
+            .done())
+    }
+
     /// Parses a function. `anonymous` decides if the function has a name or not.
     fn parse_function(
         &mut self,
@@ -490,20 +663,53 @@ impl Parser {
         })?;
         let mut parameters = Vec::new();
         self.parse_comma_separated(&mut parameters, TokenKind::RightParen, |p| {
-            let name = p.lexer.next_token()?;
-            p.parse_identifier(name)
+            let token = p.lexer.next_token()?;
+            match token.kind {
+                // Parameters can also be destructuring patterns.
+                TokenKind::LeftParen | TokenKind::LeftBrace | TokenKind::Underscore => {
+                    p.parse_prefix(token)
+                }
+                TokenKind::Ellipsis => {
+                    let name = p.lexer.next_token()?;
+                    let name = p.parse_identifier(name)?;
+                    Ok(p.ast
+                        .build_node(NodeKind::RestParameter, name)
+                        .with_span(token.span())
+                        .done())
+                }
+                _ => {
+                    let name = p.parse_identifier(token)?;
+                    match p.try_next(TokenKind::LeftBrace)? {
+                        Some(left_brace) => p.parse_struct_pattern(name, left_brace),
+                        None => Ok(name),
+                    }
+                }
+            }
         })?;
 
         // We allow either `constructor` or `static`, but not both.
         let kind = if let Some(token) = self.try_next(TokenKind::Constructor)? {
             self.ast
                 .build_node(NodeKind::Constructor, ())
-                .with_location(token.location)
+                .with_span(token.span())
                 .done()
         } else if let Some(token) = self.try_next(TokenKind::Static)? {
             self.ast
                 .build_node(NodeKind::Static, ())
-                .with_location(token.location)
+                .with_span(token.span())
+                .done()
+        } else {
+            NodeId::EMPTY
+        };
+        let visibility = if let Some(token) = self.try_next(TokenKind::Pub)? {
+            self.ast
+                .build_node(NodeKind::Pub, ())
+                .with_span(token.span())
+                .done()
+        } else if let Some(token) = self.try_next(TokenKind::Priv)? {
+            self.ast
+                .build_node(NodeKind::Priv, ())
+                .with_span(token.span())
                 .done()
         } else {
             NodeId::EMPTY
@@ -511,8 +717,8 @@ impl Parser {
 
         let parameters = self
             .ast
-            .build_node(NodeKind::Parameters, kind)
-            .with_location(left_paren.location)
+            .build_node(NodeKind::Parameters, (kind, visibility))
+            .with_span(left_paren.span())
             .with_children(parameters)
             .done();
         let name_location = self.ast.location(name);
@@ -532,15 +738,21 @@ impl Parser {
         Ok(self
             .ast
             .build_node(NodeKind::Func, (head, body))
-            .with_location(func_token.location)
+            .with_span(func_token.span())
             .done())
     }
 
     /// Parses a "break-like" expression. This includes `break` and `return`.
     ///
     /// A break-like expression is a token followed followed by an optional value on the same line
-    /// as that token.
+    /// as that token. `break` may additionally name the loop to break out of with a label placed
+    /// before the value.
     fn parse_break_like(&mut self, token: Token, kind: NodeKind) -> Result<NodeId, LanguageError> {
+        let label = if kind == NodeKind::Break {
+            self.parse_optional_label(&token)?
+        } else {
+            NodeId::EMPTY
+        };
         let next_token = self.lexer.peek_token()?;
         let result = if next_token.location.line > token.location.line
             || matches!(next_token.kind, TokenKind::End)
@@ -551,8 +763,63 @@ impl Parser {
         };
         Ok(self
             .ast
-            .build_node(kind, result)
-            .with_location(token.location)
+            .build_node(kind, (result, label))
+            .with_span(token.span())
+            .done())
+    }
+
+    /// Parses a `continue` expression.
+    fn parse_continue(&mut self, token: Token) -> Result<NodeId, LanguageError> {
+        let label = self.parse_optional_label(&token)?;
+        Ok(self
+            .ast
+            .build_node(NodeKind::Continue, label)
+            .with_span(token.span())
+            .done())
+    }
+
+    /// Parses the label following a `break` or `continue`, if there is one on the same line.
+    fn parse_optional_label(&mut self, token: &Token) -> Result<NodeId, LanguageError> {
+        let next_token = self.lexer.peek_token()?;
+        match &next_token.kind {
+            TokenKind::Label(name) if next_token.location.line == token.location.line => {
+                let name = Rc::clone(name);
+                let _label = self.lexer.next_token();
+                Ok(self
+                    .ast
+                    .build_node(NodeKind::Label, ())
+                    .with_span(next_token.span())
+                    .with_string(name)
+                    .done())
+            }
+            _ => Ok(NodeId::EMPTY),
+        }
+    }
+
+    /// Parses a labeled loop, `'label: while ... end` or `'label: for ... end`.
+    fn parse_labeled_loop(&mut self, token: Token) -> Result<NodeId, LanguageError> {
+        let TokenKind::Label(name) = &token.kind else {
+            unreachable!()
+        };
+        let label = self
+            .ast
+            .build_node(NodeKind::Label, ())
+            .with_span(token.span())
+            .with_string(Rc::clone(name))
+            .done();
+        self.expect(TokenKind::Colon, |_| {
+            LanguageErrorKind::ColonExpectedAfterLabel
+        })?;
+        let loop_token = self.lexer.next_token()?;
+        let looped = match loop_token.kind {
+            TokenKind::While => self.parse_while_expression(loop_token)?,
+            TokenKind::For => self.parse_for_expression(loop_token)?,
+            _ => return Err(self.error(&loop_token, LanguageErrorKind::LoopExpectedAfterLabel)),
+        };
+        Ok(self
+            .ast
+            .build_node(NodeKind::Labeled, (label, looped))
+            .with_span(token.span())
             .done())
     }
 
@@ -563,7 +830,18 @@ impl Parser {
         Ok(self
             .ast
             .build_node(NodeKind::Struct, name)
-            .with_location(struct_token.location)
+            .with_span(struct_token.span())
+            .done())
+    }
+
+    /// Parses an `import` expression.
+    fn parse_import(&mut self, import_token: Token) -> Result<NodeId, LanguageError> {
+        let name = self.lexer.next_token()?;
+        let name = self.parse_identifier(name)?;
+        Ok(self
+            .ast
+            .build_node(NodeKind::Import, name)
+            .with_span(import_token.span())
             .done())
     }
 
@@ -578,7 +856,7 @@ impl Parser {
         Ok(self
             .ast
             .build_node(NodeKind::ImplAs, implementee)
-            .with_location(token.location)
+            .with_span(token.span())
             .with_children(items)
             .done())
     }
@@ -595,7 +873,24 @@ impl Parser {
         Ok(self
             .ast
             .build_node(NodeKind::Trait, name)
-            .with_location(trait_token.location)
+            .with_span(trait_token.span())
+            .with_children(items)
+            .done())
+    }
+
+    /// Parses an enum declaration.
+    fn parse_enum(&mut self, enum_token: Token) -> Result<NodeId, LanguageError> {
+        let name = self.lexer.next_token()?;
+        let name = self.parse_identifier(name)?;
+        let mut items = Vec::new();
+        // Variants are parsed as regular expressions, and it's up to the codegen phase to check
+        // that they're identifiers or calls.
+        self.parse_terminated_block(&enum_token, &mut items, |k| k == &TokenKind::End)?;
+        let _end = self.lexer.next_token()?;
+        Ok(self
+            .ast
+            .build_node(NodeKind::Enum, name)
+            .with_span(enum_token.span())
             .with_children(items)
             .done())
     }
@@ -621,7 +916,7 @@ impl Parser {
                 Ok(self
                     .ast
                     .build_node(NodeKind::Field, name)
-                    .with_location(token.location)
+                    .with_span(token.span())
                     .done())
             }
 
@@ -629,19 +924,28 @@ impl Parser {
             TokenKind::LeftBracket => self.parse_list_or_dict(token),
             TokenKind::LeftBrace => self.parse_record(token),
 
-            TokenKind::Let => self.parse_let_expression(token),
+            TokenKind::Let => self.parse_let_expression(token, NodeKind::Let),
+            TokenKind::Const => self.parse_let_expression(token, NodeKind::Const),
             TokenKind::Do => self.parse_do_block(token),
             TokenKind::If => self.parse_if_expression(token),
             TokenKind::While => self.parse_while_expression(token),
             TokenKind::For => self.parse_for_expression(token),
+            TokenKind::Try => self.parse_try_expression(token),
+
+            TokenKind::Label(_) => self.parse_labeled_loop(token),
 
             TokenKind::Break => self.parse_break_like(token, NodeKind::Break),
             TokenKind::Return => self.parse_break_like(token, NodeKind::Return),
+            TokenKind::Continue => self.parse_continue(token),
+            TokenKind::Raise => self.parse_raise(token),
+            TokenKind::Defer => self.parse_defer(token),
 
             TokenKind::Func => self.parse_function(token, true),
             TokenKind::Struct => self.parse_struct(token),
+            TokenKind::Enum => self.parse_enum(token),
             TokenKind::As => self.parse_as(token),
             TokenKind::Trait => self.parse_trait(token),
+            TokenKind::Import => self.parse_import(token),
 
             _ => Err(self.error(&token, LanguageErrorKind::InvalidPrefixToken)),
         }
@@ -660,35 +964,136 @@ impl Parser {
         Ok(self
             .ast
             .build_node(kind, (left, right))
-            .with_location(token.location)
+            .with_span(token.span())
             .done())
     }
 
     /// Parses a function call.
     fn function_call(&mut self, left: NodeId, left_paren: Token) -> Result<NodeId, LanguageError> {
         let mut arguments = Vec::new();
-        self.parse_comma_separated(&mut arguments, TokenKind::RightParen, |p| {
-            p.parse_expression(0)
-        })?;
+        let in_slice_bounds = mem::replace(&mut self.in_slice_bounds, false);
+        let right_paren =
+            self.parse_comma_separated(&mut arguments, TokenKind::RightParen, |p| {
+                p.parse_argument()
+            })?;
+        self.in_slice_bounds = in_slice_bounds;
         Ok(self
             .ast
             .build_node(NodeKind::Call, left)
-            .with_location(left_paren.location)
+            .with_span(left_paren.span().union(right_paren.span()))
             .with_children(arguments)
             .done())
     }
 
+    /// Parses an argument of a function call, which may be spread with `...`.
+    fn parse_argument(&mut self) -> Result<NodeId, LanguageError> {
+        let Some(ellipsis) = self.try_next(TokenKind::Ellipsis)? else {
+            return self.parse_expression(0);
+        };
+        let list = self.parse_expression(0)?;
+        Ok(self
+            .ast
+            .build_node(NodeKind::Spread, list)
+            .with_span(ellipsis.span())
+            .done())
+    }
+
+    /// Parses a cascaded method call. Unlike with `.`, the method name is parsed together with its
+    /// arguments, such that the call is made on the receiver, and not on the result of the call.
+    fn parse_cascade(&mut self, left: NodeId, token: Token) -> Result<NodeId, LanguageError> {
+        let name_token = self.lexer.next_token()?;
+        let mut call = self.parse_identifier(name_token)?;
+        let next_token = self.lexer.peek_token()?;
+        if next_token.kind == TokenKind::LeftParen
+            && next_token.location.line == self.ast.location(call).line
+        {
+            let left_paren = self.lexer.next_token()?;
+            call = self.function_call(call, left_paren)?;
+        }
+        Ok(self
+            .ast
+            .build_node(NodeKind::Cascade, (left, call))
+            .with_span(token.span())
+            .done())
+    }
+
+    /// Parses a subscript `left[index]` or a slice `left[start..end]`, where either bound of the
+    /// slice may be omitted.
+    fn parse_subscript(
+        &mut self,
+        left: NodeId,
+        left_bracket: Token,
+    ) -> Result<NodeId, LanguageError> {
+        let in_slice_bounds = mem::replace(&mut self.in_slice_bounds, true);
+        let result = self.parse_subscript_inner(left, left_bracket);
+        self.in_slice_bounds = in_slice_bounds;
+        result
+    }
+
+    /// Parses the inside of a subscript, leaving the restoration of `in_slice_bounds` to
+    /// `parse_subscript` so that it also happens when there's an error.
+    fn parse_subscript_inner(
+        &mut self,
+        left: NodeId,
+        left_bracket: Token,
+    ) -> Result<NodeId, LanguageError> {
+        let start = if self.lexer.peek_token()?.kind == TokenKind::DotDot {
+            NodeId::EMPTY
+        } else {
+            self.parse_expression(0)?
+        };
+        let (kind, right) = if self.lexer.peek_token()?.kind == TokenKind::DotDot {
+            let dot_dot = self.lexer.next_token()?;
+            let end = if self.lexer.peek_token()?.kind == TokenKind::RightBracket {
+                NodeId::EMPTY
+            } else {
+                self.parse_expression(0)?
+            };
+            let bounds = self
+                .ast
+                .build_node(NodeKind::SliceBounds, (start, end))
+                .with_span(dot_dot.span())
+                .done();
+            (NodeKind::Slice, bounds)
+        } else {
+            (NodeKind::Index, start)
+        };
+        let right_bracket = self.expect(TokenKind::RightBracket, |_| {
+            LanguageErrorKind::MissingRightBracket
+        })?;
+        Ok(self
+            .ast
+            .build_node(kind, (left, right))
+            .with_span(left_bracket.span().union(right_bracket.span()))
+            .done())
+    }
+
+    /// Parses a struct pattern, whose type was already parsed as `left`.
+    fn parse_struct_pattern(
+        &mut self,
+        left: NodeId,
+        left_brace: Token,
+    ) -> Result<NodeId, LanguageError> {
+        let span = left_brace.span();
+        let fields = self.parse_record(left_brace)?;
+        Ok(self
+            .ast
+            .build_node(NodeKind::StructPattern, (left, fields))
+            .with_span(span)
+            .done())
+    }
+
     /// Parses an `impl` block.
     fn parse_impl(&mut self, left: NodeId, token: Token) -> Result<NodeId, LanguageError> {
         let mut items = Vec::new();
         // Note that we parse any type of item inside of the `impl` block.
         // The codegen phase is the thing that ensures the items declared are valid.
         self.parse_terminated_block(&token, &mut items, |k| k == &TokenKind::End)?;
-        let _end = self.lexer.next_token()?;
+        let end = self.lexer.next_token()?;
         Ok(self
             .ast
             .build_node(NodeKind::Impl, left)
-            .with_location(token.location)
+            .with_span(token.span().union(end.span()))
             .with_children(items)
             .done())
     }
@@ -700,6 +1105,8 @@ impl Parser {
             TokenKind::Minus => self.binary_operator(left, token, NodeKind::Subtract),
             TokenKind::Star => self.binary_operator(left, token, NodeKind::Multiply),
             TokenKind::Slash => self.binary_operator(left, token, NodeKind::Divide),
+            TokenKind::SlashSlash => self.binary_operator(left, token, NodeKind::FloorDivide),
+            TokenKind::Percent => self.binary_operator(left, token, NodeKind::Modulo),
 
             TokenKind::And => self.binary_operator(left, token, NodeKind::And),
             TokenKind::Or => self.binary_operator(left, token, NodeKind::Or),
@@ -709,11 +1116,16 @@ impl Parser {
             TokenKind::Greater => self.binary_operator(left, token, NodeKind::Greater),
             TokenKind::LessEqual => self.binary_operator(left, token, NodeKind::LessEqual),
             TokenKind::GreaterEqual => self.binary_operator(left, token, NodeKind::GreaterEqual),
+            TokenKind::Implements => self.binary_operator(left, token, NodeKind::Implements),
 
             TokenKind::Assign => self.binary_operator(left, token, NodeKind::Assign),
             TokenKind::Dot => self.binary_operator(left, token, NodeKind::Dot),
+            TokenKind::QuestionDot => self.binary_operator(left, token, NodeKind::NilSafeDot),
+            TokenKind::DotDot => self.parse_cascade(left, token),
 
             TokenKind::LeftParen => self.function_call(left, token),
+            TokenKind::LeftBracket => self.parse_subscript(left, token),
+            TokenKind::LeftBrace => self.parse_struct_pattern(left, token),
 
             TokenKind::Impl => self.parse_impl(left, token),
 
@@ -723,11 +1135,26 @@ impl Parser {
 
     /// Returns whether an infix token is not allowed to be carried over to the next line.
     fn is_invalid_continuation_token(token: &TokenKind) -> bool {
-        matches!(token, TokenKind::LeftParen)
+        matches!(
+            token,
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::LeftBrace
+        )
     }
 
     /// Parses an expression.
     fn parse_expression(&mut self, precedence: i8) -> Result<NodeId, LanguageError> {
+        if self.depth >= Self::MAX_DEPTH {
+            let token = self.lexer.peek_token()?;
+            return Err(self.error(&token, LanguageErrorKind::NestingTooDeep));
+        }
+        self.depth += 1;
+        let result = self.parse_expression_unchecked(precedence);
+        self.depth -= 1;
+        result
+    }
+
+    /// Parses an expression without checking how deeply it's nested.
+    fn parse_expression_unchecked(&mut self, precedence: i8) -> Result<NodeId, LanguageError> {
         let mut token = self.lexer.next_token()?;
         let mut left = self.parse_prefix(token)?;
 
@@ -738,6 +1165,9 @@ impl Parser {
             {
                 break;
             }
+            if next_token.kind == TokenKind::DotDot && self.in_slice_bounds {
+                break;
+            }
             token = self.lexer.next_token()?;
             left = self.parse_infix(left, token)?;
         }
@@ -753,26 +1183,176 @@ impl Parser {
                 let func_token = self.lexer.next_token()?;
                 self.parse_function(func_token, false)
             }
-            _ => self.parse_expression(0),
+            _ => {
+                let expression = self.parse_expression(0)?;
+                if self.lexer.peek_token()?.kind == TokenKind::Comma
+                    && self.ast.kind(expression) != NodeKind::Assign
+                {
+                    self.parse_parallel_assignment(expression)
+                } else {
+                    Ok(expression)
+                }
+            }
+        }
+    }
+
+    /// Parses a parallel assignment `a, b = x, y`, whose first target has already been parsed.
+    /// The targets and values are gathered into tuples, such that all values are computed before
+    /// any of them is assigned.
+    fn parse_parallel_assignment(&mut self, first: NodeId) -> Result<NodeId, LanguageError> {
+        let assignment_precedence = Self::precedence(&TokenKind::Assign);
+        let mut targets = vec![first];
+        while self.try_next(TokenKind::Comma)?.is_some() {
+            targets.push(self.parse_expression(assignment_precedence)?);
+        }
+        let Some(assign_token) = self.try_next(TokenKind::Assign)? else {
+            return Err(self
+                .ast
+                .error(first, LanguageErrorKind::ParallelAssignmentExpected));
+        };
+        let mut values = vec![self.parse_expression(assignment_precedence)?];
+        while self.try_next(TokenKind::Comma)?.is_some() {
+            values.push(self.parse_expression(assignment_precedence)?);
+        }
+
+        let targets_span = self
+            .ast
+            .span(first)
+            .union(self.ast.span(*targets.last().unwrap()));
+        let targets = self
+            .ast
+            .build_node(NodeKind::Tuple, ())
+            .with_children(targets)
+            .with_span(targets_span)
+            .done();
+        let value = if values.len() == 1 {
+            values[0]
+        } else {
+            let values_span = self
+                .ast
+                .span(values[0])
+                .union(self.ast.span(*values.last().unwrap()));
+            self.ast
+                .build_node(NodeKind::Tuple, ())
+                .with_children(values)
+                .with_span(values_span)
+                .done()
+        };
+        Ok(self
+            .ast
+            .build_node(NodeKind::Assign, (targets, value))
+            .with_span(assign_token.span())
+            .done())
+    }
+
+    /// Returns whether the token kind can begin an item, and is therefore a good place to resume
+    /// parsing after an error.
+    fn is_synchronization_point(kind: &TokenKind) -> bool {
+        matches!(
+            kind,
+            TokenKind::Identifier(_)
+                | TokenKind::Let
+                | TokenKind::Const
+                | TokenKind::Do
+                | TokenKind::If
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::Label(_)
+                | TokenKind::Break
+                | TokenKind::Continue
+                | TokenKind::Try
+                | TokenKind::Raise
+                | TokenKind::Defer
+                | TokenKind::Return
+                | TokenKind::Func
+                | TokenKind::Struct
+                | TokenKind::Enum
+                | TokenKind::Trait
+                | TokenKind::Import
+        )
+    }
+
+    /// Skips tokens until the start of what looks like the next item, so that parsing can resume
+    /// after an error. Only tokens that begin a line after the error's line are considered.
+    fn synchronize(&mut self, error: &LanguageError) {
+        let mut previous_line = match error {
+            LanguageError::Compile { span, .. } => span.start.line,
+            LanguageError::Runtime { .. } => unreachable!("the parser only emits compile errors"),
+        };
+        loop {
+            match self.lexer.peek_token() {
+                Ok(token) => {
+                    if token.kind == TokenKind::Eof {
+                        break;
+                    }
+                    if token.location.line > previous_line
+                        && Self::is_synchronization_point(&token.kind)
+                    {
+                        break;
+                    }
+                    previous_line = token.location.line;
+                    // This cannot fail because the token was peeked successfully.
+                    let _ = self.lexer.next_token();
+                }
+                Err(_) => {
+                    // Skip over the malformed token, up to the point where lexing failed.
+                    let _ = self.lexer.next_token();
+                    self.lexer.skip_char();
+                }
+            }
         }
     }
 
     /// Parses a Mica program.
-    pub fn parse(mut self) -> Result<(Ast, NodeId), LanguageError> {
-        let first_token = self.lexer.peek_token()?;
+    ///
+    /// Parsing does not stop at the first error. Instead, the parser skips to the next item and
+    /// continues, such that all errors in the program can be reported at once.
+    pub fn parse(mut self) -> Result<(Ast, NodeId), Vec<LanguageError>> {
+        let mut errors = Vec::new();
+        // If this fails, the error is reported by the first call to `parse_item` below.
+        let start = self
+            .lexer
+            .peek_token()
+            .map(|token| token.location)
+            .unwrap_or_default();
         let mut main = Vec::new();
         loop {
-            if self.lexer.peek_token()?.kind == TokenKind::Eof {
-                let main = self
-                    .ast
-                    .build_node(NodeKind::Main, ())
-                    .with_location(first_token.location)
-                    .with_children(main)
-                    .done();
-                return Ok((self.ast, main));
+            match self.lexer.peek_token() {
+                Ok(Token {
+                    kind: TokenKind::Eof,
+                    ..
+                }) => break,
+                // Stray block terminators are most likely left over from a block that was skipped
+                // over because of an error, so reporting them would only add noise.
+                Ok(Token {
+                    kind: TokenKind::End | TokenKind::Elif | TokenKind::Else,
+                    ..
+                }) if !errors.is_empty() => {
+                    let _ = self.lexer.next_token();
+                    continue;
+                }
+                _ => (),
+            }
+            match self.parse_item() {
+                Ok(item) => main.push(item),
+                Err(error) => {
+                    self.synchronize(&error);
+                    errors.push(error);
+                }
             }
-            let item = self.parse_item()?;
-            main.push(item);
+        }
+
+        if errors.is_empty() {
+            self.ast.set_front_matter(self.lexer.front_matter().clone());
+            let main = self
+                .ast
+                .build_node(NodeKind::Main, ())
+                .with_location(start)
+                .with_children(main)
+                .done();
+            Ok((self.ast, main))
+        } else {
+            Err(errors)
         }
     }
 }
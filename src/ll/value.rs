@@ -7,6 +7,7 @@ mod dicts;
 mod impls;
 mod lists;
 mod records;
+mod strings;
 mod structs;
 mod traits;
 mod tuples;
@@ -21,6 +22,7 @@ pub use dicts::*;
 use impls::ValueImpl;
 pub use lists::*;
 pub use records::*;
+pub use strings::*;
 pub use structs::*;
 pub use traits::*;
 pub use tuples::*;
@@ -46,7 +48,7 @@ trait ValueCommon: Clone + PartialEq {
     fn new_nil() -> Self;
     fn new_boolean(b: bool) -> Self;
     fn new_number(n: f64) -> Self;
-    fn new_string(s: GcRaw<String>) -> Self;
+    fn new_string(s: GcRaw<Str>) -> Self;
     fn new_function(f: GcRaw<Closure>) -> Self;
     fn new_struct(s: GcRaw<Struct>) -> Self;
     fn new_trait(s: GcRaw<Trait>) -> Self;
@@ -57,7 +59,7 @@ trait ValueCommon: Clone + PartialEq {
     unsafe fn get_boolean_unchecked(&self) -> bool;
     // This returns a reference such that mica-hl can use `f64` as a `self` parameter in methods.
     unsafe fn get_number_unchecked(&self) -> &f64;
-    unsafe fn get_raw_string_unchecked(&self) -> GcRaw<String>;
+    unsafe fn get_raw_string_unchecked(&self) -> GcRaw<Str>;
     unsafe fn get_raw_function_unchecked(&self) -> GcRaw<Closure>;
     unsafe fn get_raw_struct_unchecked(&self) -> GcRaw<Struct>;
     unsafe fn get_raw_trait_unchecked(&self) -> GcRaw<Trait>;
@@ -100,9 +102,10 @@ impl RawValue {
                 .deref()
                 .to_owned()
                 .into(),
-            ValueKind::UserData => unsafe {
-                self.0.get_raw_user_data_unchecked().get().type_name()
-            },
+            ValueKind::UserData => unsafe { self.0.get_raw_user_data_unchecked().get() }
+                .type_name()
+                .into_owned()
+                .into(),
         }
     }
 
@@ -133,7 +136,7 @@ impl RawValue {
     ///
     /// # Safety
     /// Calling this on a value that isn't known to be a string is undefined behavior.
-    pub unsafe fn get_raw_string_unchecked(&self) -> GcRaw<String> {
+    pub unsafe fn get_raw_string_unchecked(&self) -> GcRaw<Str> {
         self.0.get_raw_string_unchecked()
     }
 
@@ -217,7 +220,7 @@ impl RawValue {
     }
 
     /// Ensures the value is a `String`, returning a type mismatch error if that's not the case.
-    pub fn ensure_raw_string(&self) -> Result<GcRaw<String>, LanguageErrorKind> {
+    pub fn ensure_raw_string(&self) -> Result<GcRaw<Str>, LanguageErrorKind> {
         if self.0.kind() == ValueKind::String {
             Ok(unsafe { self.0.get_raw_string_unchecked() })
         } else {
@@ -326,6 +329,36 @@ impl RawValue {
             }
         }
     }
+
+    /// Compares two values, falling back to a total order where [`try_partial_cmp`] would not
+    /// produce one. This is what sorting uses, so that the result never depends on the order in
+    /// which elements happen to be compared.
+    ///
+    /// NaN is equal to itself and greater than all other numbers, and values that cannot be
+    /// ordered at all (such as functions) are treated as equal. Values of different types are
+    /// still an error.
+    ///
+    /// [`try_partial_cmp`]: RawValue::try_partial_cmp
+    pub fn total_cmp(&self, other: &Self) -> Result<Ordering, LanguageErrorKind> {
+        match (self.0.kind(), other.0.kind()) {
+            (ValueKind::Number, ValueKind::Number) => {
+                let a = unsafe { self.0.get_number_unchecked() };
+                let b = unsafe { other.0.get_number_unchecked() };
+                Ok(a.partial_cmp(b)
+                    .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan())))
+            }
+            (ValueKind::UserData, ValueKind::UserData) => unsafe {
+                let a = self.0.get_raw_user_data_unchecked().get().as_any();
+                let b = other.0.get_raw_user_data_unchecked().get().as_any();
+                if let (Some(a), Some(b)) = (a.downcast_ref::<List>(), b.downcast_ref::<List>()) {
+                    a.total_cmp(b)
+                } else {
+                    Ok(self.try_partial_cmp(other)?.unwrap_or(Ordering::Equal))
+                }
+            },
+            _ => Ok(self.try_partial_cmp(other)?.unwrap_or(Ordering::Equal)),
+        }
+    }
 }
 
 impl Default for RawValue {
@@ -352,8 +385,8 @@ impl From<f64> for RawValue {
     }
 }
 
-impl From<GcRaw<String>> for RawValue {
-    fn from(s: GcRaw<String>) -> Self {
+impl From<GcRaw<Str>> for RawValue {
+    fn from(s: GcRaw<Str>) -> Self {
         Self(ValueImpl::new_string(s), PhantomData)
     }
 }
@@ -404,7 +437,21 @@ impl fmt::Debug for RawValue {
                         self.0.get_raw_function_unchecked().get_raw()
                     )
                 }
-                ValueKind::Struct => dtable(f, self.0.get_raw_struct_unchecked().get().dtable()),
+                ValueKind::Struct => {
+                    let s = self.0.get_raw_struct_unchecked().get();
+                    match s.enum_variant() {
+                        Some((variant, values)) => {
+                            write!(f, "{}.{variant}", s.dtable().type_name)?;
+                            if !values.is_empty() {
+                                let values: Vec<_> =
+                                    values.iter().map(|value| format!("{value:?}")).collect();
+                                write!(f, "({})", values.join(", "))?;
+                            }
+                            Ok(())
+                        }
+                        None => dtable(f, s.dtable()),
+                    }
+                }
                 ValueKind::Trait => dtable(f, self.0.get_raw_trait_unchecked().get().dtable()),
                 ValueKind::UserData => self.0.get_raw_user_data_unchecked().get().fmt(f),
             }
@@ -465,5 +512,18 @@ pub trait UserData: Any + fmt::Debug {
 
     fn visit_references(&self, _visit: &mut dyn FnMut(RawValue)) {}
 
+    /// Like [`visit_references`][Self::visit_references], but for references that keep the memory
+    /// they point to allocated, such as [`Value`][crate::Value]s.
+    ///
+    /// Memory with references held outside of the GC is always treated as reachable, unless all
+    /// the references are owned by user data that reports them here.
+    fn visit_owned_references(&self, _visit: &mut dyn FnMut(RawValue)) {}
+
+    /// Returns the number of bytes the user data owns outside of itself, such as the elements of
+    /// a list. This counts towards the GC's memory limit.
+    fn heap_size(&self) -> usize {
+        0
+    }
+
     fn as_any(&self) -> &dyn Any;
 }
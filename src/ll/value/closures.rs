@@ -1,9 +1,14 @@
-use std::{cell::UnsafeCell, fmt, marker::PhantomPinned, mem, pin::Pin, ptr, rc::Rc};
+#[cfg(feature = "forbid-unsafe")]
+use std::cell::Cell;
+#[cfg(not(feature = "forbid-unsafe"))]
+use std::{cell::UnsafeCell, fmt, marker::PhantomPinned, mem, ptr};
+use std::{pin::Pin, rc::Rc};
 
 use super::RawValue;
 use crate::ll::bytecode::FunctionIndex;
 
 /// An upvalue captured by a closure.
+#[cfg(not(feature = "forbid-unsafe"))]
 pub struct Upvalue {
     /// A writable pointer to the variable captured by this upvalue.
     pub(crate) ptr: UnsafeCell<ptr::NonNull<RawValue>>,
@@ -13,6 +18,7 @@ pub struct Upvalue {
     _pinned: PhantomPinned,
 }
 
+#[cfg(not(feature = "forbid-unsafe"))]
 impl fmt::Debug for Upvalue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Upvalue")
@@ -22,6 +28,7 @@ impl fmt::Debug for Upvalue {
     }
 }
 
+#[cfg(not(feature = "forbid-unsafe"))]
 impl Upvalue {
     /// Creates a new upvalue pointing to a live variable.
     pub(crate) fn new(var: ptr::NonNull<RawValue>) -> Pin<Rc<Upvalue>> {
@@ -32,6 +39,18 @@ impl Upvalue {
         })
     }
 
+    /// Creates a new upvalue that's already closed and holds the given value.
+    pub(crate) fn new_closed(value: RawValue) -> Pin<Rc<Upvalue>> {
+        let upvalue = Rc::pin(Upvalue {
+            ptr: UnsafeCell::new(ptr::NonNull::dangling()),
+            closed: UnsafeCell::new(value),
+            _pinned: PhantomPinned,
+        });
+        // Safety: the upvalue is pinned, so the address of `closed` does not change anymore.
+        unsafe { *upvalue.ptr.get() = ptr::NonNull::new_unchecked(upvalue.closed.get()) };
+        upvalue
+    }
+
     /// Closes an upvalue by `mem::take`ing the value behind the `ptr` into the `closed` field, and
     /// updating the `ptr` field to point to the `closed` field's contents.
     ///
@@ -46,6 +65,15 @@ impl Upvalue {
         *ptr = ptr::NonNull::new(closed).unwrap();
     }
 
+    /// Points an open upvalue at the new location of its variable, after the stack holding it
+    /// was moved.
+    ///
+    /// # Safety
+    /// The upvalue must not be closed, and `var` must point to the variable it captured.
+    pub(crate) unsafe fn relocate(&self, var: ptr::NonNull<RawValue>) {
+        *self.ptr.get() = var;
+    }
+
     /// Returns the value pointed to by this upvalue.
     ///
     /// # Safety
@@ -65,6 +93,56 @@ impl Upvalue {
     }
 }
 
+/// An upvalue captured by a closure.
+///
+/// Instead of pointing into the stack of the fiber that declared the variable, the variable is
+/// moved into the upvalue as soon as it's captured, and the fiber accesses it through the upvalue
+/// from then on. This is slower, but doesn't need any pointers.
+#[cfg(feature = "forbid-unsafe")]
+#[derive(Debug)]
+pub struct Upvalue {
+    value: Cell<RawValue>,
+}
+
+// These functions are `unsafe` only to have the same signatures as the ones of the pointer-based
+// implementation.
+#[cfg(feature = "forbid-unsafe")]
+impl Upvalue {
+    /// Creates a new upvalue for a variable that's still in scope, holding its current value.
+    pub(crate) fn new(value: RawValue) -> Pin<Rc<Upvalue>> {
+        Rc::pin(Upvalue {
+            value: Cell::new(value),
+        })
+    }
+
+    /// Creates a new upvalue that's already closed and holds the given value.
+    pub(crate) fn new_closed(value: RawValue) -> Pin<Rc<Upvalue>> {
+        Self::new(value)
+    }
+
+    /// Closes the upvalue. This does nothing, since the upvalue already holds the variable.
+    ///
+    /// # Safety
+    /// Always safe to call.
+    pub(crate) unsafe fn close(&self) {}
+
+    /// Returns the value of the variable.
+    ///
+    /// # Safety
+    /// Always safe to call.
+    pub(crate) unsafe fn get(&self) -> RawValue {
+        self.value.get()
+    }
+
+    /// Writes to the variable.
+    ///
+    /// # Safety
+    /// Always safe to call.
+    pub(crate) unsafe fn set(&self, value: RawValue) {
+        self.value.set(value)
+    }
+}
+
 /// The runtime representation of a function.
 #[derive(Debug)]
 #[repr(align(8))]
@@ -135,7 +135,7 @@ impl PartialEq for Dict {
         if self.len() != other.len() {
             return false;
         }
-        unsafe { self.iter() }.all(|(key, value)| other.get(key).map_or(false, |v| value == v))
+        unsafe { self.iter() }.all(|(key, value)| other.get(key) == Some(value))
     }
 }
 
@@ -197,6 +197,20 @@ impl UserData for Dict {
         Cow::Borrowed("Dict")
     }
 
+    fn heap_size(&self) -> usize {
+        let inner = unsafe { &*self.inner.get() };
+        inner.table.buckets() * (mem::size_of::<(RawValue, RawValue)>() + 1)
+    }
+
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        unsafe {
+            for (key, value) in self.iter() {
+                visit(key);
+                visit(value);
+            }
+        }
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -218,8 +232,11 @@ impl RawValue {
                 ValueKind::Boolean => self.get_boolean_unchecked().hash(state),
                 // Hashing floats isn't normally considered OK by Rust, but in our case it's the
                 // only option because we don't have an integer type (and probably
-                // never will).
-                ValueKind::Number => self.get_number_unchecked().to_bits().hash(state),
+                // never will). Numbers that compare equal must hash equally, so -0 is hashed as 0,
+                // and all NaNs share a single canonical bit pattern.
+                ValueKind::Number => {
+                    canonical_number_bits(*self.get_number_unchecked()).hash(state)
+                }
                 ValueKind::String => self.get_raw_string_unchecked().get().hash(state),
                 // Objects with interior mutability are hashed by reference.
                 ValueKind::Function => self.get_raw_function_unchecked().get_raw().hash(state),
@@ -233,10 +250,26 @@ impl RawValue {
     }
 }
 
+fn canonical_number_bits(x: f64) -> u64 {
+    if x == 0.0 {
+        0.0f64.to_bits()
+    } else if x.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        x.to_bits()
+    }
+}
+
 fn make_hasher(builder: &DictHashBuilder) -> impl Fn(&(RawValue, RawValue)) -> u64 + '_ {
     move |&(key, _value)| key.hash(&mut builder.build_hasher())
 }
 
+/// Keys are compared using `==`, except that NaN is considered equal to itself. Otherwise a NaN key
+/// could be inserted any number of times and never looked up again.
 fn equivalent_key(key: RawValue) -> impl Fn(&(RawValue, RawValue)) -> bool {
-    move |&(key2, _value)| key == key2
+    move |&(key2, _value)| key == key2 || (is_nan(key) && is_nan(key2))
+}
+
+fn is_nan(value: RawValue) -> bool {
+    value.kind() == ValueKind::Number && unsafe { value.get_number_unchecked() }.is_nan()
 }
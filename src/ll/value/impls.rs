@@ -1,11 +1,33 @@
 //! Implementations of dynamically typed values.
+//!
+//! Both implementations are compiled wherever they can be, so that the one that isn't selected
+//! keeps up with changes to the other.
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(target_pointer_width = "64")]
+#[cfg_attr(
+    not(all(feature = "nan-boxing", not(feature = "forbid-unsafe"))),
+    allow(dead_code)
+)]
 mod nanbox;
-// #[cfg(not(target_arch = "x86_64"))]
+#[cfg_attr(
+    all(
+        feature = "nan-boxing",
+        target_pointer_width = "64",
+        not(feature = "forbid-unsafe")
+    ),
+    allow(dead_code)
+)]
 mod portable;
 
-#[cfg(target_arch = "x86_64")]
+#[cfg(all(
+    feature = "nan-boxing",
+    target_pointer_width = "64",
+    not(feature = "forbid-unsafe")
+))]
 pub(crate) use nanbox::ValueImpl;
-#[cfg(not(target_arch = "x86_64"))]
+#[cfg(not(all(
+    feature = "nan-boxing",
+    target_pointer_width = "64",
+    not(feature = "forbid-unsafe")
+)))]
 pub(crate) use portable::ValueImpl;
@@ -1,11 +1,11 @@
 //! NaN-boxed values. These are much less portable than the enum implementation, but each values
 //! takes up half as much space (8 bytes vs 16 bytes).
 
-use std::{hint::unreachable_unchecked, ops::Deref};
+use std::{hint::unreachable_unchecked, ops::Deref, ptr};
 
 use crate::ll::{
     gc::{GcMem, GcRaw},
-    value::{Closure, Struct, Trait, UserData, ValueCommon, ValueKind},
+    value::{Closure, Str, Struct, Trait, UserData, ValueCommon, ValueKind},
 };
 
 fn _size_and_alignment_checks() {
@@ -14,6 +14,7 @@ fn _size_and_alignment_checks() {
         assert!(std::mem::size_of::<*const ()>() == 8);
         assert!(std::mem::align_of::<Struct>() >= 8);
         assert!(std::mem::align_of::<Closure>() >= 8);
+        assert!(std::mem::align_of::<Str>() >= 8);
         assert!(std::mem::align_of::<Box<dyn UserData>>() >= 8);
     };
 }
@@ -28,7 +29,8 @@ impl ValueImpl {
 
     // We use the sign bit for disambiguating between "enum" values and "object" values.
     // Enum values are ones that only have one instance, ie. `nil`, `true`, `false`.
-    // Object values are ones that are stored in `Rc`s.
+    // Object values are ones that are allocated by the GC. The payload of an object value is
+    // a pointer straight to the object's `GcMem`, so dereferencing one is a single hop.
     const SIGN_ENUM: u64 = 0;
     const SIGN_OBJECT: u64 = 1;
 
@@ -64,9 +66,7 @@ impl ValueImpl {
 
     /// Creates a new value from a normal float.
     fn from_float(f: f64) -> Self {
-        // This function produces a valid bit pattern so it's safe to call.
-        #[allow(clippy::transmute_float_to_int)]
-        Self(unsafe { std::mem::transmute(f) })
+        Self(f.to_bits())
     }
 
     /// Returns the bit pattern of a NaN.
@@ -86,10 +86,21 @@ impl ValueImpl {
     }
 
     /// Creates a new object NaN with a type tag from a `GcRaw`.
+    ///
+    /// The `GcRaw` itself is packed into the value rather than boxed, so no extra allocation is
+    /// made here.
     unsafe fn new_object_nan<T>(tag: u64, gc: GcRaw<T>) -> Self {
         // This cast is fine because `_size_and_alignment_checks` ensures that the size of
-        // a usize == size of u64 (8 bytes).
-        let pointer = gc.get_raw() as usize as u64;
+        // a usize == size of u64 (8 bytes). The pointer's provenance is exposed, so that
+        // `object_pointer` can get it back.
+        let pointer = gc.get_raw().expose_provenance() as u64;
+        // Pointers have to fit in the payload, which rules out platforms that use the upper bits
+        // of addresses, such as for memory tagging.
+        debug_assert_eq!(
+            pointer & !Self::OBJECT_POINTER_BITS,
+            0,
+            "object pointer does not fit in a NaN's payload"
+        );
         Self::new_nan(Self::SIGN_OBJECT, pointer | tag)
     }
 
@@ -110,7 +121,7 @@ impl ValueImpl {
 
     /// Returns the object pointer. Assumes the value is an object.
     unsafe fn object_pointer<T>(&self) -> *const T {
-        (self.0 & Self::OBJECT_POINTER_BITS) as usize as *const T
+        ptr::with_exposed_provenance((self.0 & Self::OBJECT_POINTER_BITS) as usize)
     }
 
     // The functions below do not perform any checks on what's inside, they just blindly
@@ -139,10 +150,16 @@ impl ValueCommon for ValueImpl {
     }
 
     fn new_number(n: f64) -> Self {
-        Self::from_float(n)
+        // NaNs produced by arithmetic have an empty payload, but ones from elsewhere may have
+        // any payload, which would make them look like other kinds of values.
+        if n.is_nan() {
+            Self::from_float(f64::NAN)
+        } else {
+            Self::from_float(n)
+        }
     }
 
-    fn new_string(s: GcRaw<String>) -> Self {
+    fn new_string(s: GcRaw<Str>) -> Self {
         unsafe { Self::new_object_nan(Self::OBJECT_STRING, s) }
     }
 
@@ -189,7 +206,7 @@ impl ValueCommon for ValueImpl {
         self.as_float()
     }
 
-    unsafe fn get_raw_string_unchecked(&self) -> GcRaw<String> {
+    unsafe fn get_raw_string_unchecked(&self) -> GcRaw<Str> {
         self.as_gc()
     }
 
@@ -221,9 +238,12 @@ impl PartialEq for ValueImpl {
         {
             unsafe {
                 match self.object_tag() {
+                    // Interned strings are equal if they're the same object, in which case there's no
+                    // need to compare their contents.
+                    Self::OBJECT_STRING if self.0 == other.0 => return true,
                     Self::OBJECT_STRING => {
-                        let a = self.as_gc::<String>().get();
-                        let b = other.as_gc::<String>().get();
+                        let a = self.as_gc::<Str>().get();
+                        let b = other.as_gc::<Str>().get();
                         return a == b;
                     }
                     Self::OBJECT_USER_DATA => {
@@ -1,13 +1,27 @@
 //! Portable implementation of values. Uses a regular `enum`, which isn't very cache efficient,
 //! but is supported on most platforms.
 
-use std::{hint::unreachable_unchecked, mem, ops::Deref};
+use std::{mem, ops::Deref};
 
 use crate::ll::{
     gc::GcRaw,
-    value::{Closure, Struct, Trait, UserData, ValueCommon, ValueKind},
+    value::{Closure, Str, Struct, Trait, UserData, ValueCommon, ValueKind},
 };
 
+/// Called when a value is accessed as a kind it does not have, which the VM guarantees never
+/// happens.
+#[inline(always)]
+unsafe fn kind_mismatch() -> ! {
+    #[cfg(feature = "forbid-unsafe")]
+    {
+        unreachable!("value accessed as the wrong kind")
+    }
+    #[cfg(not(feature = "forbid-unsafe"))]
+    {
+        std::hint::unreachable_unchecked()
+    }
+}
+
 /// A portable implementation of values.
 #[derive(Clone, Copy)]
 pub(crate) enum ValueImpl {
@@ -20,7 +34,7 @@ pub(crate) enum ValueImpl {
     /// A double-precision floating point number.
     Number(f64),
     /// A string.
-    String(GcRaw<String>),
+    String(GcRaw<Str>),
     /// A function.
     Function(GcRaw<Closure>),
     /// A struct.
@@ -47,7 +61,7 @@ impl ValueCommon for ValueImpl {
         Self::Number(n)
     }
 
-    fn new_string(s: GcRaw<String>) -> Self {
+    fn new_string(s: GcRaw<Str>) -> Self {
         Self::String(s)
     }
 
@@ -84,7 +98,7 @@ impl ValueCommon for ValueImpl {
         match self {
             Self::True => true,
             Self::False => false,
-            _ => unreachable_unchecked(),
+            _ => kind_mismatch(),
         }
     }
 
@@ -92,15 +106,15 @@ impl ValueCommon for ValueImpl {
         if let Self::Number(x) = self {
             x
         } else {
-            unreachable_unchecked()
+            kind_mismatch()
         }
     }
 
-    unsafe fn get_raw_string_unchecked(&self) -> GcRaw<String> {
+    unsafe fn get_raw_string_unchecked(&self) -> GcRaw<Str> {
         if let Self::String(s) = self {
             *s
         } else {
-            unreachable_unchecked()
+            kind_mismatch()
         }
     }
 
@@ -108,7 +122,7 @@ impl ValueCommon for ValueImpl {
         if let Self::Function(f) = self {
             *f
         } else {
-            unreachable_unchecked()
+            kind_mismatch()
         }
     }
 
@@ -116,7 +130,7 @@ impl ValueCommon for ValueImpl {
         if let Self::Struct(s) = self {
             *s
         } else {
-            unreachable_unchecked()
+            kind_mismatch()
         }
     }
 
@@ -124,7 +138,7 @@ impl ValueCommon for ValueImpl {
         if let Self::Trait(t) = self {
             *t
         } else {
-            unreachable_unchecked()
+            kind_mismatch()
         }
     }
 
@@ -132,7 +146,7 @@ impl ValueCommon for ValueImpl {
         if let Self::UserData(u) = self {
             *u
         } else {
-            unreachable_unchecked()
+            kind_mismatch()
         }
     }
 }
@@ -141,7 +155,7 @@ impl PartialEq for ValueImpl {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::Number(l), Self::Number(r)) => l == r,
-            (Self::String(l), Self::String(r)) => unsafe { l.get() == r.get() },
+            (Self::String(l), Self::String(r)) => l == r || unsafe { l.get() == r.get() },
             (Self::Function(l), Self::Function(r)) => l == r,
             (Self::Struct(l), Self::Struct(r)) => l == r,
             (Self::Trait(l), Self::Trait(r)) => l == r,
@@ -5,9 +5,10 @@ use std::{
     cmp::Ordering,
     fmt,
     hash::{Hash, Hasher},
+    ops::Range,
 };
 
-use super::{RawValue, UserData};
+use super::{RawValue, UserData, ValueKind};
 use crate::{
     ll::{
         bytecode::{DispatchTable, Library},
@@ -46,6 +47,24 @@ impl List {
         &*self.elements.get()
     }
 
+    /// Returns the element at the given index, as read by the subscript operator `list[index]`.
+    pub(crate) unsafe fn index(&self, index: RawValue) -> Result<RawValue, LanguageErrorKind> {
+        let elements = self.as_slice();
+        Ok(elements[checked_index(index, elements.len())?])
+    }
+
+    /// Replaces the element at the given index, returning the new element.
+    pub(crate) unsafe fn set_index(
+        &self,
+        index: RawValue,
+        value: RawValue,
+    ) -> Result<RawValue, LanguageErrorKind> {
+        let elements = &mut *self.get_mut();
+        let index = checked_index(index, elements.len())?;
+        elements[index] = value;
+        Ok(value)
+    }
+
     /// Attempts to compare two lists to each other lexicographically.
     pub(crate) unsafe fn try_partial_cmp(
         &self,
@@ -65,6 +84,18 @@ impl List {
 
         Ok(left.len().partial_cmp(&right.len()))
     }
+
+    /// Compares two lists lexicographically using [`RawValue::total_cmp`] on their elements.
+    pub(crate) unsafe fn total_cmp(&self, other: &List) -> Result<Ordering, LanguageErrorKind> {
+        let (left, right) = (self.as_slice(), other.as_slice());
+        for (a, b) in left.iter().zip(right) {
+            match a.total_cmp(b)? {
+                Ordering::Equal => (),
+                non_eq => return Ok(non_eq),
+            }
+        }
+        Ok(left.len().cmp(&right.len()))
+    }
 }
 
 impl PartialEq for List {
@@ -136,6 +167,10 @@ impl UserData for List {
         Cow::Borrowed("List")
     }
 
+    fn heap_size(&self) -> usize {
+        unsafe { (*self.elements.get()).capacity() * std::mem::size_of::<RawValue>() }
+    }
+
     fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
         unsafe {
             for &value in self.as_slice() {
@@ -144,3 +179,39 @@ impl UserData for List {
         }
     }
 }
+
+/// Converts a value to an index into a sequence of length `len`. The index must be a whole number
+/// lying within the sequence's bounds.
+pub(crate) fn checked_index(index: RawValue, len: usize) -> Result<usize, LanguageErrorKind> {
+    let index = index.ensure_number()?;
+    if index >= 0.0 && index.fract() == 0.0 && index < len as f64 {
+        Ok(index as usize)
+    } else {
+        Err(LanguageErrorKind::IndexOutOfBounds { index, len })
+    }
+}
+
+/// Converts the bounds of a slice `[start..end]` to a range of indices into a sequence of length
+/// `len`. Either bound may be `nil`, in which case the slice extends to that end of the sequence.
+pub(crate) fn checked_slice(
+    start: RawValue,
+    end: RawValue,
+    len: usize,
+) -> Result<Range<usize>, LanguageErrorKind> {
+    let bound = |value: RawValue, default: usize| {
+        if value.kind() == ValueKind::Nil {
+            return Ok(default);
+        }
+        let bound = value.ensure_number()?;
+        if bound >= 0.0 && bound.fract() == 0.0 && bound <= len as f64 {
+            Ok(bound as usize)
+        } else {
+            Err(LanguageErrorKind::IndexOutOfBounds { index: bound, len })
+        }
+    };
+    let (start, end) = (bound(start, 0)?, bound(end, len)?);
+    if start > end {
+        return Err(LanguageErrorKind::InvalidSlice { start, end });
+    }
+    Ok(start..end)
+}
@@ -74,6 +74,16 @@ impl UserData for Record {
         Cow::Borrowed(self.record_type.dtable.pretty_name.deref())
     }
 
+    fn heap_size(&self) -> usize {
+        self.fields.capacity() * std::mem::size_of::<RawValue>()
+    }
+
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        for &field in &self.fields {
+            visit(field);
+        }
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -0,0 +1,220 @@
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+/// The length in bytes of the longest string a [`Str`] stores without allocating.
+const INLINE_CAPACITY: usize = 22;
+
+/// An immutable string, which is what Mica's `String` values hold.
+///
+/// Strings of up to 22 bytes, such as single characters and most words, are stored inline, so the
+/// only allocation made for them is the GC's own. Longer strings are allocated separately, with
+/// exactly as much memory as they need.
+#[derive(Clone)]
+pub struct Str(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_CAPACITY],
+    },
+    Heap(Box<str>),
+}
+
+const _: () = assert!(std::mem::size_of::<Str>() == std::mem::size_of::<String>());
+
+impl Str {
+    /// Creates a string with the contents of the given string slice.
+    pub fn new(s: &str) -> Self {
+        Self::concat(&[s])
+    }
+
+    /// Creates a string out of the given parts, laid out one after another. This is cheaper than
+    /// concatenating the parts into a `String` first, since the parts are copied into place
+    /// directly.
+    ///
+    /// # Examples
+    /// ```
+    /// use mica::Str;
+    ///
+    /// let s = Str::concat(&["Hello, ", "world", "!"]);
+    /// assert_eq!(s, "Hello, world!");
+    /// ```
+    pub fn concat(parts: &[&str]) -> Self {
+        let len = parts.iter().map(|part| part.len()).sum();
+        if len <= INLINE_CAPACITY {
+            let mut bytes = [0; INLINE_CAPACITY];
+            let mut end = 0;
+            for part in parts {
+                bytes[end..end + part.len()].copy_from_slice(part.as_bytes());
+                end += part.len();
+            }
+            Self(Repr::Inline {
+                len: len as u8,
+                bytes,
+            })
+        } else {
+            let mut s = String::with_capacity(len);
+            for part in parts {
+                s.push_str(part);
+            }
+            Self(Repr::Heap(s.into_boxed_str()))
+        }
+    }
+
+    /// Returns the string as a string slice.
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // SAFETY: Inline strings are only ever created by copying whole string slices.
+            Repr::Inline { len, bytes } => unsafe {
+                std::str::from_utf8_unchecked(&bytes[..usize::from(*len)])
+            },
+            Repr::Heap(s) => s,
+        }
+    }
+
+    /// Returns how many bytes the string owns outside of itself.
+    pub fn heap_size(&self) -> usize {
+        match &self.0 {
+            Repr::Inline { .. } => 0,
+            Repr::Heap(s) => s.len(),
+        }
+    }
+}
+
+impl Default for Str {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl Deref for Str {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Str {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl Borrow<str> for Str {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl From<&str> for Str {
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<String> for Str {
+    fn from(s: String) -> Self {
+        if s.len() <= INLINE_CAPACITY {
+            Self::new(&s)
+        } else {
+            Self(Repr::Heap(s.into_boxed_str()))
+        }
+    }
+}
+
+impl From<char> for Str {
+    fn from(c: char) -> Self {
+        Self::new(c.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl From<Str> for String {
+    fn from(s: Str) -> Self {
+        match s.0 {
+            Repr::Inline { .. } => s.as_str().to_owned(),
+            Repr::Heap(s) => s.into_string(),
+        }
+    }
+}
+
+/// Collecting characters only allocates once they don't fit inline anymore.
+impl FromIterator<char> for Str {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        let mut bytes = [0; INLINE_CAPACITY];
+        let mut len = 0;
+        let mut chars = iter.into_iter();
+        for c in chars.by_ref() {
+            if len + c.len_utf8() > INLINE_CAPACITY {
+                let mut s = String::with_capacity(len + c.len_utf8() + chars.size_hint().0);
+                // SAFETY: Only whole characters were encoded into the buffer so far.
+                s.push_str(unsafe { std::str::from_utf8_unchecked(&bytes[..len]) });
+                s.push(c);
+                s.extend(chars);
+                return Self::from(s);
+            }
+            len += c.encode_utf8(&mut bytes[len..]).len();
+        }
+        Self(Repr::Inline {
+            len: len as u8,
+            bytes,
+        })
+    }
+}
+
+impl PartialEq for Str {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Str {}
+
+impl PartialEq<str> for Str {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Str {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for Str {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Str {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+/// Strings hash like `str`s, so that they can be looked up by string slices.
+impl Hash for Str {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for Str {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Str {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
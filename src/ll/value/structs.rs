@@ -1,6 +1,6 @@
 use std::cell::{Cell, UnsafeCell};
 
-use super::RawValue;
+use super::{RawValue, Tuple};
 use crate::ll::{bytecode::DispatchTable, error::LanguageErrorKind, gc::GcRaw};
 
 /// The innards of a struct.
@@ -27,16 +27,25 @@ impl Struct {
         }
     }
 
+    /// Creates a struct from its dispatch table and fields, as saved in a heap image.
+    pub(crate) fn from_parts(
+        dtable: GcRaw<DispatchTable>,
+        sealed: bool,
+        fields: Vec<RawValue>,
+    ) -> Self {
+        Self {
+            dtable: UnsafeCell::new(dtable),
+            sealed: Cell::new(sealed),
+            fields: UnsafeCell::new(fields),
+        }
+    }
+
     /// Creates a new instance of this struct type.
     pub(crate) unsafe fn new_instance(&self, field_count: usize) -> Self {
         Self {
             dtable: UnsafeCell::new(self.dtable().instance.unwrap_unchecked()),
             sealed: Cell::new(true),
-            fields: UnsafeCell::new(
-                std::iter::repeat(RawValue::from(()))
-                    .take(field_count)
-                    .collect(),
-            ),
+            fields: UnsafeCell::new(std::iter::repeat_n(RawValue::from(()), field_count).collect()),
         }
     }
 
@@ -63,12 +72,22 @@ impl Struct {
         Ok(())
     }
 
+    /// Returns whether the struct's dispatch table can no longer be changed.
+    pub(crate) fn is_sealed(&self) -> bool {
+        self.sealed.get()
+    }
+
+    /// Returns the number of fields in the struct.
+    pub(crate) unsafe fn field_count(&self) -> usize {
+        (*self.fields.get()).len()
+    }
+
     /// Returns the value of a field.
     ///
     /// # Safety
     /// This does not perform any borrow checks or bounds checks.
     pub(crate) unsafe fn get_field(&self, index: usize) -> RawValue {
-        *(*self.fields.get()).get_unchecked(index)
+        *(&*self.fields.get()).get_unchecked(index)
     }
 
     /// Sets the value of a field.
@@ -76,10 +95,27 @@ impl Struct {
     /// # Safety
     /// This does not perform any borrow checks or bounds checks.
     pub(crate) unsafe fn set_field(&self, index: usize, value: RawValue) {
-        *(*self.fields.get()).get_unchecked_mut(index) = value;
+        *(&mut *self.fields.get()).get_unchecked_mut(index) = value;
     }
 
     pub(crate) unsafe fn fields(&self) -> impl Iterator<Item = RawValue> + '_ {
         (*self.fields.get()).iter().copied()
     }
+
+    /// If the struct is an instance of an enum, returns the name of its variant and the values the
+    /// variant carries.
+    ///
+    /// # Safety
+    /// Like with [`Struct::dtable`], the lifetime of the returned references is not tracked.
+    pub unsafe fn enum_variant<'a>(&self) -> Option<(&'a str, &'a [RawValue])> {
+        if !self.dtable().is_enum() || self.field_count() < 2 {
+            return None;
+        }
+        // The fields are filled in by the variant's constructor, so they may still be `nil` if
+        // the instance is inspected before it returns.
+        let variant = self.get_field(0).ensure_raw_string().ok()?;
+        let values = self.get_field(1).get_raw_user_data()?;
+        let values = (**values.get()).as_any().downcast_ref::<Tuple>()?;
+        Some((variant.get().as_str(), &values.fields))
+    }
 }
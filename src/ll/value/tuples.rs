@@ -99,6 +99,16 @@ impl UserData for Tuple {
         Cow::Owned(format!("Tuple({})", self.fields.len()))
     }
 
+    fn heap_size(&self) -> usize {
+        self.fields.capacity() * std::mem::size_of::<RawValue>()
+    }
+
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        for &field in &self.fields {
+            visit(field);
+        }
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,21 +1,36 @@
 //! The virtual machine.
 
-use std::{collections::HashSet, fmt, ops::Deref, pin::Pin, ptr, rc::Rc};
+use std::{
+    borrow::Cow, cell::RefCell, cmp::Ordering, collections::HashSet, fmt, mem, ops::Deref,
+    pin::Pin, ptr, rc::Rc,
+};
 
 use super::bytecode::{FunctionIndex, GlobalIndex, ImplementedTraitIndex, Library, MethodIndex};
 use crate::ll::{
     bytecode::{
-        CaptureKind, Chunk, Control, DispatchTable, Environment, FunctionKind,
-        MethodParameterCount, MethodSignature, Opcode, PrototypeIndex, RecordTypeIndex, TraitIndex,
+        CaptureKind, Chunk, Control, DispatchTable, Environment, Function, FunctionKind,
+        FunctionParameterCount, MethodParameterCount, MethodSignature, Opcode, Opr24, Prototype,
+        PrototypeIndex, RecordTypeIndex, TraitIndex, Visibility,
+    },
+    error::{
+        closest_match, AssertionFailure, CallInfo, FileLocation, LanguageError, LanguageErrorKind,
+        Location, RenderedSignature, StackTraceEntry,
     },
-    error::{LanguageError, LanguageErrorKind, Location, RenderedSignature, StackTraceEntry},
     gc::{GcRaw, Memory},
     value::{
-        create_trait, Closure, Dict, List, RawValue, Record, Struct, Trait, Tuple, Upvalue,
+        create_trait, Closure, Dict, List, RawValue, Record, Str, Struct, Trait, Tuple, Upvalue,
         UserData, ValueKind,
     },
 };
 
+mod coroutine;
+mod sorting;
+#[cfg(all(feature = "threaded-dispatch", not(feature = "forbid-unsafe")))]
+mod threaded;
+
+pub use self::coroutine::Coroutine;
+use self::sorting::{Sort, SortKind};
+
 /// Storage for global variables.
 #[derive(Debug)]
 pub struct Globals {
@@ -46,21 +61,15 @@ impl Globals {
         self.values.get(slot).cloned().unwrap_or(().into())
     }
 
-    /// Returns the global in the given slot without performing bounds checks.
-    ///
-    /// # Safety
-    ///
-    /// This is only safe to use by the VM. The code generator ensures that all globals are set
-    /// before use.
-    unsafe fn get_unchecked(&self, slot: GlobalIndex) -> RawValue {
-        let slot = slot.to_usize();
-        *self.values.get_unchecked(slot)
-    }
-
     /// Returns an iterator over all globals.
     pub(crate) fn iter(&self) -> impl Iterator<Item = RawValue> + '_ {
         self.values.iter().copied()
     }
+
+    /// Returns an iterator over mutable references to all globals.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut RawValue> + '_ {
+        self.values.iter_mut()
+    }
 }
 
 impl Default for Globals {
@@ -76,8 +85,168 @@ struct ReturnPoint {
     closure: Option<GcRaw<Closure>>,
     pc: usize,
     stack_bottom: usize,
+    /// If the function was called to overload a comparison operator, turns the ordering it
+    /// returns into the operator's result.
+    comparison: Option<fn(Ordering) -> bool>,
+    /// If the function was called by `sort_by` or `sort_by_key`, the sort to resume with the
+    /// function's result.
+    sort: Option<Box<Sort>>,
+}
+
+/// An error handler installed by a `try` or `defer` expression.
+#[derive(Debug)]
+struct Handler {
+    /// The length of the call stack when the handler was installed.
+    call_depth: usize,
+    /// The height of the value stack when the handler was installed.
+    stack_height: usize,
+    /// The number of entered breakable blocks when the handler was installed.
+    breakable_blocks: usize,
+    /// The amount of deferred code that was running when the handler was installed.
+    running_deferred: usize,
+    /// Where the `catch` block or deferred code begins.
+    catch_pc: usize,
+    /// Whether the handler runs deferred code, after which the error continues to propagate.
+    deferred: bool,
+}
+
+/// What to do once deferred code finishes running.
+#[derive(Debug)]
+enum DeferredExit {
+    /// Continue execution at the given program counter.
+    Continue(usize),
+    /// Continue propagating the error that caused the deferred code to run, along with the value
+    /// it raised.
+    Propagate(LanguageError, Option<RawValue>),
+}
+
+/// A hook called by fibers as they execute code, used for implementing debuggers.
+///
+/// Hooks are set through [`Library::debug_hook`].
+pub trait DebugHook: fmt::Debug {
+    /// Called before the fiber executes the first instruction on a new line of source code. This
+    /// also happens when a function is entered, and when a loop jumps back to a line that was
+    /// already executed.
+    fn on_line(&mut self, fiber: &Fiber, env: &Environment, globals: &Globals);
+
+    /// Called when the fiber calls a function. For bytecode functions this happens after the
+    /// function's frame is entered; for foreign functions, right before the function is called.
+    fn on_call(
+        &mut self,
+        _fiber: &Fiber,
+        _env: &Environment,
+        _globals: &Globals,
+        _function: &Function,
+    ) {
+    }
+
+    /// Called when a function returns, before its frame is left. Bytecode functions unwound by an
+    /// error do not get this called.
+    fn on_return(&mut self, _fiber: &Fiber, _env: &Environment, _globals: &Globals) {}
+
+    /// Called before the fiber executes each instruction, after [`on_line`][Self::on_line] if the
+    /// instruction starts a new line.
+    fn on_instruction(&mut self, _fiber: &Fiber, _env: &Environment, _globals: &Globals) {}
+
+    /// Called after the other callbacks that run before an instruction. If this returns `true`,
+    /// the fiber [pauses][Fiber::paused] before executing the instruction.
+    fn take_pause_request(&mut self) -> bool {
+        false
+    }
+}
+
+/// Intercepts calls to traced foreign functions, to record their results or to replay results
+/// recorded earlier.
+pub trait TraceHook: fmt::Debug {
+    /// Called in place of a traced foreign function. `call` calls the actual function; it's up to
+    /// the hook whether it's called, and what the function call returns.
+    fn on_foreign_call(
+        &mut self,
+        function: &Function,
+        library: &Library,
+        gc: &mut Memory,
+        call: &dyn Fn(&Library, &mut Memory) -> Result<RawValue, LanguageErrorKind>,
+    ) -> Result<RawValue, LanguageErrorKind>;
 }
 
+/// Takes over calls to methods, which lets tests replace methods with stubs.
+///
+/// Interceptors are set through [`Library::call_interceptor`].
+pub trait CallInterceptor: fmt::Debug {
+    /// Called before a method is looked up in the receiver's dispatch table. `arguments` begins
+    /// with the receiver. Returning `Some` skips the method, and the fiber continues as if the
+    /// method returned the given result.
+    fn intercept(
+        &mut self,
+        method_index: MethodIndex,
+        dtable: &DispatchTable,
+        arguments: &[RawValue],
+        gc: &mut Memory,
+    ) -> Option<Result<RawValue, LanguageErrorKind>>;
+}
+
+/// The position at which the debug hook was last run.
+#[derive(Debug, Clone, Copy)]
+struct DebugPosition {
+    call_depth: usize,
+    chunk: *const Chunk,
+    pc: usize,
+}
+
+/// The value stack and call stack of a fiber, which another fiber can reuse once the fiber is done
+/// with them.
+#[derive(Debug, Default)]
+pub struct Stacks {
+    values: Vec<RawValue>,
+    frames: Vec<ReturnPoint>,
+}
+
+impl Stacks {
+    /// Returns the value stack. Fibers start by calling the function at the bottom of the stack
+    /// with the values above it as arguments, unless their chunk does something else.
+    pub fn values_mut(&mut self) -> &mut Vec<RawValue> {
+        &mut self.values
+    }
+}
+
+/// Stacks of fibers that are done running, kept around so that new fibers don't have to allocate
+/// their own and grow them call by call.
+#[derive(Debug, Default)]
+pub struct StackPool {
+    spare: Vec<Stacks>,
+}
+
+impl StackPool {
+    /// The number of values a new stack has room for before it has to grow.
+    const INITIAL_VALUES: usize = 256;
+    /// The number of nested calls a new stack has room for before it has to grow.
+    const INITIAL_FRAMES: usize = 32;
+    /// Fibers started by the host mostly run one at a time, so only a few stacks are worth
+    /// keeping.
+    const MAX_SPARE: usize = 4;
+
+    /// Returns an empty pair of stacks, reusing spare ones if there are any.
+    pub fn take(&mut self) -> Stacks {
+        self.spare.pop().unwrap_or_else(|| Stacks {
+            values: Vec::with_capacity(Self::INITIAL_VALUES),
+            frames: Vec::with_capacity(Self::INITIAL_FRAMES),
+        })
+    }
+
+    /// Keeps the stacks for reuse, keeping the capacity they grew to.
+    pub fn recycle(&mut self, mut stacks: Stacks) {
+        if self.spare.len() < Self::MAX_SPARE {
+            stacks.values.clear();
+            stacks.frames.clear();
+            self.spare.push(stacks);
+        }
+    }
+}
+
+/// An upvalue pointing to a local variable that's still on the stack, along with the variable's
+/// stack slot.
+type OpenUpvalue = (u32, Pin<Rc<Upvalue>>);
+
 /// The virtual machine state.
 pub struct Fiber {
     pc: usize,
@@ -86,37 +255,265 @@ pub struct Fiber {
 
     stack: Vec<RawValue>,
     stack_bottom: usize,
-    open_upvalues: Vec<(u32, Pin<Rc<Upvalue>>)>,
+    open_upvalues: Vec<OpenUpvalue>,
     call_stack: Vec<ReturnPoint>,
     breakable_block_stack: Vec<usize>,
-
+    handlers: Vec<Handler>,
+    /// The value passed to `raise`, kept around until the error is caught. If the error isn't
+    /// caught, the value stays around so that it can be raised again in the fiber that resumed
+    /// this one.
+    raised: Option<RawValue>,
+    /// What to do after each deferred code that's currently running, innermost last.
+    running_deferred: Vec<DeferredExit>,
+    last_debug_position: Option<DebugPosition>,
+
+    /// Set once the fiber's chunk has its storage allocated, after which resuming the fiber
+    /// continues where it left off.
+    started: bool,
     halted: bool,
+    /// Set when the fiber halted because of an error.
+    errored: bool,
+    /// Set when a foreign function couldn't complete and the fiber suspended to retry it later.
+    blocked: bool,
+    /// Set when the fiber blocked while retrying the call it was previously blocked on.
+    stalled: bool,
+    /// The number of instructions the fiber can still execute before it has to suspend, or
+    /// `None` if it can run for as long as it needs.
+    budget: Option<usize>,
+    /// Set when the fiber suspended because its budget ran out.
+    out_of_budget: bool,
+    /// Set when the fiber suspended because the library's [fuel][Library::fuel] ran out.
+    out_of_fuel: bool,
+    /// The value passed to `yield`, until the fiber suspends at the end of the instruction that
+    /// called it.
+    yielding: Option<RawValue>,
+    /// Set when the fiber suspended because it yielded a value.
+    yielded: bool,
+    /// Set when the fiber suspended because its debug hook asked it to pause.
+    paused: bool,
 }
 
 impl Fiber {
     /// Creates a new VM.
     pub fn new(chunk: Rc<Chunk>, stack: Vec<RawValue>) -> Self {
+        Self::with_stacks(
+            chunk,
+            Stacks {
+                values: stack,
+                frames: Vec::new(),
+            },
+        )
+    }
+
+    /// Creates a new VM that uses the given stacks, such as ones taken from a [`StackPool`].
+    pub fn with_stacks(chunk: Rc<Chunk>, stacks: Stacks) -> Self {
         Self {
             pc: 0,
             chunk,
             closure: None,
-            stack,
+            stack: stacks.values,
             stack_bottom: 0,
             open_upvalues: Vec::new(),
-            call_stack: Vec::new(),
+            call_stack: stacks.frames,
             breakable_block_stack: Vec::new(),
+            handlers: Vec::new(),
+            raised: None,
+            running_deferred: Vec::new(),
+            last_debug_position: None,
+            started: false,
             halted: false,
+            errored: false,
+            blocked: false,
+            stalled: false,
+            budget: None,
+            out_of_budget: false,
+            out_of_fuel: false,
+            yielding: None,
+            yielded: false,
+            paused: false,
+        }
+    }
+
+    /// Takes the fiber's stacks away, so that they can be reused by another fiber. The fiber must
+    /// not be resumed afterwards.
+    ///
+    /// Closures may still refer to the fiber's locals, so those are moved off the stack first.
+    pub fn take_stacks(&mut self, gc: &mut Memory) -> Stacks {
+        for (_, upvalue) in self.open_upvalues.drain(..) {
+            unsafe { upvalue.close() };
+            gc.shade(unsafe { upvalue.get() });
+        }
+        Stacks {
+            values: mem::take(&mut self.stack),
+            frames: mem::take(&mut self.call_stack),
         }
     }
 
+    /// Returns the value passed to the `raise` that made the fiber fail, if it failed that way.
+    pub fn raised(&self) -> Option<RawValue> {
+        self.raised
+    }
+
     /// Returns whether the fiber has halted execution.
     pub fn halted(&self) -> bool {
         self.halted
     }
 
+    /// Returns whether the fiber halted because of an error.
+    pub fn errored(&self) -> bool {
+        self.errored
+    }
+
+    /// Returns whether the fiber is suspended, waiting for an operation that couldn't complete
+    /// (such as receiving from an empty channel.) The operation is retried once the fiber is
+    /// resumed.
+    pub fn blocked(&self) -> bool {
+        self.blocked
+    }
+
+    /// Returns whether the fiber blocked immediately after being resumed, on the same operation
+    /// it was blocked on before. Such a fiber hasn't done anything since it was last resumed, so
+    /// if all fibers are stalled, none of them will ever be able to continue.
+    pub fn stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Sets the number of instructions the fiber may execute before it suspends. Once the budget
+    /// runs out, [`interpret`][Self::interpret] returns early with `nil`, and continues where it
+    /// left off when called again with a new budget. `None` lets the fiber run indefinitely.
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// Returns the number of instructions the fiber can still execute, or `None` if it isn't
+    /// limited.
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Returns whether the fiber suspended because it ran out of its [budget][Self::set_budget]
+    /// the last time it was interpreted.
+    pub fn out_of_budget(&self) -> bool {
+        self.out_of_budget
+    }
+
+    /// Returns whether the fiber suspended because it ran out of [fuel][Library::fuel] the last
+    /// time it was interpreted. Like with budgets, the fiber continues where it left off once it's
+    /// interpreted again with more fuel.
+    pub fn out_of_fuel(&self) -> bool {
+        self.out_of_fuel
+    }
+
+    /// Returns whether the fiber suspended because it called `yield` the last time it was
+    /// interpreted. The value passed to `yield` is what [`interpret`][Self::interpret] returned.
+    pub fn yielded(&self) -> bool {
+        self.yielded
+    }
+
+    /// Returns whether the fiber suspended because the library's [debug hook][Library::debug_hook]
+    /// asked it to pause the last time it was interpreted. The fiber continues with the
+    /// instruction it paused before once it's interpreted again, without running the hook for
+    /// that instruction a second time.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Sets what the `yield` the fiber is suspended on evaluates to once the fiber is resumed.
+    /// Does nothing if the fiber didn't [yield][Self::yielded].
+    pub fn set_yield_result(&mut self, value: RawValue) {
+        if self.yielded {
+            self.set_stack_top(value);
+        }
+    }
+
+    /// Returns the number of function calls the fiber is currently nested in.
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    /// Returns the fiber's call frames, starting with the innermost one (the function that's
+    /// currently executing.)
+    pub fn call_frames(&self) -> Vec<CallFrame<'_>> {
+        let current = CallFrame {
+            chunk: &self.chunk,
+            closure: self.closure,
+            pc: self.pc,
+            stack: &self.stack[self.stack_bottom.min(self.stack.len())..],
+            #[cfg(feature = "forbid-unsafe")]
+            captured: (self.stack_bottom, &self.open_upvalues),
+        };
+        let callers = self
+            .call_stack
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(i, return_point)| {
+                let chunk = return_point.chunk.as_ref()?;
+                // The caller's frame ends where its callee's frame begins.
+                let top = self
+                    .call_stack
+                    .get(i + 1)
+                    .map(|callee| callee.stack_bottom)
+                    .unwrap_or(self.stack_bottom);
+                Some(CallFrame {
+                    chunk,
+                    closure: return_point.closure,
+                    // The return point points to the instruction after the call.
+                    pc: return_point.pc - Opcode::INSTRUCTION_SIZE,
+                    stack: &self.stack
+                        [return_point.stack_bottom.min(top)..top.min(self.stack.len())],
+                    #[cfg(feature = "forbid-unsafe")]
+                    captured: (return_point.stack_bottom, &self.open_upvalues),
+                })
+            });
+        std::iter::once(current).chain(callers).collect()
+    }
+
+    /// Runs the debug hook if execution has moved onto a new line since the last time it ran.
+    fn run_debug_hook(
+        &mut self,
+        hook: &RefCell<dyn DebugHook>,
+        env: &Environment,
+        globals: &Globals,
+    ) {
+        let location = self.chunk.location(self.pc);
+        if location.is_uninit() {
+            return;
+        }
+        let position = DebugPosition {
+            call_depth: self.call_stack.len(),
+            chunk: Rc::as_ptr(&self.chunk),
+            pc: self.pc,
+        };
+        let is_new_line = match self.last_debug_position {
+            // After returning from a function, the last instruction executed in this frame was
+            // the call.
+            Some(last) if last.call_depth > position.call_depth => {
+                let call = self.chunk.location(self.pc - Opcode::INSTRUCTION_SIZE);
+                call.line != location.line
+            }
+            Some(last)
+                if last.call_depth == position.call_depth && last.chunk == position.chunk =>
+            {
+                // A blocked call being retried stays on the same instruction, which doesn't count
+                // as jumping back.
+                self.chunk.location(last.pc).line != location.line || position.pc < last.pc
+            }
+            // Entering a function always counts as moving onto a new line.
+            _ => true,
+        };
+        self.last_debug_position = Some(position);
+        let mut hook = hook.borrow_mut();
+        if is_new_line {
+            hook.on_line(self, env, globals);
+        }
+        hook.on_instruction(self, env, globals);
+    }
+
     /// Halts the VM and produces an error.
     fn error(&mut self, env: &Environment, kind: LanguageErrorKind) -> LanguageError {
         self.halted = true;
+        self.errored = true;
         LanguageError::Runtime {
             kind,
             call_stack: self
@@ -148,12 +545,46 @@ impl Fiber {
                     })
                 })
                 .collect(),
+            snippet: None,
+        }
+    }
+
+    /// Makes room for at least `additional` more values on the stack.
+    fn reserve_stack(&mut self, additional: usize) {
+        if self.stack.capacity() - self.stack.len() < additional {
+            self.grow_stack(additional);
+        }
+    }
+
+    #[cold]
+    fn grow_stack(&mut self, additional: usize) {
+        self.stack.reserve(additional);
+        // Open upvalues point into the stack, so they have to follow it into its new allocation.
+        #[cfg(not(feature = "forbid-unsafe"))]
+        for (slot, upvalue) in &self.open_upvalues {
+            assert!((*slot as usize) < self.stack.len());
+            // SAFETY: The slot is within the stack, whose storage is never null.
+            let var =
+                unsafe { ptr::NonNull::new_unchecked(self.stack.as_mut_ptr().add(*slot as usize)) };
+            unsafe { upvalue.relocate(var) };
         }
     }
 
     /// Pushes a value onto the stack.
     fn push(&mut self, value: RawValue) {
-        self.stack.push(value);
+        self.reserve_stack(1);
+        #[cfg(any(debug_assertions, feature = "forbid-unsafe"))]
+        {
+            self.stack.push(value);
+        }
+        // The room for the value was reserved above, so `Vec::push` checking the capacity again
+        // would be redundant.
+        #[cfg(not(any(debug_assertions, feature = "forbid-unsafe")))]
+        unsafe {
+            let len = self.stack.len();
+            self.stack.as_mut_ptr().add(len).write(value);
+            self.stack.set_len(len + 1);
+        }
         #[cfg(feature = "trace-vm-stack-ops")]
         {
             println!("push | {:?}", &self.stack);
@@ -162,9 +593,9 @@ impl Fiber {
 
     /// Pops a value off the stack.
     fn pop(&mut self) -> RawValue {
-        #[cfg(debug_assertions)]
+        #[cfg(any(debug_assertions, feature = "forbid-unsafe"))]
         let value = { self.stack.pop().unwrap() };
-        #[cfg(not(debug_assertions))]
+        #[cfg(not(any(debug_assertions, feature = "forbid-unsafe")))]
         let value = unsafe { self.stack.pop().unwrap_unchecked() };
         #[cfg(feature = "trace-vm-stack-ops")]
         {
@@ -175,36 +606,58 @@ impl Fiber {
 
     /// Returns a reference to the value at the top of the stack.
     fn stack_top(&self) -> RawValue {
-        #[cfg(debug_assertions)]
+        #[cfg(any(debug_assertions, feature = "forbid-unsafe"))]
         {
             self.stack.last().copied().unwrap()
         }
-        #[cfg(not(debug_assertions))]
+        #[cfg(not(any(debug_assertions, feature = "forbid-unsafe")))]
         unsafe {
             *self.stack.get_unchecked(self.stack.len() - 1)
         }
     }
 
-    /// Returns a mutable reference to the value at the top of the stack.
-    fn stack_top_mut(&mut self) -> &mut RawValue {
-        #[cfg(debug_assertions)]
+    /// Replaces the value at the top of the stack.
+    fn set_stack_top(&mut self, value: RawValue) {
+        let top = self.stack.len() - 1;
+        unsafe { self.write_stack(top, value) }
+    }
+
+    /// Swaps the two values at the top of the stack.
+    fn swap_stack_top(&mut self) {
+        let len = self.stack.len();
+        let (below, top) = (self.nth_from_top(2), self.nth_from_top(1));
+        unsafe {
+            self.write_stack(len - 2, top);
+            self.write_stack(len - 1, below);
+        }
+    }
+
+    /// Writes a value to the given slot of the stack.
+    ///
+    /// Open upvalues point into the stack, so it's written to through a pointer to its buffer
+    /// rather than through a mutable reference to it, which would invalidate them.
+    ///
+    /// # Safety
+    /// The slot must be within the stack's bounds.
+    unsafe fn write_stack(&mut self, slot: usize, value: RawValue) {
+        #[cfg(feature = "forbid-unsafe")]
         {
-            self.stack.last_mut().unwrap()
+            self.stack[slot] = value;
         }
-        #[cfg(not(debug_assertions))]
-        unsafe {
-            let last = self.stack.len() - 1;
-            self.stack.get_unchecked_mut(last)
+        #[cfg(not(feature = "forbid-unsafe"))]
+        {
+            debug_assert!(slot < self.stack.len(), "stack slot out of bounds");
+            self.stack.as_mut_ptr().add(slot).write(value);
         }
     }
 
     /// Returns a reference to the `n`th value counted from the top of the stack.
     fn nth_from_top(&self, n: usize) -> RawValue {
-        #[cfg(debug_assertions)]
+        #[cfg(any(debug_assertions, feature = "forbid-unsafe"))]
         {
             self.stack[self.stack.len() - n]
         }
-        #[cfg(not(debug_assertions))]
+        #[cfg(not(any(debug_assertions, feature = "forbid-unsafe")))]
         unsafe {
             let i = self.stack.len() - n;
             *self.stack.get_unchecked(i)
@@ -218,16 +671,17 @@ impl Fiber {
             closure: self.closure,
             pc: self.pc,
             stack_bottom: self.stack_bottom,
+            comparison: None,
+            sort: None,
         });
     }
 
     /// Restores previous VM state from the return point stack.
     fn restore_return_point(&mut self) {
         let return_point = self.call_stack.pop().unwrap();
-        // Remove unnecessary values from the stack (eg. arguments).
-        while self.stack.len() > self.stack_bottom {
-            self.stack.pop().unwrap();
-        }
+        // Remove unnecessary values from the stack (eg. arguments). The stack keeps its capacity,
+        // so the next frame reuses the memory.
+        self.stack.truncate(self.stack_bottom);
         // Restore state.
         // SAFETY: Assume the matching chunk is a bytecode chunk.
         self.chunk = unsafe { return_point.chunk.unwrap_unchecked() };
@@ -246,19 +700,67 @@ impl Fiber {
         {
             Pin::clone(upvalue)
         } else {
-            let stack_ptr = &mut self.stack[stack_slot as usize] as *mut _;
-            // SAFETY: Vec storage is never null.
-            let stack_ptr = unsafe { ptr::NonNull::new_unchecked(stack_ptr) };
-            let upvalue = Upvalue::new(stack_ptr);
+            #[cfg(not(feature = "forbid-unsafe"))]
+            let upvalue = {
+                assert!((stack_slot as usize) < self.stack.len());
+                // The pointer is derived from the stack's buffer rather than from a reference to the
+                // variable, so that it is not invalidated by later writes to the stack.
+                // SAFETY: The slot is within the stack, whose storage is never null.
+                let stack_ptr = unsafe {
+                    ptr::NonNull::new_unchecked(self.stack.as_mut_ptr().add(stack_slot as usize))
+                };
+                Upvalue::new(stack_ptr)
+            };
+            #[cfg(feature = "forbid-unsafe")]
+            let upvalue = Upvalue::new(mem::take(&mut self.stack[stack_slot as usize]));
             self.open_upvalues.push((stack_slot, Pin::clone(&upvalue)));
             upvalue
         }
     }
 
+    /// Returns the value of the local variable in the given slot of the current frame.
+    fn local(&self, slot: usize) -> RawValue {
+        let slot = self.stack_bottom + slot;
+        #[cfg(feature = "forbid-unsafe")]
+        if let Some(upvalue) = self.captured_local(slot) {
+            return unsafe { upvalue.get() };
+        }
+        self.stack[slot]
+    }
+
+    /// Sets the local variable in the given slot of the current frame.
+    fn set_local(&mut self, slot: usize, value: RawValue) {
+        let slot = self.stack_bottom + slot;
+        #[cfg(feature = "forbid-unsafe")]
+        if let Some(upvalue) = self.captured_local(slot) {
+            unsafe { upvalue.set(value) };
+            return;
+        }
+        assert!(slot < self.stack.len());
+        unsafe { self.write_stack(slot, value) }
+    }
+
+    /// Returns the upvalue holding the local variable in the given stack slot, if it was captured
+    /// by a closure and is still in scope. The variable is then stored in the upvalue rather than
+    /// on the stack.
+    #[cfg(feature = "forbid-unsafe")]
+    fn captured_local(&self, stack_slot: usize) -> Option<&Upvalue> {
+        captured_local(&self.open_upvalues, stack_slot)
+    }
+
     /// Allocates `n` storage slots for local variables.
     fn allocate_chunk_storage_slots(&mut self, n: usize) {
-        self.stack
-            .extend(std::iter::repeat_with(|| RawValue::from(())).take(n));
+        self.reserve_stack(n);
+        self.stack.resize(self.stack.len() + n, RawValue::from(()));
+    }
+
+    /// Returns whether the currently executing function may call private methods declared in the
+    /// given `impl` block.
+    fn can_call_private(&self, env: &Environment, impl_block: Option<PrototypeIndex>) -> bool {
+        let caller = self
+            .closure
+            .map(|closure| unsafe { env.get_function_unchecked(closure.get().function_id) });
+        caller.is_some_and(|caller| caller.impl_block == impl_block)
     }
 
     /// Calls a function. For bytecode functions this saves the stack and begins executing the
@@ -270,45 +772,367 @@ impl Fiber {
         globals: &mut Globals,
         gc: &mut Memory,
         closure: GcRaw<Closure>,
-        argument_count: usize,
+        mut argument_count: usize,
     ) -> Result<(), LanguageError> {
         let function = unsafe { env.get_function_unchecked(closure.get().function_id) };
         match &function.kind {
             FunctionKind::Bytecode { chunk, .. } => {
+                // Foreign functions are lenient about the number of arguments they receive
+                // (missing arguments are treated as `nil`,) but bytecode functions index their
+                // parameters directly on the stack, so the count must match exactly.
+                // Subtract 1 to omit the receiver (`self` or the function itself.)
+                match function.parameter_count {
+                    FunctionParameterCount::Fixed(expected) => {
+                        if argument_count - 1 != usize::from(expected) {
+                            let call = Box::new(self.call_info(function, argument_count));
+                            return Err(self.error_outside_function_call(
+                                None,
+                                env,
+                                LanguageErrorKind::ArgumentCount { expected, call },
+                            ));
+                        }
+                    }
+                    FunctionParameterCount::AtLeast(expected) => {
+                        if argument_count - 1 < usize::from(expected) {
+                            let call = Box::new(self.call_info(function, argument_count));
+                            return Err(self.error_outside_function_call(
+                                None,
+                                env,
+                                LanguageErrorKind::TooFewArguments { expected, call },
+                            ));
+                        }
+                        // The surplus arguments are collected into a list, which then sits in the
+                        // rest parameter's slot.
+                        unsafe { gc.auto_collect(self.roots(globals), library) }
+                            .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+                        let surplus = argument_count - 1 - usize::from(expected);
+                        let rest = self.stack.drain(self.stack.len() - surplus..).collect();
+                        let rest: Box<dyn UserData> = Box::new(List::new(rest));
+                        let rest = RawValue::from(gc.allocate(rest));
+                        library
+                            .limits
+                            .check(rest)
+                            .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+                        self.push(rest);
+                        argument_count = usize::from(expected) + 2;
+                    }
+                    FunctionParameterCount::Varargs => (),
+                }
+                if function.visibility == Visibility::Private
+                    && !self.can_call_private(env, function.impl_block)
+                {
+                    return Err(self.error_outside_function_call(
+                        None,
+                        env,
+                        LanguageErrorKind::PrivateMethod(Rc::clone(unsafe { &closure.get().name })),
+                    ));
+                }
+                if library.outer_call_depth() + self.call_depth() >= library.max_call_depth {
+                    return Err(self.error_outside_function_call(
+                        None,
+                        env,
+                        LanguageErrorKind::StackOverflow,
+                    ));
+                }
                 self.save_return_point();
                 self.chunk = Rc::clone(chunk);
                 self.closure = Some(closure);
                 self.pc = 0;
                 self.stack_bottom = self.stack.len() - argument_count;
                 self.allocate_chunk_storage_slots(chunk.preallocate_stack_slots as usize);
+                if let Some(hook) = &library.debug_hook {
+                    hook.borrow_mut().on_call(self, env, globals, function);
+                }
             }
-            FunctionKind::Foreign(f) => {
+            FunctionKind::Foreign(_) | FunctionKind::Contextual(_) => {
+                if gc.stress {
+                    // Foreign functions may allocate, so in stress mode they get a collection
+                    // just like allocating opcodes do.
+                    unsafe { gc.collect(self.roots(globals), library) };
+                }
+                if let Some(hook) = &library.debug_hook {
+                    hook.borrow_mut().on_call(self, env, globals, function);
+                }
                 let arguments = unsafe {
                     self.stack
                         .get_unchecked(self.stack.len() - argument_count..)
                 };
-                let result = match f(library, gc, arguments) {
+                // Foreign functions modify the objects they're passed without any barriers, and
+                // contextual functions may run long enough for the GC to mark those objects in the
+                // meantime, so the arguments are rescanned both before and after the call.
+                for &argument in arguments {
+                    unsafe { gc.rescan_later(argument) }
+                }
+                let result = match &function.kind {
+                    FunctionKind::Foreign(f) => match &library.trace_hook {
+                        Some(hook) if function.traced => hook.borrow_mut().on_foreign_call(
+                            function,
+                            library,
+                            gc,
+                            &|library, gc| f(library, gc, arguments),
+                        ),
+                        _ => f(library, gc, arguments),
+                    },
+                    FunctionKind::Contextual(f) => {
+                        // The function may run other fibers, which don't know about this one's
+                        // stack.
+                        match library.push_outer_fiber(self.call_depth()) {
+                            Ok(depth_marker) => {
+                                let marker = gc.push_suspended_roots(self.stack_roots());
+                                let result = match &library.trace_hook {
+                                    Some(hook) if function.traced => {
+                                        let globals = RefCell::new(&mut *globals);
+                                        hook.borrow_mut().on_foreign_call(
+                                            function,
+                                            library,
+                                            gc,
+                                            &|library, gc| {
+                                                let globals = &mut globals.borrow_mut();
+                                                f(env, library, globals, gc, arguments)
+                                            },
+                                        )
+                                    }
+                                    _ => f(env, library, globals, gc, arguments),
+                                };
+                                gc.pop_suspended_roots(marker);
+                                library.pop_outer_fiber(depth_marker);
+                                result
+                            }
+                            Err(kind) => Err(kind),
+                        }
+                    }
+                    _ => unreachable!(),
+                };
+                if let Some(hook) = &library.debug_hook {
+                    hook.borrow_mut().on_return(self, env, globals);
+                }
+                let retrying = self.blocked;
+                self.blocked = false;
+                self.stalled = false;
+                let result = match result {
                     Ok(value) => value,
-                    Err(kind) => {
+                    Err(LanguageErrorKind::WouldBlock) => {
+                        // Leave the arguments on the stack and step back onto the call
+                        // instruction, such that the call is retried when the fiber is resumed.
+                        self.pc -= Opcode::INSTRUCTION_SIZE;
+                        self.blocked = true;
+                        self.stalled = retrying;
+                        return Ok(());
+                    }
+                    Err(LanguageErrorKind::FuelExhausted) if library.fuel.get() == Some(0) => {
+                        // The function ran out of fuel while calling back into the VM. It's called
+                        // again once the fiber is resumed with more fuel.
+                        self.pc -= Opcode::INSTRUCTION_SIZE;
+                        self.out_of_fuel = true;
+                        return Ok(());
+                    }
+                    Err(LanguageErrorKind::Nested(error)) => {
+                        return Err(self.nested_error(closure, env, *error));
+                    }
+                    Err(mut kind) => {
+                        if let LanguageErrorKind::ArgumentTypeMismatch(mismatch) = &mut kind {
+                            if mismatch.call.is_none() {
+                                mismatch.call = Some(self.call_info(function, argument_count));
+                            }
+                        }
                         return Err(self.error_outside_function_call(Some(closure), env, kind));
                     }
                 };
+                // Foreign functions are what grows containers and creates new strings, so this is
+                // where the limits are enforced. The receiver is checked too, as methods such as
+                // `push` modify it in place.
+                if let Err(kind) = library.limits.check_call(arguments, result) {
+                    return Err(self.error_outside_function_call(Some(closure), env, kind));
+                }
+                for &argument in arguments {
+                    unsafe { gc.rescan_later(argument) }
+                }
+                let receiver = arguments[0];
                 for _ in 0..argument_count {
                     self.pop();
                 }
                 self.push(result);
+                // For the same reason, this is where the memory limit is enforced. The result
+                // must be on the stack by now, as enforcing the limit may perform a collection.
+                unsafe {
+                    gc.remeasure(receiver);
+                    gc.remeasure(result);
+                }
+                if let Err(kind) = unsafe { gc.enforce_limit(self.roots(globals), library) } {
+                    return Err(self.error_outside_function_call(Some(closure), env, kind));
+                }
             }
             &FunctionKind::Control(ctl) => {
-                self.call_control(env, globals, gc, ctl, argument_count)?;
+                self.call_control(env, library, globals, gc, ctl, argument_count)?;
             }
         }
         Ok(())
     }
 
+    /// Calls the method overloading an operator, if the receiver has one. The operand packs the
+    /// method index and argument count like in `CallMethod`. Numbers never overload operators, and
+    /// neither do instructions emitted without an operand, such as the `nil` checks in patterns.
+    ///
+    /// Returns whether the method was called; if it wasn't, the stack is left untouched.
+    fn call_operator(
+        &mut self,
+        env: &Environment,
+        library: &Library,
+        globals: &mut Globals,
+        gc: &mut Memory,
+        operand: Opr24,
+    ) -> Result<bool, LanguageError> {
+        let (method_index, argument_count): (u16, u8) = operand.unpack();
+        if argument_count == 0 {
+            return Ok(false);
+        }
+        let receiver = self.nth_from_top(argument_count as usize);
+        if receiver.kind() == ValueKind::Number {
+            return Ok(false);
+        }
+        let dtable = Self::get_dispatch_table(receiver, library);
+        if let Some(closure) = dtable.get_method(MethodIndex::from_u16(method_index)) {
+            self.enter_function(env, library, globals, gc, closure, argument_count as usize)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Replaces the list at the top of the stack with its elements, to be passed as arguments to a
+    /// function. Returns the number of elements.
+    fn spread_arguments(&mut self) -> Result<usize, LanguageErrorKind> {
+        let list = self.pop();
+        let Some(list) = user_data_of(list).and_then(|u| u.as_any().downcast_ref::<List>()) else {
+            return Err(LanguageErrorKind::TypeError {
+                expected: "List".into(),
+                got: list.type_name(),
+            });
+        };
+        let elements = unsafe { list.as_slice() };
+        self.reserve_stack(elements.len());
+        self.stack.extend_from_slice(elements);
+        Ok(elements.len())
+    }
+
+    /// Reads `receiver[key]` if the receiver is a list or a dict, which are indexed without
+    /// calling a method. Returns `None` for all other values.
+    fn index_builtin(
+        receiver: RawValue,
+        key: RawValue,
+    ) -> Result<Option<RawValue>, LanguageErrorKind> {
+        if let Some(user_data) = receiver.get_raw_user_data() {
+            let user_data = unsafe { user_data.get() }.as_any();
+            if let Some(list) = user_data.downcast_ref::<List>() {
+                return unsafe { list.index(key) }.map(Some);
+            } else if let Some(dict) = user_data.downcast_ref::<Dict>() {
+                return Ok(Some(dict.get(key).unwrap_or_default()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Performs `receiver[key] = value` if the receiver is a list or a dict, returning whether
+    /// the assignment was done.
+    fn index_assign_builtin(
+        receiver: RawValue,
+        key: RawValue,
+        value: RawValue,
+    ) -> Result<bool, LanguageErrorKind> {
+        if let Some(user_data) = receiver.get_raw_user_data() {
+            let user_data = unsafe { user_data.get() }.as_any();
+            if let Some(list) = user_data.downcast_ref::<List>() {
+                unsafe { list.set_index(key, value)? };
+                return Ok(true);
+            } else if let Some(dict) = user_data.downcast_ref::<Dict>() {
+                dict.insert(key, value);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Compares the two values on the top of the stack by calling the `cmp` method of either one
+    /// of them. `test` determines the result of the comparison from the ordering returned by the
+    /// left value's method, and `flipped_test` from the ordering returned by the right value's.
+    ///
+    /// Returns whether a method was called; if it wasn't, the stack is left untouched.
+    #[allow(clippy::too_many_arguments)]
+    fn call_comparison(
+        &mut self,
+        env: &Environment,
+        library: &Library,
+        globals: &mut Globals,
+        gc: &mut Memory,
+        operand: Opr24,
+        test: fn(Ordering) -> bool,
+        flipped_test: fn(Ordering) -> bool,
+    ) -> Result<bool, LanguageError> {
+        if self.stack_top().kind() == ValueKind::Number
+            && self.nth_from_top(2).kind() == ValueKind::Number
+        {
+            return Ok(false);
+        }
+        let call_depth = self.call_stack.len();
+        let test = if self.call_operator(env, library, globals, gc, operand)? {
+            test
+        } else {
+            self.swap_stack_top();
+            if self.call_operator(env, library, globals, gc, operand)? && !self.blocked {
+                flipped_test
+            } else {
+                // The operands are swapped back, such that the instruction sees them in the right
+                // order when it's executed again or reports an error.
+                self.swap_stack_top();
+                return Ok(self.blocked);
+            }
+        };
+        if self.blocked {
+            return Ok(true);
+        }
+        if self.call_stack.len() > call_depth {
+            // The method is running in a new frame; its result is converted once it returns.
+            self.call_stack.last_mut().unwrap().comparison = Some(test);
+        } else {
+            let result = Self::ordering_result(self.stack_top(), test)
+                .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+            self.set_stack_top(result);
+        }
+        Ok(true)
+    }
+
+    /// Turns the number returned by a `cmp` method into the result of a comparison.
+    fn ordering_result(
+        ordering: RawValue,
+        test: fn(Ordering) -> bool,
+    ) -> Result<RawValue, LanguageErrorKind> {
+        let ordering = ordering.ensure_number()?;
+        Ok(RawValue::from(ordering.partial_cmp(&0.0).is_some_and(test)))
+    }
+
+    /// Collects information about a call to `function` with the topmost `argument_count` values on
+    /// the stack as arguments, for use in error messages.
+    fn call_info(&self, function: &Function, argument_count: usize) -> CallInfo {
+        let arguments = &self.stack[self.stack.len() - argument_count..];
+        CallInfo {
+            callee: Rc::from(function.render_signature()),
+            declared_at: function
+                .declaration
+                .as_ref()
+                .map(|declaration| (Rc::clone(&declaration.module_name), declaration.location)),
+            // Skip the receiver, which isn't an explicit argument.
+            argument_types: arguments[1..]
+                .iter()
+                .map(|value| value.type_name())
+                .collect(),
+        }
+    }
+
     /// Handles a call to a control function.
     fn call_control(
         &mut self,
         env: &Environment,
+        library: &Library,
         globals: &mut Globals,
         gc: &mut Memory,
         ctl: Control,
@@ -323,15 +1147,103 @@ impl Fiber {
                         LanguageErrorKind::TooManyArguments,
                     ));
                 }
-                unsafe { gc.collect(self.roots(globals)) }
+                unsafe { gc.collect(self.roots(globals), library) }
                 self.pop();
                 self.push(RawValue::from(()));
             }
+            Control::SortBy => self.start_sort(env, library, globals, gc, SortKind::Comparator)?,
+            Control::SortByKey => self.start_sort(env, library, globals, gc, SortKind::Key)?,
+            Control::Yield => {
+                if argument_count > 2 {
+                    return Err(self.error_outside_function_call(
+                        None,
+                        env,
+                        LanguageErrorKind::TooManyArguments,
+                    ));
+                }
+                let value = if argument_count == 2 {
+                    self.stack_top()
+                } else {
+                    RawValue::from(())
+                };
+                self.stack.truncate(self.stack.len() - argument_count);
+                // The `yield` evaluates to nil, unless the fiber is resumed with a value that
+                // replaces it.
+                self.push(RawValue::from(()));
+                self.yielding = Some(value);
+            }
+            Control::Resume => self.resume_coroutine(env, library, globals, gc, argument_count)?,
+            Control::MethodsOf => {
+                unsafe { gc.auto_collect(self.roots(globals), library) }
+                    .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+                let type_v = if argument_count > 1 {
+                    self.nth_from_top(argument_count - 1)
+                } else {
+                    RawValue::from(())
+                };
+                let methods = Self::methods_of(type_v, env, library, gc)
+                    .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+                self.stack.truncate(self.stack.len() - argument_count);
+                self.push(methods);
+            }
         }
         Ok(())
     }
 
+    /// Returns a list of the instance methods of a type, rendered like `name/arity`, sorted by
+    /// their names.
+    fn methods_of(
+        type_v: RawValue,
+        env: &Environment,
+        library: &Library,
+        gc: &mut Memory,
+    ) -> Result<RawValue, LanguageErrorKind> {
+        let dtable = Self::get_instance_dispatch_table(type_v, library)?;
+        let mut signatures: Vec<_> = dtable
+            .method_indices()
+            .filter_map(|index| env.get_method_signature(index))
+            .map(|signature| signature.render(env))
+            .filter(|signature| !signature.is_invalid())
+            .map(|signature| signature.to_string())
+            .collect();
+        signatures.sort();
+        let methods = signatures
+            .into_iter()
+            .map(|signature| RawValue::from(gc.allocate_string(Str::from(signature))))
+            .collect();
+        let methods: Box<dyn UserData> = Box::new(List::new(methods));
+        Ok(RawValue::from(gc.allocate(methods)))
+    }
+
     /// Constructs an error that wasn't triggered by a function call.
+    /// Finds the method in the dispatch table that's most likely to be what was meant when the
+    /// method with the given signature was called. A method with the same name but different arity
+    /// is preferred over ones with similar names.
+    fn suggest_method<'e>(
+        env: &'e Environment,
+        dtable: &DispatchTable,
+        missing: &MethodSignature,
+    ) -> Option<&'e MethodSignature> {
+        let available: Vec<_> = dtable
+            .method_indices()
+            .filter_map(|index| env.get_method_signature(index))
+            .filter(|signature| &*signature.name != RenderedSignature::INVALID_NAME)
+            .collect();
+        let name = available
+            .iter()
+            .map(|signature| &*signature.name)
+            .find(|&name| name == &*missing.name)
+            .or_else(|| {
+                closest_match(
+                    &missing.name,
+                    available.iter().map(|signature| &*signature.name),
+                )
+            })?;
+        available
+            .into_iter()
+            .find(|signature| &*signature.name == name)
+    }
+
     fn error_outside_function_call(
         &mut self,
         closure: Option<GcRaw<Closure>>,
@@ -345,6 +1257,8 @@ impl Fiber {
                 closure: Some(closure),
                 pc: 0,
                 stack_bottom: 0,
+                comparison: None,
+                sort: None,
             });
         }
         let error = self.error(env, kind);
@@ -355,6 +1269,31 @@ impl Fiber {
         error
     }
 
+    /// Constructs the error a foreign function failed with, after code it called back into failed
+    /// with `error`. The error keeps its kind, and its stack trace is put on top of this fiber's,
+    /// so that the trace shows the whole chain of calls leading up to it.
+    fn nested_error(
+        &mut self,
+        closure: GcRaw<Closure>,
+        env: &Environment,
+        error: LanguageError,
+    ) -> LanguageError {
+        let LanguageError::Runtime {
+            kind,
+            call_stack: nested_call_stack,
+            ..
+        } = error
+        else {
+            let kind = LanguageErrorKind::User(Box::new(error));
+            return self.error_outside_function_call(Some(closure), env, kind);
+        };
+        let mut error = self.error_outside_function_call(Some(closure), env, kind);
+        if let LanguageError::Runtime { call_stack, .. } = &mut error {
+            call_stack.extend(nested_call_stack);
+        }
+        error
+    }
+
     /// Constructs a closure from surrounding stack variables and upvalues.
     fn create_closure(
         &mut self,
@@ -389,8 +1328,14 @@ impl Fiber {
         })
     }
 
+    /// Extracts source code pushed onto the stack by an assertion. Unknown source code is `nil`.
+    fn source_text(value: RawValue) -> Option<Rc<str>> {
+        (value.kind() == ValueKind::String)
+            .then(|| Rc::from(unsafe { value.get_raw_string_unchecked().get() }.as_str()))
+    }
+
     /// Returns the dispatch table of the given value.
-    fn get_dispatch_table(value: RawValue, library: &Library) -> &DispatchTable {
+    pub(crate) fn get_dispatch_table(value: RawValue, library: &Library) -> &DispatchTable {
         unsafe {
             match value.kind() {
                 ValueKind::Nil => &library.builtin_dtables.nil,
@@ -408,6 +1353,192 @@ impl Fiber {
         }
     }
 
+    /// Returns the dispatch table of instances of a type, as used by struct patterns. Values that
+    /// aren't types produce a type error.
+    fn get_instance_dispatch_table(
+        type_v: RawValue,
+        library: &Library,
+    ) -> Result<&DispatchTable, LanguageErrorKind> {
+        match Self::get_dispatch_table(type_v, library).instance {
+            // Safety: the dispatch table is only used before the GC gets a chance to run again.
+            Some(dtable) => Ok(unsafe { dtable.get() }),
+            None => Err(LanguageErrorKind::TypeError {
+                expected: "type".into(),
+                got: type_v.type_name(),
+            }),
+        }
+    }
+
+    /// Returns the tuple of values carried by the value, if it's an instance of the given enum
+    /// variant.
+    fn variant_values(
+        value: RawValue,
+        instance_dtable: &DispatchTable,
+        variant: &str,
+    ) -> Option<RawValue> {
+        if value.kind() != ValueKind::Struct {
+            return None;
+        }
+        let s = unsafe { value.get_raw_struct_unchecked().get() };
+        if !ptr::eq(unsafe { s.dtable() }, instance_dtable) {
+            return None;
+        }
+        let (name, _) = unsafe { s.enum_variant() }?;
+        (name == variant).then(|| unsafe { s.get_field(1) })
+    }
+
+    /// Returns the name of the value's type for use in variant mismatch errors. Instances of enums
+    /// are named after their variant, like `Shape.Circle`.
+    fn variant_type_name(value: RawValue) -> Cow<'static, str> {
+        if value.kind() == ValueKind::Struct {
+            let s = unsafe { value.get_raw_struct_unchecked().get() };
+            if let Some((variant, _)) = unsafe { s.enum_variant() } {
+                let type_name = &unsafe { s.dtable() }.type_name;
+                return format!("{type_name}.{variant}").into();
+            }
+        }
+        value.type_name()
+    }
+
+    /// Returns the type and instance dispatch tables of a built-in type, or `None` if the value
+    /// isn't a built-in type.
+    fn get_builtin_dispatch_tables(
+        type_v: RawValue,
+        library: &Library,
+    ) -> Option<(&DispatchTable, &DispatchTable)> {
+        // Instances of structs always have an instance dispatch table set, so they need to be
+        // excluded explicitly.
+        if type_v.kind() == ValueKind::Struct {
+            return None;
+        }
+        let type_dtable = Self::get_dispatch_table(type_v, library);
+        let instance_dtable = unsafe { type_dtable.instance?.get() };
+        let builtin = &library.builtin_dtables;
+        [
+            &builtin.nil,
+            &builtin.boolean,
+            &builtin.number,
+            &builtin.string,
+            &builtin.function,
+            &builtin.list,
+            &builtin.dict,
+        ]
+        .into_iter()
+        .any(|dtable| ptr::eq(&**dtable, instance_dtable))
+        .then_some((type_dtable, instance_dtable))
+    }
+
+    /// Adds the functions from an `impl` block to the dispatch tables of a built-in type.
+    fn extend_builtin_type(
+        &mut self,
+        env: &Environment,
+        library: &Library,
+        gc: &mut Memory,
+        proto: &Prototype,
+        type_dtable: &DispatchTable,
+        instance_dtable: &DispatchTable,
+    ) -> Result<(), LanguageErrorKind> {
+        if !library.builtin_extensions {
+            return Err(LanguageErrorKind::BuiltinExtensionsDisabled(Rc::clone(
+                &instance_dtable.type_name,
+            )));
+        }
+        // Built-in types can't be constructed from scripts, and as such cannot have fields.
+        if proto.has_constructor || proto.implemented_trait_count > 0 {
+            return Err(LanguageErrorKind::InvalidBuiltinExtension(Rc::clone(
+                &instance_dtable.type_name,
+            )));
+        }
+
+        let extensions = [
+            (type_dtable, &proto.statics),
+            (instance_dtable, &proto.instance),
+        ];
+        // Check for conflicts up front, such that a failed extension doesn't leave the type
+        // half-extended.
+        for (dtable, functions) in extensions {
+            if let Some(&method_id) = functions
+                .keys()
+                .find(|&&id| dtable.get_method(id).is_some())
+            {
+                return Err(LanguageErrorKind::DoubleMethodImplementation {
+                    type_name: Rc::clone(&dtable.pretty_name),
+                    signature: Box::new(env.get_method_signature(method_id).unwrap().render(env)),
+                });
+            }
+        }
+        for (dtable, functions) in extensions {
+            for (&method_id, &function_id) in functions {
+                let function = unsafe { env.get_function_unchecked(function_id) };
+                let name = Rc::from(format!("{}.{}", dtable.pretty_name, function.name));
+                let closure = self.create_closure(env, gc, function_id, name);
+                // Safety: the VM doesn't hold onto references to methods across instructions.
+                unsafe { dtable.extend_method(method_id, closure) };
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether the value implements the given trait.
+    fn implements(
+        value: RawValue,
+        trait_v: RawValue,
+        env: &Environment,
+        library: &Library,
+    ) -> Result<bool, LanguageErrorKind> {
+        let trait_id = unsafe { trait_v.ensure_raw_trait()?.get() }.id;
+        let trait_prototype = env.get_trait(trait_id).unwrap();
+        Ok(Self::get_dispatch_table(value, library).implements_trait(trait_prototype))
+    }
+
+    /// Creates the error reported when a method called on a value doesn't exist.
+    fn method_does_not_exist(
+        env: &Environment,
+        dtable: &DispatchTable,
+        method_index: MethodIndex,
+    ) -> LanguageErrorKind {
+        match env.get_method_signature(method_index) {
+            Some(signature) => Self::signature_does_not_exist(env, dtable, signature),
+            None => LanguageErrorKind::MethodDoesNotExist {
+                type_name: Rc::clone(&dtable.pretty_name),
+                signature: Box::new(RenderedSignature::invalid()),
+                did_you_mean: None,
+            },
+        }
+    }
+
+    /// Like `method_does_not_exist`, but for signatures that may not have a method index.
+    fn signature_does_not_exist(
+        env: &Environment,
+        dtable: &DispatchTable,
+        signature: &MethodSignature,
+    ) -> LanguageErrorKind {
+        let did_you_mean = Self::suggest_method(env, dtable, signature)
+            .map(|signature| Box::new(signature.render(env)));
+        LanguageErrorKind::MethodDoesNotExist {
+            type_name: Rc::clone(&dtable.pretty_name),
+            signature: Box::new(signature.render(env)),
+            did_you_mean,
+        }
+    }
+
+    /// Looks up the method to call for a `receiver.(name)` call, whose name is computed at runtime.
+    fn get_dynamic_method(
+        env: &Environment,
+        dtable: &DispatchTable,
+        name: RawValue,
+        argument_count: u8,
+    ) -> Result<GcRaw<Closure>, LanguageErrorKind> {
+        let name = unsafe { name.ensure_raw_string()?.get() };
+        let signature = MethodSignature::new(
+            Rc::from(name.as_str()),
+            MethodParameterCount::from_count_with_self(argument_count),
+        );
+        env.get_method_index(&signature)
+            .and_then(|method_index| dtable.get_method(method_index))
+            .ok_or_else(|| Self::signature_does_not_exist(env, dtable, &signature))
+    }
+
     /// Initializes a dispatch table with methods obtained from a method ID to function ID map.
     /// Each function's name is prepended with `type_name.`.
     fn initialize_dtable(
@@ -425,6 +1556,7 @@ impl Fiber {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn initialize_dtable_with_trait_methods(
         &mut self,
         methods: impl Iterator<
@@ -440,6 +1572,7 @@ impl Fiber {
         gc: &mut Memory,
         dtable: &mut DispatchTable,
         unimplemented_methods: &mut HashSet<MethodIndex>,
+        is_static: bool,
     ) -> Result<(), LanguageErrorKind> {
         for (name, parameter_count, trait_index, function_id) in methods {
             let trait_handle = unsafe { traits[trait_index.to_usize()].get() };
@@ -458,13 +1591,36 @@ impl Fiber {
             dtable.set_method(method_id, closure);
 
             if !unimplemented_methods.remove(&method_id) {
+                // A method may also be missing from the set because the trait requires it to be of
+                // the other kind, which deserves a better error than a duplicate implementation.
+                let trait_prototype = env.get_trait(trait_id).unwrap();
+                let required_as_other_kind = if is_static {
+                    &trait_prototype.required
+                } else {
+                    &trait_prototype.required_statics
+                };
+                if required_as_other_kind.contains(&method_id) {
+                    return Err(LanguageErrorKind::TraitMethodKindMismatch {
+                        signature: Box::new(method_signature.render(env)),
+                        is_static: !is_static,
+                    });
+                }
                 return Err(LanguageErrorKind::DoubleMethodImplementation {
                     type_name: Rc::clone(&dtable.pretty_name),
-                    signature: method_signature.render(env),
+                    signature: Box::new(method_signature.render(env)),
                 });
             }
         }
 
+        Ok(())
+    }
+
+    /// Ensures all methods required by the implemented traits have been implemented.
+    fn ensure_trait_methods_implemented(
+        env: &Environment,
+        type_name: &Rc<str>,
+        unimplemented_methods: &HashSet<MethodIndex>,
+    ) -> Result<(), LanguageErrorKind> {
         if !unimplemented_methods.is_empty() {
             let mut methods: Vec<_> = unimplemented_methods
                 .iter()
@@ -484,7 +1640,7 @@ impl Fiber {
                 )
             });
             return Err(LanguageErrorKind::MethodsUnimplemented {
-                type_name: Rc::clone(&dtable.pretty_name),
+                type_name: Rc::clone(type_name),
                 methods,
             });
         }
@@ -494,13 +1650,124 @@ impl Fiber {
 
     /// Returns an iterator over all GC roots.
     fn roots<'a>(&'a self, globals: &'a mut Globals) -> impl Iterator<Item = RawValue> + 'a {
-        globals
-            .iter()
-            .chain(self.stack.iter().copied())
+        globals.iter().chain(self.stack_roots())
+    }
+
+    /// Returns an iterator over the GC roots owned by the fiber itself, without globals.
+    pub(crate) fn stack_roots(&self) -> impl Iterator<Item = RawValue> + '_ {
+        let stack = self.stack.iter().copied();
+        // Captured variables that are still in scope are stored in their upvalues.
+        #[cfg(feature = "forbid-unsafe")]
+        let stack = stack.chain(
+            self.open_upvalues
+                .iter()
+                .map(|(_, upvalue)| unsafe { upvalue.get() }),
+        );
+        stack
             .chain(self.closure.map(RawValue::from))
+            .chain(self.raised)
+            .chain(self.yielding)
+            .chain(self.running_deferred.iter().filter_map(|exit| match exit {
+                DeferredExit::Continue(_) => None,
+                DeferredExit::Propagate(_, raised) => *raised,
+            }))
+    }
+
+    /// Unwinds the fiber to the innermost error handler, and continues execution in its `catch`
+    /// block. The error value and the stack trace are pushed onto the stack for the `catch` block
+    /// to bind. Returns the error back if there's no handler that can catch it.
+    fn catch(
+        &mut self,
+        error: LanguageError,
+        library: &Library,
+        globals: &mut Globals,
+        gc: &mut Memory,
+    ) -> Result<(), LanguageError> {
+        let LanguageError::Runtime {
+            kind, call_stack, ..
+        } = &error
+        else {
+            return Err(error);
+        };
+        if !kind.is_catchable() {
+            self.running_deferred.clear();
+            return Err(error);
+        }
+        let Some(handler) = self.handlers.pop() else {
+            self.running_deferred.clear();
+            return Err(error);
+        };
+
+        // Closures may still refer to locals of the functions being unwound, so those have to be
+        // moved off the stack before it's cut off.
+        self.open_upvalues.retain(|(slot, upvalue)| {
+            let unwound = *slot as usize >= handler.stack_height;
+            if unwound {
+                unsafe { upvalue.close() };
+                gc.shade(unsafe { upvalue.get() });
+            }
+            !unwound
+        });
+        // The error saved a return point for its stack trace, so the innermost frame is on the
+        // call stack too. Restoring it is harmless, as it's the frame that was already executing.
+        while self.call_stack.len() > handler.call_depth {
+            let return_point = self.call_stack.pop().unwrap();
+            if let Some(chunk) = return_point.chunk {
+                self.chunk = chunk;
+            }
+            self.closure = return_point.closure;
+            self.stack_bottom = return_point.stack_bottom;
+        }
+        while self.stack.len() > handler.stack_height {
+            self.stack.pop();
+        }
+        self.breakable_block_stack
+            .truncate(handler.breakable_blocks);
+        self.running_deferred.truncate(handler.running_deferred);
+        self.halted = false;
+        self.errored = false;
+
+        if handler.deferred {
+            let raised = self.raised.take();
+            self.running_deferred
+                .push(DeferredExit::Propagate(error, raised));
+            self.pc = handler.catch_pc;
+            return Ok(());
+        }
+
+        // An error is being handled already, so exceeding the memory limit here is only reported
+        // at the next allocation point.
+        let _ = unsafe { gc.auto_collect(self.roots(globals), library) };
+        let value = match self.raised.take() {
+            Some(value) => value,
+            None => RawValue::from(gc.allocate_string(Str::from(kind.to_string()))),
+        };
+        self.push(value);
+        let trace = call_stack
+            .iter()
+            .rev()
+            .map(|entry| {
+                let line = format!(
+                    "{}  {}",
+                    FileLocation(&entry.module_name, entry.location),
+                    entry.function_name
+                );
+                RawValue::from(gc.allocate_string(Str::from(line)))
+            })
+            .collect();
+        let trace: Box<dyn UserData> = Box::new(List::new(trace));
+        self.push(RawValue::from(gc.allocate(trace)));
+
+        self.pc = handler.catch_pc;
+        Ok(())
     }
 
     /// Interprets bytecode in the chunk, with the provided user state.
+    ///
+    /// Returns early with `nil` if the fiber [blocks][Self::blocked], or runs out of its
+    /// [budget][Self::set_budget] or [fuel][Self::out_of_fuel], and with the yielded value if it
+    /// [yields][Self::yielded].
+    /// Calling this again afterwards resumes execution where it left off.
     pub fn interpret(
         &mut self,
         env: &Environment,
@@ -508,14 +1775,97 @@ impl Fiber {
         globals: &mut Globals,
         gc: &mut Memory,
     ) -> Result<RawValue, LanguageError> {
-        self.allocate_chunk_storage_slots(self.chunk.preallocate_stack_slots as usize);
+        loop {
+            match self.run(env, library, globals, gc) {
+                Err(error) => self.catch(error, library, globals, gc)?,
+                result => return result,
+            }
+        }
+    }
+
+    /// Runs bytecode until the fiber halts, suspends, or errors.
+    fn run(
+        &mut self,
+        env: &Environment,
+        library: &Library,
+        globals: &mut Globals,
+        gc: &mut Memory,
+    ) -> Result<RawValue, LanguageError> {
+        // A fiber that suspended is resumed in the middle of its chunk, with its storage already
+        // allocated.
+        if !self.started {
+            self.allocate_chunk_storage_slots(self.chunk.preallocate_stack_slots as usize);
+            self.started = true;
+        }
+        self.out_of_budget = false;
+        self.out_of_fuel = false;
+        self.yielded = false;
+        let mut resuming_from_pause = mem::take(&mut self.paused);
+        // Fuel, budgets, and debug hooks are only picked up at this point, so when none of them
+        // are enabled, the checks before each instruction can be skipped altogether.
+        let metered =
+            library.fuel.get().is_some() || self.budget.is_some() || library.debug_hook.is_some();
+        // The program counter is kept in a local, so that it can live in a register. It's
+        // written back to `self.pc` after each instruction is decoded, which is what calls and
+        // errors see, and read back after anything that can change it, such as a call.
+        let mut pc = self.pc;
 
         loop {
+            if self.yielding.is_some() {
+                self.pc = pc;
+                self.yielded = true;
+                return Ok(self.yielding.take().unwrap());
+            }
+            if metered {
+                self.pc = pc;
+                if let Some(fuel) = library.fuel.get() {
+                    if fuel == 0 {
+                        self.out_of_fuel = true;
+                        return Ok(RawValue::from(()));
+                    }
+                    library.fuel.set(Some(fuel - 1));
+                }
+                if let Some(budget) = &mut self.budget {
+                    if *budget == 0 {
+                        self.out_of_budget = true;
+                        return Ok(RawValue::from(()));
+                    }
+                    *budget -= 1;
+                }
+                if let Some(hook) = &library.debug_hook {
+                    if !mem::take(&mut resuming_from_pause) {
+                        self.run_debug_hook(hook, env, globals);
+                        if hook.borrow_mut().take_pause_request() {
+                            // The instruction is executed once the fiber is resumed, so it's not
+                            // paid for until then.
+                            if let Some(fuel) = library.fuel.get() {
+                                library.fuel.set(Some(fuel + 1));
+                            }
+                            if let Some(budget) = &mut self.budget {
+                                *budget += 1;
+                            }
+                            self.paused = true;
+                            return Ok(RawValue::from(()));
+                        }
+                    }
+                }
+            }
+            // Threaded code doesn't check arithmetic results, so it's only entered when they don't
+            // need checking.
+            #[cfg(all(
+                feature = "threaded-dispatch",
+                not(feature = "forbid-unsafe"),
+                not(feature = "trace-vm-opcodes")
+            ))]
+            if !metered && library.arithmetic == super::bytecode::Arithmetic::Ieee {
+                pc = threaded::run(self, pc);
+            }
             #[cfg(feature = "trace-vm-opcodes")]
             {
-                print!("op   @ {:06x} ", self.pc);
+                print!("op   @ {:06x} ", pc);
             }
-            let (opcode, operand) = unsafe { self.chunk.read_instruction(&mut self.pc) };
+            let (opcode, operand) = unsafe { self.chunk.read_instruction(&mut pc) };
+            self.pc = pc;
             #[cfg(feature = "trace-vm-opcodes")]
             {
                 println!("{:?} ({})", opcode, operand);
@@ -534,11 +1884,131 @@ impl Fiber {
                 }};
             }
 
+            macro_rules! call_operator {
+                () => {{
+                    let called = self.call_operator(env, library, globals, gc, operand)?;
+                    pc = self.pc;
+                    if self.blocked {
+                        return Ok(RawValue::from(()));
+                    }
+                    called
+                }};
+            }
+
+            macro_rules! call_comparison {
+                ($test:expr, $flipped_test:expr) => {{
+                    let called = self.call_comparison(
+                        env,
+                        library,
+                        globals,
+                        gc,
+                        operand,
+                        $test,
+                        $flipped_test,
+                    )?;
+                    pc = self.pc;
+                    if self.blocked {
+                        return Ok(RawValue::from(()));
+                    }
+                    called
+                }};
+            }
+
+            macro_rules! enter_function {
+                ($closure:expr, $argument_count:expr) => {{
+                    let closure = $closure;
+                    let argument_count = $argument_count;
+                    self.pc = pc;
+                    self.enter_function(env, library, globals, gc, closure, argument_count)?;
+                    pc = self.pc;
+                    if self.blocked {
+                        return Ok(RawValue::from(()));
+                    }
+                }};
+            }
+
+            macro_rules! call_function {
+                ($argument_count:expr) => {{
+                    let argument_count = $argument_count;
+                    let function = self.nth_from_top(argument_count);
+                    let closure = wrap_error!(function.ensure_raw_function());
+                    enter_function!(closure, argument_count);
+                }};
+            }
+
+            macro_rules! number_comparison_jump {
+                ($op:tt) => {{
+                    let number = unsafe { self.chunk.read_number(&mut pc) };
+                    let left = self.stack_top();
+                    if left.kind() == ValueKind::Number {
+                        let is_true = unsafe { *left.get_number_unchecked() } $op number;
+                        // Skip over the comparison, and perform the jump after it.
+                        pc += Opcode::INSTRUCTION_SIZE;
+                        let (jump, jump_operand) =
+                            unsafe { self.chunk.read_instruction(&mut pc) };
+                        if is_true {
+                            self.pop();
+                            // The condition is discarded right after the jump when it's true.
+                            pc += Opcode::INSTRUCTION_SIZE;
+                        } else {
+                            self.set_stack_top(RawValue::from(false));
+                            pc += match jump {
+                                Opcode::JumpForwardIfFalsy => usize::from(jump_operand),
+                                _ => unsafe { self.chunk.long_jump_offset(jump_operand) },
+                            };
+                        }
+                    } else {
+                        self.push(RawValue::from(number));
+                    }
+                }};
+            }
+
+            macro_rules! number_operands {
+                () => {{
+                    let right = self.stack_top();
+                    let left = self.nth_from_top(2);
+                    if left.kind() == ValueKind::Number && right.kind() == ValueKind::Number {
+                        Some(unsafe {
+                            (*left.get_number_unchecked(), *right.get_number_unchecked())
+                        })
+                    } else {
+                        None
+                    }
+                }};
+            }
+
+            macro_rules! number_result {
+                ($result:expr) => {{
+                    let result = RawValue::from($result);
+                    self.pop();
+                    self.set_stack_top(result);
+                }};
+            }
+
+            // Replaces the opcode of the instruction being executed. Generic arithmetic opcodes
+            // are quickened once they see two numbers, and quickened ones go back to being generic
+            // once they don't, so that they can be quickened again later.
+            macro_rules! quicken {
+                ($opcode:expr) => {
+                    unsafe { self.chunk.quicken(pc - Opcode::INSTRUCTION_SIZE, $opcode) }
+                };
+            }
+
+            // Like `number_result!`, but for arithmetic, whose result is checked according to the
+            // engine's arithmetic mode.
+            macro_rules! arithmetic_result {
+                ($result:expr) => {{
+                    let result = wrap_error!(library.arithmetic.check($result));
+                    number_result!(result)
+                }};
+            }
+
             macro_rules! binary_operator {
                 ($op:tt) => {{
                     let right = wrap_error!(self.pop().ensure_number());
                     let left = wrap_error!(self.pop().ensure_number());
-                    self.stack.push(RawValue::from(left $op right));
+                    let result = wrap_error!(library.arithmetic.check(left $op right));
+                    self.push(RawValue::from(result));
                 }};
             }
 
@@ -549,17 +2019,15 @@ impl Fiber {
                 Opcode::PushTrue => self.push(RawValue::from(true)),
                 Opcode::PushFalse => self.push(RawValue::from(false)),
                 Opcode::PushNumber => {
-                    let number = unsafe { self.chunk.read_number(&mut self.pc) };
+                    let number = unsafe { self.chunk.read_number(&mut pc) };
                     self.push(RawValue::from(number));
                 }
                 Opcode::PushString => {
-                    let string = unsafe { self.chunk.read_string(&mut self.pc) }.to_owned();
-                    unsafe { gc.auto_collect(self.roots(globals)) };
-                    let rc = gc.allocate(string);
-                    self.push(RawValue::from(rc));
+                    let string = unsafe { env.get_string_unchecked(operand) };
+                    self.push(RawValue::from(string));
                 }
                 Opcode::CreateClosure => {
-                    unsafe { gc.auto_collect(self.roots(globals)) };
+                    wrap_error!(unsafe { gc.auto_collect(self.roots(globals), library) });
                     let function_id = FunctionIndex::from_opr24(operand);
                     let function = unsafe { env.get_function_unchecked(function_id) };
                     let closure =
@@ -567,15 +2035,15 @@ impl Fiber {
                     self.push(RawValue::from(closure));
                 }
                 Opcode::CreateType => {
-                    let name = unsafe { self.chunk.read_string(&mut self.pc) };
+                    let name = unsafe { self.chunk.read_string(&mut pc) };
                     let mut dispatch_table = DispatchTable::new_for_type(name);
                     dispatch_table.pretty_name = Rc::from(format!("unimplemented type {name}"));
                     let dispatch_table = gc.allocate(dispatch_table);
                     let struct_v = Struct::new_type(dispatch_table);
-                    self.stack.push(RawValue::from(gc.allocate(struct_v)));
+                    self.push(RawValue::from(gc.allocate(struct_v)));
                 }
                 Opcode::CreateStruct => {
-                    unsafe { gc.auto_collect(self.roots(globals)) };
+                    wrap_error!(unsafe { gc.auto_collect(self.roots(globals), library) });
                     let type_struct = unsafe { self.pop().get_raw_struct_unchecked() };
                     let field_count = usize::from(operand);
                     let instance = unsafe { type_struct.get().new_instance(field_count) };
@@ -588,15 +2056,16 @@ impl Fiber {
                     self.push(RawValue::from(instance));
                 }
                 Opcode::CreateList => {
-                    unsafe { gc.auto_collect(self.roots(globals)) };
+                    wrap_error!(unsafe { gc.auto_collect(self.roots(globals), library) });
                     let len = usize::from(operand);
                     let elements = self.stack.drain(self.stack.len() - len..).collect();
                     let list: Box<dyn UserData> = Box::new(List::new(elements));
-                    let list = gc.allocate(list);
-                    self.push(RawValue::from(list));
+                    let list = RawValue::from(gc.allocate(list));
+                    wrap_error!(library.limits.check(list));
+                    self.push(list);
                 }
                 Opcode::CreateDict => {
-                    unsafe { gc.auto_collect(self.roots(globals)) };
+                    wrap_error!(unsafe { gc.auto_collect(self.roots(globals), library) });
                     let npairs = usize::from(operand);
                     let dict = Dict::new();
                     {
@@ -607,69 +2076,75 @@ impl Fiber {
                         }
                     }
                     let dict: Box<dyn UserData> = Box::new(dict);
-                    let dict = gc.allocate(dict);
-                    self.push(RawValue::from(dict));
+                    let dict = RawValue::from(gc.allocate(dict));
+                    wrap_error!(library.limits.check(dict));
+                    self.push(dict);
                 }
                 Opcode::CreateTuple => {
-                    unsafe { gc.auto_collect(self.roots(globals)) };
+                    wrap_error!(unsafe { gc.auto_collect(self.roots(globals), library) });
                     let len = usize::from(operand);
                     let fields = self.stack.drain(self.stack.len() - len..).collect();
-                    let tuple: Box<dyn UserData> = Box::new(Tuple::new(fields));
-                    let tuple = gc.allocate(tuple);
-                    self.push(RawValue::from(tuple));
+                    let tuple = RawValue::from(unsafe { gc.allocate_tuple(Tuple::new(fields)) });
+                    wrap_error!(library.limits.check(tuple));
+                    self.push(tuple);
                 }
                 Opcode::CreateRecord => {
-                    unsafe { gc.auto_collect(self.roots(globals)) };
+                    wrap_error!(unsafe { gc.auto_collect(self.roots(globals), library) });
                     let record_type_index = RecordTypeIndex::from_opr24(operand);
                     let record_type = library.builtin_dtables.get_record(record_type_index);
 
                     let mut fields = vec![RawValue::from(()); record_type.field_count];
                     let values = &self.stack[self.stack.len() - fields.len()..];
                     for field in &mut fields {
-                        let value_index = unsafe { self.chunk.read_u32(&mut self.pc) as usize };
+                        let value_index = unsafe { self.chunk.read_u32(&mut pc) as usize };
                         *field = values[value_index];
                     }
                     for _ in 0..fields.len() {
                         self.pop();
                     }
-                    let _sentinel = unsafe { self.chunk.read_u32(&mut self.pc) };
+                    let _sentinel = unsafe { self.chunk.read_u32(&mut pc) };
 
                     let record: Box<dyn UserData> = Box::new(Record {
                         record_type: Rc::clone(record_type),
                         fields,
                     });
-                    let record = gc.allocate(record);
-                    self.push(RawValue::from(record));
+                    let record = RawValue::from(gc.allocate(record));
+                    wrap_error!(library.limits.check(record));
+                    self.push(record);
                 }
 
                 Opcode::AssignGlobal => {
                     let global_index = GlobalIndex::from_opr24(operand);
+                    wrap_error!(env.ensure_global_not_sealed(global_index));
                     let value = self.stack_top();
                     globals.set(global_index, value);
                 }
                 Opcode::SinkGlobal => {
                     let global_index = GlobalIndex::from_opr24(operand);
+                    wrap_error!(env.ensure_global_not_sealed(global_index));
                     let value = self.pop();
                     globals.set(global_index, value);
                 }
                 Opcode::GetGlobal => {
                     let global_index = GlobalIndex::from_opr24(operand);
-                    let value = unsafe { globals.get_unchecked(global_index) };
+                    // Globals can be declared without being set (eg. through `Engine::global_id`),
+                    // so this must be checked.
+                    let value = globals.get(global_index);
                     self.push(value);
                 }
                 Opcode::AssignLocal => {
                     let slot = usize::from(operand);
                     let value = self.stack_top();
-                    self.stack[self.stack_bottom + slot] = value;
+                    self.set_local(slot, value);
                 }
                 Opcode::SinkLocal => {
                     let slot = usize::from(operand);
                     let value = self.pop();
-                    self.stack[self.stack_bottom + slot] = value;
+                    self.set_local(slot, value);
                 }
                 Opcode::GetLocal => {
                     let slot = usize::from(operand);
-                    let value = self.stack[self.stack_bottom + slot];
+                    let value = self.local(slot);
                     self.push(value);
                 }
                 Opcode::AssignUpvalue => {
@@ -677,12 +2152,14 @@ impl Fiber {
                     let closure = unsafe { self.closure.as_ref().unwrap_unchecked().get() };
                     let value = self.stack_top();
                     unsafe { Upvalue::set(&closure.captures[index], value) }
+                    gc.shade(value);
                 }
                 Opcode::SinkUpvalue => {
                     let index = usize::from(operand);
                     let value = self.pop();
                     let closure = unsafe { self.closure.as_ref().unwrap_unchecked().get() };
                     unsafe { Upvalue::set(&closure.captures[index], value) }
+                    gc.shade(value);
                 }
                 Opcode::GetUpvalue => {
                     let index = usize::from(operand);
@@ -701,19 +2178,26 @@ impl Fiber {
                         .unwrap();
                     let (_, upvalue) = self.open_upvalues.remove(index);
                     unsafe { upvalue.close() };
+                    // The value isn't on the stack anymore, so the closures capturing it have to
+                    // keep it alive.
+                    gc.shade(unsafe { upvalue.get() });
                 }
                 Opcode::AssignField => {
                     let struct_v = self.pop();
                     let value = self.pop();
-                    let struct_v = unsafe { struct_v.get_raw_struct_unchecked() };
+                    let struct_raw = unsafe { struct_v.get_raw_struct_unchecked() };
                     self.push(value);
-                    unsafe { struct_v.get().set_field(usize::from(operand), value) }
+                    unsafe { struct_raw.get().set_field(usize::from(operand), value) }
+                    gc.shade(value);
+                    wrap_error!(library.limits.check_field_assignment(struct_v, value));
                 }
                 Opcode::SinkField => {
                     let struct_v = self.pop();
                     let value = self.pop();
-                    let struct_v = unsafe { struct_v.get_raw_struct_unchecked() };
-                    unsafe { struct_v.get().set_field(usize::from(operand), value) }
+                    let struct_raw = unsafe { struct_v.get_raw_struct_unchecked() };
+                    unsafe { struct_raw.get().set_field(usize::from(operand), value) }
+                    gc.shade(value);
+                    wrap_error!(library.limits.check_field_assignment(struct_v, value));
                 }
                 Opcode::GetField => {
                     let struct_v = self.pop();
@@ -721,6 +2205,42 @@ impl Fiber {
                     let value = unsafe { struct_v.get().get_field(usize::from(operand)) };
                     self.push(value);
                 }
+                Opcode::Index => {
+                    let receiver = self.nth_from_top(2);
+                    let key = self.stack_top();
+                    if let Some(value) = wrap_error!(Self::index_builtin(receiver, key)) {
+                        self.pop();
+                        self.set_stack_top(value);
+                    } else if !call_operator!() {
+                        let (method_index, _): (u16, u8) = operand.unpack();
+                        let dtable = Self::get_dispatch_table(receiver, library);
+                        wrap_error!(Err(Self::method_does_not_exist(
+                            env,
+                            dtable,
+                            MethodIndex::from_u16(method_index)
+                        )));
+                    }
+                }
+                Opcode::IndexAssign => {
+                    let receiver = self.nth_from_top(3);
+                    let key = self.nth_from_top(2);
+                    let value = self.stack_top();
+                    if wrap_error!(Self::index_assign_builtin(receiver, key, value)) {
+                        gc.shade(key);
+                        gc.shade(value);
+                        wrap_error!(library.limits.check_call(&[receiver, key, value], value));
+                        self.stack.truncate(self.stack.len() - 3);
+                        self.push(value);
+                    } else if !call_operator!() {
+                        let (method_index, _): (u16, u8) = operand.unpack();
+                        let dtable = Self::get_dispatch_table(receiver, library);
+                        wrap_error!(Err(Self::method_does_not_exist(
+                            env,
+                            dtable,
+                            MethodIndex::from_u16(method_index)
+                        )));
+                    }
+                }
 
                 Opcode::DestructureTuple => {
                     let expected_size = usize::from(operand);
@@ -734,7 +2254,7 @@ impl Fiber {
                             got: tuple_v.type_name()
                         }));
                     }
-                    self.stack.reserve(tuple.fields.len());
+                    self.reserve_stack(tuple.fields.len());
                     for &field in &tuple.fields {
                         self.push(field);
                     }
@@ -752,7 +2272,7 @@ impl Fiber {
                             got: record_v.type_name()
                         }));
                     }
-                    self.stack.reserve(record.fields.len());
+                    self.reserve_stack(record.fields.len());
                     for &field in &record.fields {
                         self.push(field);
                     }
@@ -762,10 +2282,133 @@ impl Fiber {
                     wrap_error!(record.ensure_raw_user_data::<Record, _, _>(|| "Record"));
                 }
 
-                Opcode::Swap => {
-                    let len = self.stack.len();
-                    self.stack.swap(len - 2, len - 1);
+                Opcode::MatchesTuple => {
+                    let expected_size = usize::from(operand);
+                    let matches = user_data_of(self.stack_top())
+                        .and_then(|user_data| user_data.as_any().downcast_ref::<Tuple>())
+                        .is_some_and(|tuple| tuple.fields.len() == expected_size);
+                    self.push(RawValue::from(matches));
+                }
+                Opcode::MatchesRecord => {
+                    let record_type_index = RecordTypeIndex::from_opr24(operand);
+                    let matches = user_data_of(self.stack_top())
+                        .and_then(|user_data| user_data.as_any().downcast_ref::<Record>())
+                        .is_some_and(|record| record.record_type.index == record_type_index);
+                    self.push(RawValue::from(matches));
+                }
+                Opcode::MatchesRecordNonExhaustive => {
+                    let record_type_index = RecordTypeIndex::from_opr24(operand);
+                    let pattern_type = library.builtin_dtables.get_record(record_type_index);
+                    let matches = user_data_of(self.stack_top())
+                        .and_then(|user_data| user_data.as_any().downcast_ref::<Record>())
+                        .is_some_and(|record| {
+                            // Record identifiers list their fields in sorted order, so a single
+                            // pass over the record's fields is enough to find all of the pattern's.
+                            let mut fields = record.record_type.identifier.split('+');
+                            pattern_type
+                                .identifier
+                                .split('+')
+                                .filter(|field| !field.is_empty())
+                                .all(|field| fields.any(|other| other == field))
+                        });
+                    self.push(RawValue::from(matches));
+                }
+
+                Opcode::DestructureStruct => {
+                    let type_v = self.pop();
+                    let instance_dtable =
+                        wrap_error!(Self::get_instance_dispatch_table(type_v, library));
+                    let value = self.stack_top();
+                    let dtable = Self::get_dispatch_table(value, library);
+                    if !ptr::eq(dtable, instance_dtable) {
+                        wrap_error!(Err(LanguageErrorKind::TypeError {
+                            expected: instance_dtable.type_name.deref().to_owned().into(),
+                            got: value.type_name(),
+                        }));
+                    }
+                }
+                Opcode::MatchesStruct => {
+                    let type_v = self.pop();
+                    let instance_dtable =
+                        wrap_error!(Self::get_instance_dispatch_table(type_v, library));
+                    let dtable = Self::get_dispatch_table(self.stack_top(), library);
+                    self.push(RawValue::from(ptr::eq(dtable, instance_dtable)));
                 }
+                Opcode::DestructureVariant => {
+                    let type_v = self.pop();
+                    let instance_dtable =
+                        wrap_error!(Self::get_instance_dispatch_table(type_v, library));
+                    let variant = unsafe { self.chunk.read_string(&mut pc) };
+                    let value = self.stack_top();
+                    match Self::variant_values(value, instance_dtable, variant) {
+                        Some(values) => self.set_stack_top(values),
+                        None => {
+                            let expected = format!("{}.{variant}", instance_dtable.type_name);
+                            wrap_error!(Err(LanguageErrorKind::TypeError {
+                                expected: expected.into(),
+                                got: Self::variant_type_name(value),
+                            }));
+                        }
+                    }
+                }
+                Opcode::MatchesVariant => {
+                    let type_v = self.pop();
+                    let instance_dtable =
+                        wrap_error!(Self::get_instance_dispatch_table(type_v, library));
+                    let variant = unsafe { self.chunk.read_string(&mut pc) };
+                    let matches =
+                        Self::variant_values(self.stack_top(), instance_dtable, variant).is_some();
+                    self.push(RawValue::from(matches));
+                }
+                Opcode::GetPatternField => {
+                    let (method_index, _) = operand.unpack();
+                    let method_index = MethodIndex::from_u16(method_index);
+                    let value = self.stack_top();
+                    let dtable = Self::get_dispatch_table(value, library);
+                    let field_index =
+                        env.get_method_signature(method_index)
+                            .and_then(|signature| {
+                                dtable
+                                    .fields
+                                    .iter()
+                                    .position(|name| *name == signature.name)
+                            });
+                    match field_index {
+                        // Struct instances always have as many fields as their dispatch table
+                        // declares, so reading the field is safe.
+                        Some(index) if value.kind() == ValueKind::Struct => {
+                            let field =
+                                unsafe { value.get_raw_struct_unchecked().get().get_field(index) };
+                            self.pop();
+                            self.push(field);
+                        }
+                        _ => {
+                            if let Some(closure) = dtable.get_method(method_index) {
+                                enter_function!(closure, 1);
+                            } else {
+                                let error_kind =
+                                    Self::method_does_not_exist(env, dtable, method_index);
+                                return Err(self.error_outside_function_call(None, env, error_kind));
+                            }
+                        }
+                    }
+                }
+
+                Opcode::EnsureImplements => {
+                    let trait_v = self.pop();
+                    let value = self.stack_top();
+                    if !wrap_error!(Self::implements(value, trait_v, env, library)) {
+                        let trait_name = &unsafe { trait_v.get_raw_trait_unchecked().get() }
+                            .dtable()
+                            .type_name;
+                        wrap_error!(Err(LanguageErrorKind::TypeError {
+                            expected: format!("implementation of {trait_name}").into(),
+                            got: value.type_name(),
+                        }));
+                    }
+                }
+
+                Opcode::Swap => self.swap_stack_top(),
                 Opcode::Duplicate => {
                     self.push(self.stack_top());
                 }
@@ -775,23 +2418,43 @@ impl Fiber {
 
                 Opcode::JumpForward => {
                     let amount = usize::from(operand);
-                    self.pc += amount;
+                    pc += amount;
                 }
                 Opcode::JumpForwardIfFalsy => {
                     let amount = usize::from(operand);
                     if self.stack_top().is_falsy() {
-                        self.pc += amount;
+                        pc += amount;
                     }
                 }
                 Opcode::JumpForwardIfTruthy => {
                     let amount = usize::from(operand);
                     if self.stack_top().is_truthy() {
-                        self.pc += amount;
+                        pc += amount;
                     }
                 }
                 Opcode::JumpBackward => {
                     let amount = usize::from(operand);
-                    self.pc -= amount;
+                    pc -= amount;
+                }
+                Opcode::JumpForwardLong => {
+                    let amount = unsafe { self.chunk.long_jump_offset(operand) };
+                    pc += amount;
+                }
+                Opcode::JumpForwardIfFalsyLong => {
+                    let amount = unsafe { self.chunk.long_jump_offset(operand) };
+                    if self.stack_top().is_falsy() {
+                        pc += amount;
+                    }
+                }
+                Opcode::JumpForwardIfTruthyLong => {
+                    let amount = unsafe { self.chunk.long_jump_offset(operand) };
+                    if self.stack_top().is_truthy() {
+                        pc += amount;
+                    }
+                }
+                Opcode::JumpBackwardLong => {
+                    let amount = unsafe { self.chunk.long_jump_offset(operand) };
+                    pc -= amount;
                 }
 
                 Opcode::EnterBreakableBlock => {
@@ -811,13 +2474,54 @@ impl Fiber {
                     self.push(result);
                 }
 
-                Opcode::Call => {
+                Opcode::EnterTry | Opcode::EnterDefer => {
+                    let deferred = opcode == Opcode::EnterDefer;
+                    let catch_pc = if deferred {
+                        pc - usize::from(operand)
+                    } else {
+                        pc + usize::from(operand)
+                    };
+                    self.handlers.push(Handler {
+                        call_depth: self.call_stack.len(),
+                        stack_height: self.stack.len(),
+                        breakable_blocks: self.breakable_block_stack.len(),
+                        running_deferred: self.running_deferred.len(),
+                        catch_pc,
+                        deferred,
+                    });
+                }
+                Opcode::ExitTry => {
+                    let n = usize::from(operand);
+                    self.handlers.truncate(self.handlers.len() - n);
+                }
+                Opcode::Raise => {
+                    let value = self.pop();
+                    self.raised = Some(value);
+                    wrap_error!(Err(LanguageErrorKind::Raised(Rc::from(value.to_string()))));
+                }
+                Opcode::RunDeferred => {
+                    self.running_deferred.push(DeferredExit::Continue(pc));
+                    pc -= usize::from(operand);
+                }
+                Opcode::ExitDeferred => match self.running_deferred.pop() {
+                    Some(DeferredExit::Continue(continue_pc)) => pc = continue_pc,
+                    Some(DeferredExit::Propagate(error, raised)) => {
+                        self.raised = raised;
+                        self.halted = true;
+                        self.errored = true;
+                        return Err(error);
+                    }
+                    None => unreachable!("deferred code must be exited only after it's run"),
+                },
+
+                Opcode::Call | Opcode::CallSpread => {
                     // Add 1 to count in the called function itself, which is treated like an
                     // argument.
-                    let argument_count = usize::from(operand) + 1;
-                    let function = self.nth_from_top(argument_count);
-                    let closure = wrap_error!(function.ensure_raw_function());
-                    self.enter_function(env, library, globals, gc, closure, argument_count)?;
+                    let mut argument_count = usize::from(operand) + 1;
+                    if opcode == Opcode::CallSpread {
+                        argument_count += wrap_error!(self.spread_arguments());
+                    }
+                    call_function!(argument_count);
                 }
                 Opcode::CallMethod => {
                     let (method_index, argument_count) = operand.unpack();
@@ -833,31 +2537,84 @@ impl Fiber {
                             env.get_method_signature(method_index)
                         );
                     }
-                    if let Some(closure) = dtable.get_method(method_index) {
-                        self.enter_function(
-                            env,
-                            library,
-                            globals,
-                            gc,
-                            closure,
-                            argument_count as usize,
-                        )?;
+                    let intercepted = library.call_interceptor.as_ref().and_then(|interceptor| {
+                        let arguments = &self.stack[self.stack.len() - argument_count as usize..];
+                        interceptor
+                            .borrow_mut()
+                            .intercept(method_index, dtable, arguments, gc)
+                    });
+                    if let Some(result) = intercepted {
+                        let result = result
+                            .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+                        for _ in 0..argument_count {
+                            self.pop();
+                        }
+                        self.push(result);
+                    } else if let Some(closure) = dtable.get_method(method_index) {
+                        enter_function!(closure, argument_count as usize);
                     } else {
-                        let signature = env
-                            .get_method_signature(method_index)
-                            .map(|signature| signature.render(env))
-                            .unwrap_or_else(RenderedSignature::invalid);
-                        let error_kind = LanguageErrorKind::MethodDoesNotExist {
-                            type_name: Rc::clone(&dtable.pretty_name),
-                            signature,
-                        };
+                        let error_kind = Self::method_does_not_exist(env, dtable, method_index);
                         return Err(self.error_outside_function_call(None, env, error_kind));
                     }
                 }
+                Opcode::CallMethodDynamic => {
+                    let argument_count = u8::try_from(usize::from(operand)).unwrap();
+                    let receiver = self.nth_from_top(usize::from(argument_count));
+                    let name = self.nth_from_top(usize::from(argument_count) + 1);
+                    let dtable = Self::get_dispatch_table(receiver, library);
+                    match Self::get_dynamic_method(env, dtable, name, argument_count) {
+                        Ok(closure) => {
+                            enter_function!(closure, usize::from(argument_count));
+                        }
+                        Err(error_kind) => {
+                            return Err(self.error_outside_function_call(None, env, error_kind));
+                        }
+                    }
+                }
                 Opcode::Return => {
+                    if let Some(hook) = &library.debug_hook {
+                        hook.borrow_mut().on_return(self, env, globals);
+                    }
                     let result = self.pop();
+                    let comparison = self.call_stack.last().and_then(|point| point.comparison);
+                    let sort = self
+                        .call_stack
+                        .last_mut()
+                        .and_then(|point| point.sort.take());
                     self.restore_return_point();
-                    self.push(result);
+                    match (comparison, sort) {
+                        (Some(test), _) => {
+                            let result = wrap_error!(Self::ordering_result(result, test));
+                            self.push(result);
+                        }
+                        (None, Some(mut sort)) => {
+                            wrap_error!(self.sort_result(&mut sort, result));
+                            self.continue_sort(env, library, globals, gc, sort)?;
+                        }
+                        (None, None) => self.push(result),
+                    }
+                    pc = self.pc;
+                }
+
+                Opcode::AssertionFailed => {
+                    let message = self.pop();
+                    let message = (message.kind() != ValueKind::Nil).then(|| message.to_string());
+                    let mut operands = Vec::with_capacity(usize::from(operand));
+                    for _ in 0..usize::from(operand) {
+                        let value = self.pop();
+                        if let Some(source) = Self::source_text(self.pop()) {
+                            operands.push((source, format!("{value:?}")));
+                        }
+                    }
+                    operands.reverse();
+                    let expression = Self::source_text(self.pop());
+                    wrap_error!(Err(LanguageErrorKind::AssertionFailed(Box::new(
+                        AssertionFailure {
+                            expression,
+                            operands,
+                            message,
+                        }
+                    ))));
                 }
 
                 Opcode::Implement => {
@@ -865,110 +2622,296 @@ impl Fiber {
                     let proto = unsafe { env.get_prototype_unchecked(prototype_index) };
                     let struct_position = proto.implemented_trait_count as usize + 1;
 
-                    let traits: Vec<_> = {
-                        // TODO: Maybe get rid of this allocation, or hoist it into the fiber?
-                        let mut traits = vec![];
-                        for trait_value in &self.stack[self.stack.len() - struct_position + 1..] {
-                            traits.push(wrap_error!(trait_value.ensure_raw_trait()));
-                        }
-                        traits
-                    };
-                    let mut unimplemented_trait_methods: HashSet<_> = traits
-                        .iter()
-                        .enumerate()
-                        .map(|(trait_index, trait_handle)| {
-                            (trait_index, unsafe { trait_handle.get() }.id)
-                        })
-                        .flat_map(|(_, trait_id)| {
-                            env.get_trait(trait_id).unwrap().required.iter().copied()
-                        })
-                        .collect();
-
-                    let impld_struct =
-                        wrap_error!(self.nth_from_top(struct_position).ensure_raw_struct());
-                    let impld_struct = unsafe { impld_struct.get() };
-                    let type_name = Rc::clone(&unsafe { impld_struct.dtable() }.type_name);
+                    let implementee = self.nth_from_top(struct_position);
+                    if let Some((type_dtable, instance_dtable)) =
+                        Self::get_builtin_dispatch_tables(implementee, library)
+                    {
+                        wrap_error!(self.extend_builtin_type(
+                            env,
+                            library,
+                            gc,
+                            proto,
+                            type_dtable,
+                            instance_dtable
+                        ));
+                    } else {
+                        let traits: Vec<_> = {
+                            // TODO: Maybe get rid of this allocation, or hoist it into the fiber?
+                            let mut traits = vec![];
+                            for trait_value in &self.stack[self.stack.len() - struct_position + 1..]
+                            {
+                                traits.push(wrap_error!(trait_value.ensure_raw_trait()));
+                            }
+                            traits
+                        };
+                        let trait_prototypes = || {
+                            traits.iter().map(|trait_handle| {
+                                env.get_trait(unsafe { trait_handle.get() }.id).unwrap()
+                            })
+                        };
+                        let mut unimplemented_trait_methods: HashSet<_> = trait_prototypes()
+                            .flat_map(|prototype| prototype.required.iter().copied())
+                            .collect();
+                        let mut unimplemented_trait_statics: HashSet<_> = trait_prototypes()
+                            .flat_map(|prototype| prototype.required_statics.iter().copied())
+                            .collect();
+
+                        let struct_value = self.nth_from_top(struct_position);
+                        let impld_struct = wrap_error!(struct_value.ensure_raw_struct());
+                        let impld_struct = unsafe { impld_struct.get() };
+                        let type_name = Rc::clone(&unsafe { impld_struct.dtable() }.type_name);
+
+                        let mut type_dtable = DispatchTable::new_for_type(Rc::clone(&type_name));
+                        self.initialize_dtable(
+                            proto.statics.iter().map(|(&k, &v)| (k, v)),
+                            env,
+                            gc,
+                            &mut type_dtable,
+                        );
+                        wrap_error!(self.initialize_dtable_with_trait_methods(
+                            proto.trait_statics.iter().map(
+                                |((name, arity, trait_index), &function_index)| {
+                                    (Rc::clone(name), *arity, *trait_index, function_index)
+                                },
+                            ),
+                            &traits,
+                            env,
+                            gc,
+                            &mut type_dtable,
+                            &mut unimplemented_trait_statics,
+                            true,
+                        ));
+
+                        let mut instance_dtable = DispatchTable::new_for_instance(type_name);
+                        instance_dtable.fields = proto.fields.clone();
+                        instance_dtable.set_type_value(struct_value);
+                        self.initialize_dtable(
+                            proto.instance.iter().map(|(&k, &v)| (k, v)),
+                            env,
+                            gc,
+                            &mut instance_dtable,
+                        );
+                        wrap_error!(self.initialize_dtable_with_trait_methods(
+                            proto.trait_instance.iter().map(
+                                |((name, arity, trait_index), &function_index)| {
+                                    (Rc::clone(name), *arity, *trait_index, function_index)
+                                },
+                            ),
+                            &traits,
+                            env,
+                            gc,
+                            &mut instance_dtable,
+                            &mut unimplemented_trait_methods,
+                            false,
+                        ));
+                        unimplemented_trait_methods.extend(unimplemented_trait_statics);
+                        wrap_error!(Self::ensure_trait_methods_implemented(
+                            env,
+                            &instance_dtable.pretty_name,
+                            &unimplemented_trait_methods,
+                        ));
 
-                    let mut type_dtable = DispatchTable::new_for_type(Rc::clone(&type_name));
-                    self.initialize_dtable(
-                        proto.statics.iter().map(|(&k, &v)| (k, v)),
-                        env,
-                        gc,
-                        &mut type_dtable,
-                    );
+                        let instance_dtable = gc.allocate(instance_dtable);
+                        type_dtable.instance = Some(instance_dtable);
+                        let type_dtable = gc.allocate(type_dtable);
 
-                    let mut instance_dtable = DispatchTable::new_for_instance(type_name);
-                    self.initialize_dtable(
-                        proto.instance.iter().map(|(&k, &v)| (k, v)),
-                        env,
-                        gc,
-                        &mut instance_dtable,
-                    );
-                    wrap_error!(self.initialize_dtable_with_trait_methods(
-                        proto.trait_instance.iter().map(
-                            |((name, arity, trait_index), &function_index)| {
-                                (Rc::clone(name), *arity, *trait_index, function_index)
-                            },
-                        ),
-                        &traits,
-                        env,
-                        gc,
-                        &mut instance_dtable,
-                        &mut unimplemented_trait_methods,
-                    ));
-
-                    let instance_dtable = gc.allocate(instance_dtable);
-                    type_dtable.instance = Some(instance_dtable);
-                    let type_dtable = gc.allocate(type_dtable);
+                        for _ in 0..proto.implemented_trait_count {
+                            let _ = self.pop();
+                        }
 
-                    for _ in 0..proto.implemented_trait_count {
-                        let _ = self.pop();
+                        impld_struct
+                            .implement(type_dtable)
+                            .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
                     }
-
-                    impld_struct
-                        .implement(type_dtable)
-                        .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
                 }
 
                 Opcode::Negate => {
-                    let number = wrap_error!(self.pop().ensure_number());
-                    self.push(RawValue::from(-number));
+                    if !call_operator!() {
+                        let number = wrap_error!(self.pop().ensure_number());
+                        self.push(RawValue::from(-number));
+                    }
+                }
+                Opcode::Add | Opcode::AddNumber => {
+                    if let Some((left, right)) = number_operands!() {
+                        if opcode == Opcode::Add {
+                            quicken!(Opcode::AddNumber);
+                        }
+                        arithmetic_result!(left + right);
+                    } else {
+                        if opcode == Opcode::AddNumber {
+                            quicken!(Opcode::Add);
+                        }
+                        if !call_operator!() {
+                            binary_operator!(+)
+                        }
+                    }
+                }
+                Opcode::Subtract | Opcode::SubtractNumber => {
+                    if let Some((left, right)) = number_operands!() {
+                        if opcode == Opcode::Subtract {
+                            quicken!(Opcode::SubtractNumber);
+                        }
+                        arithmetic_result!(left - right);
+                    } else {
+                        if opcode == Opcode::SubtractNumber {
+                            quicken!(Opcode::Subtract);
+                        }
+                        if !call_operator!() {
+                            binary_operator!(-)
+                        }
+                    }
+                }
+                Opcode::Multiply | Opcode::MultiplyNumber => {
+                    if let Some((left, right)) = number_operands!() {
+                        if opcode == Opcode::Multiply {
+                            quicken!(Opcode::MultiplyNumber);
+                        }
+                        arithmetic_result!(left * right);
+                    } else {
+                        if opcode == Opcode::MultiplyNumber {
+                            quicken!(Opcode::Multiply);
+                        }
+                        let left = self.nth_from_top(2);
+                        // Other values are multiplied by calling a method on them. Numbers on the
+                        // left-hand side are moved to the right, such that `3 * x` means `x * 3`.
+                        if left.kind() == ValueKind::Number {
+                            self.swap_stack_top();
+                        }
+                        let (method_index, argument_count) = operand.unpack();
+                        let receiver = self.nth_from_top(argument_count as usize);
+                        let dtable = Self::get_dispatch_table(receiver, library);
+                        if let Some(closure) =
+                            dtable.get_method(MethodIndex::from_u16(method_index))
+                        {
+                            enter_function!(closure, argument_count as usize);
+                        } else {
+                            // Values that can't be multiplied get the usual type error.
+                            binary_operator!(*)
+                        }
+                    }
+                }
+                Opcode::Divide => {
+                    if !call_operator!() {
+                        let right = wrap_error!(self.pop().ensure_number());
+                        let left = wrap_error!(self.pop().ensure_number());
+                        let quotient =
+                            wrap_error!(library.arithmetic.check_division(right, left / right));
+                        self.push(RawValue::from(quotient));
+                    }
+                }
+                Opcode::FloorDivide => {
+                    let right = wrap_error!(self.pop().ensure_number());
+                    let left = wrap_error!(self.pop().ensure_number());
+                    let quotient = wrap_error!(library
+                        .arithmetic
+                        .check_division(right, (left / right).floor()));
+                    self.push(RawValue::from(quotient));
+                }
+                Opcode::Modulo => {
+                    if !call_operator!() {
+                        let right = wrap_error!(self.pop().ensure_number());
+                        let left = wrap_error!(self.pop().ensure_number());
+                        let remainder = wrap_error!(library
+                            .arithmetic
+                            .check_division(right, floored_remainder(left, right)));
+                        self.push(RawValue::from(remainder));
+                    }
                 }
-                Opcode::Add => binary_operator!(+),
-                Opcode::Subtract => binary_operator!(-),
-                Opcode::Multiply => binary_operator!(*),
-                Opcode::Divide => binary_operator!(/),
 
                 Opcode::Not => {
                     let value = self.stack_top();
-                    *self.stack_top_mut() = RawValue::from(!value.is_truthy());
+                    self.set_stack_top(RawValue::from(!value.is_truthy()));
                 }
                 Opcode::Equal => {
-                    let right = self.pop();
-                    let left = self.stack_top();
-                    *self.stack_top_mut() = RawValue::from(left.eq(&right));
+                    let overloadable = matches!(
+                        self.nth_from_top(2).kind(),
+                        ValueKind::Struct | ValueKind::UserData
+                    );
+                    if !overloadable || !call_operator!() {
+                        let right = self.pop();
+                        let left = self.stack_top();
+                        self.set_stack_top(RawValue::from(left.eq(&right)));
+                    }
                 }
-                Opcode::Less => {
-                    let right = self.pop();
-                    let left = self.stack_top();
-                    let is_less = if let Some(ordering) = wrap_error!(left.try_partial_cmp(&right))
-                    {
-                        ordering.is_lt()
+                Opcode::Less | Opcode::LessNumber => {
+                    if let Some((left, right)) = number_operands!() {
+                        if opcode == Opcode::Less {
+                            quicken!(Opcode::LessNumber);
+                        }
+                        number_result!(left < right);
                     } else {
-                        false
-                    };
-                    *self.stack_top_mut() = RawValue::from(is_less);
+                        if opcode == Opcode::LessNumber {
+                            quicken!(Opcode::Less);
+                        }
+                        if !call_comparison!(Ordering::is_lt, Ordering::is_gt) {
+                            let right = self.pop();
+                            let left = self.stack_top();
+                            let is_less =
+                                if let Some(ordering) = wrap_error!(left.try_partial_cmp(&right)) {
+                                    ordering.is_lt()
+                                } else {
+                                    false
+                                };
+                            self.set_stack_top(RawValue::from(is_less));
+                        }
+                    }
                 }
-                Opcode::LessEqual => {
-                    let right = self.pop();
-                    let left = self.stack_top();
-                    let is_less = if let Some(ordering) = wrap_error!(left.try_partial_cmp(&right))
-                    {
-                        ordering.is_le()
+                Opcode::LessEqual | Opcode::LessEqualNumber => {
+                    if let Some((left, right)) = number_operands!() {
+                        if opcode == Opcode::LessEqual {
+                            quicken!(Opcode::LessEqualNumber);
+                        }
+                        number_result!(left <= right);
                     } else {
-                        false
-                    };
-                    *self.stack_top_mut() = RawValue::from(is_less);
+                        if opcode == Opcode::LessEqualNumber {
+                            quicken!(Opcode::LessEqual);
+                        }
+                        if !call_comparison!(Ordering::is_le, Ordering::is_ge) {
+                            let right = self.pop();
+                            let left = self.stack_top();
+                            let is_less =
+                                if let Some(ordering) = wrap_error!(left.try_partial_cmp(&right)) {
+                                    ordering.is_le()
+                                } else {
+                                    false
+                                };
+                            self.set_stack_top(RawValue::from(is_less));
+                        }
+                    }
+                }
+
+                Opcode::Implements => {
+                    let trait_v = self.pop();
+                    let implements =
+                        wrap_error!(Self::implements(self.stack_top(), trait_v, env, library));
+                    self.set_stack_top(RawValue::from(implements));
+                }
+
+                Opcode::AddLocals => {
+                    let left = self.local(usize::from(operand));
+                    // The other local is the one loaded by the `GetLocal` that follows.
+                    let mut next = pc;
+                    let (_, right_slot) = unsafe { self.chunk.read_instruction(&mut next) };
+                    let right = self.local(usize::from(right_slot));
+                    if left.kind() == ValueKind::Number && right.kind() == ValueKind::Number {
+                        let sum =
+                            unsafe { left.get_number_unchecked() + right.get_number_unchecked() };
+                        let sum = wrap_error!(library.arithmetic.check(sum));
+                        self.push(RawValue::from(sum));
+                        pc += 2 * Opcode::INSTRUCTION_SIZE;
+                    } else {
+                        self.push(left);
+                    }
+                }
+                Opcode::LessNumberJump => number_comparison_jump!(<),
+                Opcode::LessEqualNumberJump => number_comparison_jump!(<=),
+                Opcode::GetLocalCall => {
+                    let value = self.local(usize::from(operand));
+                    self.push(value);
+                    // The return point is after the `Call`, and blocked calls are retried from
+                    // it, like they would be without fusing.
+                    let (_, operand) = unsafe { self.chunk.read_instruction(&mut pc) };
+                    call_function!(usize::from(operand) + 1);
                 }
 
                 Opcode::Halt => {
@@ -992,8 +2935,111 @@ impl Fiber {
     }
 }
 
+/// A function call on a fiber's call stack, as seen by a debugger.
+#[derive(Debug, Clone, Copy)]
+pub struct CallFrame<'f> {
+    chunk: &'f Rc<Chunk>,
+    closure: Option<GcRaw<Closure>>,
+    pc: usize,
+    stack: &'f [RawValue],
+    /// Where the frame's stack begins, and the fiber's open upvalues, which hold the frame's
+    /// captured variables.
+    #[cfg(feature = "forbid-unsafe")]
+    captured: (usize, &'f [OpenUpvalue]),
+}
+
+impl<'f> CallFrame<'f> {
+    /// Returns the chunk of bytecode executing in this frame.
+    pub fn chunk(&self) -> &'f Chunk {
+        self.chunk
+    }
+
+    /// Returns the source location of the instruction executing in this frame.
+    pub fn location(&self) -> Location {
+        self.chunk.location(self.pc)
+    }
+
+    /// Returns the name of the function executing in this frame. The top level of a module is
+    /// named `<main>`.
+    pub fn function_name(&self, env: &Environment) -> Rc<str> {
+        match self.closure {
+            Some(closure) => {
+                let closure = unsafe { closure.get() };
+                let function = unsafe { env.get_function_unchecked(closure.function_id) };
+                Rc::clone(&function.name)
+            }
+            None => Rc::from("<main>"),
+        }
+    }
+
+    /// Returns the names and values of the local variables that are in scope at the instruction
+    /// executing in this frame.
+    pub fn local_variables(&self) -> Vec<(Rc<str>, RawValue)> {
+        self.chunk
+            .local_variables_at(self.pc)
+            .filter_map(|variable| {
+                let value = *self.stack.get(variable.stack_slot as usize)?;
+                #[cfg(feature = "forbid-unsafe")]
+                let value = {
+                    let (stack_bottom, open_upvalues) = self.captured;
+                    let stack_slot = stack_bottom + variable.stack_slot as usize;
+                    captured_local(open_upvalues, stack_slot)
+                        .map_or(value, |upvalue| unsafe { upvalue.get() })
+                };
+                Some((Rc::clone(&variable.name), value))
+            })
+            .collect()
+    }
+
+    /// Returns the names and values of the variables captured by the function executing in this
+    /// frame.
+    pub fn upvalues(&self) -> Vec<(Rc<str>, RawValue)> {
+        let Some(closure) = self.closure else {
+            return Vec::new();
+        };
+        let closure = unsafe { closure.get() };
+        self.chunk
+            .upvalue_names
+            .iter()
+            .zip(&closure.captures)
+            .map(|(name, upvalue)| (Rc::clone(name), unsafe { upvalue.get() }))
+            .collect()
+    }
+}
+
 impl fmt::Debug for Fiber {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Fiber").finish_non_exhaustive()
     }
 }
+
+/// Returns the upvalue holding the local variable in the given stack slot, if there's one among the
+/// given open upvalues.
+#[cfg(feature = "forbid-unsafe")]
+fn captured_local(open_upvalues: &[OpenUpvalue], stack_slot: usize) -> Option<&Upvalue> {
+    open_upvalues
+        .iter()
+        .rev()
+        .find(|(slot, _)| *slot as usize == stack_slot)
+        .map(|(_, upvalue)| &**upvalue)
+}
+
+/// Returns the remainder of floor division, which has the same sign as the divisor. This is what
+/// `%` and `Number.mod` evaluate to.
+pub(crate) fn floored_remainder(left: f64, right: f64) -> f64 {
+    let remainder = left % right;
+    // Rust's `%` truncates, so the remainder has to be adjusted to floor.
+    if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+        remainder + right
+    } else {
+        remainder
+    }
+}
+
+/// Returns the user data stored in a value, or `None` if the value isn't user data.
+fn user_data_of<'a>(value: RawValue) -> Option<&'a dyn UserData> {
+    // Safety: values on the stack are reachable, so they're kept alive by the GC.
+    value
+        .get_raw_user_data()
+        .map(|user_data| unsafe { &**user_data.get() })
+}
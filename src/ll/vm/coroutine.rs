@@ -0,0 +1,167 @@
+//! Coroutines, which are fibers that scripts resume themselves.
+//!
+//! Resuming a coroutine interprets its fiber from within the control function that resumed it.
+//! The resuming fiber's stack is out of reach of the GC while that happens, so its roots are
+//! handed to the GC for the duration of the nested run.
+
+use std::{any::Any, borrow::Cow, cell::RefCell, cmp::Ordering, fmt, hash::Hasher};
+
+use super::{Fiber, Globals};
+use crate::ll::{
+    bytecode::{DispatchTable, Environment, Library, Opcode},
+    error::{LanguageError, LanguageErrorKind},
+    gc::{Gc, GcRaw, Memory},
+    value::{RawValue, UserData},
+};
+
+/// A fiber that can be resumed by other fibers, and hands values back to them using `yield`.
+pub struct Coroutine {
+    dtable: Gc<DispatchTable>,
+    /// The coroutine's fiber. It's borrowed mutably while the coroutine is running, which is how
+    /// resuming a coroutine that's already running is caught.
+    fiber: RefCell<Fiber>,
+}
+
+impl Coroutine {
+    /// Creates a new coroutine running the given fiber, with the given instance dispatch table.
+    pub fn new(dtable: GcRaw<DispatchTable>, fiber: Fiber) -> Self {
+        Self {
+            dtable: unsafe { Gc::from_raw(dtable) },
+            fiber: RefCell::new(fiber),
+        }
+    }
+
+    /// Returns whether the coroutine's fiber has halted, either because it finished or because
+    /// of an error.
+    pub fn is_finished(&self) -> bool {
+        self.fiber.try_borrow().is_ok_and(|fiber| fiber.halted())
+    }
+}
+
+impl fmt::Debug for Coroutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Coroutine").finish_non_exhaustive()
+    }
+}
+
+impl UserData for Coroutine {
+    fn dtable_gcraw(&self, _: Option<&Library>) -> GcRaw<DispatchTable> {
+        Gc::as_raw(&self.dtable)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn partial_eq(&self, other: &dyn UserData) -> bool {
+        std::ptr::addr_eq(self as *const Self, other as *const dyn UserData)
+    }
+
+    fn try_partial_cmp(&self, _: &dyn UserData) -> Result<Option<Ordering>, LanguageErrorKind> {
+        Ok(None)
+    }
+
+    fn hash(&self, mut hasher: &mut dyn Hasher) {
+        std::ptr::hash(self, &mut hasher);
+    }
+
+    fn type_name(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.dtable.pretty_name)
+    }
+
+    fn visit_references(&self, visit: &mut dyn FnMut(RawValue)) {
+        // A running coroutine is the fiber being interpreted, whose roots are passed to the GC
+        // directly.
+        if let Ok(fiber) = self.fiber.try_borrow() {
+            fiber.stack_roots().for_each(visit);
+        }
+    }
+}
+
+impl Fiber {
+    /// Resumes the coroutine that receives the `resume` call at the top of the stack, and
+    /// replaces the call's arguments with the value the coroutine yielded or evaluated to.
+    ///
+    /// A coroutine that blocks or runs out of budget or fuel makes this fiber block or run out of
+    /// budget or fuel too, such that the call is retried once this fiber is resumed.
+    pub(super) fn resume_coroutine(
+        &mut self,
+        env: &Environment,
+        library: &Library,
+        globals: &mut Globals,
+        gc: &mut Memory,
+        argument_count: usize,
+    ) -> Result<(), LanguageError> {
+        let retrying = self.blocked;
+        self.blocked = false;
+        self.stalled = false;
+
+        let receiver = self.nth_from_top(argument_count);
+        // SAFETY: `resume` is only ever added to the dispatch table of coroutines.
+        let coroutine = unsafe { receiver.downcast_user_data_unchecked::<Coroutine>() };
+        let Ok(mut fiber) = coroutine.fiber.try_borrow_mut() else {
+            return Err(self.error_outside_function_call(
+                None,
+                env,
+                LanguageErrorKind::FiberAlreadyRunning,
+            ));
+        };
+        if fiber.halted() {
+            return Err(self.error_outside_function_call(
+                None,
+                env,
+                LanguageErrorKind::FiberFinished,
+            ));
+        }
+        let depth_marker = library
+            .push_outer_fiber(self.call_depth())
+            .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+        if argument_count == 2 {
+            fiber.set_yield_result(self.stack_top());
+        }
+
+        fiber.set_budget(self.budget);
+        let marker = gc.push_suspended_roots(self.stack_roots());
+        let result = fiber.interpret(env, library, globals, gc);
+        gc.pop_suspended_roots(marker);
+        library.pop_outer_fiber(depth_marker);
+        // The coroutine's stack isn't visited while it's running, and it changed since.
+        unsafe { gc.rescan_later(receiver) }
+        if let Some(budget) = &mut self.budget {
+            *budget = fiber.budget().unwrap_or(0);
+        }
+        fiber.set_budget(None);
+
+        match result {
+            Ok(_) if fiber.blocked() || fiber.out_of_budget() || fiber.out_of_fuel() => {
+                // Leave the arguments on the stack and step back onto the call instruction, like
+                // blocked foreign functions do.
+                self.pc -= Opcode::INSTRUCTION_SIZE;
+                self.blocked = fiber.blocked();
+                self.stalled = retrying && fiber.stalled();
+                Ok(())
+            }
+            Ok(value) => {
+                self.stack.truncate(self.stack.len() - argument_count);
+                self.push(value);
+                Ok(())
+            }
+            Err(LanguageError::Runtime {
+                kind,
+                call_stack: coroutine_call_stack,
+                ..
+            }) => {
+                // The error is raised again in this fiber, such that it can be caught here. Its
+                // stack trace continues into the coroutine, past the chunk that called the
+                // coroutine's function.
+                self.raised = fiber.raised.take();
+                let mut error = self.error_outside_function_call(None, env, kind);
+                if let LanguageError::Runtime { call_stack, .. } = &mut error {
+                    call_stack.extend(coroutine_call_stack.into_iter().skip(1));
+                }
+                Err(error)
+            }
+            Err(error) => Err(error),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! `List.sort_by` and `List.sort_by_key`, which call back into functions while sorting.
+//!
+//! Functions called by foreign functions run in fibers of their own, where they can't block or
+//! yield on behalf of the sorting fiber. So these are control functions driven by the VM instead:
+//! the sort is a state machine that asks for one call at a time, and is resumed with the result
+//! once the called function returns.
+
+use std::mem;
+
+use super::{Fiber, Globals};
+use crate::ll::{
+    bytecode::{Environment, Library, Opcode},
+    error::{LanguageError, LanguageErrorKind},
+    gc::Memory,
+    value::{List, RawValue},
+};
+
+/// What the function passed to a sort computes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortKind {
+    /// The function compares two elements, returning a negative number, zero, or a positive
+    /// number, like `cmp` methods do.
+    Comparator,
+    /// The function maps each element to a key that is compared in place of the element.
+    Key,
+}
+
+/// A sort in progress.
+///
+/// While sorting, the stack holds the list, the function, a copy of the list's elements, and when
+/// sorting by key, the keys computed so far. Keeping them on the stack keeps them alive when the
+/// function triggers a garbage collection, and lets the function modify the list freely.
+///
+/// Comparator sorts are bottom-up merge sorts, which are stable and only need a handful of indices
+/// to remember where they left off between comparisons.
+#[derive(Debug)]
+pub(super) struct Sort {
+    kind: SortKind,
+    /// The stack index of the first element.
+    base: usize,
+    len: usize,
+    /// Element indices, in the order established so far.
+    order: Vec<usize>,
+    /// Where the current pass of merging writes its output.
+    merged: Vec<usize>,
+    /// The length of the runs merged by the current pass.
+    width: usize,
+    /// The end of the left run and the right run being merged.
+    mid: usize,
+    end: usize,
+    /// The next index to take from the left run, the right run, and to write into `merged`.
+    left: usize,
+    right: usize,
+    out: usize,
+}
+
+impl Sort {
+    fn new(kind: SortKind, base: usize, len: usize) -> Self {
+        let mut sort = Self {
+            kind,
+            base,
+            len,
+            order: (0..len).collect(),
+            merged: vec![0; len],
+            width: 1,
+            mid: 0,
+            end: 0,
+            left: 0,
+            right: 0,
+            out: 0,
+        };
+        sort.start_runs(0);
+        sort
+    }
+
+    /// Sets up merging the two runs starting at `start`.
+    fn start_runs(&mut self, start: usize) {
+        self.mid = (start + self.width).min(self.len);
+        self.end = (start + 2 * self.width).min(self.len);
+        self.left = start;
+        self.right = self.mid;
+        self.out = start;
+    }
+
+    /// Returns the indices of the elements the function should be called with next, or `None` if
+    /// the sort needs no more calls.
+    fn next_call(&mut self, stack_len: usize) -> Option<(usize, Option<usize>)> {
+        match self.kind {
+            SortKind::Comparator => loop {
+                if self.width >= self.len {
+                    return None;
+                }
+                if self.left < self.mid && self.right < self.end {
+                    return Some((self.order[self.left], Some(self.order[self.right])));
+                }
+                // One of the runs ran out, so the rest of the other one is already in order.
+                for &index in self.order[self.left..self.mid]
+                    .iter()
+                    .chain(&self.order[self.right..self.end])
+                {
+                    self.merged[self.out] = index;
+                    self.out += 1;
+                }
+                if self.end < self.len {
+                    self.start_runs(self.end);
+                } else {
+                    mem::swap(&mut self.order, &mut self.merged);
+                    self.width *= 2;
+                    self.start_runs(0);
+                }
+            },
+            SortKind::Key => {
+                let keys_computed = stack_len - self.base - self.len;
+                (keys_computed < self.len).then_some((keys_computed, None))
+            }
+        }
+    }
+
+    /// Takes the next element of the merged runs, depending on the comparator's result for the
+    /// elements returned by `next_call`.
+    fn merge(&mut self, ordering: f64) {
+        // Taking from the left run when the elements are equal keeps the sort stable.
+        if ordering > 0.0 {
+            self.merged[self.out] = self.order[self.right];
+            self.right += 1;
+        } else {
+            self.merged[self.out] = self.order[self.left];
+            self.left += 1;
+        }
+        self.out += 1;
+    }
+}
+
+impl Fiber {
+    /// Starts sorting the list below the function at the top of the stack.
+    pub(super) fn start_sort(
+        &mut self,
+        env: &Environment,
+        library: &Library,
+        globals: &mut Globals,
+        gc: &mut Memory,
+        kind: SortKind,
+    ) -> Result<(), LanguageError> {
+        let list = self.nth_from_top(2);
+        // SAFETY: Sorting functions are only ever added to the list dispatch table.
+        let elements = unsafe { list.downcast_user_data_unchecked::<List>().as_slice() };
+        let sort = Box::new(Sort::new(kind, self.stack.len(), elements.len()));
+        self.reserve_stack(elements.len());
+        self.stack.extend_from_slice(elements);
+        self.continue_sort(env, library, globals, gc, sort)
+    }
+
+    /// Calls the sorting function until it has to run in a new frame, or the sort is done.
+    pub(super) fn continue_sort(
+        &mut self,
+        env: &Environment,
+        library: &Library,
+        globals: &mut Globals,
+        gc: &mut Memory,
+        mut sort: Box<Sort>,
+    ) -> Result<(), LanguageError> {
+        while let Some((a, b)) = sort.next_call(self.stack.len()) {
+            let function = self.stack[sort.base - 1];
+            let closure = function
+                .ensure_raw_function()
+                .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+            self.push(function);
+            self.push(self.stack[sort.base + a]);
+            if let Some(b) = b {
+                self.push(self.stack[sort.base + b]);
+            }
+            let argument_count = if b.is_some() { 3 } else { 2 };
+
+            let call_depth = self.call_stack.len();
+            self.enter_function(env, library, globals, gc, closure, argument_count)?;
+            if self.blocked || self.out_of_fuel || self.yielding.is_some() {
+                // Retrying the call would start the whole sort over, so blocking isn't allowed,
+                // and neither is running out of fuel inside a foreign function. Yielding isn't
+                // either, as the fiber would only suspend once the sort is done.
+                if self.blocked || self.out_of_fuel {
+                    self.blocked = false;
+                    self.stalled = false;
+                    self.out_of_fuel = false;
+                    self.pc += Opcode::INSTRUCTION_SIZE;
+                }
+                self.yielding = None;
+                return Err(self.error_outside_function_call(
+                    None,
+                    env,
+                    LanguageErrorKind::SortFunctionBlocked,
+                ));
+            }
+            if self.call_stack.len() > call_depth {
+                self.call_stack.last_mut().unwrap().sort = Some(sort);
+                return Ok(());
+            }
+            let result = self.pop();
+            self.sort_result(&mut sort, result)
+                .map_err(|kind| self.error_outside_function_call(None, env, kind))?;
+        }
+        self.finish_sort(gc, &mut sort)
+            .map_err(|kind| self.error_outside_function_call(None, env, kind))
+    }
+
+    /// Feeds the result of a call to the sorting function into the sort.
+    pub(super) fn sort_result(
+        &mut self,
+        sort: &mut Sort,
+        result: RawValue,
+    ) -> Result<(), LanguageErrorKind> {
+        match sort.kind {
+            SortKind::Comparator => sort.merge(result.ensure_number()?),
+            SortKind::Key => self.push(result),
+        }
+        Ok(())
+    }
+
+    /// Stores the sorted elements in the list, and replaces the sort's values on the stack with
+    /// `nil`.
+    fn finish_sort(&mut self, gc: &mut Memory, sort: &mut Sort) -> Result<(), LanguageErrorKind> {
+        let elements = &self.stack[sort.base..sort.base + sort.len];
+        if sort.kind == SortKind::Key {
+            let keys = &self.stack[sort.base + sort.len..];
+            let mut error = None;
+            sort.order.sort_by(|&a, &b| {
+                keys[a].total_cmp(&keys[b]).unwrap_or_else(|e| {
+                    error.get_or_insert(e);
+                    std::cmp::Ordering::Equal
+                })
+            });
+            if let Some(error) = error {
+                return Err(error);
+            }
+        }
+        let sorted = sort.order.iter().map(|&index| elements[index]).collect();
+        let list = self.stack[sort.base - 2];
+        unsafe {
+            *list.downcast_user_data_unchecked::<List>().get_mut() = sorted;
+            gc.rescan_later(list);
+        }
+        self.stack.truncate(sort.base - 2);
+        self.push(RawValue::from(()));
+        Ok(())
+    }
+}
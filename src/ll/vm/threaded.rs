@@ -0,0 +1,285 @@
+//! Threaded dispatch, enabled by the `threaded-dispatch` feature.
+//!
+//! Every instruction handled here has a handler of its own, which executes the instruction,
+//! decodes the next one, and tail-calls the next handler directly. This gives each instruction its
+//! own indirect branch, which the CPU can predict based on which instruction came before it,
+//! unlike the single branch the `match` in `Fiber::run` compiles down to. Rust only guarantees
+//! that tail calls don't grow the stack with the nightly-only `become` keyword, which is why this
+//! is a feature rather than the default.
+//!
+//! Only straight-line instructions that can't call functions, allocate, suspend, or raise errors
+//! are handled, and only while the fiber isn't metered and arithmetic isn't checked, so that none
+//! of the checks `Fiber::run` performs are skipped. Any other instruction hands control back to the
+//! `match`, which executes it and enters threaded code again at the next instruction.
+
+use super::Fiber;
+use crate::ll::{
+    bytecode::{Chunk, Opcode, Opr24},
+    value::{RawValue, ValueKind},
+};
+
+/// A handler executing an instruction. It receives the operand of the instruction and the program
+/// counter pointing past it, and returns the program counter of the first instruction it couldn't
+/// handle.
+type Handler = fn(&mut Fiber, &Chunk, usize, Opr24) -> usize;
+
+static HANDLERS: [Handler; 256] = {
+    let mut handlers = [exit as Handler; 256];
+    handlers[Opcode::Nop as usize] = nop;
+    handlers[Opcode::PushNil as usize] = push_nil;
+    handlers[Opcode::PushTrue as usize] = push_true;
+    handlers[Opcode::PushFalse as usize] = push_false;
+    handlers[Opcode::PushNumber as usize] = push_number;
+    handlers[Opcode::AssignLocal as usize] = assign_local;
+    handlers[Opcode::SinkLocal as usize] = sink_local;
+    handlers[Opcode::GetLocal as usize] = get_local;
+    handlers[Opcode::Swap as usize] = swap;
+    handlers[Opcode::Duplicate as usize] = duplicate;
+    handlers[Opcode::Discard as usize] = discard;
+    handlers[Opcode::JumpForward as usize] = jump_forward;
+    handlers[Opcode::JumpForwardIfFalsy as usize] = jump_forward_if_falsy;
+    handlers[Opcode::JumpForwardIfTruthy as usize] = jump_forward_if_truthy;
+    handlers[Opcode::JumpBackward as usize] = jump_backward;
+    handlers[Opcode::Add as usize] = add;
+    handlers[Opcode::AddNumber as usize] = add;
+    handlers[Opcode::Subtract as usize] = subtract;
+    handlers[Opcode::SubtractNumber as usize] = subtract;
+    handlers[Opcode::Multiply as usize] = multiply;
+    handlers[Opcode::MultiplyNumber as usize] = multiply;
+    handlers[Opcode::Not as usize] = not;
+    handlers[Opcode::Less as usize] = less;
+    handlers[Opcode::LessNumber as usize] = less;
+    handlers[Opcode::LessEqual as usize] = less_equal;
+    handlers[Opcode::LessEqualNumber as usize] = less_equal;
+    handlers[Opcode::AddLocals as usize] = add_locals;
+    handlers[Opcode::LessNumberJump as usize] = less_number_jump;
+    handlers[Opcode::LessEqualNumberJump as usize] = less_equal_number_jump;
+    handlers
+};
+
+/// Runs instructions starting at `pc` for as long as they have handlers, and returns the program
+/// counter of the first one that doesn't.
+pub(super) fn run(fiber: &mut Fiber, mut pc: usize) -> usize {
+    // SAFETY: None of the handlers call functions, so the chunk stays the same throughout.
+    let chunk = unsafe { &*std::rc::Rc::as_ptr(&fiber.chunk) };
+    let (opcode, operand) = unsafe { chunk.read_instruction(&mut pc) };
+    HANDLERS[opcode as usize](fiber, chunk, pc, operand)
+}
+
+/// Decodes the instruction at `pc` and tail-calls its handler.
+macro_rules! next {
+    ($fiber:expr, $chunk:expr, $pc:expr) => {{
+        let mut pc = $pc;
+        let (opcode, operand) = unsafe { $chunk.read_instruction(&mut pc) };
+        become HANDLERS[opcode as usize]($fiber, $chunk, pc, operand)
+    }};
+}
+
+/// Returns both operands of a binary operator if they're numbers.
+fn number_operands(fiber: &Fiber) -> Option<(f64, f64)> {
+    let right = fiber.stack_top();
+    let left = fiber.nth_from_top(2);
+    if left.kind() == ValueKind::Number && right.kind() == ValueKind::Number {
+        Some(unsafe { (*left.get_number_unchecked(), *right.get_number_unchecked()) })
+    } else {
+        None
+    }
+}
+
+/// Replaces the two values at the top of the stack with the result of a binary operator.
+fn binary_result(fiber: &mut Fiber, result: RawValue) {
+    fiber.pop();
+    fiber.set_stack_top(result);
+}
+
+fn exit(_: &mut Fiber, _: &Chunk, pc: usize, _: Opr24) -> usize {
+    pc - Opcode::INSTRUCTION_SIZE
+}
+
+fn nop(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    next!(fiber, chunk, pc)
+}
+
+fn push_nil(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    fiber.push(RawValue::from(()));
+    next!(fiber, chunk, pc)
+}
+
+fn push_true(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    fiber.push(RawValue::from(true));
+    next!(fiber, chunk, pc)
+}
+
+fn push_false(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    fiber.push(RawValue::from(false));
+    next!(fiber, chunk, pc)
+}
+
+fn push_number(fiber: &mut Fiber, chunk: &Chunk, mut pc: usize, _: Opr24) -> usize {
+    let number = unsafe { chunk.read_number(&mut pc) };
+    fiber.push(RawValue::from(number));
+    next!(fiber, chunk, pc)
+}
+
+fn assign_local(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let value = fiber.stack_top();
+    fiber.set_local(usize::from(operand), value);
+    next!(fiber, chunk, pc)
+}
+
+fn sink_local(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let value = fiber.pop();
+    fiber.set_local(usize::from(operand), value);
+    next!(fiber, chunk, pc)
+}
+
+fn get_local(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let value = fiber.stack[fiber.stack_bottom + usize::from(operand)];
+    fiber.push(value);
+    next!(fiber, chunk, pc)
+}
+
+fn swap(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    fiber.swap_stack_top();
+    next!(fiber, chunk, pc)
+}
+
+fn duplicate(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    fiber.push(fiber.stack_top());
+    next!(fiber, chunk, pc)
+}
+
+fn discard(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    fiber.pop();
+    next!(fiber, chunk, pc)
+}
+
+fn jump_forward(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    next!(fiber, chunk, pc + usize::from(operand))
+}
+
+fn jump_forward_if_falsy(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let amount = if fiber.stack_top().is_falsy() {
+        usize::from(operand)
+    } else {
+        0
+    };
+    next!(fiber, chunk, pc + amount)
+}
+
+fn jump_forward_if_truthy(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let amount = if fiber.stack_top().is_truthy() {
+        usize::from(operand)
+    } else {
+        0
+    };
+    next!(fiber, chunk, pc + amount)
+}
+
+fn jump_backward(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    next!(fiber, chunk, pc - usize::from(operand))
+}
+
+// Operators are only handled when both operands are numbers. Anything else may be overloaded or
+// raise a type error, which is left to the `match`. Quickened opcodes share handlers with their
+// generic counterparts, as the check for numbers is needed either way.
+
+fn add(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let Some((left, right)) = number_operands(fiber) else {
+        return exit(fiber, chunk, pc, operand);
+    };
+    binary_result(fiber, RawValue::from(left + right));
+    next!(fiber, chunk, pc)
+}
+
+fn subtract(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let Some((left, right)) = number_operands(fiber) else {
+        return exit(fiber, chunk, pc, operand);
+    };
+    binary_result(fiber, RawValue::from(left - right));
+    next!(fiber, chunk, pc)
+}
+
+fn multiply(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let Some((left, right)) = number_operands(fiber) else {
+        return exit(fiber, chunk, pc, operand);
+    };
+    binary_result(fiber, RawValue::from(left * right));
+    next!(fiber, chunk, pc)
+}
+
+fn not(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    let value = fiber.stack_top();
+    fiber.set_stack_top(RawValue::from(!value.is_truthy()));
+    next!(fiber, chunk, pc)
+}
+
+fn less(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let Some((left, right)) = number_operands(fiber) else {
+        return exit(fiber, chunk, pc, operand);
+    };
+    binary_result(fiber, RawValue::from(left < right));
+    next!(fiber, chunk, pc)
+}
+
+fn less_equal(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let Some((left, right)) = number_operands(fiber) else {
+        return exit(fiber, chunk, pc, operand);
+    };
+    binary_result(fiber, RawValue::from(left <= right));
+    next!(fiber, chunk, pc)
+}
+
+fn add_locals(fiber: &mut Fiber, chunk: &Chunk, pc: usize, operand: Opr24) -> usize {
+    let left = fiber.stack[fiber.stack_bottom + usize::from(operand)];
+    let mut next = pc;
+    let (_, right_slot) = unsafe { chunk.read_instruction(&mut next) };
+    let right = fiber.stack[fiber.stack_bottom + usize::from(right_slot)];
+    if left.kind() == ValueKind::Number && right.kind() == ValueKind::Number {
+        let sum = unsafe { left.get_number_unchecked() + right.get_number_unchecked() };
+        fiber.push(RawValue::from(sum));
+        next!(fiber, chunk, pc + 2 * Opcode::INSTRUCTION_SIZE)
+    } else {
+        fiber.push(left);
+        next!(fiber, chunk, pc)
+    }
+}
+
+/// Performs a `LessNumberJump` or `LessEqualNumberJump`, and returns the program counter to
+/// continue at.
+fn number_comparison_jump(
+    fiber: &mut Fiber,
+    chunk: &Chunk,
+    mut pc: usize,
+    compare: fn(f64, f64) -> bool,
+) -> usize {
+    let number = unsafe { chunk.read_number(&mut pc) };
+    let left = fiber.stack_top();
+    if left.kind() != ValueKind::Number {
+        fiber.push(RawValue::from(number));
+        return pc;
+    }
+    let is_true = compare(unsafe { *left.get_number_unchecked() }, number);
+    pc += Opcode::INSTRUCTION_SIZE;
+    let (jump, jump_operand) = unsafe { chunk.read_instruction(&mut pc) };
+    if is_true {
+        fiber.pop();
+        pc + Opcode::INSTRUCTION_SIZE
+    } else {
+        fiber.set_stack_top(RawValue::from(false));
+        pc + match jump {
+            Opcode::JumpForwardIfFalsy => usize::from(jump_operand),
+            _ => unsafe { chunk.long_jump_offset(jump_operand) },
+        }
+    }
+}
+
+fn less_number_jump(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    let pc = number_comparison_jump(fiber, chunk, pc, |a, b| a < b);
+    next!(fiber, chunk, pc)
+}
+
+fn less_equal_number_jump(fiber: &mut Fiber, chunk: &Chunk, pc: usize, _: Opr24) -> usize {
+    let pc = number_comparison_jump(fiber, chunk, pc, |a, b| a <= b);
+    next!(fiber, chunk, pc)
+}
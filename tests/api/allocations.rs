@@ -0,0 +1,67 @@
+use mica::{Engine, Value};
+
+use super::run;
+
+#[test]
+fn snapshots_are_only_available_while_tracking() {
+    let mut engine = Engine::new();
+    assert!(engine.allocation_snapshot().is_none());
+    engine.set_allocation_tracking(true);
+    assert!(engine.allocation_snapshot().is_some());
+    engine.set_allocation_tracking(false);
+    assert!(engine.allocation_snapshot().is_none());
+}
+
+#[test]
+fn live_objects_are_counted_per_kind() {
+    let mut engine = Engine::new();
+    engine.set_allocation_tracking(true);
+    let _lists: Value = run(
+        &mut engine,
+        r#"
+            let lists = [[1], [2], ["a": 1]]
+            Gc.collect()
+            lists
+        "#,
+    );
+    let snapshot = engine.allocation_snapshot().unwrap();
+    let lists = snapshot.get("List").unwrap();
+    assert_eq!(lists.count, 3);
+    assert!(lists.bytes > 0);
+    assert_eq!(snapshot.get("Dict").unwrap().count, 1);
+    assert!(snapshot.count() >= 4);
+}
+
+#[test]
+fn objects_allocated_before_tracking_are_counted() {
+    let mut engine = Engine::new();
+    let _list: Value = run(&mut engine, "[1, 2, 3]");
+    engine.set_allocation_tracking(true);
+    let snapshot = engine.allocation_snapshot().unwrap();
+    assert_eq!(snapshot.get("List").unwrap().count, 1);
+}
+
+#[test]
+fn diff_shows_leaking_kinds() {
+    let mut engine = Engine::new();
+    engine.set_allocation_tracking(true);
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let leaked = []
+            func tick() = do
+                leaked.push([])
+                let temporary = "a".cat("b")
+            end
+        "#,
+    );
+    let before = engine.allocation_snapshot().unwrap();
+    let _: Value = run(&mut engine, "tick()\ntick()\nGc.collect()");
+    let after = engine.allocation_snapshot().unwrap();
+
+    let diff = after.diff(&before);
+    assert_eq!(diff.get("List").unwrap().count, 2);
+    assert!(diff.get("List").unwrap().bytes > 0);
+    assert!(diff.get("String").is_none());
+    assert!(after.diff(&after).is_empty());
+}
@@ -0,0 +1,96 @@
+use mica::{Engine, Error, Value};
+
+use super::RevealResultExt;
+
+#[test]
+fn resetting_the_heap_frees_objects_created_by_scripts() {
+    let mut engine = Engine::new();
+    engine.set_arena(Some(4096));
+    // Objects that die while the script is running are finalized, but stay in the arena.
+    engine.set_gc_stress(true);
+    let source = r#"
+        struct Point impl
+            func new(x, y) constructor = do @x = x  @y = y end
+        end
+        let points = []
+        let i = 0
+        while i < 100 do
+            points.push(Point.new(i, { x: i, y: (i, i.to_string) }))
+            i = i + 1
+        end
+        let lookup = ["a": 1]
+        points.len
+    "#;
+    for _ in 0..3 {
+        let count: f64 = engine
+            .start("run.mi", source)
+            .reveal()
+            .trampoline()
+            .reveal();
+        assert_eq!(count, 100.0);
+        engine.reset_heap().reveal();
+    }
+}
+
+#[test]
+fn objects_held_by_the_host_keep_the_heap_from_being_reset() {
+    let mut engine = Engine::new();
+    engine.set_arena(Some(4096));
+    let list: Value = engine
+        .start("run.mi", "[1, 2, 3]")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(matches!(
+        engine.reset_heap(),
+        Err(Error::HeapInUse { live_objects: 1 })
+    ));
+    drop(list);
+    engine.reset_heap().reveal();
+}
+
+#[test]
+fn globals_are_left_alone_if_the_heap_cannot_be_reset() {
+    let mut engine = Engine::new();
+    engine.set_arena(Some(4096));
+    let held: Value = engine
+        .start(
+            "run.mi",
+            "let names = [\"alice\"]\nlet count = 1\n(names, \"bob\")",
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(matches!(engine.reset_heap(), Err(Error::HeapInUse { .. })));
+    let still_there: bool = engine
+        .start("run.mi", "names[0] == \"alice\" and count == 1")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(still_there);
+    drop(held);
+    engine.reset_heap().reveal();
+    let names: Value = engine.get("names").reveal();
+    assert!(matches!(names, Value::Nil));
+}
+
+#[test]
+fn functions_registered_before_enabling_the_arena_survive_resets() {
+    let mut engine = Engine::new();
+    engine.add_function("double", |x: f64| x * 2.0).reveal();
+    engine.set_arena(Some(1024));
+    let _: Value = engine
+        .start("run.mi", "let greetings = [\"hi\"]")
+        .reveal()
+        .trampoline()
+        .reveal();
+    engine.reset_heap().reveal();
+    let result: f64 = engine
+        .start("run.mi", "double(21)")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(result, 42.0);
+    let greetings: Value = engine.get("greetings").reveal();
+    assert!(matches!(greetings, Value::Nil));
+}
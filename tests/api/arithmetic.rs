@@ -0,0 +1,133 @@
+use mica::{Arithmetic, Engine, LanguageErrorKind, TypeBuilder, UserData, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+#[test]
+fn division_by_zero_follows_ieee_by_default() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.arithmetic(), Arithmetic::Ieee);
+    let _: Value = run(
+        &mut engine,
+        "assert(1 / 0 == Number.infinity)\nassert(-1 / 0 == -Number.infinity)\nassert((0 / 0).is_nan)",
+    );
+}
+
+#[test]
+fn checked_division_by_zero_raises_an_error() {
+    let mut engine = Engine::new();
+    engine.set_arithmetic(Arithmetic::Checked);
+    for source in ["1 / 0", "0 / 0", "let zero = -0\n2 / zero", "1 // 0"] {
+        let error =
+            try_run::<Value>(&mut engine, source).expect_err("division by zero should fail");
+        let mica::Error::Runtime(error) = &error else {
+            panic!("expected a runtime error, got {error:#}");
+        };
+        assert!(matches!(error.kind(), LanguageErrorKind::DivisionByZero));
+    }
+    // Division by any other number is unaffected.
+    let _: Value = run(&mut engine, "assert(1 / 4 == 0.25)\nassert(5 // 4 == 1)");
+}
+
+fn assert_error(engine: &mut Engine, source: &str, expected: fn(&LanguageErrorKind) -> bool) {
+    let error = try_run::<Value>(engine, source).expect_err("arithmetic should fail");
+    let mica::Error::Runtime(error) = &error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    assert!(expected(error.kind()), "{source}: {error}");
+}
+
+#[test]
+fn checked_arithmetic_raises_an_error_for_results_that_are_not_finite() {
+    let mut engine = Engine::new();
+    engine.set_arithmetic(Arithmetic::Checked);
+    for source in [
+        "1e308 * 10",
+        "1e308 + 1e308",
+        "-1e308 - 1e308",
+        "Number.infinity - Number.infinity",
+        "Number.nan + 1",
+        "1e308 / 0.1",
+        "Number.infinity % 2",
+        // Quickened and fused instructions are checked too.
+        "func f(x, y) = x * y
+f(1, 2)
+f(1e308, 10)",
+        "func f(x, y) = x + y
+f(1, 2)
+f(1e308, 1e308)",
+    ] {
+        assert_error(&mut engine, source, |kind| {
+            matches!(kind, LanguageErrorKind::NonFiniteResult)
+        });
+    }
+    // Finite results are unaffected.
+    let _: Value = run(
+        &mut engine,
+        "assert(1e307 * 10 == 1e308)
+assert(1 - 3 == -2)
+assert(0.5 + 0.25 == 0.75)",
+    );
+}
+
+#[test]
+fn checked_arithmetic_applies_to_division_methods() {
+    let mut engine = Engine::new();
+    engine.set_arithmetic(Arithmetic::Checked);
+    for method in ["div", "div_floor", "div_euclid", "mod", "rem_euclid"] {
+        let source = format!("5.{method}(0)");
+        assert_error(&mut engine, &source, |kind| {
+            matches!(kind, LanguageErrorKind::DivisionByZero)
+        });
+    }
+    assert_error(&mut engine, "1e308.div_floor(0.1)", |kind| {
+        matches!(kind, LanguageErrorKind::NonFiniteResult)
+    });
+    let _: Value = run(
+        &mut engine,
+        "assert(7.div(2) == 3)
+assert((-7).mod(2) == 1)",
+    );
+
+    // They follow IEEE 754 semantics otherwise.
+    engine.set_arithmetic(Arithmetic::Ieee);
+    let _: Value = run(
+        &mut engine,
+        "assert(5.div(0) == Number.infinity)
+assert(5.mod(0).is_nan)",
+    );
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Money {
+    cents: i64,
+}
+
+impl UserData for Money {}
+
+#[test]
+fn user_data_can_overload_operators() {
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Money>::new("Money")
+                .add_static("cents", |cents| Money { cents })
+                .add_function("add", |a: &Money, b: Money| Money {
+                    cents: a.cents + b.cents,
+                })
+                .add_function("neg", |a: &Money| Money { cents: -a.cents })
+                .add_function("eq", |a: &Money, b: Money| *a == b)
+                .add_function("cmp", |a: &Money, b: Money| (a.cents - b.cents) as f64),
+        )
+        .reveal();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let a = Money.cents(150)
+            let b = Money.cents(250)
+            assert(a + b == Money.cents(400))
+            assert(-a == Money.cents(-150))
+            assert(a != b)
+            assert(a < b and b > a and a <= a)
+        "#,
+    );
+}
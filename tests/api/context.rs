@@ -0,0 +1,278 @@
+use mica::{Arguments, Engine, EngineContext, Error, FiberState, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+fn engine_with_sum() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .add_function(
+            "sum",
+            |context: &mut EngineContext, list: Vec<Value>, f: Value| -> Result<f64, Error> {
+                let mut sum = 0.0;
+                for x in list {
+                    sum += context.call::<f64>(f.clone(), [x])?;
+                }
+                Ok(sum)
+            },
+        )
+        .reveal();
+    engine
+}
+
+#[test]
+fn foreign_functions_can_call_mica_functions() {
+    let mut engine = engine_with_sum();
+    let _: Value = run(
+        &mut engine,
+        r#"
+                let offset = 10
+                assert(sum([1, 2, 3], func (x) = x + offset) == 36)
+                assert(sum([], func (x) = error("unreachable")) == 0)
+            "#,
+    );
+}
+
+#[test]
+fn errors_in_called_functions_can_be_caught() {
+    let mut engine = engine_with_sum();
+    let _: Value = run(
+        &mut engine,
+        r#"
+                let caught = try sum([1], func (x) = error("oops")) catch e, _
+                    e
+                end
+                assert(caught.contains("oops"))
+            "#,
+    );
+}
+
+#[test]
+fn varargs_functions_can_receive_a_context() {
+    let mut engine = Engine::new();
+    engine
+        .add_function(
+            "call_all",
+            |context: &mut EngineContext, arguments: Arguments| -> Result<usize, Error> {
+                for i in 0..arguments.count() {
+                    let _: Value = context.call(arguments.get(i)?, ())?;
+                }
+                Ok(arguments.count())
+            },
+        )
+        .reveal();
+    let _: Value = run(
+        &mut engine,
+        r#"
+                let calls = []
+                assert(call_all(func () = calls.push(1), func () = calls.push(2)) == 2)
+                assert(calls == [1, 2])
+            "#,
+    );
+}
+
+#[test]
+fn contexts_give_access_to_globals() {
+    let mut engine = Engine::new();
+    engine
+        .add_function(
+            "bump",
+            |context: &mut EngineContext| -> Result<f64, Error> {
+                let counter: f64 = context.get("counter")?;
+                let bumped = context.create_value(counter + 1.0);
+                assert!(context.set("counter", bumped));
+                assert!(!context.set("undeclared", 1.0));
+                Ok(counter)
+            },
+        )
+        .reveal();
+    let counter: f64 = run(
+        &mut engine,
+        "let counter = 1\nassert(bump() == 1)\nbump()\ncounter",
+    );
+    assert_eq!(counter, 3.0);
+}
+
+#[test]
+fn callers_stacks_survive_collections_during_calls() {
+    let mut engine = engine_with_sum();
+    engine.set_gc_stress(true);
+    let _: Value = run(
+        &mut engine,
+        r#"
+                func pairs(n) = do
+                    let parts = []
+                    let i = 0
+                    while i < n do
+                        parts.push([i, i + 1])
+                        i = i + 1
+                    end
+                    parts
+                end
+                let kept = ["kept", pairs(3)]
+                assert(sum([1, 2, 3], func (n) = pairs(n).len) == 6)
+                assert(kept == ["kept", [[0, 1], [1, 2], [2, 3]]])
+            "#,
+    );
+}
+
+#[test]
+fn blocking_in_called_functions_blocks_the_caller() {
+    let mut engine = engine_with_sum();
+    let mut fiber = engine
+        .start(
+            "test.mi",
+            r#"
+                let channel = Channel.new()
+                sum([1], func (x) = channel.receive())
+            "#,
+        )
+        .reveal();
+    let _: Option<Value> = fiber.resume().reveal();
+    assert_eq!(fiber.state(), FiberState::Suspended);
+}
+
+fn engine_with_apply() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .add_function(
+            "apply",
+            |context: &mut EngineContext, f: Value, x: Value| -> Result<Value, Error> {
+                context.call(f, (x,))
+            },
+        )
+        .reveal();
+    engine
+}
+
+#[test]
+fn reentering_through_foreign_functions_overflows_the_stack() {
+    let mut engine = engine_with_apply();
+    let result: Result<Value, _> = try_run(
+        &mut engine,
+        "func recurse(n) = apply(recurse, n + 1)\nrecurse(0)",
+    );
+    let error = result.unwrap_err().to_string();
+    assert!(error.starts_with("error: stack overflow"), "{error}");
+    assert!(error.contains("<FFI>         apply"), "{error}");
+    assert!(error.contains("test.mi:1:24  recurse"), "{error}");
+}
+
+#[test]
+fn stack_overflows_in_called_functions_can_be_caught() {
+    let mut engine = engine_with_apply();
+    let _: Value = run(
+        &mut engine,
+        r#"
+                func recurse(n) = apply(recurse, n + 1)
+                let caught = try recurse(0) catch e, _ e end
+                assert(caught.contains("stack overflow"))
+                # The depth is back to normal once the calls return.
+                assert(apply(func (x) = x + 1, 1) == 2)
+            "#,
+    );
+}
+
+#[test]
+fn calls_through_foreign_functions_count_toward_the_call_depth() {
+    let mut engine = engine_with_apply();
+    engine.set_max_call_depth(50);
+    let source = |outer: usize| {
+        format!(
+            "func down(n) = if n > 0 do down(n - 1) else 0 end\n\
+             func outer(n) = if n > 0 do outer(n - 1) else apply(down, 30) end\n\
+             outer({outer})"
+        )
+    };
+    let _: Value = run(&mut engine, &source(10));
+    let error = try_run::<Value>(&mut engine, &source(30)).unwrap_err();
+    assert!(
+        error.to_string().starts_with("error: stack overflow"),
+        "{error}"
+    );
+}
+
+#[test]
+fn the_reentry_depth_is_configurable() {
+    let mut engine = engine_with_apply();
+    assert_eq!(engine.max_reentry_depth(), 1000);
+    engine.set_max_reentry_depth(3);
+    let depth: f64 = run(
+        &mut engine,
+        "func count(n) = try apply(count, n + 1) catch _ n end\ncount(0)",
+    );
+    assert_eq!(depth, 3.0);
+}
+
+#[test]
+fn the_default_reentry_limits_fit_in_the_default_native_stack() {
+    std::thread::Builder::new()
+        .stack_size(2 << 20)
+        .spawn(|| {
+            let mut engine = engine_with_apply();
+            let _: Value = run(
+                &mut engine,
+                r#"
+                    func recurse(n) = apply(recurse, n + 1)
+                    func nest(n) = Fiber.new(func () = nest(n + 1)).resume
+                    func mixed(n) = Fiber.new(func () = apply(mixed, n + 1)).resume
+                    assert((try recurse(0) catch e, _ e end).contains("stack overflow"))
+                    assert((try nest(0) catch e, _ e end).contains("stack overflow"))
+                    assert((try mixed(0) catch e, _ e end).contains("stack overflow"))
+                "#,
+            );
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn the_reentry_stack_is_configurable() {
+    let mut engine = engine_with_apply();
+    engine.set_max_reentry_stack(0);
+    let depth: f64 = run(
+        &mut engine,
+        "func count(n) = try apply(count, n + 1) catch _ n end\ncount(0)",
+    );
+    // Only the outermost re-entry fits into no stack at all.
+    assert_eq!(depth, 1.0);
+}
+
+const NESTED_TO_STRING: &str = r#"
+    struct Node impl
+        func new(child) constructor = do @child = child end
+        func to_string() = "(".cat(string(@child)).cat(")")
+    end
+    func nest(depth) = do
+        let node = nil
+        for _ in countup(1, depth) do node = Node.new(node) end
+        node
+    end
+"#;
+
+#[test]
+fn nested_to_string_methods_do_not_overflow_the_stack() {
+    let mut engine = Engine::new();
+    let _: Value = run(&mut engine, NESTED_TO_STRING);
+    let string: String = run(&mut engine, "string(nest(16))");
+    assert_eq!(string, format!("{}nil{}", "(".repeat(16), ")".repeat(16)));
+}
+
+#[test]
+fn deeply_nested_to_string_methods_work_with_a_bigger_stack() {
+    std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(|| {
+            let mut engine = Engine::new();
+            engine.set_max_reentry_stack(48 << 20);
+            let _: Value = run(&mut engine, NESTED_TO_STRING);
+            let string: String = run(&mut engine, "string(nest(500))");
+            assert_eq!(string.len(), 500 * 2 + 3);
+            // Past the limit, the stack overflow can be caught like any other error.
+            let caught: String = run(&mut engine, "try string(nest(10000)) catch e, _ e end");
+            assert!(caught.contains("stack overflow"), "{caught}");
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,171 @@
+use mica::ll::{
+    cst::{SyntaxElement, SyntaxNode, SyntaxNodeKind, SyntaxTokenKind, SyntaxTree},
+    lexer::TokenKind,
+};
+
+const SOURCE: &str = r#"# Counts up.
+struct Counter impl
+    func new(max) constructor = do
+        @i = 0
+        @max = max
+    end
+
+    func next() = do
+        if @i < @max do
+            @i = @i + 1
+        elif @i == @max do
+            nil
+        else
+            [1, (2)]
+        end
+    end
+end
+
+let total =
+    1 +
+    2   # Trailing comment.
+func f(x) = x * 2
+
+let text =
+    \\long
+    \\string
+assert(f(total) == 6)
+"#;
+
+fn tokens_text(tree: &SyntaxTree) -> String {
+    tree.tokens()
+        .map(|(range, _)| &tree.source()[range])
+        .collect()
+}
+
+fn items(tree: &SyntaxTree) -> Vec<&str> {
+    let mut offset = 0;
+    let mut items = Vec::new();
+    for child in tree.root().children() {
+        if let SyntaxElement::Node(node) = child {
+            items.push(&tree.source()[offset..offset + node.len()]);
+        }
+        offset += child.len();
+    }
+    items
+}
+
+fn count_nodes(node: &SyntaxNode, kind: SyntaxNodeKind) -> usize {
+    let mut count = usize::from(node.kind() == kind);
+    for child in node.children() {
+        if let SyntaxElement::Node(child) = child {
+            count += count_nodes(child, kind);
+        }
+    }
+    count
+}
+
+#[test]
+fn trees_are_lossless() {
+    let tree = SyntaxTree::parse(SOURCE);
+    assert_eq!(tokens_text(&tree), SOURCE);
+    assert_eq!(tree.root().len(), SOURCE.len());
+    let comments = tree
+        .tokens()
+        .filter(|(_, token)| token.kind == SyntaxTokenKind::Token(TokenKind::Comment))
+        .count();
+    assert_eq!(comments, 2);
+}
+
+#[test]
+fn trees_are_split_into_items() {
+    let tree = SyntaxTree::parse(SOURCE);
+    let items = items(&tree);
+    assert_eq!(items.len(), 5);
+    assert!(items[0].starts_with("struct Counter impl") && items[0].ends_with("\nend"));
+    assert_eq!(items[1], "let total =\n    1 +\n    2");
+    assert_eq!(items[2], "func f(x) = x * 2");
+    assert_eq!(items[3], "let text =\n    \\\\long\n    \\\\string");
+    assert_eq!(items[4], "assert(f(total) == 6)");
+
+    // impl, both functions' `do`, and the `if`; `elif` does not begin a new block.
+    assert_eq!(count_nodes(tree.root(), SyntaxNodeKind::Block), 4);
+    assert_eq!(count_nodes(tree.root(), SyntaxNodeKind::Group), 7);
+}
+
+#[test]
+fn invalid_source_code_produces_error_tokens() {
+    let source = "let x = $ + 1\nlet y = \"unterminated\nlet z = 2\n";
+    let tree = SyntaxTree::parse(source);
+    assert_eq!(tokens_text(&tree), source);
+    let errors: Vec<_> = tree
+        .tokens()
+        .filter(|(_, token)| matches!(token.kind, SyntaxTokenKind::Token(TokenKind::Error(_))))
+        .map(|(range, _)| &source[range])
+        .collect();
+    assert_eq!(errors, ["$", "\"unterminated"]);
+}
+
+#[test]
+fn unterminated_blocks_are_recovered_from() {
+    let tree = SyntaxTree::parse("func f() = do\n    (1\n\nfunc g() = 2\n");
+    let items = items(&tree);
+    assert_eq!(items, ["func f() = do\n    (1", "func g() = 2"]);
+}
+
+#[test]
+fn token_at_finds_tokens() {
+    let tree = SyntaxTree::parse("let x = 1");
+    let (range, token) = tree.token_at(4).unwrap();
+    assert_eq!(range, 4..5);
+    assert_eq!(
+        token.kind,
+        SyntaxTokenKind::Token(TokenKind::Identifier("x".into()))
+    );
+    assert!(tree.token_at(9).is_none());
+}
+
+#[test]
+fn edits_produce_the_same_tree_as_parsing_from_scratch() {
+    const SNIPPETS: &[&str] = &[
+        "",
+        "do",
+        "end",
+        "(",
+        ")",
+        "\n",
+        "x + ",
+        "\"",
+        "#",
+        "func g() = ",
+        "  ",
+        "elif",
+        "=",
+        "\\\\",
+        "\nlet y = 1\n",
+        "$",
+        ".",
+        "..",
+    ];
+
+    let mut tree = SyntaxTree::parse(SOURCE);
+    // A simple linear congruential generator, so that the test is deterministic.
+    let mut seed = 12345_u64;
+    let mut random = |max: usize| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % max
+    };
+    for _ in 0..2000 {
+        let len = tree.source().len();
+        let start = random(len + 1);
+        let end = (start + random(8)).min(len);
+        let replacement = SNIPPETS[random(SNIPPETS.len())];
+        tree.edit(start..end, replacement);
+
+        let expected = SyntaxTree::parse(tree.source());
+        assert_eq!(
+            tree.root(),
+            expected.root(),
+            "incremental tree differs for source:\n{}",
+            tree.source()
+        );
+        assert_eq!(tokens_text(&tree), tree.source());
+    }
+}
@@ -0,0 +1,275 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mica::{DebuggerHooks, Engine, FiberState, PausedFiber, Value};
+
+use super::RevealResultExt;
+
+/// What the debugger saw when a line was executed.
+#[derive(Debug)]
+struct Stop {
+    line: u32,
+    functions: Vec<String>,
+    locals: Vec<(String, String)>,
+    upvalues: Vec<(String, String)>,
+    globals: Vec<(String, String)>,
+}
+
+#[derive(Clone, Default)]
+struct Recorder(Rc<RefCell<Vec<Stop>>>);
+
+fn stringify(variables: Vec<(Rc<str>, Value)>) -> Vec<(String, String)> {
+    variables
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+impl DebuggerHooks for Recorder {
+    fn on_line(&mut self, fiber: &PausedFiber<'_>) {
+        let stack = fiber.stack();
+        assert_eq!(stack.len(), fiber.call_depth() + 1);
+        self.0.borrow_mut().push(Stop {
+            line: stack[0].location.line,
+            functions: stack
+                .iter()
+                .map(|frame| frame.function_name.to_string())
+                .collect(),
+            locals: stringify(fiber.locals(0)),
+            upvalues: stringify(fiber.upvalues(0)),
+            globals: stringify(fiber.globals()),
+        });
+    }
+}
+
+fn record(source: &str) -> Vec<Stop> {
+    let recorder = Recorder::default();
+    let mut engine = Engine::new();
+    engine.set_debugger_hooks(recorder.clone());
+    let _: Value = engine
+        .start("debugger.mi", source)
+        .reveal()
+        .trampoline()
+        .reveal();
+    recorder.0.take()
+}
+
+fn variable(name: &str, value: &str) -> (String, String) {
+    (name.to_owned(), value.to_owned())
+}
+
+#[test]
+fn hooks_are_called_once_per_line() {
+    let stops = record("let x = 1\nlet y = x + 1\n\nlet z = y\n");
+    let lines: Vec<_> = stops.iter().map(|stop| stop.line).collect();
+    assert_eq!(lines, [1, 2, 4]);
+    assert_eq!(
+        stops[2].globals,
+        [variable("x", "1"), variable("y", "2"), variable("z", "nil")]
+    );
+}
+
+#[test]
+fn hooks_are_called_for_function_calls_and_loop_iterations() {
+    let stops = record(
+        "func add(a, b) = do\n    a + b\nend\nlet i = 0\nwhile i < 2 do i = add(i, 1) end\n",
+    );
+    let lines: Vec<_> = stops.iter().map(|stop| stop.line).collect();
+    // Returning from `add` to the line that called it doesn't count as a new line.
+    assert_eq!(lines, [1, 4, 5, 2, 5, 2, 5]);
+    let inside_add = &stops[3];
+    assert_eq!(inside_add.functions, ["add", "<main>"]);
+    assert_eq!(inside_add.locals, [variable("a", "0"), variable("b", "1")]);
+}
+
+#[test]
+fn locals_and_upvalues_are_visible() {
+    let stops = record(
+        r#"
+            func outer() = do
+                let captured = "hello"
+                let inner = func () = do
+                    let own = 2
+                    captured
+                end
+                inner
+            end
+            outer()()
+        "#,
+    );
+    let inner = stops
+        .iter()
+        .find(|stop| stop.line == 6)
+        .expect("the closure must have been executed");
+    assert_eq!(inner.functions.len(), 2);
+    assert_eq!(inner.locals, [variable("own", "2")]);
+    assert_eq!(inner.upvalues, [variable("captured", "hello")]);
+
+    // Locals go out of scope once their block ends.
+    let declaring = stops.iter().find(|stop| stop.line == 4).unwrap();
+    assert_eq!(declaring.locals, [variable("captured", "hello")]);
+    assert!(stops
+        .iter()
+        .filter(|stop| stop.functions.len() == 1)
+        .all(|stop| stop.locals.is_empty()));
+}
+
+#[test]
+fn hooks_can_be_removed() {
+    let recorder = Recorder::default();
+    let mut engine = Engine::new();
+    engine.set_debugger_hooks(recorder.clone());
+    engine.remove_debugger_hooks();
+    let _: Value = engine
+        .start("debugger.mi", "let x = 1")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(recorder.0.borrow().is_empty());
+}
+
+#[derive(Clone, Default)]
+struct CallRecorder(Rc<RefCell<Vec<String>>>);
+
+impl DebuggerHooks for CallRecorder {
+    fn on_line(&mut self, _fiber: &PausedFiber<'_>) {}
+
+    fn on_call(&mut self, fiber: &PausedFiber<'_>, function_name: &str) {
+        self.0.borrow_mut().push(format!(
+            "call {function_name} at depth {}",
+            fiber.call_depth()
+        ));
+    }
+
+    fn on_return(&mut self, fiber: &PausedFiber<'_>) {
+        self.0
+            .borrow_mut()
+            .push(format!("return at depth {}", fiber.call_depth()));
+    }
+}
+
+#[test]
+fn calls_and_returns_are_reported_in_pairs() {
+    let recorder = CallRecorder::default();
+    let mut engine = Engine::new();
+    engine.set_debugger_hooks(recorder.clone());
+    let _: Value = engine
+        .start(
+            "debugger.mi",
+            r#"
+                func inner() = [].len
+                func outer() = inner() + 1
+                outer()
+            "#,
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(
+        *recorder.0.borrow(),
+        [
+            "call outer at depth 1",
+            "call inner at depth 2",
+            // Foreign functions don't get a frame of their own.
+            "call List.len at depth 2",
+            "return at depth 2",
+            "return at depth 2",
+            "return at depth 1",
+        ]
+    );
+}
+
+#[test]
+fn breakpoints_pause_fibers() {
+    let recorder = Recorder::default();
+    let mut engine = Engine::new();
+    engine.set_debugger_hooks(recorder.clone());
+    engine.set_breakpoint("debugger.mi", 3);
+    engine.set_breakpoint("other.mi", 1);
+    let mut fiber = engine
+        .start(
+            "debugger.mi",
+            "func double(x) = do\n    let y = x * 2\n    y\nend\ndouble(1) + double(2)\n",
+        )
+        .reveal();
+
+    assert!(matches!(fiber.resume().reveal(), Some(Value::Nil)));
+    assert_eq!(fiber.state(), FiberState::Paused);
+    let stack = fiber.stack();
+    assert_eq!(stack[0].location.line, 3);
+    assert_eq!(stack[0].function_name.as_ref(), "double");
+    assert_eq!(
+        stringify(fiber.inspect().locals(0)),
+        [variable("x", "1"), variable("y", "2")]
+    );
+
+    // Resuming doesn't hit the same breakpoint again until the line is executed again.
+    let _: Option<Value> = fiber.resume().reveal();
+    assert_eq!(fiber.state(), FiberState::Paused);
+    assert_eq!(
+        stringify(fiber.inspect().locals(0)),
+        [variable("x", "2"), variable("y", "4")]
+    );
+    assert_eq!(fiber.resume::<f64>().reveal(), Some(6.0));
+    assert_eq!(fiber.state(), FiberState::Finished);
+
+    // The hooks still see every line, including the ones with breakpoints.
+    let lines: Vec<_> = recorder.0.borrow().iter().map(|stop| stop.line).collect();
+    assert_eq!(lines, [1, 5, 2, 3, 2, 3]);
+}
+
+#[test]
+fn breakpoints_can_be_removed() {
+    let mut engine = Engine::new();
+    engine.set_debugger_hooks(Recorder::default());
+    engine.set_breakpoint("debugger.mi", 2);
+    assert!(engine.remove_breakpoint("debugger.mi", 2));
+    assert!(!engine.remove_breakpoint("debugger.mi", 2));
+    engine.set_breakpoint("debugger.mi", 1);
+    engine.clear_breakpoints();
+    let mut fiber = engine.start("debugger.mi", "let x = 1\nx + 1").reveal();
+    assert_eq!(fiber.resume::<f64>().reveal(), Some(2.0));
+}
+
+/// Pauses the fiber after a fixed number of instructions.
+struct Stepper {
+    instructions: usize,
+    every: usize,
+}
+
+impl DebuggerHooks for Stepper {
+    fn on_line(&mut self, _fiber: &PausedFiber<'_>) {}
+
+    fn on_instruction(&mut self, fiber: &PausedFiber<'_>) {
+        self.instructions += 1;
+        if self.instructions.is_multiple_of(self.every) {
+            fiber.pause();
+        }
+    }
+}
+
+#[test]
+fn hooks_can_pause_on_any_instruction() {
+    let mut engine = Engine::new();
+    engine.set_debugger_hooks(Stepper {
+        instructions: 0,
+        every: 3,
+    });
+    let mut fiber = engine
+        .start("debugger.mi", "let i = 0\nwhile i < 10 do i = i + 1 end\ni")
+        .reveal();
+    let mut pauses = 0;
+    while fiber.state() != FiberState::Finished {
+        let _: Option<Value> = fiber.resume().reveal();
+        if fiber.state() == FiberState::Paused {
+            pauses += 1;
+        }
+    }
+    assert!(pauses > 10);
+
+    let result: f64 = engine
+        .start("debugger.mi", "1 + 2")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(result, 3.0);
+}
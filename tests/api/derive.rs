@@ -0,0 +1,107 @@
+use mica::{Engine, MicaType, UserData, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+#[derive(MicaType)]
+#[mica(name = "Player", constructor = "spawn", methods)]
+struct PlayerData {
+    name: String,
+    #[mica(readonly)]
+    id: u32,
+    #[mica(name = "hp")]
+    health: f64,
+    #[mica(skip)]
+    log: Vec<String>,
+}
+
+impl UserData for PlayerData {}
+
+#[mica::methods]
+impl PlayerData {
+    #[mica]
+    fn damage(&mut self, amount: f64) {
+        self.health -= amount;
+        self.log.push(format!("took {amount} damage"));
+    }
+
+    #[mica(name = "log_len")]
+    fn log_length(&self) -> usize {
+        self.log.len()
+    }
+
+    #[mica]
+    fn unnamed() -> Self {
+        Self {
+            name: "?".into(),
+            id: 0,
+            health: 1.0,
+            log: Vec::new(),
+        }
+    }
+
+    /// Functions that aren't marked stay hidden from Mica.
+    #[allow(dead_code)]
+    fn internal(&self) {}
+}
+
+#[derive(MicaType)]
+#[mica(no_constructor)]
+struct Marker;
+
+impl UserData for Marker {}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.add_type(PlayerData::type_builder()).reveal();
+    engine.add_type(Marker::type_builder()).reveal();
+    engine
+}
+
+#[test]
+fn fields_get_getters_and_setters() {
+    let mut engine = engine();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let p = Player.spawn("alice", 7, 10)
+            assert(p.name == "alice")
+            assert(p.id == 7)
+            assert(p.hp == 10)
+            p.set_name("bob")
+            p.set_hp(5)
+            assert(p.name == "bob" and p.hp == 5)
+        "#,
+    );
+}
+
+#[test]
+fn readonly_and_skipped_fields_have_no_setters() {
+    let methods: Vec<String> = run(&mut engine(), "methods_of(Player)");
+    assert!(methods.contains(&"id/0".to_owned()));
+    assert!(!methods.contains(&"set_id/1".to_owned()));
+    assert!(!methods.iter().any(|method| method.contains("log/")));
+    assert!(!methods.iter().any(|method| method.starts_with("internal")));
+}
+
+#[test]
+fn marked_functions_are_added() {
+    let mut engine = engine();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let p = Player.unnamed
+            assert(p.name == "?")
+            p.damage(0.5)
+            p.damage(0.25)
+            assert(p.hp == 0.25)
+            assert(p.log_len == 2)
+        "#,
+    );
+}
+
+#[test]
+fn constructors_can_be_left_out() {
+    let mut engine = engine();
+    let result = try_run::<Value>(&mut engine, "Marker.new");
+    assert!(result.is_err());
+}
@@ -0,0 +1,235 @@
+use mica::{
+    ll::bytecode::{Opcode, Operands},
+    Engine,
+};
+
+use super::RevealResultExt;
+
+#[test]
+fn instructions_decode_their_operands() {
+    let mut engine = Engine::new();
+    let script = engine
+        .compile("test.mi", "let r = { a: 1.5, b: \"x\" }\nstruct S\n[r].len")
+        .reveal();
+    let chunks = script.chunks();
+    let (name, chunk) = &chunks[0];
+    assert_eq!(name, "<main>");
+    let instructions: Vec<_> = chunk.instructions().collect();
+    // String literals are interned in the environment, so only their index is stored in the chunk.
+    assert!(instructions.iter().any(|instruction| {
+        instruction.opcode == Opcode::PushString
+            && matches!(instruction.operands, Operands::Opr24(_))
+    }));
+    let operands: Vec<_> = instructions
+        .into_iter()
+        .map(|instruction| instruction.operands)
+        .collect();
+    assert!(operands.contains(&Operands::Number(1.5)));
+    assert!(operands
+        .iter()
+        .any(|operands| matches!(operands, Operands::String(_, "S"))));
+    assert!(operands
+        .iter()
+        .any(|operands| matches!(operands, Operands::Record(_, fields) if fields.len() == 2)));
+    assert!(operands.iter().any(|operands| matches!(
+        operands,
+        Operands::Method {
+            argument_count: 1,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn instructions_are_located_in_the_source() {
+    let mut engine = Engine::new();
+    let script = engine.compile("test.mi", "nil\n\n2").reveal();
+    let chunks = script.chunks();
+    let push = chunks[0]
+        .1
+        .instructions()
+        .find(|instruction| instruction.opcode == Opcode::PushNumber)
+        .unwrap();
+    assert_eq!(push.location.line, 3);
+    assert_eq!(
+        push.to_string(),
+        format!("{:06x} 3:1 PushNumber 2", push.offset)
+    );
+}
+
+#[test]
+fn jumps_lead_to_instructions() {
+    let mut engine = Engine::new();
+    let script = engine
+        .compile(
+            "test.mi",
+            r#"
+                func f(x) = do
+                    let i = 0
+                    while i < x and x > 0 do
+                        i = i + 1
+                        if i == 5 do break end
+                    end
+                    i or 0
+                end
+            "#,
+        )
+        .reveal();
+    let mut jumps = 0;
+    for (name, chunk) in script.chunks() {
+        let instructions: Vec<_> = chunk.instructions().collect();
+        for instruction in &instructions {
+            if let Operands::Jump(target) = instruction.operands {
+                jumps += 1;
+                assert!(
+                    instructions.iter().any(|other| other.offset == target),
+                    "jump at {:06x} in {name} leads to {target:06x}, which is not an instruction",
+                    instruction.offset
+                );
+            }
+        }
+    }
+    assert!(jumps >= 4);
+}
+
+#[test]
+fn conditional_jumps_skip_to_the_else_branch() {
+    let mut engine = Engine::new();
+    let script = engine
+        .compile("test.mi", "func f(x) = if x do 1 else 2 end")
+        .reveal();
+    let chunks = script.chunks();
+    let instructions: Vec<_> = chunks[1].1.instructions().collect();
+    let Some(Operands::Jump(target)) = instructions
+        .iter()
+        .find(|instruction| instruction.opcode == Opcode::JumpForwardIfFalsy)
+        .map(|instruction| &instruction.operands)
+    else {
+        panic!("no conditional jump in {instructions:#?}");
+    };
+    // The else branch starts by discarding the condition.
+    let target = instructions
+        .iter()
+        .position(|instruction| instruction.offset == *target)
+        .unwrap();
+    assert_eq!(instructions[target].opcode, Opcode::Discard);
+    assert_eq!(instructions[target + 1].operands, Operands::Number(2.0));
+}
+
+#[test]
+fn constant_expressions_are_folded() {
+    let mut engine = Engine::new();
+    let script = engine
+        .compile("test.mi", "(1 + 2 * 3 == 7) and -(10 // 4) < 0")
+        .reveal();
+    let chunks = script.chunks();
+    let opcodes: Vec<_> = chunks[0]
+        .1
+        .instructions()
+        .map(|instruction| instruction.opcode)
+        .collect();
+    assert_eq!(opcodes, [Opcode::PushTrue, Opcode::Halt]);
+
+    let script = engine.compile("test.mi", "-(1 + 2.5) % 2").reveal();
+    let chunks = script.chunks();
+    let operands: Vec<_> = chunks[0]
+        .1
+        .instructions()
+        .map(|instruction| instruction.operands)
+        .collect();
+    assert_eq!(operands[0], Operands::Number(0.5));
+    assert_eq!(operands.len(), 2);
+}
+
+#[test]
+fn dead_branches_are_not_generated() {
+    let mut engine = Engine::new();
+    let script = engine
+        .compile(
+            "test.mi",
+            r#"
+                func f(x) = do
+                    if false do print("dead") elif 1 < 2 do x else print("dead") end
+                    while false do print("dead") end
+                    nil and print("dead")
+                end
+            "#,
+        )
+        .reveal();
+    let chunks = script.chunks();
+    let instructions: Vec<_> = chunks[1].1.instructions().collect();
+    assert!(
+        instructions.iter().all(|instruction| !matches!(
+            instruction.opcode,
+            Opcode::Call | Opcode::JumpForwardIfFalsy | Opcode::JumpForward
+        )),
+        "{instructions:#?}"
+    );
+}
+
+#[test]
+fn dead_branches_are_still_checked_for_errors() {
+    let mut engine = Engine::new();
+    for source in [
+        "if false do nope end",
+        "while false do nope end",
+        "false and nope",
+        "if true do 1 else break end",
+    ] {
+        assert!(
+            engine.compile("test.mi", source).is_err(),
+            "{source} compiled"
+        );
+    }
+}
+
+#[test]
+fn hot_sequences_are_fused() {
+    let mut engine = Engine::new();
+    let script = engine
+        .compile(
+            "test.mi",
+            r#"
+                func f(g, n) = do
+                    let sum = 0
+                    let i = 0
+                    while i < 10 do
+                        sum = sum + i
+                        if sum <= n do sum = g(sum) end
+                        i = i + 1
+                    end
+                    sum
+                end
+            "#,
+        )
+        .reveal();
+    let chunks = script.chunks();
+    let instructions: Vec<_> = chunks[1].1.instructions().collect();
+    let find = |opcode| {
+        instructions
+            .iter()
+            .position(|instruction| instruction.opcode == opcode)
+            .unwrap_or_else(|| panic!("no {opcode:?} in {instructions:#?}"))
+    };
+    // Superinstructions keep the instructions they were fused with after them.
+    let compare = find(Opcode::LessNumberJump);
+    assert_eq!(instructions[compare].operands, Operands::Number(10.0));
+    assert_eq!(instructions[compare + 1].opcode, Opcode::Less);
+    assert_eq!(instructions[compare + 2].opcode, Opcode::JumpForwardIfFalsy);
+    let add = find(Opcode::AddLocals);
+    assert_eq!(instructions[add + 1].opcode, Opcode::GetLocal);
+    assert_eq!(instructions[add + 2].opcode, Opcode::Add);
+    let call = find(Opcode::GetLocalCall);
+    assert_eq!(instructions[call + 1].opcode, Opcode::Call);
+    // `sum <= n` doesn't compare with a constant, and `i + 1` doesn't add two locals.
+    assert!(instructions
+        .iter()
+        .all(|instruction| instruction.opcode != Opcode::LessEqualNumberJump));
+    assert_eq!(
+        instructions
+            .iter()
+            .filter(|instruction| instruction.opcode == Opcode::AddLocals)
+            .count(),
+        1
+    );
+}
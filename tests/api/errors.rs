@@ -0,0 +1,260 @@
+use std::{error::Error as _, fmt};
+
+use mica::{Engine, EngineContext, LanguageErrorKind, Value};
+
+use super::try_run;
+
+#[test]
+fn compile_errors_expose_their_kind_and_span() {
+    let mut engine = Engine::new();
+    let error = engine
+        .compile("test.mi", "let x = 1\nlet y = $")
+        .expect_err("compilation should fail");
+
+    let (module_name, span) = error.span().expect("compile error should have a span");
+    assert_eq!(&**module_name, "test.mi");
+    assert_eq!((span.start.line, span.start.column), (2, 9));
+    assert_eq!(
+        error.location().map(|(_, location)| location),
+        Some(span.start)
+    );
+
+    let source = error.source().expect("compile error should have a source");
+    let kind = source
+        .downcast_ref::<LanguageErrorKind>()
+        .expect("the source of a compile error should be its kind");
+    assert!(matches!(kind, LanguageErrorKind::InvalidCharacter('$')));
+    assert_eq!(source.to_string(), "invalid character: '$'");
+}
+
+#[derive(Debug)]
+struct OutOfCheese {
+    source: fmt::Error,
+}
+
+impl fmt::Display for OutOfCheese {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("out of cheese")
+    }
+}
+
+impl std::error::Error for OutOfCheese {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[test]
+fn runtime_errors_chain_to_user_errors() {
+    let mut engine = Engine::new();
+    engine
+        .add_function("cheese", || -> Result<(), OutOfCheese> {
+            Err(OutOfCheese { source: fmt::Error })
+        })
+        .unwrap();
+    let error = try_run::<Value>(&mut engine, "\ncheese()").expect_err("execution should fail");
+
+    let (_, location) = error
+        .location()
+        .expect("runtime error should have a location");
+    assert_eq!(location.line, 2);
+
+    let user_error = error
+        .source()
+        .and_then(|source| source.downcast_ref::<OutOfCheese>())
+        .expect("the source of the runtime error should be the user error");
+    assert_eq!(user_error.to_string(), "out of cheese");
+    assert!(user_error.source().unwrap().is::<fmt::Error>());
+}
+
+#[test]
+fn errors_outside_the_language_have_no_span() {
+    let error = mica::Error::TooManyGlobals;
+    assert!(error.span().is_none());
+    assert!(error.source().is_none());
+}
+
+#[test]
+fn errors_from_foreign_functions_can_be_caught() {
+    let mut engine = Engine::new();
+    engine
+        .add_function("cheese", || -> Result<(), OutOfCheese> {
+            Err(OutOfCheese { source: fmt::Error })
+        })
+        .unwrap();
+    let message: String = try_run(&mut engine, "try cheese() catch e e end").unwrap();
+    assert_eq!(message, "out of cheese");
+}
+
+#[test]
+fn uncaught_raised_values_are_rendered_in_the_error() {
+    let mut engine = Engine::new();
+    let error =
+        try_run::<Value>(&mut engine, "raise {code: 418}").expect_err("execution should fail");
+    let source = error.source().expect("runtime error should have a source");
+    let kind = source.downcast_ref::<LanguageErrorKind>().unwrap();
+    assert!(matches!(kind, LanguageErrorKind::Raised(value) if &**value == "{ code: 418 }"));
+}
+
+#[test]
+fn runtime_errors_carry_the_raised_value() {
+    let mut engine = Engine::new();
+    let error = try_run::<Value>(&mut engine, "raise [418, \"teapot\"]")
+        .expect_err("execution should fail");
+    let mica::Error::Runtime(error) = error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    assert!(matches!(error.kind(), LanguageErrorKind::Raised(_)));
+    let raised = error.raised().expect("the raised value should be kept");
+    assert_eq!(raised.display(&mut engine).unwrap(), "[418, teapot]");
+
+    // The value outlives collections, as the error holds onto it.
+    let _: Value = engine
+        .start("gc.mi", "Gc.collect()")
+        .unwrap()
+        .trampoline()
+        .unwrap();
+    assert_eq!(raised.display(&mut engine).unwrap(), "[418, teapot]");
+}
+
+#[test]
+fn errors_that_were_not_raised_have_no_value() {
+    let mut engine = Engine::new();
+    let error = try_run::<Value>(&mut engine, "nil + 1").expect_err("execution should fail");
+    let mica::Error::Runtime(error) = error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    assert!(error.raised().is_none());
+    assert!(!matches!(error.kind(), LanguageErrorKind::Raised(_)));
+}
+
+#[test]
+fn runtime_errors_have_a_structured_stack_trace() {
+    let mut engine = Engine::new();
+    let error = engine
+        .start(
+            "main.mi",
+            "func inner() =\n    raise \"oops\"\nfunc outer() = inner()\nouter()",
+        )
+        .unwrap()
+        .trampoline::<Value>()
+        .expect_err("execution should fail");
+    let mica::Error::Runtime(error) = error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    let frames: Vec<_> = error
+        .stack_trace()
+        .iter()
+        .map(|entry| {
+            (
+                &*entry.function_name,
+                &*entry.module_name,
+                entry.location.line,
+            )
+        })
+        .collect();
+    assert_eq!(
+        frames,
+        [
+            ("<main>", "main.mi", 4),
+            ("outer", "main.mi", 3),
+            ("inner", "main.mi", 2)
+        ]
+    );
+}
+
+/// Returns the function names and lines in a runtime error's stack trace.
+fn frames(error: mica::Error) -> Vec<(String, u32)> {
+    let mica::Error::Runtime(error) = error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    error
+        .stack_trace()
+        .iter()
+        .map(|entry| (entry.function_name.to_string(), entry.location.line))
+        .collect()
+}
+
+fn engine_with_callbacks() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .add_function(
+            "call_back",
+            |context: &mut EngineContext, f: Value| -> Result<Value, mica::Error> {
+                context.call(f, ())
+            },
+        )
+        .unwrap();
+    engine
+}
+
+#[test]
+fn stack_traces_include_functions_called_back_by_foreign_functions() {
+    let mut engine = engine_with_callbacks();
+    let error = engine
+        .start(
+            "main.mi",
+            "func fail() =\n    nil + 1\nfunc outer() = call_back(fail)\nouter()",
+        )
+        .unwrap()
+        .trampoline::<Value>()
+        .expect_err("execution should fail");
+    let message = format!("{error}");
+    assert!(message.starts_with("error: type mismatch"), "{message}");
+    assert_eq!(message.matches("stack traceback").count(), 1, "{message}");
+    assert_eq!(
+        frames(error),
+        [
+            ("<main>".to_owned(), 4),
+            ("outer".to_owned(), 3),
+            ("call_back".to_owned(), 0),
+            ("fail".to_owned(), 2),
+        ]
+    );
+}
+
+#[test]
+fn errors_in_callbacks_keep_their_kind() {
+    let mut engine = engine_with_callbacks();
+    let message: String = engine
+        .start(
+            "main.mi",
+            "try call_back(func () = raise \"oops\") catch e e end",
+        )
+        .unwrap()
+        .trampoline()
+        .unwrap();
+    assert_eq!(message, "oops");
+
+    // Exiting can't be caught, no matter how deep the exit happens.
+    engine
+        .add_function("quit", || -> Result<(), mica::Error> {
+            Err(mica::Error::Exit(3))
+        })
+        .unwrap();
+    let error = engine
+        .start("main.mi", "try call_back(func () = quit()) catch _ nil end")
+        .unwrap()
+        .trampoline::<Value>()
+        .expect_err("execution should fail");
+    let mica::Error::Runtime(error) = error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    assert_eq!(error.exit_code(), Some(3));
+}
+
+#[test]
+fn hidden_foreign_functions_are_left_out_of_stack_traces() {
+    let mut engine = engine_with_callbacks();
+    assert!(engine.hide_in_stack_traces("call_back"));
+    assert!(!engine.hide_in_stack_traces("no_such_function"));
+    let error = engine
+        .start("main.mi", "call_back(func () =\n    nil + 1)")
+        .unwrap()
+        .trampoline::<Value>()
+        .expect_err("execution should fail");
+    assert_eq!(
+        frames(error),
+        [("<main>".to_owned(), 1), ("<anonymous>".to_owned(), 2)]
+    );
+}
@@ -0,0 +1,81 @@
+//! Tests for extending built-in types from scripts.
+
+use mica::{Engine, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+#[test]
+fn builtin_types_cannot_be_extended_by_default() {
+    let mut engine = Engine::new();
+    let error = try_run::<Value>(&mut engine, "Number impl func seconds() = self * 1000 end")
+        .expect_err("extending Number should fail");
+    assert!(error
+        .to_string()
+        .contains("Number is a built-in type, and extending built-in types is not enabled"));
+}
+
+#[test]
+fn builtin_types_can_be_extended_when_enabled() {
+    let mut engine = Engine::new();
+    engine.set_builtin_extensions(true);
+
+    let _: Value = engine
+        .start(
+            "prelude.mi",
+            r#"
+                Number impl
+                    func seconds() = self * 1000
+                    func from_seconds(ms) static = ms / 1000
+                end
+                String impl
+                    func shout() = self.to_uppercase.cat("!")
+                end
+                List impl
+                    func second() = self.get(1)
+                end
+            "#,
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+
+    // Extensions are visible to every script run by the engine afterwards.
+    let seconds: f64 = run(&mut engine, "(5).seconds");
+    assert_eq!(seconds, 5000.0);
+    let from_seconds: f64 = run(&mut engine, "Number.from_seconds(2000)");
+    assert_eq!(from_seconds, 2.0);
+    let shout: String = run(&mut engine, r#""hi".shout"#);
+    assert_eq!(shout, "HI!");
+    let second: f64 = run(&mut engine, "[1, 2, 3].second");
+    assert_eq!(second, 2.0);
+}
+
+#[test]
+fn extensions_cannot_replace_existing_methods() {
+    let mut engine = Engine::new();
+    engine.set_builtin_extensions(true);
+    let error = try_run::<Value>(&mut engine, "String impl func cat(other) = other end")
+        .expect_err("replacing String.cat should fail");
+    assert!(error
+        .to_string()
+        .contains("method cat/1 is already implemented by String"));
+    // The original method is left intact.
+    let cat: String = run(&mut engine, r#""a".cat("b")"#);
+    assert_eq!(cat, "ab");
+}
+
+#[test]
+fn extensions_cannot_declare_constructors_or_traits() {
+    let mut engine = Engine::new();
+    engine.set_builtin_extensions(true);
+    for source in [
+        "Number impl func new() constructor = nil end",
+        "trait T func t() end\nNumber impl as T func t() = nil end end",
+    ] {
+        let error =
+            try_run::<Value>(&mut engine, source).expect_err("the extension should be rejected");
+        assert!(error.to_string().contains(
+            "extensions of built-in type Number can only contain instance and static functions"
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+use mica::{Engine, FiberState, Value};
+
+use super::RevealResultExt;
+
+fn function_names(stack: &[mica::ll::error::StackTraceEntry]) -> Vec<&str> {
+    stack.iter().map(|entry| &*entry.function_name).collect()
+}
+
+#[test]
+fn suspended_fibers_report_where_they_are() {
+    let mut engine = Engine::new();
+    let mut fiber = engine
+        .start(
+            "test.mi",
+            "let channel = Channel.new()\nfunc wait() = channel.receive()\nwait()",
+        )
+        .reveal();
+    assert_eq!(fiber.state(), FiberState::Running);
+    assert_eq!(function_names(&fiber.stack()), ["<main>"]);
+
+    let _: Option<Value> = fiber.resume().reveal();
+    assert_eq!(fiber.state(), FiberState::Suspended);
+    let stack = fiber.stack();
+    assert_eq!(function_names(&stack), ["wait", "<main>"]);
+    assert_eq!(stack[0].location.line, 2);
+    assert_eq!(stack[1].location.line, 3);
+    let current = fiber.current_frame().unwrap();
+    assert_eq!(&*current.function_name, "wait");
+    assert_eq!(&*current.module_name, "test.mi");
+}
+
+#[test]
+fn finished_and_errored_fibers_have_no_stack() {
+    let mut engine = Engine::new();
+    let mut fiber = engine.start("finished.mi", "1").reveal();
+    while fiber.resume::<Value>().reveal().is_some() {}
+    assert_eq!(fiber.state(), FiberState::Finished);
+    assert!(fiber.current_frame().is_none());
+
+    let mut fiber = engine.start("errored.mi", "error(\"oops\")").reveal();
+    assert!(fiber.resume::<Value>().is_err());
+    assert_eq!(fiber.state(), FiberState::Errored);
+    assert!(fiber.stack().is_empty());
+}
+
+#[test]
+fn fibers_out_of_budget_continue_where_they_left_off() {
+    let mut engine = Engine::new();
+    let mut fiber = engine
+        .start("test.mi", "let i = 0\nwhile i < 10 do i = i + 1 end\ni")
+        .reveal();
+    assert!(fiber.resume_with_budget(5).reveal());
+    assert_eq!(fiber.state(), FiberState::Running);
+    assert_eq!(fiber.current_frame().unwrap().location.line, 2);
+    let result: f64 = fiber.trampoline().reveal();
+    assert_eq!(result, 10.0);
+}
+
+#[test]
+fn blocking_is_not_running_out_of_budget() {
+    let mut engine = Engine::new();
+    let mut fiber = engine.start("test.mi", "Channel.new().receive()").reveal();
+    assert!(!fiber.resume_with_budget(1000).reveal());
+    assert_eq!(fiber.state(), FiberState::Suspended);
+}
+
+#[test]
+fn yielded_values_are_returned_to_the_host() {
+    let mut engine = Engine::new();
+    let mut fiber = engine
+        .start(
+            "entity.mi",
+            r#"
+                let position = 0
+                while position < 3 do
+                    let speed = yield(position)
+                    position = position + speed
+                end
+                "arrived"
+            "#,
+        )
+        .reveal();
+    assert_eq!(fiber.resume::<f64>().reveal(), Some(0.0));
+    assert_eq!(fiber.state(), FiberState::Yielded);
+    assert_eq!(
+        fiber.resume_with::<f64>(Value::new(1.0)).reveal(),
+        Some(1.0)
+    );
+    assert_eq!(
+        fiber.resume_with::<f64>(Value::new(1.0)).reveal(),
+        Some(2.0)
+    );
+    let arrived: Option<String> = fiber.resume_with(Value::new(1.0)).reveal();
+    assert_eq!(arrived.as_deref(), Some("arrived"));
+    assert_eq!(fiber.state(), FiberState::Finished);
+}
+
+#[test]
+fn values_passed_to_yields_survive_collections() {
+    let mut engine = Engine::new();
+    engine.set_gc_stress(true);
+    let mut fiber = engine
+        .start("test.mi", "let name = yield(nil)\n[name, [1, 2, 3]]\nname")
+        .reveal();
+    let _: Option<Value> = fiber.resume().reveal();
+    let name: Option<String> = fiber.resume_with(Value::new("mica")).reveal();
+    assert_eq!(name.as_deref(), Some("mica"));
+}
+
+#[test]
+fn stepping_stops_at_yields_and_once_finished() {
+    let mut engine = Engine::new();
+    let mut fiber = engine
+        .start(
+            "test.mi",
+            "let i = 0\nwhile i < 20 do i = i + 1 end\nyield(i)\ni = 0",
+        )
+        .reveal();
+    let mut steps = 1;
+    while fiber.step(10).reveal() == FiberState::Running {
+        steps += 1;
+    }
+    assert!(steps > 2);
+    assert_eq!(fiber.state(), FiberState::Yielded);
+    assert!(!fiber.is_finished());
+    assert_eq!(fiber.step(10).reveal(), FiberState::Finished);
+    assert!(fiber.is_finished());
+    assert_eq!(fiber.step(10).reveal(), FiberState::Finished);
+}
+
+#[test]
+fn errored_fibers_are_finished() {
+    let mut engine = Engine::new();
+    let mut fiber = engine.start("test.mi", "error(\"oops\")").reveal();
+    assert!(fiber.step(100).is_err());
+    assert!(fiber.is_finished());
+}
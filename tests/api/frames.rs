@@ -0,0 +1,127 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use mica::{Engine, Value};
+
+use super::{run, RevealResultExt};
+
+/// Counts the allocations made by each thread, so that tests running in parallel don't see each
+/// other's allocations.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations made while running the function `call` with the given
+/// number of iterations.
+fn allocations_while_calling(call: &str, iterations: usize) -> usize {
+    let mut engine = Engine::new();
+    let _: Value = run(
+        &mut engine,
+        &format!(
+            r#"
+                    struct Point impl
+                        func new(x, y) constructor = do
+                            @x = x
+                            @y = y
+                        end
+
+                        func x() = @x
+                        func sum(other) = @x + other.x
+                    end
+
+                    func add(a, b) = a + b
+                    func nested(a) = add(a, add(a, a))
+                    func fib(n) = if n < 2 do n else fib(n - 1) + fib(n - 2) end
+                    func rest(first, ...rest) = first
+                    let point = Point.new(1, 2)
+
+                    func run(n) = do
+                        let i = 0
+                        let sum = 0
+                        while i < n do
+                            sum = sum + {call}
+                            i = i + 1
+                        end
+                        sum
+                    end
+                "#
+        ),
+    );
+    let run: Value = engine.get("run").reveal();
+    let before = ALLOCATIONS.with(Cell::get);
+    let _: Value = engine.call(run, (iterations as f64,)).reveal();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Checks that calling a function many times allocates no more than calling it a few times.
+fn assert_calls_do_not_allocate(call: &str) {
+    let few = allocations_while_calling(call, 10);
+    let many = allocations_while_calling(call, 10_000);
+    assert_eq!(few, many, "{call} allocates on every call");
+}
+
+#[test]
+fn function_calls_do_not_allocate() {
+    assert_calls_do_not_allocate("add(i, 1)");
+    assert_calls_do_not_allocate("nested(i)");
+}
+
+#[test]
+fn method_calls_do_not_allocate() {
+    assert_calls_do_not_allocate("point.x");
+    assert_calls_do_not_allocate("point.sum(point)");
+}
+
+#[test]
+fn deep_recursion_does_not_allocate_once_the_stack_has_grown() {
+    let shallow = allocations_while_calling("fib(2)", 100);
+    let deep = allocations_while_calling("fib(20)", 100);
+    assert_eq!(shallow, deep);
+}
+
+#[test]
+fn rest_parameters_allocate_a_list() {
+    let few = allocations_while_calling("rest(i, 1, 2)", 10);
+    let many = allocations_while_calling("rest(i, 1, 2)", 1000);
+    assert!(many > few);
+}
+
+#[test]
+fn calls_from_the_host_reuse_stacks() {
+    let mut engine = Engine::new();
+    let _: Value = run(
+        &mut engine,
+        "func depth(n) = if n == 0 do 0 else 1 + depth(n - 1) end",
+    );
+    let depth = engine.get_function::<_, f64>("depth").reveal();
+    // The first call grows the stacks to fit the recursion.
+    assert_eq!(depth.call(&mut engine, (100.0,)).reveal(), 100.0);
+    let before = ALLOCATIONS.with(Cell::get);
+    for _ in 0..100 {
+        assert_eq!(depth.call(&mut engine, (100.0,)).reveal(), 100.0);
+    }
+    assert_eq!(ALLOCATIONS.with(Cell::get) - before, 0);
+}
@@ -0,0 +1,112 @@
+use mica::{
+    ll::lexer::{Lexer, TokenKind},
+    Engine, LanguageErrorKind, Value,
+};
+
+use super::RevealResultExt;
+
+fn compile_error(source: &str) -> String {
+    let mut engine = Engine::new();
+    let error = engine
+        .compile("front_matter.mi", source)
+        .expect_err("compilation should fail");
+    format!("{error}")
+}
+
+#[test]
+fn front_matter_is_surfaced_through_the_script() {
+    let mut engine = Engine::new();
+    let source = "#!/usr/bin/env mica\n---\nname = \"greeter\"\n\n# The script's version.\nversion = 1.0\n---\n\"hi\"";
+    let script = engine.compile("front_matter.mi", source).reveal();
+    let front_matter = script.front_matter();
+    assert_eq!(front_matter.get("name"), Some("greeter"));
+    assert_eq!(front_matter.get("version"), Some("1.0"));
+    assert_eq!(front_matter.get("author"), None);
+    assert_eq!(
+        front_matter.iter().collect::<Vec<_>>(),
+        [("name", "greeter"), ("version", "1.0")]
+    );
+    let result: String = script.into_fiber().trampoline().reveal();
+    assert_eq!(result, "hi");
+}
+
+#[test]
+fn front_matter_is_optional() {
+    let mut engine = Engine::new();
+    let script = engine
+        .compile("front_matter.mi", "#!/usr/bin/env mica\n1")
+        .reveal();
+    assert!(script.front_matter().is_empty());
+    assert!(script.front_matter().span().is_none());
+    let script = engine.compile("front_matter.mi", "---\n---\n1").reveal();
+    assert!(script.front_matter().is_empty());
+    assert!(script.front_matter().span().is_some());
+}
+
+#[test]
+fn front_matter_must_be_at_the_start() {
+    let mut engine = Engine::new();
+    // Anywhere else, `---` is three negations.
+    let result: f64 = engine
+        .start("front_matter.mi", "\n---\n1")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(result, -1.0);
+    let _: Value = engine
+        .start("front_matter.mi", "#!/usr/bin/env mica\n\n---\n1")
+        .reveal()
+        .trampoline()
+        .reveal();
+}
+
+#[test]
+fn malformed_front_matter_is_reported() {
+    assert_eq!(
+        compile_error("---\nname = \"a\"\nno value\n---\n1"),
+        "front_matter.mi:3:1: error: front matter entries must be of the form 'key = value'"
+    );
+    assert_eq!(
+        compile_error("---\n = 1\n---\n1"),
+        "front_matter.mi:2:1: error: front matter entries must be of the form 'key = value'"
+    );
+    assert_eq!(
+        compile_error("---\nname = a\nname = b\n---\n1"),
+        "front_matter.mi:3:1: error: front matter key 'name' is declared more than once"
+    );
+    assert_eq!(
+        compile_error("#!/usr/bin/env mica\n---\nname = a\n"),
+        "front_matter.mi:2:1: error: front matter is missing its closing '---'"
+    );
+}
+
+#[test]
+fn errors_in_front_matter_do_not_cascade() {
+    let mut engine = Engine::new();
+    let error = engine
+        .compile("front_matter.mi", "---\nthis is } not valid\n---\n1")
+        .expect_err("compilation should fail");
+    let errors = error.compile_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        errors[0].kind(),
+        LanguageErrorKind::InvalidFrontMatterEntry
+    ));
+}
+
+#[test]
+fn front_matter_is_tokenized_as_a_comment() {
+    let source = "#!/usr/bin/env mica\n---\nname = a\n---\nx";
+    let tokens: Vec<_> = Lexer::new("tokens".into(), source.to_owned())
+        .into_tokens()
+        .map(|(kind, span)| (kind, &source[span.start.byte..span.end.byte]))
+        .collect();
+    assert_eq!(
+        tokens,
+        [
+            (TokenKind::Comment, "#!/usr/bin/env mica"),
+            (TokenKind::Comment, "---\nname = a\n---\n"),
+            (TokenKind::Identifier("x".into()), "x"),
+        ]
+    );
+}
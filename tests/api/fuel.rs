@@ -0,0 +1,127 @@
+use mica::{Engine, EngineContext, Error, Scheduler, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+const COUNT_TO_1000: &str = "let i = 0\nwhile i < 1000 do i = i + 1 end\ni";
+
+/// Resumes the fiber with `refill` fuel until it finishes, and returns how many times it ran out
+/// along with its result.
+fn run_refueling(engine: &mut Engine, source: &str, refill: u64) -> (usize, f64) {
+    engine.set_fuel(Some(refill));
+    let mut fiber = engine.start("test.mi", source).reveal();
+    let mut refuels = 0;
+    loop {
+        match fiber.resume::<Value>() {
+            Ok(Some(_)) => (),
+            Ok(None) => break,
+            Err(Error::FuelExhausted) => {
+                refuels += 1;
+                fiber.set_fuel(Some(refill));
+            }
+            Err(error) => panic!("unexpected error: {error:#}"),
+        }
+    }
+    engine.set_fuel(None);
+    let result: f64 = engine.get("i").reveal();
+    (refuels, result)
+}
+
+#[test]
+fn there_is_no_fuel_limit_by_default() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.fuel(), None);
+    let result: f64 = run(&mut engine, COUNT_TO_1000);
+    assert_eq!(result, 1000.0);
+}
+
+#[test]
+fn runaway_scripts_are_interrupted() {
+    let mut engine = Engine::new();
+    engine.set_fuel(Some(10_000));
+    let result: Result<Value, _> = try_run(&mut engine, "while true do end");
+    assert!(matches!(result, Err(Error::FuelExhausted)));
+    assert_eq!(engine.fuel(), Some(0));
+}
+
+#[test]
+fn fibers_continue_where_they_left_off_after_refueling() {
+    let mut engine = Engine::new();
+    let (refuels, result) = run_refueling(&mut engine, COUNT_TO_1000, 100);
+    assert!(refuels > 10);
+    assert_eq!(result, 1000.0);
+}
+
+#[test]
+fn coroutines_draw_from_the_same_fuel() {
+    let mut engine = Engine::new();
+    let (refuels, result) = run_refueling(
+        &mut engine,
+        r#"
+            let i = 0
+            let f = Fiber.new(func () = do
+                while i < 1000 do i = i + 1 end
+            end)
+            f.resume
+        "#,
+        100,
+    );
+    assert!(refuels > 10);
+    assert_eq!(result, 1000.0);
+}
+
+#[test]
+fn functions_called_by_foreign_functions_draw_from_the_same_fuel() {
+    let mut engine = Engine::new();
+    engine
+        .add_function(
+            "call",
+            |context: &mut EngineContext, f: Value| -> Result<Value, Error> { context.call(f, ()) },
+        )
+        .reveal();
+    engine.set_fuel(Some(100));
+    let result: Result<Value, _> = try_run(&mut engine, "call(func () = do while true do end end)");
+    assert!(matches!(result, Err(Error::FuelExhausted)));
+
+    // The foreign function is called again from the start once the engine is refueled.
+    let (refuels, result) = run_refueling(
+        &mut engine,
+        "let i = 0\ncall(func () = do i = 0\nwhile i < 10 do i = i + 1 end end)",
+        1000,
+    );
+    assert_eq!(refuels, 0);
+    assert_eq!(result, 10.0);
+}
+
+#[test]
+fn fuel_exhaustion_cannot_be_caught_by_scripts() {
+    let mut engine = Engine::new();
+    engine.set_fuel(Some(1000));
+    let result: Result<Value, _> = try_run(
+        &mut engine,
+        "try do while true do end end catch e, _\n    \"caught\"\nend",
+    );
+    assert!(matches!(result, Err(Error::FuelExhausted)));
+}
+
+#[test]
+fn schedulers_stop_when_the_engine_runs_out_of_fuel() {
+    let mut engine = Engine::new();
+    engine.set_fuel(Some(500));
+    let mut scheduler = Scheduler::new(&mut engine);
+    let first = scheduler.start("first.mi", COUNT_TO_1000).reveal();
+    let second = scheduler.start("second.mi", COUNT_TO_1000).reveal();
+    let mut refuels = 0;
+    loop {
+        match scheduler.run() {
+            Ok(()) => break,
+            Err(Error::FuelExhausted) => {
+                refuels += 1;
+                scheduler.engine().set_fuel(Some(500));
+            }
+            Err(error) => panic!("unexpected error: {error:#}"),
+        }
+    }
+    assert!(refuels > 1);
+    assert_eq!(scheduler.result::<f64>(first).reveal(), Some(1000.0));
+    assert_eq!(scheduler.result::<f64>(second).reveal(), Some(1000.0));
+}
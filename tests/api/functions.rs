@@ -2,7 +2,7 @@
 
 use mica::{Engine, TryFromValue, TypeBuilder, UserData, Value};
 
-use super::RevealResultExt;
+use super::{run, try_run, RevealResultExt};
 
 #[derive(Debug, Clone, PartialEq)]
 struct Vec2 {
@@ -34,11 +34,7 @@ fn user_data_parameters_can_be_passed_into_functions() {
         })
         .reveal();
 
-    let _: Value = engine
-        .start("test.mi", "same_vec2(Vec2.new(1, 2), 1, 2)")
-        .reveal()
-        .trampoline()
-        .reveal();
+    let _: Value = run(&mut engine, "same_vec2(Vec2.new(1, 2), 1, 2)");
 }
 
 #[test]
@@ -48,7 +44,7 @@ fn user_data_parameters_cause_type_errors() {
     engine.add_type(TypeBuilder::<Vec2>::new("Vec2")).reveal();
     engine.add_function("eat_vec2", |_: Vec2| ()).reveal();
 
-    let result: Result<Value, _> = engine.start("test.mi", "eat_vec2(1)").reveal().trampoline();
+    let result: Result<Value, _> = try_run(&mut engine, "eat_vec2(1)");
     assert!(result.is_err());
 }
 
@@ -58,7 +54,7 @@ fn unregistered_user_data_parameters_cause_type_errors() {
 
     engine.add_function("eat_vec2", |_: Vec2| ()).reveal();
 
-    let result: Result<Value, _> = engine.start("test.mi", "eat_vec2(1)").reveal().trampoline();
+    let result: Result<Value, _> = try_run(&mut engine, "eat_vec2(1)");
     assert!(result.is_err());
 }
 
@@ -74,15 +70,134 @@ fn mutable_aliasing_of_user_data_is_not_allowed() {
         )
         .reveal();
 
-    let result: Result<Value, _> = engine
-        .start(
-            "test.mi",
-            r#"
+    let result: Result<Value, _> = try_run(
+        &mut engine,
+        r#"
                 let v = Vec2.new(0, 0)
                 v.mutably_alias(v)
             "#,
-        )
-        .reveal()
-        .trampoline();
+    );
     assert!(result.is_err());
 }
+
+#[test]
+fn cascades_configure_user_data_in_place() {
+    let mut engine = Engine::new();
+
+    engine
+        .add_type(
+            TypeBuilder::<Vec2>::new("Vec2")
+                .add_static("new", |x, y| Vec2 { x, y })
+                .add_function("set_x", |v: &mut Vec2, x: f32| v.x = x)
+                .add_function("set_y", |v: &mut Vec2, y: f32| v.y = y),
+        )
+        .reveal();
+
+    let v: Vec2 = run(&mut engine, "Vec2.new(0, 0)..set_x(1)..set_y(2)");
+    assert_eq!(v, Vec2 { x: 1.0, y: 2.0 });
+}
+
+#[test]
+fn struct_patterns_destructure_user_data_through_getters() {
+    let mut engine = Engine::new();
+
+    engine
+        .add_type(
+            TypeBuilder::<Vec2>::new("Vec2")
+                .add_static("new", |x, y| Vec2 { x, y })
+                .add_function("x", |v: &Vec2| v.x)
+                .add_function("y", |v: &Vec2| v.y),
+        )
+        .reveal();
+
+    let sum: f32 = run(
+        &mut engine,
+        r#"
+                let Vec2 { x, y } = Vec2.new(1, 2)
+                assert(if let Vec2 { x } = { x: 1 } do false else true end)
+                x + y
+            "#,
+    );
+    assert_eq!(sum, 3.0);
+}
+
+#[test]
+fn typed_functions_can_be_called_repeatedly() {
+    let mut engine = Engine::new();
+    let _: Value = run(
+        &mut engine,
+        r#"
+                let calls = 0
+                func greet(name, punctuation) = do
+                    calls = calls + 1
+                    "Hello, ".cat(name).cat(punctuation)
+                end
+                func count() = calls
+                func count_all(first, ...rest) = 1 + rest.len
+            "#,
+    );
+
+    let greet = engine
+        .get_function::<(&str, char), String>("greet")
+        .reveal();
+    assert_eq!(
+        greet.call(&mut engine, ("world", '!')).reveal(),
+        "Hello, world!"
+    );
+    assert_eq!(
+        greet.call(&mut engine, ("Mica", '?')).reveal(),
+        "Hello, Mica?"
+    );
+    let count = engine.get_function::<(), usize>("count").reveal();
+    assert_eq!(count.call(&mut engine, ()).reveal(), 2);
+
+    let count_all: Value = engine.get("count_all").reveal();
+    let count_all = count_all
+        .into_typed_fn::<(f64, f64, f64), usize>(&engine)
+        .reveal();
+    assert_eq!(count_all.call(&mut engine, (1.0, 2.0, 3.0)).reveal(), 3);
+}
+
+#[test]
+fn typed_functions_check_their_signature() {
+    let mut engine = Engine::new();
+    let _: Value = run(&mut engine, "func one(x) = x\nlet not_a_function = 1");
+
+    assert!(engine.get_function::<(), Value>("one").is_err());
+    assert!(engine.get_function::<(f64, f64), Value>("one").is_err());
+    assert!(engine
+        .get_function::<(f64,), Value>("not_a_function")
+        .is_err());
+    assert!(engine.get_function::<(f64,), Value>("undeclared").is_err());
+
+    let one = engine.get_function::<(f64,), String>("one").reveal();
+    assert!(one.call(&mut engine, (1.0,)).is_err());
+}
+
+#[test]
+fn calls_convert_their_arguments() {
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Vec2>::new("Vec2")
+                .add_function("x", |v: &Vec2| v.x)
+                .add_function("scaled", |v: &Vec2, by: f32| Vec2 {
+                    x: v.x * by,
+                    y: v.y * by,
+                }),
+        )
+        .reveal();
+    let describe: Value = run(
+        &mut engine,
+        "(func (n, s, b, v) = (n + 1, s.cat(\"!\"), !b, v.x))",
+    );
+
+    let result: (f64, String, bool, f64) = engine
+        .call(describe, (41, "hi", true, Vec2 { x: 3.0, y: 4.0 }))
+        .reveal();
+    assert_eq!(result, (42.0, "hi!".to_owned(), false, 3.0));
+
+    let v = engine.create_value(Vec2 { x: 1.0, y: 2.0 });
+    let scaled: Vec2 = engine.call_method(v, ("scaled", 1), (2,)).reveal();
+    assert_eq!(scaled, Vec2 { x: 2.0, y: 4.0 });
+}
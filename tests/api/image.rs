@@ -0,0 +1,192 @@
+use mica::{Engine, Error, HeapImage, TypeBuilder, UserData, Value};
+
+use super::{run, RevealResultExt};
+
+#[derive(Debug)]
+struct Handle;
+
+impl UserData for Handle {}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.add_function("double", |x: f64| x * 2.0).reveal();
+    engine
+        .add_type(TypeBuilder::<Handle>::new("Handle").add_static("new", || Handle))
+        .reveal();
+    engine
+}
+
+const STATE: &str = r#"
+    trait Shape
+        func area()
+    end
+
+    struct Square impl
+        func new(side) constructor = do
+            @side = side
+            @neighbor = nil
+        end
+
+        func link(other) = do @neighbor = other end
+        func neighbor() = @neighbor
+
+        as Shape
+            func area() = @side * @side
+        end
+    end
+
+    let get = nil
+    let inc = nil
+    do
+        let count = 1
+        get = func () = count
+        inc = func () = count = count + 1
+    end
+    inc()
+
+    let square = Square.new(3)
+    square.link(square)
+    let cycle = [1, "two"]
+    cycle.push(cycle)
+    let alias = cycle
+    let lookup = [[1, 2]: "list key", "nested": [(1, { x: 0 / 0, y: "y" }), ()]]
+    let host = [double, Handle, Shape]
+"#;
+
+const CHECK: &str = r#"
+    assert(get() == 2)
+    assert(inc() == 3)
+    assert(get() == 3)
+
+    assert(square.neighbor == square)
+    assert(Shape.area(square) == 9)
+    assert(square implements Shape)
+    assert(type_of(square) == Square)
+
+    assert(cycle.len == 3)
+    assert(cycle.get(2).get(1) == "two")
+    alias.push(4)
+    assert(cycle.len == 4)
+
+    assert(lookup.get([1, 2]) == "list key")
+    let record = lookup.get("nested").get(0)._1
+    assert(record.x != record.x)
+    assert(record.y == "y")
+
+    assert(host.get(0)(2) == 4)
+    host.get(1).new()
+    assert(host.get(1) == Handle)
+    assert(host.get(2) == Shape)
+"#;
+
+#[test]
+fn restored_globals_behave_like_the_saved_ones() {
+    let mut saved = engine();
+    let _: Value = run(&mut saved, STATE);
+    let image = saved.snapshot().reveal();
+    let _: Value = run(&mut saved, CHECK);
+
+    let mut restored = engine();
+    restored.compile("test.mi", STATE).reveal();
+    restored.restore(&image).reveal();
+    let _: Value = run(&mut restored, CHECK);
+}
+
+#[test]
+fn images_can_be_saved_as_bytes() {
+    let mut saved = engine();
+    let _: Value = run(&mut saved, STATE);
+    let bytes = saved.snapshot().reveal().into_bytes();
+
+    let mut restored = engine();
+    restored.compile("test.mi", STATE).reveal();
+    restored.restore(&HeapImage::from_bytes(bytes)).reveal();
+    let _: Value = run(&mut restored, CHECK);
+}
+
+#[test]
+fn deeply_nested_values_can_be_saved() {
+    let mut saved = Engine::new();
+    let source = r#"
+        let list = []
+        let tuple = ()
+        for _ in countup(1, 50000) do
+            list = [list]
+            tuple = (tuple, 1)
+        end
+    "#;
+    let _: Value = run(&mut saved, source);
+    let image = saved.snapshot().reveal();
+
+    let mut restored = Engine::new();
+    restored.compile("test.mi", source).reveal();
+    restored.restore(&image).reveal();
+    let depth: f64 = restored
+        .start(
+            "depth.mi",
+            r#"
+                let depth = 0
+                while list.len > 0 do
+                    list = list.get(0)
+                    tuple = tuple._0
+                    depth = depth + 1
+                end
+                depth
+            "#,
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(depth, 50000.0);
+}
+
+#[test]
+fn user_data_that_is_not_a_global_cannot_be_saved() {
+    let mut engine = engine();
+    let _: Value = run(&mut engine, "let handle = Handle.new()");
+    match engine.snapshot() {
+        Err(Error::UnsupportedInImage { type_name }) => assert_eq!(type_name, "Handle"),
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn images_cannot_be_restored_in_engines_running_different_code() {
+    let mut saved = engine();
+    let _: Value = run(&mut saved, STATE);
+    let image = saved.snapshot().reveal();
+
+    let mut restored = engine();
+    restored
+        .compile("test.mi", STATE.replace("@side * @side", "@side * 2"))
+        .reveal();
+    let _: Value = run(&mut restored, "let cycle = 1");
+    let error = restored.restore(&image).unwrap_err();
+    assert!(matches!(error, Error::IncompatibleImage(_)));
+    assert!(error.to_string().contains("is not the same"));
+    let cycle: f64 = restored.get("cycle").reveal();
+    assert_eq!(cycle, 1.0);
+
+    let error = engine().restore(&image).unwrap_err();
+    assert!(error.to_string().contains("functions"));
+}
+
+#[test]
+fn damaged_images_are_rejected() {
+    let mut saved = engine();
+    let _: Value = run(&mut saved, STATE);
+    let bytes = saved.snapshot().reveal().into_bytes();
+
+    let mut restored = engine();
+    restored.compile("test.mi", STATE).reveal();
+    for len in [0, 5, bytes.len() / 2, bytes.len() - 1] {
+        let image = HeapImage::from_bytes(&bytes[..len]);
+        assert!(matches!(
+            restored.restore(&image),
+            Err(Error::IncompatibleImage(_))
+        ));
+    }
+    let mut garbage = bytes.clone();
+    garbage.push(0);
+    assert!(restored.restore(&HeapImage::from_bytes(garbage)).is_err());
+}
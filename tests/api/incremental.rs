@@ -0,0 +1,187 @@
+use mica::{Engine, GcMode, Value};
+
+use super::{run, RevealResultExt};
+
+/// An engine that takes tiny steps, so that scripts spend most of their time running while a
+/// collection is in progress.
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_gc_mode(GcMode::Incremental { step_size: 8 });
+    engine
+}
+
+#[test]
+fn objects_modified_during_a_collection_are_kept_alive() {
+    let mut engine = engine();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            struct Node impl
+                func new(value, next) constructor = do
+                    @value = value
+                    @next = next
+                end
+
+                func value() = @value
+                func next() = @next
+                func set_next(next) = @next = next
+            end
+
+            struct Cell impl
+                func new(value) constructor = @value = value
+                func value() = @value
+                func set(value) = @value = value
+            end
+
+            func counter() = do
+                let count = 0
+                let increment = func () = do
+                    count = count + 1
+                    [count]
+                end
+                increment
+            end
+
+            # Objects that are only ever moved around, such that they may end up in an object that
+            # was already marked while the one they were taken from is yet to be marked.
+            let left = Cell.new([1])
+            let right = Cell.new([2])
+            let lists = [[[3]], [[4]]]
+            let captured = [5]
+            let swap_captured = func (value) = do
+                let previous = captured
+                captured = value
+                previous
+            end
+
+            let head = Node.new([0], nil)
+            let list = []
+            let dict = [:]
+            let next = counter()
+            let fiber = Fiber.new(func () = do
+                let held = []
+                while true do
+                    held.push([held.len])
+                    yield(held)
+                end
+            end)
+
+            let i = 1
+            while i <= 5000 do
+                # Every kind of object gets a fresh object stored in it, long after it was created.
+                head = Node.new([i], head)
+                head.next.set_next(Node.new([i, -i], head.next.next))
+                list.push((i, [i]))
+                dict[i] = [i]
+                list[list.len - 1] = [i]
+                let swapped = left.value
+                left.set(right.value)
+                right.set(swapped)
+                swapped = lists.get(0).get(0)
+                lists.get(0)[0] = lists.get(1).get(0)
+                lists.get(1)[0] = swapped
+                left.set(swap_captured(left.value))
+                let last = next()
+                let held = fiber.resume
+                # Drop some of the objects, so that there is garbage to collect.
+                if i % 100 == 50 do
+                    list = []
+                    dict = [:]
+                end
+                assert(last.get(0) == i)
+                assert(held.get(held.len - 1).get(0) == i - 1)
+                i = i + 1
+            end
+
+            let count = 0
+            let sum = 0
+            let negated_sum = 0
+            let node = head
+            while node != nil do
+                count = count + 1
+                sum = sum + node.value.get(0)
+                if node.value.len == 2 do
+                    negated_sum = negated_sum + node.value.get(1)
+                end
+                node = node.next
+            end
+            assert(count == 10001)
+            assert(sum == 5000 * 5001)
+            assert(negated_sum == -5000 * 5001 / 2)
+            let moved = [left.value, right.value, lists.get(0).get(0), lists.get(1).get(0), captured]
+            let moved_sum = 0
+            for value in moved.iter do
+                moved_sum = moved_sum + value.get(0)
+            end
+            assert(moved_sum == 1 + 2 + 3 + 4 + 5)
+            let j = 4951
+            while j <= 5000 do
+                assert(dict[j].get(0) == j)
+                j = j + 1
+            end
+        "#,
+    );
+    let stats = engine.gc_stats();
+    assert!(stats.collections >= 2);
+    assert!(stats.pauses > stats.collections);
+    assert!(stats.freed_bytes > 0);
+}
+
+#[test]
+fn values_held_by_the_host_survive_collections() {
+    let mut engine = engine();
+    let held: Value = run(&mut engine, "[[1], [2]]");
+    engine.set("held", held.clone()).reveal();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let i = 0
+            while i < 5000 do
+                held.push([i])
+                let garbage = [i, [i]]
+                i = i + 1
+            end
+        "#,
+    );
+    assert!(engine.gc_stats().collections >= 1);
+    let sum: f64 = run(
+        &mut engine,
+        r#"
+            Gc.collect()
+            let sum = 0
+            for element in held.iter do
+                sum = sum + element.get(0)
+            end
+            sum
+        "#,
+    );
+    assert_eq!(sum, 3.0 + 4999.0 * 5000.0 / 2.0);
+    drop(held);
+}
+
+#[test]
+fn collections_can_go_back_to_stopping_the_world() {
+    let mut engine = engine();
+    let source = r#"
+        let xs = []
+        let i = 0
+        while i < 2000 do
+            xs.push([i])
+            i = i + 1
+        end
+        xs
+    "#;
+    let first: Value = run(&mut engine, source);
+    engine.set_gc_mode(GcMode::StopTheWorld);
+    assert_eq!(engine.gc_mode(), GcMode::StopTheWorld);
+    let second: Value = run(&mut engine, source);
+    let _: Value = run(&mut engine, "Gc.collect()");
+    let stats = engine.gc_stats();
+    assert!(stats.pauses > stats.collections);
+    let len = |engine: &mut Engine, xs: Value| -> f64 {
+        engine.set("xs", xs).reveal();
+        run(engine, "xs.len")
+    };
+    assert_eq!(len(&mut engine, first), 2000.0);
+    assert_eq!(len(&mut engine, second), 2000.0);
+}
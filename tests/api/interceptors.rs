@@ -0,0 +1,124 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mica::{Engine, Error, Interception, TypeBuilder, UserData, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+struct Http;
+
+impl UserData for Http {}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Http>::new("Http")
+                .add_static("get", |url: String| format!("real response from {url}")),
+        )
+        .reveal();
+    engine
+}
+
+#[test]
+fn interceptors_can_replace_host_methods() {
+    let mut engine = engine();
+    let stub = engine
+        .intercept("Http", ("get", 1), |_, arguments| {
+            Ok(Interception::Return(Value::new(format!(
+                "stubbed response from {}",
+                arguments[0]
+            ))))
+        })
+        .reveal();
+    let response: Value = run(&mut engine, r#"Http.get("example.com")"#);
+    assert_eq!(response.to_string(), "stubbed response from example.com");
+
+    assert!(engine.remove_interceptor(stub));
+    assert!(!engine.remove_interceptor(stub));
+    let response: Value = run(&mut engine, r#"Http.get("example.com")"#);
+    assert_eq!(response.to_string(), "real response from example.com");
+}
+
+#[test]
+fn interceptors_can_observe_calls_and_let_them_through() {
+    let mut engine = engine();
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&calls);
+    engine
+        .intercept("Counter", ("add", 1), move |receiver, arguments| {
+            log.borrow_mut()
+                .push(format!("{}.add({})", receiver.type_name(), arguments[0]));
+            Ok(Interception::Proceed)
+        })
+        .reveal();
+    let total: f64 = run(
+        &mut engine,
+        r#"
+                struct Counter impl
+                    func new() constructor = do @total = 0 end
+                    func add(n) = do @total = @total + n end
+                end
+                let counter = Counter.new()
+                counter.add(1)
+                counter.add(2)
+            "#,
+    );
+    assert_eq!(total, 3.0);
+    assert_eq!(*calls.borrow(), ["Counter.add(1)", "Counter.add(2)"]);
+}
+
+#[test]
+fn most_recent_interceptors_are_asked_first() {
+    let mut engine = engine();
+    engine
+        .intercept("Http", ("get", 1), |_, _| {
+            Ok(Interception::Return(Value::new("general")))
+        })
+        .reveal();
+    let specific = engine
+        .intercept("Http", ("get", 1), |_, arguments| {
+            if arguments[0].to_string() == "special.com" {
+                Ok(Interception::Return(Value::new("specific")))
+            } else {
+                Ok(Interception::Proceed)
+            }
+        })
+        .reveal();
+    let responses: Value = run(
+        &mut engine,
+        r#"[Http.get("special.com"), Http.get("example.com")]"#,
+    );
+    assert_eq!(responses.to_string(), "[specific, general]");
+    engine.remove_interceptor(specific);
+    let response: Value = run(&mut engine, r#"Http.get("special.com")"#);
+    assert_eq!(response.to_string(), "general");
+}
+
+#[test]
+fn methods_that_do_not_exist_can_be_stubbed() {
+    let mut engine = engine();
+    engine
+        .intercept("Http", ("post", 2), |_, arguments| {
+            Ok(Interception::Return(Value::new(arguments.len() as f64)))
+        })
+        .reveal();
+    let count: f64 = run(&mut engine, r#"Http.post("example.com", "body")"#);
+    assert_eq!(count, 2.0);
+    // Other types are not affected.
+    let error = try_run::<Value>(&mut engine, r#"[].post(1, 2)"#).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("method post/2 is not defined for List"));
+}
+
+#[test]
+fn errors_from_interceptors_are_raised_in_scripts() {
+    let mut engine = engine();
+    engine
+        .intercept("Http", ("get", 1), |_, _| {
+            Err(Error::User("network is unreachable".into()))
+        })
+        .reveal();
+    let error = try_run::<Value>(&mut engine, r#"Http.get("example.com")"#).unwrap_err();
+    assert!(error.to_string().contains("network is unreachable"));
+}
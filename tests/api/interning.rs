@@ -0,0 +1,145 @@
+use mica::{Engine, Gc, Interning, Value};
+
+use super::{run, RevealResultExt};
+
+fn engine(interning: Option<Interning>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_interning(interning);
+    engine.set_allocation_tracking(true);
+    engine
+}
+
+fn count(engine: &Engine, kind: &str) -> usize {
+    let snapshot = engine.allocation_snapshot().unwrap();
+    snapshot.get(kind).map_or(0, |stats| stats.count)
+}
+
+const DUPLICATES: &str = r#"
+    let xs = []
+    for _ in countup(1, 100) do
+        xs.push(("ke".cat("y"), 1))
+        xs.push("a string that is definitely longer than the limit on interned strings".cat(""))
+    end
+    Gc.collect()
+    xs
+"#;
+
+#[test]
+fn interning_is_disabled_by_default() {
+    let mut engine = engine(None);
+    assert_eq!(engine.interning(), None);
+    let _xs: Value = run(&mut engine, DUPLICATES);
+    assert_eq!(count(&engine, "Tuple(2)"), 100);
+}
+
+#[test]
+fn equal_values_share_one_allocation() {
+    let mut engine = engine(Some(Interning::default()));
+    // Compiling the script allocates its literals, which are then shared by every run of it, so
+    // only strings created by running it are counted.
+    drop(engine.compile("test.mi", DUPLICATES).reveal());
+    let strings_before = count(&engine, "String");
+    let _xs: Value = run(&mut engine, DUPLICATES);
+    assert_eq!(count(&engine, "Tuple(2)"), 1);
+    // The long strings are not interned, while all the "key" strings are the same string.
+    assert_eq!(count(&engine, "String") - strings_before, 101);
+}
+
+#[test]
+fn tuples_of_mutable_values_are_not_interned() {
+    let mut engine = engine(Some(Interning::default()));
+    let _xs: Value = run(
+        &mut engine,
+        r#"
+            let xs = []
+            for _ in countup(1, 10) do
+                xs.push(([], 1))
+            end
+            Gc.collect()
+            xs
+        "#,
+    );
+    assert_eq!(count(&engine, "Tuple(2)"), 10);
+}
+
+#[test]
+fn interned_values_behave_like_regular_values() {
+    let mut engine = engine(Some(Interning {
+        max_string_len: 8,
+        tuples: true,
+    }));
+    let result: Value = run(
+        &mut engine,
+        r#"
+            let a = (1, ("x", nil))
+            let b = (1, ("x", nil))
+            let zero = (0, nil)
+            let negative_zero = (-0, nil)
+            [a == b, "x" == "x", "x".cat("y") == "xy", 1 / zero._0, 1 / negative_zero._0]
+        "#,
+    );
+    assert_eq!(format!("{result:?}"), "[true, true, true, inf, -inf]");
+}
+
+#[test]
+fn interned_values_are_still_collected() {
+    let mut engine = engine(Some(Interning::default()));
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let xs = [("a", 1), ("b", 2)]
+            Gc.collect()
+        "#,
+    );
+    assert_eq!(count(&engine, "Tuple(2)"), 2);
+    let _: Value = run(&mut engine, "xs = nil\nGc.collect()");
+    assert_eq!(count(&engine, "Tuple(2)"), 0);
+    let _xs: Value = run(&mut engine, r#"xs = [("a", 1), ("a", 1)]"#);
+    assert_eq!(count(&engine, "Tuple(2)"), 1);
+}
+
+#[test]
+fn strings_from_the_host_are_interned() {
+    let mut engine = engine(Some(Interning::default()));
+    engine
+        .add_function("name", || String::from("interned"))
+        .reveal();
+    let strings_before = count(&engine, "String");
+    let _names: Value = run(&mut engine, "[name(), name(), name(), \"interned\"]");
+    assert_eq!(count(&engine, "String") - strings_before, 1);
+}
+
+#[test]
+fn string_literals_are_allocated_once() {
+    let mut engine = engine(None);
+    let source = r#"
+        let xs = []
+        for _ in countup(1, 100) do
+            xs.push("a literal that is too long to be interned by the default settings")
+        end
+        Gc.collect()
+        xs
+    "#;
+    drop(engine.compile("test.mi", source).reveal());
+    let strings_before = count(&engine, "String");
+    let _xs: Value = run(&mut engine, source);
+    assert_eq!(count(&engine, "String"), strings_before);
+}
+
+#[test]
+fn strings_interned_by_the_host_are_shared_with_literals() {
+    let mut engine = engine(None);
+    let key = engine.intern("name").reveal();
+    let Value::String(key) = key else {
+        panic!("interned string is not a string: {key:?}");
+    };
+    let literal = run(&mut engine, r#""name""#);
+    let Value::String(literal) = literal else {
+        panic!("literal is not a string: {literal:?}");
+    };
+    assert_eq!(Gc::as_raw(&key), Gc::as_raw(&literal));
+    let Value::String(again) = engine.intern("name").reveal() else {
+        unreachable!()
+    };
+    assert_eq!(Gc::as_raw(&key), Gc::as_raw(&again));
+}
@@ -0,0 +1,139 @@
+use mica::{Engine, GlobalKind, TypeBuilder, UserData};
+
+use super::try_run;
+
+struct Counter;
+
+impl UserData for Counter {}
+
+#[test]
+fn globals_can_be_enumerated() {
+    let mut engine = Engine::new();
+    engine.add_function("add", |x: f64, y: f64| x + y).unwrap();
+    engine.set("answer", 42.0).unwrap();
+
+    let globals = engine.introspect().globals();
+    assert!(globals.windows(2).all(|pair| pair[0].name <= pair[1].name));
+
+    let add = globals
+        .iter()
+        .find(|global| &*global.name == "add")
+        .unwrap();
+    assert!(add.is_builtin);
+    assert!(matches!(
+        &add.kind,
+        GlobalKind::Function(function) if function.parameter_count == Some(2)
+    ));
+
+    let answer = engine.introspect().global("answer").unwrap();
+    assert!(matches!(
+        answer.kind,
+        GlobalKind::Value { type_name } if &*type_name == "Number"
+    ));
+    assert!(engine.introspect().global("nonexistent").is_none());
+}
+
+#[test]
+fn script_functions_list_their_parameters() {
+    let mut engine = Engine::new();
+    try_run::<()>(&mut engine, "func greet(name, greeting) = nil").unwrap();
+    let greet = engine.introspect().global("greet").unwrap();
+    assert!(!greet.is_builtin);
+    let GlobalKind::Function(function) = greet.kind else {
+        panic!("greet is not a function");
+    };
+    let names: Vec<_> = function.parameter_names.unwrap();
+    assert_eq!(names, ["name".into(), "greeting".into()]);
+}
+
+#[test]
+fn pattern_parameters_are_listed_as_written() {
+    let mut engine = Engine::new();
+    try_run::<()>(&mut engine, "func f((x, _), { a, b: (c, d), .. }) = nil").unwrap();
+    let GlobalKind::Function(function) = engine.introspect().global("f").unwrap().kind else {
+        panic!("f is not a function");
+    };
+    let names: Vec<_> = function.parameter_names.unwrap();
+    assert_eq!(names, ["(x, _)".into(), "{a, b: (c, d), ..}".into()]);
+}
+
+#[test]
+fn methods_of_types_can_be_enumerated() {
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Counter>::new("Counter")
+                .add_static("new", || Counter)
+                .add_function("increment", |_: &mut Counter, _by: f64| ()),
+        )
+        .unwrap();
+
+    let GlobalKind::Type(counter) = engine.introspect().global("Counter").unwrap().kind else {
+        panic!("Counter is not a type");
+    };
+    assert!(counter
+        .type_methods
+        .iter()
+        .any(|method| &*method.name == "new" && method.parameter_count == 0));
+    assert!(counter
+        .instance_methods
+        .iter()
+        .any(|method| &*method.name == "increment" && method.parameter_count == 1));
+
+    let dict = engine.introspect().builtin_type("Dict").unwrap();
+    assert!(dict
+        .instance_methods
+        .iter()
+        .any(|method| &*method.name == "insert"));
+    assert!(engine.introspect().builtin_type("Counter").is_none());
+}
+
+#[test]
+fn functions_types_and_method_signatures_can_be_listed() {
+    let mut engine = Engine::new();
+    engine
+        .add_type(TypeBuilder::<Counter>::new("Counter").add_static("new", || Counter))
+        .unwrap();
+    try_run::<()>(
+        &mut engine,
+        r#"
+                func double(x) = x * 2
+                struct Point impl
+                    func new(x, y) constructor = do @x = x @y = y end
+                    func length_squared() = @x * @x + @y * @y
+                end
+                nil
+            "#,
+    )
+    .unwrap();
+    let introspection = engine.introspect();
+
+    let functions = introspection.functions();
+    assert!(functions
+        .windows(2)
+        .all(|pair| pair[0].name <= pair[1].name));
+    assert!(functions.iter().any(|function| &*function.name == "print"));
+    let double = functions
+        .iter()
+        .find(|function| &*function.name == "double")
+        .unwrap();
+    assert_eq!(double.parameter_count, Some(1));
+
+    let types = introspection.types();
+    let type_names: Vec<_> = types.iter().map(|info| &*info.name).collect();
+    assert!(type_names.contains(&"Counter"));
+    assert!(type_names.contains(&"Point"));
+    assert!(type_names.contains(&"Number"));
+    assert!(!type_names.contains(&"double"));
+
+    let signatures = introspection.method_signatures();
+    assert!(signatures
+        .iter()
+        .any(|method| &*method.name == "length_squared" && method.parameter_count == 0));
+    assert!(signatures
+        .iter()
+        .any(|method| &*method.name == "new" && method.parameter_count == 2));
+    assert!(signatures
+        .windows(2)
+        .all(|pair| pair[0].name <= pair[1].name));
+}
@@ -0,0 +1,59 @@
+use mica::{Engine, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+#[test]
+fn reading_and_writing_files() {
+    let path = std::env::temp_dir().join(format!("mica-io-test-{}.txt", std::process::id()));
+    let mut engine = Engine::new();
+    engine
+        .set("path", path.to_string_lossy().into_owned())
+        .reveal();
+
+    let _: Value = run(
+        &mut engine,
+        r#"
+                File.write(path, "one\ntwo\n")
+                File.append(path, "three")
+                assert(File.read_to_string(path) == "one\ntwo\nthree")
+
+                let lines = []
+                for line in File.lines(path) do
+                    lines.push(line)
+                end
+                assert(lines == ["one", "two", "three"])
+
+                assert(Path.is_file(path))
+                File.remove(path)
+                assert(!Path.exists(path))
+            "#,
+    );
+}
+
+#[test]
+fn io_errors_mention_the_path() {
+    let mut engine = Engine::new();
+    let error = try_run::<Value>(
+        &mut engine,
+        r#"File.read_to_string("/nonexistent/file.txt")"#,
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(error.contains("/nonexistent/file.txt: "), "{error}");
+}
+
+#[test]
+fn path_helpers() {
+    let mut engine = Engine::new();
+    let _: Value = run(
+        &mut engine,
+        r#"
+                let path = Path.join("dir", "file.tar.gz")
+                assert(Path.parent(path) == "dir")
+                assert(Path.file_name(path) == "file.tar.gz")
+                assert(Path.stem(path) == "file.tar")
+                assert(Path.extension(path) == "gz")
+                assert(Path.extension("dir") == nil)
+            "#,
+    );
+}
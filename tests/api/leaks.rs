@@ -0,0 +1,74 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mica::{Engine, Value};
+
+use super::run;
+
+type Leaks = Rc<RefCell<Option<Vec<(String, usize)>>>>;
+
+/// Installs a leak handler that stores `(kind, count)` pairs from the report.
+fn collect_leaks(engine: &mut Engine) -> Leaks {
+    let leaks = Rc::new(RefCell::new(None));
+    let leaks2 = Rc::clone(&leaks);
+    engine.set_leak_handler(move |report| {
+        let leaks = report
+            .leaks
+            .iter()
+            .map(|leak| (leak.kind.clone(), leak.count))
+            .collect();
+        *leaks2.borrow_mut() = Some(leaks);
+    });
+    leaks
+}
+
+#[test]
+fn values_outliving_the_engine_are_reported() {
+    let mut engine = Engine::new();
+    let leaks = collect_leaks(&mut engine);
+    let list: Value = run(&mut engine, "[1, 2, 3]");
+    let string: Value = run(&mut engine, "\"leaked\".cat(\"!\")");
+    drop(engine);
+
+    let mut leaks = leaks
+        .borrow_mut()
+        .take()
+        .expect("leak handler was not called");
+    leaks.sort();
+    assert_eq!(leaks, [("List".to_owned(), 1), ("String".to_owned(), 1)]);
+
+    // Leaked values must remain usable after the engine is gone.
+    assert_eq!(string.to_string(), "leaked!");
+    drop(list);
+}
+
+#[test]
+fn leak_handler_is_not_called_without_leaks() {
+    let mut engine = Engine::new();
+    let leaks = collect_leaks(&mut engine);
+    let _: Value = run(
+        &mut engine,
+        r#"
+                struct Point impl
+                    func new() constructor = nil
+                end
+                [Point.new, ("a", "b"), ["k": "v"]]
+            "#,
+    );
+    drop(engine);
+    assert!(leaks.borrow().is_none());
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn leak_reports_include_backtraces_in_debug_builds() {
+    let mut engine = Engine::new();
+    let backtraces = Rc::new(RefCell::new(0));
+    let backtraces2 = Rc::clone(&backtraces);
+    engine.set_leak_handler(move |report| {
+        *backtraces2.borrow_mut() = report.leaks.iter().map(|l| l.backtraces.len()).sum();
+    });
+    let value: Value = run(&mut engine, "\"a\".cat(\"b\")");
+    drop(engine);
+    assert_eq!(*backtraces.borrow(), 1);
+    drop(value);
+}
@@ -0,0 +1,277 @@
+use mica::{Engine, LanguageErrorKind, Limits, Value};
+
+use super::{run, try_run};
+
+fn assert_exceeds(engine: &mut Engine, source: &str, is_expected: fn(&LanguageErrorKind) -> bool) {
+    let error = try_run::<Value>(engine, source).expect_err("the script should exceed a limit");
+    let kind = error.language_error().map(|error| error.kind());
+    assert!(kind.is_some_and(is_expected), "unexpected error: {error:#}");
+}
+
+fn limited(limits: Limits) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_limits(limits);
+    engine
+}
+
+#[test]
+fn there_are_no_limits_by_default() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.limits(), Limits::UNLIMITED);
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let xs = [1, 2, 3].repeat(1000)
+            assert(xs.len == 3000)
+            assert("ab".repeat(1000).byte_len == 2000)
+        "#,
+    );
+}
+
+#[test]
+fn list_and_dict_lengths_are_limited() {
+    let mut engine = limited(Limits {
+        max_list_len: 4,
+        max_dict_len: 2,
+        ..Limits::UNLIMITED
+    });
+    let _: Value = run(&mut engine, "let xs = [1, 2, 3]\nxs.push(4)");
+    assert_exceeds(&mut engine, "let xs = [1, 2, 3, 4]\nxs.push(5)", |kind| {
+        matches!(
+            kind,
+            LanguageErrorKind::LengthLimitExceeded {
+                type_name: "List",
+                len: 5,
+                max: 4
+            }
+        )
+    });
+    assert_exceeds(&mut engine, "[1, 2, 3, 4, 5]", |kind| {
+        matches!(
+            kind,
+            LanguageErrorKind::LengthLimitExceeded {
+                type_name: "List",
+                ..
+            }
+        )
+    });
+    assert_exceeds(
+        &mut engine,
+        "let d = [1: 1, 2: 2]\nd.insert(3, 3)",
+        |kind| {
+            matches!(
+                kind,
+                LanguageErrorKind::LengthLimitExceeded {
+                    type_name: "Dict",
+                    len: 3,
+                    max: 2
+                }
+            )
+        },
+    );
+}
+
+#[test]
+fn repeating_is_rejected_before_allocating() {
+    let mut engine = limited(Limits {
+        max_list_len: 1000,
+        max_string_len: 1000,
+        ..Limits::UNLIMITED
+    });
+    // These would need far more memory than is available if they were to allocate.
+    assert_exceeds(&mut engine, "[1, 2].repeat(1000000000000000)", |kind| {
+        matches!(
+            kind,
+            LanguageErrorKind::LengthLimitExceeded {
+                type_name: "List",
+                ..
+            }
+        )
+    });
+    assert_exceeds(&mut engine, "[].resize(1000000000000000, nil)", |kind| {
+        matches!(
+            kind,
+            LanguageErrorKind::LengthLimitExceeded {
+                type_name: "List",
+                ..
+            }
+        )
+    });
+    assert_exceeds(&mut engine, "\"abc\".repeat(1000000000000000)", |kind| {
+        matches!(
+            kind,
+            LanguageErrorKind::LengthLimitExceeded {
+                type_name: "String",
+                ..
+            }
+        )
+    });
+    assert_exceeds(&mut engine, "[0] * 1000000000000000", |kind| {
+        matches!(
+            kind,
+            LanguageErrorKind::LengthLimitExceeded {
+                type_name: "List",
+                ..
+            }
+        )
+    });
+}
+
+#[test]
+fn string_lengths_are_limited() {
+    let mut engine = limited(Limits {
+        max_string_len: 8,
+        ..Limits::UNLIMITED
+    });
+    let _: Value = run(&mut engine, "\"abcd\".cat(\"efgh\")");
+    assert_exceeds(&mut engine, "\"abcd\".cat(\"efghi\")", |kind| {
+        matches!(
+            kind,
+            LanguageErrorKind::LengthLimitExceeded {
+                type_name: "String",
+                len: 9,
+                max: 8
+            }
+        )
+    });
+}
+
+#[test]
+fn nesting_depth_is_limited() {
+    let mut engine = limited(Limits {
+        max_nesting_depth: 3,
+        ..Limits::UNLIMITED
+    });
+    let _: Value = run(&mut engine, "[[[1]], ([2], {x: 3})]");
+    assert_exceeds(&mut engine, "[[[[1]]]]", |kind| {
+        matches!(kind, LanguageErrorKind::NestingLimitExceeded { max: 3 })
+    });
+    assert_exceeds(
+        &mut engine,
+        "let xs = [[[1]]]\nlet ys = []\nys.push(xs)",
+        |kind| matches!(kind, LanguageErrorKind::NestingLimitExceeded { max: 3 }),
+    );
+    assert_exceeds(
+        &mut engine,
+        r#"
+                struct Box impl
+                    func new(x) constructor = @x = x
+                end
+                Box.new(Box.new(Box.new([1])))
+            "#,
+        |kind| matches!(kind, LanguageErrorKind::NestingLimitExceeded { max: 3 }),
+    );
+}
+
+#[test]
+fn values_containing_themselves_exceed_the_nesting_limit() {
+    let mut engine = limited(Limits {
+        max_nesting_depth: 100,
+        ..Limits::UNLIMITED
+    });
+    assert_exceeds(&mut engine, "let xs = []\nxs.push(xs)", |kind| {
+        matches!(kind, LanguageErrorKind::NestingLimitExceeded { max: 100 })
+    });
+}
+
+#[test]
+fn shared_values_are_walked_through_once() {
+    let mut engine = limited(Limits {
+        max_nesting_depth: 100,
+        ..Limits::UNLIMITED
+    });
+    // Each level refers to the previous one twice, so walking through every path would take
+    // 2^64 steps.
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let x = [1]
+            let i = 0
+            while i < 64 do
+                x = [x, x]
+                i = i + 1
+            end
+        "#,
+    );
+}
+
+#[test]
+fn growing_past_the_memory_limit_raises_an_error() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.memory_limit(), None);
+    engine.set_memory_limit(Some(256 * 1024));
+    let is_memory = |kind: &LanguageErrorKind| matches!(kind, LanguageErrorKind::MemoryLimitExceeded { limit, .. } if *limit == 256 * 1024);
+    assert_exceeds(
+        &mut engine,
+        "let xs = []\nwhile true do xs.push(1) end",
+        is_memory,
+    );
+    assert_exceeds(&mut engine, r#""abc".repeat(100000)"#, is_memory);
+    assert_exceeds(
+        &mut engine,
+        "let d = [:]\nlet i = 0\nwhile true do d.set(i, i)\ni = i + 1 end",
+        is_memory,
+    );
+}
+
+#[test]
+fn huge_allocations_are_rejected_before_being_made() {
+    let mut engine = Engine::new();
+    engine.set_memory_limit(Some(16 << 20));
+    let is_memory =
+        |kind: &LanguageErrorKind| matches!(kind, LanguageErrorKind::MemoryLimitExceeded { .. });
+    assert_exceeds(&mut engine, r#""a" * 1e12"#, is_memory);
+    assert_exceeds(&mut engine, "[0] * 1e12", is_memory);
+    assert_exceeds(&mut engine, "[].resize(1e12, 0)", is_memory);
+    assert_exceeds(&mut engine, r#""x".pad_start(1e11, "y")"#, is_memory);
+    assert_exceeds(&mut engine, r#""x".pad_end(1e11, "y")"#, is_memory);
+}
+
+#[test]
+fn objects_grown_before_setting_the_memory_limit_count_towards_it() {
+    let mut engine = Engine::new();
+    let xs: Value = run(
+        &mut engine,
+        "let xs = []\nlet i = 0\nwhile i < 100000 do xs.push(i)\ni = i + 1 end\nxs",
+    );
+    engine.set("xs", xs).unwrap();
+    engine.set_memory_limit(Some(256 * 1024));
+    assert_exceeds(&mut engine, "[xs]", |kind| {
+        matches!(kind, LanguageErrorKind::MemoryLimitExceeded { .. })
+    });
+}
+
+#[test]
+fn garbage_does_not_count_towards_the_memory_limit() {
+    let mut engine = Engine::new();
+    engine.set_memory_limit(Some(256 * 1024));
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let i = 0
+            while i < 1000 do
+                let garbage = "abc".repeat(1000)
+                i = i + 1
+            end
+        "#,
+    );
+}
+
+#[test]
+fn exceeding_the_memory_limit_can_be_recovered_from() {
+    let mut engine = Engine::new();
+    engine.set_memory_limit(Some(256 * 1024));
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let xs = []
+            let error = try do while true do xs.push(1) end end catch e, _
+                # Nothing else can be allocated until the list is freed.
+                xs = nil
+                e
+            end
+            assert(error.contains("exceeds the memory limit"))
+            assert("abc".repeat(1000).byte_len == 3000)
+        "#,
+    );
+}
@@ -0,0 +1,146 @@
+use mica::Engine;
+
+fn compile_error(source: &str) -> String {
+    let mut engine = Engine::new();
+    match engine.compile("test.mi", source) {
+        Ok(_) => panic!("compiling {source:?} should fail"),
+        Err(error) => error.to_string(),
+    }
+}
+
+#[test]
+fn comments_may_end_the_input() {
+    let mut engine = Engine::new();
+    assert!(engine.compile("test.mi", "1 # comment").is_ok());
+    assert!(engine.compile("test.mi", "#").is_ok());
+}
+
+#[test]
+fn deeply_nested_code_is_rejected_without_overflowing_the_stack() {
+    let n = 100_000;
+    let sources = [
+        format!("{}1{}", "(".repeat(n), ")".repeat(n)),
+        format!("{}1", "-".repeat(n)),
+        format!("{}{}", "[".repeat(n), "]".repeat(n)),
+        format!("{}{}", "do ".repeat(n), "end ".repeat(n)),
+        format!("let x = 0\n{}1", "x = ".repeat(n)),
+        // Left-associative operators are parsed in a loop, but still produce deep syntax trees.
+        format!("1{}", " + 1".repeat(n)),
+        format!("1{}", ".a".repeat(n)),
+        format!("print{}", "()".repeat(n)),
+    ];
+    for source in sources {
+        let error = compile_error(&source);
+        assert!(
+            error.ends_with("error: expression is nested too deeply"),
+            "unexpected error: {error}"
+        );
+    }
+}
+
+#[test]
+fn reasonably_nested_code_compiles() {
+    let mut engine = Engine::new();
+    let n = 100;
+    let source = format!("{}1{}", "(".repeat(n), ")".repeat(n));
+    assert!(engine.compile("test.mi", source).is_ok());
+    let source = format!("1{}", " + 1".repeat(200));
+    assert!(engine.compile("test.mi", source).is_ok());
+}
+
+#[test]
+fn methods_without_a_body_are_rejected() {
+    // A misspelled function kind ends the function before its body.
+    let error = compile_error("struct P impl\n    func new(x) onstructor = do\n    end\nend");
+    assert!(
+        error.contains("error: missing function body"),
+        "unexpected error: {error}"
+    );
+    let error = compile_error("struct P impl\n    func get()\nend");
+    assert!(
+        error.contains("error: missing function body"),
+        "unexpected error: {error}"
+    );
+}
+
+/// Compiles a bunch of pseudo-randomly generated token soup, making sure none of it panics.
+/// The fuzzing harness in the `fuzz` directory does this much more thoroughly.
+#[test]
+fn random_input_does_not_panic() {
+    const TOKENS: &[&str] = &[
+        "let",
+        "x",
+        "=",
+        "1",
+        "2.5",
+        "1e",
+        "1_",
+        "\"s\"",
+        "\"\\u{",
+        "\\\\ s",
+        "\n",
+        "(",
+        ")",
+        "[",
+        "]",
+        "{",
+        "}",
+        ",",
+        ":",
+        ".",
+        "..",
+        "@",
+        "+",
+        "-",
+        "*",
+        "/",
+        "==",
+        "<",
+        "!",
+        "and",
+        "or",
+        "do",
+        "end",
+        "if",
+        "elif",
+        "else",
+        "while",
+        "for",
+        "in",
+        "break",
+        "return",
+        "defer",
+        "func",
+        "static",
+        "constructor",
+        "struct",
+        "enum",
+        "impl",
+        "trait",
+        "as",
+        "implements",
+        "pub",
+        "priv",
+        "import",
+        "_",
+        "nil",
+        "true",
+        "S",
+        "#",
+        "ü",
+    ];
+    // xorshift64, so that failures are reproducible.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+    let mut engine = Engine::new();
+    for _ in 0..2000 {
+        let length = next() % 32;
+        let source: Vec<_> = (0..length).map(|_| TOKENS[next() % TOKENS.len()]).collect();
+        let _ = engine.compile("test.mi", source.join(" "));
+    }
+}
@@ -1,9 +1,61 @@
 use std::fmt::Display;
 
+use mica::{Engine, Error, TryFromValue};
+
+mod allocations;
+mod arena;
+mod arithmetic;
+mod context;
+mod cst;
+mod debugger;
+#[cfg(feature = "derive")]
+mod derive;
+mod disassembly;
+mod errors;
+mod extensions;
+mod fibers;
+mod frames;
+mod front_matter;
+mod fuel;
 mod functions;
+mod image;
+mod incremental;
+mod interceptors;
+mod interning;
+mod introspection;
+#[cfg(feature = "io")]
+mod io;
+mod leaks;
+mod limits;
+mod malformed;
+mod modules;
+mod option;
+#[cfg(feature = "os")]
+mod os;
+mod parallel;
+mod persistent;
+mod program;
+mod query;
+#[cfg(feature = "regex")]
+mod regex;
+mod reload;
+mod scheduler;
+mod sealed;
+#[cfg(feature = "serde")]
+mod serde;
+mod session;
+mod snippets;
 mod stress;
+mod testing;
+mod tokens;
+#[cfg(feature = "tooling")]
+mod tooling;
+mod trace;
 mod traits;
+mod types;
+mod user_data;
 mod value;
+mod warnings;
 
 pub trait RevealResultExt<T> {
     /// Basically the same as `unwrap()` but `Display`s the error instead of `Debug`ging it.
@@ -18,8 +70,19 @@ where
         match self {
             Ok(ok) => ok,
             Err(error) => {
-                panic!("Err result revealed:\n\n{error}\n\n");
+                panic!("Err result revealed:\n\n{error:#}\n\n");
             }
         }
     }
 }
+
+/// Runs a script to completion and returns its result, panicking if it fails to compile or run.
+pub fn run<T: TryFromValue>(engine: &mut Engine, source: &str) -> T {
+    try_run(engine, source).reveal()
+}
+
+/// Runs a script to completion and returns its result or the error it failed with. Compilation
+/// errors still panic.
+pub fn try_run<T: TryFromValue>(engine: &mut Engine, source: &str) -> Result<T, Error> {
+    engine.start("test.mi", source).reveal().trampoline()
+}
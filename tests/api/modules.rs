@@ -0,0 +1,153 @@
+use std::{cell::Cell, rc::Rc};
+
+use mica::{Engine, Error, ModuleBuilder, TypeBuilder, UserData, Value};
+
+use super::RevealResultExt;
+
+struct Counter;
+
+impl UserData for Counter {}
+
+fn counter_module(created: &Rc<Cell<usize>>) -> impl FnOnce() -> TypeBuilder<Counter> {
+    let created = Rc::clone(created);
+    move || {
+        created.set(created.get() + 1);
+        TypeBuilder::<Counter>::new("Counter")
+            .add_static("double", |x: f64| x * 2.0)
+            .add_static("name", || "counter")
+    }
+}
+
+#[test]
+fn lazy_modules_are_created_on_first_import() {
+    let created = Rc::new(Cell::new(0));
+    let mut engine = Engine::new();
+    engine.add_lazy_module("counter", counter_module(&created));
+    engine.add_lazy_module("unused", || -> Value {
+        panic!("unused module was created")
+    });
+    assert_eq!(created.get(), 0);
+
+    let _: Value = engine.start("first.mi", "1").reveal().trampoline().reveal();
+    assert_eq!(created.get(), 0);
+
+    let doubled: f64 = engine
+        .start("second.mi", "import counter\ncounter.double(21)")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(doubled, 42.0);
+    assert_eq!(created.get(), 1);
+
+    let name: String = engine
+        .start(
+            "third.mi",
+            "func f() = do\n    import counter\n    counter.name\nend\nf()",
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(name, "counter");
+    assert_eq!(created.get(), 1);
+}
+
+#[test]
+fn imports_declare_variables_in_the_current_scope() {
+    let mut engine = Engine::new();
+    engine.add_lazy_module("counter", counter_module(&Rc::new(Cell::new(0))));
+    let error = engine
+        .compile("scope.mi", "do\n    import counter\nend\ncounter")
+        .expect_err("the variable should not be visible outside the block");
+    assert!(error
+        .to_string()
+        .contains("variable 'counter' does not exist"));
+    let _: Value = engine
+        .start("global.mi", "import counter")
+        .reveal()
+        .trampoline()
+        .reveal();
+    let doubled: f64 = engine
+        .start("other.mi", "counter.double(2)")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(doubled, 4.0);
+}
+
+#[test]
+fn modules_can_be_plain_values() {
+    let mut engine = Engine::new();
+    engine.add_lazy_module("answer", || Value::new(42.0));
+    let answer: f64 = engine
+        .start("answer.mi", "import answer\nanswer")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(answer, 42.0);
+}
+
+#[test]
+fn errors_while_creating_a_module_fail_compilation() {
+    let mut engine = Engine::new();
+    engine.add_lazy_module("user", || {
+        Err::<Value, _>(Error::User("could not connect".into()))
+    });
+    let error = engine
+        .compile("user.mi", "import user")
+        .expect_err("creating the module should fail");
+    assert_eq!(error.to_string(), "could not connect");
+}
+
+#[test]
+fn modules_that_are_not_registered_are_reported() {
+    let mut engine = Engine::new();
+    let error = engine
+        .compile("missing.mi", "import nothing")
+        .expect_err("the module should not exist");
+    assert_eq!(
+        error.to_string(),
+        "missing.mi:1:1: error: module 'nothing' does not exist"
+    );
+}
+
+#[test]
+fn module_builders_expose_functions_and_constants_under_one_global() {
+    let mut engine = Engine::new();
+    engine
+        .add_module(
+            "audio",
+            ModuleBuilder::new("audio")
+                .add_function("gain", |sample: f64, gain: f64| sample * gain)
+                .add_constant("channels", 2)
+                .add_constant("name", "mixer")
+                .add_module(
+                    "filters",
+                    ModuleBuilder::new("filters").add_constant("q", 0.5),
+                ),
+        )
+        .reveal();
+    let result: f64 = engine
+        .start(
+            "audio.mi",
+            "assert(audio.name == \"mixer\")\naudio.gain(audio.channels, audio.filters.q)",
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(result, 1.0);
+    assert!(engine.compile("flat.mi", "gain(1, 2)").is_err());
+}
+
+#[test]
+fn module_builders_can_be_imported_lazily() {
+    let mut engine = Engine::new();
+    engine.add_lazy_module("math2", || {
+        ModuleBuilder::new("math2").add_function("square", |x: f64| x * x)
+    });
+    let result: f64 = engine
+        .start("square.mi", "import math2\nmath2.square(7)")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(result, 49.0);
+}
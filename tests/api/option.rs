@@ -0,0 +1,83 @@
+use mica::{Engine, OptionValue, ResultValue, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .add_function("find", |x: Value| OptionValue((!x.is_falsy()).then_some(x)))
+        .reveal();
+    engine
+        .add_function("parse", |s: String| {
+            ResultValue(s.parse::<f64>().map_err(|error| error.to_string()))
+        })
+        .reveal();
+    engine
+}
+
+#[test]
+fn option_values_convert_to_option_variants() {
+    run::<Value>(
+        &mut engine(),
+        r#"
+            assert(find(1) == Some(1))
+            assert(find(false) == None)
+            let Option.Some(x) = find("x")
+            assert(x == "x")
+        "#,
+    );
+}
+
+#[test]
+fn result_values_convert_to_result_variants_instead_of_raising() {
+    run::<Value>(
+        &mut engine(),
+        r#"
+            assert(parse("1.5") == Ok(1.5))
+            assert(parse("x") == Err("invalid float literal"))
+            assert(parse("x").unwrap_or(2) == 2)
+        "#,
+    );
+}
+
+#[test]
+fn plain_options_still_convert_to_nil() {
+    let mut engine = engine();
+    engine
+        .add_function("plain", |x: f64| (x > 0.0).then_some(x))
+        .reveal();
+    run::<Value>(&mut engine, "assert(plain(1) == 1 and plain(-1) == nil)");
+}
+
+#[test]
+fn variants_convert_back_into_options_and_results() {
+    let mut engine = engine();
+    let value: (OptionValue<f64>, OptionValue<f64>) = run(&mut engine, "(Some(1), None)");
+    assert_eq!(value, (OptionValue(Some(1.0)), OptionValue(None)));
+
+    let value: Vec<Result<f64, String>> = run(&mut engine, r#"[Ok(1), Err("no")]"#);
+    assert_eq!(value, [Ok(1.0), Err("no".to_owned())]);
+
+    let value: ResultValue<f64, String> = run(&mut engine, r#"Err("no")"#);
+    assert_eq!(value, ResultValue(Err("no".to_owned())));
+
+    let wrong: Result<Result<f64, String>, _> = try_run(&mut engine, "Some(1)");
+    assert!(wrong.is_err());
+}
+
+#[test]
+fn conversions_use_the_types_restored_from_an_image() {
+    let saved = engine();
+    let image = saved.snapshot().reveal();
+
+    let mut restored = engine();
+    restored.restore(&image).reveal();
+    run::<Value>(
+        &mut restored,
+        r#"
+            assert(type_of(find(1)) == Option)
+            assert(if let Option.Some(_) = find(1) do true else false end)
+            assert(type_of(parse("x")) == Result)
+        "#,
+    );
+}
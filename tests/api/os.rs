@@ -0,0 +1,84 @@
+use mica::{Engine, Error, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+#[test]
+fn process_args_are_set_by_the_host() {
+    let mut engine = Engine::new();
+    let args: Vec<String> = run(&mut engine, "Process.args");
+    assert!(args.is_empty());
+
+    engine.set_process_args(["one", "--two"]);
+    let args: Vec<String> = run(&mut engine, "Process.args");
+    assert_eq!(args, ["one", "--two"]);
+}
+
+#[test]
+fn environment_variables_can_be_read() {
+    let path = std::env::var("PATH").expect("tests must run with PATH set");
+    let mut engine = Engine::new();
+    engine.set("expected", path).reveal();
+
+    let _: Value = run(
+        &mut engine,
+        r#"
+                assert(Env.get("PATH") == expected)
+                assert(Env.get("MICA_TEST_VARIABLE_THAT_IS_NOT_SET") == nil)
+
+                let found = false
+                for pair in Env.vars do
+                    let (name, value) = pair
+                    if name == "PATH" do
+                        assert(value == expected)
+                        found = true
+                    end
+                end
+                assert(found)
+            "#,
+    );
+}
+
+#[test]
+fn exiting_stops_the_script_without_being_caught() {
+    let mut engine = Engine::new();
+    let error = try_run::<Value>(
+        &mut engine,
+        r#"
+                let reached = false
+                try Process.exit(3) catch _ nil end
+                reached = true
+            "#,
+    )
+    .unwrap_err();
+    let Error::Runtime(error) = error else {
+        panic!("runtime error expected, got {error:?}");
+    };
+    assert_eq!(error.exit_code(), Some(3));
+    let reached: bool = engine.get("reached").reveal();
+    assert!(!reached);
+}
+
+#[test]
+fn exit_codes_must_be_integers_from_0_to_255() {
+    let mut engine = Engine::new();
+    for code in ["0", "255"] {
+        let error = try_run::<Value>(&mut engine, &format!("Process.exit({code})")).unwrap_err();
+        let Error::Runtime(error) = error else {
+            panic!("runtime error expected, got {error:?}");
+        };
+        assert_eq!(
+            error.exit_code().map(u32::from),
+            Some(code.parse().unwrap())
+        );
+    }
+    for code in ["1.5", "0 / 0", "256", "300", "-1", "Number.infinity"] {
+        let message: String = run(
+            &mut engine,
+            &format!("try Process.exit({code}) catch e, _ e end"),
+        );
+        assert!(
+            message.starts_with("invalid exit code"),
+            "{code}: {message}"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::num::NonZeroUsize;
+
+use mica::{Engine, LanguageErrorKind, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+fn engine(threads: usize) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_compilation_threads(NonZeroUsize::new(threads).unwrap());
+    engine
+}
+
+fn compile_error(threads: usize, source: &str) -> mica::LanguageError {
+    match engine(threads).compile("test.mi", source) {
+        Ok(_) => panic!("compilation should fail"),
+        Err(mica::Error::Compile(error)) => error,
+        Err(error) => panic!("expected a single compile error, got {error:#}"),
+    }
+}
+
+#[test]
+fn parallel_compilation_gives_the_same_results() {
+    let mut source = String::new();
+    for i in 0..200 {
+        source += &format!(
+            r#"
+                func f{i}(x, thing) = do
+                    let offset = {i}
+                    let add = func (y) = y + offset
+                    let pair = (x, "f{i}")
+                    let record = {{ value: add(x), field{m}: pair }}
+                    let total = 0
+                    for item in [1, 2, 3].iter do
+                        total = total + item
+                    end
+                    record.value + total + thing.method{m}() + pair._1.byte_len
+                end
+            "#,
+            m = i % 7,
+        );
+    }
+    source += "struct Thing impl\nfunc new() constructor = nil\n";
+    for m in 0..7 {
+        source += &format!("func method{m}() = {m}\n");
+    }
+    source += "end\nlet thing = Thing.new()\nlet sum = 0\n";
+    for i in 0..200 {
+        source += &format!("sum = sum + f{i}({i}, thing)\n");
+    }
+    source += "sum";
+
+    let sequential: f64 = run(&mut engine(1), &source);
+    let parallel: f64 = run(&mut engine(4), &source);
+    assert_eq!(parallel, sequential);
+}
+
+#[test]
+fn functions_cannot_see_globals_declared_after_them() {
+    let source = "func f() = later\nlet later = 1";
+    for threads in [1, 4] {
+        let error = compile_error(threads, source);
+        assert!(matches!(
+            error.kind(),
+            LanguageErrorKind::VariableDoesNotExist { .. }
+        ));
+    }
+}
+
+#[test]
+fn functions_cannot_assign_to_constants() {
+    let source = "const limit = 1\nfunc f() = do limit = 2 end";
+    let error = compile_error(4, source);
+    assert!(matches!(
+        error.kind(),
+        LanguageErrorKind::CannotAssignConst(_)
+    ));
+}
+
+#[test]
+fn the_first_error_in_the_source_is_reported() {
+    let source = "func a() = 1\nfunc b() = missing1\nfunc c() = missing2\nmissing3";
+    for threads in [1, 4] {
+        let error = compile_error(threads, source);
+        assert_eq!(error.location().unwrap().1.line, 2);
+    }
+}
+
+#[test]
+fn functions_declaring_types_are_compiled_in_place() {
+    let mut engine = engine(4);
+    let x: f64 = run(
+        &mut engine,
+        r#"
+            func make() = do
+                struct Point impl
+                    func new(x) constructor = do @x = x end
+                    func x() = @x
+                end
+                Point.new(3)
+            end
+            func twice(p) = p.x * 2
+            twice(make())
+        "#,
+    );
+    assert_eq!(x, 6.0);
+}
+
+#[test]
+fn warnings_come_out_in_source_order() {
+    let source = r#"
+        let _ = do
+            let unused_a = 1
+        end
+        func f() = do
+            let unused_b = 1
+        end
+        let _ = do
+            let unused_c = 1
+        end
+        func g() = do
+            let unused_d = 1
+        end
+    "#;
+    let warnings = |threads| -> Vec<String> {
+        let mut engine = engine(threads);
+        let script = engine.compile("test.mi", source).reveal();
+        script
+            .warnings()
+            .iter()
+            .map(|warning| warning.to_string())
+            .collect()
+    };
+    let sequential = warnings(1);
+    assert_eq!(sequential.len(), 4);
+    assert_eq!(warnings(4), sequential);
+}
+
+#[test]
+fn runtime_errors_in_functions_compiled_in_parallel_have_snippets() {
+    let mut engine = engine(4);
+    let error = try_run::<Value>(&mut engine, "func a() = 1\nfunc b() = nil + 1\nb()")
+        .expect_err("execution should fail");
+    let mica::Error::Runtime(error) = &error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    assert_eq!(error.snippet().unwrap().text, "func b() = nil + 1");
+}
@@ -0,0 +1,139 @@
+use std::{collections::HashMap, thread};
+
+use mica::{Engine, PersistentDict, PersistentList, SharedValue, Value};
+
+use super::{run, RevealResultExt};
+
+#[test]
+fn list_updates_share_structure_with_the_original() {
+    let list: PersistentList = (0..5000).map(|i| SharedValue::from(i as f64)).collect();
+    let updated = list.set(4321, "changed").unwrap().push(true);
+    assert_eq!(list.len(), 5000);
+    assert_eq!(updated.len(), 5001);
+    assert_eq!(list.get(4321), Some(&SharedValue::from(4321.0)));
+    assert_eq!(updated.get(4321), Some(&SharedValue::from("changed")));
+    assert_eq!(updated.last(), Some(&SharedValue::from(true)));
+    assert!(list.set(5000, 1.0).is_none());
+
+    let mut popped = updated;
+    for expected_len in (0..5001).rev() {
+        popped = popped.pop();
+        assert_eq!(popped.len(), expected_len);
+        assert_eq!(popped.iter().count(), expected_len);
+    }
+    assert_eq!(popped, PersistentList::new());
+    assert!(list
+        .iter()
+        .eq((0..5000).map(|i| SharedValue::from(i as f64))));
+}
+
+#[test]
+fn dict_behaves_like_a_hash_map() {
+    let mut dict = PersistentDict::new();
+    let mut expected = HashMap::new();
+    for i in 0..3000 {
+        let key = format!("key{}", i % 2000);
+        dict = dict.insert(key.as_str(), i as f64);
+        expected.insert(key, i as f64);
+    }
+    for i in (0..2000).step_by(3) {
+        let key = format!("key{i}");
+        dict = dict.remove(&SharedValue::from(key.as_str()));
+        expected.remove(&key);
+    }
+
+    assert_eq!(dict.len(), expected.len());
+    assert_eq!(dict.iter().count(), expected.len());
+    for (key, &value) in &expected {
+        assert_eq!(
+            dict.get(&SharedValue::from(key.as_str())),
+            Some(&SharedValue::from(value))
+        );
+    }
+    assert!(!dict.contains_key(&SharedValue::from("key0")));
+
+    let rebuilt: PersistentDict = dict.iter().collect();
+    assert_eq!(rebuilt, dict);
+}
+
+#[test]
+fn dict_keys_with_equal_hashes_are_kept_apart() {
+    // Dicts are hashed by their length only, so these keys all collide.
+    let key = |i: usize| SharedValue::from(PersistentDict::new().insert("id", i as f64));
+    let mut dict = PersistentDict::new().insert("other", 0.0);
+    for i in 0..10 {
+        dict = dict.insert(key(i), i as f64);
+    }
+    assert_eq!(dict.len(), 11);
+    for i in 0..10 {
+        assert_eq!(dict.get(&key(i)), Some(&SharedValue::from(i as f64)));
+    }
+
+    for i in 0..9 {
+        dict = dict.remove(&key(i));
+        assert!(!dict.contains_key(&key(i)));
+    }
+    assert_eq!(dict.len(), 2);
+    assert_eq!(dict.get(&key(9)), Some(&SharedValue::from(9.0)));
+    assert_eq!(
+        dict.get(&SharedValue::from("other")),
+        Some(&SharedValue::from(0.0))
+    );
+}
+
+#[test]
+fn collections_can_be_used_by_scripts() {
+    let dataset: PersistentList = (1..=100).map(|i| SharedValue::from(i as f64)).collect();
+    let mut engine = Engine::new();
+    engine.set("dataset", dataset.clone()).reveal();
+    let sum: f64 = run(
+        &mut engine,
+        r#"
+                let sum = 0
+                for x in dataset.iter do
+                    sum = sum + x
+                end
+                sum
+            "#,
+    );
+    assert_eq!(sum, 5050.0);
+
+    let extended: PersistentList = run(&mut engine, "dataset.push([1, 2])");
+    assert_eq!(extended.len(), 101);
+    assert_eq!(
+        extended.last(),
+        Some(&SharedValue::List(
+            [1.0, 2.0].into_iter().map(SharedValue::from).collect()
+        ))
+    );
+    assert_eq!(dataset.len(), 100);
+}
+
+#[test]
+fn collections_can_be_shared_between_engines_on_different_threads() {
+    let config = PersistentDict::new()
+        .insert("name", "shared")
+        .insert("limits", PersistentList::new().push(1.0).push(2.0));
+
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let config = config.clone();
+            thread::spawn(move || {
+                let mut engine = Engine::new();
+                engine.set("config", config).reveal();
+                engine.set("i", i as f64).reveal();
+                let result: Value = run(
+                    &mut engine,
+                    r#" config.get("name").cat(string(config.get("limits").get(1) + i)) "#,
+                );
+                result.to_string()
+            })
+        })
+        .collect();
+
+    let results: Vec<_> = threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect();
+    assert_eq!(results, ["shared2", "shared3", "shared4", "shared5"]);
+}
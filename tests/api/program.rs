@@ -0,0 +1,91 @@
+use std::thread;
+
+use mica::{Engine, Error, HeapImage, Program, SharedValue};
+
+use super::RevealResultExt;
+
+fn engine_with_double() -> Result<Engine, Error> {
+    let mut engine = Engine::new();
+    engine.add_function("double", |x: f64| x * 2.0)?;
+    Ok(engine)
+}
+
+#[test]
+fn programs_can_be_sent_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Program>();
+    assert_send_sync::<HeapImage>();
+    assert_send_sync::<SharedValue>();
+}
+
+#[test]
+fn instances_start_from_the_state_after_running_the_scripts() {
+    let program = Program::new(
+        engine_with_double,
+        [
+            (
+                "counter.mi",
+                r#"
+                    struct Counter impl
+                        func new(start) constructor = @count = start
+                        func increment() = do @count = @count + 1 end
+                    end
+                    let runs = 0
+                "#,
+            ),
+            (
+                "setup.mi",
+                "runs = runs + 1\nlet counter = Counter.new(double(5))",
+            ),
+        ],
+    )
+    .reveal();
+
+    let results: Vec<(f64, f64)> = thread::scope(|scope| {
+        let workers: Vec<_> = (1..=4)
+            .map(|n| {
+                let program = &program;
+                scope.spawn(move || {
+                    let mut engine = program.instantiate().reveal();
+                    let mut count = 0.0;
+                    for _ in 0..n {
+                        count = engine
+                            .start("worker.mi", "counter.increment()")
+                            .reveal()
+                            .trampoline()
+                            .reveal();
+                    }
+                    (engine.get("runs").reveal(), count)
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    // The scripts aren't run again, and each engine has a counter of its own.
+    assert_eq!(
+        results,
+        [(1.0, 11.0), (1.0, 12.0), (1.0, 13.0), (1.0, 14.0)]
+    );
+}
+
+#[test]
+fn instances_can_call_functions_added_by_the_engine_constructor() {
+    let program =
+        Program::new(engine_with_double, [("f.mi", "func f(x) = double(x) + 1")]).reveal();
+    let mut engine = program.instantiate().reveal();
+    let result: f64 = engine
+        .start("call.mi", "f(20)")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(result, 41.0);
+}
+
+#[test]
+fn errors_in_scripts_are_reported_when_building_the_program() {
+    let result = Program::new(|| Ok(Engine::new()), [("broken.mi", "error(\"broken\")")]);
+    assert!(result.unwrap_err().to_string().contains("broken"));
+}
@@ -0,0 +1,207 @@
+use std::rc::Rc;
+
+use mica::ll::{
+    ast::query::{FunctionItemKind, Query},
+    lexer::Lexer,
+    parser::Parser,
+};
+
+fn query(source: &str) -> Query {
+    let lexer = Lexer::new(Rc::from("query.mi"), source.to_owned());
+    let (ast, root_node) = Parser::new(lexer).parse().unwrap();
+    Query::new(&ast, root_node)
+}
+
+fn names<T>(items: &[T], name: impl Fn(&T) -> &str) -> Vec<&str> {
+    items.iter().map(name).collect()
+}
+
+#[test]
+fn locals_are_not_reported_as_globals() {
+    let query = query(
+        r#"
+            let total = 0
+            func add(x) = do
+                let doubled = x * 2
+                total = total + doubled
+                for item in [x, doubled] do
+                    print(item)
+                end
+                unknown = 1
+            end
+            let { a, b: c } = { a: 1, b: 2 }
+            let shadowed = do
+                let total = 1
+                total
+            end
+        "#,
+    );
+    assert_eq!(names(query.globals_read(), |r| &r.name), ["total", "print"]);
+    assert_eq!(
+        names(query.globals_written(), |r| &r.name),
+        ["total", "add", "total", "unknown", "a", "c", "shadowed"]
+    );
+}
+
+#[test]
+fn pattern_parameters_declare_locals() {
+    let query = query("func dist((x1, y1), { x: x2, y: y2 }) = x2 - x1 + y2 - y1 + z");
+    assert_eq!(names(query.globals_read(), |r| &r.name), ["z"]);
+    assert_eq!(
+        query.functions()[0].parameters,
+        [Rc::from("(x1, y1)"), Rc::from("{x: x2, y: y2}")]
+    );
+}
+
+#[test]
+fn struct_patterns_read_their_type() {
+    let query = query("let Point { x, y: (a, b) } = p\nfunc f(Point { x }) = x + a");
+    assert_eq!(
+        names(query.globals_read(), |r| &r.name),
+        ["p", "Point", "Point", "a"]
+    );
+    assert_eq!(
+        names(query.globals_written(), |r| &r.name),
+        ["x", "a", "b", "f"]
+    );
+    assert_eq!(query.functions()[0].parameters, [Rc::from("Point {x}")]);
+}
+
+#[test]
+fn assigned_patterns_write_their_variables() {
+    let query = query("(a, { b, c: d }) = pair\nPoint { x } = p");
+    assert_eq!(
+        names(query.globals_read(), |r| &r.name),
+        ["pair", "p", "Point"]
+    );
+    assert_eq!(
+        names(query.globals_written(), |r| &r.name),
+        ["a", "b", "d", "x"]
+    );
+}
+
+#[test]
+fn functions_and_methods_are_found() {
+    let query = query(
+        r#"
+            trait Shape
+                func area()
+            end
+            struct Square impl
+                func new(side) constructor = do
+                    @side = side
+                end
+                func unit() static = Square.new(1)
+                as Shape
+                    func area() = @side * @side
+                end
+            end
+            let double = func (x) = x * 2
+        "#,
+    );
+    let functions: Vec<_> = query
+        .functions()
+        .iter()
+        .map(|function| {
+            (
+                function.name.as_deref(),
+                function.kind,
+                function.owner.as_deref(),
+                function.parameters.len(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        functions,
+        [
+            (
+                Some("area"),
+                FunctionItemKind::TraitMethod,
+                Some("Shape"),
+                0
+            ),
+            (
+                Some("new"),
+                FunctionItemKind::Constructor,
+                Some("Square"),
+                1
+            ),
+            (
+                Some("unit"),
+                FunctionItemKind::StaticMethod,
+                Some("Square"),
+                0
+            ),
+            (Some("area"), FunctionItemKind::Method, Some("Square"), 0),
+            (None, FunctionItemKind::Function, None, 1),
+        ]
+    );
+    // `self` and fields don't refer to globals.
+    assert_eq!(
+        names(query.globals_read(), |r| &r.name),
+        ["Square", "Shape"]
+    );
+}
+
+#[test]
+fn method_calls_are_found() {
+    let query = query(
+        r#"
+            let list = [1, 2, 3]
+            list.push(4)
+            print(list.len, "a".cat("b").cat("c"))
+        "#,
+    );
+    let calls: Vec<_> = query
+        .method_calls()
+        .iter()
+        .map(|call| (&*call.name, call.argument_count, call.location.line))
+        .collect();
+    assert_eq!(
+        calls,
+        [("push", 1, 3), ("len", 0, 4), ("cat", 1, 4), ("cat", 1, 4)]
+    );
+}
+
+#[test]
+fn cascaded_calls_are_found() {
+    let query = query("let list = []..push(1)..push(x)..len");
+    let calls: Vec<_> = query
+        .method_calls()
+        .iter()
+        .map(|call| (&*call.name, call.argument_count))
+        .collect();
+    assert_eq!(calls, [("push", 1), ("push", 1), ("len", 0)]);
+    assert_eq!(names(query.globals_read(), |global| &global.name), ["x"]);
+}
+
+#[test]
+fn dynamic_method_names_are_queried_for_variables() {
+    let query = query("let name = \"len\"\nlist.(name)\nlist.(prefix.cat(\"push\"))(1)");
+    let calls: Vec<_> = query
+        .method_calls()
+        .iter()
+        .map(|call| (&*call.name, call.argument_count))
+        .collect();
+    assert_eq!(calls, [("cat", 1)]);
+    assert_eq!(
+        names(query.globals_read(), |global| &global.name),
+        ["list", "name", "list", "prefix"]
+    );
+}
+
+#[test]
+fn imports_are_queried_and_declare_variables() {
+    let query = query(
+        "import http\nfunc get(url) = do\n    import json\n    json.parse(http.get(url))\nend",
+    );
+    assert_eq!(
+        names(query.imports(), |import| &import.name),
+        ["http", "json"]
+    );
+    assert_eq!(
+        names(query.globals_written(), |global| &global.name),
+        ["http", "get"]
+    );
+    assert_eq!(names(query.globals_read(), |global| &global.name), ["http"]);
+}
@@ -0,0 +1,65 @@
+use mica::{Engine, Value};
+
+use super::{run, try_run};
+
+#[test]
+fn matching_and_finding() {
+    let _: Value = run(
+        &mut Engine::new(),
+        r#"
+        let re = Regex.new("([a-z]+)-(\\d+)?")
+        assert(re.is_match("abc-12"))
+        assert(!re.is_match("123"))
+        assert(re.find("xx abc-12 yy") == "abc-12")
+        assert(re.find("123") == nil)
+        assert(re.pattern == "([a-z]+)-(\\d+)?")
+    "#,
+    );
+}
+
+#[test]
+fn captures_include_unmatched_groups_as_nil() {
+    let _: Value = run(
+        &mut Engine::new(),
+        r#"
+        let re = Regex.new("([a-z]+)-(\\d+)?")
+        assert(re.captures("xx abc-12 yy") == ["abc-12", "abc", "12"])
+        assert(re.captures("xx abc- yy") == ["abc-", "abc", nil])
+        assert(re.captures("123") == nil)
+    "#,
+    );
+}
+
+#[test]
+fn replacing_matches() {
+    let _: Value = run(
+        &mut Engine::new(),
+        r#"
+        let re = Regex.new("([a-z])-(\\d)")
+        assert(re.replace("a-1 b-2", "$2$1") == "1a b-2")
+        assert(re.replace_all("a-1 b-2", "$2$1") == "1a 2b")
+    "#,
+    );
+}
+
+#[test]
+fn iterating_over_matches() {
+    let _: Value = run(
+        &mut Engine::new(),
+        r#"
+        let found = []
+        for m in Regex.new("\\d*").matches("a1b22c") do
+            found.push(m)
+        end
+        assert(found == ["", "1", "22", ""])
+    "#,
+    );
+}
+
+#[test]
+fn invalid_patterns_are_errors() {
+    let mut engine = Engine::new();
+    let result = try_run::<Value>(&mut engine, r#"Regex.new("(")"#);
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("unclosed group"), "{error}");
+}
@@ -0,0 +1,118 @@
+use mica::{Engine, Value};
+
+use super::{run, RevealResultExt};
+
+const COUNTER: &str = r#"
+    struct Counter impl
+        func new() constructor = @count = 0
+        func increment() = do @count = @count + 1 end
+    end
+    let counter = Counter.new()
+    let step = func () = counter.increment()
+"#;
+
+#[test]
+fn globals_keep_their_values() {
+    let mut engine = Engine::new();
+    let _: Value = run(&mut engine, "let count = 1\nlet name = \"a\"");
+    engine
+        .reload(
+            "main.mi",
+            "let count = 100\nlet name = \"b\"\nlet added = 3",
+        )
+        .reveal();
+    let result: f64 = run(&mut engine, "assert(name == \"a\")\ncount + added");
+    assert_eq!(result, 4.0);
+}
+
+#[test]
+fn existing_instances_and_closures_run_the_new_code() {
+    let mut engine = Engine::new();
+    let _: Value = engine
+        .start("counter.mi", COUNTER)
+        .reveal()
+        .trampoline()
+        .reveal();
+    let _: Value = run(&mut engine, "step()\nstep()");
+
+    let v2 = COUNTER.replace("@count + 1", "@count + 10");
+    engine.reload("counter.mi", v2).reveal();
+    let count: f64 = run(&mut engine, "step()");
+    assert_eq!(count, 12.0);
+    let fresh: f64 = run(&mut engine, "Counter.new().increment()");
+    assert_eq!(fresh, 10.0);
+}
+
+#[test]
+fn methods_added_by_a_reload_are_available_on_existing_instances() {
+    let mut engine = Engine::new();
+    let _: Value = engine
+        .start("counter.mi", COUNTER)
+        .reveal()
+        .trampoline()
+        .reveal();
+    let v2 = COUNTER.replace(
+        "func increment()",
+        "func reset() = do @count = 0 end\n        func increment()",
+    );
+    engine.reload("counter.mi", v2).reveal();
+    let count: f64 = run(&mut engine, "step()\nstep()\ncounter.reset()\nstep()");
+    assert_eq!(count, 1.0);
+}
+
+#[test]
+fn functions_whose_signature_changed_are_replaced_with_new_ones() {
+    let mut engine = Engine::new();
+    let _: Value = engine
+        .start("math.mi", "func scale(x) = x * 2\nlet kept = [scale]")
+        .reveal()
+        .trampoline()
+        .reveal();
+    engine
+        .reload("math.mi", "func scale(x, by) = x * by\nlet kept = [scale]")
+        .reveal();
+    // The old function is still around for closures that refer to it.
+    let result: f64 = run(
+        &mut engine,
+        "let old_scale = kept[0]\nscale(3, 4) + old_scale(3)",
+    );
+    assert_eq!(result, 18.0);
+}
+
+#[test]
+fn scripts_can_be_reloaded_many_times() {
+    let mut engine = Engine::new();
+    let source = |n: u32| format!("func make() = func () = {n}\nlet made = [make()]");
+    let _: Value = engine
+        .start("make.mi", source(1))
+        .reveal()
+        .trampoline()
+        .reveal();
+    for n in 2..5 {
+        engine.reload("make.mi", source(n)).reveal();
+        // Closures made before each reload, whichever version made them, run the latest code.
+        let _: Value = run(&mut engine, "made.push(make())");
+        let results: Vec<f64> = run(
+            &mut engine,
+            "let results = []\nfor f in made.iter do results.push(f()) end\nresults",
+        );
+        assert!(results.iter().all(|&x| x == f64::from(n)), "{results:?}");
+    }
+}
+
+#[test]
+fn compile_errors_leave_the_old_code_running() {
+    let mut engine = Engine::new();
+    let _: Value = engine
+        .start("greet.mi", "func greet() = \"hello\"")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(engine.reload("greet.mi", "func greet( = \"hi\"").is_err());
+    let greeting: String = run(&mut engine, "greet()");
+    assert_eq!(greeting, "hello");
+
+    engine.reload("greet.mi", "func greet() = \"hi\"").reveal();
+    let greeting: String = run(&mut engine, "greet()");
+    assert_eq!(greeting, "hi");
+}
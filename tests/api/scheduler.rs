@@ -0,0 +1,384 @@
+use mica::{Completion, Engine, Error, FiberState, Scheduler, Value};
+
+use super::{try_run, RevealResultExt};
+
+fn engine_with_channel(gc_stress: bool) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_gc_stress(gc_stress);
+    let _: Value = engine
+        .start("setup.mi", "let channel = Channel.new()")
+        .reveal()
+        .trampoline()
+        .reveal();
+    engine
+}
+
+const PRODUCER: &str = r#"
+    let i = 0
+    while i < 20 do
+        # Allocate, so that the values in the channel are only reachable through it.
+        channel.send([i, i.to_string])
+        i = i + 1
+    end
+    channel.close()
+"#;
+
+const CONSUMER: &str = r#"
+    let received = []
+    for pair in channel do
+        assert(pair.get(1) == pair.get(0).to_string)
+        received.push(pair.get(0))
+    end
+    received.len
+"#;
+
+#[test]
+fn producer_and_consumer_exchange_values() {
+    for gc_stress in [false, true] {
+        let mut engine = engine_with_channel(gc_stress);
+        let mut scheduler = Scheduler::new(&mut engine);
+        // The consumer is started first, so it has to wait for the producer right away.
+        let consumer = scheduler.start("consumer.mi", CONSUMER).reveal();
+        let producer = scheduler.start("producer.mi", PRODUCER).reveal();
+        scheduler.run().reveal();
+        assert!(scheduler.is_finished(producer));
+        assert_eq!(scheduler.result::<f64>(consumer).reveal(), Some(20.0));
+    }
+}
+
+#[test]
+fn fibers_waiting_on_each_other_deadlock() {
+    let mut engine = engine_with_channel(false);
+    let mut scheduler = Scheduler::new(&mut engine);
+    let first = scheduler.start("first.mi", "channel.receive()").reveal();
+    let second = scheduler.start("second.mi", "channel.receive()").reveal();
+    assert!(matches!(scheduler.run(), Err(Error::Deadlock)));
+    assert!(!scheduler.is_finished(first));
+    assert!(!scheduler.is_finished(second));
+
+    // Sending a value from the host unblocks one of the fibers.
+    let _: Value = scheduler
+        .engine()
+        .start("host.mi", "channel.send(1)")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(matches!(scheduler.run(), Err(Error::Deadlock)));
+    assert_eq!(scheduler.result::<f64>(first).reveal(), Some(1.0));
+    assert!(!scheduler.is_finished(second));
+}
+
+#[test]
+fn errors_in_one_fiber_do_not_stop_the_others() {
+    let mut engine = engine_with_channel(false);
+    let mut scheduler = Scheduler::new(&mut engine);
+    let failing = scheduler
+        .start("failing.mi", "channel.receive()\nerror(\"oops\")")
+        .reveal();
+    let sender = scheduler.start("sender.mi", "channel.send(1)\n2").reveal();
+    assert!(scheduler.run().is_err());
+    assert!(scheduler.is_finished(failing));
+    assert!(scheduler.result::<Value>(failing).reveal().is_none());
+    scheduler.run().reveal();
+    assert_eq!(scheduler.result::<f64>(sender).reveal(), Some(2.0));
+}
+
+#[test]
+fn trampolining_a_blocked_fiber_is_a_deadlock() {
+    let mut engine = engine_with_channel(false);
+    let result: Result<Value, _> = try_run(&mut engine, "channel.receive()");
+    assert!(matches!(result, Err(Error::Deadlock)));
+}
+
+#[test]
+fn blocked_calls_are_retried_with_their_arguments() {
+    let mut engine = Engine::new();
+    let completion = Completion::new();
+    engine.set("completion", completion.clone()).reveal();
+
+    let mut scheduler = Scheduler::new(&mut engine);
+    // The last argument being a local makes the call fused with loading it.
+    let waiter = scheduler
+        .start(
+            "waiter.mi",
+            r#"
+                func wait(first, second) = select(first, second)
+                let winner = wait(Completion.new(), completion)
+                if winner._1 == "hello" do winner._0 else -1 end
+            "#,
+        )
+        .reveal();
+    assert!(!scheduler.run_until_blocked().reveal());
+    assert!(!scheduler.run_until_blocked().reveal());
+
+    completion.complete(Value::new("hello"));
+    assert!(scheduler.run_until_blocked().reveal());
+    assert_eq!(scheduler.result::<f64>(waiter).reveal(), Some(1.0));
+}
+
+#[test]
+fn select_resumes_with_the_first_ready_event() {
+    let mut engine = engine_with_channel(false);
+    let completion = Completion::new();
+    engine.set("completion", completion.clone()).reveal();
+
+    let mut scheduler = Scheduler::new(&mut engine);
+    let worker = scheduler
+        .start("worker.mi", "channel.receive() + 1")
+        .reveal();
+    let worker_handle = scheduler.handle(worker);
+    scheduler.engine().set("worker", worker_handle).reveal();
+    let waiter = scheduler
+        .start(
+            "waiter.mi",
+            r#"
+                let order = []
+                let i = 0
+                while i < 2 do
+                    let winner = select(completion, worker)
+                    order.push(winner._0)
+                    if winner._0 == 0 do completion = Completion.new() end
+                    i = i + 1
+                end
+                order.get(0) * 10 + order.get(1)
+            "#,
+        )
+        .reveal();
+    assert!(!scheduler.run_until_blocked().reveal());
+
+    completion.complete(Value::new("hello"));
+    assert!(!scheduler.run_until_blocked().reveal());
+    assert!(!scheduler.is_finished(waiter));
+
+    let _: Value = scheduler
+        .engine()
+        .start("host.mi", "channel.send(1)")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(scheduler.run_until_blocked().reveal());
+    assert_eq!(scheduler.result::<f64>(worker).reveal(), Some(2.0));
+    assert_eq!(scheduler.result::<f64>(waiter).reveal(), Some(1.0));
+}
+
+#[test]
+fn spawned_fibers_run_concurrently_with_their_parent() {
+    for gc_stress in [false, true] {
+        let mut engine = engine_with_channel(gc_stress);
+        let mut scheduler = Scheduler::new(&mut engine);
+        let parent = scheduler
+            .start(
+                "parent.mi",
+                r#"
+                    let consumer = spawn(func () = do
+                        let sum = 0
+                        for n in channel do sum = sum + n end
+                        sum
+                    end)
+                    channel.send(1)
+                    channel.send(2)
+                    channel.close()
+                    consumer.join() * 10
+                "#,
+            )
+            .reveal();
+        scheduler.run().reveal();
+        assert_eq!(scheduler.result::<f64>(parent).reveal(), Some(30.0));
+    }
+}
+
+#[test]
+fn parents_wait_for_their_children_to_finish() {
+    let mut engine = engine_with_channel(false);
+    let mut scheduler = Scheduler::new(&mut engine);
+    let parent = scheduler
+        .start("parent.mi", "spawn(func () = channel.receive())\n1")
+        .reveal();
+    assert!(!scheduler.run_until_blocked().reveal());
+    assert!(!scheduler.is_finished(parent));
+    assert!(scheduler.result::<f64>(parent).reveal().is_none());
+
+    let _: Value = scheduler
+        .engine()
+        .start("host.mi", "channel.send(2)")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(scheduler.run_until_blocked().reveal());
+    assert_eq!(scheduler.result::<f64>(parent).reveal(), Some(1.0));
+}
+
+#[test]
+fn children_of_failed_fibers_are_cancelled() {
+    let mut engine = engine_with_channel(false);
+    let mut scheduler = Scheduler::new(&mut engine);
+    let parent = scheduler
+        .start(
+            "parent.mi",
+            r#"
+                let child = spawn(func () = do
+                    spawn(func () = channel.receive())
+                    channel.receive()
+                end)
+                error("oops")
+            "#,
+        )
+        .reveal();
+    let watcher = scheduler.start("watcher.mi", "child.join()").reveal();
+    assert!(scheduler.run().is_err());
+    assert!(scheduler.is_finished(parent));
+    // Cancelling the child must also cancel its own child, as otherwise it'd be waiting for the
+    // channel forever.
+    let error = scheduler.run().unwrap_err();
+    assert!(scheduler.is_finished(watcher));
+    assert!(error.to_string().contains("the awaited task was cancelled"));
+    scheduler.run().reveal();
+}
+
+#[test]
+fn task_states_follow_the_fibers() {
+    let mut engine = engine_with_channel(false);
+    let mut scheduler = Scheduler::new(&mut engine);
+    let receiver = scheduler.start("receiver.mi", "channel.receive()").reveal();
+    let failing = scheduler.start("failing.mi", "error(\"oops\")").reveal();
+    assert_eq!(scheduler.state(receiver), FiberState::Running);
+    assert!(scheduler.run().is_err());
+    assert_eq!(scheduler.state(failing), FiberState::Errored);
+    assert!(matches!(scheduler.run(), Err(Error::Deadlock)));
+    assert_eq!(scheduler.state(receiver), FiberState::Suspended);
+    assert_eq!(&*scheduler.stack(receiver)[0].module_name, "receiver.mi");
+}
+
+#[test]
+fn ticks_split_the_budget_between_fibers() {
+    let mut engine = Engine::new();
+    let _: Value = engine
+        .start("setup.mi", "let counters = [0, 0]")
+        .reveal()
+        .trampoline()
+        .reveal();
+    let mut scheduler = Scheduler::new(&mut engine);
+    for i in 0..2 {
+        scheduler
+            .start(
+                format!("counter{i}.mi"),
+                format!("while true do counters.set({i}, counters.get({i}) + 1) end"),
+            )
+            .reveal();
+    }
+    assert!(!scheduler.tick(1000).reveal());
+    let counts: Vec<f64> = (0..2)
+        .map(|i| {
+            scheduler
+                .engine()
+                .start("read.mi", format!("counters.get({i})"))
+                .reveal()
+                .trampoline()
+                .reveal()
+        })
+        .collect();
+    assert!(counts[0] > 0.0);
+    assert_eq!(counts[0], counts[1]);
+}
+
+#[test]
+fn fibers_left_out_of_a_tick_run_first_during_the_next_one() {
+    let mut engine = Engine::new();
+    let _: Value = engine
+        .start("setup.mi", "let counters = [0, 0, 0, 0]")
+        .reveal()
+        .trampoline()
+        .reveal();
+    let mut scheduler = Scheduler::new(&mut engine);
+    for i in 0..4 {
+        scheduler
+            .start(
+                format!("counter{i}.mi"),
+                format!("while true do counters.set({i}, counters.get({i}) + 1) end"),
+            )
+            .reveal();
+    }
+    // The budget is too small for every fiber to run during a single tick.
+    for _ in 0..200 {
+        assert!(!scheduler.tick(2).reveal());
+    }
+    let all_ran: bool = scheduler
+        .engine()
+        .start(
+            "read.mi",
+            "counters.get(0) > 0 and counters.get(1) > 0 and counters.get(2) > 0 and counters.get(3) > 0",
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert!(all_ran);
+}
+
+#[test]
+fn yielding_lets_other_fibers_run() {
+    let mut engine = Engine::new();
+    let _: Value = engine
+        .start("setup.mi", "let log = []")
+        .reveal()
+        .trampoline()
+        .reveal();
+    let mut scheduler = Scheduler::new(&mut engine);
+    for name in ["a", "b"] {
+        scheduler
+            .start(
+                format!("{name}.mi"),
+                format!("log.push(\"{name}1\")\nyield()\nlog.push(\"{name}2\")"),
+            )
+            .reveal();
+    }
+    scheduler.run().reveal();
+    let log: Value = scheduler
+        .engine()
+        .start("read.mi", "log")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(log.to_string(), "[a1, b1, a2, b2]");
+}
+
+#[test]
+fn fibers_blocked_inside_coroutines_are_resumed_by_the_scheduler() {
+    let mut engine = engine_with_channel(false);
+    let mut scheduler = Scheduler::new(&mut engine);
+    let consumer = scheduler
+        .start(
+            "consumer.mi",
+            r#"
+                let receiver = Fiber.new(func () = do
+                    while true do
+                        yield(channel.receive())
+                    end
+                end)
+                receiver.resume + receiver.resume
+            "#,
+        )
+        .reveal();
+    scheduler
+        .start("producer.mi", "channel.send(1)\nchannel.send(2)")
+        .reveal();
+    scheduler.run().reveal();
+    assert_eq!(scheduler.result::<f64>(consumer).reveal(), Some(3.0));
+}
+
+#[test]
+fn coroutines_share_the_budget_of_their_resumer() {
+    let mut engine = Engine::new();
+    let mut scheduler = Scheduler::new(&mut engine);
+    let looping = scheduler
+        .start(
+            "looping.mi",
+            "let f = Fiber.new(func () = do\nlet i = 0\nwhile i < 1000 do i = i + 1 end\ni\nend)\nf.resume",
+        )
+        .reveal();
+    let mut ticks = 0;
+    while !scheduler.tick(100).reveal() {
+        ticks += 1;
+    }
+    assert!(ticks > 1);
+    assert_eq!(scheduler.result::<f64>(looping).reveal(), Some(1000.0));
+}
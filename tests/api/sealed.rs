@@ -0,0 +1,62 @@
+use mica::{Engine, Value};
+
+use super::{run, RevealResultExt};
+
+#[test]
+fn assigning_to_sealed_globals_fails_to_compile() {
+    let mut engine = Engine::new();
+    engine.set("config", 1.0).reveal();
+    engine.seal("config").reveal();
+    for (source, column) in [
+        ("config = 2", 1),
+        ("let config = 2", 5),
+        ("func config() = nil", 6),
+        ("struct config", 1),
+    ] {
+        let error = engine
+            .compile("test.mi", source)
+            .expect_err("assigning to a sealed global should fail");
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "test.mi:1:{column}: error: global 'config' is sealed and cannot be reassigned"
+            )
+        );
+    }
+    // Locals may still shadow sealed globals, and the embedder can still set them.
+    let _: Value = run(&mut engine, "do let config = 2 end");
+    engine.set("config", 3.0).reveal();
+    let config: f64 = engine.get("config").reveal();
+    assert_eq!(config, 3.0);
+}
+
+#[test]
+fn code_compiled_before_sealing_fails_at_runtime() {
+    let mut engine = Engine::new();
+    let _: Value = run(&mut engine, "let x = 1\nfunc bump() = x = x + 1");
+    engine.seal("x").reveal();
+    let bump: Value = engine.get("bump").reveal();
+    let error = engine
+        .call::<Value>(bump, ())
+        .expect_err("assigning to a sealed global should fail");
+    assert!(error
+        .to_string()
+        .starts_with("error: global 'x' is sealed and cannot be reassigned"));
+}
+
+#[test]
+fn global_constants_stay_constant_in_later_chunks() {
+    let mut engine = Engine::new();
+    let _: Value = engine
+        .start("first.mi", "const limit = 10")
+        .reveal()
+        .trampoline()
+        .reveal();
+    let error = engine
+        .compile("second.mi", "limit = 20")
+        .expect_err("assigning to a constant should fail");
+    assert_eq!(
+        error.to_string(),
+        "second.mi:1:1: error: cannot assign to 'limit', which was declared with 'const'"
+    );
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use mica::{
+    serde::{from_value, to_value},
+    Engine, Value,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{run, RevealResultExt};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: String,
+    retries: u32,
+    ratio: f64,
+    tags: Vec<String>,
+    parent: Option<Box<Config>>,
+    mode: Mode,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Mode {
+    Fast,
+    Limited(u8),
+    Ranged { min: i32, max: i32 },
+    Pair(bool, char),
+}
+
+fn config() -> Config {
+    Config {
+        name: "main".into(),
+        retries: 3,
+        ratio: 0.5,
+        tags: vec!["a".into(), "b".into()],
+        parent: Some(Box::new(Config {
+            name: "base".into(),
+            retries: 0,
+            ratio: 1.0,
+            tags: vec![],
+            parent: None,
+            mode: Mode::Ranged { min: -1, max: 1 },
+        })),
+        mode: Mode::Limited(4),
+    }
+}
+
+#[test]
+fn structs_become_dicts_that_scripts_can_read() {
+    let mut engine = Engine::new();
+    let config = to_value(&mut engine, &config()).reveal();
+    engine.set("config", config).reveal();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            assert(config["name"] == "main")
+            assert(config["retries"] == 3)
+            assert(config["tags"][1] == "b")
+            assert(config["parent"]["parent"] == nil)
+            assert(config["parent"]["mode"]["Ranged"]["min"] == -1)
+            assert(config["mode"]["Limited"] == 4)
+        "#,
+    );
+}
+
+#[test]
+fn values_round_trip() {
+    let mut engine = Engine::new();
+    let value = to_value(&mut engine, &config()).reveal();
+    let config_back: Config = from_value(&value).reveal();
+    assert_eq!(config_back, config());
+
+    for mode in [Mode::Fast, Mode::Pair(true, 'x')] {
+        let value = to_value(&mut engine, &mode).reveal();
+        assert_eq!(from_value::<Mode>(&value).reveal(), mode);
+    }
+}
+
+#[test]
+fn script_results_can_be_read_back() {
+    let mut engine = Engine::new();
+    let result: Value = run(
+        &mut engine,
+        r#"
+            [
+                "name": "from script",
+                "retries": 1,
+                "ratio": 2,
+                "tags": ("tuples", "work", "too"),
+                "parent": nil,
+                "mode": "Fast",
+            ]
+        "#,
+    );
+    let config: Config = from_value(&result).reveal();
+    assert_eq!(config.name, "from script");
+    assert_eq!(config.ratio, 2.0);
+    assert_eq!(config.tags, ["tuples", "work", "too"]);
+    assert_eq!(config.mode, Mode::Fast);
+
+    let record: Value = run(&mut engine, "{ min: 1, max: 2 }");
+    let ranged: BTreeMap<String, i32> = from_value(&record).reveal();
+    assert_eq!(
+        ranged,
+        BTreeMap::from([("max".into(), 2), ("min".into(), 1)])
+    );
+}
+
+#[test]
+fn mismatched_values_are_reported() {
+    let mut engine = Engine::new();
+    let value: Value = run(&mut engine, r#" ["name": "x", "retries": -1] "#);
+    let error = from_value::<Config>(&value).unwrap_err();
+    assert!(error.to_string().contains("invalid value: integer `-1`"));
+
+    let value: Value = run(&mut engine, "(func (x) = x)");
+    let error = from_value::<Vec<u8>>(&value).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "value of type Function cannot be deserialized"
+    );
+}
+
+#[test]
+fn self_referential_lists_are_rejected() {
+    let mut engine = Engine::new();
+    let value: Value = run(&mut engine, "let l = [] l.push(l) l");
+
+    #[derive(Debug, Deserialize)]
+    struct Nested(#[allow(dead_code)] Vec<Nested>);
+    let error = from_value::<Nested>(&value).unwrap_err();
+    assert!(error.to_string().contains("nested too deeply"));
+}
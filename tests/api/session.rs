@@ -0,0 +1,56 @@
+use mica::{Engine, Error, Session, Value};
+
+#[test]
+fn definitions_carry_over_between_snippets() -> Result<(), Error> {
+    let mut engine = Engine::new();
+    let mut session = Session::new(&mut engine, "(repl)");
+    let _: Value = session.eval("let greeting = \"hello\"")?;
+    let _: Value = session.eval("func greet(name) = greeting.cat(\", \").cat(name)")?;
+    let _: Value =
+        session.eval("struct Counter impl func new() constructor = do @n = 0 end end")?;
+    let greeting: String = session.eval("greet(\"world\")")?;
+    assert_eq!(greeting, "hello, world");
+    let is_counter: bool = session.eval("Counter.new() != nil")?;
+    assert!(is_counter);
+    Ok(())
+}
+
+#[test]
+fn incomplete_input_is_told_apart_from_invalid_input() {
+    assert!(!Session::is_complete("func f() = do"));
+    assert!(!Session::is_complete("print(1, "));
+    assert!(!Session::is_complete("[1, 2"));
+    assert!(!Session::is_complete("\"unterminated"));
+    assert!(!Session::is_complete("1 +\n"));
+    assert!(Session::is_complete("1 + 2"));
+    assert!(Session::is_complete("1 + + 2"));
+    assert!(Session::is_complete(") + 1"));
+}
+
+#[test]
+fn errors_point_to_the_snippet_that_declared_the_function() -> Result<(), Error> {
+    let mut engine = Engine::new();
+    let mut session = Session::new(&mut engine, "(repl)");
+    let _: Value = session.eval("func fail() = do\n  error(\"oops\")\nend")?;
+    let error = session.eval::<Value>("nil\nfail()").unwrap_err();
+    let Error::Runtime(error) = error else {
+        panic!("runtime error expected, got {error:?}");
+    };
+    let snippet = error
+        .snippet()
+        .expect("runtime error should have a snippet");
+    assert_eq!(snippet.line, 2);
+    assert_eq!(snippet.text, "  error(\"oops\")");
+    Ok(())
+}
+
+#[test]
+fn warnings_are_kept_for_the_last_snippet() -> Result<(), Error> {
+    let mut engine = Engine::new();
+    let mut session = Session::new(&mut engine, "(repl)");
+    let _: Value = session.eval("func f() = do let unused = 1 end")?;
+    assert_eq!(session.warnings().len(), 1);
+    let _: Value = session.eval("f()")?;
+    assert!(session.warnings().is_empty());
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use mica::{Diagnostic, DiagnosticSeverity, Engine, Value};
+
+use super::try_run;
+
+#[test]
+fn compile_errors_include_source_snippets() {
+    let mut engine = Engine::new();
+    let error = engine
+        .compile("test.mi", "let counter = 1\n\tcountr + 1\n")
+        .expect_err("compilation should fail");
+    assert_eq!(
+        format!("{error:#}"),
+        "test.mi:2:2: error: variable 'countr' does not exist (did you mean 'counter'?)\n  \
+        |\n\
+        2 | \tcountr + 1\n  \
+        | \t^^^^^^"
+    );
+    // The regular format stays on a single line.
+    assert_eq!(
+        error.to_string(),
+        "test.mi:2:2: error: variable 'countr' does not exist (did you mean 'counter'?)"
+    );
+}
+
+#[test]
+fn runtime_errors_include_source_snippets() {
+    let mut engine = Engine::new();
+    let error = try_run::<Value>(&mut engine, "let x = 1\nlet y = \"ü\" + nil\n")
+        .expect_err("execution should fail");
+    let mica::Error::Runtime(error) = &error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    let snippet = error
+        .snippet()
+        .expect("runtime error should have a snippet");
+    assert_eq!(snippet.line, 2);
+    assert_eq!(snippet.text, "let y = \"ü\" + nil");
+    // The underline is measured in characters, not bytes.
+    assert_eq!(snippet.underline, 12..13);
+}
+
+#[test]
+fn snippets_point_at_the_right_module() {
+    let mut engine = Engine::new();
+    let _: Value = try_run(&mut engine, "func fail() = nil + 1").unwrap();
+    let error = try_run::<Value>(&mut engine, "\n\nfail()").expect_err("execution should fail");
+    let mica::Error::Runtime(error) = &error else {
+        panic!("expected a runtime error, got {error:#}");
+    };
+    assert_eq!(error.snippet().unwrap().text, "func fail() = nil + 1");
+}
+
+#[test]
+fn snippets_underline_whole_expressions() {
+    let mut engine = Engine::new();
+    let error = engine
+        .compile("test.mi", "let ö = 1\nf(ö, \"ü\") = 3\n")
+        .expect_err("compilation should fail");
+    let mica::Error::Compile(error) = &error else {
+        panic!("expected a single compile error, got {error:#}");
+    };
+    let snippet = error
+        .snippet()
+        .expect("compile error should have a snippet");
+    assert_eq!(snippet.underline, 0..9);
+    assert_eq!(
+        format!("{error:#}"),
+        "test.mi:2:1: error: invalid left hand side of assignment\n  \
+        |\n\
+        2 | f(ö, \"ü\") = 3\n  \
+        | ^^^^^^^^^\n  \
+        = hint: only variables, fields, and indexed elements can be assigned to"
+    );
+}
+
+#[test]
+fn compile_errors_include_hints() {
+    let mut engine = Engine::new();
+    let error = engine
+        .compile("test.mi", "let x = 1\nx = = 2\n")
+        .expect_err("compilation should fail");
+    assert_eq!(
+        format!("{error:#}"),
+        "test.mi:2:5: error: invalid token in prefix position\n  \
+        |\n\
+        2 | x = = 2\n  \
+        |     ^\n  \
+        = hint: an expression was expected here"
+    );
+    assert_eq!(
+        error.to_string(),
+        "test.mi:2:5: error: invalid token in prefix position"
+    );
+}
+
+#[test]
+fn parsing_recovers_to_report_every_error() {
+    let mut engine = Engine::new();
+    let error = engine
+        .compile("test.mi", "let a = )\nlet b = 1\nlet c = ]\nprint(a b)\n")
+        .expect_err("compilation should fail");
+    let diagnostics = error.diagnostics();
+    let problems: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.severity,
+                diagnostic.span.start.line,
+                diagnostic.span.start.column,
+                diagnostic.message.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        problems,
+        [
+            (
+                DiagnosticSeverity::Error,
+                1,
+                9,
+                "invalid token in prefix position"
+            ),
+            (
+                DiagnosticSeverity::Error,
+                3,
+                9,
+                "invalid token in prefix position"
+            ),
+            (DiagnosticSeverity::Error, 4, 9, "comma ',' expected"),
+        ]
+    );
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| &*diagnostic.module_name == "test.mi" && diagnostic.hint.is_some()));
+}
+
+#[test]
+fn warnings_and_runtime_errors_are_diagnostics_too() {
+    let mut engine = Engine::new();
+    let script = engine
+        .compile(
+            "test.mi",
+            "func f() = do\n    let unused = 1\n    nil + 1\nend\nf()",
+        )
+        .unwrap();
+    let warnings: Vec<Diagnostic> = script.warnings().iter().map(Diagnostic::from).collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].severity, DiagnosticSeverity::Warning);
+    assert_eq!(warnings[0].span.start.line, 2);
+
+    let error = script
+        .into_fiber()
+        .trampoline::<Value>()
+        .expect_err("execution should fail");
+    let diagnostics = error.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Error);
+    assert_eq!(diagnostics[0].span.start.line, 3);
+    assert!(diagnostics[0].message.starts_with("type mismatch"));
+
+    assert!(mica::Error::Deadlock.diagnostics().is_empty());
+}
@@ -1,15 +1,112 @@
 use mica::{Engine, Value};
 
-use super::RevealResultExt;
+use super::run;
 
 #[test]
 fn gc_collect_should_not_cause_use_after_free() {
     let mut engine = Engine::new();
-    let _: Value = engine.start("test.mi", "[]").reveal().trampoline().reveal();
-    let _: Value = engine.start("test.mi", "[]").reveal().trampoline().reveal();
-    let _: Value = engine
-        .start("test.mi", "Gc.collect")
-        .reveal()
-        .trampoline()
-        .reveal();
+    let _: Value = run(&mut engine, "[]");
+    let _: Value = run(&mut engine, "[]");
+    let _: Value = run(&mut engine, "Gc.collect");
+}
+
+#[test]
+fn jumps_over_huge_branches_use_long_jumps() {
+    // A string literal is embedded directly in the bytecode, so a large enough literal pushes
+    // the jump offsets past what fits in an instruction's 24-bit operand.
+    let huge_string = "a".repeat(1 << 24);
+    let source = format!(
+        r#"
+            let x = 0
+            let i = 0
+            while i < 3 do
+                if x == 1 do
+                    "{huge_string}"
+                else
+                    x = 2
+                end
+                i = i + 1
+            end
+            assert(x == 2 and i == 3)
+        "#
+    );
+    let mut engine = Engine::new();
+    let _: Value = run(&mut engine, &source);
+}
+
+#[test]
+fn collecting_while_containers_are_alive_keeps_their_contents() {
+    let mut engine = Engine::new();
+    let _: Value = run(
+        &mut engine,
+        r#"
+                let list = ["a".cat(""), "b"]
+                let dict = ["key": "value"]
+                let tuple = ("x", "y")
+                let record = { field: "z" }
+                Gc.collect
+                Gc.collect
+                assert(list.len == 2 and list.get(0) == "a")
+                assert(dict.get("key") == "value")
+                assert(tuple._1 == "y")
+                assert(record.field == "z")
+            "#,
+    );
+}
+
+#[test]
+fn gc_stress_mode_does_not_free_reachable_values() {
+    let mut engine = Engine::new();
+    engine.set_gc_stress(true);
+    assert!(engine.gc_stress());
+    let _: Value = run(
+        &mut engine,
+        r#"
+                struct Point impl
+                    func new(x, y) constructor = do
+                        @x = x
+                        @y = y
+                    end
+
+                    func sum() = @x + @y
+                end
+
+                let points = []
+                for i in CountUp.new(0, 9) do
+                    points.push(Point.new(i, [i].get(0)))
+                end
+                let sum = 0
+                for point in points.iter do
+                    sum = sum + point.sum
+                end
+                assert(sum == 90)
+
+                let chars = []
+                for c in "stress".chars do
+                    chars.push(c)
+                end
+                assert(chars == ["s", "t", "r", "e", "s", "s"])
+
+                let words = []
+                for word in "a b c".split(" ") do
+                    words.push(word)
+                end
+                assert(words == ["a", "b", "c"])
+            "#,
+    );
+}
+
+#[test]
+fn gc_stats_count_collections() {
+    let mut engine = Engine::new();
+    assert_eq!(engine.gc_stats().collections, 0);
+    let _: Value = run(
+        &mut engine,
+        "let xs = [[], [], []]\nxs = nil\nGc.collect()\nGc.collect()",
+    );
+    let stats = engine.gc_stats();
+    assert_eq!(stats.collections, 2);
+    assert_eq!(stats.pauses, 2);
+    assert!(stats.freed_bytes > 0);
+    assert!(stats.max_pause <= stats.total_pause);
 }
@@ -0,0 +1,91 @@
+use mica::{Engine, Error, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+#[test]
+fn tests_are_run_in_order_of_declaration() {
+    let mut engine = Engine::new();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            Test.case("first", func () = Test.assert_eq(1 + 1, 2))
+            Test.case("second", func () = Test.assert_ne(1, 1))
+            Test.case("third", func () = Test.fail("not implemented yet"))
+        "#,
+    );
+    let report = engine.run_tests();
+    let names: Vec<_> = report
+        .results()
+        .iter()
+        .map(|result| result.name())
+        .collect();
+    assert_eq!(names, ["first", "second", "third"]);
+    assert_eq!(report.passed(), 1);
+    assert_eq!(report.failed(), 2);
+    assert!(!report.is_success());
+
+    let error = report.results()[2].error().unwrap();
+    assert!(matches!(error, Error::Runtime(_)));
+    assert!(error.to_string().contains("not implemented yet"));
+}
+
+#[test]
+fn output_is_captured_per_test() {
+    let mut engine = Engine::new();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            Test.case("a", func () = print("from a"))
+            Test.case("b", func () = debug("from b", 1))
+        "#,
+    );
+    let report = engine.run_tests();
+    assert!(report.is_success());
+    assert_eq!(report.results()[0].output(), "from a\n");
+    assert_eq!(report.results()[1].output(), "\"from b\"\t1\n");
+}
+
+#[test]
+fn teardown_runs_even_if_the_test_fails() {
+    let mut engine = Engine::new();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let events = []
+            Test.setup(func () = events.push("setup"))
+            Test.teardown(func () = events.push("teardown"))
+            Test.case("failing", func () = do
+                events.push("case")
+                Test.assert_eq("actual", "expected")
+                events.push("unreachable")
+            end)
+        "#,
+    );
+    let report = engine.run_tests();
+    assert_eq!(report.failed(), 1);
+    let message = report.results()[0].error().unwrap().to_string();
+    assert!(message.contains(r#"expected "expected", got "actual""#));
+    assert!(message.contains("stack traceback"));
+
+    let events: Vec<String> = engine
+        .start("events.mi", "events")
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(events, ["setup", "case", "teardown"]);
+}
+
+#[test]
+fn tests_only_run_once() {
+    let mut engine = Engine::new();
+    let _: Value = run(&mut engine, r#"Test.case("once", func () = nil)"#);
+    assert_eq!(engine.run_tests().results().len(), 1);
+    assert!(engine.run_tests().results().is_empty());
+}
+
+#[test]
+fn test_cases_must_be_functions() {
+    let mut engine = Engine::new();
+    let result: Result<Value, _> = try_run(&mut engine, r#"Test.case("not a function", 1)"#);
+    assert!(result.is_err());
+}
@@ -0,0 +1,59 @@
+use mica::ll::lexer::{Lexer, TokenKind};
+
+fn tokens(source: &str) -> Vec<(TokenKind, &str)> {
+    Lexer::new("tokens".into(), source.to_owned())
+        .into_tokens()
+        .map(|(kind, span)| (kind, &source[span.start.byte..span.end.byte]))
+        .collect()
+}
+
+#[test]
+fn tokens_cover_source_code_without_whitespace() {
+    let source = "let x = 1 # The answer.\nprint(x)";
+    assert_eq!(
+        tokens(source),
+        [
+            (TokenKind::Let, "let"),
+            (TokenKind::Identifier("x".into()), "x"),
+            (TokenKind::Assign, "="),
+            (TokenKind::Number(1.0), "1"),
+            (TokenKind::Comment, "# The answer."),
+            (TokenKind::Identifier("print".into()), "print"),
+            (TokenKind::LeftParen, "("),
+            (TokenKind::Identifier("x".into()), "x"),
+            (TokenKind::RightParen, ")"),
+        ]
+    );
+}
+
+#[test]
+fn errors_become_error_tokens() {
+    let tokens = tokens("a $ b\n\"unterminated\nc");
+    let kinds: Vec<_> = tokens
+        .iter()
+        .map(|(kind, text)| match kind {
+            TokenKind::Error(_) => ("error", *text),
+            _ => ("token", *text),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("token", "a"),
+            ("error", "$"),
+            ("token", "b"),
+            ("error", "\"unterminated"),
+            ("token", "c"),
+        ]
+    );
+}
+
+#[test]
+fn error_tokens_carry_a_message() {
+    let tokens = tokens("\"abc\\");
+    let [(TokenKind::Error(message), text)] = &tokens[..] else {
+        panic!("expected a single error token, got {tokens:?}");
+    };
+    assert_eq!(*text, "\"abc\\");
+    assert!(!message.is_empty());
+}
@@ -0,0 +1,131 @@
+use mica::tooling::{self, Ast, NodeId, NodeKind, TokenKind, Visit, Visitor};
+
+use super::RevealResultExt;
+
+const SOURCE: &str = r#"
+struct Point impl
+    func new(x, y) constructor = do
+        @x = x
+        @y = y
+    end
+
+    func len() = (@x * @x + @y * @y).sqrt
+end
+
+let points = [Point.new(1, 2), Point.new(3, 4)]
+for point in points do
+    print(point.len)
+end
+"#;
+
+#[derive(Default)]
+struct Events(Vec<String>);
+
+impl Visitor for Events {
+    fn enter(&mut self, ast: &Ast, node: NodeId) -> Visit {
+        self.0.push(format!("enter {:?}", ast.kind(node)));
+        if ast.kind(node) == NodeKind::Call {
+            Visit::SkipChildren
+        } else {
+            Visit::Children
+        }
+    }
+
+    fn leave(&mut self, ast: &Ast, node: NodeId) {
+        self.0.push(format!("leave {:?}", ast.kind(node)));
+    }
+}
+
+#[test]
+fn every_node_has_a_span() {
+    let (ast, root) = tooling::parse("test.mi", SOURCE).reveal();
+    let mut nodes = 0;
+    ast.walk(root, |node, parent| {
+        nodes += 1;
+        let span = ast.span(node);
+        assert!(!span.is_uninit(), "{:?} has no span", ast.kind(node));
+        if let Some(parent) = parent {
+            let parent = ast.span(parent);
+            assert!(parent.start.byte <= span.start.byte && span.end.byte <= parent.end.byte);
+        }
+    });
+    assert!(nodes > 50);
+}
+
+#[test]
+fn visitors_enter_and_leave_nodes_in_order() {
+    let (ast, root) = tooling::parse("test.mi", "a + f(b)").reveal();
+    let mut events = Events::default();
+    ast.visit(root, &mut events);
+    assert_eq!(
+        events.0,
+        [
+            "enter Main",
+            "enter Add",
+            "enter Identifier",
+            "leave Identifier",
+            "enter Call",
+            "leave Call",
+            "leave Add",
+            "leave Main",
+        ]
+    );
+}
+
+#[test]
+fn syntax_errors_are_reported_with_snippets() {
+    let error = tooling::parse("test.mi", "let x = (1 +").unwrap_err();
+    let message = format!("{error:#}");
+    assert!(message.starts_with("test.mi:1:"), "{message}");
+    assert!(message.contains("let x = (1 +"), "{message}");
+}
+
+fn tokens(source: &str) -> Vec<(TokenKind, &str)> {
+    tooling::tokenize(source)
+        .map(|(kind, span)| (kind, &source[span.start.byte..span.end.byte]))
+        .collect()
+}
+
+#[test]
+fn tokens_are_categorized_for_highlighting() {
+    let source = "## Doc.\nfunc f(x) = x == nil and 'a\n# Comment.\n@y = \\\\ long\n[1, _]";
+    assert_eq!(
+        tokens(source),
+        [
+            (TokenKind::DocComment, "## Doc."),
+            (TokenKind::Keyword, "func"),
+            (TokenKind::Identifier, "f"),
+            (TokenKind::Punctuation, "("),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::Punctuation, ")"),
+            (TokenKind::Operator, "="),
+            (TokenKind::Identifier, "x"),
+            (TokenKind::Operator, "=="),
+            (TokenKind::Constant, "nil"),
+            (TokenKind::Keyword, "and"),
+            (TokenKind::Label, "'a"),
+            (TokenKind::Comment, "# Comment."),
+            (TokenKind::Punctuation, "@"),
+            (TokenKind::Identifier, "y"),
+            (TokenKind::Operator, "="),
+            (TokenKind::String, "\\\\ long"),
+            (TokenKind::Punctuation, "["),
+            (TokenKind::Number, "1"),
+            (TokenKind::Punctuation, ","),
+            (TokenKind::Identifier, "_"),
+            (TokenKind::Punctuation, "]"),
+        ]
+    );
+}
+
+#[test]
+fn tokenizing_continues_after_errors() {
+    assert_eq!(
+        tokens("a $ \"b"),
+        [
+            (TokenKind::Identifier, "a"),
+            (TokenKind::Error, "$"),
+            (TokenKind::Error, "\"b"),
+        ]
+    );
+}
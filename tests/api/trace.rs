@@ -0,0 +1,186 @@
+use std::{cell::Cell, rc::Rc};
+
+use mica::{Engine, Error, Trace, TypeBuilder, UserData, Value};
+
+use super::{run, try_run, RevealResultExt};
+
+/// Creates an engine with functions that return different values every time they're called, and
+/// a counter of how many times they were called.
+fn nondeterministic_engine() -> (Engine, Rc<Cell<u32>>) {
+    let calls = Rc::new(Cell::new(0));
+    let mut engine = Engine::new();
+    let counter = Rc::clone(&calls);
+    let next = move || {
+        counter.set(counter.get() + 1);
+        counter.get()
+    };
+    let roll = next.clone();
+    engine
+        .add_function("roll", move || f64::from(roll() * 7 % 6 + 1))
+        .reveal();
+    let sensor = next.clone();
+    engine
+        .add_function("sensor", move || {
+            let n = f64::from(sensor());
+            (n, n * 0.1, format!("reading #{n}"))
+        })
+        .reveal();
+    let flaky = next;
+    engine
+        .add_function("flaky", move || -> Result<f64, Error> {
+            let n = flaky();
+            if n < 5 {
+                Err(Error::User(format!("failure #{n}").into()))
+            } else {
+                Ok(f64::from(n))
+            }
+        })
+        .reveal();
+    engine.add_function("echo", |value: Value| value).reveal();
+    (engine, calls)
+}
+
+const SCRIPT: &str = r#"
+    let values = [roll(), roll(), sensor(), echo(["key": [nil, true]])]
+    values.push(flaky())
+    values
+"#;
+
+#[test]
+fn replayed_scripts_observe_the_recorded_values() {
+    let (mut engine, calls) = nondeterministic_engine();
+    engine.start_recording();
+    let error = try_run::<Value>(&mut engine, SCRIPT).unwrap_err();
+    assert!(error.to_string().contains("failure #4"));
+    let recorded: Value = run(&mut engine, SCRIPT);
+    let trace = engine.stop_tracing().unwrap();
+    assert_eq!(trace.len(), 10);
+    assert_eq!(calls.get(), 8);
+
+    // A fresh engine returns different values, unless the trace is replayed.
+    let (mut engine, calls) = nondeterministic_engine();
+    let _: Value = run(&mut engine, "roll()");
+    engine.start_replaying(trace);
+    let error = try_run::<Value>(&mut engine, SCRIPT).unwrap_err();
+    assert!(error.to_string().contains("failure #4"));
+    let replayed: Value = run(&mut engine, SCRIPT);
+    assert_eq!(format!("{recorded:?}"), format!("{replayed:?}"));
+    assert_eq!(calls.get(), 1);
+}
+
+#[test]
+fn traces_survive_being_saved_as_text() {
+    let (mut engine, _) = nondeterministic_engine();
+    engine.start_recording();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            echo("spaces, \"quotes\"\nand newlines ✓")
+            echo(0 / 0)
+            echo(-0)
+            echo(((), [:], []))
+            sensor()
+        "#,
+    );
+    let _ = try_run::<Value>(&mut engine, "flaky()").unwrap_err();
+    let trace = engine.stop_tracing().unwrap();
+
+    let text = trace.to_string();
+    assert!(text.starts_with("mica-trace 1\n"));
+    let loaded: Trace = text.parse().reveal();
+    assert_eq!(loaded.to_string(), text);
+    assert_eq!(loaded.len(), 6);
+}
+
+#[test]
+fn invalid_traces_are_rejected() {
+    assert!("".parse::<Trace>().is_err());
+    assert!("mica-trace 1\n\"4:roll".parse::<Trace>().is_err());
+    assert!("mica-trace 1\n\"4:roll = [ #3ff0000000000000"
+        .parse::<Trace>()
+        .is_err());
+    assert!("mica-trace 1\n\"99:roll = n".parse::<Trace>().is_err());
+    let trace: Trace = "mica-trace 1\n\"4:roll = #4018000000000000\n"
+        .parse()
+        .reveal();
+    assert_eq!(trace.functions().collect::<Vec<_>>(), ["roll"]);
+}
+
+#[test]
+fn core_library_functions_are_not_traced() {
+    let (mut engine, _) = nondeterministic_engine();
+    engine.start_recording();
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let list = [roll()]
+            list.push("a".cat("b"))
+            let tuple = (1, 2)
+            tuple._0 + list.len
+        "#,
+    );
+    let trace = engine.stop_tracing().unwrap();
+    assert_eq!(trace.functions().collect::<Vec<_>>(), ["roll"]);
+}
+
+#[test]
+fn diverging_from_the_trace_is_an_error() {
+    let (mut engine, _) = nondeterministic_engine();
+    engine.start_recording();
+    let _: Value = run(&mut engine, "roll()");
+    let trace = engine.stop_tracing().unwrap();
+
+    engine.start_replaying(trace.clone());
+    let error = try_run::<Value>(&mut engine, "sensor()").unwrap_err();
+    assert!(error.to_string().contains(
+        "execution diverged from the replayed trace: expected a call to roll, but sensor was called"
+    ));
+
+    engine.start_replaying(trace);
+    let error = try_run::<Value>(&mut engine, "roll()\nroll()").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("the trace has ended, but roll was called"));
+    assert!(engine.stop_tracing().is_some());
+    assert!(engine.stop_tracing().is_none());
+}
+
+#[derive(Clone)]
+struct Counter(Rc<Cell<u32>>);
+
+impl UserData for Counter {}
+
+#[test]
+fn calls_returning_user_data_are_repeated_during_replay() {
+    let created = Rc::new(Cell::new(0));
+    let mut engine = Engine::new();
+    let count = Rc::clone(&created);
+    engine
+        .add_type(
+            TypeBuilder::<Counter>::new("Counter")
+                .add_static("new", move || {
+                    count.set(count.get() + 1);
+                    Counter(Rc::new(Cell::new(100)))
+                })
+                .add_function("bump", |counter: &Counter| {
+                    counter.0.set(counter.0.get() + 1);
+                    counter.0.get()
+                }),
+        )
+        .reveal();
+    let script = "let c = Counter.new()\nc.bump()\nc.bump()";
+
+    engine.start_recording();
+    let recorded: f64 = run(&mut engine, script);
+    let trace = engine.stop_tracing().unwrap();
+    assert_eq!(
+        trace.functions().collect::<Vec<_>>(),
+        ["type Counter.new", "Counter.bump", "Counter.bump"]
+    );
+    assert!(trace.to_string().contains("\"16:type Counter.new ~"));
+
+    engine.start_replaying(trace);
+    let replayed: f64 = run(&mut engine, script);
+    assert_eq!(recorded, replayed);
+    assert_eq!(created.get(), 2);
+}
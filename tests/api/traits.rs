@@ -1,6 +1,6 @@
 use mica::{builtin_traits::iterator, Engine, TypeBuilder, UserData, Value};
 
-use crate::api::RevealResultExt;
+use super::{run, RevealResultExt};
 
 #[test]
 fn exposing_traits_from_rust() {
@@ -12,24 +12,18 @@ fn exposing_traits_from_rust() {
     let game_loop_trait = builder.build();
     engine.set("GameLoop", game_loop_trait).reveal();
 
-    let game: Value = engine
-        .start(
-            "test.mi",
-            include_str!("traits/exposing_traits_from_rust.mi"),
-        )
-        .reveal()
-        .trampoline()
-        .reveal();
+    let game: Value = run(
+        &mut engine,
+        include_str!("traits/exposing_traits_from_rust.mi"),
+    );
 
-    let _: Value = engine
-        .call_method(game.clone(), m_draw, [Value::new(0.1666)])
-        .reveal();
-    let _: Value = engine.call_method(game.clone(), m_update, []).reveal();
+    let _: Value = engine.call_method(game.clone(), m_draw, (0.1666,)).reveal();
+    let _: Value = engine.call_method(game.clone(), m_update, ()).reveal();
 
     let did_draw: bool = engine
-        .call_method(game.clone(), ("did_draw", 0), [])
+        .call_method(game.clone(), ("did_draw", 0), ())
         .reveal();
-    let did_update: bool = engine.call_method(game, ("did_update", 0), []).reveal();
+    let did_update: bool = engine.call_method(game, ("did_update", 0), ()).reveal();
 
     assert!(did_draw);
     assert!(did_update);
@@ -74,14 +68,115 @@ fn binding_type_that_implements_builtin_traits() {
         )
         .reveal();
 
-    let result: usize = engine
-        .start(
-            "test.mi",
-            include_str!("traits/binding_type_that_implements_builtin_traits.mi"),
+    let result: usize = run(
+        &mut engine,
+        include_str!("traits/binding_type_that_implements_builtin_traits.mi"),
+    );
+
+    assert_eq!(result, 1024);
+}
+
+#[test]
+fn checking_trait_implementations_from_rust() {
+    let mut engine = Engine::new();
+
+    let mut builder = engine.build_trait("Shape").reveal();
+    builder.add_function("area", 0).reveal();
+    let shape_trait = builder.build();
+    engine.set("Shape", shape_trait.clone()).reveal();
+
+    let square: Value = run(
+        &mut engine,
+        r#"
+                struct Square impl
+                    func new(side) constructor = do
+                        @side = side
+                    end
+
+                    as Shape
+                        func area() = @side * @side
+                    end
+                end
+                Square.new(2)
+            "#,
+    );
+
+    assert!(engine.implements(&square, &shape_trait).reveal());
+    assert!(!engine.implements(&Value::new(2.0), &shape_trait).reveal());
+    assert!(engine.implements(&square, &Value::new(1.0)).is_err());
+
+    // Trait values can also be passed into scripts.
+    let check: Value = run(
+        &mut engine,
+        "func check(value, t) = value implements t\ncheck",
+    );
+    let implements: bool = engine.call(check, [square, shape_trait]).reveal();
+    assert!(implements);
+}
+
+#[test]
+fn calling_static_trait_functions_from_rust() {
+    let mut engine = Engine::new();
+
+    let mut builder = engine.build_trait("Default").reveal();
+    let m_default = builder.add_static_function("default", 0).reveal();
+    let default_trait = builder.build();
+    engine.set("Default", default_trait).reveal();
+
+    let point_type: Value = run(
+        &mut engine,
+        r#"
+                struct Point impl
+                    func new(x) constructor = do
+                        @x = x
+                    end
+
+                    func x() = @x
+
+                    as Default
+                        func default() static = Point.new(42)
+                    end
+                end
+                Point
+            "#,
+    );
+
+    let point: Value = engine.call_method(point_type, m_default, ()).reveal();
+    let x: f64 = engine.call_method(point, ("x", 0), ()).reveal();
+    assert_eq!(x, 42.0);
+}
+
+#[test]
+fn implementing_iterator_from_rust_at_once() {
+    struct Results {
+        rows: Vec<f64>,
+    }
+
+    impl UserData for Results {}
+
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Results>::new("Results")
+                .add_static("query", || Results {
+                    rows: vec![3.0, 2.0, 1.0],
+                })
+                .implement_iterator(
+                    |results: &Results| !results.rows.is_empty(),
+                    |results: &mut Results| results.rows.pop(),
+                ),
         )
-        .reveal()
-        .trampoline()
         .reveal();
 
-    assert_eq!(result, 1024);
+    let result: f64 = run(
+        &mut engine,
+        r#"
+                let results = Results.query
+                assert(results implements Iterator)
+                let sum = 0
+                for row in results do sum = sum * 10 + row end
+                sum
+            "#,
+    );
+    assert_eq!(result, 123.0);
 }
@@ -0,0 +1,170 @@
+use mica::{operators, Engine, TypeBuilder, UserData, Value};
+
+use super::RevealResultExt;
+
+struct Player {
+    name: String,
+    hp: f64,
+}
+
+impl UserData for Player {}
+
+fn engine_with_player() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Player>::new("Player")
+                .add_static("new", |name: String| Player { name, hp: 100.0 })
+                .add_readonly_field("name", |p: &Player| p.name.clone())
+                .add_field("hp", |p: &Player| p.hp, |p: &mut Player, hp| p.hp = hp),
+        )
+        .reveal();
+    engine
+}
+
+#[test]
+fn fields_can_be_read_and_assigned() {
+    let mut engine = engine_with_player();
+    let hp: f64 = engine
+        .start(
+            "fields.mi",
+            r#"
+                let p = Player.new("Ferris")
+                assert(p.name == "Ferris")
+                assert((p.hp = 50) == 50)
+                p.hp = p.hp + 5
+                p.hp
+            "#,
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(hp, 55.0);
+}
+
+#[test]
+fn readonly_fields_cannot_be_assigned() {
+    let mut engine = engine_with_player();
+    let result: Result<Value, _> = engine
+        .start(
+            "readonly.mi",
+            "let p = Player.new(\"Ferris\")\np.name = \"Crab\"",
+        )
+        .reveal()
+        .trampoline();
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("set_name"), "{error}");
+}
+
+#[test]
+fn field_setters_check_the_type_of_the_value() {
+    let mut engine = engine_with_player();
+    let result: Result<Value, _> = engine
+        .start("type.mi", "let p = Player.new(\"Ferris\")\np.hp = \"full\"")
+        .reveal()
+        .trampoline();
+    assert!(result.is_err());
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Vec2 {
+    x: f64,
+    y: f64,
+}
+
+impl UserData for Vec2 {}
+
+impl Vec2 {
+    fn length(&self) -> f64 {
+        self.x.hypot(self.y)
+    }
+}
+
+struct Grid {
+    cells: Vec<f64>,
+}
+
+impl UserData for Grid {}
+
+fn engine_with_operators() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Vec2>::new("Vec2")
+                .add_static("new", |x, y| Vec2 { x, y })
+                .add_readonly_field("x", |v: &Vec2| v.x)
+                .add_readonly_field("y", |v: &Vec2| v.y)
+                .add_operator(operators::Add, |a: &Vec2, b: Vec2| Vec2 {
+                    x: a.x + b.x,
+                    y: a.y + b.y,
+                })
+                .add_operator(operators::Mul, |a: &Vec2, s: f64| Vec2 {
+                    x: a.x * s,
+                    y: a.y * s,
+                })
+                .add_operator(operators::Neg, |a: &Vec2| Vec2 { x: -a.x, y: -a.y })
+                .add_operator(operators::Eq, |a: &Vec2, b: Vec2| *a == b)
+                .add_operator(operators::Cmp, |a: &Vec2, b: Vec2| a.length() - b.length()),
+        )
+        .reveal();
+    engine
+        .add_type(
+            TypeBuilder::<Grid>::new("Grid")
+                .add_static("new", |size: usize| Grid {
+                    cells: vec![0.0; size],
+                })
+                .add_operator(operators::Index, |g: &Grid, i: usize| g.cells[i])
+                .add_operator(operators::SetIndex, |g: &mut Grid, i: usize, x: f64| {
+                    g.cells[i] = x;
+                    x
+                }),
+        )
+        .reveal();
+    engine
+}
+
+#[test]
+fn user_data_can_overload_arithmetic_operators() {
+    let mut engine = engine_with_operators();
+    let v: (f64, f64) = engine
+        .start(
+            "arithmetic.mi",
+            "let v = -(Vec2.new(1, 2) + Vec2.new(3, 4) * 2)\n(v.x, v.y)",
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(v, (-7.0, -10.0));
+}
+
+#[test]
+fn user_data_can_overload_comparison_operators() {
+    let mut engine = engine_with_operators();
+    let _: Value = engine
+        .start(
+            "comparison.mi",
+            r#"
+                assert(Vec2.new(1, 2) == Vec2.new(1, 2))
+                assert(Vec2.new(1, 2) != Vec2.new(2, 1))
+                assert(Vec2.new(1, 1) < Vec2.new(2, 2))
+                assert(Vec2.new(3, 4) >= Vec2.new(0, 5))
+            "#,
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+}
+
+#[test]
+fn user_data_can_overload_indexing() {
+    let mut engine = engine_with_operators();
+    let sum: f64 = engine
+        .start(
+            "grid.mi",
+            "let g = Grid.new(3)\nassert((g[1] = 5) == 5)\ng[2] = 2\ng[0] + g[1] + g[2]",
+        )
+        .reveal()
+        .trampoline()
+        .reveal();
+    assert_eq!(sum, 7.0);
+}
@@ -0,0 +1,172 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use mica::{Engine, TypeBuilder, UserData, Value};
+
+use super::{run, RevealResultExt};
+
+const MAKE_GARBAGE: &str = r#"
+    let i = 0
+    while i < 1000 do
+        let garbage = [i, "x".cat("y")]
+        i = i + 1
+    end
+    Gc.collect()
+"#;
+
+#[test]
+fn values_held_by_rust_keep_what_they_refer_to_alive() {
+    let mut engine = Engine::new();
+    let held: Value = run(
+        &mut engine,
+        r#"
+            struct Holder impl
+                func new() constructor = do @items = [[1, 2], "held".cat("!")] end
+                func items() = @items
+            end
+            Holder.new()
+        "#,
+    );
+    let _: Value = run(&mut engine, MAKE_GARBAGE);
+    engine.set("held", held).reveal();
+    let item: String = run(&mut engine, "held.items[1]");
+    assert_eq!(item, "held!");
+}
+
+struct Button {
+    on_click: Option<Value>,
+    dropped: Rc<Cell<bool>>,
+    traced: bool,
+}
+
+impl Drop for Button {
+    fn drop(&mut self) {
+        self.dropped.set(true);
+    }
+}
+
+impl UserData for Button {
+    fn visit_values(&self, visit: &mut dyn FnMut(&Value)) {
+        if self.traced {
+            if let Some(on_click) = &self.on_click {
+                visit(on_click);
+            }
+        }
+    }
+}
+
+fn engine_with_button(traced: bool) -> (Engine, Rc<Cell<bool>>) {
+    let dropped = Rc::new(Cell::new(false));
+    let dropped_in_button = Rc::clone(&dropped);
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Button>::new("Button")
+                .add_static("new", move || Button {
+                    on_click: None,
+                    dropped: Rc::clone(&dropped_in_button),
+                    traced,
+                })
+                .add_field(
+                    "on_click",
+                    |b: &Button| b.on_click.clone().unwrap_or(Value::Nil),
+                    |b: &mut Button, f: Value| b.on_click = Some(f),
+                ),
+        )
+        .reveal();
+    (engine, dropped)
+}
+
+#[test]
+fn callbacks_stored_in_user_data_survive_collections() {
+    let (mut engine, _) = engine_with_button(true);
+    let _: Value = run(
+        &mut engine,
+        r#"
+            let clicks = []
+            let button = Button.new()
+            button.on_click = func () = clicks.push("click".cat("!"))
+        "#,
+    );
+    let _: Value = run(&mut engine, MAKE_GARBAGE);
+    let clicks: Vec<String> = run(
+        &mut engine,
+        "let click = button.on_click\nclick()\nclick()\nclicks",
+    );
+    assert_eq!(clicks, ["click!", "click!"]);
+}
+
+const BUTTON_CYCLE: &str = r#"
+    let button = Button.new()
+    button.on_click = func () = button
+"#;
+
+#[test]
+fn cycles_through_traced_values_are_collected() {
+    let (mut engine, dropped) = engine_with_button(true);
+    let _: Value = run(
+        &mut engine,
+        &format!("do\n{BUTTON_CYCLE}\nend\nGc.collect()"),
+    );
+    assert!(dropped.get());
+}
+
+#[test]
+fn values_that_are_not_traced_are_kept_alive() {
+    let (mut engine, dropped) = engine_with_button(false);
+    let _: Value = run(
+        &mut engine,
+        &format!("do\n{BUTTON_CYCLE}\nend\nGc.collect()"),
+    );
+    assert!(!dropped.get());
+}
+
+struct Handle {
+    id: u32,
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+impl UserData for Handle {}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.log.borrow_mut().push(format!("drop {}", self.id));
+    }
+}
+
+fn engine_with_handles() -> (Engine, Rc<RefCell<Vec<String>>>) {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = Engine::new();
+    let log_in_new = Rc::clone(&log);
+    let log_in_finalizer = Rc::clone(&log);
+    engine
+        .add_type(
+            TypeBuilder::<Handle>::new("Handle")
+                .add_static("open", move |id| Handle {
+                    id,
+                    log: Rc::clone(&log_in_new),
+                })
+                .finalizer(move |handle: &mut Handle| {
+                    log_in_finalizer
+                        .borrow_mut()
+                        .push(format!("close {}", handle.id));
+                }),
+        )
+        .reveal();
+    (engine, log)
+}
+
+#[test]
+fn finalizers_run_when_values_are_collected() {
+    let (mut engine, log) = engine_with_handles();
+    let _: Value = run(
+        &mut engine,
+        "let a = Handle.open(1)\nlet b = Handle.open(2)\na = nil\nGc.collect()",
+    );
+    assert_eq!(*log.borrow(), ["close 1", "drop 1"]);
+
+    drop(engine);
+    assert_eq!(*log.borrow(), ["close 1", "drop 1", "close 2", "drop 2"]);
+}
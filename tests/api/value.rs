@@ -1,27 +1,187 @@
-use mica::{Engine, Value};
+use std::collections::{BTreeMap, HashMap};
 
-use super::RevealResultExt;
+use mica::{Engine, Str, TypeBuilder, UserData, Value};
+
+use super::{run, try_run, RevealResultExt};
 
 #[test]
 fn passing_tuples_to_mica() {
     let mut engine = Engine::new();
 
     engine.set("coords", (1, 2, 3)).reveal();
-    let _: Value = engine
-        .start("test.mi", "assert(coords == (1, 2, 3))")
-        .reveal()
-        .trampoline()
-        .reveal();
+    let _: Value = run(&mut engine, "assert(coords == (1, 2, 3))");
 }
 
 #[test]
 fn receiving_tuples_from_mica() {
     let mut engine = Engine::new();
 
-    let coords: (i32, i32, i32) = engine
-        .start("test.mi", "(1, 2, 3)")
-        .reveal()
-        .trampoline()
-        .reveal();
+    let coords: (i32, i32, i32) = run(&mut engine, "(1, 2, 3)");
     assert_eq!(coords, (1, 2, 3));
 }
+
+#[test]
+fn passing_containers_to_mica() {
+    let mut engine = Engine::new();
+
+    engine.set("list", vec![vec![1, 2], vec![3]]).reveal();
+    engine.set("slice", &["a", "b"][..]).reveal();
+    engine
+        .set("hash_map", HashMap::from([("one", 1), ("two", 2)]))
+        .reveal();
+    engine
+        .set("btree_map", BTreeMap::from([(1, vec![Some(true), None])]))
+        .reveal();
+    let _: Value = run(
+        &mut engine,
+        r#"
+                assert(list == [[1, 2], [3]])
+                assert(slice == ["a", "b"])
+                assert(hash_map == ["one": 1, "two": 2])
+                assert(btree_map == [1: [true, nil]])
+            "#,
+    );
+}
+
+#[test]
+fn receiving_containers_from_mica() {
+    let mut engine = Engine::new();
+
+    type Pair = (Vec<Vec<String>>, HashMap<String, (f64, bool)>);
+    let (list, map): Pair = run(
+        &mut engine,
+        r#"([["a"], [], ["b", "c"]], ["x": (1, true)])"#,
+    );
+    assert_eq!(list, [vec!["a"], vec![], vec!["b", "c"]]);
+    assert_eq!(map, HashMap::from([("x".to_owned(), (1.0, true))]));
+
+    let map: BTreeMap<u32, Option<f64>> = run(&mut engine, "[3: nil, 1: 2, 2: 3]");
+    assert_eq!(
+        map.into_iter().collect::<Vec<_>>(),
+        [(1, Some(2.0)), (2, Some(3.0)), (3, None)]
+    );
+}
+
+#[test]
+fn mismatched_elements_fail_the_conversion() {
+    let mut engine = Engine::new();
+
+    let result: Result<HashMap<String, f64>, _> = try_run(&mut engine, r#"["a": 1, "b": "two"]"#);
+    assert!(result.is_err());
+    let result: Result<Vec<f64>, _> = try_run(&mut engine, r#"["a": 1]"#);
+    assert!(result.is_err());
+}
+
+#[test]
+fn globals_declared_but_never_set_are_nil() {
+    let mut engine = Engine::new();
+
+    for i in 0..1000 {
+        engine.global_id(format!("unset{i}").as_str()).reveal();
+    }
+    let value: Value = run(&mut engine, "unset999");
+    assert!(matches!(value, Value::Nil));
+}
+
+#[derive(Debug)]
+struct Color(u8, u8, u8);
+
+impl UserData for Color {}
+
+#[test]
+fn values_are_displayed_like_print_does() {
+    let mut engine = Engine::new();
+    engine
+        .add_type(
+            TypeBuilder::<Color>::new("Color")
+                .add_static("gray", |v: u8| Color(v, v, v))
+                .add_function("to_string", |c: &Color| {
+                    format!("#{:02x}{:02x}{:02x}", c.0, c.1, c.2)
+                }),
+        )
+        .reveal();
+    let value: Value = run(
+        &mut engine,
+        r#"
+                struct Pixel impl
+                    func new(at, color) constructor = do
+                        @at = at
+                        @color = color
+                    end
+                    func to_debug() = "Pixel at ".cat(string(@at)).cat(" ").cat(string(@color))
+                end
+                let pixels = [Pixel.new((1, 2), Color.gray(255)), "text"]
+                pixels.push(pixels)
+                (pixels, Color.gray(16), "text")
+            "#,
+    );
+    assert_eq!(
+        value.display(&mut engine).reveal(),
+        r#"([Pixel at (1, 2) #ffffff, "text", [...]], #101010, "text")"#
+    );
+    let pixels: Value = engine.get("pixels").reveal();
+    assert_eq!(
+        pixels.display(&mut engine).reveal(),
+        "[<[Pixel]>, text, [...]]"
+    );
+    assert_eq!(
+        pixels.display_debug(&mut engine).reveal(),
+        r#"[Pixel at (1, 2) #ffffff, "text", [...]]"#
+    );
+}
+
+#[test]
+fn strings_round_trip_regardless_of_length() {
+    let mut engine = Engine::new();
+    engine
+        .add_function("shout", |s: Str| Str::concat(&[&s.to_uppercase(), "!"]))
+        .reveal();
+    for s in ["", "hi", "ąćęłńóśźżą", "a string that doesn't fit inline"] {
+        engine.set("s", Str::new(s)).reveal();
+        let (same, shouted): (Str, String) = run(&mut engine, "(s, shout(s))");
+        assert_eq!(same, s);
+        assert_eq!(shouted, format!("{}!", s.to_uppercase()));
+        assert_eq!(String::from(same), s);
+    }
+    assert_eq!("ab".chars().collect::<Str>(), "ab");
+    let long: Str = "ą".repeat(20).chars().collect();
+    assert_eq!(long, "ą".repeat(20).as_str());
+}
+
+#[test]
+fn nans_from_the_host_stay_numbers() {
+    let mut engine = Engine::new();
+    // NaNs with all sorts of signs and payloads, some of which look like other kinds of values
+    // when NaN-boxed.
+    for bits in [
+        0x7ff8_0000_0000_0001,
+        0x7ffc_0000_0000_0001,
+        0xfffc_0000_0000_0001,
+        0xfffc_0000_0000_1004,
+        0xffff_ffff_ffff_ffff,
+    ] {
+        engine.set("x", f64::from_bits(bits)).reveal();
+        let x: f64 = run(
+            &mut engine,
+            r#"
+                    assert(x != x and x != nil and x != true)
+                    x + 1
+                "#,
+        );
+        assert!(x.is_nan());
+    }
+}
+
+#[test]
+fn values_take_up_eight_bytes_when_nan_boxed() {
+    let size = std::mem::size_of::<mica::ll::value::RawValue>();
+    if cfg!(all(
+        feature = "nan-boxing",
+        target_pointer_width = "64",
+        not(feature = "forbid-unsafe")
+    )) {
+        assert_eq!(size, 8);
+    } else {
+        assert_eq!(size, 16);
+    }
+}
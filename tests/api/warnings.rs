@@ -0,0 +1,261 @@
+use std::rc::Rc;
+
+use mica::{
+    ll::ast::{NodeId, NodeKind},
+    Engine, LanguageWarningKind, Lint, LintContext, LintPass, Severity, Value,
+};
+
+use super::{run, RevealResultExt};
+
+fn warnings(engine: &mut Engine, source: &str) -> Vec<LanguageWarningKind> {
+    let script = engine.compile("test.mi", source).reveal();
+    script
+        .warnings()
+        .iter()
+        .map(|warning| warning.kind.clone())
+        .collect()
+}
+
+#[test]
+fn lints_are_reported_as_warnings() {
+    let mut engine = Engine::new();
+    let warnings = warnings(
+        &mut engine,
+        r#"
+            func f() = do
+                let unused = 1
+                let _ignored = 2
+                let x = 1
+                do
+                    let x = 2
+                    x
+                end
+                x + 1
+                return x
+                nil
+            end
+        "#,
+    );
+    let lints: Vec<_> = warnings.iter().map(|kind| kind.lint()).collect();
+    assert_eq!(
+        lints,
+        [
+            Lint::UnusedVariable,
+            Lint::ShadowedVariable,
+            Lint::UnusedResult,
+            Lint::UnreachableCode,
+        ]
+    );
+    assert!(
+        matches!(&warnings[0], LanguageWarningKind::UnusedVariable(name) if &**name == "unused")
+    );
+}
+
+#[test]
+fn assignments_that_are_never_read_are_reported() {
+    let mut engine = Engine::new();
+    let warnings = warnings(
+        &mut engine,
+        "func f() = do\n  let x = 1\n  f(x)\n  x = 2\n  nil\nend",
+    );
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(&warnings[0], LanguageWarningKind::UnusedAssignment(name) if &**name == "x"));
+}
+
+#[test]
+fn assignments_read_later_are_not_reported() {
+    let mut engine = Engine::new();
+    let sources = [
+        // Read by the next iteration of a loop.
+        "func f() = do\n  let i = 0\n  while i < 10 do\n    i = i + 1\n  end\n  nil\nend",
+        // Read after a branch.
+        "func f(c) = do\n  let x = 1\n  if c do x = 2 end\n  x\nend",
+        // Read by a deferred block when the scope is exited.
+        "func f() = do\n  let x = 1\n  defer f(x)\n  x = 2\n  nil\nend",
+        // Read by a closure that captured the variable.
+        "func f() = do\n  let x = 1\n  let g = func () = x\n  x = 2\n  g\nend",
+    ];
+    for source in sources {
+        assert!(warnings(&mut engine, source).is_empty(), "{source}");
+    }
+}
+
+#[test]
+fn lints_are_named_in_snake_case() {
+    for &lint in Lint::BUILTIN {
+        assert_eq!(Lint::from_name(lint.name()), Some(lint));
+    }
+    assert_eq!(Lint::UnusedAssignment.name(), "unused_assignment");
+    assert_eq!(Lint::from_name("no_such_lint"), None);
+}
+
+#[test]
+fn allowed_lints_are_not_reported() {
+    let mut engine = Engine::new();
+    engine.set_lint_severity(Lint::UnusedResult, Severity::Allow);
+    assert!(warnings(&mut engine, "1\n2").is_empty());
+}
+
+#[test]
+fn denied_lints_fail_compilation() {
+    let mut engine = Engine::new();
+    engine.set_lint_severity(Lint::UnusedVariable, Severity::Deny);
+    let error = engine
+        .compile("test.mi", "func f() = do let x = 1 end")
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "test.mi:1:19: error: unused variable 'x'"
+    );
+}
+
+#[test]
+fn redeclarations_are_allowed_by_default() {
+    let mut engine = Engine::new();
+    assert_eq!(
+        engine.lint_severity(Lint::RedeclaredVariable),
+        Severity::Allow
+    );
+    let _: Value = run(&mut engine, "let x = 1\nlet x = x + 1\nassert(x == 2)");
+}
+
+#[test]
+fn denied_redeclarations_fail_compilation_in_any_scope() {
+    let mut engine = Engine::new();
+    engine.set_lint_severity(Lint::RedeclaredVariable, Severity::Deny);
+    let error = engine
+        .compile("test.mi", "let x = 1\nlet x = 2")
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "test.mi:2:5: error: variable 'x' is already declared in this scope"
+    );
+    let error = engine
+        .compile(
+            "test.mi",
+            "func f() = do\n  let y = 1\n  let y = y + 1\n  y\nend",
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "test.mi:3:7: error: variable 'y' is already declared in this scope"
+    );
+    // Declaring the same name in a nested block or a separate script is fine.
+    engine
+        .compile("test.mi", "let x = 1\ndo\n  let x = 2\n  x\nend")
+        .reveal();
+    engine.compile("test.mi", "let x = 3").reveal();
+}
+
+#[test]
+fn builtin_lint_passes_check_the_syntax_tree() {
+    let mut engine = Engine::new();
+    let warnings = warnings(
+        &mut engine,
+        r#"
+            func get_count() = 1
+            if get_count == 0 do
+                let print = nil
+                print
+            elif !nil do
+                nil
+            end
+            while true do
+                break
+            end
+            while (false) do end
+        "#,
+    );
+    let lints: Vec<_> = warnings.iter().map(|kind| kind.lint()).collect();
+    assert_eq!(
+        lints,
+        [
+            Lint::FunctionComparison,
+            Lint::ShadowedBuiltin,
+            Lint::ConstantCondition,
+            Lint::ConstantCondition,
+        ]
+    );
+    assert!(
+        matches!(&warnings[1], LanguageWarningKind::ShadowedBuiltin(name) if &**name == "print")
+    );
+    assert!(matches!(
+        warnings[2],
+        LanguageWarningKind::ConstantCondition(true)
+    ));
+    assert!(matches!(
+        warnings[3],
+        LanguageWarningKind::ConstantCondition(false)
+    ));
+}
+
+#[test]
+fn globals_declared_by_scripts_are_not_builtins() {
+    let mut engine = Engine::new();
+    let _: Value = run(&mut engine, "let counter = 1");
+    let warnings = warnings(
+        &mut engine,
+        "func f() = do\n  let counter = 2\n  counter\nend",
+    );
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn variables_rebound_elsewhere_are_not_treated_as_functions() {
+    let mut engine = Engine::new();
+    let warnings = warnings(
+        &mut engine,
+        "func f() = nil\nlet g = f\nf = nil\nassert(f == g)",
+    );
+    assert!(warnings.is_empty());
+}
+
+/// Reports calls to a function the host application considers deprecated.
+#[derive(Debug)]
+struct DeprecatedFunction;
+
+impl LintPass for DeprecatedFunction {
+    fn check_node(&mut self, cx: &mut LintContext<'_>, node: NodeId) {
+        let ast = cx.ast();
+        if ast.kind(node) != NodeKind::Call {
+            return;
+        }
+        let (callee, _) = ast.node_pair(node);
+        if ast.string(callee).is_some_and(|name| &**name == "old_api") {
+            cx.warn(
+                callee,
+                LanguageWarningKind::Custom {
+                    lint: "deprecated",
+                    message: Rc::from("'old_api' is deprecated; use 'new_api' instead"),
+                },
+            );
+        }
+    }
+}
+
+#[test]
+fn custom_lint_passes_can_be_added() {
+    let mut engine = Engine::new();
+    engine.add_lint_pass(DeprecatedFunction);
+    engine.add_function("old_api", || ()).reveal();
+
+    let script = engine.compile("test.mi", "old_api()").reveal();
+    let warnings: Vec<_> = script.warnings().iter().map(|w| w.to_string()).collect();
+    assert_eq!(
+        warnings,
+        ["test.mi:1:1: warning: 'old_api' is deprecated; use 'new_api' instead"]
+    );
+
+    engine.set_lint_severity(Lint::Custom("deprecated"), Severity::Deny);
+    let error = engine.compile("test.mi", "old_api()").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "test.mi:1:1: error: 'old_api' is deprecated; use 'new_api' instead"
+    );
+    engine.set_lint_severity(Lint::Custom("deprecated"), Severity::Allow);
+    assert!(engine
+        .compile("test.mi", "old_api()")
+        .reveal()
+        .warnings()
+        .is_empty());
+}
@@ -43,6 +43,17 @@ struct TestSpec {
     /// compiled regardless of whether they're skipped.
     ignore: bool,
 
+    /// Whether the test opts out of GC stress mode.
+    ///
+    /// This can be set to `true` by using a `@no-gc-stress` annotation, like so:
+    /// ```
+    /// # @no-gc-stress
+    /// ```
+    ///
+    /// Tests that observe the GC's behavior directly would fail when run with `--gc-stress`, so
+    /// they always run with the default GC settings.
+    no_gc_stress: bool,
+
     /// Named markers for line numbers.
     ///
     /// Markers can be set using the `@line` annotation, like so:
@@ -104,6 +115,7 @@ impl SpecParser {
     fn parse_directive(&mut self, directive: &str) -> bool {
         match directive {
             "ignore" => self.spec.ignore = true,
+            "no-gc-stress" => self.spec.no_gc_stress = true,
             _ => return false,
         }
         true
@@ -338,6 +350,8 @@ fn interpret<'e>(
     let mut fiber = engine.start(filename, input)?;
 
     Ok(Some(std::iter::from_fn(move || match fiber.resume() {
+        // Nothing else is running that could unblock the fiber.
+        Ok(Some(_)) if fiber.is_blocked() => Some(Err(Error::Deadlock)),
         Ok(Some(value)) => Some(Ok(value)),
         Ok(None) => None,
         Err(error) => Some(Err(error)),
@@ -347,8 +361,9 @@ fn interpret<'e>(
 }
 
 /// Evaluates a script, discarding its result and reporting any errors along the way.
-fn evaluate(test_name: &str, code: &str) -> Result<(), mica::Error> {
+fn evaluate(test_name: &str, code: &str, gc_stress: bool) -> Result<(), mica::Error> {
     let mut engine = Engine::new();
+    engine.set_gc_stress(gc_stress);
     for result in interpret(&mut engine, test_name, code)? {
         result?;
     }
@@ -364,7 +379,7 @@ struct Test {
 
 impl Test {
     /// Runs the test, printing the outcome into stdout.
-    fn run(&self, include_ignored: bool) -> Outcome {
+    fn run(&self, args: &Arguments) -> Outcome {
         let test_name = self
             .path
             .file_name()
@@ -376,8 +391,10 @@ impl Test {
         let spec = TestSpec::parse(test_name, &code);
         let mut outcome = Outcome::Success;
 
-        if !spec.ignore || include_ignored {
-            let got = Outcome::wrap_panic(|| evaluate(test_name, &code));
+        if !spec.ignore || args.include_ignored {
+            let got = Outcome::wrap_panic(|| {
+                evaluate(test_name, &code, args.gc_stress && !spec.no_gc_stress)
+            });
             if !got.matches(&spec.expected_outcome, &spec) {
                 if got.is_success() {
                     if let Outcome::Failure(error) = &spec.expected_outcome {
@@ -473,6 +490,10 @@ struct Arguments {
     /// Single-threaded mode; prevents tests from running in parallel.
     #[clap(long)]
     single_threaded: bool,
+
+    /// Run every test with the GC in stress mode, collecting garbage before every allocation.
+    #[clap(long)]
+    gc_stress: bool,
 }
 
 fn main() -> ExitCode {
@@ -483,14 +504,11 @@ fn main() -> ExitCode {
     let tests = collect_all_tests("tests");
     let start_time = Instant::now();
     let outcomes: Vec<_> = if args.single_threaded {
-        tests
-            .iter()
-            .map(|test| (test.run(args.include_ignored), test))
-            .collect()
+        tests.iter().map(|test| (test.run(&args), test)).collect()
     } else {
         tests
             .par_iter()
-            .map(|test| (test.run(args.include_ignored), test))
+            .map(|test| (test.run(&args), test))
             .collect()
     };
     let end_time = Instant::now();
@@ -0,0 +1,83 @@
+use std::{
+    fs,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::{info, info_span, warn};
+
+/// Builds the CLI in release mode and times every script in `code/` that has a Lua counterpart,
+/// reporting the fastest of `runs` runs of each. The Lua scripts are timed too if `lua` is
+/// available, which gives a point of reference that doesn't depend on the machine.
+pub fn run(runs: usize, features: &[String], only: &[String]) -> anyhow::Result<()> {
+    let _span = info_span!("bench").entered();
+
+    let mut build = Command::new(env!("CARGO"));
+    build.args(["build", "--release", "--package", "mica-cli"]);
+    for feature in features {
+        build.arg("--features").arg(format!("mica/{feature}"));
+    }
+    if !build.status()?.success() {
+        bail!("building mica-cli failed");
+    }
+    let mica = Utf8Path::new("target/release/mica");
+    let lua = Command::new("lua")
+        .arg("-v")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok();
+    if !lua {
+        warn!("lua was not found, only Mica scripts will be timed");
+    }
+
+    let mut scripts: Vec<Utf8PathBuf> = fs::read_dir("code")?
+        .map(|entry| Ok(Utf8PathBuf::try_from(entry?.path())?))
+        .collect::<anyhow::Result<_>>()?;
+    scripts.retain(|path| {
+        path.extension() == Some("mi")
+            && path.with_extension("lua").exists()
+            && (only.is_empty() || only.iter().any(|name| path.file_stem() == Some(name)))
+    });
+    scripts.sort();
+
+    println!("{:<16} {:>10} {:>10}", "benchmark", "mica", "lua");
+    for script in &scripts {
+        let name = script.file_stem().unwrap();
+        let mica_time = time(runs, Command::new(mica).arg(script))
+            .with_context(|| format!("running {script} failed"))?;
+        let lua_time = if lua {
+            let script = script.with_extension("lua");
+            let time = time(runs, Command::new("lua").arg(&script))
+                .with_context(|| format!("running {script} failed"))?;
+            format!("{:.3}s", time.as_secs_f64())
+        } else {
+            "-".to_owned()
+        };
+        println!(
+            "{name:<16} {:>9.3}s {lua_time:>10}",
+            mica_time.as_secs_f64()
+        );
+    }
+    info!(count = scripts.len(), "benchmarks done");
+
+    Ok(())
+}
+
+/// Runs the command `runs` times and returns the shortest time it took.
+fn time(runs: usize, command: &mut Command) -> anyhow::Result<Duration> {
+    command.stdout(Stdio::null());
+    let mut best = Duration::MAX;
+    for _ in 0..runs {
+        let start = Instant::now();
+        let status = command.status()?;
+        let elapsed = start.elapsed();
+        if !status.success() {
+            bail!("exited with {status}");
+        }
+        best = best.min(elapsed);
+    }
+    Ok(best)
+}
@@ -15,8 +15,8 @@ const PRELUDE: &str = r#"// Generated by mica xtask. Do not modify directly.
 
 use crate::{
     ll::bytecode::{FunctionParameterCount, MethodParameterCount},
-    ffvariants, Arguments, ForeignFunction, IntoValue, MutSelfFromRawValue, RawForeignFunction,
-    RawSelf, SelfFromRawValue, TryFromValue, wrap_in_language_error,
+    ffvariants, Arguments, EngineContext, ForeignFunction, IntoValue, MutSelfFromRawValue,
+    RawFunctionKind, RawSelf, SelfFromRawValue, TryFromValue, wrap_in_language_error,
 };
 "#;
 
@@ -79,6 +79,7 @@ fn generate_for_params(
             Ok(result.into_value_with_engine_state(library, gc).to_raw(gc))
         "#,
         self_mode: SelfMode::Disabled,
+        context: false,
     };
     let fallible_options = GenerateVariantOptions {
         variant: "Fallible",
@@ -93,10 +94,27 @@ fn generate_for_params(
             wrap_in_language_error(result.map(|v| v.into_value_with_engine_state(library, gc).to_raw(gc)))
         "#,
         self_mode: SelfMode::Disabled,
+        context: false,
     };
 
     generate_variant(w, infallible_options)?;
     generate_variant(w, fallible_options)?;
+    generate_variant(
+        w,
+        GenerateVariantOptions {
+            variant: "ContextInfallible",
+            context: true,
+            ..infallible_options
+        },
+    )?;
+    generate_variant(
+        w,
+        GenerateVariantOptions {
+            variant: "ContextFallible",
+            context: true,
+            ..fallible_options
+        },
+    )?;
 
     const SETUP_RAW_SELF: &str = r#"
         let arg_self = RawSelf(arguments.raw_self());
@@ -226,6 +244,8 @@ struct GenerateVariantOptions<'a> {
     user_generic_bounds: &'a [&'a str],
     map_result_action: &'a str,
     self_mode: SelfMode<'a>,
+    /// Whether the function receives an `&mut EngineContext` before its other parameters.
+    context: bool,
 }
 
 fn generate_variant(
@@ -243,6 +263,7 @@ fn generate_variant(
         user_generic_bounds,
         map_result_action,
         self_mode,
+        context,
     } = opts;
 
     let variant_args = to_comma_separated_list(variant_args.iter());
@@ -263,11 +284,11 @@ fn generate_variant(
             .map(|bound| format!("{bound} + 'static")),
     );
 
-    let mut into_raw_foreign_function =
+    let mut into_raw_function_kind =
         String::from(r#" let arguments = Arguments::new(args, library); "#);
 
     if let SelfMode::Enabled { setup_code } = &self_mode {
-        into_raw_foreign_function.push_str(setup_code);
+        into_raw_function_kind.push_str(setup_code);
     }
 
     let variable_list: Vec<_> = function_param_value_types
@@ -277,7 +298,7 @@ fn generate_variant(
         .collect();
     for (i, variable) in variable_list.iter().enumerate() {
         writeln!(
-            into_raw_foreign_function,
+            into_raw_function_kind,
             "let {variable} = wrap_in_language_error(arguments.get({i}))?;"
         )?;
     }
@@ -285,16 +306,45 @@ fn generate_variant(
         SelfMode::Disabled => None,
         SelfMode::Enabled { .. } => Some("arg_self".to_string()),
     };
-    let variable_list: Vec<_> = self_variable.into_iter().chain(variable_list).collect();
+    let context_variable = context.then(|| "&mut context".to_string());
+    let variable_list: Vec<_> = context_variable
+        .into_iter()
+        .chain(self_variable)
+        .chain(variable_list)
+        .collect();
 
     let args = variable_list.join(", ");
-    write!(
-        into_raw_foreign_function,
-        r#"
-            let result = self({args});
-            {map_result_action}
-        "#
-    )?;
+    if context {
+        // The context borrows the GC, so it has to be dropped before the result is converted.
+        write!(
+            into_raw_function_kind,
+            r#"
+                let result = {{
+                    let mut context = EngineContext::new(env, library, globals, gc);
+                    self({args})
+                }};
+                {map_result_action}
+            "#
+        )?;
+    } else {
+        write!(
+            into_raw_function_kind,
+            r#"
+                let result = self({args});
+                {map_result_action}
+            "#
+        )?;
+    }
+    let (function_kind, closure_params) = if context {
+        ("Contextual", "env, library, globals, gc, args")
+    } else {
+        ("Foreign", "library, gc, args")
+    };
+    let context_param = if context {
+        "&mut EngineContext<'_>,"
+    } else {
+        ""
+    };
 
     let parameter_count =
         function_param_value_types.len() + (self_mode != SelfMode::Disabled) as usize;
@@ -309,15 +359,15 @@ fn generate_variant(
                 {value_params}
             > ForeignFunction<ffvariants::{variant}<{variant_args} ({user_params} {value_params})>> for Fun
             where
-                Fun: Fn({user_params} {value_params}) -> {function_return_type} + 'static,
+                Fun: Fn({context_param} {user_params} {value_params}) -> {function_return_type} + 'static,
                 {params_bounds}
             {{
                 {parameter_count_definition}
 
-                fn into_raw_foreign_function(self) -> RawForeignFunction {{
-                    Box::new(move |library, gc, args| {{
-                        {into_raw_foreign_function}
-                    }})
+                fn into_raw_function_kind(self) -> RawFunctionKind {{
+                    RawFunctionKind::{function_kind}(Box::new(move |{closure_params}| {{
+                        {into_raw_function_kind}
+                    }}))
                 }}
             }}
         "#
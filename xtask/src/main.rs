@@ -2,6 +2,7 @@ use clap::{Parser, Subcommand};
 use tracing::{error, metadata::LevelFilter};
 use tracing_subscriber::{prelude::*, EnvFilter};
 
+mod bench;
 mod codegen;
 
 #[derive(Parser)]
@@ -14,6 +15,17 @@ struct Args {
 enum Command {
     /// Generate code that is checked into the repository.
     Codegen,
+    /// Time the example scripts that have Lua counterparts.
+    Bench {
+        /// How many times each script is run. The fastest run is reported.
+        #[clap(long, default_value_t = 5)]
+        runs: usize,
+        /// Features of the `mica` crate to build the CLI with, eg. `threaded-dispatch`.
+        #[clap(long)]
+        features: Vec<String>,
+        /// Names of the scripts to run, eg. `fib`. All of them are run if none are given.
+        only: Vec<String>,
+    },
 }
 
 fn main() {
@@ -31,6 +43,11 @@ fn main() {
 
     let result = match args.command {
         Command::Codegen => codegen::run(),
+        Command::Bench {
+            runs,
+            features,
+            only,
+        } => bench::run(runs, &features, &only),
     };
     if let Err(err) = result {
         error!(%err, "xtask failed");